@@ -1,10 +1,10 @@
 #!/usr/bin/env bash
 
 # Test all these features with "std" enabled.
-FEATURES_WITH_STD="rand-std serde secp-recovery bitcoinconsensus-std base64 ordered"
+FEATURES_WITH_STD="rand-std serde secp-recovery bitcoinconsensus-std base64 ordered interpreter slip132 bip38 anyprevout auxpow async v2transport"
 
 # Test all these features without "std" or "alloc" enabled.
-FEATURES_WITHOUT_STD="rand serde secp-recovery bitcoinconsensus base64 ordered"
+FEATURES_WITHOUT_STD="rand serde secp-recovery bitcoinconsensus base64 ordered interpreter slip132 bip38 anyprevout auxpow"
 
 # Run and lint these examples.
 EXAMPLES="ecdsa-psbt:std,bitcoinconsensus sign-tx-segwit-v0:rand-std sign-tx-taproot:rand-std taproot-psbt:bitcoinconsensus,rand-std sighash:std"
@@ -7,7 +7,7 @@ use internals::write_err;
 use crate::address::{Address, NetworkUnchecked};
 use crate::blockdata::script::{witness_program, witness_version};
 use crate::prelude::*;
-use crate::Network;
+use crate::{Network, NetworkKind};
 
 /// Address's network differs from required one.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -29,6 +29,30 @@ impl fmt::Display for NetworkValidationError {
 #[cfg(feature = "std")]
 impl std::error::Error for NetworkValidationError {}
 
+/// Address's network kind differs from required one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkKindValidationError {
+    /// Network kind that was required.
+    pub(crate) required: NetworkKind,
+    /// The address itself.
+    pub(crate) address: Address<NetworkUnchecked>,
+}
+
+impl fmt::Display for NetworkKindValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.required {
+            NetworkKind::Main => "mainnet",
+            NetworkKind::Test => "a test network",
+        };
+        write!(f, "address ")?;
+        fmt::Display::fmt(&self.address.0, f)?;
+        write!(f, " is not valid on {}", kind)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NetworkKindValidationError {}
+
 /// Error while generating address from script.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -137,6 +161,14 @@ pub enum ParseError {
     WitnessProgram(witness_program::Error),
     /// Tried to parse an unknown HRP.
     UnknownHrp(UnknownHrpError),
+    /// A bech32 address mixes upper and lower case characters.
+    MixedCase,
+    /// The bech32 checksum is invalid, the address most likely contains a typo.
+    InvalidBech32Checksum,
+    /// The base58 checksum is invalid, the address most likely contains a typo.
+    InvalidBase58Checksum,
+    /// The version byte of a base58 address is not a known P2PKH or P2SH prefix.
+    UnknownLegacyPrefix(u8),
 }
 
 internals::impl_from_infallible!(ParseError);
@@ -151,6 +183,11 @@ impl fmt::Display for ParseError {
             WitnessVersion(ref e) => write_err!(f, "witness version conversion/parsing error"; e),
             WitnessProgram(ref e) => write_err!(f, "witness program error"; e),
             UnknownHrp(ref e) => write_err!(f, "tried to parse an unknown hrp"; e),
+            MixedCase => write!(f, "bech32 address mixes upper and lower case characters"),
+            InvalidBech32Checksum => write!(f, "invalid bech32 checksum, the address has a typo"),
+            InvalidBase58Checksum => write!(f, "invalid base58 checksum, the address has a typo"),
+            UnknownLegacyPrefix(prefix) =>
+                write!(f, "unknown base58 address version byte {:#04x}", prefix),
         }
     }
 }
@@ -166,16 +203,37 @@ impl std::error::Error for ParseError {
             WitnessVersion(ref e) => Some(e),
             WitnessProgram(ref e) => Some(e),
             UnknownHrp(ref e) => Some(e),
+            MixedCase | InvalidBech32Checksum | InvalidBase58Checksum | UnknownLegacyPrefix(_) =>
+                None,
         }
     }
 }
 
 impl From<base58::Error> for ParseError {
-    fn from(e: base58::Error) -> Self { Self::Base58(e) }
+    fn from(e: base58::Error) -> Self {
+        match e {
+            base58::Error::BadChecksum(..) => Self::InvalidBase58Checksum,
+            base58::Error::InvalidAddressVersion(prefix) => Self::UnknownLegacyPrefix(prefix),
+            e => Self::Base58(e),
+        }
+    }
 }
 
 impl From<bech32::segwit::DecodeError> for ParseError {
-    fn from(e: bech32::segwit::DecodeError) -> Self { Self::Bech32(e) }
+    fn from(e: bech32::segwit::DecodeError) -> Self {
+        use bech32::primitives::decode::{
+            CharError, ChecksumError, SegwitHrpstringError, UncheckedHrpstringError,
+        };
+
+        match e.0 {
+            SegwitHrpstringError::Unchecked(UncheckedHrpstringError::Char(
+                CharError::MixedCase,
+            )) => Self::MixedCase,
+            SegwitHrpstringError::Checksum(ChecksumError::InvalidResidue) =>
+                Self::InvalidBech32Checksum,
+            _ => Self::Bech32(e),
+        }
+    }
 }
 
 impl From<witness_version::TryFromError> for ParseError {
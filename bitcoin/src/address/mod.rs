@@ -27,6 +27,7 @@
 //! ```
 
 pub mod error;
+pub mod silent_payments;
 
 use core::fmt;
 use core::marker::PhantomData;
@@ -36,25 +37,25 @@ use bech32::primitives::hrp::Hrp;
 use hashes::{sha256, Hash, HashEngine};
 use secp256k1::{Secp256k1, Verification, XOnlyPublicKey};
 
+use self::error::P2shError;
 use crate::blockdata::constants::{
     MAX_SCRIPT_ELEMENT_SIZE, PUBKEY_ADDRESS_PREFIX_MAIN, PUBKEY_ADDRESS_PREFIX_TEST,
     SCRIPT_ADDRESS_PREFIX_MAIN, SCRIPT_ADDRESS_PREFIX_TEST,
 };
 use crate::blockdata::script::witness_program::WitnessProgram;
 use crate::blockdata::script::witness_version::WitnessVersion;
-use crate::blockdata::script::{self, Script, ScriptBuf, ScriptHash};
+use crate::blockdata::script::{self, Script, ScriptBuf, ScriptHash, ScriptType, WScriptHash};
 use crate::crypto::key::{
-    CompressedPublicKey, PubkeyHash, PublicKey, TweakedPublicKey, UntweakedPublicKey,
+    CompressedPublicKey, PubkeyHash, PublicKey, TweakedPublicKey, UntweakedPublicKey, WPubkeyHash,
 };
 use crate::network::{Network, NetworkKind};
 use crate::prelude::*;
-use crate::taproot::TapNodeHash;
-
-use self::error::P2shError;
+use crate::taproot::{TapNodeHash, TapTree, TaprootSpendInfo};
+use crate::{Amount, FeeRate, Weight};
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
 pub use self::{
-    error::{NetworkValidationError, ParseError, UnknownAddressTypeError, UnknownHrpError, FromScriptError, },
+    error::{NetworkKindValidationError, NetworkValidationError, ParseError, UnknownAddressTypeError, UnknownHrpError, FromScriptError, },
 };
 
 /// The different types of addresses.
@@ -99,6 +100,71 @@ impl FromStr for AddressType {
     }
 }
 
+impl AddressType {
+    /// Returns true if outputs of this type are spent using segregated witness.
+    ///
+    /// Note that P2SH outputs may wrap a segwit program too, this is not known from the address.
+    pub fn is_segwit(&self) -> bool {
+        match *self {
+            AddressType::P2pkh | AddressType::P2sh => false,
+            AddressType::P2wpkh | AddressType::P2wsh | AddressType::P2tr => true,
+        }
+    }
+
+    /// Returns the length in bytes of a script pubkey of this type.
+    pub fn script_pubkey_len(&self) -> usize {
+        match *self {
+            AddressType::P2pkh => 25,
+            AddressType::P2sh => 23,
+            AddressType::P2wpkh => 22,
+            AddressType::P2wsh | AddressType::P2tr => 34,
+        }
+    }
+
+    /// Returns the maximum weight of the data needed to spend an output of this type.
+    ///
+    /// See [`Script::max_satisfaction_weight`], returns `None` for P2SH and P2WSH since their
+    /// satisfaction depends on the script being committed to.
+    pub fn max_satisfaction_weight(&self) -> Option<Weight> {
+        self.template_script_pubkey().max_satisfaction_weight()
+    }
+
+    /// Returns the minimum value an output of this type should have in order to not be dust.
+    ///
+    /// See [`Script::minimal_non_dust`].
+    pub fn minimal_non_dust(&self) -> Amount { self.template_script_pubkey().minimal_non_dust() }
+
+    /// Returns the minimum value an output of this type should have for a custom dust relay fee.
+    ///
+    /// See [`Script::minimal_non_dust_custom`].
+    pub fn minimal_non_dust_custom(&self, dust_relay_fee: FeeRate) -> Amount {
+        self.template_script_pubkey().minimal_non_dust_custom(dust_relay_fee)
+    }
+
+    /// Returns a script pubkey of this type committing to all zeros.
+    fn template_script_pubkey(&self) -> ScriptBuf {
+        match *self {
+            AddressType::P2pkh => ScriptBuf::new_p2pkh(&PubkeyHash::all_zeros()),
+            AddressType::P2sh => ScriptBuf::new_p2sh(&ScriptHash::all_zeros()),
+            AddressType::P2wpkh => ScriptBuf::new_p2wpkh(&WPubkeyHash::all_zeros()),
+            AddressType::P2wsh => ScriptBuf::new_p2wsh(&WScriptHash::all_zeros()),
+            AddressType::P2tr =>
+                ScriptBuf::new_witness_program_unchecked(WitnessVersion::V1, [0; 32]),
+        }
+    }
+}
+
+/// An output script which cannot be represented as an address.
+///
+/// Returned by [`Address::from_script_any`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownAddressType<'a> {
+    /// The output script.
+    pub script: &'a Script,
+    /// The template the script matches, if any.
+    pub script_type: ScriptType<'a>,
+}
+
 mod sealed {
     pub trait NetworkValidation {}
     impl NetworkValidation for super::NetworkChecked {}
@@ -232,6 +298,15 @@ impl From<Network> for KnownHrp {
     fn from(n: Network) -> Self { Self::from_network(n) }
 }
 
+impl From<KnownHrp> for NetworkKind {
+    fn from(hrp: KnownHrp) -> Self {
+        match hrp {
+            KnownHrp::Mainnet => NetworkKind::Main,
+            KnownHrp::Testnets | KnownHrp::Regtest => NetworkKind::Test,
+        }
+    }
+}
+
 /// A Bitcoin address.
 ///
 /// ### Parsing addresses
@@ -355,6 +430,18 @@ impl<V: NetworkValidation> Address<V> {
     pub fn as_unchecked(&self) -> &Address<NetworkUnchecked> {
         unsafe { &*(self as *const Address<V> as *const Address<NetworkUnchecked>) }
     }
+
+    /// Returns the kind of network this address is for.
+    ///
+    /// Testnet, signet and regtest addresses all have kind [`NetworkKind::Test`].
+    pub fn network_kind(&self) -> NetworkKind {
+        use AddressInner::*;
+        match self.0 {
+            P2pkh { hash: _, network } => network,
+            P2sh { hash: _, network } => network,
+            Segwit { program: _, hrp } => hrp.into(),
+        }
+    }
 }
 
 /// Methods and functions that can be called only on `Address<NetworkChecked>`.
@@ -389,8 +476,16 @@ impl Address {
     /// Creates a witness pay to public key address from a public key.
     ///
     /// This is the native segwit address type for an output redeemable with a single signature.
+    ///
+    /// Segwit does not allow uncompressed keys. Use [`PublicKey::try_into_compressed`] to convert
+    /// a key that may be uncompressed.
     pub fn p2wpkh(pk: &CompressedPublicKey, hrp: impl Into<KnownHrp>) -> Self {
-        let program = WitnessProgram::p2wpkh(pk);
+        Address::p2wpkh_from_hash(pk.wpubkey_hash(), hrp)
+    }
+
+    /// Creates a witness pay to public key address from the hash of a public key.
+    pub fn p2wpkh_from_hash(hash: WPubkeyHash, hrp: impl Into<KnownHrp>) -> Self {
+        let program = WitnessProgram::p2wpkh_from_hash(hash);
         Address::from_witness_program(program, hrp)
     }
 
@@ -398,7 +493,12 @@ impl Address {
     ///
     /// This is a segwit address type that looks familiar (as p2sh) to legacy clients.
     pub fn p2shwpkh(pk: &CompressedPublicKey, network: impl Into<NetworkKind>) -> Address {
-        let builder = script::Builder::new().push_int(0).push_slice(pk.wpubkey_hash());
+        Address::p2shwpkh_from_hash(pk.wpubkey_hash(), network)
+    }
+
+    /// Creates a pay to script address that embeds a witness pay to public key hash.
+    pub fn p2shwpkh_from_hash(hash: WPubkeyHash, network: impl Into<NetworkKind>) -> Address {
+        let builder = script::Builder::new().push_int(0).push_slice(hash);
         let script_hash = builder.as_script().script_hash();
         Address::p2sh_from_hash(script_hash, network)
     }
@@ -429,6 +529,21 @@ impl Address {
         Address::from_witness_program(program, hrp)
     }
 
+    /// Creates a pay to taproot address committing to the script tree `tree`.
+    ///
+    /// Returns the address together with the [`TaprootSpendInfo`] required to spend from it.
+    pub fn p2tr_tweaked_from_tree<C: Verification>(
+        secp: &Secp256k1<C>,
+        internal_key: UntweakedPublicKey,
+        tree: TapTree,
+        hrp: impl Into<KnownHrp>,
+    ) -> (Address, TaprootSpendInfo) {
+        let node = tree.into_node_info();
+        let spend_info = TaprootSpendInfo::from_node_info(secp, internal_key, node);
+        let address = Address::p2tr_tweaked(spend_info.output_key(), hrp);
+        (address, spend_info)
+    }
+
     /// Creates a pay to taproot address from a pre-tweaked output key.
     pub fn p2tr_tweaked(output_key: TweakedPublicKey, hrp: impl Into<KnownHrp>) -> Address {
         let program = WitnessProgram::p2tr_tweaked(output_key);
@@ -467,6 +582,29 @@ impl Address {
         }
     }
 
+    /// Gets the witness version for this address if this is a segwit address.
+    ///
+    /// Future witness versions are returned as well, see [`Address::witness_program`].
+    pub fn witness_version(&self) -> Option<WitnessVersion> {
+        self.witness_program().map(|program| program.version())
+    }
+
+    /// Gets the witness program for this address if this is a segwit address.
+    ///
+    /// Addresses with witness versions or program lengths without defined semantics are valid
+    /// (see [BIP-350]) and can be inspected using this method, e.g. to apply a relay or display
+    /// policy to them.
+    ///
+    /// [BIP-350]: <https://github.com/bitcoin/bips/blob/master/bip-0350.mediawiki>
+    pub fn witness_program(&self) -> Option<&WitnessProgram> {
+        use AddressInner::*;
+
+        match self.0 {
+            Segwit { ref program, hrp: _ } => Some(program),
+            _ => None,
+        }
+    }
+
     /// Gets the pubkey hash for this address if this is a P2PKH address.
     pub fn pubkey_hash(&self) -> Option<PubkeyHash> {
         use AddressInner::*;
@@ -513,16 +651,37 @@ impl Address {
             let hash = ScriptHash::from_byte_array(bytes);
             Ok(Address::p2sh_from_hash(hash, network))
         } else if script.is_witness_program() {
-            let opcode = script.first_opcode().expect("is_witness_program guarantees len > 4");
-
-            let version = WitnessVersion::try_from(opcode)?;
-            let program = WitnessProgram::new(version, &script.as_bytes()[2..])?;
+            let program = WitnessProgram::from_script(script)?;
             Ok(Address::from_witness_program(program, network))
         } else {
             Err(FromScriptError::UnrecognizedScript)
         }
     }
 
+    /// Constructs an [`Address`] from an output script, describing scripts without one.
+    ///
+    /// Unlike [`Address::from_script`] this never fails outright: scripts that cannot be
+    /// represented as an address (e.g. `OP_RETURN`, bare multisig) are returned as an
+    /// [`UnknownAddressType`] so that every output of a transaction can be rendered.
+    pub fn from_script_any(
+        script: &Script,
+        network: Network,
+    ) -> Result<Address, UnknownAddressType<'_>> {
+        Address::from_script(script, network)
+            .map_err(|_| UnknownAddressType { script, script_type: script.classify() })
+    }
+
+    /// Converts a batch of output scripts using [`Address::from_script_any`].
+    pub fn from_scripts<'a, I>(
+        scripts: I,
+        network: Network,
+    ) -> impl Iterator<Item = Result<Address, UnknownAddressType<'a>>>
+    where
+        I: IntoIterator<Item = &'a Script>,
+    {
+        scripts.into_iter().map(move |script| Address::from_script_any(script, network))
+    }
+
     /// Generates a script pubkey spending to this address.
     pub fn script_pubkey(&self) -> ScriptBuf {
         use AddressInner::*;
@@ -599,7 +758,8 @@ impl Address {
             P2sh { ref hash, network: _ } if script.is_p2sh() =>
                 &script.as_bytes()[2..22] == <ScriptHash as AsRef<[u8; 20]>>::as_ref(hash),
             Segwit { ref program, hrp: _ } if script.is_witness_program() =>
-                &script.as_bytes()[2..] == program.program().as_bytes(),
+                script.witness_version() == Some(program.version())
+                    && &script.as_bytes()[2..] == program.program().as_bytes(),
             P2pkh { .. } | P2sh { .. } | Segwit { .. } => false,
         }
     }
@@ -674,6 +834,39 @@ impl Address<NetworkUnchecked> {
         }
     }
 
+    /// Returns true if this address is valid on any network of kind `kind`.
+    ///
+    /// Unlike [`is_valid_for_network`](Address<NetworkUnchecked>::is_valid_for_network) this
+    /// treats all test networks as a group, so an application supporting both testnet and signet
+    /// can validate addresses with a single check.
+    ///
+    /// ```rust
+    /// use bitcoin::{Address, NetworkKind};
+    /// use bitcoin::address::NetworkUnchecked;
+    ///
+    /// let address: Address<NetworkUnchecked> = "bcrt1q2nfxmhd4n3c8834pj72xagvyr9gl57n5r94fsl".parse().unwrap();
+    /// assert!(address.is_valid_for_network_kind(NetworkKind::Test));
+    /// assert!(!address.is_valid_for_network_kind(NetworkKind::Main));
+    /// ```
+    pub fn is_valid_for_network_kind(&self, kind: NetworkKind) -> bool {
+        self.network_kind() == kind
+    }
+
+    /// Checks whether this address is valid on any network of kind `required`.
+    ///
+    /// See [`is_valid_for_network_kind`](Address<NetworkUnchecked>::is_valid_for_network_kind).
+    #[inline]
+    pub fn require_network_kind(
+        self,
+        required: NetworkKind,
+    ) -> Result<Address, NetworkKindValidationError> {
+        if self.is_valid_for_network_kind(required) {
+            Ok(self.assume_checked())
+        } else {
+            Err(NetworkKindValidationError { required, address: self })
+        }
+    }
+
     /// Marks, without any additional checks, network of this address as checked.
     ///
     /// Improper use of this method may lead to loss of funds. Reader will most likely prefer
@@ -720,14 +913,20 @@ impl FromStr for Address<NetworkUnchecked> {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Address<NetworkUnchecked>, ParseError> {
-        if let Ok((hrp, witness_version, data)) = bech32::segwit::decode(s) {
-            let version = WitnessVersion::try_from(witness_version)?;
-            let program = WitnessProgram::new(version, &data)
-                .expect("bech32 guarantees valid program length for witness");
-
-            let hrp = KnownHrp::from_hrp(hrp)?;
-            let inner = AddressInner::Segwit { program, hrp };
-            return Ok(Address(inner, PhantomData));
+        match bech32::segwit::decode(s) {
+            Ok((hrp, witness_version, data)) => {
+                let version = WitnessVersion::try_from(witness_version)?;
+                let program = WitnessProgram::new(version, &data)
+                    .expect("bech32 guarantees valid program length for witness");
+
+                let hrp = KnownHrp::from_hrp(hrp)?;
+                let inner = AddressInner::Segwit { program, hrp };
+                return Ok(Address(inner, PhantomData));
+            }
+            // Base58 addresses never start with a known HRP followed by the separator, so report
+            // the bech32 error rather than a confusing base58 one.
+            Err(e) if has_known_hrp(s) => return Err(e.into()),
+            Err(_) => {}
         }
 
         // If segwit decoding fails, assume its a legacy address.
@@ -760,13 +959,21 @@ impl FromStr for Address<NetworkUnchecked> {
                 let hash = ScriptHash::from_byte_array(data);
                 AddressInner::P2sh { hash, network: NetworkKind::Test }
             }
-            x => return Err(ParseError::Base58(base58::Error::InvalidAddressVersion(x))),
+            x => return Err(ParseError::UnknownLegacyPrefix(x)),
         };
 
         Ok(Address(inner, PhantomData))
     }
 }
 
+/// Returns true if `s` starts with a known segwit HRP, in any case, followed by the separator.
+fn has_known_hrp(s: &str) -> bool {
+    match s.rfind('1') {
+        Some(pos) => ["bc", "tb", "bcrt"].iter().any(|hrp| s[..pos].eq_ignore_ascii_case(hrp)),
+        None => false,
+    }
+}
+
 /// Convert a byte array of a pubkey hash into a segwit redeem hash
 fn segwit_redeem_hash(pubkey_hash: &PubkeyHash) -> crate::hashes::hash160::Hash {
     let mut sha_engine = sha256::Hash::engine();
@@ -874,6 +1081,10 @@ mod tests {
         assert_eq!(&addr.to_string(), "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw");
         assert_eq!(addr.address_type(), Some(AddressType::P2wpkh));
         roundtrips(&addr, Bitcoin);
+
+        assert_eq!(Address::p2wpkh_from_hash(key.wpubkey_hash(), KnownHrp::Mainnet), addr);
+        let key = PublicKey::from(key).try_into_compressed().unwrap();
+        assert_eq!(Address::p2wpkh(&key, KnownHrp::Mainnet), addr);
     }
 
     #[test]
@@ -899,6 +1110,8 @@ mod tests {
         assert_eq!(&addr.to_string(), "3QBRmWNqqBGme9er7fMkGqtZtp4gjMFxhE");
         assert_eq!(addr.address_type(), Some(AddressType::P2sh));
         roundtrips(&addr, Bitcoin);
+
+        assert_eq!(Address::p2shwpkh_from_hash(key.wpubkey_hash(), NetworkKind::Main), addr);
     }
 
     #[test]
@@ -976,6 +1189,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn address_type_metadata() {
+        let addresses = [
+            "1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY",
+            "33iFwdLuRpW1uK1RTRqsoi8rR4NpDzk66k",
+            "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw",
+            "bc1qwqdg6squsna38e46795at95yu9atm8azzmyvckulcc7kytlcckxswvvzej",
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+        ];
+        for address in &addresses {
+            let addr = Address::from_str(address).unwrap().assume_checked();
+            let address_type = addr.address_type().unwrap();
+            let script_pubkey = addr.script_pubkey();
+            assert_eq!(address_type.script_pubkey_len(), script_pubkey.len());
+            assert_eq!(address_type.is_segwit(), script_pubkey.is_witness_program());
+            assert_eq!(address_type.minimal_non_dust(), script_pubkey.minimal_non_dust());
+            assert_eq!(
+                address_type.max_satisfaction_weight(),
+                script_pubkey.max_satisfaction_weight()
+            );
+        }
+        assert_eq!(AddressType::P2pkh.minimal_non_dust(), Amount::from_sat(546));
+        assert_eq!(AddressType::P2wpkh.minimal_non_dust(), Amount::from_sat(294));
+        assert_eq!(AddressType::P2tr.minimal_non_dust(), Amount::from_sat(330));
+        assert!(AddressType::P2wsh.max_satisfaction_weight().is_none());
+    }
+
+    #[test]
+    fn future_witness_versions() {
+        // BIP-350 test vectors for witness versions without defined semantics.
+        let addresses = [
+            ("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7kt5nd6y", 1, 40),
+            ("BC1SW50QGDZ25J", 16, 2),
+            ("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs", 2, 16),
+        ];
+        for (address, version, len) in &addresses {
+            let addr = Address::from_str(address).unwrap().assume_checked();
+            assert_eq!(addr.witness_version().unwrap().to_num(), *version);
+            assert_eq!(addr.witness_program().unwrap().program().len(), *len);
+            assert!(!addr.is_spend_standard());
+            assert!(addr.matches_script_pubkey(&addr.script_pubkey()));
+            assert_eq!(Address::from_script(&addr.script_pubkey(), Network::Bitcoin), Ok(addr));
+        }
+
+        let legacy = Address::from_str("1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY").unwrap();
+        assert!(legacy.assume_checked().witness_program().is_none());
+    }
+
     #[test]
     #[cfg(feature = "serde")]
     fn test_json_serialize() {
@@ -1259,6 +1520,25 @@ mod tests {
         assert_eq!(got, want);
     }
 
+    #[test]
+    fn address_parse_errors() {
+        let mixed_case = "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuW";
+        assert_eq!(Address::from_str(mixed_case), Err(ParseError::MixedCase));
+
+        let typo = "bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkux";
+        assert_eq!(Address::from_str(typo), Err(ParseError::InvalidBech32Checksum));
+        let typo = "TB1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KXPJZSY";
+        assert_eq!(Address::from_str(typo), Err(ParseError::InvalidBech32Checksum));
+
+        let typo = "1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhZ";
+        assert_eq!(Address::from_str(typo), Err(ParseError::InvalidBase58Checksum));
+
+        let mut litecoin = [0u8; 21];
+        litecoin[0] = 0x30;
+        let litecoin = base58::encode_check(&litecoin);
+        assert_eq!(Address::from_str(&litecoin), Err(ParseError::UnknownLegacyPrefix(0x30)));
+    }
+
     #[test]
     fn test_matches_script_pubkey() {
         let addresses = [
@@ -1279,5 +1559,80 @@ mod tests {
                 assert_eq!(addr.matches_script_pubkey(&another.script_pubkey()), addr == another);
             }
         }
+
+        // Same program with a different witness version.
+        let v0 =
+            Address::from_str("bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3")
+                .unwrap()
+                .assume_checked();
+        let mut v1 = v0.script_pubkey().into_bytes();
+        v1[0] = 0x51;
+        assert!(!v0.matches_script_pubkey(Script::from_bytes(&v1)));
+    }
+
+    #[test]
+    fn p2tr_from_tree() {
+        use crate::taproot::{LeafVersion, TaprootBuilder};
+
+        let secp = Secp256k1::verification_only();
+        let internal_key = XOnlyPublicKey::from_str(
+            "cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115",
+        )
+        .unwrap();
+        let script = ScriptBuf::from_hex("51").unwrap();
+        let tree = TaprootBuilder::new()
+            .add_leaf(1, script.clone())
+            .unwrap()
+            .add_leaf(1, ScriptBuf::from_hex("52").unwrap())
+            .unwrap()
+            .try_into_taptree()
+            .unwrap();
+
+        let (address, spend_info) =
+            Address::p2tr_tweaked_from_tree(&secp, internal_key, tree, KnownHrp::Mainnet);
+        let merkle_root = spend_info.merkle_root();
+        let expected = Address::p2tr(&secp, internal_key, merkle_root, Network::Bitcoin);
+        assert_eq!(address, expected);
+        assert_eq!(spend_info.internal_key(), internal_key);
+        assert!(spend_info.control_block(&(script, LeafVersion::TapScript)).is_some());
+    }
+
+    #[test]
+    fn network_kind() {
+        let cases = [
+            ("1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY", NetworkKind::Main),
+            ("2N83imGV3gPwBzKJQvWJ7cRUY2SpUyU6A5e", NetworkKind::Test),
+            ("bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw", NetworkKind::Main),
+            ("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx", NetworkKind::Test),
+            ("bcrt1q2nfxmhd4n3c8834pj72xagvyr9gl57n5r94fsl", NetworkKind::Test),
+        ];
+        for (s, kind) in cases {
+            let addr = s.parse::<Address<_>>().unwrap();
+            assert_eq!(addr.network_kind(), kind);
+            assert!(addr.is_valid_for_network_kind(kind));
+            let other = if kind.is_mainnet() { NetworkKind::Test } else { NetworkKind::Main };
+            assert!(!addr.is_valid_for_network_kind(other));
+            assert!(addr.clone().require_network_kind(other).is_err());
+            assert_eq!(addr.require_network_kind(kind).unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn from_script_any() {
+        let p2wpkh = Address::from_str("bc1qvzvkjn4q3nszqxrv3nraga2r822xjty3ykvkuw")
+            .unwrap()
+            .assume_checked();
+        let future = ScriptBuf::from_hex("5a0401020304").unwrap();
+        let op_return = ScriptBuf::from_hex("6a0401020304").unwrap();
+        let scripts = [p2wpkh.script_pubkey(), future.clone(), op_return.clone()];
+
+        let scripts = scripts.iter().map(|s| s.as_script());
+        let converted = Address::from_scripts(scripts, Network::Bitcoin).collect::<Vec<_>>();
+        assert_eq!(converted[0], Ok(p2wpkh));
+        assert_eq!(converted[1].as_ref().unwrap().script_pubkey(), future);
+        let unknown = converted[2].as_ref().unwrap_err();
+        assert_eq!(unknown.script, op_return.as_script());
+        let data = Script::from_bytes(&[0x04, 1, 2, 3, 4]);
+        assert_eq!(unknown.script_type, ScriptType::OpReturn(data));
     }
 }
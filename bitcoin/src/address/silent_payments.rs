@@ -0,0 +1,561 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Silent payment addresses.
+//!
+//! Implementation of the address format and sender side output derivation of [BIP-352].
+//!
+//! A silent payment address encodes a scan key and a spend key. A sender combines the private
+//! keys of the inputs it spends with the recipient's scan key to derive a fresh taproot output
+//! key, which the recipient can find by scanning the chain but nobody else can link to the
+//! address.
+//!
+//! [BIP-352]: <https://github.com/bitcoin/bips/blob/master/bip-0352.mediawiki>
+
+use core::fmt;
+use core::str::FromStr;
+
+use bech32::primitives::decode::{CheckedHrpstring, CheckedHrpstringError};
+use bech32::primitives::gf32::Fe32;
+use bech32::primitives::hrp::Hrp;
+use bech32::primitives::iter::{ByteIterExt, Fe32IterExt};
+use bech32::Bech32m;
+use hashes::{sha256t_hash_newtype, Hash, HashEngine};
+use internals::write_err;
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification};
+
+use crate::address::KnownHrp;
+use crate::blockdata::transaction::OutPoint;
+use crate::consensus::encode;
+use crate::crypto::key::TweakedPublicKey;
+use crate::network::Network;
+use crate::prelude::*;
+
+/// The human-readable part of mainnet silent payment addresses.
+const HRP_MAINNET: Hrp = Hrp::parse_unchecked("sp");
+/// The human-readable part of testnet and signet silent payment addresses.
+const HRP_TESTNETS: Hrp = Hrp::parse_unchecked("tsp");
+/// The human-readable part of regtest silent payment addresses.
+const HRP_REGTEST: Hrp = Hrp::parse_unchecked("sprt");
+
+/// The highest address version, `31` is reserved for backwards incompatible changes.
+const MAX_VERSION: u8 = 30;
+
+/// Length of the address payload: the serialized scan key followed by the spend key.
+const PAYLOAD_LEN: usize = 66;
+
+sha256t_hash_newtype! {
+    pub struct InputsTag = hash_str("BIP0352/Inputs");
+
+    /// Tagged hash committing to the smallest outpoint and the summed input public key.
+    #[hash_newtype(forward)]
+    pub struct InputsHash(_);
+
+    pub struct SharedSecretTag = hash_str("BIP0352/SharedSecret");
+
+    /// Tagged hash of the ECDH shared secret and the output counter, used to tweak spend keys.
+    #[hash_newtype(forward)]
+    pub struct SharedSecretHash(_);
+}
+
+impl InputsHash {
+    /// Computes `hash_BIP0352/Inputs(outpoint_L || A)`.
+    pub fn from_outpoint_and_key(smallest_outpoint: &OutPoint, input_key: &PublicKey) -> Self {
+        let mut eng = InputsHash::engine();
+        eng.input(&encode::serialize(smallest_outpoint));
+        eng.input(&input_key.serialize());
+        InputsHash::from_engine(eng)
+    }
+}
+
+impl SharedSecretHash {
+    /// Computes `hash_BIP0352/SharedSecret(ecdh_shared_secret || k)`.
+    pub fn from_shared_secret(shared_secret: &PublicKey, k: u32) -> Self {
+        let mut eng = SharedSecretHash::engine();
+        eng.input(&shared_secret.serialize());
+        eng.input(&k.to_be_bytes());
+        SharedSecretHash::from_engine(eng)
+    }
+}
+
+/// A silent payment address.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SilentPaymentAddress {
+    version: u8,
+    hrp: KnownHrp,
+    scan_key: PublicKey,
+    spend_key: PublicKey,
+}
+
+impl SilentPaymentAddress {
+    /// Creates a version 0 silent payment address.
+    pub fn new(scan_key: PublicKey, spend_key: PublicKey, hrp: impl Into<KnownHrp>) -> Self {
+        SilentPaymentAddress { version: 0, hrp: hrp.into(), scan_key, spend_key }
+    }
+
+    /// Returns the address version.
+    pub fn version(&self) -> u8 { self.version }
+
+    /// Returns the human-readable part this address is encoded with.
+    pub fn hrp(&self) -> KnownHrp { self.hrp }
+
+    /// Returns the scan key, used by the recipient to detect payments.
+    pub fn scan_key(&self) -> PublicKey { self.scan_key }
+
+    /// Returns the spend key, tweaked by the sender to produce the output key.
+    pub fn spend_key(&self) -> PublicKey { self.spend_key }
+
+    /// Returns true if this address is valid on `network`.
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        self.hrp == KnownHrp::from_network(network)
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hrp = match self.hrp {
+            KnownHrp::Mainnet => HRP_MAINNET,
+            KnownHrp::Testnets => HRP_TESTNETS,
+            KnownHrp::Regtest => HRP_REGTEST,
+        };
+        let version = Fe32::try_from(self.version).expect("version is at most 30");
+
+        let mut payload = [0u8; PAYLOAD_LEN];
+        payload[..33].copy_from_slice(&self.scan_key.serialize());
+        payload[33..].copy_from_slice(&self.spend_key.serialize());
+
+        let chars = payload
+            .iter()
+            .copied()
+            .bytes_to_fes()
+            .with_checksum::<Bech32m>(&hrp)
+            .with_witness_version(version)
+            .chars();
+        for c in chars {
+            fmt::Write::write_char(f, c)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let checked = CheckedHrpstring::new::<Bech32m>(s)?;
+
+        let hrp = checked.hrp();
+        let hrp = if hrp == HRP_MAINNET {
+            KnownHrp::Mainnet
+        } else if hrp == HRP_TESTNETS {
+            KnownHrp::Testnets
+        } else if hrp == HRP_REGTEST {
+            KnownHrp::Regtest
+        } else {
+            return Err(ParseError::UnknownHrp(hrp.to_lowercase()));
+        };
+
+        // The checksum is valid so every character is a valid field element.
+        let mut fes =
+            checked.data_part_ascii_no_checksum().iter().map(|&c| Fe32::from_char_unchecked(c));
+        let version = fes.next().ok_or(ParseError::InvalidLength(0))?.to_u8();
+        if version > MAX_VERSION {
+            return Err(ParseError::UnsupportedVersion(version));
+        }
+        let fes = fes.collect::<Vec<_>>();
+
+        // Padding must be at most 4 bits, all zero, as with segwit addresses.
+        let padding_len = fes.len() * 5 % 8;
+        let padding_mask = (1u8 << padding_len) - 1;
+        if padding_len > 4 || fes.last().map_or(false, |fe| fe.to_u8() & padding_mask != 0) {
+            return Err(ParseError::InvalidPadding);
+        }
+
+        let data = fes.into_iter().fes_to_bytes().collect::<Vec<_>>();
+        // Future versions may append data which we must ignore, version 0 is exactly 66 bytes.
+        if data.len() < PAYLOAD_LEN || (version == 0 && data.len() != PAYLOAD_LEN) {
+            return Err(ParseError::InvalidLength(data.len()));
+        }
+
+        let scan_key = PublicKey::from_slice(&data[..33]).map_err(ParseError::InvalidKey)?;
+        let spend_key =
+            PublicKey::from_slice(&data[33..PAYLOAD_LEN]).map_err(ParseError::InvalidKey)?;
+
+        Ok(SilentPaymentAddress { version, hrp, scan_key, spend_key })
+    }
+}
+
+/// The private key of an input eligible for silent payment output derivation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InputKey {
+    /// The key of a P2PKH, P2WPKH or P2SH-P2WPKH input.
+    Ecdsa(SecretKey),
+    /// The key of a P2TR key path input, this is the tweaked output key.
+    ///
+    /// The key is negated if necessary so it corresponds to the even-Y output key.
+    Taproot(SecretKey),
+}
+
+impl InputKey {
+    /// Returns the private key normalized for summing as required by BIP-352.
+    fn normalized<C: Signing>(&self, secp: &Secp256k1<C>) -> SecretKey {
+        match *self {
+            InputKey::Ecdsa(key) => key,
+            InputKey::Taproot(key) => {
+                let (_, parity) = key.x_only_public_key(secp);
+                if parity == secp256k1::Parity::Odd {
+                    key.negate()
+                } else {
+                    key
+                }
+            }
+        }
+    }
+}
+
+/// Derives the taproot output keys paying to `recipients`.
+///
+/// `input_keys` are the private keys of all eligible inputs the transaction spends and
+/// `outpoints` are the outpoints of all its inputs. The returned keys correspond, in order, to
+/// the `recipients`; paying the same address more than once yields distinct keys.
+pub fn sender_output_keys<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    input_keys: &[InputKey],
+    outpoints: &[OutPoint],
+    recipients: &[SilentPaymentAddress],
+) -> Result<Vec<TweakedPublicKey>, OutputKeyError> {
+    // Intermediate sums may be zero so only the final sum is checked.
+    let mut input_sum: Option<SecretKey> = None;
+    for key in input_keys {
+        let key = key.normalized(secp);
+        input_sum = match input_sum {
+            None => Some(key),
+            Some(sum) => sum.add_tweak(&Scalar::from(key)).ok(),
+        };
+    }
+    let input_sum = input_sum.ok_or(OutputKeyError::NoInputKeys)?;
+    let input_pubkey = input_sum.public_key(secp);
+
+    let smallest_outpoint = outpoints
+        .iter()
+        .min_by_key(|outpoint| encode::serialize(*outpoint))
+        .ok_or(OutputKeyError::NoOutpoints)?;
+    let input_hash = InputsHash::from_outpoint_and_key(smallest_outpoint, &input_pubkey);
+    let input_hash = Scalar::from_be_bytes(input_hash.to_byte_array())
+        .map_err(|_| OutputKeyError::InvalidTweak)?;
+    let tweak = input_sum.mul_tweak(&input_hash).map_err(|_| OutputKeyError::InvalidTweak)?;
+    let tweak = Scalar::from(tweak);
+
+    // Outputs to the same scan key are distinguished by a counter.
+    let mut counters: BTreeMap<PublicKey, u32> = BTreeMap::new();
+    let mut output_keys = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let k = counters.entry(recipient.scan_key).or_insert(0);
+        let shared_secret =
+            recipient.scan_key.mul_tweak(secp, &tweak).map_err(|_| OutputKeyError::InvalidTweak)?;
+        let t_k = SharedSecretHash::from_shared_secret(&shared_secret, *k);
+        let t_k =
+            Scalar::from_be_bytes(t_k.to_byte_array()).map_err(|_| OutputKeyError::InvalidTweak)?;
+        let output_key = recipient
+            .spend_key
+            .add_exp_tweak(secp, &t_k)
+            .map_err(|_| OutputKeyError::InvalidTweak)?;
+        let (output_key, _) = output_key.x_only_public_key();
+        output_keys.push(TweakedPublicKey::dangerous_assume_tweaked(output_key));
+        *k += 1;
+    }
+    Ok(output_keys)
+}
+
+/// Error parsing a silent payment address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseError {
+    /// Bech32m decoding error.
+    Bech32(CheckedHrpstringError),
+    /// The human-readable part is not a silent payment one.
+    UnknownHrp(String),
+    /// The address version is not supported.
+    UnsupportedVersion(u8),
+    /// The padding of the data part is invalid.
+    InvalidPadding,
+    /// The data part has an invalid length.
+    InvalidLength(usize),
+    /// The scan key or spend key is invalid.
+    InvalidKey(secp256k1::Error),
+}
+
+internals::impl_from_infallible!(ParseError);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseError::*;
+
+        match *self {
+            Bech32(ref e) => write_err!(f, "bech32m decoding error"; e),
+            UnknownHrp(ref hrp) => write!(f, "unknown silent payment hrp: {}", hrp),
+            UnsupportedVersion(v) => write!(f, "unsupported silent payment version: {}", v),
+            InvalidPadding => write!(f, "invalid padding in the data part"),
+            InvalidLength(len) => write!(f, "invalid silent payment data length: {}", len),
+            InvalidKey(ref e) => write_err!(f, "invalid silent payment key"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ParseError::*;
+
+        match *self {
+            Bech32(ref e) => Some(e),
+            InvalidKey(ref e) => Some(e),
+            UnknownHrp(_) | UnsupportedVersion(_) | InvalidPadding | InvalidLength(_) => None,
+        }
+    }
+}
+
+impl From<CheckedHrpstringError> for ParseError {
+    fn from(e: CheckedHrpstringError) -> Self { Self::Bech32(e) }
+}
+
+/// Error deriving silent payment output keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OutputKeyError {
+    /// No input keys were given or they sum to zero.
+    NoInputKeys,
+    /// No outpoints were given.
+    NoOutpoints,
+    /// A tweak was out of range or produced an invalid key (negligible probability).
+    InvalidTweak,
+}
+
+internals::impl_from_infallible!(OutputKeyError);
+
+impl fmt::Display for OutputKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use OutputKeyError::*;
+
+        match *self {
+            NoInputKeys => write!(f, "no input keys or the input keys sum to zero"),
+            NoOutpoints => write!(f, "no outpoints"),
+            InvalidTweak => write!(f, "tweak out of range or resulted in an invalid key"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OutputKeyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use OutputKeyError::*;
+
+        match *self {
+            NoInputKeys | NoOutpoints | InvalidTweak => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::FromHex;
+
+    use super::*;
+
+    const ADDRESS: &str = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+
+    fn key(hex: &str) -> PublicKey { PublicKey::from_slice(&Vec::from_hex(hex).unwrap()).unwrap() }
+
+    #[test]
+    fn address_roundtrip() {
+        let address = ADDRESS.parse::<SilentPaymentAddress>().unwrap();
+        assert_eq!(address.version(), 0);
+        assert_eq!(address.hrp(), KnownHrp::Mainnet);
+        assert!(address.is_valid_for_network(Network::Bitcoin));
+        assert_eq!(
+            address.scan_key(),
+            key("0220bcfac5b99e04ad1a06ddfb016ee13582609d60b6291e98d01a9bc9a16c96d4")
+        );
+        assert_eq!(
+            address.spend_key(),
+            key("025cc9856d6f8375350e123978daac200c260cb5b5ae83106cab90484dcd8fcf36")
+        );
+        assert_eq!(address.to_string(), ADDRESS);
+
+        let testnet =
+            SilentPaymentAddress::new(address.scan_key(), address.spend_key(), Network::Signet);
+        assert!(testnet.to_string().starts_with("tsp1q"));
+        assert_eq!(testnet.to_string().parse::<SilentPaymentAddress>().unwrap(), testnet);
+    }
+
+    #[test]
+    fn address_parse_errors() {
+        let segwit = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0";
+        assert!(matches!(segwit.parse::<SilentPaymentAddress>(), Err(ParseError::UnknownHrp(_))));
+
+        let mut bad_checksum = ADDRESS.to_owned();
+        bad_checksum.pop();
+        bad_checksum.push('q');
+        assert!(matches!(bad_checksum.parse::<SilentPaymentAddress>(), Err(ParseError::Bech32(_))));
+    }
+
+    #[test]
+    fn sender_output_keys_match_receiver() {
+        let secp = Secp256k1::new();
+        let scan_secret = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let spend_secret = SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let address = SilentPaymentAddress::new(
+            scan_secret.public_key(&secp),
+            spend_secret.public_key(&secp),
+            Network::Bitcoin,
+        );
+
+        let input_keys = [
+            InputKey::Ecdsa(SecretKey::from_slice(&[0x33; 32]).unwrap()),
+            InputKey::Taproot(SecretKey::from_slice(&[0x44; 32]).unwrap()),
+        ];
+        let outpoints = [
+            "a2365547d16b555593e3f58a2b67143fc8ab84e7e1257b1c13d2a9a2ec3a2efb:0".parse().unwrap(),
+            "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16:1".parse().unwrap(),
+        ];
+        let outputs =
+            sender_output_keys(&secp, &input_keys, &outpoints, &[address, address]).unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert_ne!(outputs[0], outputs[1]);
+
+        // The recipient computes the shared secret from the summed input public key instead.
+        let input_pubkey = input_keys
+            .iter()
+            .map(|key| key.normalized(&secp).public_key(&secp))
+            .fold(None, |sum: Option<PublicKey>, key| match sum {
+                None => Some(key),
+                Some(sum) => Some(sum.combine(&key).unwrap()),
+            })
+            .unwrap();
+        let smallest = outpoints.iter().min_by_key(|o| encode::serialize(*o)).unwrap();
+        let input_hash = InputsHash::from_outpoint_and_key(smallest, &input_pubkey);
+        let tweak = scan_secret
+            .mul_tweak(&Scalar::from_be_bytes(input_hash.to_byte_array()).unwrap())
+            .unwrap();
+        let shared_secret = input_pubkey.mul_tweak(&secp, &Scalar::from(tweak)).unwrap();
+        for (k, output) in outputs.iter().enumerate() {
+            let t_k = SharedSecretHash::from_shared_secret(&shared_secret, k as u32);
+            let expected = address
+                .spend_key()
+                .add_exp_tweak(&secp, &Scalar::from_be_bytes(t_k.to_byte_array()).unwrap())
+                .unwrap();
+            assert_eq!(output.to_inner(), expected.x_only_public_key().0);
+        }
+
+        assert_eq!(
+            sender_output_keys(&secp, &[], &outpoints, &[address]),
+            Err(OutputKeyError::NoInputKeys)
+        );
+        assert_eq!(
+            sender_output_keys(&secp, &input_keys, &[], &[address]),
+            Err(OutputKeyError::NoOutpoints)
+        );
+    }
+
+    // The first eight cases are sending and receiving cases of BIP 352's test vectors, restricted
+    // to the fields used here. The last two, for outpoint ordering and several recipients, were
+    // generated for this crate.
+    #[test]
+    fn bip352_vectors() {
+        let secp = Secp256k1::new();
+        let vectors: serde_json::Value = serde_json::from_str(include_str!(
+            "../../tests/data/bip352_send_and_receive_vectors.json"
+        ))
+        .unwrap();
+        for case in vectors.as_array().unwrap() {
+            let comment = case["comment"].as_str().unwrap();
+            let vin = case["given"]["vin"].as_array().unwrap();
+            let input_keys = vin
+                .iter()
+                .map(|input| {
+                    let secret = input["private_key"].as_str().unwrap().parse().unwrap();
+                    if input["taproot"].as_bool().unwrap() {
+                        InputKey::Taproot(secret)
+                    } else {
+                        InputKey::Ecdsa(secret)
+                    }
+                })
+                .collect::<Vec<_>>();
+            let outpoints = vin
+                .iter()
+                .map(|input| OutPoint {
+                    txid: input["txid"].as_str().unwrap().parse().unwrap(),
+                    vout: input["vout"].as_u64().unwrap() as u32,
+                })
+                .collect::<Vec<_>>();
+            let recipients = case["given"]["recipients"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|address| address.as_str().unwrap().parse().unwrap())
+                .collect::<Vec<SilentPaymentAddress>>();
+
+            let mut outputs = sender_output_keys(&secp, &input_keys, &outpoints, &recipients)
+                .unwrap()
+                .iter()
+                .map(|key| key.to_inner().to_string())
+                .collect::<Vec<_>>();
+            let mut expected = case["expected"]["outputs"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|key| key.as_str().unwrap().to_owned())
+                .collect::<Vec<_>>();
+            // Outputs for the same scan key may be assigned to the recipients in any order.
+            outputs.sort();
+            expected.sort();
+            assert_eq!(outputs, expected, "{}", comment);
+
+            // The receiver only sees the input public keys, as they appear on chain.
+            let input_pubkey = input_keys
+                .iter()
+                .map(|key| key.normalized(&secp).public_key(&secp))
+                .fold(None, |sum: Option<PublicKey>, key| match sum {
+                    None => Some(key),
+                    Some(sum) => Some(sum.combine(&key).unwrap()),
+                })
+                .unwrap();
+            let smallest = outpoints.iter().min_by_key(|o| encode::serialize(*o)).unwrap();
+            let input_hash = InputsHash::from_outpoint_and_key(smallest, &input_pubkey);
+            for receiving in case["receiving"].as_array().unwrap() {
+                let scan_secret: SecretKey =
+                    receiving["scan_priv_key"].as_str().unwrap().parse().unwrap();
+                let spend_secret: SecretKey =
+                    receiving["spend_priv_key"].as_str().unwrap().parse().unwrap();
+                let tweak = scan_secret
+                    .mul_tweak(&Scalar::from_be_bytes(input_hash.to_byte_array()).unwrap())
+                    .unwrap();
+                let shared_secret = input_pubkey.mul_tweak(&secp, &Scalar::from(tweak)).unwrap();
+
+                // Scan for outputs with increasing `k` until one is missing.
+                let mut found = vec![];
+                for k in 0.. {
+                    let t_k = SharedSecretHash::from_shared_secret(&shared_secret, k);
+                    let t_k = SecretKey::from_slice(t_k.as_byte_array()).unwrap();
+                    let key = spend_secret.add_tweak(&Scalar::from(t_k)).unwrap();
+                    let pub_key = key.x_only_public_key(&secp).0.to_string();
+                    if !outputs.contains(&pub_key) {
+                        break;
+                    }
+                    found.push((pub_key, t_k.display_secret().to_string()));
+                }
+                let expected = receiving["outputs"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|output| {
+                        (
+                            output["pub_key"].as_str().unwrap().to_owned(),
+                            output["priv_key_tweak"].as_str().unwrap().to_owned(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(found, expected, "{}", comment);
+            }
+        }
+    }
+}
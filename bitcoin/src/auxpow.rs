@@ -0,0 +1,442 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Auxiliary proof-of-work (merged mining).
+//!
+//! Merge-mined chains, such as Namecoin and Dogecoin, accept the proof-of-work of a block of a
+//! parent chain, usually Bitcoin, that commits to the merge-mined block in its coinbase
+//! transaction. The block header of a merge-mined block sets the [`VERSION_AUXPOW`] bit of its
+//! version and is followed by an [`AuxPow`] that links it to the parent block.
+//!
+//! This module implements the format and checks of Namecoin's `CAuxPow`. Whether the parent
+//! block hash meets the target of the merge-mined block is up to the caller.
+
+use core::fmt;
+
+use hashes::Hash;
+use io::{BufRead, Write};
+
+use crate::blockdata::block::{BlockHash, Header, TxMerkleNode};
+use crate::blockdata::transaction::Transaction;
+use crate::consensus::encode::{self, Decodable, Encodable};
+use crate::internal_macros::impl_consensus_encoding;
+use crate::merkle_tree::combine;
+use crate::prelude::*;
+
+/// The bit set in the version of a block header followed by an [`AuxPow`].
+pub const VERSION_AUXPOW: i32 = 1 << 8;
+
+/// The magic bytes preceding the merkle root of the merge-mined chains in the parent coinbase.
+pub const MERGED_MINING_HEADER: [u8; 4] = [0xfa, 0xbe, 0x6d, 0x6d];
+
+/// The maximum length of the merkle branch of the merge-mined chains.
+const MAX_CHAIN_MERKLE_HEIGHT: usize = 30;
+
+/// Returns true if the block header with `header`'s version is followed by an [`AuxPow`].
+pub fn has_auxpow(header: &Header) -> bool { header.version.to_consensus() & VERSION_AUXPOW != 0 }
+
+/// Returns the chain ID encoded in the upper 16 bits of `header`'s version.
+pub fn chain_id(header: &Header) -> i32 { header.version.to_consensus() >> 16 }
+
+/// Auxiliary proof-of-work, links a merge-mined block to a parent block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuxPow {
+    /// The coinbase transaction of the parent block, which commits to the merge-mined block.
+    pub coinbase_tx: Transaction,
+    /// The hash of the parent block, not used.
+    pub parent_hash: BlockHash,
+    /// The merkle branch linking the coinbase transaction to the parent block's merkle root.
+    pub coinbase_branch: Vec<TxMerkleNode>,
+    /// The position of the coinbase transaction in the parent block, must be zero.
+    pub coinbase_index: i32,
+    /// The merkle branch linking the merge-mined block to the merkle root of merge-mined chains.
+    pub blockchain_branch: Vec<TxMerkleNode>,
+    /// The position of the merge-mined block in the merkle tree of merge-mined chains.
+    pub blockchain_index: i32,
+    /// The header of the parent block.
+    pub parent_block_header: Header,
+}
+
+impl_consensus_encoding!(
+    AuxPow,
+    coinbase_tx,
+    parent_hash,
+    coinbase_branch,
+    coinbase_index,
+    blockchain_branch,
+    blockchain_index,
+    parent_block_header
+);
+
+impl AuxPow {
+    /// Checks that this commits to the block with hash `aux_block_hash` of chain `chain_id`.
+    ///
+    /// If `strict_chain_id` is set the parent block must not have the same chain ID. This does not
+    /// check the proof-of-work of the parent block, see [`AuxPow::parent_block_hash`].
+    pub fn check(
+        &self,
+        aux_block_hash: BlockHash,
+        chain_id: i32,
+        strict_chain_id: bool,
+    ) -> Result<(), AuxPowError> {
+        use AuxPowError::*;
+
+        if self.coinbase_index != 0 {
+            return Err(CoinbaseIndexNotZero);
+        }
+        if strict_chain_id && self::chain_id(&self.parent_block_header) == chain_id {
+            return Err(ParentHasChainId);
+        }
+        if self.blockchain_branch.len() > MAX_CHAIN_MERKLE_HEIGHT {
+            return Err(ChainMerkleBranchTooLong);
+        }
+
+        let aux_leaf = TxMerkleNode::from_byte_array(aux_block_hash.to_byte_array());
+        let root = branch_root(aux_leaf, &self.blockchain_branch, self.blockchain_index);
+        // The root is committed to in display byte order.
+        let mut root = root.to_byte_array();
+        root.reverse();
+
+        let coinbase_leaf =
+            TxMerkleNode::from_byte_array(self.coinbase_tx.compute_txid().to_byte_array());
+        if branch_root(coinbase_leaf, &self.coinbase_branch, self.coinbase_index)
+            != self.parent_block_header.merkle_root
+        {
+            return Err(CoinbaseNotInParent);
+        }
+
+        let script = self.coinbase_tx.input.first().ok_or(NoCoinbaseInput)?.script_sig.as_bytes();
+        let root_pos = find(script, &root).ok_or(ChainMerkleRootNotFound)?;
+        match find(script, &MERGED_MINING_HEADER) {
+            Some(header_pos) => {
+                if find(&script[header_pos + 1..], &MERGED_MINING_HEADER).is_some() {
+                    return Err(MultipleMergedMiningHeaders);
+                }
+                if header_pos + MERGED_MINING_HEADER.len() != root_pos {
+                    return Err(MergedMiningHeaderNotBeforeRoot);
+                }
+            }
+            // Without the header the root must start early to ensure there is only one.
+            None =>
+                if root_pos > 20 {
+                    return Err(ChainMerkleRootTooLate);
+                },
+        }
+
+        let rest = &script[root_pos + root.len()..];
+        if rest.len() < 8 {
+            return Err(MissingTreeSizeAndNonce);
+        }
+        let size = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        let height = self.blockchain_branch.len() as u32;
+        if size != 1 << height {
+            return Err(TreeSizeMismatch);
+        }
+        let nonce = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]);
+        if self.blockchain_index as u32 != expected_index(nonce, chain_id, height) {
+            return Err(WrongChainIndex);
+        }
+        Ok(())
+    }
+
+    /// Returns the hash of the parent block, which must meet the target of the merge-mined block.
+    pub fn parent_block_hash(&self) -> BlockHash { self.parent_block_header.block_hash() }
+}
+
+/// A block header of a merge-mined chain, followed by an [`AuxPow`] if it has one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuxPowHeader {
+    /// The block header.
+    pub header: Header,
+    /// The auxiliary proof-of-work, present iff the [`VERSION_AUXPOW`] bit of the version is set.
+    pub aux_pow: Option<AuxPow>,
+}
+
+impl Encodable for AuxPowHeader {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let mut len = self.header.consensus_encode(w)?;
+        if let Some(ref aux_pow) = self.aux_pow {
+            len += aux_pow.consensus_encode(w)?;
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for AuxPowHeader {
+    fn consensus_decode_from_finite_reader<R: BufRead + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let header: Header = Decodable::consensus_decode_from_finite_reader(r)?;
+        let aux_pow = if has_auxpow(&header) {
+            Some(Decodable::consensus_decode_from_finite_reader(r)?)
+        } else {
+            None
+        };
+        Ok(AuxPowHeader { header, aux_pow })
+    }
+}
+
+// Like Namecoin's `CAuxPow::CheckMerkleBranch`, including its handling of negative indexes.
+fn branch_root(leaf: TxMerkleNode, branch: &[TxMerkleNode], mut index: i32) -> TxMerkleNode {
+    if index == -1 {
+        return TxMerkleNode::all_zeros();
+    }
+    let mut node = leaf;
+    for sibling in branch {
+        node = if index & 1 != 0 { combine(sibling, &node) } else { combine(&node, sibling) };
+        index >>= 1;
+    }
+    node
+}
+
+/// Returns the position of the merge-mined chain `chain_id` in a tree of height `height`.
+fn expected_index(nonce: u32, chain_id: i32, height: u32) -> u32 {
+    // The low bits of a linear congruential generator are used, as in Namecoin.
+    let mut rand = nonce;
+    rand = rand.wrapping_mul(1103515245).wrapping_add(12345);
+    rand = rand.wrapping_add(chain_id as u32);
+    rand = rand.wrapping_mul(1103515245).wrapping_add(12345);
+    rand % (1 << height)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// An error when checking an [`AuxPow`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AuxPowError {
+    /// The coinbase transaction is not at position zero.
+    CoinbaseIndexNotZero,
+    /// The parent block has the chain ID of the merge-mined chain.
+    ParentHasChainId,
+    /// The merkle branch of the merge-mined chains is longer than 30 hashes.
+    ChainMerkleBranchTooLong,
+    /// The coinbase transaction is not committed to by the parent block's merkle root.
+    CoinbaseNotInParent,
+    /// The coinbase transaction has no inputs.
+    NoCoinbaseInput,
+    /// The coinbase doesn't contain the merkle root of the merge-mined chains.
+    ChainMerkleRootNotFound,
+    /// The coinbase contains the merged mining header more than once.
+    MultipleMergedMiningHeaders,
+    /// The merged mining header doesn't immediately precede the merkle root.
+    MergedMiningHeaderNotBeforeRoot,
+    /// Without the merged mining header, the merkle root doesn't start in the first 20 bytes.
+    ChainMerkleRootTooLate,
+    /// The merkle tree size and nonce don't follow the merkle root.
+    MissingTreeSizeAndNonce,
+    /// The merkle tree size doesn't match the merkle branch of the merge-mined chains.
+    TreeSizeMismatch,
+    /// The position of the merge-mined block is not the one required by the nonce and chain ID.
+    WrongChainIndex,
+}
+
+internals::impl_from_infallible!(AuxPowError);
+
+impl fmt::Display for AuxPowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use AuxPowError::*;
+
+        match *self {
+            CoinbaseIndexNotZero => f.write_str("auxpow coinbase is not at position zero"),
+            ParentHasChainId => f.write_str("auxpow parent block has our chain ID"),
+            ChainMerkleBranchTooLong => f.write_str("auxpow chain merkle branch too long"),
+            CoinbaseNotInParent => f.write_str("auxpow coinbase not in parent block"),
+            NoCoinbaseInput => f.write_str("auxpow coinbase has no inputs"),
+            ChainMerkleRootNotFound => f.write_str("auxpow chain merkle root not in coinbase"),
+            MultipleMergedMiningHeaders =>
+                f.write_str("multiple merged mining headers in coinbase"),
+            MergedMiningHeaderNotBeforeRoot =>
+                f.write_str("merged mining header not just before chain merkle root"),
+            ChainMerkleRootTooLate =>
+                f.write_str("auxpow chain merkle root not in the first 20 bytes of coinbase"),
+            MissingTreeSizeAndNonce =>
+                f.write_str("auxpow chain merkle tree size and nonce not in coinbase"),
+            TreeSizeMismatch => f.write_str("auxpow chain merkle tree size doesn't match branch"),
+            WrongChainIndex => f.write_str("auxpow wrong chain index"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AuxPowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use AuxPowError::*;
+
+        match *self {
+            CoinbaseIndexNotZero
+            | ParentHasChainId
+            | ChainMerkleBranchTooLong
+            | CoinbaseNotInParent
+            | NoCoinbaseInput
+            | ChainMerkleRootNotFound
+            | MultipleMergedMiningHeaders
+            | MergedMiningHeaderNotBeforeRoot
+            | ChainMerkleRootTooLate
+            | MissingTreeSizeAndNonce
+            | TreeSizeMismatch
+            | WrongChainIndex => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockdata::block::Version;
+    use crate::blockdata::locktime::absolute;
+    use crate::blockdata::script::ScriptBuf;
+    use crate::blockdata::transaction::{self, OutPoint, Sequence, TxIn};
+    use crate::blockdata::witness::Witness;
+    use crate::consensus::encode::{deserialize, serialize};
+    use crate::pow::CompactTarget;
+
+    const CHAIN_ID: i32 = 1;
+
+    fn header(version: i32, prev_blockhash: BlockHash, merkle_root: TxMerkleNode) -> Header {
+        Header {
+            version: Version::from_consensus(version),
+            prev_blockhash,
+            merkle_root,
+            time: 1_700_000_000,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        }
+    }
+
+    // Builds an auxpow committing to `aux_block_hash` with the coinbase script `script`.
+    fn aux_pow(script: Vec<u8>, blockchain_branch: Vec<TxMerkleNode>, index: i32) -> AuxPow {
+        let coinbase_tx = Transaction {
+            version: transaction::Version::ONE,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::null(),
+                script_sig: ScriptBuf::from_bytes(script),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![],
+        };
+        let merkle_root = TxMerkleNode::from_byte_array(coinbase_tx.compute_txid().to_byte_array());
+        AuxPow {
+            coinbase_tx,
+            parent_hash: BlockHash::all_zeros(),
+            coinbase_branch: vec![],
+            coinbase_index: 0,
+            blockchain_branch,
+            blockchain_index: index,
+            parent_block_header: header(0x20000000, BlockHash::all_zeros(), merkle_root),
+        }
+    }
+
+    // The coinbase script committing to `root` in a tree of height `height` with `nonce`.
+    fn commitment(root: TxMerkleNode, height: u32, nonce: u32) -> Vec<u8> {
+        let mut root = root.to_byte_array();
+        root.reverse();
+        let mut script = vec![0x03, 0x01, 0x02, 0x03];
+        script.extend_from_slice(&MERGED_MINING_HEADER);
+        script.extend_from_slice(&root);
+        script.extend_from_slice(&(1_u32 << height).to_le_bytes());
+        script.extend_from_slice(&nonce.to_le_bytes());
+        script
+    }
+
+    #[test]
+    fn auxpow_check() {
+        let aux_header = header(
+            VERSION_AUXPOW | (CHAIN_ID << 16),
+            BlockHash::all_zeros(),
+            TxMerkleNode::all_zeros(),
+        );
+        let aux_hash = aux_header.block_hash();
+        let aux_leaf = TxMerkleNode::from_byte_array(aux_hash.to_byte_array());
+        assert!(has_auxpow(&aux_header));
+        assert_eq!(chain_id(&aux_header), CHAIN_ID);
+
+        // Single merge-mined chain.
+        let aux_pow = aux_pow(commitment(aux_leaf, 0, 7), vec![], 0);
+        assert_eq!(aux_pow.check(aux_hash, CHAIN_ID, true), Ok(()));
+        assert_eq!(
+            aux_pow.check(BlockHash::all_zeros(), CHAIN_ID, true),
+            Err(AuxPowError::ChainMerkleRootNotFound)
+        );
+
+        // Two merge-mined chains, the position is determined by the nonce.
+        let sibling = TxMerkleNode::all_zeros();
+        let nonce = 7;
+        let index = expected_index(nonce, CHAIN_ID, 1) as i32;
+        let root = branch_root(aux_leaf, &[sibling], index);
+        let mut aux_pow = self::aux_pow(commitment(root, 1, nonce), vec![sibling], index);
+        assert_eq!(aux_pow.check(aux_hash, CHAIN_ID, true), Ok(()));
+        aux_pow.blockchain_index = 1 - index;
+        assert_eq!(
+            aux_pow.check(aux_hash, CHAIN_ID, true),
+            Err(AuxPowError::ChainMerkleRootNotFound)
+        );
+
+        let mut aux_pow = self::aux_pow(commitment(root, 1, nonce + 1), vec![sibling], index);
+        if expected_index(nonce + 1, CHAIN_ID, 1) as i32 != index {
+            assert_eq!(aux_pow.check(aux_hash, CHAIN_ID, true), Err(AuxPowError::WrongChainIndex));
+        }
+        aux_pow.coinbase_index = 1;
+        assert_eq!(aux_pow.check(aux_hash, CHAIN_ID, true), Err(AuxPowError::CoinbaseIndexNotZero));
+
+        let mut aux_pow = self::aux_pow(commitment(aux_leaf, 1, 7), vec![], 0);
+        assert_eq!(aux_pow.check(aux_hash, CHAIN_ID, true), Err(AuxPowError::TreeSizeMismatch));
+        aux_pow.parent_block_header.merkle_root = TxMerkleNode::all_zeros();
+        assert_eq!(aux_pow.check(aux_hash, CHAIN_ID, true), Err(AuxPowError::CoinbaseNotInParent));
+
+        let mut script = commitment(aux_leaf, 0, 7);
+        script.extend_from_slice(&MERGED_MINING_HEADER);
+        let aux_pow = self::aux_pow(script, vec![], 0);
+        assert_eq!(
+            aux_pow.check(aux_hash, CHAIN_ID, true),
+            Err(AuxPowError::MultipleMergedMiningHeaders)
+        );
+
+        // Without the merged mining header the root must be in the first 20 bytes.
+        let mut script = commitment(aux_leaf, 0, 7);
+        script.drain(4..4 + MERGED_MINING_HEADER.len());
+        assert_eq!(
+            self::aux_pow(script.clone(), vec![], 0).check(aux_hash, CHAIN_ID, true),
+            Ok(())
+        );
+        let script = [vec![0; 20], script].concat();
+        assert_eq!(
+            self::aux_pow(script, vec![], 0).check(aux_hash, CHAIN_ID, true),
+            Err(AuxPowError::ChainMerkleRootTooLate)
+        );
+
+        let mut aux_pow = self::aux_pow(commitment(aux_leaf, 0, 7), vec![], 0);
+        aux_pow.parent_block_header.version = Version::from_consensus(CHAIN_ID << 16);
+        assert_eq!(aux_pow.check(aux_hash, CHAIN_ID, true), Err(AuxPowError::ParentHasChainId));
+        assert_eq!(aux_pow.check(aux_hash, CHAIN_ID, false), Ok(()));
+    }
+
+    #[test]
+    fn auxpow_header_roundtrip() {
+        let plain = AuxPowHeader {
+            header: header(0x20000000, BlockHash::all_zeros(), TxMerkleNode::all_zeros()),
+            aux_pow: None,
+        };
+        let serialized = serialize(&plain);
+        assert_eq!(serialized.len(), Header::SIZE);
+        assert_eq!(deserialize::<AuxPowHeader>(&serialized).unwrap(), plain);
+
+        let aux_header = header(
+            VERSION_AUXPOW | (CHAIN_ID << 16),
+            BlockHash::all_zeros(),
+            TxMerkleNode::all_zeros(),
+        );
+        let aux_leaf = TxMerkleNode::from_byte_array(aux_header.block_hash().to_byte_array());
+        let merged = AuxPowHeader {
+            header: aux_header,
+            aux_pow: Some(aux_pow(commitment(aux_leaf, 0, 7), vec![], 0)),
+        };
+        let serialized = serialize(&merged);
+        assert_eq!(deserialize::<AuxPowHeader>(&serialized).unwrap(), merged);
+        // The auxpow is only read if the version says so.
+        assert!(deserialize::<AuxPowHeader>(&serialized[..Header::SIZE]).is_err());
+    }
+}
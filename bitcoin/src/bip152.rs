@@ -26,6 +26,12 @@ pub enum Error {
     UnknownVersion,
     /// The prefill slice provided was invalid.
     InvalidPrefill,
+    /// Two transactions of a compact block have the same short ID.
+    ShortIdCollision,
+    /// The block transactions don't match the missing transactions.
+    InvalidBlockTransactions,
+    /// The reconstructed block doesn't match the merkle root of its header.
+    MerkleRootMismatch,
 }
 
 internals::impl_from_infallible!(Error);
@@ -35,6 +41,11 @@ impl fmt::Display for Error {
         match *self {
             Error::UnknownVersion => write!(f, "an unknown version number was used"),
             Error::InvalidPrefill => write!(f, "the prefill slice provided was invalid"),
+            Error::ShortIdCollision => write!(f, "two transactions have the same short ID"),
+            Error::InvalidBlockTransactions =>
+                write!(f, "the block transactions don't match the missing transactions"),
+            Error::MerkleRootMismatch =>
+                write!(f, "the reconstructed block doesn't match its merkle root"),
         }
     }
 }
@@ -45,7 +56,11 @@ impl std::error::Error for Error {
         use self::Error::*;
 
         match *self {
-            UnknownVersion | InvalidPrefill => None,
+            UnknownVersion
+            | InvalidPrefill
+            | ShortIdCollision
+            | InvalidBlockTransactions
+            | MerkleRootMismatch => None,
         }
     }
 }
@@ -241,6 +256,103 @@ impl HeaderAndShortIds {
             short_ids,
         })
     }
+
+    /// Returns the siphash keys the short IDs of this compact block are calculated with.
+    pub fn siphash_keys(&self) -> (u64, u64) {
+        ShortId::calculate_siphash_keys(&self.header, self.nonce)
+    }
+}
+
+/// A block being reconstructed from a [HeaderAndShortIds].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PartialBlock {
+    header: block::Header,
+    txdata: Vec<Option<Transaction>>,
+}
+
+impl PartialBlock {
+    /// Starts reconstructing the block of `compact`, looking up transactions by short ID with
+    /// `mempool_lookup`.
+    ///
+    /// The short ID of a mempool transaction is calculated with the
+    /// [`HeaderAndShortIds::siphash_keys`] from its wtxid (or txid for version 1 compact blocks).
+    /// Returns [`Error::ShortIdCollision`] if short IDs aren't unique, in which case the full
+    /// block should be requested instead.
+    pub fn from_compact<F>(
+        compact: &HeaderAndShortIds,
+        mut mempool_lookup: F,
+    ) -> Result<Self, Error>
+    where
+        F: FnMut(ShortId) -> Option<Transaction>,
+    {
+        let len = compact.short_ids.len() + compact.prefilled_txs.len();
+        let mut txdata = vec![None; len];
+
+        // Prefilled transaction indexes are differentially encoded.
+        let mut next_idx = 0;
+        for prefilled in &compact.prefilled_txs {
+            let idx = next_idx + usize::from(prefilled.idx);
+            let slot = txdata.get_mut(idx).ok_or(Error::InvalidPrefill)?;
+            *slot = Some(prefilled.tx.clone());
+            next_idx = idx + 1;
+        }
+
+        let mut short_ids = compact.short_ids.clone();
+        short_ids.sort_unstable();
+        if short_ids.windows(2).any(|w| w[0] == w[1]) {
+            return Err(Error::ShortIdCollision);
+        }
+
+        let empty = txdata.iter_mut().filter(|tx| tx.is_none());
+        for (slot, short_id) in empty.zip(&compact.short_ids) {
+            *slot = mempool_lookup(*short_id);
+        }
+        Ok(PartialBlock { header: compact.header, txdata })
+    }
+
+    /// Returns the indexes of the transactions that were not found.
+    pub fn missing_indexes(&self) -> Vec<u64> {
+        self.txdata
+            .iter()
+            .enumerate()
+            .filter(|(_, tx)| tx.is_none())
+            .map(|(idx, _)| idx as u64)
+            .collect()
+    }
+
+    /// Returns the request for the transactions that were not found.
+    pub fn request(&self) -> BlockTransactionsRequest {
+        BlockTransactionsRequest::from_missing_indexes(
+            self.header.block_hash(),
+            self.missing_indexes(),
+        )
+    }
+
+    /// Completes the block with the transactions that were not found.
+    ///
+    /// `missing` is the response to [`PartialBlock::request`], it is empty if no transactions are
+    /// missing. Returns [`Error::MerkleRootMismatch`] if a mempool transaction was looked up
+    /// wrongly, in which case the full block should be requested instead.
+    pub fn fill(self, missing: &BlockTransactions) -> Result<Block, Error> {
+        let n_missing = self.txdata.iter().filter(|tx| tx.is_none()).count();
+        if n_missing != missing.transactions.len()
+            || (n_missing > 0 && missing.block_hash != self.header.block_hash())
+        {
+            return Err(Error::InvalidBlockTransactions);
+        }
+
+        let mut missing = missing.transactions.iter();
+        let txdata = self
+            .txdata
+            .into_iter()
+            .map(|tx| tx.unwrap_or_else(|| missing.next().expect("counted").clone()))
+            .collect();
+        let block = Block { header: self.header, txdata };
+        if !block.check_merkle_root() {
+            return Err(Error::MerkleRootMismatch);
+        }
+        Ok(block)
+    }
 }
 
 /// A [BlockTransactionsRequest] structure is used to list transaction indexes
@@ -255,6 +367,21 @@ pub struct BlockTransactionsRequest {
     pub indexes: Vec<u64>,
 }
 
+impl BlockTransactionsRequest {
+    /// Creates a request for the transactions at `indexes` of the block with `block_hash`.
+    ///
+    /// The indexes are sorted and deduplicated, as required by the differential encoding.
+    pub fn from_missing_indexes<I>(block_hash: BlockHash, indexes: I) -> BlockTransactionsRequest
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        let mut indexes = indexes.into_iter().collect::<Vec<_>>();
+        indexes.sort_unstable();
+        indexes.dedup();
+        BlockTransactionsRequest { block_hash, indexes }
+    }
+}
+
 impl Encodable for BlockTransactionsRequest {
     /// # Panics
     ///
@@ -489,6 +616,91 @@ mod test {
         }
     }
 
+    // A block with a valid merkle root and `n` transactions.
+    fn dummy_block_with_txs(n: u8) -> Block {
+        let mut block = dummy_block();
+        block.txdata = (0..n).map(|i| dummy_tx(&[i])).collect();
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        block
+    }
+
+    #[test]
+    fn test_partial_block_reconstruction() {
+        let block = dummy_block_with_txs(6);
+        let compact = HeaderAndShortIds::from_block(&block, 42, 2, &[3]).unwrap();
+        let keys = compact.siphash_keys();
+        // The mempool has all but the transactions at indexes 2 and 4.
+        let mempool = block
+            .txdata
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| *idx != 2 && *idx != 4)
+            .map(|(_, tx)| (ShortId::with_siphash_keys(&tx.compute_wtxid(), keys), tx.clone()))
+            .collect::<BTreeMap<_, _>>();
+
+        let partial = PartialBlock::from_compact(&compact, |id| mempool.get(&id).cloned()).unwrap();
+        assert_eq!(partial.missing_indexes(), vec![2, 4]);
+        let request = partial.request();
+        assert_eq!(request.block_hash, block.block_hash());
+        // indexes 2 and 4 are differentially encoded as 2 and 1.
+        assert_eq!(serialize(&request)[32..], [2, 2, 1]);
+
+        let missing = BlockTransactions::from_request(&request, &block).unwrap();
+        assert_eq!(partial.clone().fill(&missing).unwrap(), block);
+
+        let mut wrong = missing.clone();
+        wrong.transactions.swap(0, 1);
+        assert_eq!(partial.clone().fill(&wrong), Err(Error::MerkleRootMismatch));
+        wrong.transactions.pop();
+        assert_eq!(partial.fill(&wrong), Err(Error::InvalidBlockTransactions));
+
+        // Nothing is missing if the mempool has all transactions.
+        let keys = compact.siphash_keys();
+        let partial = PartialBlock::from_compact(&compact, |id| {
+            block
+                .txdata
+                .iter()
+                .find(|tx| ShortId::with_siphash_keys(&tx.compute_wtxid(), keys) == id)
+                .cloned()
+        })
+        .unwrap();
+        assert!(partial.missing_indexes().is_empty());
+        let empty = BlockTransactions { block_hash: Hash::all_zeros(), transactions: vec![] };
+        assert_eq!(partial.fill(&empty).unwrap(), block);
+    }
+
+    #[test]
+    fn test_partial_block_invalid_compact() {
+        let block = dummy_block_with_txs(3);
+        let mut compact = HeaderAndShortIds::from_block(&block, 42, 2, &[]).unwrap();
+        compact.short_ids[1] = compact.short_ids[0];
+        assert_eq!(PartialBlock::from_compact(&compact, |_| None), Err(Error::ShortIdCollision));
+
+        let mut compact = HeaderAndShortIds::from_block(&block, 42, 2, &[2]).unwrap();
+        compact.prefilled_txs[1].idx = 2;
+        assert_eq!(PartialBlock::from_compact(&compact, |_| None), Err(Error::InvalidPrefill));
+    }
+
+    #[test]
+    fn test_prefilled_differential_encoding() {
+        let block = dummy_block_with_txs(8);
+        let compact = HeaderAndShortIds::from_block(&block, 42, 2, &[1, 4, 7]).unwrap();
+        let idxs = compact.prefilled_txs.iter().map(|t| t.idx).collect::<Vec<_>>();
+        assert_eq!(idxs, vec![0, 0, 2, 2]);
+
+        let decoded: HeaderAndShortIds = deserialize(&serialize(&compact)).unwrap();
+        let partial = PartialBlock::from_compact(&decoded, |_| None).unwrap();
+        assert_eq!(partial.missing_indexes(), vec![2, 3, 5, 6]);
+    }
+
+    #[test]
+    fn test_block_transactions_request_from_missing_indexes() {
+        let request =
+            BlockTransactionsRequest::from_missing_indexes(Hash::all_zeros(), vec![5, 1, 5, 3]);
+        assert_eq!(request.indexes, vec![1, 3, 5]);
+        assert_eq!(serialize(&request)[32..], [3, 1, 1, 1]);
+    }
+
     #[test]
     #[cfg(debug_assertions)]
     #[should_panic] // 'attempt to add with overflow' in consensus_encode()
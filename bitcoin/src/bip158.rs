@@ -143,6 +143,35 @@ impl BlockFilter {
         Ok(BlockFilter { content: out })
     }
 
+    /// Computes the basic filter of `block`, looking up spent output scripts with `prevout_lookup`.
+    ///
+    /// The filter contains the output scripts of the block, except `OP_RETURN` outputs, and the
+    /// output scripts spent by its inputs, except the coinbase input. Empty scripts are skipped.
+    /// Returns [`Error::UtxoMissing`] if `prevout_lookup` doesn't find a spent output.
+    pub fn new_script_filter_from_block<M, S>(
+        block: &Block,
+        mut prevout_lookup: M,
+    ) -> Result<BlockFilter, Error>
+    where
+        M: FnMut(&OutPoint) -> Option<S>,
+        S: Borrow<Script>,
+    {
+        let mut out = Vec::new();
+        let mut writer = BlockFilterWriter::new(&mut out, block);
+
+        writer.add_output_scripts();
+        for tx in block.txdata.iter().skip(1) {
+            for input in &tx.input {
+                let outpoint = input.previous_output;
+                let script = prevout_lookup(&outpoint).ok_or(Error::UtxoMissing(outpoint))?;
+                writer.add_element(script.borrow().as_bytes());
+            }
+        }
+        writer.finish()?;
+
+        Ok(BlockFilter { content: out })
+    }
+
     /// Computes this filter's ID in a chain of filters (see [BIP 157]).
     ///
     /// [BIP 157]: <https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki#Filter_Headers>
@@ -170,6 +199,39 @@ impl BlockFilter {
         let filter_reader = BlockFilterReader::new(block_hash);
         filter_reader.match_all(&mut self.content.as_slice(), query)
     }
+
+    /// Returns the positions of the queries that match against this [`BlockFilter`], in order.
+    pub fn match_any_with_hits<I>(
+        &self,
+        block_hash: &BlockHash,
+        query: I,
+    ) -> Result<Vec<usize>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<[u8]>,
+    {
+        let filter_reader = BlockFilterReader::new(block_hash);
+        filter_reader.match_any_with_hits(&mut self.content.as_slice(), query)
+    }
+}
+
+/// Computes the filter headers of the consecutive `filters` (see [BIP 157]).
+///
+/// The first filter builds on `previous_filter_header`, which is all zeros for the genesis block.
+///
+/// [BIP 157]: <https://github.com/bitcoin/bips/blob/master/bip-0157.mediawiki#Filter_Headers>
+pub fn filter_headers<'a, I>(previous_filter_header: FilterHeader, filters: I) -> Vec<FilterHeader>
+where
+    I: IntoIterator<Item = &'a BlockFilter>,
+{
+    let mut previous = previous_filter_header;
+    filters
+        .into_iter()
+        .map(|filter| {
+            previous = filter.filter_header(&previous);
+            previous
+        })
+        .collect()
 }
 
 /// Compiles and writes a block filter.
@@ -261,6 +323,25 @@ impl BlockFilterReader {
     {
         self.reader.match_all(reader, query)
     }
+
+    /// Returns the positions of the queries that match against this [`BlockFilterReader`], in
+    /// order.
+    pub fn match_any_with_hits<I, R>(&self, reader: &mut R, query: I) -> Result<Vec<usize>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<[u8]>,
+        R: BufRead + ?Sized,
+    {
+        self.reader.match_any_with_hits(reader, query)
+    }
+
+    /// Returns an iterator over the decoded set of the filter read from `reader`.
+    pub fn decode<'a, R>(&self, reader: &'a mut R) -> GcsFilterValues<'a, R>
+    where
+        R: BufRead + ?Sized,
+    {
+        self.reader.decode(reader)
+    }
 }
 
 /// Golomb-Rice encoded filter reader.
@@ -361,6 +442,162 @@ impl GcsFilterReader {
         }
         Ok(true)
     }
+
+    /// Returns the positions of the queries that match against this [`GcsFilterReader`], in
+    /// order.
+    ///
+    /// The filter is decoded while matching but this allocates, the mapped queries are buffered and
+    /// sorted since their hashes are not ordered. Use [`GcsFilterValues::match_sorted`] to match
+    /// queries that are already mapped and sorted without any allocation.
+    pub fn match_any_with_hits<I, R>(&self, reader: &mut R, query: I) -> Result<Vec<usize>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<[u8]>,
+        R: BufRead + ?Sized,
+    {
+        let values = self.decode(reader);
+        let mut mapped = query
+            .into_iter()
+            .enumerate()
+            .map(|(i, e)| (values.map_to_range(e.borrow()), i))
+            .collect::<Vec<_>>();
+        mapped.sort_unstable();
+
+        let mut hits = values
+            .match_sorted(mapped.iter().map(|&(p, _)| p))
+            .map(|j| j.map(|j| mapped[j].1))
+            .collect::<Result<Vec<_>, _>>()?;
+        hits.sort_unstable();
+        Ok(hits)
+    }
+
+    /// Returns an iterator over the decoded set of the filter read from `reader`.
+    ///
+    /// The set is decoded while iterating, without buffering the filter.
+    pub fn decode<'a, R>(&self, reader: &'a mut R) -> GcsFilterValues<'a, R>
+    where
+        R: BufRead + ?Sized,
+    {
+        let n_elements: VarInt = Decodable::consensus_decode(reader).unwrap_or(VarInt(0));
+        GcsFilterValues {
+            filter: GcsFilter::new(self.filter.k0, self.filter.k1, self.filter.p),
+            nm: n_elements.0 * self.m,
+            remaining: n_elements.0,
+            last: 0,
+            reader: BitStreamReader::new(reader),
+        }
+    }
+}
+
+/// The decoded set of a Golomb-Rice encoded filter, in ascending order.
+///
+/// Created by [`GcsFilterReader::decode`] and [`BlockFilterReader::decode`].
+pub struct GcsFilterValues<'a, R: ?Sized> {
+    filter: GcsFilter,
+    nm: u64,
+    remaining: u64,
+    last: u64,
+    reader: BitStreamReader<'a, R>,
+}
+
+impl<'a, R: BufRead + ?Sized> GcsFilterValues<'a, R> {
+    /// Maps `element` to the value it has in the set if it is an element of the filter.
+    pub fn map_to_range(&self, element: &[u8]) -> u64 {
+        map_to_range(self.filter.hash(element), self.nm)
+    }
+
+    /// Returns an iterator over the positions of the `sorted` values that are elements of the set.
+    ///
+    /// The values must be mapped with [`GcsFilterValues::map_to_range`] and in ascending order,
+    /// both sequences are then merged in a single pass without buffering either of them.
+    pub fn match_sorted<I>(self, sorted: I) -> GcsFilterMatches<'a, R, I::IntoIter>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        GcsFilterMatches {
+            values: self,
+            sorted: sorted.into_iter().enumerate(),
+            value: None,
+            started: false,
+            done: false,
+        }
+    }
+}
+
+impl<'a, R: BufRead + ?Sized> Iterator for GcsFilterValues<'a, R> {
+    type Item = Result<u64, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match self.filter.golomb_rice_decode(&mut self.reader) {
+            Ok(delta) => {
+                self.remaining -= 1;
+                self.last += delta;
+                Some(Ok(self.last))
+            }
+            Err(e) => {
+                self.remaining = 0;
+                Some(Err(e))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = usize::try_from(self.remaining).unwrap_or(usize::MAX);
+        (0, Some(remaining))
+    }
+}
+
+/// The positions of sorted values that are elements of a filter's set, in ascending order.
+///
+/// Created by [`GcsFilterValues::match_sorted`].
+pub struct GcsFilterMatches<'a, R: ?Sized, I> {
+    values: GcsFilterValues<'a, R>,
+    sorted: core::iter::Enumerate<I>,
+    value: Option<u64>,
+    started: bool,
+    done: bool,
+}
+
+impl<'a, R: BufRead + ?Sized, I: Iterator<Item = u64>> GcsFilterMatches<'a, R, I> {
+    fn advance(&mut self) -> Result<(), io::Error> {
+        self.value = self.values.next().transpose()?;
+        Ok(())
+    }
+}
+
+impl<'a, R: BufRead + ?Sized, I: Iterator<Item = u64>> Iterator for GcsFilterMatches<'a, R, I> {
+    type Item = Result<usize, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            if let Err(e) = self.advance() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+        while let Some((i, p)) = self.sorted.next() {
+            while self.value.map_or(false, |data| data < p) {
+                if let Err(e) = self.advance() {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+            match self.value {
+                Some(data) if data == p => return Some(Ok(i)),
+                Some(_) => {}
+                None => break,
+            }
+        }
+        self.done = true;
+        None
+    }
 }
 
 /// Fast reduction of hash to [0, nm) range.
@@ -610,6 +847,13 @@ mod test {
             let test_filter = BlockFilter::new(filter_content.as_slice());
 
             assert_eq!(test_filter.content, filter.content);
+            assert_eq!(
+                BlockFilter::new_script_filter_from_block(&block, |o| txmap
+                    .get(o)
+                    .map(|s| s.as_script()))
+                .unwrap(),
+                filter
+            );
 
             let block_hash = &block.block_hash();
             assert!(filter
@@ -623,6 +867,12 @@ mod test {
                 )
                 .unwrap());
 
+            let scripts = txmap.values().filter(|s| !s.is_empty()).collect::<Vec<_>>();
+            let hits = filter
+                .match_any_with_hits(block_hash, scripts.iter().map(|s| s.as_bytes()))
+                .unwrap();
+            assert_eq!(hits, (0..scripts.len()).collect::<Vec<_>>());
+
             for script in txmap.values() {
                 let query = [script];
                 if !script.is_empty() {
@@ -636,6 +886,39 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_filter_from_block_missing_prevout() {
+        let data = include_str!("../tests/data/blockfilters.json");
+        let testdata = serde_json::from_str::<Value>(data).unwrap();
+        // The first test vector with an input other than the coinbase.
+        let block = testdata
+            .as_array()
+            .unwrap()
+            .iter()
+            .skip(1)
+            .map(|t| deserialize::<Block>(&hex!(t.get(2).unwrap().as_str().unwrap())).unwrap())
+            .find(|block| block.txdata.len() > 1)
+            .unwrap();
+        let outpoint = block.txdata[1].input[0].previous_output;
+
+        let err =
+            BlockFilter::new_script_filter_from_block(&block, |_| None::<ScriptBuf>).unwrap_err();
+        assert!(matches!(err, Error::UtxoMissing(o) if o == outpoint));
+    }
+
+    #[test]
+    fn test_filter_headers() {
+        let filters = [BlockFilter::new(&[0]), BlockFilter::new(&[1, 2]), BlockFilter::new(&[3])];
+        let genesis_previous = FilterHeader::all_zeros();
+
+        let headers = filter_headers(genesis_previous, &filters);
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers[0], filters[0].filter_header(&genesis_previous));
+        assert_eq!(headers[1], filters[1].filter_header(&headers[0]));
+        assert_eq!(headers[2], filters[2].filter_header(&headers[1]));
+        assert!(filter_headers(genesis_previous, &[]).is_empty());
+    }
+
     #[test]
     fn test_filter() {
         let mut patterns = BTreeSet::new();
@@ -703,6 +986,47 @@ mod test {
                 .match_all(&mut bytes.as_slice(), &mut query.iter().map(|v| v.as_slice()))
                 .unwrap());
         }
+        {
+            let reader = GcsFilterReader::new(0, 0, M, P);
+            let query = [hex!("abcdef"), hex!("eeeeee"), hex!("123456"), hex!("000000")];
+            let hits = reader
+                .match_any_with_hits(&mut bytes.as_slice(), query.iter().map(|v| v.as_slice()))
+                .unwrap();
+            assert_eq!(hits, vec![1, 3]);
+            let hits = reader
+                .match_any_with_hits(&mut bytes.as_slice(), core::iter::empty::<&[u8]>())
+                .unwrap();
+            assert!(hits.is_empty());
+        }
+        {
+            let reader = GcsFilterReader::new(0, 0, M, P);
+            let mut input = bytes.as_slice();
+            let values = reader.decode(&mut input);
+            let mut expected = patterns.iter().map(|p| values.map_to_range(p)).collect::<Vec<_>>();
+            expected.sort_unstable();
+            assert_eq!(values.collect::<Result<Vec<_>, _>>().unwrap(), expected);
+        }
+        {
+            let reader = GcsFilterReader::new(0, 0, M, P);
+            let mut input = bytes.as_slice();
+            let values = reader.decode(&mut input);
+            let miss = values.map_to_range(&hex!("abcdef"));
+            let mut sorted = [hex!("222222"), hex!("abcdef"), hex!("000000")]
+                .iter()
+                .map(|p| values.map_to_range(p))
+                .collect::<Vec<_>>();
+            sorted.sort_unstable();
+            let hits =
+                values.match_sorted(sorted.iter().copied()).collect::<Result<Vec<_>, _>>().unwrap();
+            let expected = (0..3).filter(|&i| sorted[i] != miss).collect::<Vec<_>>();
+            assert_eq!(hits, expected);
+
+            // A truncated filter fails once the missing values are needed.
+            let mut input = &bytes[..bytes.len() / 2];
+            let mut matches = reader.decode(&mut input).match_sorted(Some(u64::MAX));
+            assert!(matches!(matches.next(), Some(Err(_))));
+            assert!(matches.next().is_none());
+        }
     }
 
     #[test]
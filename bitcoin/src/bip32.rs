@@ -6,7 +6,7 @@
 //! at <https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki>.
 //!
 
-use core::ops::Index;
+use core::ops::{Index, Range};
 use core::str::FromStr;
 use core::{fmt, slice};
 
@@ -213,7 +213,7 @@ impl FromStr for ChildNumber {
     type Err = Error;
 
     fn from_str(inp: &str) -> Result<ChildNumber, Error> {
-        let is_hardened = inp.chars().last().map_or(false, |l| l == '\'' || l == 'h');
+        let is_hardened = inp.chars().last().map_or(false, |l| l == '\'' || l == 'h' || l == 'H');
         Ok(if is_hardened {
             ChildNumber::from_hardened_idx(
                 inp[0..inp.len() - 1].parse().map_err(|_| Error::InvalidChildNumberFormat)?,
@@ -441,17 +441,93 @@ impl DerivationPath {
     /// assert_eq!(path.to_u32_vec(), vec![84 + HARDENED, HARDENED, HARDENED, 0, 1]);
     /// ```
     pub fn to_u32_vec(&self) -> Vec<u32> { self.into_iter().map(|&el| el.into()).collect() }
+
+    /// Formats the derivation path using `marker` for hardened child numbers.
+    ///
+    /// Parsing accepts both markers, so this can be used to normalize paths exported by other
+    /// wallets. The [`Display`](fmt::Display) implementation uses `'`, or `h` when formatted
+    /// with the alternate flag (`{:#}`).
+    ///
+    /// ```
+    /// use bitcoin::bip32::{DerivationPath, HardenedMarker};
+    ///
+    /// let path = "84h/0'/0h/1/5".parse::<DerivationPath>().unwrap();
+    /// assert_eq!(path.to_string_with_marker(HardenedMarker::Apostrophe), "84'/0'/0'/1/5");
+    /// assert_eq!(path.to_string_with_marker(HardenedMarker::H), "84h/0h/0h/1/5");
+    /// ```
+    pub fn to_string_with_marker(&self, marker: HardenedMarker) -> String {
+        match marker {
+            HardenedMarker::Apostrophe => format!("{}", self),
+            HardenedMarker::H => format!("{:#}", self),
+        }
+    }
+
+    /// Returns true if this is a [BIP-44] (legacy P2PKH) path.
+    ///
+    /// See [`DerivationPath::is_bip86`] for the accepted path shapes.
+    ///
+    /// [BIP-44]: <https://github.com/bitcoin/bips/blob/master/bip-0044.mediawiki>
+    pub fn is_bip44(&self) -> bool { self.has_purpose(44) }
+
+    /// Returns true if this is a [BIP-49] (P2SH-P2WPKH) path.
+    ///
+    /// See [`DerivationPath::is_bip86`] for the accepted path shapes.
+    ///
+    /// [BIP-49]: <https://github.com/bitcoin/bips/blob/master/bip-0049.mediawiki>
+    pub fn is_bip49(&self) -> bool { self.has_purpose(49) }
+
+    /// Returns true if this is a [BIP-84] (P2WPKH) path.
+    ///
+    /// See [`DerivationPath::is_bip86`] for the accepted path shapes.
+    ///
+    /// [BIP-84]: <https://github.com/bitcoin/bips/blob/master/bip-0084.mediawiki>
+    pub fn is_bip84(&self) -> bool { self.has_purpose(84) }
+
+    /// Returns true if this is a [BIP-86] (P2TR key path) path.
+    ///
+    /// Accepts account level paths `purpose'/coin_type'/account'` as well as paths below them
+    /// down to `purpose'/coin_type'/account'/change/address_index`, where `change` is `0` or `1`
+    /// and the trailing steps are unhardened.
+    ///
+    /// [BIP-86]: <https://github.com/bitcoin/bips/blob/master/bip-0086.mediawiki>
+    pub fn is_bip86(&self) -> bool { self.has_purpose(86) }
+
+    /// Returns true if this path follows the BIP-44 structure with the given `purpose`.
+    fn has_purpose(&self, purpose: u32) -> bool {
+        if self.0.len() < 3 || self.0.len() > 5 {
+            return false;
+        }
+        let (account, rest) = self.0.split_at(3);
+        let account_level = account[0] == ChildNumber::Hardened { index: purpose }
+            && account[1].is_hardened()
+            && account[2].is_hardened();
+        let change_valid = match rest.first() {
+            Some(&ChildNumber::Normal { index }) => index <= 1,
+            Some(&ChildNumber::Hardened { .. }) => false,
+            None => true,
+        };
+        account_level && change_valid && rest.iter().all(ChildNumber::is_normal)
+    }
+}
+
+/// The marker used to denote hardened child numbers in a derivation path string.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HardenedMarker {
+    /// An apostrophe, e.g. `84'/0'/0'`.
+    Apostrophe,
+    /// A lowercase `h`, e.g. `84h/0h/0h`.
+    H,
 }
 
 impl fmt::Display for DerivationPath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut iter = self.0.iter();
         if let Some(first_element) = iter.next() {
-            write!(f, "{}", first_element)?;
+            fmt::Display::fmt(first_element, f)?;
         }
         for cn in iter {
             f.write_str("/")?;
-            write!(f, "{}", cn)?;
+            fmt::Display::fmt(cn, f)?;
         }
         Ok(())
     }
@@ -461,6 +537,154 @@ impl fmt::Debug for DerivationPath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self, f) }
 }
 
+/// A BIP-32 derivation path which may contain a multipath step, as specified in [BIP-389].
+///
+/// A multipath step such as `<0;1>` lists alternative child numbers, so that a single object can
+/// describe e.g. both the receive and the change path of a descriptor wallet. At most one
+/// multipath step is allowed. The path may end with a `*` wildcard step, which is not part of
+/// the expanded [`paths`](MultipathDerivationPath::paths).
+///
+/// When parsing, the steps are separated by `/` and may be preceded by an `m/` master prefix
+/// (or be just `m`), which is not written back by [`fmt::Display`].
+///
+/// ```
+/// use bitcoin::bip32::{DerivationPath, MultipathDerivationPath};
+///
+/// let multipath = "m/86'/0'/0'/<0;1>/*".parse::<MultipathDerivationPath>().unwrap();
+/// assert!(multipath.has_wildcard());
+/// assert_eq!(
+///     multipath.paths(),
+///     ["86'/0'/0'/0".parse::<DerivationPath>().unwrap(), "86'/0'/0'/1".parse().unwrap()]
+/// );
+/// ```
+///
+/// [BIP-389]: <https://github.com/bitcoin/bips/blob/master/bip-0389.mediawiki>
+#[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
+pub struct MultipathDerivationPath {
+    /// The expanded paths, differing in at most one step.
+    paths: Vec<DerivationPath>,
+    /// Whether the path ends with a `*` wildcard step.
+    wildcard: bool,
+}
+
+impl MultipathDerivationPath {
+    /// Returns the concrete paths this multipath expands to, in the listed order.
+    ///
+    /// A path without a multipath step expands to a single path.
+    pub fn paths(&self) -> &[DerivationPath] { &self.paths }
+
+    /// Consumes the multipath and returns the concrete paths it expands to.
+    pub fn into_paths(self) -> Vec<DerivationPath> { self.paths }
+
+    /// Returns true if this path contains a multipath step.
+    pub fn is_multipath(&self) -> bool { self.paths.len() > 1 }
+
+    /// Returns true if this path ends with a `*` wildcard step.
+    pub fn has_wildcard(&self) -> bool { self.wildcard }
+
+    /// Returns the index of the multipath step, if any.
+    fn multipath_index(&self) -> Option<usize> {
+        match self.paths[..] {
+            [ref first, ref second, ..] => first.0.iter().zip(&second.0).position(|(a, b)| a != b),
+            _ => None,
+        }
+    }
+}
+
+impl From<DerivationPath> for MultipathDerivationPath {
+    fn from(path: DerivationPath) -> Self {
+        MultipathDerivationPath { paths: vec![path], wildcard: false }
+    }
+}
+
+/// Converts to a [`DerivationPath`] if this is a single path without a wildcard.
+impl IntoDerivationPath for MultipathDerivationPath {
+    fn into_derivation_path(mut self) -> Result<DerivationPath, Error> {
+        if self.paths.len() > 1 {
+            return Err(Error::MultipleDerivationPaths(self.paths.len()));
+        }
+        if self.wildcard {
+            return Err(Error::InvalidDerivationPathFormat);
+        }
+        Ok(self.paths.pop().expect("always contains at least one path"))
+    }
+}
+
+impl FromStr for MultipathDerivationPath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<MultipathDerivationPath, Error> {
+        let (path, wildcard) = match path.strip_suffix('*') {
+            Some("") => ("", true),
+            Some(path) => (path.strip_suffix('/').ok_or(Error::InvalidDerivationPathFormat)?, true),
+            None => (path, false),
+        };
+        let path = match path.strip_prefix('m') {
+            Some("") => "",
+            Some(path) => path.strip_prefix('/').ok_or(Error::InvalidDerivationPathFormat)?,
+            None => path,
+        };
+
+        let mut prefix = vec![];
+        let mut alternatives: Option<Vec<ChildNumber>> = None;
+        let mut suffix = vec![];
+        for step in path.split('/').filter(|_| !path.is_empty()) {
+            match step.strip_prefix('<').and_then(|step| step.strip_suffix('>')) {
+                Some(step) => {
+                    if alternatives.is_some() {
+                        return Err(Error::InvalidDerivationPathFormat);
+                    }
+                    let children =
+                        step.split(';').map(str::parse).collect::<Result<Vec<_>, _>>()?;
+                    let unique = children.iter().collect::<BTreeSet<_>>();
+                    if children.len() < 2 || unique.len() != children.len() {
+                        return Err(Error::InvalidDerivationPathFormat);
+                    }
+                    alternatives = Some(children);
+                }
+                None if alternatives.is_some() => suffix.push(step.parse()?),
+                None => prefix.push(step.parse()?),
+            }
+        }
+
+        let paths = match alternatives {
+            Some(alternatives) => alternatives
+                .into_iter()
+                .map(|child| prefix.iter().chain(Some(&child)).chain(&suffix).copied().collect())
+                .collect(),
+            None => vec![DerivationPath(prefix)],
+        };
+        Ok(MultipathDerivationPath { paths, wildcard })
+    }
+}
+
+impl fmt::Display for MultipathDerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let index = self.multipath_index();
+        for (i, child) in self.paths[0].0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("/")?;
+            }
+            if Some(i) == index {
+                f.write_str("<")?;
+                for (j, path) in self.paths.iter().enumerate() {
+                    if j > 0 {
+                        f.write_str(";")?;
+                    }
+                    fmt::Display::fmt(&path.0[i], f)?;
+                }
+                f.write_str(">")?;
+            } else {
+                fmt::Display::fmt(child, f)?;
+            }
+        }
+        if self.wildcard {
+            f.write_str(if self.paths[0].is_empty() { "*" } else { "/*" })?;
+        }
+        Ok(())
+    }
+}
+
 /// Full information on the used extended public key: fingerprint of the
 /// master extended public key and a derivation path from it.
 pub type KeySource = (Fingerprint, DerivationPath);
@@ -489,6 +713,8 @@ pub enum Error {
     Hex(hex::HexToArrayError),
     /// `PublicKey` hex should be 66 or 130 digits long.
     InvalidPublicKeyHexLength(usize),
+    /// A multipath derivation path expands to more than one path.
+    MultipleDerivationPaths(usize),
 }
 
 internals::impl_from_infallible!(Error);
@@ -512,6 +738,8 @@ impl fmt::Display for Error {
             Hex(ref e) => write_err!(f, "Hexadecimal decoding error"; e),
             InvalidPublicKeyHexLength(got) =>
                 write!(f, "PublicKey hex should be 66 or 130 digits long, got: {}", got),
+            MultipleDerivationPaths(n) =>
+                write!(f, "multipath derivation path expands to {} paths, expected one", n),
         }
     }
 }
@@ -531,7 +759,8 @@ impl std::error::Error for Error {
             | InvalidDerivationPathFormat
             | UnknownVersion(_)
             | WrongExtendedKeyLength(_)
-            | InvalidPublicKeyHexLength(_) => None,
+            | InvalidPublicKeyHexLength(_)
+            | MultipleDerivationPaths(_) => None,
         }
     }
 }
@@ -588,6 +817,31 @@ impl Xpriv {
         Ok(sk)
     }
 
+    /// Derives the private keys of the unhardened children in `range` below `path_prefix`.
+    ///
+    /// The key at `path_prefix` is derived once and reused for every child, which makes this
+    /// considerably cheaper than calling [`Xpriv::derive_priv`] for each index when scanning
+    /// many addresses. As specified by BIP-32, indices yielding an invalid key (with negligible
+    /// probability) are skipped.
+    ///
+    /// Returns an error if `path_prefix` can not be derived or `range` contains hardened indices.
+    pub fn derive_range<'a, C: secp256k1::Signing, P: AsRef<[ChildNumber]>>(
+        &self,
+        secp: &'a Secp256k1<C>,
+        path_prefix: &P,
+        range: Range<u32>,
+    ) -> Result<impl Iterator<Item = (ChildNumber, PrivateKey)> + 'a, Error> {
+        check_normal_range(&range)?;
+        let parent = self.derive_priv(secp, path_prefix)?;
+        let parent_pub = Xpub::from_priv(secp, &parent);
+        Ok(range.filter_map(move |index| {
+            let child = ChildNumber::Normal { index };
+            let (tweak, _) = parent_pub.ckd_pub_tweak(child).ok()?;
+            let key = tweak.add_tweak(&parent.private_key.into()).ok()?;
+            Some((child, PrivateKey::new(key, parent.network)))
+        }))
+    }
+
     /// Private->Private child key derivation
     fn ckd_priv<C: secp256k1::Signing>(
         &self,
@@ -758,6 +1012,30 @@ impl Xpub {
         })
     }
 
+    /// Derives the public keys of the children in `range` below `path_prefix`.
+    ///
+    /// The key at `path_prefix` is derived once and reused for every child, which makes this
+    /// considerably cheaper than calling [`Xpub::derive_pub`] for each index when scanning
+    /// a gap limit worth of addresses. As specified by BIP-32, indices yielding an invalid key
+    /// (with negligible probability) are skipped.
+    ///
+    /// Returns an error if `path_prefix` can not be derived or `range` contains hardened indices.
+    pub fn derive_range<'a, C: secp256k1::Verification, P: AsRef<[ChildNumber]>>(
+        &self,
+        secp: &'a Secp256k1<C>,
+        path_prefix: &P,
+        range: Range<u32>,
+    ) -> Result<impl Iterator<Item = (ChildNumber, CompressedPublicKey)> + 'a, Error> {
+        check_normal_range(&range)?;
+        let parent = self.derive_pub(secp, path_prefix)?;
+        Ok(range.filter_map(move |index| {
+            let child = ChildNumber::Normal { index };
+            let (tweak, _) = parent.ckd_pub_tweak(child).ok()?;
+            let key = parent.public_key.add_exp_tweak(secp, &tweak.into()).ok()?;
+            Some((child, CompressedPublicKey(key)))
+        }))
+    }
+
     /// Decoding extended public key from binary data according to BIP 32
     pub fn decode(data: &[u8]) -> Result<Xpub, Error> {
         if data.len() != 78 {
@@ -815,6 +1093,14 @@ impl Xpub {
     }
 }
 
+/// Returns an error if `range` contains hardened child indices.
+fn check_normal_range(range: &Range<u32>) -> Result<(), Error> {
+    if range.end > 1 << 31 {
+        return Err(Error::InvalidChildNumber(range.end - 1));
+    }
+    Ok(())
+}
+
 impl fmt::Display for Xpriv {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         base58::encode_check_to_fmt(fmt, &self.encode()[..])
@@ -931,6 +1217,99 @@ mod tests {
         assert_eq!(DerivationPath::from_str(s), s.to_string().into_derivation_path());
     }
 
+    #[test]
+    fn test_derivation_path_markers_and_purpose() {
+        let path = DerivationPath::from_str("86H/1'/0h/1/7").unwrap();
+        assert_eq!(path, DerivationPath::from_str("86'/1'/0'/1/7").unwrap());
+        assert_eq!(path.to_string_with_marker(HardenedMarker::Apostrophe), "86'/1'/0'/1/7");
+        assert_eq!(path.to_string_with_marker(HardenedMarker::H), "86h/1h/0h/1/7");
+        assert_eq!(format!("{:#}", path), "86h/1h/0h/1/7");
+
+        assert!(path.is_bip86());
+        assert!(!path.is_bip84());
+        assert!(DerivationPath::from_str("44'/0'/0'").unwrap().is_bip44());
+        assert!(DerivationPath::from_str("49'/0'/3'/0").unwrap().is_bip49());
+        assert!(DerivationPath::from_str("84'/0'/0'/0/0").unwrap().is_bip84());
+
+        let invalid = ["84'/0'", "84/0'/0'", "84'/0'/0", "84'/0'/0'/2/0", "84'/0'/0'/0/0'"];
+        for path in invalid {
+            assert!(!DerivationPath::from_str(path).unwrap().is_bip84(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_parse_multipath_derivation_path() {
+        let multipath = MultipathDerivationPath::from_str("86'/0'/0'/<0;1>/*").unwrap();
+        assert!(multipath.is_multipath());
+        assert!(multipath.has_wildcard());
+        assert_eq!(
+            multipath.paths(),
+            [
+                DerivationPath::from_str("86'/0'/0'/0").unwrap(),
+                DerivationPath::from_str("86'/0'/0'/1").unwrap(),
+            ]
+        );
+        assert_eq!(multipath.to_string(), "86'/0'/0'/<0;1>/*");
+        assert_eq!(MultipathDerivationPath::from_str("m/86'/0'/0'/<0;1>/*"), Ok(multipath.clone()));
+        assert_eq!(multipath.into_derivation_path(), Err(Error::MultipleDerivationPaths(2)));
+
+        let multipath = MultipathDerivationPath::from_str("<1';2h;0'>/7").unwrap();
+        assert_eq!(multipath.paths().len(), 3);
+        assert_eq!(multipath.paths()[1], DerivationPath::from_str("2'/7").unwrap());
+        assert_eq!(multipath.to_string(), "<1';2';0'>/7");
+
+        let single = MultipathDerivationPath::from_str("0'/1").unwrap();
+        assert!(!single.is_multipath());
+        assert_eq!(single.to_string(), "0'/1");
+        assert_eq!(single.into_derivation_path(), DerivationPath::from_str("0'/1"));
+        assert_eq!(MultipathDerivationPath::from_str("*").unwrap().to_string(), "*");
+        assert_eq!(MultipathDerivationPath::from_str("m/*").unwrap().to_string(), "*");
+        assert_eq!(
+            MultipathDerivationPath::from_str("m"),
+            Ok(MultipathDerivationPath::from(DerivationPath::master()))
+        );
+        assert!(MultipathDerivationPath::from_str("")
+            .unwrap()
+            .into_derivation_path()
+            .unwrap()
+            .is_master());
+
+        let invalid =
+            ["<0;1>/<2;3>", "<0>", "<0;0>", "0/<0;1", "0*", "*/0", "<0;x>", "m0", "m*", "m/m"];
+        for invalid in invalid {
+            assert!(MultipathDerivationPath::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_derive_range() {
+        let secp = Secp256k1::new();
+        let xpriv = Xpriv::new_master(NetworkKind::Main, &[7; 32]).unwrap();
+        let xpub = Xpub::from_priv(&secp, &xpriv);
+        let prefix = DerivationPath::from_str("0/1").unwrap();
+
+        let pubkeys = xpub.derive_range(&secp, &prefix, 5..25).unwrap().collect::<Vec<_>>();
+        let privkeys = xpriv.derive_range(&secp, &prefix, 5..25).unwrap().collect::<Vec<_>>();
+        assert_eq!(pubkeys.len(), 20);
+        for ((child, pubkey), (priv_child, privkey)) in pubkeys.iter().zip(&privkeys) {
+            let path = prefix.child(*child);
+            assert_eq!(*pubkey, xpub.derive_pub(&secp, &path).unwrap().to_pub());
+            assert_eq!(child, priv_child);
+            assert_eq!(privkey.inner, xpriv.derive_priv(&secp, &path).unwrap().private_key);
+        }
+
+        let hardened = DerivationPath::from_str("0'").unwrap();
+        assert!(matches!(
+            xpub.derive_range(&secp, &hardened, 0..10),
+            Err(Error::CannotDeriveFromHardenedKey)
+        ));
+        assert!(matches!(
+            xpub.derive_range(&secp, &prefix, 0..(1 << 31) + 1),
+            Err(Error::InvalidChildNumber(0x8000_0000))
+        ));
+        assert_eq!(xpriv.derive_range(&secp, &hardened, 0..3).unwrap().count(), 3);
+    }
+
     #[test]
     fn test_derivation_path_conversion_index() {
         let path = DerivationPath::from_str("0h/1/2'").unwrap();
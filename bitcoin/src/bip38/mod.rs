@@ -0,0 +1,416 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP 38 passphrase-protected private keys.
+//!
+//! Implementation of encrypted private keys (`6P...` strings) as found on paper wallets. Both
+//! encryption modes are recognized: keys encrypted directly from a private key ("non-EC-multiply")
+//! and keys generated by a third party from an intermediate code ("EC-multiply"). Encryption is
+//! only supported for the former since the latter requires the intermediate code workflow.
+//!
+//! Passphrases are used as given, callers must normalize them to Unicode NFC as required by the
+//! BIP before passing them in.
+//!
+//! ### Relevant BIPS
+//!
+//! * [BIP 38 - Passphrase-protected private key](https://github.com/bitcoin/bips/blob/master/bip-0038.mediawiki)
+
+use core::fmt;
+use core::str::FromStr;
+
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::Aes256;
+use hashes::{sha256d, Hash};
+use internals::write_err;
+use secp256k1::{Secp256k1, Signing};
+
+use crate::address::Address;
+use crate::crypto::key::PrivateKey;
+use crate::network::NetworkKind;
+use crate::prelude::*;
+
+/// Length of a decoded encrypted private key.
+const ENCODED_LEN: usize = 39;
+
+/// Prefix of keys encrypted without EC multiplication.
+const PREFIX_NON_EC: [u8; 2] = [0x01, 0x42];
+/// Prefix of keys encrypted with EC multiplication.
+const PREFIX_EC: [u8; 2] = [0x01, 0x43];
+
+/// Flag bits that must be set for keys encrypted without EC multiplication.
+const FLAG_NON_EC: u8 = 0xC0;
+/// Flag bit set if the public key is compressed.
+const FLAG_COMPRESSED: u8 = 0x20;
+/// Flag bit set if the owner entropy contains lot and sequence numbers (EC multiplication only).
+const FLAG_LOT_SEQUENCE: u8 = 0x04;
+
+/// A BIP 38 encrypted private key.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EncryptedPrivateKey([u8; ENCODED_LEN]);
+
+impl EncryptedPrivateKey {
+    /// Encrypts `key` with `passphrase` without EC multiplication.
+    ///
+    /// The encryption is bound to the P2PKH address of `key`, compressed or not depending on
+    /// `key.compressed`.
+    pub fn encrypt<C: Signing>(
+        secp: &Secp256k1<C>,
+        key: &PrivateKey,
+        passphrase: &str,
+    ) -> EncryptedPrivateKey {
+        let address_hash = address_hash(secp, key);
+        let mut derived = [0u8; 64];
+        scrypt(passphrase.as_bytes(), &address_hash, 14, 8, 8, &mut derived);
+        let (half1, half2) = derived.split_at(32);
+        let aes = Aes256::new_from_slice(half2).expect("32 bytes");
+
+        let secret = key.inner.secret_bytes();
+        let mut data = [0u8; ENCODED_LEN];
+        data[..2].copy_from_slice(&PREFIX_NON_EC);
+        data[2] = if key.compressed { FLAG_NON_EC | FLAG_COMPRESSED } else { FLAG_NON_EC };
+        data[3..7].copy_from_slice(&address_hash);
+        for i in 0..2 {
+            let block = xor_block(&secret[16 * i..16 * i + 16], &half1[16 * i..16 * i + 16]);
+            data[7 + 16 * i..23 + 16 * i].copy_from_slice(&encrypt_block(&aes, block));
+        }
+        EncryptedPrivateKey(data)
+    }
+
+    /// Decrypts this key with `passphrase`, the returned key is marked for use on `network`.
+    ///
+    /// The passphrase is checked against the address hash embedded in the encrypted key, so this
+    /// fails with [`Error::WrongPassphrase`] rather than returning a wrong key. Since the address
+    /// hash commits to the network the key was encrypted for, decrypting a key encrypted for the
+    /// other kind of network fails with [`Error::NetworkMismatch`].
+    pub fn decrypt<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        passphrase: &str,
+        network: impl Into<NetworkKind>,
+    ) -> Result<PrivateKey, Error> {
+        let network = network.into();
+        let secret = if self.is_ec_multiplied() {
+            self.decrypt_ec_multiplied(secp, passphrase)?
+        } else {
+            self.decrypt_non_ec_multiplied(passphrase)?
+        };
+
+        let key = PrivateKey { compressed: self.is_compressed(), network, inner: secret };
+        if address_hash(secp, &key) == self.address_hash() {
+            return Ok(key);
+        }
+        let other = match network {
+            NetworkKind::Main => NetworkKind::Test,
+            NetworkKind::Test => NetworkKind::Main,
+        };
+        if address_hash(secp, &PrivateKey { network: other, ..key }) == self.address_hash() {
+            return Err(Error::NetworkMismatch(other));
+        }
+        Err(Error::WrongPassphrase)
+    }
+
+    /// Returns true if this key was generated using EC multiplication.
+    pub fn is_ec_multiplied(&self) -> bool { self.0[..2] == PREFIX_EC }
+
+    /// Returns true if the decrypted key is for a compressed public key.
+    pub fn is_compressed(&self) -> bool { self.flags() & FLAG_COMPRESSED != 0 }
+
+    /// Returns true if the owner entropy of an EC-multiplied key contains lot and sequence numbers.
+    pub fn has_lot_sequence(&self) -> bool {
+        self.is_ec_multiplied() && self.flags() & FLAG_LOT_SEQUENCE != 0
+    }
+
+    /// Returns the raw 39 bytes of this key, as encoded in base58.
+    pub fn as_bytes(&self) -> &[u8; ENCODED_LEN] { &self.0 }
+
+    /// Parses an encrypted key from its raw 39 bytes.
+    pub fn from_slice(data: &[u8]) -> Result<EncryptedPrivateKey, Error> {
+        let data: [u8; ENCODED_LEN] =
+            data.try_into().map_err(|_| Error::InvalidLength(data.len()))?;
+
+        let flags = data[2];
+        match [data[0], data[1]] {
+            PREFIX_NON_EC =>
+                if flags & !FLAG_COMPRESSED != FLAG_NON_EC {
+                    return Err(Error::InvalidFlags(flags));
+                },
+            PREFIX_EC =>
+                if flags & !(FLAG_COMPRESSED | FLAG_LOT_SEQUENCE) != 0 {
+                    return Err(Error::InvalidFlags(flags));
+                },
+            prefix => return Err(Error::InvalidPrefix(prefix)),
+        }
+        Ok(EncryptedPrivateKey(data))
+    }
+
+    fn flags(&self) -> u8 { self.0[2] }
+
+    fn address_hash(&self) -> [u8; 4] { [self.0[3], self.0[4], self.0[5], self.0[6]] }
+
+    fn decrypt_non_ec_multiplied(&self, passphrase: &str) -> Result<secp256k1::SecretKey, Error> {
+        let mut derived = [0u8; 64];
+        scrypt(passphrase.as_bytes(), &self.address_hash(), 14, 8, 8, &mut derived);
+        let (half1, half2) = derived.split_at(32);
+        let aes = Aes256::new_from_slice(half2).expect("32 bytes");
+
+        let mut secret = [0u8; 32];
+        for i in 0..2 {
+            let block = self.0[7 + 16 * i..23 + 16 * i].try_into().expect("16 bytes");
+            let decrypted = decrypt_block(&aes, block);
+            secret[16 * i..16 * i + 16]
+                .copy_from_slice(&xor_block(&decrypted, &half1[16 * i..16 * i + 16]));
+        }
+        secp256k1::SecretKey::from_slice(&secret).map_err(|_| Error::WrongPassphrase)
+    }
+
+    fn decrypt_ec_multiplied<C: Signing>(
+        &self,
+        secp: &Secp256k1<C>,
+        passphrase: &str,
+    ) -> Result<secp256k1::SecretKey, Error> {
+        let owner_entropy = &self.0[7..15];
+        // With lot and sequence numbers only the first four bytes are salt.
+        let owner_salt = if self.has_lot_sequence() { &owner_entropy[..4] } else { owner_entropy };
+
+        let mut prefactor = [0u8; 32];
+        scrypt(passphrase.as_bytes(), owner_salt, 14, 8, 8, &mut prefactor);
+        let pass_factor = if self.has_lot_sequence() {
+            let mut data = prefactor.to_vec();
+            data.extend_from_slice(owner_entropy);
+            sha256d::Hash::hash(&data).to_byte_array()
+        } else {
+            prefactor
+        };
+        let pass_factor =
+            secp256k1::SecretKey::from_slice(&pass_factor).map_err(|_| Error::WrongPassphrase)?;
+        let pass_point = pass_factor.public_key(secp).serialize();
+
+        let mut salt = [0u8; 12];
+        salt[..4].copy_from_slice(&self.address_hash());
+        salt[4..].copy_from_slice(owner_entropy);
+        let mut derived = [0u8; 64];
+        scrypt(&pass_point, &salt, 10, 1, 1, &mut derived);
+        let (half1, half2) = derived.split_at(32);
+        let aes = Aes256::new_from_slice(half2).expect("32 bytes");
+
+        // The second encrypted part holds the end of the first one and the end of `seedb`.
+        let part2 = self.0[23..39].try_into().expect("16 bytes");
+        let part2 = xor_block(&decrypt_block(&aes, part2), &half1[16..32]);
+        let mut part1 = [0u8; 16];
+        part1[..8].copy_from_slice(&self.0[15..23]);
+        part1[8..].copy_from_slice(&part2[..8]);
+        let part1 = xor_block(&decrypt_block(&aes, part1), &half1[..16]);
+
+        let mut seed_b = [0u8; 24];
+        seed_b[..16].copy_from_slice(&part1);
+        seed_b[16..].copy_from_slice(&part2[8..]);
+        let factor_b = sha256d::Hash::hash(&seed_b).to_byte_array();
+        let factor_b =
+            secp256k1::Scalar::from_be_bytes(factor_b).map_err(|_| Error::WrongPassphrase)?;
+        pass_factor.mul_tweak(&factor_b).map_err(|_| Error::WrongPassphrase)
+    }
+}
+
+/// Returns the first four bytes of the double SHA256 of the P2PKH address of `key`.
+fn address_hash<C: Signing>(secp: &Secp256k1<C>, key: &PrivateKey) -> [u8; 4] {
+    let public_key = key.public_key(secp);
+    let address = Address::p2pkh(public_key, key.network).to_string();
+    let hash = sha256d::Hash::hash(address.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// Runs scrypt with cost parameter `2^log_n`, the parameters used by BIP 38 are all valid.
+fn scrypt(password: &[u8], salt: &[u8], log_n: u8, r: u32, p: u32, output: &mut [u8]) {
+    let params = scrypt::Params::new(log_n, r, p).expect("valid scrypt parameters");
+    scrypt::scrypt(password, salt, &params, output).expect("valid output length");
+}
+
+fn encrypt_block(aes: &Aes256, block: [u8; 16]) -> [u8; 16] {
+    let mut block = block.into();
+    aes.encrypt_block(&mut block);
+    block.into()
+}
+
+fn decrypt_block(aes: &Aes256, block: [u8; 16]) -> [u8; 16] {
+    let mut block = block.into();
+    aes.decrypt_block(&mut block);
+    block.into()
+}
+
+fn xor_block(a: &[u8], b: &[u8]) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    for (out, (a, b)) in out.iter_mut().zip(a.iter().zip(b)) {
+        *out = a ^ b;
+    }
+    out
+}
+
+impl fmt::Debug for EncryptedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("EncryptedPrivateKey").field(&format_args!("{}", self)).finish()
+    }
+}
+
+impl fmt::Display for EncryptedPrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { base58::encode_check_to_fmt(f, &self.0) }
+}
+
+impl FromStr for EncryptedPrivateKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<EncryptedPrivateKey, Error> {
+        EncryptedPrivateKey::from_slice(&base58::decode_check(s)?)
+    }
+}
+
+/// BIP 38 encryption and decryption error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Base58 decoding error.
+    Base58(base58::Error),
+    /// The decoded data does not have the expected length of 39 bytes.
+    InvalidLength(usize),
+    /// The two byte prefix is neither of the BIP 38 prefixes.
+    InvalidPrefix([u8; 2]),
+    /// Reserved flag bits are set.
+    InvalidFlags(u8),
+    /// The passphrase does not match the encrypted key.
+    WrongPassphrase,
+    /// The key was encrypted for the contained kind of network, not the requested one.
+    NetworkMismatch(NetworkKind),
+}
+
+internals::impl_from_infallible!(Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            Base58(ref e) => write_err!(f, "base58 encoding error"; e),
+            InvalidLength(len) => write!(f, "invalid encrypted key length {} (expected 39)", len),
+            InvalidPrefix(ref prefix) => write!(f, "invalid encrypted key prefix {:02x?}", prefix),
+            InvalidFlags(flags) => write!(f, "invalid encrypted key flag byte {:#04x}", flags),
+            WrongPassphrase => f.write_str("wrong passphrase for encrypted key"),
+            NetworkMismatch(network) =>
+                write!(f, "encrypted key is for network kind {:?}", network),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            Base58(ref e) => Some(e),
+            InvalidLength(_) | InvalidPrefix(_) | InvalidFlags(_) | WrongPassphrase
+            | NetworkMismatch(_) => None,
+        }
+    }
+}
+
+impl From<base58::Error> for Error {
+    fn from(e: base58::Error) -> Self { Self::Base58(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vectors from BIP 38.
+
+    fn check_non_ec(encrypted: &str, passphrase: &str, wif: &str) {
+        let secp = Secp256k1::signing_only();
+        let key = PrivateKey::from_wif(wif).unwrap();
+        let encrypted = EncryptedPrivateKey::from_str(encrypted).unwrap();
+        assert!(!encrypted.is_ec_multiplied());
+        assert_eq!(encrypted.is_compressed(), key.compressed);
+        assert_eq!(encrypted.decrypt(&secp, passphrase, NetworkKind::Main).unwrap(), key);
+        assert_eq!(EncryptedPrivateKey::encrypt(&secp, &key, passphrase), encrypted);
+    }
+
+    #[test]
+    fn non_ec_multiplied_uncompressed() {
+        check_non_ec(
+            "6PRVWUbkzzsbcVac2qwfssoUJAN1Xhrg6bNk8J7Nzm5H7kxEbn2Nh2ZoGg",
+            "TestingOneTwoThree",
+            "5KN7MzqK5wt2TP1fQCYyHBtDrXdJuXbUzm4A9rKAteGu3Qi5CVR",
+        );
+    }
+
+    #[test]
+    fn non_ec_multiplied_compressed() {
+        check_non_ec(
+            "6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo",
+            "TestingOneTwoThree",
+            "L44B5gGEpqEDRS9vVPz7QT35jcBG2r3CZwSwQ4fCewXAhAhqGVpP",
+        );
+    }
+
+    #[test]
+    fn ec_multiplied() {
+        let secp = Secp256k1::signing_only();
+        let encrypted = EncryptedPrivateKey::from_str(
+            "6PfQu77ygVyJLZjfvMLyhLMQbYnu5uguoJJ4kMCLqWwPEdfpwANVS76gTX",
+        )
+        .unwrap();
+        assert!(encrypted.is_ec_multiplied());
+        assert!(!encrypted.is_compressed());
+        assert!(!encrypted.has_lot_sequence());
+
+        let key = encrypted.decrypt(&secp, "TestingOneTwoThree", NetworkKind::Main).unwrap();
+        assert_eq!(key.to_wif(), "5K4caxezwjGCGfnoPTZ8tMcJBLB7Jvyjv4xxeacadhq8nLisLR2");
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let secp = Secp256k1::signing_only();
+        let encrypted = EncryptedPrivateKey::from_str(
+            "6PRVWUbkzzsbcVac2qwfssoUJAN1Xhrg6bNk8J7Nzm5H7kxEbn2Nh2ZoGg",
+        )
+        .unwrap();
+        assert_eq!(
+            encrypted.decrypt(&secp, "TestingOneTwoFour", NetworkKind::Main),
+            Err(Error::WrongPassphrase)
+        );
+    }
+
+    #[test]
+    fn network_mismatch() {
+        let secp = Secp256k1::signing_only();
+        let encrypted = EncryptedPrivateKey::from_str(
+            "6PYNKZ1EAgYgmQfmNVamxyXVWHzK5s6DGhwP4J5o44cvXdoY7sRzhtpUeo",
+        )
+        .unwrap();
+        assert_eq!(
+            encrypted.decrypt(&secp, "TestingOneTwoThree", NetworkKind::Test),
+            Err(Error::NetworkMismatch(NetworkKind::Main))
+        );
+
+        let key =
+            PrivateKey::from_wif("cVt4o7BGAig1UXywgGSmARhxMdzP5qvQsxKkSsc1XEkw3tDTQFpy").unwrap();
+        let encrypted = EncryptedPrivateKey::encrypt(&secp, &key, "TestingOneTwoThree");
+        assert_eq!(encrypted.decrypt(&secp, "TestingOneTwoThree", NetworkKind::Test).unwrap(), key);
+        assert_eq!(
+            encrypted.decrypt(&secp, "TestingOneTwoThree", NetworkKind::Main),
+            Err(Error::NetworkMismatch(NetworkKind::Test))
+        );
+    }
+
+    #[test]
+    fn invalid_encoding() {
+        let mut data = *EncryptedPrivateKey::from_str(
+            "6PRVWUbkzzsbcVac2qwfssoUJAN1Xhrg6bNk8J7Nzm5H7kxEbn2Nh2ZoGg",
+        )
+        .unwrap()
+        .as_bytes();
+
+        assert_eq!(EncryptedPrivateKey::from_slice(&data[..38]), Err(Error::InvalidLength(38)));
+        data[2] = 0xC1;
+        assert_eq!(EncryptedPrivateKey::from_slice(&data), Err(Error::InvalidFlags(0xC1)));
+        data[1] = 0x44;
+        assert_eq!(EncryptedPrivateKey::from_slice(&data), Err(Error::InvalidPrefix([0x01, 0x44])));
+    }
+}
@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP 85 Deterministic Entropy From BIP32 Keychains.
+//!
+//! Derives entropy for child wallets (mnemonics, WIF keys, extended private keys, raw bytes)
+//! from a single master [`Xpriv`], so that one backup covers all of them.
+//!
+//! Entropy is derived by taking the private key at `m/83696968'/{app}'/...` and computing
+//! `HMAC-SHA512(key = "bip-entropy-from-k", msg = private_key)`.
+//!
+//! ### Relevant BIPS
+//!
+//! * [BIP 85 - Deterministic Entropy From BIP32 Keychains](https://github.com/bitcoin/bips/blob/master/bip-0085.mediawiki)
+
+use core::fmt;
+
+use hashes::{sha512, Hash, HashEngine, Hmac, HmacEngine};
+use internals::write_err;
+use secp256k1::{Secp256k1, Signing};
+
+use crate::bip32::{self, ChainCode, ChildNumber, DerivationPath, Fingerprint, Xpriv};
+use crate::crypto::key::PrivateKey;
+use crate::prelude::*;
+
+/// The purpose child number of all BIP 85 derivation paths.
+const PURPOSE: u32 = 83696968;
+
+/// Application number for BIP 39 mnemonic entropy.
+const APP_BIP39: u32 = 39;
+/// Application number for WIF encoded private keys.
+const APP_WIF: u32 = 2;
+/// Application number for extended private keys.
+const APP_XPRV: u32 = 32;
+/// Application number for raw hex entropy.
+const APP_HEX: u32 = 128169;
+
+/// The BIP 39 wordlist language, used as part of the derivation path.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Language {
+    /// English.
+    English,
+    /// Japanese.
+    Japanese,
+    /// Korean.
+    Korean,
+    /// Spanish.
+    Spanish,
+    /// Chinese (simplified).
+    ChineseSimplified,
+    /// Chinese (traditional).
+    ChineseTraditional,
+    /// French.
+    French,
+    /// Italian.
+    Italian,
+    /// Czech.
+    Czech,
+}
+
+impl Language {
+    /// Returns the language code used in the derivation path.
+    pub fn to_code(self) -> u32 {
+        match self {
+            Language::English => 0,
+            Language::Japanese => 1,
+            Language::Korean => 2,
+            Language::Spanish => 3,
+            Language::ChineseSimplified => 4,
+            Language::ChineseTraditional => 5,
+            Language::French => 6,
+            Language::Italian => 7,
+            Language::Czech => 8,
+        }
+    }
+}
+
+/// Derives the 64 bytes of entropy for the hardened BIP 85 application `path`.
+///
+/// `path` is the part following the purpose, e.g. `[2, 0]` for the first WIF key.
+pub fn derive_entropy<C: Signing>(
+    secp: &Secp256k1<C>,
+    root: &Xpriv,
+    path: &[u32],
+) -> Result<[u8; 64], Error> {
+    let path = core::iter::once(PURPOSE)
+        .chain(path.iter().copied())
+        .map(ChildNumber::from_hardened_idx)
+        .collect::<Result<DerivationPath, _>>()?;
+    let derived = root.derive_priv(secp, &path)?;
+
+    let mut engine = HmacEngine::<sha512::Hash>::new(b"bip-entropy-from-k");
+    engine.input(&derived.private_key.secret_bytes());
+    Ok(Hmac::<sha512::Hash>::from_engine(engine).to_byte_array())
+}
+
+/// Derives the entropy for a BIP 39 mnemonic of `words` words.
+///
+/// `words` must be 12, 18 or 24; the returned entropy is 16, 24 or 32 bytes long respectively
+/// and can be converted into a mnemonic with any BIP 39 implementation.
+pub fn derive_mnemonic_entropy<C: Signing>(
+    secp: &Secp256k1<C>,
+    root: &Xpriv,
+    language: Language,
+    words: u32,
+    index: u32,
+) -> Result<Vec<u8>, Error> {
+    let len = match words {
+        12 => 16,
+        18 => 24,
+        24 => 32,
+        _ => return Err(Error::InvalidWordCount(words)),
+    };
+    let path = [APP_BIP39, language.to_code(), words, index];
+    let entropy = derive_entropy(secp, root, &path)?;
+    Ok(entropy[..len].to_vec())
+}
+
+/// Derives a private key for use in a WIF encoded HD-seed, e.g. for Bitcoin Core.
+pub fn derive_wif<C: Signing>(
+    secp: &Secp256k1<C>,
+    root: &Xpriv,
+    index: u32,
+) -> Result<PrivateKey, Error> {
+    let entropy = derive_entropy(secp, root, &[APP_WIF, index])?;
+    let key = PrivateKey::from_slice(&entropy[..32], root.network)?;
+    Ok(key)
+}
+
+/// Derives a master extended private key.
+pub fn derive_xpriv<C: Signing>(
+    secp: &Secp256k1<C>,
+    root: &Xpriv,
+    index: u32,
+) -> Result<Xpriv, Error> {
+    let entropy = derive_entropy(secp, root, &[APP_XPRV, index])?;
+    let chain_code: [u8; 32] = entropy[..32].try_into().expect("32 bytes");
+    Ok(Xpriv {
+        network: root.network,
+        depth: 0,
+        parent_fingerprint: Fingerprint::default(),
+        child_number: ChildNumber::Normal { index: 0 },
+        private_key: secp256k1::SecretKey::from_slice(&entropy[32..])?,
+        chain_code: ChainCode::from(chain_code),
+    })
+}
+
+/// Derives `num_bytes` bytes of raw entropy, `num_bytes` must be between 16 and 64.
+pub fn derive_hex<C: Signing>(
+    secp: &Secp256k1<C>,
+    root: &Xpriv,
+    num_bytes: u32,
+    index: u32,
+) -> Result<Vec<u8>, Error> {
+    if !(16..=64).contains(&num_bytes) {
+        return Err(Error::InvalidByteCount(num_bytes));
+    }
+    let entropy = derive_entropy(secp, root, &[APP_HEX, num_bytes, index])?;
+    Ok(entropy[..num_bytes as usize].to_vec())
+}
+
+/// BIP 85 entropy derivation error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Key derivation failed or a path component is out of range.
+    Bip32(bip32::Error),
+    /// The derived entropy is not a valid private key (negligible probability).
+    Secp256k1(secp256k1::Error),
+    /// The number of mnemonic words is not 12, 18 or 24.
+    InvalidWordCount(u32),
+    /// The number of entropy bytes is not between 16 and 64.
+    InvalidByteCount(u32),
+}
+
+internals::impl_from_infallible!(Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            Bip32(ref e) => write_err!(f, "key derivation error"; e),
+            Secp256k1(ref e) => write_err!(f, "derived entropy is not a valid private key"; e),
+            InvalidWordCount(n) => write!(f, "invalid mnemonic word count {}", n),
+            InvalidByteCount(n) => write!(f, "invalid number of entropy bytes {}", n),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            Bip32(ref e) => Some(e),
+            Secp256k1(ref e) => Some(e),
+            InvalidWordCount(_) | InvalidByteCount(_) => None,
+        }
+    }
+}
+
+impl From<bip32::Error> for Error {
+    fn from(e: bip32::Error) -> Self { Self::Bip32(e) }
+}
+
+impl From<secp256k1::Error> for Error {
+    fn from(e: secp256k1::Error) -> Self { Self::Secp256k1(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use hex::test_hex_unwrap as hex;
+
+    use super::*;
+
+    // Test vectors from BIP 85.
+    const ROOT: &str = "xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb";
+
+    #[test]
+    fn entropy() {
+        let secp = Secp256k1::signing_only();
+        let root = Xpriv::from_str(ROOT).unwrap();
+
+        let entropy = derive_entropy(&secp, &root, &[0, 0]).unwrap();
+        assert_eq!(entropy.to_vec(), hex!("efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f00b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7"));
+
+        let entropy = derive_entropy(&secp, &root, &[0, 1]).unwrap();
+        assert_eq!(entropy.to_vec(), hex!("70c6e3e8ebee8dc4c0dbba66076819bb8c09672527c4277ca8729532ad711872218f826919f6b67218adde99018a6df9095ab2b58d803b5b93ec9802085a690e"));
+    }
+
+    #[test]
+    fn applications() {
+        let secp = Secp256k1::signing_only();
+        let root = Xpriv::from_str(ROOT).unwrap();
+
+        let wif = derive_wif(&secp, &root, 0).unwrap();
+        assert_eq!(wif.to_wif(), "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp");
+
+        let xpriv = derive_xpriv(&secp, &root, 0).unwrap();
+        assert_eq!(xpriv.to_string(), "xprv9s21ZrQH143K2srSbCSg4m4kLvPMzcWydgmKEnMmoZUurYuBuYG46c6P71UGXMzmriLzCCBvKQWBUv3vPB3m1SATMhp3uEjXHJ42jFg7myX");
+
+        let bytes = derive_hex(&secp, &root, 64, 0).unwrap();
+        assert_eq!(bytes, hex!("492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f878555d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"));
+
+        // The BIP 39 mnemonic for this entropy is
+        // "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose".
+        let mnemonic = derive_mnemonic_entropy(&secp, &root, Language::English, 12, 0).unwrap();
+        assert_eq!(mnemonic, hex!("6250b68daf746d12a24d58b4787a714b"));
+    }
+
+    #[test]
+    fn errors() {
+        let secp = Secp256k1::signing_only();
+        let root = Xpriv::from_str(ROOT).unwrap();
+
+        assert_eq!(
+            derive_mnemonic_entropy(&secp, &root, Language::English, 15, 0),
+            Err(Error::InvalidWordCount(15))
+        );
+        assert_eq!(derive_hex(&secp, &root, 65, 0), Err(Error::InvalidByteCount(65)));
+        assert_eq!(
+            derive_wif(&secp, &root, 1 << 31),
+            Err(Error::Bip32(bip32::Error::InvalidChildNumber(1 << 31)))
+        );
+    }
+}
@@ -8,7 +8,7 @@
 //! these blocks and the blockchain.
 //!
 
-use core::fmt;
+use core::{fmt, iter};
 
 use hashes::{sha256d, Hash, HashEngine};
 use io::{BufRead, Write};
@@ -16,7 +16,7 @@ use io::{BufRead, Write};
 use super::Weight;
 use crate::blockdata::script;
 use crate::blockdata::transaction::{Transaction, Txid, Wtxid};
-use crate::consensus::{encode, Decodable, Encodable};
+use crate::consensus::{encode, Decodable, Encodable, Params};
 use crate::internal_macros::{impl_consensus_encoding, impl_hashencode};
 use crate::pow::{CompactTarget, Target, Work};
 use crate::prelude::*;
@@ -288,6 +288,58 @@ impl Block {
         false
     }
 
+    /// Checks the structure of the block at `height` against the consensus rules that don't need
+    /// the previous blocks.
+    ///
+    /// This checks, in order, that the block has transactions, that the merkle root matches the
+    /// transactions and that the merkle tree is not mutated by duplicating transactions
+    /// (CVE-2012-2459), that only the first transaction is a coinbase, that the witness
+    /// commitment matches and, once BIP34 is active on the chain described by `params`, that the
+    /// coinbase script starts with `height` serialized like Bitcoin Core does.
+    pub fn validate_structure(
+        &self,
+        height: u32,
+        params: &Params,
+    ) -> Result<(), BlockStructureError> {
+        use BlockStructureError::*;
+
+        let hashes = self.txdata.iter().map(|tx| tx.compute_txid().to_raw_hash());
+        let (merkle_root, levels) =
+            merkle_tree::calculate_root_with_levels(hashes.clone()).ok_or(NoTransactions)?;
+        if self.header.merkle_root != TxMerkleNode::from_raw_hash(merkle_root) {
+            return Err(BadMerkleRoot);
+        }
+        // A level ending in two identical hashes has the same root as if the last hash was left
+        // out and duplicated by the tree, so any such pair is rejected.
+        let leaves = hashes.collect::<Vec<_>>();
+        if iter::once(&leaves)
+            .chain(&levels)
+            .any(|level| level.chunks_exact(2).any(|pair| pair[0] == pair[1]))
+        {
+            return Err(MutatedMerkleTree);
+        }
+
+        if !self.txdata[0].is_coinbase() {
+            return Err(FirstNotCoinbase);
+        }
+        if let Some(index) = self.txdata.iter().skip(1).position(|tx| tx.is_coinbase()) {
+            return Err(ExtraCoinbase(index + 1));
+        }
+
+        if !self.check_witness_commitment() {
+            return Err(BadWitnessCommitment);
+        }
+
+        if params.is_bip34_active(height) {
+            let expected = script::Builder::new().push_int(i64::from(height)).into_script();
+            let script_sig = self.txdata[0].input.first().map(|input| input.script_sig.as_bytes());
+            if !script_sig.map_or(false, |script_sig| script_sig.starts_with(expected.as_bytes())) {
+                return Err(BadBip34Height);
+            }
+        }
+        Ok(())
+    }
+
     /// Computes the transaction merkle root.
     pub fn compute_merkle_root(&self) -> Option<TxMerkleNode> {
         let hashes = self.txdata.iter().map(|obj| obj.compute_txid().to_raw_hash());
@@ -323,15 +375,15 @@ impl Block {
     /// > Block weight is defined as Base size * 3 + Total size.
     pub fn weight(&self) -> Weight {
         // This is the exact definition of a weight unit, as defined by BIP-141 (quote above).
-        let wu = self.base_size() * 3 + self.total_size();
+        let wu = self.stripped_size() * 3 + self.total_size();
         Weight::from_wu_usize(wu)
     }
 
-    /// Returns the base block size.
+    /// Returns the stripped block size, also known as the base size.
     ///
     /// > Base size is the block size in bytes with the original transaction serialization without
     /// > any witness-related data, as seen by a non-upgraded node.
-    fn base_size(&self) -> usize {
+    pub fn stripped_size(&self) -> usize {
         let mut size = Header::SIZE;
 
         size += VarInt::from(self.txdata.len()).size();
@@ -353,6 +405,25 @@ impl Block {
         size
     }
 
+    /// Encodes the block without any witness data, as seen by a non-upgraded node.
+    ///
+    /// Writes [`Block::stripped_size`] bytes, transactions are written in the legacy serialization
+    /// without being copied.
+    pub fn serialize_without_witness<W: Write + ?Sized>(
+        &self,
+        w: &mut W,
+    ) -> Result<usize, io::Error> {
+        let mut len = self.header.consensus_encode(w)?;
+        len += VarInt::from(self.txdata.len()).consensus_encode(w)?;
+        for tx in &self.txdata {
+            len += tx.version.consensus_encode(w)?;
+            len += tx.input.consensus_encode(w)?;
+            len += tx.output.consensus_encode(w)?;
+            len += tx.lock_time.consensus_encode(w)?;
+        }
+        Ok(len)
+    }
+
     /// Returns the coinbase transaction, if one is present.
     pub fn coinbase(&self) -> Option<&Transaction> { self.txdata.first() }
 
@@ -385,7 +456,9 @@ impl Block {
                     Ok(h as u64)
                 }
             }
-            _ => Err(Bip34Error::NotPresent),
+            // Heights 1 to 16 are pushed with the small integer opcodes.
+            script::Instruction::Op(op) =>
+                op.decode_pushnum().map(u64::from).ok_or(Bip34Error::NotPresent),
         }
     }
 }
@@ -448,6 +521,57 @@ impl std::error::Error for Bip34Error {
     }
 }
 
+/// An error in the structure of a block, returned by [`Block::validate_structure`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BlockStructureError {
+    /// The block contains no transactions.
+    NoTransactions,
+    /// The merkle root in the header doesn't match the transactions.
+    BadMerkleRoot,
+    /// The merkle tree contains duplicated transactions (CVE-2012-2459).
+    MutatedMerkleTree,
+    /// The first transaction is not a coinbase transaction.
+    FirstNotCoinbase,
+    /// The transaction at this index is a coinbase transaction but not the first one.
+    ExtraCoinbase(usize),
+    /// The witness commitment in the coinbase doesn't match the transactions.
+    BadWitnessCommitment,
+    /// The coinbase script doesn't start with the block height as required by BIP34.
+    BadBip34Height,
+}
+
+internals::impl_from_infallible!(BlockStructureError);
+
+impl fmt::Display for BlockStructureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use BlockStructureError::*;
+
+        match *self {
+            NoTransactions => f.write_str("block contains no transactions"),
+            BadMerkleRoot => f.write_str("merkle root doesn't match the transactions"),
+            MutatedMerkleTree => f.write_str("merkle tree contains duplicated transactions"),
+            FirstNotCoinbase => f.write_str("first transaction is not a coinbase"),
+            ExtraCoinbase(index) => write!(f, "transaction {} is an extra coinbase", index),
+            BadWitnessCommitment =>
+                f.write_str("witness commitment doesn't match the transactions"),
+            BadBip34Height => f.write_str("coinbase doesn't start with the BIP34 block height"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BlockStructureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use BlockStructureError::*;
+
+        match *self {
+            NoTransactions | BadMerkleRoot | MutatedMerkleTree | FirstNotCoinbase
+            | ExtraCoinbase(_) | BadWitnessCommitment | BadBip34Height => None,
+        }
+    }
+}
+
 /// A block validation error.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -541,7 +665,7 @@ mod tests {
         assert_eq!(real_decode.header.difficulty_float(), 1.0);
 
         assert_eq!(real_decode.total_size(), some_block.len());
-        assert_eq!(real_decode.base_size(), some_block.len());
+        assert_eq!(real_decode.stripped_size(), some_block.len());
         assert_eq!(
             real_decode.weight(),
             Weight::from_non_witness_data_size(some_block.len() as u64)
@@ -551,6 +675,9 @@ mod tests {
         assert!(real_decode.check_witness_commitment());
 
         assert_eq!(serialize(&real_decode), some_block);
+        let mut stripped = Vec::new();
+        real_decode.serialize_without_witness(&mut stripped).unwrap();
+        assert_eq!(stripped, some_block);
     }
 
     // Check testnet block 000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b
@@ -583,14 +710,101 @@ mod tests {
         assert_eq!(real_decode.header.difficulty_float(), 2456598.4399242126);
 
         assert_eq!(real_decode.total_size(), segwit_block.len());
-        assert_eq!(real_decode.base_size(), 4283);
+        assert_eq!(real_decode.stripped_size(), 4283);
         assert_eq!(real_decode.weight(), Weight::from_wu(17168));
 
+        let mut stripped = Vec::new();
+        let len = real_decode.serialize_without_witness(&mut stripped).unwrap();
+        assert_eq!(len, 4283);
+        assert_eq!(stripped.len(), 4283);
+        let mut without_witness = real_decode.clone();
+        without_witness
+            .txdata
+            .iter_mut()
+            .flat_map(|tx| &mut tx.input)
+            .for_each(|txin| txin.witness.clear());
+        assert_eq!(stripped, serialize(&without_witness));
+
         assert!(real_decode.check_witness_commitment());
 
         assert_eq!(serialize(&real_decode), segwit_block);
     }
 
+    #[test]
+    fn validate_structure() {
+        let segwit_block = include_bytes!("../../tests/data/testnet_block_000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b.raw");
+        let block: Block = deserialize(&segwit_block[..]).unwrap();
+        let height = block.bip34_block_height().unwrap() as u32;
+        let params = Params::TESTNET;
+        assert_eq!(block.validate_structure(height, &params), Ok(()));
+        assert_eq!(
+            block.validate_structure(height + 1, &params),
+            Err(BlockStructureError::BadBip34Height)
+        );
+        // The height is not checked before BIP34 activation.
+        assert_eq!(block.validate_structure(height + 1, &Params::REGTEST), Ok(()));
+
+        let mut empty = block.clone();
+        empty.txdata.clear();
+        assert_eq!(
+            empty.validate_structure(height, &params),
+            Err(BlockStructureError::NoTransactions)
+        );
+
+        let mut swapped = block.clone();
+        swapped.txdata.swap(1, 2);
+        assert_eq!(
+            swapped.validate_structure(height, &params),
+            Err(BlockStructureError::BadMerkleRoot)
+        );
+
+        // The block has an odd number of transactions, duplicating the last keeps the root.
+        let mut mutated = block.clone();
+        mutated.txdata.push(mutated.txdata[mutated.txdata.len() - 1].clone());
+        assert_eq!(mutated.compute_merkle_root(), block.compute_merkle_root());
+        assert_eq!(
+            mutated.validate_structure(height, &params),
+            Err(BlockStructureError::MutatedMerkleTree)
+        );
+
+        let mut no_coinbase = block.clone();
+        no_coinbase.txdata.remove(0);
+        no_coinbase.header.merkle_root = no_coinbase.compute_merkle_root().unwrap();
+        assert_eq!(
+            no_coinbase.validate_structure(height, &params),
+            Err(BlockStructureError::FirstNotCoinbase)
+        );
+
+        let mut extra_coinbase = block.clone();
+        extra_coinbase.txdata.insert(3, extra_coinbase.txdata[0].clone());
+        extra_coinbase.header.merkle_root = extra_coinbase.compute_merkle_root().unwrap();
+        assert_eq!(
+            extra_coinbase.validate_structure(height, &params),
+            Err(BlockStructureError::ExtraCoinbase(3))
+        );
+
+        // Changing a witness keeps the txid but changes the witness root.
+        let mut bad_witness = block.clone();
+        bad_witness.txdata[1].input[0].witness.push([0x01]);
+        assert_eq!(
+            bad_witness.validate_structure(height, &params),
+            Err(BlockStructureError::BadWitnessCommitment)
+        );
+
+        // Heights up to 16 are pushed as small integers, the coinbase wtxid is always zero so the
+        // witness commitment is unchanged.
+        let mut small_height = block.clone();
+        let script_sig = &mut small_height.txdata[0].input[0].script_sig;
+        *script_sig = script::Builder::new().push_int(5).push_slice([0xab]).into_script();
+        small_height.header.merkle_root = small_height.compute_merkle_root().unwrap();
+        assert_eq!(small_height.bip34_block_height(), Ok(5));
+        assert_eq!(small_height.validate_structure(5, &Params::SIGNET), Ok(()));
+        assert_eq!(
+            small_height.validate_structure(6, &Params::SIGNET),
+            Err(BlockStructureError::BadBip34Height)
+        );
+    }
+
     #[test]
     fn block_version_test() {
         let block = hex!("ffffff7f0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000");
@@ -11,12 +11,13 @@ use hashes::{sha256d, Hash};
 use hex_lit::hex;
 use internals::impl_array_newtype;
 
-use crate::blockdata::block::{self, Block};
+use crate::blockdata::block::{self, Block, BlockHash};
 use crate::blockdata::locktime::absolute;
 use crate::blockdata::opcodes::all::*;
 use crate::blockdata::script;
 use crate::blockdata::transaction::{self, OutPoint, Sequence, Transaction, TxIn, TxOut};
 use crate::blockdata::witness::Witness;
+use crate::consensus::Params;
 use crate::internal_macros::impl_bytes_newtype;
 use crate::network::Network;
 use crate::pow::CompactTarget;
@@ -45,10 +46,20 @@ pub const SCRIPT_ADDRESS_PREFIX_TEST: u8 = 196; // 0xc4
 pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
 /// How may blocks between halvings.
 pub const SUBSIDY_HALVING_INTERVAL: u32 = 210_000;
+/// The block subsidy before the first halving.
+pub const INITIAL_BLOCK_SUBSIDY: Amount = Amount::from_sat(50 * 100_000_000);
 /// Maximum allowed value for an integer in Script.
 pub const MAX_SCRIPTNUM_VALUE: u32 = 0x80000000; // 2^31
 /// Number of blocks needed for an output from a coinbase transaction to be spendable.
 pub const COINBASE_MATURITY: u32 = 100;
+/// The challenge script of the default signet, a 1-of-2 multisig.
+pub const DEFAULT_SIGNET_CHALLENGE: [u8; 71] = [
+    0x51, 0x21, 0x03, 0xad, 0x5e, 0x0e, 0xda, 0xd1, 0x8c, 0xb1, 0xf0, 0xfc, 0x0d, 0x28, 0xa3, 0xd4,
+    0xf1, 0xf3, 0xe4, 0x45, 0x64, 0x03, 0x37, 0x48, 0x9a, 0xbb, 0x10, 0x40, 0x4f, 0x2d, 0x1e, 0x08,
+    0x6b, 0xe4, 0x30, 0x21, 0x03, 0x59, 0xef, 0x50, 0x21, 0x96, 0x4f, 0xe2, 0x2d, 0x6f, 0x8e, 0x05,
+    0xb2, 0x46, 0x3c, 0x95, 0x40, 0xce, 0x96, 0x88, 0x3f, 0xe3, 0xb2, 0x78, 0x76, 0x0f, 0x04, 0x8f,
+    0x51, 0x89, 0xf2, 0xe6, 0xc4, 0x52, 0xae,
+];
 
 /// Constructs and returns the coinbase (and only) transaction of the Bitcoin genesis block.
 fn bitcoin_genesis_tx() -> Transaction {
@@ -77,12 +88,25 @@ fn bitcoin_genesis_tx() -> Transaction {
     let script_bytes = hex!("04678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5f");
     let out_script =
         script::Builder::new().push_slice(script_bytes).push_opcode(OP_CHECKSIG).into_script();
-    ret.output.push(TxOut { value: Amount::from_sat(50 * 100_000_000), script_pubkey: out_script });
+    ret.output.push(TxOut { value: INITIAL_BLOCK_SUBSIDY, script_pubkey: out_script });
 
     // end
     ret
 }
 
+/// Returns the block subsidy (the newly created coins a coinbase may claim, excluding fees) of
+/// the block at `height`.
+///
+/// The subsidy starts at [`INITIAL_BLOCK_SUBSIDY`] and halves every
+/// [`Params::subsidy_halving_interval`] blocks, reaching zero after 64 halvings.
+pub fn block_subsidy(height: u32, params: &Params) -> Amount {
+    let halvings = height / params.subsidy_halving_interval;
+    if halvings >= 64 {
+        return Amount::ZERO;
+    }
+    Amount::from_sat(INITIAL_BLOCK_SUBSIDY.to_sat() >> halvings)
+}
+
 /// Constructs and returns the genesis block.
 pub fn genesis_block(network: Network) -> Block {
     let txdata = vec![bitcoin_genesis_tx()];
@@ -180,6 +204,68 @@ impl ChainHash {
     }
 }
 
+/// A block known to be in the best chain of a network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Checkpoint {
+    /// The height of the block.
+    pub height: u32,
+    // The hash of the block, in the byte order it is displayed in.
+    hash: [u8; 32],
+}
+
+impl Checkpoint {
+    /// Returns the hash of the block.
+    pub fn block_hash(&self) -> BlockHash {
+        let mut hash = self.hash;
+        hash.reverse();
+        BlockHash::from_byte_array(hash)
+    }
+}
+
+macro_rules! checkpoint {
+    ($height:expr, $hash:expr) => {
+        Checkpoint { height: $height, hash: hex!($hash) }
+    };
+}
+
+// The checkpoints of Bitcoin Core.
+const MAINNET_CHECKPOINTS: &[Checkpoint] = &[
+    checkpoint!(11111, "0000000069e244f73d78e8fd29ba2fd2ed618bd6fa2ee92559f542fdb26e7c1d"),
+    checkpoint!(33333, "000000002dd5588a74784eaa7ab0507a18ad16a236e7b1ce69f00d7ddfb5d0a6"),
+    checkpoint!(74000, "0000000000573993a3c9e41ce34471c079dcf5f52a0e824a81e7f953b8661a20"),
+    checkpoint!(105000, "00000000000291ce28027faea320c8d2b054b2e0fe44a773f3eefb151d6bdc97"),
+    checkpoint!(134444, "00000000000005b12ffd4cd315cd34ffd4a594f430ac814c91184a0d42d2b0fe"),
+    checkpoint!(168000, "000000000000099e61ea72015e79632f216fe6cb33d7899acb35b75c8303b763"),
+    checkpoint!(193000, "000000000000059f452a5f7340de6682a977387c17010ff6e6c3bd83ca8b1317"),
+    checkpoint!(210000, "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e"),
+    checkpoint!(216116, "00000000000001b4f4b433e81ee46494af945cf96014816a4e2370f11b23df4e"),
+    checkpoint!(225430, "00000000000001c108384350f74090433e7fcf79a606b8e797f065b130575932"),
+    checkpoint!(250000, "000000000000003887df1f29024b06fc2200b55f8af8f35453d7be294df2d214"),
+    checkpoint!(279000, "0000000000000001ae8c72a0b0c301f67e3afca10e819efa9041e458e9bd7e40"),
+    checkpoint!(295000, "00000000000000004d9b4ef50f0f9d686fd69db2e03af35a100370c64632a983"),
+];
+const TESTNET_CHECKPOINTS: &[Checkpoint] =
+    &[checkpoint!(546, "000000002a936ca763904c3c35fce2f3556c559c0214345d31b1bcebf76acb70")];
+const SIGNET_CHECKPOINTS: &[Checkpoint] = &[];
+const REGTEST_CHECKPOINTS: &[Checkpoint] =
+    &[checkpoint!(0, "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206")];
+
+/// Returns the well-known checkpoints of `network`, ordered by height.
+///
+/// These are the checkpoints of Bitcoin Core. A header chain that conflicts with them is invalid,
+/// so node software may use them to reject low-difficulty forks early during initial sync.
+///
+/// No default assume-valid block is provided. Bitcoin Core updates its default with every release,
+/// node software should take the block from the Core release it wants to track.
+pub fn checkpoints(network: Network) -> &'static [Checkpoint] {
+    match network {
+        Network::Bitcoin => MAINNET_CHECKPOINTS,
+        Network::Testnet => TESTNET_CHECKPOINTS,
+        Network::Signet => SIGNET_CHECKPOINTS,
+        Network::Regtest => REGTEST_CHECKPOINTS,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use core::str::FromStr;
@@ -321,4 +407,42 @@ mod test {
         let want = "6fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000";
         assert_eq!(got, want);
     }
+
+    #[test]
+    fn network_checkpoints() {
+        for network in [Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            let checkpoints = checkpoints(network);
+            assert!(checkpoints.windows(2).all(|w| w[0].height < w[1].height));
+            if let Some(genesis) = checkpoints.iter().find(|c| c.height == 0) {
+                assert_eq!(genesis.block_hash(), genesis_block(network).block_hash());
+            }
+        }
+
+        let mainnet = checkpoints(Network::Bitcoin);
+        assert_eq!(mainnet.len(), 13);
+        assert_eq!(mainnet[7].height, 210000);
+        assert_eq!(
+            mainnet[7].block_hash().to_string(),
+            "000000000000048b95347e83192f69cf0366076336c639f9b7228e9ba171342e"
+        );
+        assert_eq!(mainnet.last().unwrap().height, 295000);
+        assert!(checkpoints(Network::Signet).is_empty());
+    }
+
+    #[test]
+    fn block_subsidy_halvings() {
+        let params = Params::MAINNET;
+        assert_eq!(block_subsidy(0, &params), Amount::from_sat(5_000_000_000));
+        assert_eq!(block_subsidy(209_999, &params), Amount::from_sat(5_000_000_000));
+        assert_eq!(block_subsidy(210_000, &params), Amount::from_sat(2_500_000_000));
+        assert_eq!(block_subsidy(840_000, &params), Amount::from_sat(312_500_000));
+        assert_eq!(block_subsidy(6_929_999, &params), Amount::from_sat(1));
+        assert_eq!(block_subsidy(6_930_000, &params), Amount::ZERO);
+        assert_eq!(block_subsidy(u32::MAX, &params), Amount::ZERO);
+
+        let params = Params::REGTEST;
+        assert_eq!(block_subsidy(149, &params), Amount::from_sat(5_000_000_000));
+        assert_eq!(block_subsidy(150, &params), Amount::from_sat(2_500_000_000));
+        assert_eq!(block_subsidy(150 * 64, &params), Amount::ZERO);
+    }
 }
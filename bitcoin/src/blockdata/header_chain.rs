@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Validation of chains of block headers.
+//!
+//! Light clients only download block headers, [`HeaderChain`] checks them against the consensus
+//! rules that apply to headers alone, including the difficulty adjustment.
+
+use core::fmt;
+
+use crate::blockdata::block::{BlockHash, Header};
+use crate::consensus::Params;
+use crate::pow::{CompactTarget, Target};
+use crate::prelude::*;
+
+/// The number of blocks the median time past is computed over.
+const MEDIAN_TIME_SPAN: usize = 11;
+
+/// A chain of block headers validated against the consensus rules.
+///
+/// The chain starts with trusted headers, such as a checkpoint. Every header added must:
+///
+/// * build on the tip of the chain,
+/// * have a timestamp greater than the median time past of the previous 11 headers,
+/// * have the target required by the difficulty adjustment rules of the chain's [`Params`],
+/// * have a block hash meeting its target.
+///
+/// Timestamps too far in the future are not rejected since that requires the current time.
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    params: Params,
+    /// The height of the first header.
+    start_height: u32,
+    headers: Vec<Header>,
+}
+
+impl HeaderChain {
+    /// Creates a chain from trusted `headers`, the first of which is at height `start_height`.
+    ///
+    /// The trusted headers are not validated. They must include the first header of a difficulty
+    /// adjustment period, which is required to validate the targets of the headers added. The
+    /// median time past is computed over the available headers only, so starting at least 10
+    /// headers before the start of a period checks the timestamps of all added headers fully.
+    pub fn new(
+        params: impl Into<Params>,
+        start_height: u32,
+        headers: Vec<Header>,
+    ) -> Result<HeaderChain, HeaderChainError> {
+        let params = params.into();
+        let interval = params.difficulty_adjustment_interval();
+        let start = u64::from(start_height);
+        let period_start = (start + interval - 1) / interval * interval;
+        if period_start >= start + headers.len() as u64 {
+            return Err(HeaderChainError::NoPeriodStart);
+        }
+        Ok(HeaderChain { params, start_height, headers })
+    }
+
+    /// Returns the consensus parameters the headers are validated against.
+    pub fn params(&self) -> &Params { &self.params }
+
+    /// Returns the header at the tip of the chain.
+    pub fn tip(&self) -> &Header { self.headers.last().expect("chain is never empty") }
+
+    /// Returns the height of the tip of the chain.
+    pub fn tip_height(&self) -> u32 { self.start_height + self.headers.len() as u32 - 1 }
+
+    /// Returns the header at `height`, if it is in the chain.
+    pub fn get(&self, height: u32) -> Option<&Header> {
+        let index = height.checked_sub(self.start_height)?;
+        self.headers.get(index as usize)
+    }
+
+    /// Returns the median time past of the tip, the median timestamp of the last 11 headers.
+    pub fn median_time_past(&self) -> u32 {
+        let start = self.headers.len().saturating_sub(MEDIAN_TIME_SPAN);
+        let mut times = self.headers[start..].iter().map(|h| h.time).collect::<Vec<_>>();
+        times.sort_unstable();
+        times[times.len() / 2]
+    }
+
+    /// Returns the target required of a header extending the tip with timestamp `time`.
+    pub fn next_work_required(&self, time: u32) -> CompactTarget {
+        let params = &self.params;
+        let interval = params.difficulty_adjustment_interval() as u32;
+        let tip = self.tip();
+        let tip_height = self.tip_height();
+
+        if (tip_height + 1) % interval != 0 {
+            if !params.allow_min_difficulty_blocks {
+                return tip.bits;
+            }
+            let pow_limit = params.pow_limit.to_compact_lossy();
+            if Target::allows_min_difficulty(params, tip.time, time) {
+                return pow_limit;
+            }
+            // Otherwise the target of the last block that didn't use the minimum difficulty.
+            let mut height = tip_height;
+            while height % interval != 0 && self.header_at(height).bits == pow_limit {
+                height -= 1;
+            }
+            return self.header_at(height).bits;
+        }
+
+        if params.no_pow_retargeting {
+            return tip.bits;
+        }
+        let first = self.header_at(tip_height + 1 - interval);
+        tip.target().retarget(params, first.time, tip.time).to_compact_lossy()
+    }
+
+    /// Validates `header` and adds it to the tip of the chain, returning its block hash.
+    pub fn push(&mut self, header: Header) -> Result<BlockHash, HeaderChainError> {
+        use HeaderChainError::*;
+
+        if header.prev_blockhash != self.tip().block_hash() {
+            return Err(PrevBlockHashMismatch);
+        }
+        if header.time <= self.median_time_past() {
+            return Err(TimeTooOld);
+        }
+        if header.bits != self.next_work_required(header.time) {
+            return Err(BadTarget);
+        }
+        let block_hash = header.block_hash();
+        if !header.target().is_met_by(block_hash) {
+            return Err(BadProofOfWork);
+        }
+
+        self.headers.push(header);
+        Ok(block_hash)
+    }
+
+    /// Validates `headers` and adds them to the tip of the chain one after another.
+    ///
+    /// Stops at the first invalid header, the headers before it remain in the chain.
+    pub fn push_headers<I>(&mut self, headers: I) -> Result<(), HeaderChainError>
+    where
+        I: IntoIterator<Item = Header>,
+    {
+        for header in headers {
+            self.push(header)?;
+        }
+        Ok(())
+    }
+
+    // The start of the tip's difficulty adjustment period is always in the chain.
+    fn header_at(&self, height: u32) -> &Header {
+        self.get(height).expect("start of difficulty adjustment period is in the chain")
+    }
+}
+
+/// An error when validating a chain of headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeaderChainError {
+    /// The trusted headers don't include the first header of a difficulty adjustment period.
+    NoPeriodStart,
+    /// The header doesn't build on the tip of the chain.
+    PrevBlockHashMismatch,
+    /// The header's timestamp is not greater than the median time past.
+    TimeTooOld,
+    /// The header's target is not the one required by the difficulty adjustment.
+    BadTarget,
+    /// The header's block hash doesn't meet its target.
+    BadProofOfWork,
+}
+
+internals::impl_from_infallible!(HeaderChainError);
+
+impl fmt::Display for HeaderChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use HeaderChainError::*;
+
+        match *self {
+            NoPeriodStart =>
+                f.write_str("trusted headers don't include the start of an adjustment period"),
+            PrevBlockHashMismatch => f.write_str("header doesn't build on the tip of the chain"),
+            TimeTooOld => f.write_str("header timestamp not greater than median time past"),
+            BadTarget => f.write_str("header target incorrect"),
+            BadProofOfWork => f.write_str("header target correct but not attained"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HeaderChainError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use HeaderChainError::*;
+
+        match *self {
+            NoPeriodStart | PrevBlockHashMismatch | TimeTooOld | BadTarget | BadProofOfWork => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hashes::Hash;
+
+    use super::*;
+    use crate::blockdata::block::{TxMerkleNode, Version};
+    use crate::blockdata::constants::genesis_block;
+    use crate::Network;
+
+    fn mine(prev: &Header, time: u32, bits: CompactTarget) -> Header {
+        let mut header = Header {
+            version: Version::TWO,
+            prev_blockhash: prev.block_hash(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time,
+            bits,
+            nonce: 0,
+        };
+        while !header.target().is_met_by(header.block_hash()) {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    // Regtest with retargeting every 10 blocks.
+    fn params() -> Params {
+        let mut params = Params::REGTEST;
+        params.pow_target_timespan = 10 * params.pow_target_spacing;
+        params.no_pow_retargeting = false;
+        params.allow_min_difficulty_blocks = false;
+        params
+    }
+
+    #[test]
+    fn header_chain() {
+        let genesis = genesis_block(Network::Regtest).header;
+        let mut chain = HeaderChain::new(params(), 0, vec![genesis]).unwrap();
+        let pow_limit = chain.params().pow_limit.to_compact_lossy();
+        assert_eq!(genesis.bits, pow_limit);
+
+        // Blocks every minute make the difficulty go up by the maximum factor of four.
+        for _ in 0..9 {
+            let tip = *chain.tip();
+            chain.push(mine(&tip, tip.time + 60, tip.bits)).unwrap();
+        }
+        let time = chain.tip().time + 60;
+        let bits = chain.next_work_required(time);
+        assert_eq!(
+            bits,
+            chain.params().pow_limit.min_difficulty_transition_threshold().to_compact_lossy()
+        );
+        let tip = *chain.tip();
+        assert_eq!(chain.push(mine(&tip, time, pow_limit)), Err(HeaderChainError::BadTarget));
+        chain.push(mine(&tip, time, bits)).unwrap();
+        assert_eq!(chain.tip_height(), 10);
+        assert_eq!(chain.get(10), Some(chain.tip()));
+
+        let tip = *chain.tip();
+        let header = mine(&tip, chain.median_time_past(), bits);
+        assert_eq!(chain.push(header), Err(HeaderChainError::TimeTooOld));
+        let header = mine(&genesis, tip.time + 60, bits);
+        assert_eq!(chain.push(header), Err(HeaderChainError::PrevBlockHashMismatch));
+        let mut header = mine(&tip, tip.time + 60, bits);
+        while header.target().is_met_by(header.block_hash()) {
+            header.nonce += 1;
+        }
+        assert_eq!(chain.push(header), Err(HeaderChainError::BadProofOfWork));
+
+        for _ in 0..10 {
+            let tip = *chain.tip();
+            let time = tip.time + 600;
+            chain.push(mine(&tip, time, chain.next_work_required(time))).unwrap();
+        }
+        assert_eq!(chain.tip_height(), 20);
+
+        // A chain starting from a checkpoint at the start of a period validates the same headers.
+        let headers = (10..=20).map(|h| *chain.get(h).unwrap()).collect::<Vec<_>>();
+        let mut checkpoint = HeaderChain::new(params(), 10, headers[..1].to_vec()).unwrap();
+        checkpoint.push_headers(headers[1..].iter().copied()).unwrap();
+        assert_eq!(checkpoint.tip(), chain.tip());
+
+        let err = HeaderChain::new(params(), 11, headers[1..10].to_vec()).unwrap_err();
+        assert_eq!(err, HeaderChainError::NoPeriodStart);
+    }
+
+    #[test]
+    fn header_chain_min_difficulty() {
+        let mut params = params();
+        params.allow_min_difficulty_blocks = true;
+        let genesis = genesis_block(Network::Regtest).header;
+        let mut chain = HeaderChain::new(params, 0, vec![genesis]).unwrap();
+        for _ in 0..10 {
+            let tip = *chain.tip();
+            chain.push(mine(&tip, tip.time + 60, chain.next_work_required(tip.time + 60))).unwrap();
+        }
+        let bits = chain.tip().bits;
+        let pow_limit = chain.params().pow_limit.to_compact_lossy();
+        assert_ne!(bits, pow_limit);
+
+        // A block more than 20 minutes after the previous one may use the minimum difficulty,
+        // the next block goes back to the difficulty before it.
+        let tip = *chain.tip();
+        assert_eq!(chain.next_work_required(tip.time + 1201), pow_limit);
+        chain.push(mine(&tip, tip.time + 1201, pow_limit)).unwrap();
+        let tip = *chain.tip();
+        assert_eq!(chain.next_work_required(tip.time + 60), bits);
+        chain.push(mine(&tip, tip.time + 60, bits)).unwrap();
+    }
+}
@@ -228,6 +228,35 @@ impl LockTime {
         }
     }
 
+    /// Returns true if this timelock constraint is satisfied by the respective `height`/`mtp`.
+    ///
+    /// Equivalent to [`LockTime::is_satisfied_by`] except that a blocktime based lock is checked
+    /// against the median time past of the chain, as required by consensus (see [BIP-113]).
+    ///
+    /// [BIP-113]: <https://github.com/bitcoin/bips/blob/master/bip-0113.mediawiki>
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use bitcoin::absolute::{LockTime, Height, Mtp};
+    /// // The timestamps of the last 11 blocks, e.g. read from block headers.
+    /// let timestamps = [1_653_195_000, 1_653_195_600, 1_653_196_200];
+    /// let mtp = Mtp::from_timestamps(timestamps).expect("non-empty");
+    /// let height = Height::from_consensus(741521).expect("valid height");
+    ///
+    /// let n = LockTime::from_consensus(1_653_195_600); // `n OP_CHEKCLOCKTIMEVERIFY`.
+    /// assert!(n.is_satisfied_by_mtp(height, mtp));
+    /// ```
+    #[inline]
+    #[cfg_attr(all(test, mutate), mutate)]
+    pub fn is_satisfied_by_mtp(&self, height: Height, mtp: Mtp) -> bool {
+        use LockTime::*;
+
+        match *self {
+            Blocks(n) => n <= height,
+            Seconds(n) => n.to_consensus_u32() <= mtp.to_consensus_u32(),
+        }
+    }
+
     /// Returns true if satisfaction of `other` lock time implies satisfaction of this
     /// [`absolute::LockTime`].
     ///
@@ -584,6 +613,75 @@ impl From<ParseError> for ParseTimeError {
     fn from(value: ParseError) -> Self { Self(value) }
 }
 
+/// The median time past (MTP) of a block, as defined by [BIP-113].
+///
+/// This is the median of the timestamps of the (up to) 11 blocks preceding a block, and is the
+/// value consensus compares time based lock times against. Using this type instead of a raw block
+/// timestamp prevents accidentally checking a lock against the timestamp of the chain tip.
+///
+/// Note that unlike [`Time`], an `Mtp` may hold any `u32` value since the timestamps of blocks are
+/// not required to be above [`LOCK_TIME_THRESHOLD`]. A median time past below the threshold will
+/// not satisfy any lock-by-blocktime lock.
+///
+/// [BIP-113]: <https://github.com/bitcoin/bips/blob/master/bip-0113.mediawiki>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
+pub struct Mtp(u32);
+
+impl Mtp {
+    /// Median time past of zero, satisfies no lock-by-blocktime lock.
+    pub const ZERO: Self = Mtp(0);
+
+    /// The number of preceding blocks whose timestamps are used to compute the median time past.
+    pub const MEDIAN_TIME_SPAN: usize = 11;
+
+    /// Computes the median time past from the timestamps of the preceding blocks.
+    ///
+    /// Only the first [`Mtp::MEDIAN_TIME_SPAN`] timestamps are used, any extra items are not
+    /// consumed. Order does not matter. Fewer timestamps may be provided close to the genesis
+    /// block, this matches the behaviour of Bitcoin Core.
+    ///
+    /// Returns `None` if `timestamps` is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use bitcoin::absolute::Mtp;
+    /// let timestamps = [1_700_000_600, 1_700_000_000, 1_700_001_200];
+    /// let mtp = Mtp::from_timestamps(timestamps).expect("non-empty");
+    /// assert_eq!(mtp.to_consensus_u32(), 1_700_000_600);
+    /// ```
+    pub fn from_timestamps<I: IntoIterator<Item = u32>>(timestamps: I) -> Option<Self> {
+        let mut buf = [0_u32; Self::MEDIAN_TIME_SPAN];
+        let mut len = 0;
+        for (slot, timestamp) in buf.iter_mut().zip(timestamps) {
+            *slot = timestamp;
+            len += 1;
+        }
+        if len == 0 {
+            return None;
+        }
+
+        let timestamps = &mut buf[..len];
+        timestamps.sort_unstable();
+        Some(Mtp(timestamps[len / 2]))
+    }
+
+    /// Constructs a new `Mtp` from a median time past already computed elsewhere (e.g. the
+    /// `mediantime` field returned by Bitcoin Core's `getblockheader` RPC).
+    #[inline]
+    pub fn from_consensus(n: u32) -> Self { Mtp(n) }
+
+    /// Returns the inner `u32` value.
+    #[inline]
+    pub fn to_consensus_u32(self) -> u32 { self.0 }
+}
+
+impl fmt::Display for Mtp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self.0, f) }
+}
+
 fn parser<T, E, S, F>(f: F) -> impl FnOnce(S) -> Result<T, E>
 where
     E: From<ParseError>,
@@ -961,6 +1059,32 @@ mod tests {
         assert!(lock.is_satisfied_by(height, time))
     }
 
+    #[test]
+    fn mtp_from_timestamps() {
+        assert_eq!(Mtp::from_timestamps(core::iter::empty()), None);
+        assert_eq!(Mtp::from_timestamps([7]), Some(Mtp::from_consensus(7)));
+        assert_eq!(Mtp::from_timestamps([3, 1, 2, 4]), Some(Mtp::from_consensus(3)));
+
+        // Only the first 11 timestamps are used.
+        let timestamps = [10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 100, 100, 100];
+        assert_eq!(Mtp::from_timestamps(timestamps), Some(Mtp::from_consensus(5)));
+    }
+
+    #[test]
+    fn satisfied_by_mtp() {
+        let height = Height::from_consensus(750_000).unwrap();
+        let mtp = Mtp::from_consensus(1_653_195_600);
+
+        let lock = LockTime::from_consensus(1_653_195_600);
+        assert!(lock.is_satisfied_by_mtp(height, mtp));
+        assert!(!lock.is_satisfied_by_mtp(height, Mtp::from_consensus(1_653_195_599)));
+        assert!(!lock.is_satisfied_by_mtp(height, Mtp::from_consensus(0)));
+
+        let lock = LockTime::from_consensus(750_000);
+        assert!(lock.is_satisfied_by_mtp(height, mtp));
+        assert!(!lock.is_satisfied_by_mtp(Height::from_consensus(749_999).unwrap(), mtp));
+    }
+
     #[test]
     fn height_correctly_implies() {
         let lock = LockTime::from_consensus(750_005);
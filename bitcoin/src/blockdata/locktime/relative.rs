@@ -6,19 +6,29 @@
 //! whether bit 22 of the `u32` consensus value is set.
 //!
 
-use core::fmt;
+use core::cmp::{self, Ordering};
+use core::time::Duration;
+use core::{fmt, mem};
 
 #[cfg(all(test, mutate))]
 use mutagen::mutate;
 
+use crate::absolute;
 use crate::parse::impl_parse_str_from_int_infallible;
 #[cfg(doc)]
 use crate::relative;
 
+/// BIP 68 relative lock time disable flag mask.
+const LOCK_TIME_DISABLE_FLAG_MASK: u32 = 0x80000000;
+
+/// BIP 68 relative lock time type flag mask.
+const LOCK_TYPE_MASK: u32 = 0x00400000;
+
 /// A relative lock time value, representing either a block height or time (512 second intervals).
 ///
-/// The `relative::LockTime` type does not have any constructors, this is by design, please use
-/// `Sequence::to_relative_lock_time` to create a relative lock time.
+/// A `relative::LockTime` can be created from an nSequence value or the argument to
+/// `OP_CHECKSEQUENCEVERIFY` using [`LockTime::from_consensus`], or from a [`crate::Sequence`]
+/// using `Sequence::to_relative_lock_time`.
 ///
 /// ### Relevant BIPs
 ///
@@ -35,6 +45,105 @@ pub enum LockTime {
 }
 
 impl LockTime {
+    /// Constructs a `LockTime` from an nSequence value or the argument to OP_CHECKSEQUENCEVERIFY.
+    ///
+    /// This method will **not** round-trip with [`Self::to_consensus_u32`], because relative
+    /// lock times only use some bits of the underlying `u32` value and discard the rest. If
+    /// you want to preserve the full value, you should use the [`crate::Sequence`] type instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the BIP 68 disable flag (bit 31) is set.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use bitcoin::locktime::relative::{LockTime, Height, Time};
+    /// // Bit 22 is not set, this is a lock-by-blockheight value.
+    /// let lock = LockTime::from_consensus(144).expect("disable flag not set");
+    /// assert_eq!(lock, LockTime::from(Height::from(144)));
+    ///
+    /// // Bit 22 is set, this is a lock-by-blocktime value.
+    /// let lock = LockTime::from_consensus(0x0040_0046).expect("disable flag not set");
+    /// assert_eq!(lock, LockTime::from(Time::from_512_second_intervals(70)));
+    ///
+    /// // Bit 31 is set, the value does not encode a relative lock time.
+    /// assert!(LockTime::from_consensus(0x8000_0000).is_err());
+    /// ```
+    #[inline]
+    pub fn from_consensus(n: u32) -> Result<Self, DisabledLockTimeError> {
+        if n & LOCK_TIME_DISABLE_FLAG_MASK != 0 {
+            return Err(DisabledLockTimeError(n));
+        }
+
+        let value = n as u16; // BIP 68 only uses the low 16 bits for the lock value.
+        if n & LOCK_TYPE_MASK == 0 {
+            Ok(LockTime::Blocks(Height::from(value)))
+        } else {
+            Ok(LockTime::Time(Time::from_512_second_intervals(value)))
+        }
+    }
+
+    /// Returns the `u32` value used to encode this lock time as an nSequence value or an
+    /// argument to OP_CHECKSEQUENCEVERIFY.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use bitcoin::locktime::relative::{LockTime, Time};
+    /// let lock = LockTime::from(Time::from_512_second_intervals(70));
+    /// assert_eq!(lock.to_consensus_u32(), 0x0040_0046);
+    /// ```
+    #[inline]
+    pub fn to_consensus_u32(&self) -> u32 {
+        match *self {
+            LockTime::Blocks(ref h) => u32::from(h.value()),
+            LockTime::Time(ref t) => LOCK_TYPE_MASK | u32::from(t.value()),
+        }
+    }
+
+    /// Returns true if both lock times use the same unit i.e., both height based or both time based.
+    #[inline]
+    pub fn is_same_unit(&self, other: LockTime) -> bool {
+        mem::discriminant(self) == mem::discriminant(&other)
+    }
+
+    /// Returns true if this lock time value is in units of block height.
+    #[inline]
+    pub fn is_block_height(&self) -> bool { matches!(*self, LockTime::Blocks(_)) }
+
+    /// Returns true if this lock time value is in units of time (512 second intervals).
+    #[inline]
+    pub fn is_block_time(&self) -> bool { !self.is_block_height() }
+
+    /// Compares two lock times that use the same unit.
+    ///
+    /// Returns `None` if the lock times use different units, because block heights and 512 second
+    /// intervals are incommensurate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use core::cmp::Ordering;
+    /// # use bitcoin::locktime::relative::{LockTime, Height, Time};
+    /// let a = LockTime::from(Height::from(10));
+    /// let b = LockTime::from(Height::from(20));
+    /// let c = LockTime::from(Time::from_512_second_intervals(10));
+    ///
+    /// assert_eq!(a.compare_same_unit(b), Some(Ordering::Less));
+    /// assert_eq!(a.compare_same_unit(c), None);
+    /// ```
+    #[inline]
+    pub fn compare_same_unit(&self, other: LockTime) -> Option<Ordering> {
+        use LockTime::*;
+
+        match (*self, other) {
+            (Blocks(this), Blocks(other)) => Some(this.cmp(&other)),
+            (Time(this), Time(other)) => Some(this.cmp(&other)),
+            _ => None, // Not the same units.
+        }
+    }
+
     /// Returns true if this [`relative::LockTime`] is satisfied by either height or time.
     ///
     /// # Examples
@@ -63,6 +172,58 @@ impl LockTime {
         }
     }
 
+    /// Returns true if this [`relative::LockTime`] is satisfied for an input spending an output
+    /// confirmed at `confirmed_in` by a transaction to be included at `included_in`.
+    ///
+    /// This implements the BIP 68 semantics: a lock-by-blockheight lock is satisfied once the
+    /// number of blocks elapsed since the output was confirmed is at least the lock value, and a
+    /// lock-by-blocktime lock is satisfied once the median time past has advanced by at least the
+    /// lock value (in 512 second intervals) since the output was confirmed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use bitcoin::absolute;
+    /// # use bitcoin::locktime::relative::{ChainPosition, LockTime, Height};
+    /// let confirmed_in = ChainPosition::new(
+    ///     absolute::Height::from_consensus(800_000).expect("valid height"),
+    ///     absolute::Mtp::from_consensus(1_690_000_000),
+    /// );
+    /// let included_in = ChainPosition::new(
+    ///     absolute::Height::from_consensus(800_144).expect("valid height"),
+    ///     absolute::Mtp::from_consensus(1_690_086_400),
+    /// );
+    ///
+    /// let lock = LockTime::from(Height::from(144));
+    /// assert!(lock.is_satisfied_by_chain(confirmed_in, included_in));
+    /// ```
+    #[inline]
+    #[cfg_attr(all(test, mutate), mutate)]
+    pub fn is_satisfied_by_chain(
+        &self,
+        confirmed_in: ChainPosition,
+        included_in: ChainPosition,
+    ) -> bool {
+        use LockTime::*;
+
+        match *self {
+            Blocks(ref h) => {
+                let confirmed = confirmed_in.height.to_consensus_u32();
+                match included_in.height.to_consensus_u32().checked_sub(confirmed) {
+                    Some(elapsed) => u32::from(h.value()) <= elapsed,
+                    None => false,
+                }
+            }
+            Time(ref t) => {
+                let confirmed = confirmed_in.median_time_past.to_consensus_u32();
+                match included_in.median_time_past.to_consensus_u32().checked_sub(confirmed) {
+                    Some(elapsed) => u64::from(t.value()) * 512 <= u64::from(elapsed),
+                    None => false,
+                }
+            }
+        }
+    }
+
     /// Returns true if satisfaction of `other` lock time implies satisfaction of this
     /// [`relative::LockTime`].
     ///
@@ -188,6 +349,82 @@ impl fmt::Display for LockTime {
     }
 }
 
+/// The position of a block in the chain, as used to evaluate BIP 68 relative lock times.
+///
+/// To check a relative lock time, one position describes the block in which the output being spent
+/// was confirmed and the other describes the block in which the spending transaction is to be
+/// included (i.e., the block after the current chain tip).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChainPosition {
+    /// The height of the block.
+    pub height: absolute::Height,
+    /// The median time past of the 11 blocks preceding the block (see BIP 113).
+    ///
+    /// For the block following the current chain tip this is the median time past of the tip.
+    pub median_time_past: absolute::Mtp,
+}
+
+impl ChainPosition {
+    /// Constructs a new [`ChainPosition`] from a block height and median time past.
+    #[inline]
+    pub fn new(height: absolute::Height, median_time_past: absolute::Mtp) -> Self {
+        ChainPosition { height, median_time_past }
+    }
+}
+
+/// The earliest chain position at which a set of relative lock times is satisfied.
+///
+/// This is the result of evaluating the BIP 68 relative lock times of all inputs of a transaction
+/// (see `Transaction::calculate_sequence_locks`), a transaction may be included in a block once
+/// both the block height and the median time past of the block are at least the bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LockTimeBound {
+    /// The earliest block height at which the lock-by-blockheight locks are satisfied.
+    pub height: absolute::Height,
+    /// The earliest median time past at which the lock-by-blocktime locks are satisfied.
+    pub median_time_past: absolute::Mtp,
+}
+
+impl LockTimeBound {
+    /// A bound that is satisfied by any chain position i.e., no relative lock time constraint.
+    pub const NONE: Self =
+        LockTimeBound { height: absolute::Height::ZERO, median_time_past: absolute::Mtp::ZERO };
+
+    /// Returns the bound for a single `lock` on an output confirmed at `confirmed_in`.
+    ///
+    /// Saturates at [`absolute::Height::MAX`] and `u32::MAX` seconds instead of overflowing.
+    pub fn from_lock(lock: LockTime, confirmed_in: ChainPosition) -> Self {
+        match lock {
+            LockTime::Blocks(h) => {
+                let height = confirmed_in.height.to_consensus_u32();
+                let height = height.saturating_add(u32::from(h.value()));
+                let height =
+                    absolute::Height::from_consensus(height).unwrap_or(absolute::Height::MAX);
+                LockTimeBound { height, ..LockTimeBound::NONE }
+            }
+            LockTime::Time(t) => {
+                let seconds = u32::from(t.value()) * 512; // Max value fits in 25 bits.
+                let mtp = confirmed_in.median_time_past.to_consensus_u32().saturating_add(seconds);
+                let median_time_past = absolute::Mtp::from_consensus(mtp);
+                LockTimeBound { median_time_past, ..LockTimeBound::NONE }
+            }
+        }
+    }
+
+    /// Returns the bound satisfied only when both `self` and `other` are satisfied.
+    pub fn combine(self, other: LockTimeBound) -> Self {
+        LockTimeBound {
+            height: cmp::max(self.height, other.height),
+            median_time_past: cmp::max(self.median_time_past, other.median_time_past),
+        }
+    }
+
+    /// Returns true if a transaction with this bound may be included in a block at `included_in`.
+    pub fn is_satisfied_by(&self, included_in: ChainPosition) -> bool {
+        self.height <= included_in.height && self.median_time_past <= included_in.median_time_past
+    }
+}
+
 /// A relative lock time lock-by-blockheight value.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -207,6 +444,34 @@ impl Height {
     /// Returns the inner `u16` value.
     #[inline]
     pub fn value(self) -> u16 { self.0 }
+
+    /// Checked addition.
+    ///
+    /// Returns [None] if overflow occurred.
+    #[inline]
+    pub fn checked_add(self, rhs: Height) -> Option<Height> {
+        self.0.checked_add(rhs.0).map(Height)
+    }
+
+    /// Checked subtraction.
+    ///
+    /// Returns [None] if overflow occurred.
+    #[inline]
+    pub fn checked_sub(self, rhs: Height) -> Option<Height> {
+        self.0.checked_sub(rhs.0).map(Height)
+    }
+
+    /// Saturating addition.
+    ///
+    /// Computes `self + rhs`, saturating at [`Height::MAX`] instead of overflowing.
+    #[inline]
+    pub fn saturating_add(self, rhs: Height) -> Height { Height(self.0.saturating_add(rhs.0)) }
+
+    /// Saturating subtraction.
+    ///
+    /// Computes `self - rhs`, saturating at [`Height::MIN`] instead of overflowing.
+    #[inline]
+    pub fn saturating_sub(self, rhs: Height) -> Height { Height(self.0.saturating_sub(rhs.0)) }
 }
 
 impl From<u16> for Height {
@@ -252,16 +517,97 @@ impl Time {
     /// Will return an error if the input cannot be encoded in 16 bits.
     #[inline]
     pub fn from_seconds_ceil(seconds: u32) -> Result<Self, TimeOverflowError> {
-        if let Ok(interval) = u16::try_from((seconds + 511) / 512) {
+        if let Ok(interval) = u16::try_from((u64::from(seconds) + 511) / 512) {
             Ok(Time::from_512_second_intervals(interval))
         } else {
             Err(TimeOverflowError { seconds })
         }
     }
 
+    /// Create a [`Time`] from a [`Duration`], converting the duration into 512 second intervals
+    /// with ceiling division (any fraction of an interval, including sub-second parts, rounds up).
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the input cannot be encoded in 16 bits.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use core::time::Duration;
+    /// # use bitcoin::locktime::relative::Time;
+    /// let time = Time::from_duration_ceil(Duration::from_secs(513)).expect("valid time");
+    /// assert_eq!(time, Time::from_512_second_intervals(2));
+    /// ```
+    #[inline]
+    pub fn from_duration_ceil(duration: Duration) -> Result<Self, TimeOverflowError> {
+        let mut seconds = duration.as_secs();
+        if duration.subsec_nanos() > 0 {
+            seconds = seconds.saturating_add(1);
+        }
+        match u32::try_from(seconds) {
+            Ok(seconds) => Self::from_seconds_ceil(seconds),
+            Err(_) => Err(TimeOverflowError { seconds: u32::MAX }),
+        }
+    }
+
+    /// Create a [`Time`] from a [`Duration`], converting the duration into 512 second intervals
+    /// with floor division (any fraction of an interval is discarded).
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the input cannot be encoded in 16 bits.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use core::time::Duration;
+    /// # use bitcoin::locktime::relative::Time;
+    /// let time = Time::from_duration_floor(Duration::from_secs(1023)).expect("valid time");
+    /// assert_eq!(time, Time::from_512_second_intervals(1));
+    /// ```
+    #[inline]
+    pub fn from_duration_floor(duration: Duration) -> Result<Self, TimeOverflowError> {
+        let seconds = duration.as_secs();
+        if let Ok(interval) = u16::try_from(seconds / 512) {
+            Ok(Time::from_512_second_intervals(interval))
+        } else {
+            Err(TimeOverflowError { seconds: u32::try_from(seconds).unwrap_or(u32::MAX) })
+        }
+    }
+
+    /// Returns the [`Duration`] represented by this [`Time`] (the number of 512 second intervals
+    /// multiplied by 512).
+    #[inline]
+    pub fn to_duration(self) -> Duration { Duration::from_secs(u64::from(self.0) * 512) }
+
     /// Returns the inner `u16` value.
     #[inline]
     pub fn value(self) -> u16 { self.0 }
+
+    /// Checked addition.
+    ///
+    /// Returns [None] if overflow occurred.
+    #[inline]
+    pub fn checked_add(self, rhs: Time) -> Option<Time> { self.0.checked_add(rhs.0).map(Time) }
+
+    /// Checked subtraction.
+    ///
+    /// Returns [None] if overflow occurred.
+    #[inline]
+    pub fn checked_sub(self, rhs: Time) -> Option<Time> { self.0.checked_sub(rhs.0).map(Time) }
+
+    /// Saturating addition.
+    ///
+    /// Computes `self + rhs`, saturating at [`Time::MAX`] instead of overflowing.
+    #[inline]
+    pub fn saturating_add(self, rhs: Time) -> Time { Time(self.0.saturating_add(rhs.0)) }
+
+    /// Saturating subtraction.
+    ///
+    /// Computes `self - rhs`, saturating at [`Time::MIN`] instead of overflowing.
+    #[inline]
+    pub fn saturating_sub(self, rhs: Time) -> Time { Time(self.0.saturating_sub(rhs.0)) }
 }
 
 impl_parse_str_from_int_infallible!(Time, u16, from_512_second_intervals);
@@ -287,6 +633,25 @@ impl fmt::Display for TimeOverflowError {
 #[cfg(feature = "std")]
 impl std::error::Error for TimeOverflowError {}
 
+/// Tried to construct a relative lock time from a `u32` value with the disable flag set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisabledLockTimeError(u32);
+
+impl DisabledLockTimeError {
+    /// Returns the `u32` value whose disable flag was set, preventing it from being parsed as a
+    /// relative lock time.
+    pub fn disabled_locktime_value(&self) -> u32 { self.0 }
+}
+
+impl fmt::Display for DisabledLockTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "disable flag set in relative lock time value {:#010x}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DisabledLockTimeError {}
+
 /// Tried to satisfy a lock-by-blocktime lock using a height value.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -381,4 +746,193 @@ mod tests {
         let lock = LockTime::from(time);
         assert!(!lock.is_implied_by(LockTime::from(height)));
     }
+
+    fn position(height: u32, median_time_past: u32) -> ChainPosition {
+        ChainPosition::new(
+            absolute::Height::from_consensus(height).unwrap(),
+            absolute::Mtp::from_consensus(median_time_past),
+        )
+    }
+
+    #[test]
+    fn satisfied_by_chain_height() {
+        let lock = LockTime::from(Height::from(10));
+        let confirmed_in = position(100, 1_700_000_000);
+
+        assert!(!lock.is_satisfied_by_chain(confirmed_in, position(109, 1_700_100_000)));
+        assert!(lock.is_satisfied_by_chain(confirmed_in, position(110, 1_700_000_000)));
+        assert!(lock.is_satisfied_by_chain(confirmed_in, position(111, 1_700_000_000)));
+        // Tip below the confirmation height (e.g. during a reorg).
+        assert!(!lock.is_satisfied_by_chain(confirmed_in, position(99, 1_700_000_000)));
+    }
+
+    #[test]
+    fn satisfied_by_chain_time() {
+        let lock = LockTime::from(Time::from_512_second_intervals(2));
+        let confirmed_in = position(100, 1_700_000_000);
+
+        assert!(!lock.is_satisfied_by_chain(confirmed_in, position(200, 1_700_001_023)));
+        assert!(lock.is_satisfied_by_chain(confirmed_in, position(101, 1_700_001_024)));
+        assert!(lock.is_satisfied_by_chain(confirmed_in, position(101, 1_700_001_025)));
+        assert!(!lock.is_satisfied_by_chain(confirmed_in, position(101, 1_699_999_999)));
+
+        let max = LockTime::from(Time::MAX);
+        assert!(max.is_satisfied_by_chain(position(0, 0), position(1, u32::MAX)));
+    }
+
+    #[test]
+    fn lock_time_bound_matches_satisfied_by_chain() {
+        let confirmed_in = position(100, 1_700_000_000);
+        let locks = [
+            LockTime::from(Height::from(0)),
+            LockTime::from(Height::from(10)),
+            LockTime::from(Time::from_512_second_intervals(0)),
+            LockTime::from(Time::from_512_second_intervals(2)),
+        ];
+        let positions = [
+            position(100, 1_700_000_000),
+            position(109, 1_700_001_023),
+            position(110, 1_700_001_024),
+            position(111, 1_700_001_025),
+        ];
+
+        for lock in locks {
+            let bound = LockTimeBound::from_lock(lock, confirmed_in);
+            for included_in in positions {
+                let want = lock.is_satisfied_by_chain(confirmed_in, included_in);
+                assert_eq!(bound.is_satisfied_by(included_in), want);
+            }
+        }
+    }
+
+    #[test]
+    fn lock_time_bound_combine() {
+        let height = LockTimeBound::from_lock(LockTime::from(Height::from(10)), position(100, 0));
+        let time = LockTimeBound::from_lock(
+            LockTime::from(Time::from_512_second_intervals(1)),
+            position(90, 1_700_000_000),
+        );
+        let bound = height.combine(time).combine(LockTimeBound::NONE);
+
+        let want = position(110, 1_700_000_512);
+        assert_eq!(bound.height, want.height);
+        assert_eq!(bound.median_time_past, want.median_time_past);
+        assert!(!bound.is_satisfied_by(position(110, 1_700_000_511)));
+        assert!(!bound.is_satisfied_by(position(109, 1_700_000_512)));
+        assert!(bound.is_satisfied_by(position(110, 1_700_000_512)));
+    }
+
+    #[test]
+    fn same_unit() {
+        let height = LockTime::from(Height::from(10));
+        let time = LockTime::from(Time::from_512_second_intervals(70));
+
+        assert!(height.is_block_height());
+        assert!(!height.is_block_time());
+        assert!(time.is_block_time());
+        assert!(!time.is_block_height());
+
+        assert!(height.is_same_unit(LockTime::from(Height::from(11))));
+        assert!(time.is_same_unit(LockTime::from(Time::from_512_second_intervals(71))));
+        assert!(!height.is_same_unit(time));
+    }
+
+    #[test]
+    fn compare_same_unit() {
+        let height = LockTime::from(Height::from(10));
+        let time = LockTime::from(Time::from_512_second_intervals(70));
+
+        assert_eq!(
+            height.compare_same_unit(LockTime::from(Height::from(9))),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(height.compare_same_unit(height), Some(Ordering::Equal));
+        assert_eq!(
+            time.compare_same_unit(LockTime::from(Time::from_512_second_intervals(71))),
+            Some(Ordering::Less)
+        );
+        assert_eq!(height.compare_same_unit(time), None);
+        assert_eq!(time.compare_same_unit(height), None);
+    }
+
+    #[test]
+    fn consensus_round_trip() {
+        let height = LockTime::from(Height::from(10));
+        assert_eq!(LockTime::from_consensus(height.to_consensus_u32()), Ok(height));
+
+        let time = LockTime::from(Time::from_512_second_intervals(70));
+        assert_eq!(LockTime::from_consensus(time.to_consensus_u32()), Ok(time));
+    }
+
+    #[test]
+    fn from_consensus_matches_sequence() {
+        use crate::Sequence;
+
+        for n in [0, 10, 0x0040_0046, 0x0041_0046, 0xFFFF_0001, 0x8000_0000, 0xFFFF_FFFE] {
+            let lock = LockTime::from_consensus(n).ok();
+            assert_eq!(lock, Sequence::from_consensus(n).to_relative_lock_time());
+        }
+    }
+
+    #[test]
+    fn from_consensus_disabled() {
+        let err = LockTime::from_consensus(0x8000_000a).unwrap_err();
+        assert_eq!(err.disabled_locktime_value(), 0x8000_000a);
+    }
+
+    #[test]
+    fn time_from_duration() {
+        let interval = Time::from_512_second_intervals(1);
+
+        assert_eq!(Time::from_duration_ceil(Duration::from_secs(0)), Ok(Time::ZERO));
+        assert_eq!(Time::from_duration_ceil(Duration::from_secs(512)), Ok(interval));
+        assert_eq!(Time::from_duration_ceil(Duration::from_secs(1)), Ok(interval));
+        assert_eq!(Time::from_duration_ceil(Duration::from_nanos(1)), Ok(interval));
+        assert_eq!(Time::from_duration_floor(Duration::from_millis(1_023_999)), Ok(interval));
+        assert_eq!(Time::from_duration_floor(Duration::from_secs(511)), Ok(Time::ZERO));
+
+        assert_eq!(Time::from_duration_floor(Time::MAX.to_duration()), Ok(Time::MAX));
+        assert_eq!(Time::from_duration_ceil(Time::MAX.to_duration()), Ok(Time::MAX));
+        let too_long = Time::MAX.to_duration() + Duration::from_secs(1);
+        assert!(Time::from_duration_ceil(too_long).is_err());
+        assert!(Time::from_duration_ceil(Duration::from_secs(u64::MAX)).is_err());
+        assert!(Time::from_duration_floor(Duration::from_secs(u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn time_from_seconds_ceil_does_not_overflow() {
+        assert!(Time::from_seconds_ceil(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn height_checked_arithmetic() {
+        let height = Height::from(10);
+
+        assert_eq!(height.checked_add(Height::from(5)), Some(Height::from(15)));
+        assert_eq!(Height::MAX.checked_add(Height::from(1)), None);
+        assert_eq!(height.checked_sub(Height::from(5)), Some(Height::from(5)));
+        assert_eq!(height.checked_sub(Height::from(11)), None);
+
+        assert_eq!(Height::MAX.saturating_add(height), Height::MAX);
+        assert_eq!(height.saturating_sub(Height::from(11)), Height::MIN);
+    }
+
+    #[test]
+    fn time_checked_arithmetic() {
+        let time = Time::from_512_second_intervals(70);
+
+        assert_eq!(
+            time.checked_add(Time::from_512_second_intervals(10)),
+            Some(Time::from_512_second_intervals(80))
+        );
+        assert_eq!(Time::MAX.checked_add(Time::from_512_second_intervals(1)), None);
+        assert_eq!(
+            time.checked_sub(Time::from_512_second_intervals(10)),
+            Some(Time::from_512_second_intervals(60))
+        );
+        assert_eq!(time.checked_sub(Time::from_512_second_intervals(71)), None);
+
+        assert_eq!(Time::MAX.saturating_add(time), Time::MAX);
+        assert_eq!(time.saturating_sub(Time::from_512_second_intervals(71)), Time::MIN);
+    }
 }
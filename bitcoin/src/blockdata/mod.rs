@@ -9,6 +9,7 @@
 pub mod block;
 pub mod constants;
 pub mod fee_rate;
+pub mod header_chain;
 pub mod locktime;
 pub mod opcodes;
 pub mod script;
@@ -72,6 +72,36 @@ macro_rules! all_opcodes {
                 }
             }
         }
+
+        impl Opcode {
+            /// Returns the opcode named `name` as written by [`fmt::Display`], also accepting the
+            /// aliases defined in this module (e.g. `OP_TRUE`) and the names used by Bitcoin Core
+            /// (e.g. `OP_CHECKLOCKTIMEVERIFY` or `OP_16`).
+            pub(crate) fn from_name(name: &str) -> Option<Opcode> {
+                match name {
+                    "OP_0" | "OP_FALSE" => return Some(OP_PUSHBYTES_0),
+                    "OP_TRUE" => return Some(OP_PUSHNUM_1),
+                    "OP_1NEGATE" => return Some(OP_PUSHNUM_NEG1),
+                    "OP_NOP2" | "OP_CHECKLOCKTIMEVERIFY" => return Some(OP_CLTV),
+                    "OP_NOP3" | "OP_CHECKSEQUENCEVERIFY" => return Some(OP_CSV),
+                    _ => {}
+                }
+                if let Some(n) = name.strip_prefix("OP_") {
+                    if !n.starts_with('0') && n.bytes().all(|b| b.is_ascii_digit()) {
+                        match n.parse::<u8>() {
+                            Ok(n @ 1..=16) => return Some(Opcode::from(OP_PUSHNUM_1.code + n - 1)),
+                            _ => return None,
+                        }
+                    }
+                }
+                $(
+                    if name == stringify!($op) {
+                        return Some($op);
+                    }
+                )+
+                None
+            }
+        }
     }
 }
 
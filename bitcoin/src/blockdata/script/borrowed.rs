@@ -9,14 +9,17 @@ use hashes::Hash;
 use secp256k1::{Secp256k1, Verification};
 
 use super::PushBytes;
+use crate::blockdata::constants::MAX_SCRIPT_ELEMENT_SIZE;
 use crate::blockdata::fee_rate::FeeRate;
 use crate::blockdata::opcodes::all::*;
-use crate::blockdata::opcodes::{self, Opcode};
+use crate::blockdata::opcodes::{self, Class, ClassifyContext, Opcode};
+use crate::blockdata::script::witness_program::WitnessProgram;
 use crate::blockdata::script::witness_version::WitnessVersion;
 use crate::blockdata::script::{
-    bytes_to_asm_fmt, Builder, Instruction, InstructionIndices, Instructions, ScriptBuf,
-    ScriptHash, WScriptHash,
+    bytes_to_asm_fmt, Builder, Instruction, InstructionIndices, Instructions, InstructionsLossy,
+    ScriptBuf, ScriptHash, TapscriptError, WScriptHash,
 };
+use crate::blockdata::weight::Weight;
 use crate::consensus::Encodable;
 use crate::key::{PublicKey, UntweakedPublicKey, WPubkeyHash};
 use crate::policy::DUST_RELAY_TX_FEE;
@@ -209,6 +212,64 @@ impl Script {
         true
     }
 
+    /// Checks whether all pushes in the script use the smallest possible encoding.
+    ///
+    /// This is the `SCRIPT_VERIFY_MINIMALDATA` policy rule of Bitcoin Core applied to the whole
+    /// script, without executing it. Returns `false` for scripts with truncated pushes.
+    pub fn has_minimal_pushes(&self) -> bool {
+        self.instructions_minimal().all(|inst| inst.is_ok())
+    }
+
+    /// Checks whether the script is usable as a tapscript leaf ([BIP-342]).
+    ///
+    /// Returns `false` in the cases described in [`Script::check_tapscript`].
+    ///
+    /// [BIP-342]: <https://github.com/bitcoin/bips/blob/master/bip-0342.mediawiki>
+    pub fn is_valid_tapscript(&self) -> bool { self.check_tapscript().is_ok() }
+
+    /// Statically checks the script against the tapscript rules of [BIP-342].
+    ///
+    /// Rejects scripts that fail to parse, contain `OP_SUCCESSx` opcodes (which make the leaf
+    /// spendable by anyone), illegal opcodes, `OP_CHECKMULTISIG(VERIFY)`, pushes larger than
+    /// 520 bytes or unbalanced conditionals. Rules depending on execution, like the stack size
+    /// and signature operation budget, are not checked. Returns the first error found.
+    ///
+    /// [BIP-342]: <https://github.com/bitcoin/bips/blob/master/bip-0342.mediawiki>
+    pub fn check_tapscript(&self) -> Result<(), TapscriptError> {
+        let mut depth = 0usize;
+        for instruction in self.instruction_indices() {
+            let (position, instruction) = instruction.map_err(TapscriptError::Parse)?;
+            let op = match instruction {
+                Instruction::PushBytes(bytes) => {
+                    if bytes.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                        return Err(TapscriptError::PushTooLarge { position, len: bytes.len() });
+                    }
+                    continue;
+                }
+                Instruction::Op(op) => op,
+            };
+            match op.classify(ClassifyContext::TapScript) {
+                Class::SuccessOp => return Err(TapscriptError::OpSuccess { position, opcode: op }),
+                Class::IllegalOp =>
+                    return Err(TapscriptError::IllegalOpcode { position, opcode: op }),
+                _ => {}
+            }
+            match op {
+                OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY =>
+                    return Err(TapscriptError::CheckMultisig { position }),
+                OP_IF | OP_NOTIF => depth += 1,
+                OP_ELSE if depth == 0 => return Err(TapscriptError::UnbalancedConditional),
+                OP_ENDIF =>
+                    depth = depth.checked_sub(1).ok_or(TapscriptError::UnbalancedConditional)?,
+                _ => {}
+            }
+        }
+        if depth != 0 {
+            return Err(TapscriptError::UnbalancedConditional);
+        }
+        Ok(())
+    }
+
     /// Checks whether a script pubkey is a P2PK output.
     ///
     /// You can obtain the public key, if its valid,
@@ -291,6 +352,64 @@ impl Script {
         instructions.next().is_none()
     }
 
+    /// Checks whether a script is a timelocked single key script as generated by
+    /// [`ScriptBuf::new_cltv_timelocked`].
+    pub fn is_cltv_timelocked(&self) -> bool {
+        use Template::*;
+
+        self.matches_template(&[Num, Op(OP_CLTV), Op(OP_DROP), Key, Op(OP_CHECKSIG)])
+    }
+
+    /// Checks whether a script is a relative timelocked single key script as generated by
+    /// [`ScriptBuf::new_csv_timelocked`].
+    pub fn is_csv_timelocked(&self) -> bool {
+        use Template::*;
+
+        self.matches_template(&[Num, Op(OP_CSV), Op(OP_DROP), Key, Op(OP_CHECKSIG)])
+    }
+
+    /// Checks whether a script is a hash time locked contract as generated by
+    /// [`ScriptBuf::new_htlc`].
+    pub fn is_htlc(&self) -> bool {
+        use Template::*;
+
+        self.matches_template(&[
+            Op(OP_IF),
+            Op(OP_SHA256),
+            Hash32,
+            Op(OP_EQUALVERIFY),
+            Key,
+            Op(OP_ELSE),
+            Num,
+            Op(OP_CLTV),
+            Op(OP_DROP),
+            Key,
+            Op(OP_ENDIF),
+            Op(OP_CHECKSIG),
+        ])
+    }
+
+    /// Checks whether the minimally encoded instructions of this script match `template` exactly.
+    fn matches_template(&self, template: &[Template]) -> bool {
+        let mut instructions = self.instructions_minimal();
+        for expected in template {
+            let matches = match (instructions.next(), expected) {
+                (Some(Ok(Instruction::Op(op))), Template::Op(expected)) => op == *expected,
+                (Some(Ok(Instruction::Op(op))), Template::Num) => op.decode_pushnum().is_some(),
+                // Lock times may need 5 bytes to be encoded as a positive script number.
+                (Some(Ok(Instruction::PushBytes(bytes))), Template::Num) => bytes.len() <= 5,
+                (Some(Ok(Instruction::PushBytes(bytes))), Template::Key) =>
+                    bytes.len() == 33 || bytes.len() == 65,
+                (Some(Ok(Instruction::PushBytes(bytes))), Template::Hash32) => bytes.len() == 32,
+                _ => false,
+            };
+            if !matches {
+                return false;
+            }
+        }
+        instructions.next().is_none()
+    }
+
     /// Checks whether a script pubkey is a Segregated Witness (segwit) program.
     #[inline]
     pub fn is_witness_program(&self) -> bool {
@@ -311,6 +430,13 @@ impl Script {
             && script_len - 2 == push_opbyte as usize
     }
 
+    /// Returns the witness program if this script pubkey is a valid witness program.
+    ///
+    /// See [`WitnessProgram::from_script`] for the error cases.
+    pub fn witness_program(&self) -> Option<WitnessProgram> {
+        WitnessProgram::from_script(self).ok()
+    }
+
     /// Checks whether a script pubkey is a P2WSH output.
     #[inline]
     pub fn is_p2wsh(&self) -> bool {
@@ -344,6 +470,23 @@ impl Script {
         }
     }
 
+    /// Returns the data pushed after the `OP_RETURN` of a null data output.
+    ///
+    /// Returns `None` if the script is not an OP_RETURN output, fails to parse or contains
+    /// anything other than data pushes after the `OP_RETURN`. Push size limits are not checked.
+    pub fn op_return_data(&self) -> Option<Vec<&[u8]>> {
+        if !self.is_op_return() {
+            return None;
+        }
+        Script::from_bytes(&self.0[1..])
+            .instructions()
+            .map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes()),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Checks whether a script is trivially known to have no satisfying input.
     ///
     /// This method has potentially confusing semantics and an unclear purpose, so it's going to be
@@ -436,6 +579,34 @@ impl Script {
         crate::Amount::from_sat(sats)
     }
 
+    /// Returns the maximum weight of the data needed to spend an output with this script pubkey.
+    ///
+    /// The returned weight covers the `scriptSig` (including its length prefix) and the witness,
+    /// assuming signatures with the maximum DER encoded length and `SIGHASH_ALL`. It excludes the
+    /// outpoint and sequence, matching the `satisfaction_weight` expected by
+    /// [`crate::transaction::effective_value`].
+    ///
+    /// Only P2PKH (compressed key), P2WPKH and P2TR key-path spends are supported; returns `None`
+    /// for any other script since its satisfaction can not be derived from the script pubkey.
+    pub fn max_satisfaction_weight(&self) -> Option<Weight> {
+        if self.is_p2pkh() {
+            // <sig> <pubkey> in the scriptSig, preceded by its one byte length.
+            Some(Weight::from_non_witness_data_size(1 + 1 + 73 + 1 + 33))
+        } else if self.is_p2wpkh() {
+            // Empty scriptSig, witness is the element count followed by <sig> <pubkey>.
+            Some(
+                Weight::from_non_witness_data_size(1)
+                    + Weight::from_witness_data_size(1 + 1 + 73 + 1 + 33),
+            )
+        } else if self.is_p2tr() {
+            // Empty scriptSig, witness is the element count followed by a 64 byte Schnorr
+            // signature with a sighash byte.
+            Some(Weight::from_non_witness_data_size(1) + Weight::from_witness_data_size(1 + 1 + 65))
+        } else {
+            None
+        }
+    }
+
     /// Counts the sigops for this Script using accurate counting.
     ///
     /// In Bitcoin Core, there are two ways to count sigops, "accurate" and "legacy".
@@ -548,6 +719,15 @@ impl Script {
         InstructionIndices::from_instructions(self.instructions_minimal())
     }
 
+    /// Iterates over the script instructions and their indices without failing on malformed data.
+    ///
+    /// Non-minimal and truncated pushes are yielded as markers instead of errors, see
+    /// [`InstructionsLossy`].
+    #[inline]
+    pub fn instructions_lossy(&self) -> InstructionsLossy<'_> {
+        InstructionsLossy::from_instructions(self.instructions())
+    }
+
     /// Writes the human-readable assembly representation of the script to the formatter.
     pub fn fmt_asm(&self, f: &mut dyn fmt::Write) -> fmt::Result {
         bytes_to_asm_fmt(self.as_ref(), f)
@@ -608,6 +788,18 @@ impl Script {
     }
 }
 
+/// Element of a script template, see `Script::matches_template`.
+enum Template {
+    /// The given opcode.
+    Op(Opcode),
+    /// A small positive number, either a `OP_PUSHNUM_x` or a push of at most 5 bytes.
+    Num,
+    /// A push of a compressed or uncompressed public key.
+    Key,
+    /// A push of a 32 byte hash.
+    Hash32,
+}
+
 /// Iterator over bytes of a script
 pub struct Bytes<'a>(core::iter::Copied<core::slice::Iter<'a, u8>>);
 
@@ -4,13 +4,14 @@ use core::fmt;
 
 use secp256k1::XOnlyPublicKey;
 
-use crate::blockdata::locktime::absolute;
+use crate::blockdata::locktime::{absolute, relative};
 use crate::blockdata::opcodes::all::*;
 use crate::blockdata::opcodes::{self, Opcode};
 use crate::blockdata::script::{opcode_to_verify, write_scriptint, PushBytes, Script, ScriptBuf};
 use crate::blockdata::transaction::Sequence;
 use crate::key::PublicKey;
 use crate::prelude::*;
+use crate::Amount;
 
 /// An Object which can be used to construct a script piece by piece.
 #[derive(PartialEq, Eq, Clone)]
@@ -110,11 +111,29 @@ impl Builder {
         self.push_int(lock_time.to_consensus_u32().into())
     }
 
+    /// Adds instructions to push a relative lock time onto the stack, e.g. for `OP_CSV`.
+    pub fn push_relative_lock_time(self, lock_time: relative::LockTime) -> Builder {
+        self.push_int(lock_time.to_consensus_u32().into())
+    }
+
     /// Adds instructions to push a sequence number onto the stack.
     pub fn push_sequence(self, sequence: Sequence) -> Builder {
         self.push_int(sequence.to_consensus_u32().into())
     }
 
+    /// Adds instructions to push an amount in satoshis onto the stack.
+    ///
+    /// Note that script arithmetic only operates on numbers of at most 4 bytes, so amounts of
+    /// 2^31 satoshis or more can only be compared for equality.
+    ///
+    /// # Panics
+    ///
+    /// If `amount` is more than `i64::MAX` satoshis, which is far above [`Amount::MAX_MONEY`].
+    pub fn push_amount(self, amount: Amount) -> Builder {
+        let sat = i64::try_from(amount.to_sat()).expect("amount exceeds i64::MAX satoshis");
+        self.push_int(sat)
+    }
+
     /// Converts the `Builder` into `ScriptBuf`.
     pub fn into_script(self) -> ScriptBuf { self.0 }
 
@@ -1,5 +1,6 @@
 // SPDX-License-Identifier: CC0-1.0
 
+use crate::blockdata::opcodes::all::*;
 use crate::blockdata::opcodes::{self, Opcode};
 use crate::blockdata::script::{read_uint_iter, Error, PushBytes, Script, ScriptBuf, UintError};
 
@@ -252,3 +253,83 @@ impl<'a> Iterator for InstructionIndices<'a> {
 }
 
 impl core::iter::FusedIterator for InstructionIndices<'_> {}
+
+/// An instruction or a marker for malformed script data.
+///
+/// Yielded by [`Script::instructions_lossy`].
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum LossyInstruction<'a> {
+    /// A well formed instruction.
+    Instruction(Instruction<'a>),
+    /// A push that does not use the smallest possible encoding, with the pushed bytes.
+    NonMinimalPush(&'a PushBytes),
+    /// A push running past the end of the script, always the last item.
+    TruncatedPush {
+        /// The push opcode.
+        opcode: Opcode,
+        /// All bytes following the opcode, including any partial length prefix.
+        data: &'a [u8],
+    },
+}
+
+/// Iterator over script instructions and their positions which does not stop at malformed data.
+///
+/// Non-minimal pushes are yielded as [`LossyInstruction::NonMinimalPush`] and iteration
+/// continues, a truncated push is yielded as [`LossyInstruction::TruncatedPush`] and ends it.
+/// This is useful to render malformed scripts, e.g. in disassemblers.
+#[derive(Debug, Clone)]
+pub struct InstructionsLossy<'a> {
+    instructions: Instructions<'a>,
+    pos: usize,
+}
+
+impl<'a> InstructionsLossy<'a> {
+    /// Creates `Self` from a non-minimal `Instructions` iterator.
+    pub(super) fn from_instructions(instructions: Instructions<'a>) -> Self {
+        debug_assert!(!instructions.enforce_minimal);
+        InstructionsLossy { instructions, pos: 0 }
+    }
+
+    /// Views the remaining script as a slice.
+    ///
+    /// This is analogous to what [`core::str::Chars::as_str`] does.
+    #[inline]
+    pub fn as_script(&self) -> &'a Script { self.instructions.as_script() }
+}
+
+impl<'a> Iterator for InstructionsLossy<'a> {
+    /// The `usize` in the tuple represents index at which the returned item is located.
+    type Item = (usize, LossyInstruction<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let remaining = self.instructions.as_script().as_bytes();
+        let opcode = Opcode::from(*remaining.first()?);
+        let item = match self.instructions.next()? {
+            Ok(Instruction::PushBytes(bytes)) if !is_minimal_push(opcode, bytes.as_bytes()) =>
+                LossyInstruction::NonMinimalPush(bytes),
+            Ok(instruction) => LossyInstruction::Instruction(instruction),
+            // Non-minimal `Instructions` only fail on truncated pushes and then stop.
+            Err(_) => LossyInstruction::TruncatedPush { opcode, data: &remaining[1..] },
+        };
+        let pos = self.pos;
+        self.pos += remaining.len() - self.instructions.as_script().len();
+        Some((pos, item))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) { self.instructions.size_hint() }
+}
+
+impl core::iter::FusedIterator for InstructionsLossy<'_> {}
+
+/// Returns `true` if the push opcode is the smallest way to push `data`.
+pub(crate) fn is_minimal_push(opcode: Opcode, data: &[u8]) -> bool {
+    match data.len() {
+        0 => opcode == OP_PUSHBYTES_0,
+        1 if (1..=16).contains(&data[0]) || data[0] == 0x81 => false,
+        len @ 1..=75 => usize::from(opcode.to_u8()) == len,
+        76..=255 => opcode == OP_PUSHDATA1,
+        256..=65535 => opcode == OP_PUSHDATA2,
+        _ => true,
+    }
+}
@@ -0,0 +1,2249 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Bitcoin script interpreter.
+//!
+//! A pure Rust script execution engine modelled after Bitcoin Core's `interpreter.cpp`. It
+//! evaluates legacy, P2SH, segwit v0 and taproot (key path and tapscript) spends, with the set of
+//! enforced rules controlled by [`VerifyFlags`].
+//!
+//! Signature and lock time checks are delegated to a [`SignatureChecker`] so that the engine can
+//! be used without a transaction. [`TransactionSignatureChecker`] checks them against an input of
+//! a real transaction, and [`verify_input`] wires everything together.
+//!
+//! This module is not a consensus implementation, use the `bitcoinconsensus` feature when exact
+//! consensus compatibility is required.
+
+use core::{fmt, ops};
+
+use hashes::{hash160, ripemd160, sha1, sha256, sha256d, Hash};
+use secp256k1::{Message, Parity, Secp256k1, VerifyOnly, XOnlyPublicKey};
+
+use super::instruction::is_minimal_push;
+use super::{read_scriptbool, scriptint_parse, write_scriptint, Script};
+use crate::blockdata::locktime::absolute;
+use crate::blockdata::opcodes::all::*;
+use crate::blockdata::opcodes::{self, Opcode};
+use crate::blockdata::transaction::{Sequence, Transaction, TxOut};
+use crate::blockdata::witness::Witness;
+use crate::consensus::Encodable;
+use crate::prelude::*;
+use crate::sighash::{Annex, EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use crate::taproot::{
+    LeafVersion, TapLeafHash, TapNodeHash, TapTweakHash, TAPROOT_ANNEX_PREFIX,
+    TAPROOT_CONTROL_BASE_SIZE, TAPROOT_CONTROL_MAX_SIZE, TAPROOT_CONTROL_NODE_SIZE,
+    TAPROOT_LEAF_MASK, TAPROOT_LEAF_TAPSCRIPT,
+};
+
+/// Maximum number of bytes pushable to the stack.
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+
+/// Maximum number of non-push operations per (non-tapscript) script.
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+
+/// Maximum number of public keys per multisig.
+pub const MAX_PUBKEYS_PER_MULTISIG: i64 = 20;
+
+/// Maximum size of a (non-tapscript) script in bytes.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+
+/// Maximum number of elements on the stack and the altstack combined.
+pub const MAX_STACK_SIZE: usize = 1000;
+
+/// Validation weight consumed by each executed signature check in tapscript.
+pub const VALIDATION_WEIGHT_PER_SIGOP_PASSED: i64 = 50;
+
+/// Validation weight every tapscript starts with, on top of the witness size.
+pub const VALIDATION_WEIGHT_OFFSET: i64 = 50;
+
+/// Set of script verification rules to enforce.
+///
+/// Flags are combined with `|`, e.g. `VerifyFlags::P2SH | VerifyFlags::WITNESS`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VerifyFlags(u32);
+
+impl VerifyFlags {
+    /// No additional rules, i.e. pre-[BIP-16] validation.
+    ///
+    /// [BIP-16]: <https://github.com/bitcoin/bips/blob/master/bip-0016.mediawiki>
+    pub const NONE: Self = VerifyFlags(0);
+    /// Evaluate P2SH subscripts ([BIP-16]).
+    ///
+    /// [BIP-16]: <https://github.com/bitcoin/bips/blob/master/bip-0016.mediawiki>
+    pub const P2SH: Self = VerifyFlags(1 << 0);
+    /// Enforce strict signature and public key encodings.
+    pub const STRICTENC: Self = VerifyFlags(1 << 1);
+    /// Enforce strict DER signatures ([BIP-66]).
+    ///
+    /// [BIP-66]: <https://github.com/bitcoin/bips/blob/master/bip-0066.mediawiki>
+    pub const DERSIG: Self = VerifyFlags(1 << 2);
+    /// Require low S values in ECDSA signatures.
+    pub const LOW_S: Self = VerifyFlags(1 << 3);
+    /// Require the dummy `OP_CHECKMULTISIG` element to be empty ([BIP-147]).
+    ///
+    /// [BIP-147]: <https://github.com/bitcoin/bips/blob/master/bip-0147.mediawiki>
+    pub const NULLDUMMY: Self = VerifyFlags(1 << 4);
+    /// Require the scriptSig to only contain pushes.
+    pub const SIGPUSHONLY: Self = VerifyFlags(1 << 5);
+    /// Require minimal encodings for pushes and script numbers.
+    pub const MINIMALDATA: Self = VerifyFlags(1 << 6);
+    /// Fail on the upgradable `OP_NOPx` opcodes.
+    pub const DISCOURAGE_UPGRADABLE_NOPS: Self = VerifyFlags(1 << 7);
+    /// Require exactly one element on the stack after evaluation.
+    pub const CLEANSTACK: Self = VerifyFlags(1 << 8);
+    /// Enable `OP_CHECKLOCKTIMEVERIFY` ([BIP-65]).
+    ///
+    /// [BIP-65]: <https://github.com/bitcoin/bips/blob/master/bip-0065.mediawiki>
+    pub const CHECKLOCKTIMEVERIFY: Self = VerifyFlags(1 << 9);
+    /// Enable `OP_CHECKSEQUENCEVERIFY` ([BIP-112]).
+    ///
+    /// [BIP-112]: <https://github.com/bitcoin/bips/blob/master/bip-0112.mediawiki>
+    pub const CHECKSEQUENCEVERIFY: Self = VerifyFlags(1 << 10);
+    /// Evaluate segwit programs ([BIP-141]).
+    ///
+    /// [BIP-141]: <https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki>
+    pub const WITNESS: Self = VerifyFlags(1 << 11);
+    /// Fail on witness programs of unknown versions.
+    pub const DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM: Self = VerifyFlags(1 << 12);
+    /// Require the argument of `OP_IF`/`OP_NOTIF` in segwit v0 scripts to be minimal.
+    pub const MINIMALIF: Self = VerifyFlags(1 << 13);
+    /// Require failing signatures to be empty.
+    pub const NULLFAIL: Self = VerifyFlags(1 << 14);
+    /// Require compressed public keys in segwit v0 scripts.
+    pub const WITNESS_PUBKEYTYPE: Self = VerifyFlags(1 << 15);
+    /// Evaluate taproot spends ([BIP-341] and [BIP-342]).
+    ///
+    /// [BIP-341]: <https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki>
+    /// [BIP-342]: <https://github.com/bitcoin/bips/blob/master/bip-0342.mediawiki>
+    pub const TAPROOT: Self = VerifyFlags(1 << 16);
+    /// Fail on unknown taproot leaf versions.
+    pub const DISCOURAGE_UPGRADABLE_TAPROOT_VERSION: Self = VerifyFlags(1 << 17);
+    /// Fail on `OP_SUCCESSx` opcodes in tapscript.
+    pub const DISCOURAGE_OP_SUCCESS: Self = VerifyFlags(1 << 18);
+    /// Fail on unknown public key types in tapscript.
+    pub const DISCOURAGE_UPGRADABLE_PUBKEYTYPE: Self = VerifyFlags(1 << 19);
+
+    /// Rules enforced by consensus on all blocks since the taproot activation.
+    pub const CONSENSUS: Self = VerifyFlags(
+        Self::P2SH.0
+            | Self::DERSIG.0
+            | Self::NULLDUMMY.0
+            | Self::CHECKLOCKTIMEVERIFY.0
+            | Self::CHECKSEQUENCEVERIFY.0
+            | Self::WITNESS.0
+            | Self::TAPROOT.0,
+    );
+
+    /// Rules enforced by Bitcoin Core's mempool policy.
+    pub const STANDARD: Self = VerifyFlags(
+        Self::CONSENSUS.0
+            | Self::STRICTENC.0
+            | Self::MINIMALDATA.0
+            | Self::DISCOURAGE_UPGRADABLE_NOPS.0
+            | Self::CLEANSTACK.0
+            | Self::MINIMALIF.0
+            | Self::NULLFAIL.0
+            | Self::LOW_S.0
+            | Self::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM.0
+            | Self::WITNESS_PUBKEYTYPE.0
+            | Self::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION.0
+            | Self::DISCOURAGE_OP_SUCCESS.0
+            | Self::DISCOURAGE_UPGRADABLE_PUBKEYTYPE.0,
+    );
+
+    /// Creates flags from their raw bit representation.
+    pub const fn from_bits(bits: u32) -> Self { VerifyFlags(bits) }
+
+    /// Returns the raw bit representation of the flags.
+    pub const fn bits(self) -> u32 { self.0 }
+
+    /// Returns `true` if all flags in `other` are also set in `self`.
+    pub const fn contains(self, other: VerifyFlags) -> bool { self.0 & other.0 == other.0 }
+}
+
+impl ops::BitOr for VerifyFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self { VerifyFlags(self.0 | rhs.0) }
+}
+
+impl ops::BitOrAssign for VerifyFlags {
+    fn bitor_assign(&mut self, rhs: Self) { self.0 |= rhs.0 }
+}
+
+/// The context a script is executed in, this determines the rules and sighash algorithm used.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SigVersion {
+    /// Legacy scripts: scriptSig, scriptPubKey and P2SH redeem scripts.
+    Base,
+    /// Segwit v0 scripts: P2WPKH and P2WSH.
+    WitnessV0,
+    /// Taproot key path spends.
+    Taproot,
+    /// Taproot script path spends of leaf version `0xc0`.
+    Tapscript,
+}
+
+/// Taproot specific data committed to by schnorr signatures.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TaprootExecution<'a> {
+    /// The annex, including its `0x50` prefix, if present in the witness.
+    pub annex: Option<&'a [u8]>,
+    /// The executed leaf and the opcode position of the last executed `OP_CODESEPARATOR`
+    /// (`0xFFFFFFFF` if none), `None` for key path spends.
+    pub leaf_hash_code_separator: Option<(TapLeafHash, u32)>,
+}
+
+/// Checks signatures and lock times on behalf of the interpreter.
+///
+/// All methods default to failing the check, which is suitable for evaluating scripts that are
+/// not tied to a transaction.
+pub trait SignatureChecker {
+    /// Checks an ECDSA `signature` (DER with the sighash byte appended) against `pubkey`.
+    ///
+    /// For [`SigVersion::Base`] the `script_code` has already had the signatures removed but
+    /// may still contain `OP_CODESEPARATOR`s.
+    fn check_ecdsa_signature(
+        &mut self,
+        _signature: &[u8],
+        _pubkey: &[u8],
+        _script_code: &Script,
+        _sig_version: SigVersion,
+    ) -> bool {
+        false
+    }
+
+    /// Checks a 64 or 65 byte schnorr `signature` against a 32 byte x-only `pubkey`.
+    fn check_schnorr_signature(
+        &mut self,
+        _signature: &[u8],
+        _pubkey: &[u8],
+        _sig_version: SigVersion,
+        _execution: &TaprootExecution,
+    ) -> bool {
+        false
+    }
+
+    /// Checks that the `lock_time` required by `OP_CHECKLOCKTIMEVERIFY` is satisfied.
+    fn check_lock_time(&self, _lock_time: absolute::LockTime) -> bool { false }
+
+    /// Checks that the `sequence` required by `OP_CHECKSEQUENCEVERIFY` is satisfied.
+    fn check_sequence(&self, _sequence: Sequence) -> bool { false }
+}
+
+/// A [`SignatureChecker`] that fails all signature and lock time checks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NoSignatureChecker;
+
+impl SignatureChecker for NoSignatureChecker {}
+
+/// A [`SignatureChecker`] validating signatures against an input of a transaction.
+pub struct TransactionSignatureChecker<'a> {
+    cache: SighashCache<&'a Transaction>,
+    input_index: usize,
+    prevouts: &'a [TxOut],
+    secp: Secp256k1<VerifyOnly>,
+}
+
+impl<'a> TransactionSignatureChecker<'a> {
+    /// Creates a checker for input `input_index` of `tx`.
+    ///
+    /// `prevouts` must contain the outputs spent by all inputs of `tx`, in order.
+    pub fn new(tx: &'a Transaction, input_index: usize, prevouts: &'a [TxOut]) -> Self {
+        TransactionSignatureChecker {
+            cache: SighashCache::new(tx),
+            input_index,
+            prevouts,
+            secp: Secp256k1::verification_only(),
+        }
+    }
+
+    fn tx(&self) -> &Transaction { self.cache.transaction() }
+}
+
+impl<'a> SignatureChecker for TransactionSignatureChecker<'a> {
+    /// Note that segwit v0 signatures with an undefined sighash type are not supported and always
+    /// fail, these are non-standard.
+    fn check_ecdsa_signature(
+        &mut self,
+        signature: &[u8],
+        pubkey: &[u8],
+        script_code: &Script,
+        sig_version: SigVersion,
+    ) -> bool {
+        let (sighash_type, der) = match signature.split_last() {
+            Some((sighash_type, der)) => (u32::from(*sighash_type), der),
+            None => return false,
+        };
+        let mut sig = match secp256k1::ecdsa::Signature::from_der_lax(der) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        };
+        sig.normalize_s();
+        let pubkey = match secp256k1::PublicKey::from_slice(pubkey) {
+            Ok(pubkey) => pubkey,
+            Err(_) => return false,
+        };
+        let digest = match sig_version {
+            SigVersion::Base => {
+                let script_code = remove_code_separators(script_code);
+                match self.cache.legacy_signature_hash(self.input_index, &script_code, sighash_type)
+                {
+                    Ok(sighash) => sighash.to_byte_array(),
+                    Err(_) => return false,
+                }
+            }
+            SigVersion::WitnessV0 => {
+                let value = match self.prevouts.get(self.input_index) {
+                    Some(prevout) => prevout.value,
+                    None => return false,
+                };
+                let sighash_type = match EcdsaSighashType::from_standard(sighash_type) {
+                    Ok(sighash_type) => sighash_type,
+                    Err(_) => return false,
+                };
+                match self.cache.p2wsh_signature_hash(
+                    self.input_index,
+                    script_code,
+                    value,
+                    sighash_type,
+                ) {
+                    Ok(sighash) => sighash.to_byte_array(),
+                    Err(_) => return false,
+                }
+            }
+            SigVersion::Taproot | SigVersion::Tapscript => return false,
+        };
+        let msg = Message::from_digest(digest);
+        self.secp.verify_ecdsa(&msg, &sig, &pubkey).is_ok()
+    }
+
+    fn check_schnorr_signature(
+        &mut self,
+        signature: &[u8],
+        pubkey: &[u8],
+        _sig_version: SigVersion,
+        execution: &TaprootExecution,
+    ) -> bool {
+        let (sig, sighash_type) = match signature.len() {
+            64 => (signature, TapSighashType::Default),
+            65 => match TapSighashType::from_consensus_u8(signature[64]) {
+                Ok(sighash_type) => (&signature[..64], sighash_type),
+                Err(_) => return false,
+            },
+            _ => return false,
+        };
+        let sig = match secp256k1::schnorr::Signature::from_slice(sig) {
+            Ok(sig) => sig,
+            Err(_) => return false,
+        };
+        let pubkey = match XOnlyPublicKey::from_slice(pubkey) {
+            Ok(pubkey) => pubkey,
+            Err(_) => return false,
+        };
+        let annex = match execution.annex.map(Annex::new) {
+            Some(Ok(annex)) => Some(annex),
+            Some(Err(_)) => return false,
+            None => None,
+        };
+        let sighash = match self.cache.taproot_signature_hash(
+            self.input_index,
+            &Prevouts::All(self.prevouts),
+            annex,
+            execution.leaf_hash_code_separator,
+            sighash_type,
+        ) {
+            Ok(sighash) => sighash,
+            Err(_) => return false,
+        };
+        let msg = Message::from_digest(sighash.to_byte_array());
+        self.secp.verify_schnorr(&sig, &msg, &pubkey).is_ok()
+    }
+
+    fn check_lock_time(&self, lock_time: absolute::LockTime) -> bool {
+        let tx = self.tx();
+        if !tx.lock_time.is_same_unit(lock_time) {
+            return false;
+        }
+        if lock_time.to_consensus_u32() > tx.lock_time.to_consensus_u32() {
+            return false;
+        }
+        // A final sequence number disables the lock time of the transaction.
+        match tx.input.get(self.input_index) {
+            Some(input) => input.sequence != Sequence::MAX,
+            None => false,
+        }
+    }
+
+    fn check_sequence(&self, sequence: Sequence) -> bool {
+        const TYPE_FLAG: u32 = 1 << 22;
+        const MASK: u32 = TYPE_FLAG | 0x0000_ffff;
+
+        let tx = self.tx();
+        // Like Bitcoin Core, compare the version as an unsigned number.
+        if (tx.version.0 as u32) < 2 {
+            return false;
+        }
+        let tx_sequence = match tx.input.get(self.input_index) {
+            Some(input) => input.sequence,
+            None => return false,
+        };
+        if !tx_sequence.is_relative_lock_time() {
+            return false;
+        }
+        let tx_masked = tx_sequence.to_consensus_u32() & MASK;
+        let masked = sequence.to_consensus_u32() & MASK;
+        if (tx_masked < TYPE_FLAG) != (masked < TYPE_FLAG) {
+            return false;
+        }
+        masked <= tx_masked
+    }
+}
+
+/// Verifies input `input_index` of `tx` against the outputs it spends.
+///
+/// `prevouts` must contain the outputs spent by all inputs of `tx`, in order.
+pub fn verify_input(
+    tx: &Transaction,
+    input_index: usize,
+    prevouts: &[TxOut],
+    flags: VerifyFlags,
+) -> Result<(), Error> {
+    if prevouts.len() != tx.input.len() {
+        return Err(Error::PrevoutsSize { inputs: tx.input.len(), prevouts: prevouts.len() });
+    }
+    let input = tx.input.get(input_index).ok_or(Error::InputIndex(input_index))?;
+    let mut checker = TransactionSignatureChecker::new(tx, input_index, prevouts);
+    verify_script(
+        &input.script_sig,
+        &prevouts[input_index].script_pubkey,
+        &input.witness,
+        flags,
+        &mut checker,
+    )
+}
+
+/// Verifies that `script_sig` and `witness` satisfy `script_pubkey`.
+///
+/// [`VerifyFlags::CLEANSTACK`] is only meaningful together with [`VerifyFlags::P2SH`] and
+/// [`VerifyFlags::WITNESS`], and [`VerifyFlags::TAPROOT`] requires [`VerifyFlags::WITNESS`].
+pub fn verify_script<C: SignatureChecker + ?Sized>(
+    script_sig: &Script,
+    script_pubkey: &Script,
+    witness: &Witness,
+    flags: VerifyFlags,
+    checker: &mut C,
+) -> Result<(), Error> {
+    if flags.contains(VerifyFlags::SIGPUSHONLY) && !script_sig.is_push_only() {
+        return Err(Error::SigPushOnly);
+    }
+
+    let mut stack = Vec::new();
+    eval_script(&mut stack, script_sig, flags, checker, SigVersion::Base)?;
+    let stack_copy = if flags.contains(VerifyFlags::P2SH) { stack.clone() } else { Vec::new() };
+    eval_script(&mut stack, script_pubkey, flags, checker, SigVersion::Base)?;
+    if !stack.last().map_or(false, |top| read_scriptbool(top)) {
+        return Err(Error::EvalFalse);
+    }
+
+    let mut had_witness = false;
+    if flags.contains(VerifyFlags::WITNESS) {
+        if let Some((version, program)) = witness_program(script_pubkey) {
+            had_witness = true;
+            if !script_sig.is_empty() {
+                return Err(Error::WitnessMalleated);
+            }
+            verify_witness_program(witness, version, program, flags, checker, false)?;
+            stack.truncate(1);
+        }
+    }
+
+    if flags.contains(VerifyFlags::P2SH) && script_pubkey.is_p2sh() {
+        if !script_sig.is_push_only() {
+            return Err(Error::SigPushOnly);
+        }
+        stack = stack_copy;
+        let redeem_script = stack.pop().ok_or(Error::EvalFalse)?;
+        let redeem_script = Script::from_bytes(&redeem_script);
+        eval_script(&mut stack, redeem_script, flags, checker, SigVersion::Base)?;
+        if !stack.last().map_or(false, |top| read_scriptbool(top)) {
+            return Err(Error::EvalFalse);
+        }
+
+        if flags.contains(VerifyFlags::WITNESS) {
+            if let Some((version, program)) = witness_program(redeem_script) {
+                had_witness = true;
+                // The scriptSig must be exactly a single push of the redeem script.
+                if script_sig.as_bytes() != push_bytes(redeem_script.as_bytes()) {
+                    return Err(Error::WitnessMalleatedP2sh);
+                }
+                verify_witness_program(witness, version, program, flags, checker, true)?;
+                stack.truncate(1);
+            }
+        }
+    }
+
+    if flags.contains(VerifyFlags::CLEANSTACK) && stack.len() != 1 {
+        return Err(Error::CleanStack);
+    }
+
+    if flags.contains(VerifyFlags::WITNESS) && !had_witness && !witness.is_empty() {
+        return Err(Error::WitnessUnexpected);
+    }
+
+    Ok(())
+}
+
+/// Evaluates `script` on top of `stack`.
+///
+/// This does not support [`SigVersion::Tapscript`] since tapscript execution depends on data
+/// from the witness, use [`verify_script`] for those.
+pub fn eval_script<C: SignatureChecker + ?Sized>(
+    stack: &mut Vec<Vec<u8>>,
+    script: &Script,
+    flags: VerifyFlags,
+    checker: &mut C,
+    sig_version: SigVersion,
+) -> Result<(), Error> {
+    if sig_version == SigVersion::Tapscript {
+        return Err(Error::UnsupportedSigVersion);
+    }
+    let mut exec = ExecState::default();
+    eval(stack, script, flags, checker, sig_version, &mut exec)
+}
+
+/// Returns the version and program of a witness program script.
+fn witness_program(script: &Script) -> Option<(u8, &[u8])> {
+    if !script.is_witness_program() {
+        return None;
+    }
+    let bytes = script.as_bytes();
+    let version = match bytes[0] {
+        0 => 0,
+        op => op - (OP_PUSHNUM_1.to_u8() - 1),
+    };
+    Some((version, &bytes[2..]))
+}
+
+fn verify_witness_program<C: SignatureChecker + ?Sized>(
+    witness: &Witness,
+    version: u8,
+    program: &[u8],
+    flags: VerifyFlags,
+    checker: &mut C,
+    is_p2sh: bool,
+) -> Result<(), Error> {
+    let mut stack = witness.to_vec();
+    match (version, program.len()) {
+        (0, 32) => {
+            let witness_script = stack.pop().ok_or(Error::WitnessProgramWitnessEmpty)?;
+            if sha256::Hash::hash(&witness_script).as_byte_array()[..] != *program {
+                return Err(Error::WitnessProgramMismatch);
+            }
+            let mut exec = ExecState::default();
+            execute_witness_script(
+                stack,
+                Script::from_bytes(&witness_script),
+                flags,
+                checker,
+                SigVersion::WitnessV0,
+                &mut exec,
+            )
+        }
+        (0, 20) => {
+            if stack.len() != 2 {
+                return Err(Error::WitnessProgramMismatch);
+            }
+            let mut script_code = vec![OP_DUP.to_u8(), OP_HASH160.to_u8(), 20];
+            script_code.extend_from_slice(program);
+            script_code.extend_from_slice(&[OP_EQUALVERIFY.to_u8(), OP_CHECKSIG.to_u8()]);
+            let mut exec = ExecState::default();
+            execute_witness_script(
+                stack,
+                Script::from_bytes(&script_code),
+                flags,
+                checker,
+                SigVersion::WitnessV0,
+                &mut exec,
+            )
+        }
+        (0, _) => Err(Error::WitnessProgramWrongLength),
+        (1, 32) if !is_p2sh => {
+            if !flags.contains(VerifyFlags::TAPROOT) {
+                return Ok(());
+            }
+            if stack.is_empty() {
+                return Err(Error::WitnessProgramWitnessEmpty);
+            }
+            let mut annex = None;
+            if stack.len() >= 2
+                && stack.last().and_then(|e| e.first()) == Some(&TAPROOT_ANNEX_PREFIX)
+            {
+                annex = witness.last();
+                stack.pop();
+            }
+
+            if stack.len() == 1 {
+                // Key path spend.
+                let execution = TaprootExecution { annex, leaf_hash_code_separator: None };
+                return check_schnorr(&stack[0], program, SigVersion::Taproot, &execution, checker);
+            }
+
+            // Script path spend.
+            let control = stack.pop().expect("at least two elements");
+            let script = stack.pop().expect("at least two elements");
+            if control.len() < TAPROOT_CONTROL_BASE_SIZE
+                || control.len() > TAPROOT_CONTROL_MAX_SIZE
+                || (control.len() - TAPROOT_CONTROL_BASE_SIZE) % TAPROOT_CONTROL_NODE_SIZE != 0
+            {
+                return Err(Error::TaprootWrongControlSize);
+            }
+            let script = Script::from_bytes(&script);
+            if !verify_taproot_commitment(&control, program, script) {
+                return Err(Error::WitnessProgramMismatch);
+            }
+
+            if control[0] & TAPROOT_LEAF_MASK == TAPROOT_LEAF_TAPSCRIPT {
+                let leaf_hash = TapLeafHash::from_script(script, LeafVersion::TapScript);
+                let mut exec = ExecState {
+                    execution: TaprootExecution {
+                        annex,
+                        leaf_hash_code_separator: Some((leaf_hash, u32::MAX)),
+                    },
+                    validation_weight_left: witness.size() as i64 + VALIDATION_WEIGHT_OFFSET,
+                };
+                return execute_witness_script(
+                    stack,
+                    script,
+                    flags,
+                    checker,
+                    SigVersion::Tapscript,
+                    &mut exec,
+                );
+            }
+            if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION) {
+                return Err(Error::DiscourageUpgradableTaprootVersion);
+            }
+            Ok(())
+        }
+        _ => {
+            if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM) {
+                return Err(Error::DiscourageUpgradableWitnessProgram);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Checks that `control` proves the commitment of the output key `program` to `script`.
+///
+/// Unlike [`ControlBlock::decode`](crate::taproot::ControlBlock::decode) this accepts any leaf version, including the ones that can't be told
+/// apart from an annex, as consensus does.
+fn verify_taproot_commitment(control: &[u8], program: &[u8], script: &Script) -> bool {
+    let internal_key = match XOnlyPublicKey::from_slice(&control[1..TAPROOT_CONTROL_BASE_SIZE]) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let output_key = match XOnlyPublicKey::from_slice(program) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    let parity = match control[0] & 1 {
+        0 => Parity::Even,
+        _ => Parity::Odd,
+    };
+
+    let mut engine = TapLeafHash::engine();
+    (control[0] & TAPROOT_LEAF_MASK).consensus_encode(&mut engine).expect("engines don't error");
+    script.consensus_encode(&mut engine).expect("engines don't error");
+    let mut hash = TapNodeHash::from(TapLeafHash::from_engine(engine));
+    for node in control[TAPROOT_CONTROL_BASE_SIZE..].chunks_exact(TAPROOT_CONTROL_NODE_SIZE) {
+        let node = TapNodeHash::from_slice(node).expect("node size is 32");
+        hash = TapNodeHash::from_node_hashes(hash, node);
+    }
+    let tweak = TapTweakHash::from_key_and_tweak(internal_key, Some(hash)).to_scalar();
+    internal_key.tweak_add_check(&Secp256k1::verification_only(), &output_key, parity, tweak)
+}
+
+fn execute_witness_script<C: SignatureChecker + ?Sized>(
+    mut stack: Vec<Vec<u8>>,
+    script: &Script,
+    flags: VerifyFlags,
+    checker: &mut C,
+    sig_version: SigVersion,
+    exec: &mut ExecState,
+) -> Result<(), Error> {
+    if sig_version == SigVersion::Tapscript {
+        // Any OP_SUCCESSx makes the script succeed unconditionally, even if unreachable.
+        for op in Ops::new(script.as_bytes()) {
+            let (opcode, _) = op?;
+            if opcode.classify(opcodes::ClassifyContext::TapScript) == opcodes::Class::SuccessOp {
+                if flags.contains(VerifyFlags::DISCOURAGE_OP_SUCCESS) {
+                    return Err(Error::DiscourageOpSuccess);
+                }
+                return Ok(());
+            }
+        }
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(Error::StackSize);
+        }
+    }
+
+    if stack.iter().any(|elem| elem.len() > MAX_SCRIPT_ELEMENT_SIZE) {
+        return Err(Error::PushSize);
+    }
+
+    eval(&mut stack, script, flags, checker, sig_version, exec)?;
+
+    if stack.len() != 1 {
+        return Err(Error::CleanStack);
+    }
+    if !read_scriptbool(&stack[0]) {
+        return Err(Error::EvalFalse);
+    }
+    Ok(())
+}
+
+/// State carried through the execution of a single script.
+#[derive(Default)]
+struct ExecState<'a> {
+    execution: TaprootExecution<'a>,
+    validation_weight_left: i64,
+}
+
+/// Iterator over raw operations of a script, yielding the opcode and any pushed data.
+struct Ops<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Ops<'a> {
+    fn new(bytes: &'a [u8]) -> Self { Ops { bytes, pos: 0 } }
+
+    fn position(&self) -> usize { self.pos }
+
+    fn read_len(&mut self, size: usize) -> Result<usize, Error> {
+        let bytes = self.bytes.get(self.pos..self.pos + size).ok_or(Error::BadOpcode)?;
+        self.pos += size;
+        Ok(bytes.iter().rev().fold(0, |acc, &b| (acc << 8) | usize::from(b)))
+    }
+}
+
+impl<'a> Iterator for Ops<'a> {
+    type Item = Result<(Opcode, &'a [u8]), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let byte = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        let opcode = Opcode::from(byte);
+        let len = match opcode {
+            OP_PUSHDATA1 => self.read_len(1),
+            OP_PUSHDATA2 => self.read_len(2),
+            OP_PUSHDATA4 => self.read_len(4),
+            _ if byte < OP_PUSHDATA1.to_u8() => Ok(usize::from(byte)),
+            _ => Ok(0),
+        };
+        let data = len.and_then(|len| {
+            let end = self.pos.checked_add(len).ok_or(Error::BadOpcode)?;
+            let data = self.bytes.get(self.pos..end).ok_or(Error::BadOpcode)?;
+            self.pos = end;
+            Ok(data)
+        });
+        if data.is_err() {
+            // Stop iterating after a truncated push.
+            self.pos = self.bytes.len();
+        }
+        Some(data.map(|data| (opcode, data)))
+    }
+}
+
+/// Parses a script number of at most `max_size` bytes.
+fn read_num(v: &[u8], require_minimal: bool, max_size: usize) -> Result<i64, Error> {
+    let last = match v.last() {
+        Some(last) => last,
+        None => return Ok(0),
+    };
+    if v.len() > max_size {
+        return Err(Error::NumberOverflow);
+    }
+    if require_minimal && (*last & 0x7f) == 0 && (v.len() <= 1 || (v[v.len() - 2] & 0x80) == 0) {
+        return Err(Error::NonMinimalNumber);
+    }
+    Ok(scriptint_parse(v))
+}
+
+fn push_num(stack: &mut Vec<Vec<u8>>, n: i64) {
+    let mut buf = [0u8; 8];
+    let len = write_scriptint(&mut buf, n);
+    stack.push(buf[..len].to_vec());
+}
+
+fn push_bool(stack: &mut Vec<Vec<u8>>, b: bool) { stack.push(if b { vec![1] } else { vec![] }) }
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, Error> {
+    stack.pop().ok_or(Error::InvalidStackOperation)
+}
+
+/// Returns the stack element `depth` positions from the top, the top element being at depth 1.
+fn top(stack: &[Vec<u8>], depth: usize) -> Result<&Vec<u8>, Error> {
+    stack.len().checked_sub(depth).map(|i| &stack[i]).ok_or(Error::InvalidStackOperation)
+}
+
+/// Removes all `OP_CODESEPARATOR`s from `script`, leaving any trailing garbage in place.
+fn remove_code_separators(script: &Script) -> crate::ScriptBuf {
+    let bytes = script.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut ops = Ops::new(bytes);
+    let mut start = 0;
+    while let Some(Ok((opcode, _))) = ops.next() {
+        if opcode != OP_CODESEPARATOR {
+            out.extend_from_slice(&bytes[start..ops.position()]);
+        }
+        start = ops.position();
+    }
+    out.extend_from_slice(&bytes[start..]);
+    crate::ScriptBuf::from_bytes(out)
+}
+
+/// Returns the bytes of a push of `data`, never using `OP_0` or `OP_PUSHNUM_x` like Core's
+/// `CScript() << data`.
+fn push_bytes(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 3);
+    match data.len() {
+        len @ 0..=75 => out.push(len as u8),
+        len @ 76..=255 => out.extend_from_slice(&[OP_PUSHDATA1.to_u8(), len as u8]),
+        len => {
+            out.push(OP_PUSHDATA2.to_u8());
+            out.extend_from_slice(&(len as u16).to_le_bytes());
+        }
+    }
+    out.extend_from_slice(data);
+    out
+}
+
+/// Removes all pushes of `sig` found at opcode boundaries, like Core's `FindAndDelete`.
+fn find_and_delete(script: &mut Vec<u8>, sig: &[u8]) {
+    let pattern = push_bytes(sig);
+    let mut result = Vec::with_capacity(script.len());
+    let mut found = false;
+    let (mut pos, mut copied) = (0, 0);
+    loop {
+        result.extend_from_slice(&script[copied..pos]);
+        while script[pos..].starts_with(&pattern) {
+            pos += pattern.len();
+            found = true;
+        }
+        copied = pos;
+        let mut ops = Ops::new(script);
+        ops.pos = pos;
+        match ops.next() {
+            Some(Ok(_)) => pos = ops.position(),
+            _ => break,
+        }
+    }
+    if found {
+        result.extend_from_slice(&script[copied..]);
+        *script = result;
+    }
+}
+
+/// Checks that a signature is a strictly encoded DER signature with a sighash byte ([BIP-66]).
+///
+/// [BIP-66]: <https://github.com/bitcoin/bips/blob/master/bip-0066.mediawiki>
+fn is_valid_signature_encoding(sig: &[u8]) -> bool {
+    let len = sig.len();
+    if !(9..=73).contains(&len) || sig[0] != 0x30 || usize::from(sig[1]) != len - 3 {
+        return false;
+    }
+    let len_r = usize::from(sig[3]);
+    if 5 + len_r >= len {
+        return false;
+    }
+    let len_s = usize::from(sig[5 + len_r]);
+    if len_r + len_s + 7 != len {
+        return false;
+    }
+    if sig[2] != 0x02 || len_r == 0 || sig[4] & 0x80 != 0 {
+        return false;
+    }
+    if len_r > 1 && sig[4] == 0x00 && sig[5] & 0x80 == 0 {
+        return false;
+    }
+    if sig[len_r + 4] != 0x02 || len_s == 0 || sig[len_r + 6] & 0x80 != 0 {
+        return false;
+    }
+    if len_s > 1 && sig[len_r + 6] == 0x00 && sig[len_r + 7] & 0x80 == 0 {
+        return false;
+    }
+    true
+}
+
+fn is_low_s(sig: &[u8]) -> bool {
+    let der = &sig[..sig.len() - 1];
+    match secp256k1::ecdsa::Signature::from_der_lax(der) {
+        Ok(parsed) => {
+            let mut normalized = parsed;
+            normalized.normalize_s();
+            normalized == parsed
+        }
+        Err(_) => false,
+    }
+}
+
+fn check_signature_encoding(sig: &[u8], flags: VerifyFlags) -> Result<(), Error> {
+    // An empty signature is a compact way to provide an invalid signature.
+    if sig.is_empty() {
+        return Ok(());
+    }
+    let strict = VerifyFlags::DERSIG.0 | VerifyFlags::LOW_S.0 | VerifyFlags::STRICTENC.0;
+    if flags.0 & strict != 0 && !is_valid_signature_encoding(sig) {
+        return Err(Error::SigDer);
+    }
+    if flags.contains(VerifyFlags::LOW_S) && !is_low_s(sig) {
+        return Err(Error::SigHighS);
+    }
+    if flags.contains(VerifyFlags::STRICTENC) {
+        let sighash_type = sig[sig.len() - 1] & !0x80;
+        if !(1..=3).contains(&sighash_type) {
+            return Err(Error::SigHashType);
+        }
+    }
+    Ok(())
+}
+
+fn check_pubkey_encoding(
+    pubkey: &[u8],
+    flags: VerifyFlags,
+    sig_version: SigVersion,
+) -> Result<(), Error> {
+    let compressed = pubkey.len() == 33 && (pubkey[0] == 0x02 || pubkey[0] == 0x03);
+    let uncompressed = pubkey.len() == 65 && pubkey[0] == 0x04;
+    if flags.contains(VerifyFlags::STRICTENC) && !compressed && !uncompressed {
+        return Err(Error::PubkeyType);
+    }
+    if flags.contains(VerifyFlags::WITNESS_PUBKEYTYPE)
+        && sig_version == SigVersion::WitnessV0
+        && !compressed
+    {
+        return Err(Error::WitnessPubkeyType);
+    }
+    Ok(())
+}
+
+fn check_schnorr<C: SignatureChecker + ?Sized>(
+    sig: &[u8],
+    pubkey: &[u8],
+    sig_version: SigVersion,
+    execution: &TaprootExecution,
+    checker: &mut C,
+) -> Result<(), Error> {
+    match sig.len() {
+        64 => {}
+        65 if sig[64] == TapSighashType::Default as u8 => return Err(Error::SchnorrSigHashType),
+        65 => {}
+        _ => return Err(Error::SchnorrSigSize),
+    }
+    if checker.check_schnorr_signature(sig, pubkey, sig_version, execution) {
+        Ok(())
+    } else {
+        Err(Error::SchnorrSig)
+    }
+}
+
+/// Checks a signature in tapscript, returning whether it is non-empty and valid.
+fn eval_checksig_tapscript<C: SignatureChecker + ?Sized>(
+    sig: &[u8],
+    pubkey: &[u8],
+    flags: VerifyFlags,
+    checker: &mut C,
+    exec: &mut ExecState,
+) -> Result<bool, Error> {
+    let success = !sig.is_empty();
+    if success {
+        exec.validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP_PASSED;
+        if exec.validation_weight_left < 0 {
+            return Err(Error::TapscriptValidationWeight);
+        }
+    }
+    match pubkey.len() {
+        0 => return Err(Error::PubkeyType),
+        32 =>
+            if success {
+                check_schnorr(sig, pubkey, SigVersion::Tapscript, &exec.execution, checker)?;
+            },
+        _ =>
+            if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE) {
+                return Err(Error::DiscourageUpgradablePubkeyType);
+            },
+    }
+    Ok(success)
+}
+
+fn eval<C: SignatureChecker + ?Sized>(
+    stack: &mut Vec<Vec<u8>>,
+    script: &Script,
+    flags: VerifyFlags,
+    checker: &mut C,
+    sig_version: SigVersion,
+    exec: &mut ExecState,
+) -> Result<(), Error> {
+    let is_tapscript = sig_version == SigVersion::Tapscript;
+    if !is_tapscript && script.len() > MAX_SCRIPT_SIZE {
+        return Err(Error::ScriptSize);
+    }
+
+    let require_minimal = flags.contains(VerifyFlags::MINIMALDATA);
+    let bytes = script.as_bytes();
+    let mut alt_stack: Vec<Vec<u8>> = Vec::new();
+    let mut exec_stack: Vec<bool> = Vec::new();
+    let mut op_count = 0;
+    let mut code_hash_start = 0;
+    let mut ops = Ops::new(bytes);
+    let mut opcode_pos: u32 = 0;
+
+    while let Some(op) = ops.next() {
+        let (opcode, data) = op?;
+        let executing = !exec_stack.contains(&false);
+        let code = opcode.to_u8();
+
+        if data.len() > MAX_SCRIPT_ELEMENT_SIZE {
+            return Err(Error::PushSize);
+        }
+        if !is_tapscript && code > OP_PUSHNUM_16.to_u8() {
+            op_count += 1;
+            if op_count > MAX_OPS_PER_SCRIPT {
+                return Err(Error::OpCount);
+            }
+        }
+
+        match opcode {
+            OP_CAT | OP_SUBSTR | OP_LEFT | OP_RIGHT | OP_INVERT | OP_AND | OP_OR | OP_XOR
+            | OP_2MUL | OP_2DIV | OP_MUL | OP_DIV | OP_MOD | OP_LSHIFT | OP_RSHIFT =>
+                return Err(Error::DisabledOpcode),
+            _ => {}
+        }
+
+        if executing && code <= OP_PUSHDATA4.to_u8() {
+            if require_minimal && !is_minimal_push(opcode, data) {
+                return Err(Error::MinimalData);
+            }
+            stack.push(data.to_vec());
+        } else if executing || (OP_IF.to_u8()..=OP_ENDIF.to_u8()).contains(&code) {
+            match opcode {
+                OP_PUSHNUM_NEG1 => push_num(stack, -1),
+                _ if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&code) =>
+                    push_num(stack, i64::from(code - (OP_PUSHNUM_1.to_u8() - 1))),
+
+                OP_NOP => {}
+                // Not enabled CLTV and CSV are NOPs, without discouraging their use.
+                OP_CLTV =>
+                    if flags.contains(VerifyFlags::CHECKLOCKTIMEVERIFY) {
+                        let lock_time = read_num(top(stack, 1)?, require_minimal, 5)?;
+                        if lock_time < 0 {
+                            return Err(Error::NegativeLockTime);
+                        }
+                        let satisfied = u32::try_from(lock_time).map_or(false, |n| {
+                            checker.check_lock_time(absolute::LockTime::from_consensus(n))
+                        });
+                        if !satisfied {
+                            return Err(Error::UnsatisfiedLockTime);
+                        }
+                    },
+                OP_CSV =>
+                    if flags.contains(VerifyFlags::CHECKSEQUENCEVERIFY) {
+                        let sequence = read_num(top(stack, 1)?, require_minimal, 5)?;
+                        if sequence < 0 {
+                            return Err(Error::NegativeLockTime);
+                        }
+                        // Only the low 32 bits are meaningful, the disable flag makes it a NOP.
+                        let sequence = Sequence::from_consensus(sequence as u32);
+                        if sequence.is_relative_lock_time() && !checker.check_sequence(sequence) {
+                            return Err(Error::UnsatisfiedLockTime);
+                        }
+                    },
+                OP_NOP1 | OP_NOP4 | OP_NOP5 | OP_NOP6 | OP_NOP7 | OP_NOP8 | OP_NOP9 | OP_NOP10 =>
+                    if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                        return Err(Error::DiscourageUpgradableNops);
+                    },
+
+                OP_IF | OP_NOTIF => {
+                    let mut value = false;
+                    if executing {
+                        let condition = stack.pop().ok_or(Error::UnbalancedConditional)?;
+                        let minimal =
+                            condition.is_empty() || (condition.len() == 1 && condition[0] == 1);
+                        if is_tapscript && !minimal {
+                            return Err(Error::TapscriptMinimalIf);
+                        }
+                        if sig_version == SigVersion::WitnessV0
+                            && flags.contains(VerifyFlags::MINIMALIF)
+                            && !minimal
+                        {
+                            return Err(Error::MinimalIf);
+                        }
+                        value = read_scriptbool(&condition) == (opcode == OP_IF);
+                    }
+                    exec_stack.push(value);
+                }
+                OP_ELSE => {
+                    let last = exec_stack.last_mut().ok_or(Error::UnbalancedConditional)?;
+                    *last = !*last;
+                }
+                OP_ENDIF => {
+                    exec_stack.pop().ok_or(Error::UnbalancedConditional)?;
+                }
+                OP_VERIFY =>
+                    if !read_scriptbool(&pop(stack)?) {
+                        return Err(Error::Verify);
+                    },
+                OP_RETURN => return Err(Error::OpReturn),
+
+                OP_TOALTSTACK => alt_stack.push(pop(stack)?),
+                OP_FROMALTSTACK =>
+                    stack.push(alt_stack.pop().ok_or(Error::InvalidAltstackOperation)?),
+                OP_2DROP => {
+                    top(stack, 2)?;
+                    stack.truncate(stack.len() - 2);
+                }
+                OP_2DUP => {
+                    let (a, b) = (top(stack, 2)?.clone(), top(stack, 1)?.clone());
+                    stack.push(a);
+                    stack.push(b);
+                }
+                OP_3DUP => {
+                    let (a, b, c) =
+                        (top(stack, 3)?.clone(), top(stack, 2)?.clone(), top(stack, 1)?.clone());
+                    stack.push(a);
+                    stack.push(b);
+                    stack.push(c);
+                }
+                OP_2OVER => {
+                    let (a, b) = (top(stack, 4)?.clone(), top(stack, 3)?.clone());
+                    stack.push(a);
+                    stack.push(b);
+                }
+                OP_2ROT => {
+                    top(stack, 6)?;
+                    let i = stack.len() - 6;
+                    let a = stack.remove(i);
+                    let b = stack.remove(i);
+                    stack.push(a);
+                    stack.push(b);
+                }
+                OP_2SWAP => {
+                    top(stack, 4)?;
+                    let len = stack.len();
+                    stack.swap(len - 4, len - 2);
+                    stack.swap(len - 3, len - 1);
+                }
+                OP_IFDUP => {
+                    let a = top(stack, 1)?;
+                    if read_scriptbool(a) {
+                        let a = a.clone();
+                        stack.push(a);
+                    }
+                }
+                OP_DEPTH => push_num(stack, stack.len() as i64),
+                OP_DROP => {
+                    pop(stack)?;
+                }
+                OP_DUP => {
+                    let a = top(stack, 1)?.clone();
+                    stack.push(a);
+                }
+                OP_NIP => {
+                    top(stack, 2)?;
+                    stack.remove(stack.len() - 2);
+                }
+                OP_OVER => {
+                    let a = top(stack, 2)?.clone();
+                    stack.push(a);
+                }
+                OP_PICK | OP_ROLL => {
+                    let n = read_num(&pop(stack)?, require_minimal, 4)?;
+                    if n < 0 || n as usize >= stack.len() {
+                        return Err(Error::InvalidStackOperation);
+                    }
+                    let i = stack.len() - 1 - n as usize;
+                    let a = if opcode == OP_ROLL { stack.remove(i) } else { stack[i].clone() };
+                    stack.push(a);
+                }
+                OP_ROT => {
+                    top(stack, 3)?;
+                    let i = stack.len() - 3;
+                    let a = stack.remove(i);
+                    stack.push(a);
+                }
+                OP_SWAP => {
+                    top(stack, 2)?;
+                    let len = stack.len();
+                    stack.swap(len - 2, len - 1);
+                }
+                OP_TUCK => {
+                    let a = top(stack, 1)?.clone();
+                    top(stack, 2)?;
+                    stack.insert(stack.len() - 2, a);
+                }
+
+                OP_SIZE => {
+                    let len = top(stack, 1)?.len();
+                    push_num(stack, len as i64);
+                }
+
+                OP_EQUAL | OP_EQUALVERIFY => {
+                    let b = pop(stack)?;
+                    let a = pop(stack)?;
+                    let equal = a == b;
+                    if opcode == OP_EQUALVERIFY {
+                        if !equal {
+                            return Err(Error::EqualVerify);
+                        }
+                    } else {
+                        push_bool(stack, equal);
+                    }
+                }
+
+                OP_1ADD | OP_1SUB | OP_NEGATE | OP_ABS | OP_NOT | OP_0NOTEQUAL => {
+                    let n = read_num(&pop(stack)?, require_minimal, 4)?;
+                    let result = match opcode {
+                        OP_1ADD => n + 1,
+                        OP_1SUB => n - 1,
+                        OP_NEGATE => -n,
+                        OP_ABS => n.abs(),
+                        OP_NOT => i64::from(n == 0),
+                        _ => i64::from(n != 0),
+                    };
+                    push_num(stack, result);
+                }
+
+                OP_ADD
+                | OP_SUB
+                | OP_BOOLAND
+                | OP_BOOLOR
+                | OP_NUMEQUAL
+                | OP_NUMEQUALVERIFY
+                | OP_NUMNOTEQUAL
+                | OP_LESSTHAN
+                | OP_GREATERTHAN
+                | OP_LESSTHANOREQUAL
+                | OP_GREATERTHANOREQUAL
+                | OP_MIN
+                | OP_MAX => {
+                    top(stack, 2)?;
+                    let b = read_num(&pop(stack)?, require_minimal, 4)?;
+                    let a = read_num(&pop(stack)?, require_minimal, 4)?;
+                    let result = match opcode {
+                        OP_ADD => a + b,
+                        OP_SUB => a - b,
+                        OP_BOOLAND => i64::from(a != 0 && b != 0),
+                        OP_BOOLOR => i64::from(a != 0 || b != 0),
+                        OP_NUMEQUAL | OP_NUMEQUALVERIFY => i64::from(a == b),
+                        OP_NUMNOTEQUAL => i64::from(a != b),
+                        OP_LESSTHAN => i64::from(a < b),
+                        OP_GREATERTHAN => i64::from(a > b),
+                        OP_LESSTHANOREQUAL => i64::from(a <= b),
+                        OP_GREATERTHANOREQUAL => i64::from(a >= b),
+                        OP_MIN => a.min(b),
+                        _ => a.max(b),
+                    };
+                    if opcode == OP_NUMEQUALVERIFY {
+                        if result == 0 {
+                            return Err(Error::NumEqualVerify);
+                        }
+                    } else {
+                        push_num(stack, result);
+                    }
+                }
+                OP_WITHIN => {
+                    top(stack, 3)?;
+                    let max = read_num(&pop(stack)?, require_minimal, 4)?;
+                    let min = read_num(&pop(stack)?, require_minimal, 4)?;
+                    let n = read_num(&pop(stack)?, require_minimal, 4)?;
+                    push_bool(stack, min <= n && n < max);
+                }
+
+                OP_RIPEMD160 | OP_SHA1 | OP_SHA256 | OP_HASH160 | OP_HASH256 => {
+                    let a = pop(stack)?;
+                    let hash = match opcode {
+                        OP_RIPEMD160 => ripemd160::Hash::hash(&a).to_byte_array().to_vec(),
+                        OP_SHA1 => sha1::Hash::hash(&a).to_byte_array().to_vec(),
+                        OP_SHA256 => sha256::Hash::hash(&a).to_byte_array().to_vec(),
+                        OP_HASH160 => hash160::Hash::hash(&a).to_byte_array().to_vec(),
+                        _ => sha256d::Hash::hash(&a).to_byte_array().to_vec(),
+                    };
+                    stack.push(hash);
+                }
+
+                OP_CODESEPARATOR => {
+                    code_hash_start = ops.position();
+                    exec.execution.leaf_hash_code_separator =
+                        exec.execution.leaf_hash_code_separator.map(|(leaf, _)| (leaf, opcode_pos));
+                }
+
+                OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                    top(stack, 2)?;
+                    let pubkey = pop(stack)?;
+                    let sig = pop(stack)?;
+                    let success = if is_tapscript {
+                        eval_checksig_tapscript(&sig, &pubkey, flags, checker, exec)?
+                    } else {
+                        let mut script_code = bytes[code_hash_start..].to_vec();
+                        if sig_version == SigVersion::Base {
+                            find_and_delete(&mut script_code, &sig);
+                        }
+                        check_signature_encoding(&sig, flags)?;
+                        check_pubkey_encoding(&pubkey, flags, sig_version)?;
+                        let success = !sig.is_empty()
+                            && checker.check_ecdsa_signature(
+                                &sig,
+                                &pubkey,
+                                Script::from_bytes(&script_code),
+                                sig_version,
+                            );
+                        if !success && flags.contains(VerifyFlags::NULLFAIL) && !sig.is_empty() {
+                            return Err(Error::NullFail);
+                        }
+                        success
+                    };
+                    if opcode == OP_CHECKSIGVERIFY {
+                        if !success {
+                            return Err(Error::CheckSigVerify);
+                        }
+                    } else {
+                        push_bool(stack, success);
+                    }
+                }
+
+                OP_CHECKSIGADD => {
+                    if !is_tapscript {
+                        return Err(Error::BadOpcode);
+                    }
+                    top(stack, 3)?;
+                    let pubkey = pop(stack)?;
+                    let n = read_num(&pop(stack)?, require_minimal, 4)?;
+                    let sig = pop(stack)?;
+                    let success = eval_checksig_tapscript(&sig, &pubkey, flags, checker, exec)?;
+                    push_num(stack, n + i64::from(success));
+                }
+
+                OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                    if is_tapscript {
+                        return Err(Error::TapscriptCheckMultiSig);
+                    }
+                    let success = eval_checkmultisig(
+                        stack,
+                        &bytes[code_hash_start..],
+                        flags,
+                        checker,
+                        sig_version,
+                        &mut op_count,
+                    )?;
+                    if opcode == OP_CHECKMULTISIGVERIFY {
+                        if !success {
+                            return Err(Error::CheckMultiSigVerify);
+                        }
+                    } else {
+                        push_bool(stack, success);
+                    }
+                }
+
+                _ => return Err(Error::BadOpcode),
+            }
+        }
+
+        if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+            return Err(Error::StackSize);
+        }
+        opcode_pos += 1;
+    }
+
+    if !exec_stack.is_empty() {
+        return Err(Error::UnbalancedConditional);
+    }
+    Ok(())
+}
+
+fn eval_checkmultisig<C: SignatureChecker + ?Sized>(
+    stack: &mut Vec<Vec<u8>>,
+    script_code: &[u8],
+    flags: VerifyFlags,
+    checker: &mut C,
+    sig_version: SigVersion,
+    op_count: &mut usize,
+) -> Result<bool, Error> {
+    let require_minimal = flags.contains(VerifyFlags::MINIMALDATA);
+
+    let key_count = read_num(top(stack, 1)?, require_minimal, 4)?;
+    if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&key_count) {
+        return Err(Error::PubkeyCount);
+    }
+    let key_count = key_count as usize;
+    *op_count += key_count;
+    if *op_count > MAX_OPS_PER_SCRIPT {
+        return Err(Error::OpCount);
+    }
+    let sig_count = read_num(top(stack, key_count + 2)?, require_minimal, 4)?;
+    if sig_count < 0 || sig_count as usize > key_count {
+        return Err(Error::SigCount);
+    }
+    let sig_count = sig_count as usize;
+    // Key count, keys, signature count, signatures and the dummy element.
+    let total = key_count + sig_count + 3;
+    if stack.len() < total {
+        return Err(Error::InvalidStackOperation);
+    }
+
+    let items = stack.split_off(stack.len() - total);
+    // Stored bottom to top: dummy, signatures, signature count, keys, key count.
+    let dummy = &items[0];
+    let sigs = &items[1..=sig_count];
+    let keys = &items[sig_count + 2..sig_count + 2 + key_count];
+
+    let mut script_code = script_code.to_vec();
+    if sig_version == SigVersion::Base {
+        for sig in sigs {
+            find_and_delete(&mut script_code, sig);
+        }
+    }
+    let script_code = Script::from_bytes(&script_code);
+
+    // Signatures and keys are checked in order starting from the top of the stack.
+    let mut sig_iter = sigs.iter().rev().peekable();
+    let mut keys_left = key_count;
+    let mut success = true;
+    for key in keys.iter().rev() {
+        let sig = match sig_iter.peek() {
+            Some(sig) => sig,
+            None => break,
+        };
+        check_signature_encoding(sig, flags)?;
+        check_pubkey_encoding(key, flags, sig_version)?;
+        if !sig.is_empty() && checker.check_ecdsa_signature(sig, key, script_code, sig_version) {
+            sig_iter.next();
+        }
+        keys_left -= 1;
+        if sig_iter.len() > keys_left {
+            success = false;
+            break;
+        }
+    }
+
+    if !success && flags.contains(VerifyFlags::NULLFAIL) && sigs.iter().any(|sig| !sig.is_empty()) {
+        return Err(Error::NullFail);
+    }
+    if flags.contains(VerifyFlags::NULLDUMMY) && !dummy.is_empty() {
+        return Err(Error::NullDummy);
+    }
+    Ok(success)
+}
+
+/// Error returned when a script fails to verify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The script evaluated to false.
+    EvalFalse,
+    /// An `OP_RETURN` was executed.
+    OpReturn,
+    /// The script exceeds [`MAX_SCRIPT_SIZE`].
+    ScriptSize,
+    /// A push exceeds [`MAX_SCRIPT_ELEMENT_SIZE`].
+    PushSize,
+    /// The script exceeds [`MAX_OPS_PER_SCRIPT`].
+    OpCount,
+    /// The stack exceeds [`MAX_STACK_SIZE`].
+    StackSize,
+    /// A multisig has more signatures than keys or a negative signature count.
+    SigCount,
+    /// A multisig has more than [`MAX_PUBKEYS_PER_MULTISIG`] or a negative number of keys.
+    PubkeyCount,
+    /// An `OP_VERIFY` failed.
+    Verify,
+    /// An `OP_EQUALVERIFY` failed.
+    EqualVerify,
+    /// An `OP_CHECKMULTISIGVERIFY` failed.
+    CheckMultiSigVerify,
+    /// An `OP_CHECKSIGVERIFY` failed.
+    CheckSigVerify,
+    /// An `OP_NUMEQUALVERIFY` failed.
+    NumEqualVerify,
+    /// An invalid or truncated opcode was encountered.
+    BadOpcode,
+    /// A disabled opcode was encountered.
+    DisabledOpcode,
+    /// An operation required more stack elements than available.
+    InvalidStackOperation,
+    /// An `OP_FROMALTSTACK` was executed with an empty altstack.
+    InvalidAltstackOperation,
+    /// Unbalanced `OP_IF`/`OP_ELSE`/`OP_ENDIF`.
+    UnbalancedConditional,
+    /// A script number is longer than allowed.
+    NumberOverflow,
+    /// A script number is not minimally encoded.
+    NonMinimalNumber,
+    /// The lock time argument of `OP_CHECKLOCKTIMEVERIFY`/`OP_CHECKSEQUENCEVERIFY` is negative.
+    NegativeLockTime,
+    /// The lock time required by the script is not satisfied.
+    UnsatisfiedLockTime,
+    /// A signature has an undefined sighash type.
+    SigHashType,
+    /// A signature is not strictly DER encoded.
+    SigDer,
+    /// A push is not minimally encoded.
+    MinimalData,
+    /// The scriptSig is not push only.
+    SigPushOnly,
+    /// A signature has a high S value.
+    SigHighS,
+    /// The `OP_CHECKMULTISIG` dummy element is not empty.
+    NullDummy,
+    /// A public key is not compressed or uncompressed, or empty in tapscript.
+    PubkeyType,
+    /// The stack is not clean after evaluation.
+    CleanStack,
+    /// The argument of `OP_IF`/`OP_NOTIF` is not minimal.
+    MinimalIf,
+    /// A failing signature is not empty.
+    NullFail,
+    /// An upgradable `OP_NOPx` was executed.
+    DiscourageUpgradableNops,
+    /// A witness program of an unknown version was spent.
+    DiscourageUpgradableWitnessProgram,
+    /// A taproot leaf of an unknown version was spent.
+    DiscourageUpgradableTaprootVersion,
+    /// A tapscript contains an `OP_SUCCESSx`.
+    DiscourageOpSuccess,
+    /// A tapscript signature check used an unknown public key type.
+    DiscourageUpgradablePubkeyType,
+    /// A witness program was spent with an empty witness.
+    WitnessProgramWitnessEmpty,
+    /// The witness does not match the witness program.
+    WitnessProgramMismatch,
+    /// A version 0 witness program has an invalid length.
+    WitnessProgramWrongLength,
+    /// A native witness program was spent with a non-empty scriptSig.
+    WitnessMalleated,
+    /// A P2SH wrapped witness program was spent with an unexpected scriptSig.
+    WitnessMalleatedP2sh,
+    /// A witness was provided for a non-witness spend.
+    WitnessUnexpected,
+    /// A segwit v0 public key is not compressed.
+    WitnessPubkeyType,
+    /// A schnorr signature is not 64 or 65 bytes long.
+    SchnorrSigSize,
+    /// A 65 byte schnorr signature uses the default sighash type.
+    SchnorrSigHashType,
+    /// A schnorr signature is invalid.
+    SchnorrSig,
+    /// A taproot control block has an invalid size.
+    TaprootWrongControlSize,
+    /// A tapscript exceeded its signature validation weight budget.
+    TapscriptValidationWeight,
+    /// `OP_CHECKMULTISIG` was used in tapscript.
+    TapscriptCheckMultiSig,
+    /// The argument of `OP_IF`/`OP_NOTIF` in tapscript is not minimal.
+    TapscriptMinimalIf,
+    /// [`eval_script`] was called with [`SigVersion::Tapscript`].
+    UnsupportedSigVersion,
+    /// The input index is out of range.
+    InputIndex(usize),
+    /// The number of prevouts does not match the number of inputs.
+    PrevoutsSize {
+        /// Number of transaction inputs.
+        inputs: usize,
+        /// Number of prevouts provided.
+        prevouts: usize,
+    },
+}
+
+internals::impl_from_infallible!(Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            EvalFalse => f.write_str("script evaluated to false"),
+            OpReturn => f.write_str("OP_RETURN was encountered"),
+            ScriptSize => f.write_str("script is too big"),
+            PushSize => f.write_str("push value size limit exceeded"),
+            OpCount => f.write_str("operation limit exceeded"),
+            StackSize => f.write_str("stack size limit exceeded"),
+            SigCount => f.write_str("signature count negative or greater than pubkey count"),
+            PubkeyCount => f.write_str("pubkey count negative or limit exceeded"),
+            Verify => f.write_str("OP_VERIFY failed"),
+            EqualVerify => f.write_str("OP_EQUALVERIFY failed"),
+            CheckMultiSigVerify => f.write_str("OP_CHECKMULTISIGVERIFY failed"),
+            CheckSigVerify => f.write_str("OP_CHECKSIGVERIFY failed"),
+            NumEqualVerify => f.write_str("OP_NUMEQUALVERIFY failed"),
+            BadOpcode => f.write_str("opcode missing or not understood"),
+            DisabledOpcode => f.write_str("attempted to use a disabled opcode"),
+            InvalidStackOperation => f.write_str("operation not valid with the current stack size"),
+            InvalidAltstackOperation =>
+                f.write_str("operation not valid with the current altstack size"),
+            UnbalancedConditional => f.write_str("invalid OP_IF construction"),
+            NumberOverflow => f.write_str("script number overflow"),
+            NonMinimalNumber => f.write_str("non-minimally encoded script number"),
+            NegativeLockTime => f.write_str("negative locktime"),
+            UnsatisfiedLockTime => f.write_str("locktime requirement not satisfied"),
+            SigHashType => f.write_str("signature hash type missing or not understood"),
+            SigDer => f.write_str("non-canonical DER signature"),
+            MinimalData => f.write_str("data push larger than necessary"),
+            SigPushOnly => f.write_str("only push operators allowed in signatures"),
+            SigHighS => f.write_str("non-canonical signature: S value is unnecessarily high"),
+            NullDummy => f.write_str("dummy CHECKMULTISIG argument must be zero"),
+            PubkeyType => f.write_str("public key is neither compressed or uncompressed"),
+            CleanStack => f.write_str("stack size must be exactly one after execution"),
+            MinimalIf => f.write_str("OP_IF/NOTIF argument must be minimal"),
+            NullFail => f.write_str("signature must be zero for failed CHECK(MULTI)SIG operation"),
+            DiscourageUpgradableNops => f.write_str("NOPx reserved for soft-fork upgrades"),
+            DiscourageUpgradableWitnessProgram =>
+                f.write_str("witness version reserved for soft-fork upgrades"),
+            DiscourageUpgradableTaprootVersion =>
+                f.write_str("taproot version reserved for soft-fork upgrades"),
+            DiscourageOpSuccess => f.write_str("OP_SUCCESSx reserved for soft-fork upgrades"),
+            DiscourageUpgradablePubkeyType =>
+                f.write_str("public key version reserved for soft-fork upgrades"),
+            WitnessProgramWitnessEmpty =>
+                f.write_str("witness program was passed an empty witness"),
+            WitnessProgramMismatch => f.write_str("witness program hash mismatch"),
+            WitnessProgramWrongLength => f.write_str("witness program has incorrect length"),
+            WitnessMalleated => f.write_str("witness requires empty scriptSig"),
+            WitnessMalleatedP2sh => f.write_str("witness requires only-redeemscript scriptSig"),
+            WitnessUnexpected => f.write_str("witness provided for non-witness script"),
+            WitnessPubkeyType => f.write_str("using non-compressed keys in segwit"),
+            SchnorrSigSize => f.write_str("invalid schnorr signature size"),
+            SchnorrSigHashType => f.write_str("invalid schnorr signature hash type"),
+            SchnorrSig => f.write_str("invalid schnorr signature"),
+            TaprootWrongControlSize => f.write_str("invalid taproot control block size"),
+            TapscriptValidationWeight =>
+                f.write_str("too much signature validation relative to witness weight"),
+            TapscriptCheckMultiSig =>
+                f.write_str("OP_CHECKMULTISIG(VERIFY) is not available in tapscript"),
+            TapscriptMinimalIf => f.write_str("OP_IF/NOTIF argument must be minimal in tapscript"),
+            UnsupportedSigVersion => f.write_str("tapscript requires a full witness to evaluate"),
+            InputIndex(index) => write!(f, "input index {} is out of range", index),
+            PrevoutsSize { inputs, prevouts } =>
+                write!(f, "{} prevouts provided for {} inputs", prevouts, inputs),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::test_hex_unwrap as hex;
+
+    use super::*;
+    use crate::blockdata::transaction::{OutPoint, TxIn, Version};
+    use crate::consensus::encode::deserialize;
+    use crate::script::{Builder, PushBytes};
+    use crate::{Amount, ScriptBuf};
+
+    fn eval_ok(script: &Script) -> Result<Vec<Vec<u8>>, Error> {
+        let mut stack = Vec::new();
+        eval_script(
+            &mut stack,
+            script,
+            VerifyFlags::STANDARD,
+            &mut NoSignatureChecker,
+            SigVersion::Base,
+        )?;
+        Ok(stack)
+    }
+
+    #[test]
+    fn arithmetic_and_stack_ops() {
+        let script = Builder::new()
+            .push_int(2)
+            .push_int(3)
+            .push_opcode(OP_ADD)
+            .push_opcode(OP_DUP)
+            .push_int(5)
+            .push_opcode(OP_NUMEQUALVERIFY)
+            .push_int(1)
+            .push_opcode(OP_SUB)
+            .into_script();
+        assert_eq!(eval_ok(&script).unwrap(), vec![vec![4]]);
+
+        let script =
+            Builder::new().push_int(-1).push_opcode(OP_ABS).push_opcode(OP_NEGATE).into_script();
+        assert_eq!(eval_ok(&script).unwrap(), vec![vec![0x81]]);
+
+        let script =
+            Builder::new().push_int(1).push_int(2).push_int(3).push_opcode(OP_ROT).into_script();
+        assert_eq!(eval_ok(&script).unwrap(), vec![vec![2], vec![3], vec![1]]);
+
+        let script = Builder::new()
+            .push_int(7)
+            .push_int(5)
+            .push_int(10)
+            .push_opcode(OP_WITHIN)
+            .into_script();
+        assert_eq!(eval_ok(&script).unwrap(), vec![vec![1]]);
+    }
+
+    #[test]
+    fn conditionals() {
+        let script = Builder::new()
+            .push_int(0)
+            .push_opcode(OP_IF)
+            .push_opcode(OP_RETURN)
+            .push_opcode(OP_ELSE)
+            .push_int(9)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        assert_eq!(eval_ok(&script).unwrap(), vec![vec![9]]);
+
+        let script = Builder::new().push_int(1).push_opcode(OP_IF).into_script();
+        assert_eq!(eval_ok(&script), Err(Error::UnbalancedConditional));
+
+        // Disabled opcodes fail even when not executed.
+        let script = Builder::new()
+            .push_int(0)
+            .push_opcode(OP_IF)
+            .push_opcode(OP_CAT)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        assert_eq!(eval_ok(&script), Err(Error::DisabledOpcode));
+    }
+
+    #[test]
+    fn script_errors() {
+        assert_eq!(
+            eval_ok(&Builder::new().push_opcode(OP_DROP).into_script()),
+            Err(Error::InvalidStackOperation)
+        );
+        assert_eq!(
+            eval_ok(
+                &Builder::new()
+                    .push_int(1)
+                    .push_opcode(OP_VERIFY)
+                    .push_opcode(OP_RETURN)
+                    .into_script()
+            ),
+            Err(Error::OpReturn)
+        );
+        assert_eq!(
+            eval_ok(&Builder::new().push_opcode(OP_NOP4).into_script()),
+            Err(Error::DiscourageUpgradableNops)
+        );
+        // Non-minimal push of 0x05.
+        assert_eq!(eval_ok(Script::from_bytes(&[0x01, 0x05])), Err(Error::MinimalData));
+        // Truncated push.
+        assert_eq!(eval_ok(Script::from_bytes(&[0x02, 0x05])), Err(Error::BadOpcode));
+        // Numbers are limited to four bytes.
+        let script = Builder::new().push_slice([1, 2, 3, 4, 5]).push_opcode(OP_1ADD).into_script();
+        assert_eq!(eval_ok(&script), Err(Error::NumberOverflow));
+    }
+
+    #[test]
+    fn verify_p2sh_and_p2wsh() {
+        let redeem_script = Builder::new().push_int(5).push_opcode(OP_EQUAL).into_script();
+        let flags = VerifyFlags::STANDARD;
+
+        let script_pubkey = redeem_script.to_p2sh();
+        let script_sig = Builder::new()
+            .push_int(5)
+            .push_slice(<&crate::script::PushBytes>::try_from(redeem_script.as_bytes()).unwrap())
+            .into_script();
+        let empty = Witness::new();
+        verify_script(&script_sig, &script_pubkey, &empty, flags, &mut NoSignatureChecker).unwrap();
+
+        let bad_sig = Builder::new()
+            .push_int(6)
+            .push_slice(<&crate::script::PushBytes>::try_from(redeem_script.as_bytes()).unwrap())
+            .into_script();
+        assert_eq!(
+            verify_script(&bad_sig, &script_pubkey, &empty, flags, &mut NoSignatureChecker),
+            Err(Error::EvalFalse)
+        );
+
+        let script_pubkey = redeem_script.to_p2wsh();
+        let witness = Witness::from_slice(&[vec![5], redeem_script.to_bytes()]);
+        let empty_sig = ScriptBuf::new();
+        verify_script(&empty_sig, &script_pubkey, &witness, flags, &mut NoSignatureChecker)
+            .unwrap();
+
+        let witness = Witness::from_slice(&[vec![4], redeem_script.to_bytes()]);
+        assert_eq!(
+            verify_script(&empty_sig, &script_pubkey, &witness, flags, &mut NoSignatureChecker),
+            Err(Error::EvalFalse)
+        );
+        let witness = Witness::from_slice(&[vec![5], vec![OP_PUSHNUM_1.to_u8()]]);
+        assert_eq!(
+            verify_script(&empty_sig, &script_pubkey, &witness, flags, &mut NoSignatureChecker),
+            Err(Error::WitnessProgramMismatch)
+        );
+    }
+
+    #[test]
+    fn find_and_delete_sig() {
+        let mut script = vec![0x02, 0xaa, 0xbb, OP_CHECKSIG.to_u8(), 0x02, 0xaa, 0xbb];
+        find_and_delete(&mut script, &[0xaa, 0xbb]);
+        assert_eq!(script, vec![OP_CHECKSIG.to_u8()]);
+
+        // Matches are only removed at opcode boundaries.
+        let mut script = vec![0x03, 0x02, 0xaa, 0xbb];
+        find_and_delete(&mut script, &[0xaa, 0xbb]);
+        assert_eq!(script, vec![0x03, 0x02, 0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn verify_p2wpkh_spend() {
+        // Input 1 of a BIP-143 example transaction spending a P2WPKH output.
+        let tx: Transaction = deserialize(&hex!(
+            "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f\
+             00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e\
+             5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01\
+             eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a010000\
+             0000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d59\
+             88ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247\
+             304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a022057\
+             3a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e831\
+             88368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000"
+        ))
+        .unwrap();
+        let prevouts = vec![
+            TxOut {
+                value: Amount::from_sat(625_000_000),
+                script_pubkey: ScriptBuf::from_hex(
+                    "2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac",
+                )
+                .unwrap(),
+            },
+            TxOut {
+                value: Amount::from_sat(600_000_000),
+                script_pubkey: ScriptBuf::from_hex("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1")
+                    .unwrap(),
+            },
+        ];
+        // Input 0 is a legacy P2PK spend.
+        verify_input(&tx, 0, &prevouts, VerifyFlags::STANDARD).unwrap();
+        verify_input(&tx, 1, &prevouts, VerifyFlags::STANDARD).unwrap();
+
+        let mut wrong_amount = prevouts.clone();
+        wrong_amount[1].value = Amount::from_sat(600_000_001);
+        assert_eq!(
+            verify_input(&tx, 1, &wrong_amount, VerifyFlags::CONSENSUS),
+            Err(Error::EvalFalse)
+        );
+        assert_eq!(
+            verify_input(&tx, 1, &wrong_amount, VerifyFlags::STANDARD),
+            Err(Error::NullFail)
+        );
+        assert_eq!(
+            verify_input(&tx, 2, &prevouts, VerifyFlags::STANDARD),
+            Err(Error::InputIndex(2))
+        );
+    }
+
+    #[test]
+    fn verify_bare_multisig() {
+        use crate::key::PublicKey;
+
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[2; 32]).unwrap();
+        let pk = PublicKey::new(secp256k1::PublicKey::from_secret_key(&secp, &sk));
+        let other = PublicKey::new(secp256k1::PublicKey::from_secret_key(
+            &secp,
+            &secp256k1::SecretKey::from_slice(&[3; 32]).unwrap(),
+        ));
+        let script_pubkey = Builder::new()
+            .push_int(1)
+            .push_key(&other)
+            .push_key(&pk)
+            .push_int(2)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        let prevouts = vec![TxOut { value: Amount::from_sat(10_000), script_pubkey }];
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(crate::Txid::all_zeros(), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut { value: Amount::from_sat(9_000), script_pubkey: ScriptBuf::new() }],
+        };
+
+        let sighash = SighashCache::new(&tx)
+            .legacy_signature_hash(0, &prevouts[0].script_pubkey, EcdsaSighashType::All.to_u32())
+            .unwrap();
+        let sig = secp.sign_ecdsa(&Message::from_digest(sighash.to_byte_array()), &sk);
+        let sig = crate::ecdsa::Signature::sighash_all(sig);
+
+        tx.input[0].script_sig =
+            Builder::new().push_int(0).push_slice(sig.serialize()).into_script();
+        verify_input(&tx, 0, &prevouts, VerifyFlags::STANDARD).unwrap();
+
+        tx.input[0].script_sig =
+            Builder::new().push_int(1).push_slice(sig.serialize()).into_script();
+        assert_eq!(verify_input(&tx, 0, &prevouts, VerifyFlags::STANDARD), Err(Error::NullDummy));
+        verify_input(&tx, 0, &prevouts, VerifyFlags::P2SH).unwrap();
+    }
+
+    #[test]
+    fn verify_taproot_spends() {
+        use crate::key::{Keypair, TapTweak};
+        use crate::taproot::TaprootBuilder;
+
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let (internal_key, _) = keypair.x_only_public_key();
+        let leaf =
+            Builder::new().push_x_only_key(&internal_key).push_opcode(OP_CHECKSIG).into_script();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, leaf.clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+        let merkle_root = spend_info.merkle_root();
+        let prevouts = vec![TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr(&secp, internal_key, merkle_root),
+        }];
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(crate::Txid::all_zeros(), 0),
+                ..Default::default()
+            }],
+            output: vec![TxOut { value: Amount::from_sat(9_000), script_pubkey: ScriptBuf::new() }],
+        };
+        let flags = VerifyFlags::STANDARD;
+
+        // Key path.
+        let sighash = SighashCache::new(&tx)
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), TapSighashType::Default)
+            .unwrap();
+        let msg = Message::from_digest(sighash.to_byte_array());
+        let tweaked = keypair.tap_tweak(&secp, merkle_root).to_inner();
+        let sig = secp.sign_schnorr_no_aux_rand(&msg, &tweaked);
+        tx.input[0].witness = Witness::from_slice(&[sig.as_ref()]);
+        verify_input(&tx, 0, &prevouts, flags).unwrap();
+
+        let mut bad_sig = *sig.as_ref();
+        bad_sig[0] ^= 1;
+        tx.input[0].witness = Witness::from_slice(&[&bad_sig[..]]);
+        assert_eq!(verify_input(&tx, 0, &prevouts, flags), Err(Error::SchnorrSig));
+
+        // Script path.
+        let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::TapScript);
+        let sighash = SighashCache::new(&tx)
+            .taproot_script_spend_signature_hash(
+                0,
+                &Prevouts::All(&prevouts),
+                leaf_hash,
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let msg = Message::from_digest(sighash.to_byte_array());
+        let sig = secp.sign_schnorr_no_aux_rand(&msg, &keypair);
+        let control_block =
+            spend_info.control_block(&(leaf.clone(), LeafVersion::TapScript)).unwrap();
+        tx.input[0].witness =
+            Witness::from_slice(&[sig.as_ref(), leaf.as_bytes(), &control_block.serialize()]);
+        verify_input(&tx, 0, &prevouts, flags).unwrap();
+
+        // The BIP118 sighash types are only valid for BIP118 public keys, not 32 byte keys.
+        #[cfg(feature = "anyprevout")]
+        {
+            use crate::sighash::AnyPrevoutSighashType;
+
+            let sighash_type = AnyPrevoutSighashType::AllPlusAnyPrevout;
+            let sighash = SighashCache::new(&tx)
+                .taproot_anyprevout_signature_hash(
+                    0,
+                    &Prevouts::All(&prevouts),
+                    None,
+                    (leaf_hash, 0xFFFFFFFF),
+                    sighash_type,
+                )
+                .unwrap();
+            let msg = Message::from_digest(sighash.to_byte_array());
+            let mut sig = secp.sign_schnorr_no_aux_rand(&msg, &keypair).as_ref().to_vec();
+            sig.push(sighash_type as u8);
+            tx.input[0].witness =
+                Witness::from_slice(&[&sig[..], leaf.as_bytes(), &control_block.serialize()]);
+            assert_eq!(verify_input(&tx, 0, &prevouts, flags), Err(Error::SchnorrSig));
+        }
+
+        // An empty signature makes the leaf script evaluate to false.
+        tx.input[0].witness =
+            Witness::from_slice(&[&[][..], leaf.as_bytes(), &control_block.serialize()]);
+        assert_eq!(verify_input(&tx, 0, &prevouts, flags), Err(Error::EvalFalse));
+
+        // A different script is not committed to by the output key.
+        let other = Builder::new().push_int(1).into_script();
+        tx.input[0].witness = Witness::from_slice(&[other.as_bytes(), &control_block.serialize()]);
+        assert_eq!(verify_input(&tx, 0, &prevouts, flags), Err(Error::WitnessProgramMismatch));
+    }
+
+    #[test]
+    fn check_lock_time_verify() {
+        let script_pubkey = Builder::new()
+            .push_int(100)
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_DROP)
+            .push_int(1)
+            .into_script();
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::from_consensus(100),
+            input: vec![TxIn {
+                previous_output: OutPoint::new(crate::Txid::all_zeros(), 0),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+        let prevouts =
+            vec![TxOut { value: Amount::from_sat(1000), script_pubkey: script_pubkey.clone() }];
+        let flags = VerifyFlags::STANDARD;
+        verify_input(&tx, 0, &prevouts, flags).unwrap();
+
+        tx.lock_time = absolute::LockTime::from_consensus(99);
+        assert_eq!(verify_input(&tx, 0, &prevouts, flags), Err(Error::UnsatisfiedLockTime));
+
+        // Without the flag CLTV is a NOP.
+        verify_input(&tx, 0, &prevouts, VerifyFlags::P2SH).unwrap();
+    }
+
+    #[test]
+    fn check_sequence_unsigned_version() {
+        let script_pubkey = Builder::new().push_int(1).push_opcode(OP_CSV).into_script();
+        let mut tx = Transaction {
+            version: Version(-1),
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(crate::Txid::all_zeros(), 0),
+                sequence: Sequence::from_height(1),
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+        let prevouts = vec![TxOut { value: Amount::from_sat(1000), script_pubkey }];
+        let flags = VerifyFlags::STANDARD;
+        verify_input(&tx, 0, &prevouts, flags).unwrap();
+
+        tx.version = Version::ONE;
+        assert_eq!(verify_input(&tx, 0, &prevouts, flags), Err(Error::UnsatisfiedLockTime));
+    }
+
+    #[test]
+    fn verify_unknown_leaf_version() {
+        use crate::key::{Keypair, TweakedPublicKey};
+
+        let secp = Secp256k1::new();
+        let keypair = Keypair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+        let (internal_key, _) = keypair.x_only_public_key();
+        let leaf = Builder::new().push_opcode(OP_RETURN).into_script();
+        // 0x50 can't be represented by `LeafVersion` but is a valid, unknown, leaf version.
+        let mut engine = TapLeafHash::engine();
+        0x50u8.consensus_encode(&mut engine).unwrap();
+        leaf.consensus_encode(&mut engine).unwrap();
+        let merkle_root = TapNodeHash::from(TapLeafHash::from_engine(engine));
+        let tweak = TapTweakHash::from_key_and_tweak(internal_key, Some(merkle_root)).to_scalar();
+        let (output_key, parity) = internal_key.add_tweak(&secp, &tweak).unwrap();
+        let prevouts = vec![TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(
+                output_key,
+            )),
+        }];
+        let mut control_block = vec![0x50 | parity.to_u8()];
+        control_block.extend_from_slice(&internal_key.serialize());
+        // The control block starts with 0x50 or 0x51, so an annex is needed to tell them apart.
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(crate::Txid::all_zeros(), 0),
+                witness: Witness::from_slice(&[leaf.as_bytes(), &control_block, &[0x50]]),
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+
+        // Unknown leaf versions are left unexecuted for future soft forks.
+        verify_input(&tx, 0, &prevouts, VerifyFlags::CONSENSUS).unwrap();
+        assert_eq!(
+            verify_input(&tx, 0, &prevouts, VerifyFlags::STANDARD),
+            Err(Error::DiscourageUpgradableTaprootVersion)
+        );
+    }
+
+    /// The script flags by the names used in Bitcoin Core's test data.
+    const CORE_FLAGS: [(&str, VerifyFlags); 21] = [
+        ("NONE", VerifyFlags::NONE),
+        ("P2SH", VerifyFlags::P2SH),
+        ("STRICTENC", VerifyFlags::STRICTENC),
+        ("DERSIG", VerifyFlags::DERSIG),
+        ("LOW_S", VerifyFlags::LOW_S),
+        ("NULLDUMMY", VerifyFlags::NULLDUMMY),
+        ("SIGPUSHONLY", VerifyFlags::SIGPUSHONLY),
+        ("MINIMALDATA", VerifyFlags::MINIMALDATA),
+        ("DISCOURAGE_UPGRADABLE_NOPS", VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS),
+        ("CLEANSTACK", VerifyFlags::CLEANSTACK),
+        ("CHECKLOCKTIMEVERIFY", VerifyFlags::CHECKLOCKTIMEVERIFY),
+        ("CHECKSEQUENCEVERIFY", VerifyFlags::CHECKSEQUENCEVERIFY),
+        ("WITNESS", VerifyFlags::WITNESS),
+        (
+            "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM",
+            VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM,
+        ),
+        ("MINIMALIF", VerifyFlags::MINIMALIF),
+        ("NULLFAIL", VerifyFlags::NULLFAIL),
+        ("WITNESS_PUBKEYTYPE", VerifyFlags::WITNESS_PUBKEYTYPE),
+        ("TAPROOT", VerifyFlags::TAPROOT),
+        (
+            "DISCOURAGE_UPGRADABLE_TAPROOT_VERSION",
+            VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION,
+        ),
+        ("DISCOURAGE_OP_SUCCESS", VerifyFlags::DISCOURAGE_OP_SUCCESS),
+        ("DISCOURAGE_UPGRADABLE_PUBKEYTYPE", VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE),
+    ];
+
+    fn parse_core_flags(flags: &str) -> VerifyFlags {
+        flags.split(',').filter(|name| !name.is_empty()).fold(VerifyFlags::NONE, |acc, name| {
+            match CORE_FLAGS.iter().find(|(n, _)| *n == name) {
+                Some((_, flag)) => acc | *flag,
+                None => panic!("unknown flag {}", name),
+            }
+        })
+    }
+
+    /// Parses a script in the notation of Bitcoin Core's test data.
+    fn parse_core_script(asm: &str) -> ScriptBuf {
+        let mut bytes = Vec::new();
+        for token in asm.split_whitespace() {
+            if let Ok(n) = token.parse::<i64>() {
+                bytes.extend_from_slice(Builder::new().push_int(n).as_bytes());
+            } else if let Some(raw) = token.strip_prefix("0x") {
+                bytes.extend(hex!(raw));
+            } else if let Some(s) = token.strip_prefix('\'').and_then(|s| s.strip_suffix('\'')) {
+                let push = <&PushBytes>::try_from(s.as_bytes()).unwrap();
+                bytes.extend_from_slice(Builder::new().push_slice(push).as_bytes());
+            } else {
+                let name =
+                    if token.starts_with("OP_") { token.into() } else { format!("OP_{}", token) };
+                let op =
+                    Opcode::from_name(&name).unwrap_or_else(|| panic!("unknown opcode {}", token));
+                bytes.push(op.to_u8());
+            }
+        }
+        ScriptBuf::from_bytes(bytes)
+    }
+
+    #[test]
+    fn script_test_vectors() {
+        let data = include_str!("../../../tests/data/script_tests.json");
+        let tests: Vec<serde_json::Value> = serde_json::from_str(data).unwrap();
+        for test in &tests {
+            let mut test = test.as_array().unwrap().as_slice();
+            // Comments.
+            if test.len() == 1 {
+                continue;
+            }
+            let (witness, amount) = match test[0].as_array() {
+                Some(items) => {
+                    test = &test[1..];
+                    let (amount, items) = items.split_last().unwrap();
+                    let witness: Vec<_> = items.iter().map(|w| hex!(w.as_str().unwrap())).collect();
+                    let amount = Amount::from_btc(amount.as_f64().unwrap()).unwrap();
+                    (Witness::from_slice(&witness), amount)
+                }
+                None => (Witness::new(), Amount::ZERO),
+            };
+            let script_sig = parse_core_script(test[0].as_str().unwrap());
+            let script_pubkey = parse_core_script(test[1].as_str().unwrap());
+            let flags = parse_core_flags(test[2].as_str().unwrap());
+            let expected = test[3].as_str().unwrap();
+
+            let credit = Transaction {
+                version: Version::ONE,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::null(),
+                    script_sig: Builder::new().push_int(0).push_int(0).into_script(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                }],
+                output: vec![TxOut { value: amount, script_pubkey }],
+            };
+            let spend = Transaction {
+                version: Version::ONE,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![TxIn {
+                    previous_output: OutPoint::new(credit.compute_txid(), 0),
+                    script_sig,
+                    sequence: Sequence::MAX,
+                    witness,
+                }],
+                output: vec![TxOut { value: amount, script_pubkey: ScriptBuf::new() }],
+            };
+            let result = verify_input(&spend, 0, &credit.output, flags);
+            assert_eq!(result.is_ok(), expected == "OK", "{:?}: {:?}", test, result);
+        }
+    }
+
+    /// Checks the scripts of transactions given in the format of Bitcoin Core's `tx_valid.json`
+    /// and `tx_invalid.json`, the other checks of `CheckTransaction` are not done.
+    fn check_tx_tests(data: &str, valid: bool) {
+        let all_flags = CORE_FLAGS.iter().fold(VerifyFlags::NONE, |acc, (_, flag)| acc | *flag);
+        let tests: Vec<serde_json::Value> = serde_json::from_str(data).unwrap();
+        for test in &tests {
+            let test = test.as_array().unwrap();
+            // Comments.
+            if test[0].is_string() {
+                continue;
+            }
+            let inputs = test[0].as_array().unwrap();
+            let tx: Transaction = deserialize(&hex!(test[1].as_str().unwrap())).unwrap();
+            let flags = parse_core_flags(test[2].as_str().unwrap());
+            let flags = if valid {
+                VerifyFlags::from_bits(all_flags.bits() & !flags.bits())
+            } else {
+                flags
+            };
+
+            let prevouts: Vec<_> = tx
+                .input
+                .iter()
+                .map(|txin| {
+                    let input = inputs
+                        .iter()
+                        .map(|input| input.as_array().unwrap())
+                        .find(|input| {
+                            let txid: crate::Txid = input[0].as_str().unwrap().parse().unwrap();
+                            let vout = input[1].as_i64().unwrap() as u32;
+                            txin.previous_output == OutPoint::new(txid, vout)
+                        })
+                        .unwrap();
+                    let value = input
+                        .get(3)
+                        .map_or(Amount::ZERO, |amount| Amount::from_sat(amount.as_u64().unwrap()));
+                    TxOut { value, script_pubkey: parse_core_script(input[2].as_str().unwrap()) }
+                })
+                .collect();
+            let result =
+                (0..tx.input.len()).try_for_each(|i| verify_input(&tx, i, &prevouts, flags));
+            assert_eq!(result.is_ok(), valid, "{:?}: {:?}", test, result);
+        }
+    }
+
+    #[test]
+    fn tx_valid_vectors() {
+        check_tx_tests(include_str!("../../../tests/data/tx_valid.json"), true)
+    }
+
+    #[test]
+    fn tx_invalid_vectors() {
+        check_tx_tests(include_str!("../../../tests/data/tx_invalid.json"), false)
+    }
+}
@@ -50,8 +50,11 @@
 mod borrowed;
 mod builder;
 mod instruction;
+#[cfg(feature = "interpreter")]
+pub mod interpreter;
 mod owned;
 mod push_bytes;
+mod script_type;
 #[cfg(test)]
 mod tests;
 pub mod witness_program;
@@ -65,6 +68,7 @@ use core::fmt;
 use core::ops::{Deref, DerefMut};
 
 use hashes::{hash160, sha256};
+use internals::write_err;
 use io::{BufRead, Write};
 
 use crate::blockdata::opcodes::all::*;
@@ -82,6 +86,7 @@ pub use self::{
     instruction::*,
     owned::*,
     push_bytes::*,
+    script_type::*,
 };
 
 hashes::hash_newtype! {
@@ -732,6 +737,202 @@ impl std::error::Error for Error {
     }
 }
 
+/// Error returned by [`ScriptBuf::new_multisig`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MultisigError {
+    /// More than 16 public keys were provided.
+    TooManyKeys(usize),
+    /// The threshold is zero or larger than the number of keys.
+    InvalidThreshold {
+        /// The required number of signatures.
+        threshold: u8,
+        /// The number of public keys.
+        keys: usize,
+    },
+}
+
+internals::impl_from_infallible!(MultisigError);
+
+impl fmt::Display for MultisigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use MultisigError::*;
+
+        match *self {
+            TooManyKeys(n) => write!(f, "bare multisig supports at most 16 keys, got {}", n),
+            InvalidThreshold { threshold, keys } =>
+                write!(f, "invalid multisig threshold {} for {} keys", threshold, keys),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MultisigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use MultisigError::*;
+
+        match *self {
+            TooManyKeys(_) | InvalidThreshold { .. } => None,
+        }
+    }
+}
+
+/// Error returned by [`Script::check_tapscript`].
+///
+/// Positions are byte offsets of the offending instruction within the script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TapscriptError {
+    /// The script could not be parsed into instructions.
+    Parse(Error),
+    /// The script contains an `OP_SUCCESSx` opcode, making it spendable by anyone.
+    OpSuccess {
+        /// Position of the opcode.
+        position: usize,
+        /// The `OP_SUCCESSx` opcode.
+        opcode: Opcode,
+    },
+    /// The script contains an opcode that is invalid even when not executed.
+    IllegalOpcode {
+        /// Position of the opcode.
+        position: usize,
+        /// The illegal opcode.
+        opcode: Opcode,
+    },
+    /// The script contains `OP_CHECKMULTISIG` or `OP_CHECKMULTISIGVERIFY` which are disabled in
+    /// tapscript ([BIP-342]).
+    ///
+    /// [BIP-342]: <https://github.com/bitcoin/bips/blob/master/bip-0342.mediawiki>
+    CheckMultisig {
+        /// Position of the opcode.
+        position: usize,
+    },
+    /// The script pushes an element larger than 520 bytes.
+    PushTooLarge {
+        /// Position of the push.
+        position: usize,
+        /// Length of the pushed element.
+        len: usize,
+    },
+    /// The script contains unbalanced `OP_IF`/`OP_NOTIF`, `OP_ELSE` and `OP_ENDIF` opcodes.
+    UnbalancedConditional,
+}
+
+internals::impl_from_infallible!(TapscriptError);
+
+impl fmt::Display for TapscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TapscriptError::*;
+
+        match *self {
+            Parse(ref e) => write_err!(f, "failed to parse tapscript"; e),
+            OpSuccess { position, opcode } =>
+                write!(f, "{} at position {} makes the script anyone-can-spend", opcode, position),
+            IllegalOpcode { position, opcode } =>
+                write!(f, "illegal opcode {} at position {}", opcode, position),
+            CheckMultisig { position } =>
+                write!(f, "checkmultisig at position {} is disabled in tapscript", position),
+            PushTooLarge { position, len } =>
+                write!(f, "push of {} bytes at position {} exceeds 520 bytes", len, position),
+            UnbalancedConditional => f.write_str("unbalanced conditional"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TapscriptError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use TapscriptError::*;
+
+        match *self {
+            Parse(ref e) => Some(e),
+            OpSuccess { .. }
+            | IllegalOpcode { .. }
+            | CheckMultisig { .. }
+            | PushTooLarge { .. }
+            | UnbalancedConditional => None,
+        }
+    }
+}
+
+/// Error returned by [`ScriptBuf::new_op_return_multi`] if the script would not be relayed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct OpReturnSizeError {
+    /// Size of the script in bytes.
+    pub size: usize,
+}
+
+impl fmt::Display for OpReturnSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "OP_RETURN script of {} bytes exceeds the standard limit of {} bytes",
+            self.size,
+            crate::policy::MAX_OP_RETURN_RELAY
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OpReturnSizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+/// Error returned by [`ScriptBuf::from_asm_string`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseAsmError {
+    /// A token starting with `OP_` is not a known opcode name.
+    UnknownOpcode(String),
+    /// A push opcode is not followed by its data.
+    MissingPushData(Opcode),
+    /// The length of the data does not fit the push opcode preceding it.
+    InvalidPushLength {
+        /// The push opcode.
+        opcode: Opcode,
+        /// Length of the data in bytes.
+        len: usize,
+    },
+    /// A decimal number is out of the range `-0xffffffff..=0xffffffff`.
+    InvalidNumber(String),
+    /// Invalid hex data.
+    Hex(hex::HexToBytesError),
+}
+
+internals::impl_from_infallible!(ParseAsmError);
+
+impl fmt::Display for ParseAsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseAsmError::*;
+
+        match *self {
+            UnknownOpcode(ref s) => write!(f, "unknown opcode: {}", s),
+            MissingPushData(opcode) => write!(f, "{} is not followed by data", opcode),
+            InvalidPushLength { opcode, len } => write!(f, "{} can not push {} bytes", opcode, len),
+            InvalidNumber(ref s) => write!(f, "number out of range: {}", s),
+            Hex(ref e) => write_err!(f, "invalid hex data"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseAsmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ParseAsmError::*;
+
+        match *self {
+            Hex(ref e) => Some(e),
+            UnknownOpcode(_) | MissingPushData(_) | InvalidPushLength { .. } | InvalidNumber(_) =>
+                None,
+        }
+    }
+}
+
+impl From<hex::HexToBytesError> for ParseAsmError {
+    fn from(e: hex::HexToBytesError) -> Self { Self::Hex(e) }
+}
+
 // Our internal error proves that we only return these two cases from `read_uint_iter`.
 // Since it's private we don't bother with trait impls besides From.
 enum UintError {
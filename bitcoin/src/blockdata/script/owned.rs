@@ -3,21 +3,25 @@
 #[cfg(doc)]
 use core::ops::Deref;
 
+use hashes::{sha256, Hash};
 use hex::FromHex;
 use secp256k1::{Secp256k1, Verification};
 
+use crate::blockdata::locktime::{absolute, relative};
 use crate::blockdata::opcodes::all::*;
 use crate::blockdata::opcodes::{self, Opcode};
 use crate::blockdata::script::witness_program::WitnessProgram;
 use crate::blockdata::script::witness_version::WitnessVersion;
 use crate::blockdata::script::{
-    opcode_to_verify, Builder, Instruction, PushBytes, Script, ScriptHash, WScriptHash,
+    opcode_to_verify, Builder, Instruction, MultisigError, OpReturnSizeError, ParseAsmError,
+    PushBytes, PushBytesBuf, Script, ScriptHash, WScriptHash,
 };
 use crate::key::{
     PubkeyHash, PublicKey, TapTweak, TweakedPublicKey, UntweakedPublicKey, WPubkeyHash,
 };
-use crate::taproot::TapNodeHash;
+use crate::policy::MAX_OP_RETURN_RELAY;
 use crate::prelude::*;
+use crate::taproot::TapNodeHash;
 
 /// An owned, growable script.
 ///
@@ -173,12 +177,194 @@ impl ScriptBuf {
         Builder::new().push_opcode(OP_RETURN).push_slice(data).into_script()
     }
 
+    /// Generates an OP_RETURN-type scriptPubkey pushing each of the given data items.
+    ///
+    /// Returns an error if the script is larger than [`MAX_OP_RETURN_RELAY`] bytes and thus not
+    /// relayed by default, use [`ScriptBuf::new_op_return_multi_unchecked`] to skip this check.
+    ///
+    /// [`MAX_OP_RETURN_RELAY`]: crate::policy::MAX_OP_RETURN_RELAY
+    pub fn new_op_return_multi<T: AsRef<PushBytes>>(data: &[T]) -> Result<Self, OpReturnSizeError> {
+        let script = ScriptBuf::new_op_return_multi_unchecked(data);
+        if script.len() > MAX_OP_RETURN_RELAY as usize {
+            return Err(OpReturnSizeError { size: script.len() });
+        }
+        Ok(script)
+    }
+
+    /// Generates an OP_RETURN-type scriptPubkey pushing each of the given data items.
+    ///
+    /// Does not check the script size against the standardness limit.
+    pub fn new_op_return_multi_unchecked<T: AsRef<PushBytes>>(data: &[T]) -> Self {
+        let mut builder = Builder::new().push_opcode(OP_RETURN);
+        for item in data {
+            builder = builder.push_slice(item);
+        }
+        builder.into_script()
+    }
+
+    /// Generates a bare `threshold`-of-`pubkeys.len()` multisig script.
+    ///
+    /// The script is `<threshold> <pubkey>... <pubkeys.len()> OP_CHECKMULTISIG`, keys are used in
+    /// the given order. At most 16 keys are supported so that both counts are single opcodes.
+    pub fn new_multisig(threshold: u8, pubkeys: &[PublicKey]) -> Result<Self, MultisigError> {
+        if pubkeys.len() > 16 {
+            return Err(MultisigError::TooManyKeys(pubkeys.len()));
+        }
+        if threshold == 0 || usize::from(threshold) > pubkeys.len() {
+            return Err(MultisigError::InvalidThreshold { threshold, keys: pubkeys.len() });
+        }
+        let builder = pubkeys
+            .iter()
+            .fold(Builder::new().push_int(threshold.into()), |builder, key| builder.push_key(key));
+        Ok(builder.push_int(pubkeys.len() as i64).push_opcode(OP_CHECKMULTISIG).into_script())
+    }
+
+    /// Generates a script spendable by `pubkey` once the absolute `lock_time` is reached ([BIP-65]).
+    ///
+    /// The script is `<lock_time> OP_CHECKLOCKTIMEVERIFY OP_DROP <pubkey> OP_CHECKSIG`.
+    ///
+    /// [BIP-65]: <https://github.com/bitcoin/bips/blob/master/bip-0065.mediawiki>
+    pub fn new_cltv_timelocked(pubkey: &PublicKey, lock_time: absolute::LockTime) -> Self {
+        Builder::new()
+            .push_lock_time(lock_time)
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_DROP)
+            .push_key(pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Generates a script spendable by `pubkey` once the output is `lock_time` old ([BIP-112]).
+    ///
+    /// The script is `<lock_time> OP_CHECKSEQUENCEVERIFY OP_DROP <pubkey> OP_CHECKSIG`.
+    ///
+    /// [BIP-112]: <https://github.com/bitcoin/bips/blob/master/bip-0112.mediawiki>
+    pub fn new_csv_timelocked(pubkey: &PublicKey, lock_time: relative::LockTime) -> Self {
+        Builder::new()
+            .push_relative_lock_time(lock_time)
+            .push_opcode(OP_CSV)
+            .push_opcode(OP_DROP)
+            .push_key(pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Generates a hash time locked contract script.
+    ///
+    /// The `receiver` can spend by revealing the preimage of `payment_hash`, the `sender` can
+    /// spend once `timeout` is reached:
+    ///
+    /// ```text
+    /// OP_IF
+    ///     OP_SHA256 <payment_hash> OP_EQUALVERIFY <receiver>
+    /// OP_ELSE
+    ///     <timeout> OP_CHECKLOCKTIMEVERIFY OP_DROP <sender>
+    /// OP_ENDIF
+    /// OP_CHECKSIG
+    /// ```
+    pub fn new_htlc(
+        payment_hash: &sha256::Hash,
+        receiver: &PublicKey,
+        sender: &PublicKey,
+        timeout: absolute::LockTime,
+    ) -> Self {
+        Builder::new()
+            .push_opcode(OP_IF)
+            .push_opcode(OP_SHA256)
+            .push_slice(payment_hash.as_byte_array())
+            .push_opcode(OP_EQUALVERIFY)
+            .push_key(receiver)
+            .push_opcode(OP_ELSE)
+            .push_lock_time(timeout)
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_DROP)
+            .push_key(sender)
+            .push_opcode(OP_ENDIF)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
     /// Creates a [`ScriptBuf`] from a hex string.
     pub fn from_hex(s: &str) -> Result<Self, hex::HexToBytesError> {
         let v = Vec::from_hex(s)?;
         Ok(ScriptBuf::from_bytes(v))
     }
 
+    /// Parses the assembly format written by [`Script::to_asm_string`].
+    ///
+    /// Opcodes are given by name, including the aliases like `OP_TRUE` and the names used by
+    /// Bitcoin Core like `OP_CHECKSEQUENCEVERIFY`. Push opcodes must be followed by their data in
+    /// hex, which preserves the exact encoding of the push.
+    ///
+    /// As in the format used by Bitcoin Core, decimal numbers (e.g. `0` or `-1`) between
+    /// `-0xffffffff` and `0xffffffff` are pushed as script integers using the shortest encoding.
+    /// Other hex data without a preceding push opcode is pushed using the shortest push opcode.
+    pub fn from_asm_string(asm: &str) -> Result<Self, ParseAsmError> {
+        let mut script = ScriptBuf::new();
+        let mut tokens = asm.split_whitespace().peekable();
+        while let Some(token) = tokens.next() {
+            let digits = token.strip_prefix('-').unwrap_or(token);
+            if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+                let n = token
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|n| n.unsigned_abs() <= 0xffff_ffff)
+                    .ok_or_else(|| ParseAsmError::InvalidNumber(token.to_owned()))?;
+                script.0.extend_from_slice(Builder::new().push_int(n).as_bytes());
+                continue;
+            }
+            if !token.starts_with("OP_") {
+                let data = Vec::from_hex(token)?;
+                let len = data.len();
+                let data = PushBytesBuf::try_from(data)
+                    .map_err(|_| ParseAsmError::InvalidPushLength { opcode: OP_PUSHDATA4, len })?;
+                script.push_slice(data);
+                continue;
+            }
+
+            let opcode = Opcode::from_name(token)
+                .ok_or_else(|| ParseAsmError::UnknownOpcode(token.to_owned()))?;
+            let push_len = match opcode.classify(opcodes::ClassifyContext::Legacy) {
+                opcodes::Class::PushBytes(n) => n as usize,
+                _ => 0,
+            };
+            let is_push_data = matches!(opcode, OP_PUSHDATA1 | OP_PUSHDATA2 | OP_PUSHDATA4);
+            if push_len == 0 && !is_push_data {
+                script.push_opcode(opcode);
+                continue;
+            }
+
+            // Empty `OP_PUSHDATA` pushes are written without data.
+            let data = match tokens.peek() {
+                Some(data) if !data.starts_with("OP_") => Vec::from_hex(data)?,
+                _ if is_push_data => Vec::new(),
+                _ => return Err(ParseAsmError::MissingPushData(opcode)),
+            };
+            if !data.is_empty() {
+                tokens.next();
+            }
+            let len = data.len();
+            let fits = match opcode {
+                OP_PUSHDATA1 => len <= u8::MAX as usize,
+                OP_PUSHDATA2 => len <= u16::MAX as usize,
+                OP_PUSHDATA4 => u32::try_from(len).is_ok(),
+                _ => len == push_len,
+            };
+            if !fits {
+                return Err(ParseAsmError::InvalidPushLength { opcode, len });
+            }
+            script.push_opcode(opcode);
+            match opcode {
+                OP_PUSHDATA1 => script.0.push(len as u8),
+                OP_PUSHDATA2 => script.0.extend_from_slice(&(len as u16).to_le_bytes()),
+                OP_PUSHDATA4 => script.0.extend_from_slice(&(len as u32).to_le_bytes()),
+                _ => {}
+            }
+            script.0.extend_from_slice(&data);
+        }
+        Ok(script)
+    }
+
     /// Converts byte vector into script.
     ///
     /// This method doesn't (re)allocate.
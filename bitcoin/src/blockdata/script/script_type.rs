@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Classification of output scripts into well known templates.
+
+use hashes::Hash;
+use secp256k1::XOnlyPublicKey;
+
+use super::{Instruction, PushBytes, Script, ScriptHash, WScriptHash};
+use crate::blockdata::opcodes::all::*;
+use crate::blockdata::script::witness_version::WitnessVersion;
+use crate::key::{PubkeyHash, PublicKey, TweakedPublicKey, WPubkeyHash};
+use crate::prelude::*;
+
+/// The template of a script pubkey together with the data it commits to.
+///
+/// Returned by [`Script::classify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScriptType<'a> {
+    /// Pay to public key.
+    P2pk(PublicKey),
+    /// Pay to public key hash.
+    P2pkh(PubkeyHash),
+    /// Pay to script hash ([BIP-16]).
+    ///
+    /// [BIP-16]: <https://github.com/bitcoin/bips/blob/master/bip-0016.mediawiki>
+    P2sh(ScriptHash),
+    /// Pay to witness public key hash ([BIP-141]).
+    ///
+    /// [BIP-141]: <https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki>
+    P2wpkh(WPubkeyHash),
+    /// Pay to witness script hash ([BIP-141]).
+    ///
+    /// [BIP-141]: <https://github.com/bitcoin/bips/blob/master/bip-0141.mediawiki>
+    P2wsh(WScriptHash),
+    /// Pay to taproot ([BIP-341]).
+    ///
+    /// [BIP-341]: <https://github.com/bitcoin/bips/blob/master/bip-0341.mediawiki>
+    P2tr(TweakedPublicKey),
+    /// A provably unspendable `OP_RETURN` output, with the script following the `OP_RETURN`.
+    OpReturn(&'a Script),
+    /// Bare `required`-of-`pubkeys.len()` multisig.
+    Multisig {
+        /// Number of signatures required to spend.
+        required: u8,
+        /// The public keys in script order.
+        pubkeys: Vec<PublicKey>,
+    },
+    /// A witness program of a version or length without defined semantics.
+    WitnessUnknown {
+        /// The witness version, never [`WitnessVersion::V0`].
+        version: WitnessVersion,
+        /// The witness program.
+        program: &'a PushBytes,
+    },
+    /// Any other script, including templates committing to invalid public keys.
+    NonStandard,
+}
+
+impl Script {
+    /// Classifies this script pubkey and extracts the data it commits to.
+    ///
+    /// Scripts matching a template but containing an invalid public key are unspendable by that
+    /// template and thus classified as [`ScriptType::NonStandard`].
+    pub fn classify(&self) -> ScriptType<'_> {
+        let bytes = self.as_bytes();
+
+        if self.is_p2pkh() {
+            return ScriptType::P2pkh(PubkeyHash::from_slice(&bytes[3..23]).expect("20 bytes"));
+        }
+        if self.is_p2sh() {
+            return ScriptType::P2sh(ScriptHash::from_slice(&bytes[2..22]).expect("20 bytes"));
+        }
+        if self.is_witness_program() {
+            let version = self.witness_version().expect("witness program has a version");
+            let program = <&PushBytes>::try_from(&bytes[2..]).expect("at most 40 bytes");
+            return match (version, program.len()) {
+                (WitnessVersion::V0, 20) =>
+                    ScriptType::P2wpkh(WPubkeyHash::from_slice(program.as_bytes()).expect("20")),
+                (WitnessVersion::V0, 32) =>
+                    ScriptType::P2wsh(WScriptHash::from_slice(program.as_bytes()).expect("32")),
+                (WitnessVersion::V0, _) => ScriptType::NonStandard,
+                (WitnessVersion::V1, 32) => match XOnlyPublicKey::from_slice(program.as_bytes()) {
+                    Ok(key) => ScriptType::P2tr(TweakedPublicKey::dangerous_assume_tweaked(key)),
+                    Err(_) => ScriptType::NonStandard,
+                },
+                (version, _) => ScriptType::WitnessUnknown { version, program },
+            };
+        }
+        if self.is_op_return() {
+            return ScriptType::OpReturn(Script::from_bytes(&bytes[1..]));
+        }
+        if let Some(pubkey) = self.p2pk_pubkey_bytes() {
+            return match PublicKey::from_slice(pubkey) {
+                Ok(pubkey) => ScriptType::P2pk(pubkey),
+                Err(_) => ScriptType::NonStandard,
+            };
+        }
+        self.classify_multisig().unwrap_or(ScriptType::NonStandard)
+    }
+
+    /// Parses a bare multisig script, returns `None` if this is not one or a key is invalid.
+    fn classify_multisig(&self) -> Option<ScriptType<'_>> {
+        let mut instructions = self.instructions();
+        let required = match instructions.next()? {
+            Ok(Instruction::Op(op)) => op.decode_pushnum()?,
+            _ => return None,
+        };
+        let mut pubkeys = Vec::new();
+        let count = loop {
+            match instructions.next()?.ok()? {
+                Instruction::PushBytes(bytes) =>
+                    pubkeys.push(PublicKey::from_slice(bytes.as_bytes()).ok()?),
+                Instruction::Op(op) => break op.decode_pushnum()?,
+            }
+        };
+        if usize::from(count) != pubkeys.len() || required > count {
+            return None;
+        }
+        match instructions.next()? {
+            Ok(Instruction::Op(OP_CHECKMULTISIG)) => {}
+            _ => return None,
+        }
+        if instructions.next().is_some() {
+            return None;
+        }
+        Some(ScriptType::Multisig { required, pubkeys })
+    }
+}
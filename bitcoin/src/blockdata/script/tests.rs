@@ -7,8 +7,8 @@ use hex_lit::hex;
 
 use super::*;
 use crate::consensus::encode::{deserialize, serialize};
-use crate::crypto::key::{PubkeyHash, PublicKey, WPubkeyHash, XOnlyPublicKey};
-use crate::FeeRate;
+use crate::crypto::key::{PubkeyHash, PublicKey, TweakedPublicKey, WPubkeyHash, XOnlyPublicKey};
+use crate::{FeeRate, Weight};
 
 #[test]
 #[rustfmt::skip]
@@ -232,6 +232,160 @@ fn script_generators() {
     );
 }
 
+#[test]
+fn script_template_generators() {
+    use crate::locktime::{absolute, relative};
+
+    let alice =
+        PublicKey::from_str("0234e6a79c5359c613762d537e0e19d86c77c1666d8c9ab050f23acd198e97f93e")
+            .unwrap();
+    let bob =
+        PublicKey::from_str("03f7ed2b36a9dc1ac3e5db4ea8b4cc2cd2e0f7ab1c6ee6d1e3fbbbeaf85e2c1e5c")
+            .unwrap();
+
+    let cltv = ScriptBuf::new_cltv_timelocked(&alice, absolute::LockTime::from_consensus(500_000));
+    assert_eq!(
+        cltv.to_hex_string(),
+        "0320a107b1752102\
+         34e6a79c5359c613762d537e0e19d86c77c1666d8c9ab050f23acd198e97f93eac"
+    );
+    assert!(cltv.is_cltv_timelocked());
+    assert!(!cltv.is_csv_timelocked());
+    assert!(!cltv.is_htlc());
+
+    let blocks = |n: u16| relative::LockTime::from(relative::Height::from(n));
+    let csv = ScriptBuf::new_csv_timelocked(&alice, blocks(144));
+    assert_eq!(
+        csv.to_hex_string(),
+        "029000b2752102\
+         34e6a79c5359c613762d537e0e19d86c77c1666d8c9ab050f23acd198e97f93eac"
+    );
+    assert!(csv.is_csv_timelocked());
+    assert!(!csv.is_cltv_timelocked());
+    // Small lock times are pushed with `OP_PUSHNUM_x`.
+    assert!(ScriptBuf::new_csv_timelocked(&alice, blocks(6)).is_csv_timelocked());
+
+    let payment_hash = sha256::Hash::hash(b"preimage");
+    let htlc =
+        ScriptBuf::new_htlc(&payment_hash, &bob, &alice, absolute::LockTime::from_consensus(800));
+    assert!(htlc.is_htlc());
+    assert!(!htlc.is_cltv_timelocked());
+    assert_eq!(htlc.instructions().count(), 12);
+
+    let multisig = ScriptBuf::new_multisig(2, &[alice, bob]).unwrap();
+    assert!(multisig.is_multisig());
+    assert_eq!(multisig.as_bytes()[0], OP_PUSHNUM_2.to_u8());
+    assert_eq!(multisig.as_bytes()[multisig.len() - 2], OP_PUSHNUM_2.to_u8());
+    assert_eq!(
+        ScriptBuf::new_multisig(3, &[alice, bob]),
+        Err(MultisigError::InvalidThreshold { threshold: 3, keys: 2 })
+    );
+    assert_eq!(
+        ScriptBuf::new_multisig(0, &[alice]),
+        Err(MultisigError::InvalidThreshold { threshold: 0, keys: 1 })
+    );
+    assert_eq!(ScriptBuf::new_multisig(1, &[alice; 17]), Err(MultisigError::TooManyKeys(17)));
+
+    // Non-minimal pushes are not recognized.
+    let mut non_minimal = vec![OP_PUSHDATA1.to_u8(), 3];
+    non_minimal.extend_from_slice(&cltv.as_bytes()[1..]);
+    assert!(!Script::from_bytes(&non_minimal).is_cltv_timelocked());
+}
+
+#[test]
+fn script_classify() {
+    let pubkey =
+        PublicKey::from_str("0234e6a79c5359c613762d537e0e19d86c77c1666d8c9ab050f23acd198e97f93e")
+            .unwrap();
+    assert_eq!(ScriptBuf::new_p2pk(&pubkey).classify(), ScriptType::P2pk(pubkey));
+
+    let pubkey_hash = PubkeyHash::hash(&pubkey.inner.serialize());
+    assert_eq!(ScriptBuf::new_p2pkh(&pubkey_hash).classify(), ScriptType::P2pkh(pubkey_hash));
+
+    let wpubkey_hash = WPubkeyHash::hash(&pubkey.inner.serialize());
+    assert_eq!(ScriptBuf::new_p2wpkh(&wpubkey_hash).classify(), ScriptType::P2wpkh(wpubkey_hash));
+
+    let script = Builder::new().push_opcode(OP_NUMEQUAL).push_verify().into_script();
+    assert_eq!(script.to_p2sh().classify(), ScriptType::P2sh(script.script_hash()));
+    assert_eq!(script.to_p2wsh().classify(), ScriptType::P2wsh(script.wscript_hash()));
+    assert_eq!(script.classify(), ScriptType::NonStandard);
+
+    let output_key = TweakedPublicKey::dangerous_assume_tweaked(
+        XOnlyPublicKey::from_str(
+            "a47e88bd47b81c19a8745e79daa457aeb3aafe8a69afb8d3b10e5bec12b6c1e8",
+        )
+        .unwrap(),
+    );
+    assert_eq!(ScriptBuf::new_p2tr_tweaked(output_key).classify(), ScriptType::P2tr(output_key));
+
+    let op_return = ScriptBuf::new_op_return([0xab; 4]);
+    assert_eq!(
+        op_return.classify(),
+        ScriptType::OpReturn(Script::from_bytes(&[4, 0xab, 0xab, 0xab, 0xab]))
+    );
+
+    let multisig = ScriptBuf::new_multisig(1, &[pubkey, pubkey]).unwrap();
+    assert_eq!(
+        multisig.classify(),
+        ScriptType::Multisig { required: 1, pubkeys: vec![pubkey, pubkey] }
+    );
+
+    let program = [0x01; 10];
+    let future = ScriptBuf::new_witness_program(
+        &witness_program::WitnessProgram::new(witness_version::WitnessVersion::V2, &program)
+            .unwrap(),
+    );
+    assert_eq!(
+        future.classify(),
+        ScriptType::WitnessUnknown {
+            version: witness_version::WitnessVersion::V2,
+            program: <&PushBytes>::try_from(&program[..]).unwrap(),
+        }
+    );
+
+    // A P2PK with an invalid public key.
+    let mut invalid = ScriptBuf::new_p2pk(&pubkey).into_bytes();
+    invalid[1] = 0x05;
+    assert_eq!(Script::from_bytes(&invalid).classify(), ScriptType::NonStandard);
+    assert_eq!(Script::new().classify(), ScriptType::NonStandard);
+}
+
+#[test]
+fn script_builder_typed_pushes() {
+    use crate::locktime::{absolute, relative};
+    use crate::{Amount, Sequence};
+
+    let script = Builder::new()
+        .push_lock_time(absolute::LockTime::from_consensus(500_000))
+        .push_relative_lock_time(relative::LockTime::from(relative::Height::from(144)))
+        .push_sequence(Sequence::from_consensus(10))
+        .push_amount(Amount::from_sat(100_000))
+        .push_amount(Amount::ZERO)
+        .into_script();
+    assert_eq!(script.to_hex_string(), "0320a1070290005a03a0860100");
+
+    let x_only_key = XOnlyPublicKey::from_str(
+        "a47e88bd47b81c19a8745e79daa457aeb3aafe8a69afb8d3b10e5bec12b6c1e8",
+    )
+    .unwrap();
+    let script = Builder::new()
+        .push_x_only_key(&x_only_key)
+        .push_opcode(OP_CHECKSIG)
+        .push_verify()
+        .push_amount(Amount::from_sat(1))
+        .into_script();
+    assert_eq!(
+        script.to_hex_string(),
+        "20a47e88bd47b81c19a8745e79daa457aeb3aafe8a69afb8d3b10e5bec12b6c1e8ad51"
+    );
+}
+
+#[test]
+#[should_panic]
+fn script_builder_push_amount_overflow() {
+    Builder::new().push_amount(crate::Amount::from_sat(i64::MAX as u64 + 1));
+}
+
 #[test]
 fn script_builder_verify() {
     let simple = Builder::new().push_verify().into_script();
@@ -556,6 +710,223 @@ macro_rules! unwrap_all {
     }
 }
 
+#[test]
+fn instructions_lossy() {
+    // Non-minimal PUSHDATA1, OP_CSV, a push of 2 bytes missing its second byte.
+    let script = ScriptBuf::from_hex("4c0169b20269").unwrap();
+    let items: Vec<_> = script.instructions_lossy().collect();
+    assert_eq!(
+        items,
+        vec![
+            (0, LossyInstruction::NonMinimalPush([0x69].as_ref())),
+            (3, LossyInstruction::Instruction(Instruction::Op(OP_CSV))),
+            (4, LossyInstruction::TruncatedPush { opcode: OP_PUSHBYTES_2, data: &[0x69] }),
+        ]
+    );
+
+    // OP_NOP followed by a PUSHDATA2 with a truncated length prefix.
+    let script = ScriptBuf::from_hex("614d01").unwrap();
+    let items: Vec<_> = script.instructions_lossy().collect();
+    assert_eq!(
+        items,
+        vec![
+            (0, LossyInstruction::Instruction(Instruction::Op(OP_NOP))),
+            (1, LossyInstruction::TruncatedPush { opcode: OP_PUSHDATA2, data: &[0x01] }),
+        ]
+    );
+
+    // Well formed scripts yield the same as `instruction_indices`.
+    let script = ScriptBuf::from_hex("0100519c").unwrap();
+    let lossy: Vec<_> = script.instructions_lossy().collect();
+    let strict: Vec<_> = script
+        .instruction_indices()
+        .map(|res| res.map(|(pos, ins)| (pos, LossyInstruction::Instruction(ins))))
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(lossy, strict);
+}
+
+#[test]
+fn script_minimal_pushes() {
+    assert!(ScriptBuf::from_hex("0100515a0169b2").unwrap().has_minimal_pushes());
+    assert!(ScriptBuf::new().has_minimal_pushes());
+    // `OP_PUSHBYTES_1 0x05` instead of `OP_PUSHNUM_5`.
+    assert!(!ScriptBuf::from_hex("0105").unwrap().has_minimal_pushes());
+    // `OP_PUSHDATA1` for a single byte.
+    assert!(!ScriptBuf::from_hex("4c0169").unwrap().has_minimal_pushes());
+    // Truncated push.
+    assert!(!ScriptBuf::from_hex("0269").unwrap().has_minimal_pushes());
+}
+
+#[test]
+fn check_tapscript() {
+    let key = XOnlyPublicKey::from_str(
+        "a6ac32163539c16b6b5dbbca01b725b8e8acaa5f821ba42c80e7940062140d19",
+    )
+    .unwrap();
+    let script = Builder::new()
+        .push_opcode(OP_IF)
+        .push_x_only_key(&key)
+        .push_opcode(OP_CHECKSIG)
+        .push_opcode(OP_ELSE)
+        .push_opcode(OP_RETURN)
+        .push_opcode(OP_ENDIF)
+        .into_script();
+    assert_eq!(script.check_tapscript(), Ok(()));
+    assert!(script.is_valid_tapscript());
+    assert!(ScriptBuf::new().is_valid_tapscript());
+
+    // OP_SUCCESS80 takes the place of OP_RESERVED.
+    let script = Builder::new().push_int(1).push_opcode(OP_RESERVED).into_script();
+    assert_eq!(
+        script.check_tapscript(),
+        Err(TapscriptError::OpSuccess { position: 1, opcode: OP_RESERVED })
+    );
+    let script = Builder::new().push_opcode(OP_VERIF).into_script();
+    assert_eq!(
+        script.check_tapscript(),
+        Err(TapscriptError::IllegalOpcode { position: 0, opcode: OP_VERIF })
+    );
+    let script = Builder::new().push_int(1).push_opcode(OP_CHECKMULTISIG).into_script();
+    assert_eq!(script.check_tapscript(), Err(TapscriptError::CheckMultisig { position: 1 }));
+    let script =
+        Builder::new().push_slice(<&PushBytes>::try_from(&[0; 521][..]).unwrap()).into_script();
+    assert_eq!(
+        script.check_tapscript(),
+        Err(TapscriptError::PushTooLarge { position: 0, len: 521 })
+    );
+    let script = Builder::new().push_int(1).push_opcode(OP_IF).into_script();
+    assert_eq!(script.check_tapscript(), Err(TapscriptError::UnbalancedConditional));
+    let script = Builder::new().push_opcode(OP_ENDIF).into_script();
+    assert_eq!(script.check_tapscript(), Err(TapscriptError::UnbalancedConditional));
+    assert_eq!(
+        ScriptBuf::from_hex("0201").unwrap().check_tapscript(),
+        Err(TapscriptError::Parse(Error::EarlyEndOfScript))
+    );
+}
+
+#[test]
+fn witness_program_from_script() {
+    use witness_program::{Error, WitnessProgram};
+    use witness_version::WitnessVersion;
+
+    let p2wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([42; 20]));
+    let program = p2wpkh.witness_program().unwrap();
+    assert!(program.is_p2wpkh());
+    assert_eq!(program.program().as_bytes(), &[42; 20]);
+    assert_eq!(program.script_pubkey(), p2wpkh);
+
+    let future = WitnessProgram::new(WitnessVersion::V16, &[1; 2]).unwrap();
+    assert_eq!(WitnessProgram::from_script(&future.script_pubkey()), Ok(future));
+
+    let invalid_v0 = ScriptBuf::from_hex("00110101010101010101010101010101010101").unwrap();
+    assert_eq!(WitnessProgram::from_script(&invalid_v0), Err(Error::InvalidSegwitV0Length(17)));
+    assert_eq!(invalid_v0.witness_program(), None);
+    assert_eq!(
+        WitnessProgram::from_script(&ScriptBuf::new_p2sh(&ScriptHash::all_zeros())),
+        Err(Error::NotWitnessProgram)
+    );
+}
+
+#[test]
+fn op_return_multi() {
+    let push = |data: &'static [u8]| <&PushBytes>::try_from(data).unwrap();
+
+    let script =
+        ScriptBuf::new_op_return_multi(&[push(&[0xaa; 2]), push(&[]), push(&[0xbb; 20])]).unwrap();
+    assert!(script.is_op_return());
+    assert_eq!(script.op_return_data(), Some(vec![&[0xaa; 2][..], &[], &[0xbb; 20]]));
+    assert_eq!(ScriptBuf::new_op_return([0xcc; 4]).op_return_data(), Some(vec![&[0xcc; 4][..]]));
+    assert_eq!(
+        ScriptBuf::new_op_return_multi::<&PushBytes>(&[]).unwrap().op_return_data(),
+        Some(vec![])
+    );
+
+    // OP_RETURN, OP_PUSHDATA1 and the length byte leave 80 bytes for data.
+    assert!(ScriptBuf::new_op_return_multi(&[push(&[0; 80])]).is_ok());
+    assert_eq!(
+        ScriptBuf::new_op_return_multi(&[push(&[0; 81])]),
+        Err(OpReturnSizeError { size: 84 })
+    );
+    assert_eq!(ScriptBuf::new_op_return_multi_unchecked(&[push(&[0; 81])]).len(), 84);
+
+    assert_eq!(
+        Builder::new().push_opcode(OP_RETURN).push_int(1).into_script().op_return_data(),
+        None
+    );
+    assert_eq!(ScriptBuf::from_hex("6a02aa").unwrap().op_return_data(), None);
+    assert_eq!(ScriptBuf::new_p2sh(&ScriptHash::all_zeros()).op_return_data(), None);
+}
+
+#[test]
+fn script_from_asm_string() {
+    let scripts = [
+        "76a914162c5ea71c0b23f5b9022ef047c4a86470a5b07088ac",
+        "0047304402202457e78cc1b7f50d0543863c27de75d07982bde8359b9e3316adec0aec165f2f02200203fd331c4e4a4a02f48cf1c291e2c0d6b2f7078a784b5b3649fca41f8794d401",
+        "6363636363686868686800",
+        // Non-minimal pushes keep their encoding.
+        "4c0201024d0100ff4e0000000051b1",
+        "4f5160b1b20114",
+    ];
+    for hex in scripts {
+        let script = ScriptBuf::from_hex(hex).unwrap();
+        assert_eq!(ScriptBuf::from_asm_string(&script.to_asm_string()).unwrap(), script);
+    }
+
+    let p2pkh = ScriptBuf::from_asm_string(
+        "OP_DUP OP_HASH160 162c5ea71c0b23f5b9022ef047c4a86470a5b070 OP_EQUALVERIFY OP_CHECKSIG",
+    )
+    .unwrap();
+    assert!(p2pkh.is_p2pkh());
+    assert_eq!(
+        ScriptBuf::from_asm_string("OP_TRUE OP_NOP2 OP_FALSE").unwrap(),
+        ScriptBuf::from_asm_string("OP_PUSHNUM_1 OP_CLTV OP_0").unwrap()
+    );
+    assert_eq!(ScriptBuf::from_asm_string("  ").unwrap(), ScriptBuf::new());
+
+    // The format used by Bitcoin Core.
+    let p2wpkh = ScriptBuf::from_asm_string("0 751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+    assert_eq!(p2wpkh.to_hex_string(), "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+    assert_eq!(
+        ScriptBuf::from_asm_string(
+            "OP_CHECKLOCKTIMEVERIFY OP_CHECKSEQUENCEVERIFY OP_1NEGATE OP_1 OP_16"
+        )
+        .unwrap(),
+        ScriptBuf::from_asm_string("OP_CLTV OP_CSV OP_PUSHNUM_NEG1 OP_PUSHNUM_1 OP_PUSHNUM_16")
+            .unwrap()
+    );
+    assert_eq!(
+        ScriptBuf::from_asm_string("-1 1 16 17 -1000 4294967295").unwrap().to_hex_string(),
+        "4f5160011102e88305ffffffff00"
+    );
+
+    assert_eq!(
+        ScriptBuf::from_asm_string("OP_FOO"),
+        Err(ParseAsmError::UnknownOpcode("OP_FOO".to_owned()))
+    );
+    assert_eq!(
+        ScriptBuf::from_asm_string("OP_PUSHBYTES_2"),
+        Err(ParseAsmError::MissingPushData(OP_PUSHBYTES_2))
+    );
+    assert_eq!(
+        ScriptBuf::from_asm_string("OP_PUSHBYTES_2 aa"),
+        Err(ParseAsmError::InvalidPushLength { opcode: OP_PUSHBYTES_2, len: 1 })
+    );
+    assert!(matches!(ScriptBuf::from_asm_string("abc"), Err(ParseAsmError::Hex(_))));
+    assert_eq!(
+        ScriptBuf::from_asm_string("4294967296"),
+        Err(ParseAsmError::InvalidNumber("4294967296".to_owned()))
+    );
+    assert_eq!(
+        ScriptBuf::from_asm_string("OP_17"),
+        Err(ParseAsmError::UnknownOpcode("OP_17".to_owned()))
+    );
+    assert_eq!(
+        ScriptBuf::from_asm_string("OP_01"),
+        Err(ParseAsmError::UnknownOpcode("OP_01".to_owned()))
+    );
+}
+
 #[test]
 fn test_iterator() {
     let zero = ScriptBuf::from_hex("00").unwrap();
@@ -670,6 +1041,26 @@ fn defult_dust_value_tests() {
     );
 }
 
+#[test]
+fn max_satisfaction_weight() {
+    let p2pkh = ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array([42; 20]));
+    assert_eq!(p2pkh.max_satisfaction_weight(), Some(Weight::from_wu(436)));
+
+    let p2wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([42; 20]));
+    assert_eq!(p2wpkh.max_satisfaction_weight(), Some(Weight::from_wu(113)));
+
+    let key = XOnlyPublicKey::from_str(
+        "a6ac32163539c16b6b5dbbca01b725b8e8acaa5f821ba42c80e7940062140d19",
+    )
+    .unwrap();
+    let p2tr = ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(key));
+    assert_eq!(p2tr.max_satisfaction_weight(), Some(Weight::from_wu(71)));
+
+    let p2sh = ScriptBuf::new_p2sh(&ScriptHash::from_byte_array([42; 20]));
+    assert_eq!(p2sh.max_satisfaction_weight(), None);
+    assert_eq!(ScriptBuf::new_op_return([]).max_satisfaction_weight(), None);
+}
+
 #[test]
 fn test_script_get_sigop_count() {
     assert_eq!(
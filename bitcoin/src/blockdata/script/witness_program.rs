@@ -14,8 +14,10 @@ use internals::array_vec::ArrayVec;
 use secp256k1::{Secp256k1, Verification};
 
 use crate::blockdata::script::witness_version::WitnessVersion;
-use crate::blockdata::script::{PushBytes, Script};
-use crate::crypto::key::{CompressedPublicKey, TapTweak, TweakedPublicKey, UntweakedPublicKey};
+use crate::blockdata::script::{PushBytes, Script, ScriptBuf};
+use crate::crypto::key::{
+    CompressedPublicKey, TapTweak, TweakedPublicKey, UntweakedPublicKey, WPubkeyHash,
+};
 use crate::taproot::TapNodeHash;
 
 /// The minimum byte size of a segregated witness program.
@@ -73,7 +75,11 @@ impl WitnessProgram {
 
     /// Creates a [`WitnessProgram`] from `pk` for a P2WPKH output.
     pub fn p2wpkh(pk: &CompressedPublicKey) -> Self {
-        let hash = pk.wpubkey_hash();
+        WitnessProgram::p2wpkh_from_hash(pk.wpubkey_hash())
+    }
+
+    /// Creates a [`WitnessProgram`] from the public key hash `hash` for a P2WPKH output.
+    pub fn p2wpkh_from_hash(hash: WPubkeyHash) -> Self {
         WitnessProgram::new_p2wpkh(hash.to_byte_array())
     }
 
@@ -100,6 +106,21 @@ impl WitnessProgram {
         WitnessProgram::new_p2tr(pubkey)
     }
 
+    /// Extracts the witness program from a script pubkey.
+    ///
+    /// Unlike [`Script::is_witness_program`] this also enforces the program length rules of the
+    /// witness version.
+    pub fn from_script(script: &Script) -> Result<Self, Error> {
+        if !script.is_witness_program() {
+            return Err(Error::NotWitnessProgram);
+        }
+        let version = script.witness_version().expect("is_witness_program checks the version");
+        WitnessProgram::new(version, &script.as_bytes()[2..])
+    }
+
+    /// Generates the script pubkey paying to this witness program.
+    pub fn script_pubkey(&self) -> ScriptBuf { ScriptBuf::new_witness_program(self) }
+
     /// Returns the witness program version.
     pub fn version(&self) -> WitnessVersion { self.version }
 
@@ -133,6 +154,8 @@ pub enum Error {
     InvalidLength(usize),
     /// A v0 witness program must be either of length 20 or 32.
     InvalidSegwitV0Length(usize),
+    /// The script is not a version push followed by a single program push.
+    NotWitnessProgram,
 }
 
 internals::impl_from_infallible!(Error);
@@ -146,6 +169,7 @@ impl fmt::Display for Error {
                 write!(f, "witness program must be between 2 and 40 bytes: length={}", len),
             InvalidSegwitV0Length(len) =>
                 write!(f, "a v0 witness program must be either 20 or 32 bytes: length={}", len),
+            NotWitnessProgram => f.write_str("script is not a witness program"),
         }
     }
 }
@@ -156,7 +180,7 @@ impl std::error::Error for Error {
         use Error::*;
 
         match *self {
-            InvalidLength(_) | InvalidSegwitV0Length(_) => None,
+            InvalidLength(_) | InvalidSegwitV0Length(_) | NotWitnessProgram => None,
         }
     }
 }
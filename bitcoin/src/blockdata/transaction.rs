@@ -18,7 +18,8 @@ use internals::write_err;
 use io::{BufRead, Write};
 
 use super::Weight;
-use crate::blockdata::locktime::absolute::{self, Height, Time};
+use crate::blockdata::constants::COINBASE_MATURITY;
+use crate::blockdata::locktime::absolute::{self, Height, Mtp, Time};
 use crate::blockdata::locktime::relative::{self, TimeOverflowError};
 use crate::blockdata::script::{Script, ScriptBuf};
 use crate::blockdata::witness::Witness;
@@ -86,6 +87,12 @@ impl OutPoint {
     #[inline]
     pub fn null() -> OutPoint { OutPoint { txid: Hash::all_zeros(), vout: u32::MAX } }
 
+    /// Creates the `OutPoint` spent by the input of a coinbase transaction.
+    ///
+    /// This is the same as [`OutPoint::null`].
+    #[inline]
+    pub fn new_coinbase() -> OutPoint { OutPoint::null() }
+
     /// Checks if an `OutPoint` is "null".
     ///
     /// # Examples
@@ -386,6 +393,13 @@ impl Sequence {
     #[inline]
     pub fn is_rbf(&self) -> bool { *self < Sequence::MIN_NO_RBF }
 
+    /// Returns `true` if the sequence number both enables absolute lock-time
+    /// ([`Transaction::lock_time`]) and opts-in to BIP125 replace-by-fee.
+    #[inline]
+    pub fn enables_locktime_and_rbf(&self) -> bool {
+        self.enables_absolute_lock_time() && self.is_rbf()
+    }
+
     /// Returns `true` if the sequence has a relative lock-time.
     #[inline]
     pub fn is_relative_lock_time(&self) -> bool {
@@ -440,6 +454,54 @@ impl Sequence {
         Sequence(u32::from(intervals) | Sequence::LOCK_TYPE_MASK)
     }
 
+    /// Creates a sequence number that opts-in to replace-by-fee, enables absolute lock-time and
+    /// encodes a block based relative lock-time of `height` blocks.
+    ///
+    /// Any sequence number encoding a relative lock-time also signals replace-by-fee, this
+    /// constructor exists to make that explicit at the call site.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bitcoin::Sequence;
+    /// let sequence = Sequence::rbf_with_height(144);
+    /// assert!(sequence.enables_locktime_and_rbf());
+    /// assert!(sequence.is_height_locked());
+    /// ```
+    #[inline]
+    pub fn rbf_with_height(height: u16) -> Self { Sequence::from_height(height) }
+
+    /// Creates a sequence number that opts-in to replace-by-fee, enables absolute lock-time and
+    /// encodes a time based relative lock-time of `intervals` 512 second intervals.
+    ///
+    /// Any sequence number encoding a relative lock-time also signals replace-by-fee, this
+    /// constructor exists to make that explicit at the call site.
+    #[inline]
+    pub fn rbf_with_512_second_intervals(intervals: u16) -> Self {
+        Sequence::from_512_second_intervals(intervals)
+    }
+
+    /// Returns a copy of this sequence number with the relative lock-time set to `lock`.
+    ///
+    /// The BIP-68 disable flag is cleared and the type flag and low 16 bits are replaced, all other
+    /// bits (which have no consensus meaning) are preserved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bitcoin::Sequence;
+    /// # use bitcoin::locktime::relative::{Height, LockTime};
+    /// let lock = LockTime::from(Height::from(144));
+    /// let sequence = Sequence::ENABLE_RBF_NO_LOCKTIME.with_relative_lock_time(lock);
+    /// assert_eq!(sequence.to_relative_lock_time(), Some(lock));
+    /// assert!(sequence.is_rbf());
+    /// ```
+    #[inline]
+    pub fn with_relative_lock_time(self, lock: relative::LockTime) -> Self {
+        let mask = Sequence::LOCK_TIME_DISABLE_FLAG_MASK | Sequence::LOCK_TYPE_MASK | 0xFFFF;
+        Sequence((self.0 & !mask) | lock.to_consensus_u32())
+    }
+
     /// Creates a relative lock-time from seconds, converting the seconds into 512 second
     /// interval with floor division.
     ///
@@ -563,7 +625,7 @@ impl TxOut {
     /// # Panics
     ///
     /// If output size * 4 overflows, this should never happen under normal conditions. Use
-    /// `Weght::from_vb_checked(self.size() as u64)` if you are concerned.
+    /// `Weight::from_vb(self.size() as u64)` if you are concerned.
     pub fn weight(&self) -> Weight {
         // Size is equivalent to virtual size since all bytes of a TxOut are non-witness bytes.
         Weight::from_vb(self.size() as u64).expect("should never happen under normal conditions")
@@ -601,6 +663,44 @@ impl TxOut {
     pub fn minimal_non_dust_custom(script_pubkey: ScriptBuf, dust_relay_fee: FeeRate) -> Self {
         TxOut { value: script_pubkey.minimal_non_dust_custom(dust_relay_fee), script_pubkey }
     }
+
+    /// Returns true if this output is dust per current Core policy (`IsDust`).
+    ///
+    /// An output is dust if its value is lower than the fee it would cost to spend it at
+    /// `dust_relay_fee`, the segwit discount is applied for witness program outputs. Use
+    /// [`FeeRate::DUST`] for the default value used by Bitcoin Core.
+    ///
+    /// See [`Script::minimal_non_dust_custom`] for the dust threshold of a given script.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bitcoin::{Amount, FeeRate, ScriptBuf, TxOut};
+    /// let script_pubkey = ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6")?;
+    /// let txout = TxOut { value: Amount::from_sat(293), script_pubkey };
+    /// assert!(txout.is_dust(FeeRate::DUST));
+    /// # Ok::<_, bitcoin::hex::HexToBytesError>(())
+    /// ```
+    pub fn is_dust(&self, dust_relay_fee: FeeRate) -> bool {
+        self.value < self.script_pubkey.minimal_non_dust_custom(dust_relay_fee)
+    }
+
+    /// Computes the value of this output accounting for the cost of spending it.
+    ///
+    /// This is equivalent to calling [`effective_value`] with the value of this output, returns
+    /// `None` if overflow occurred.
+    ///
+    /// # Arguments
+    ///
+    /// * `fee_rate` - the fee rate of the transaction being created.
+    /// * `satisfaction_weight` - satisfied spending conditions weight.
+    pub fn effective_value(
+        &self,
+        fee_rate: FeeRate,
+        satisfaction_weight: Weight,
+    ) -> Option<SignedAmount> {
+        effective_value(fee_rate, satisfaction_weight, self.value)
+    }
 }
 
 /// Returns the total number of bytes that this script pubkey would contribute to a transaction.
@@ -878,6 +978,20 @@ impl Transaction {
         self.input.len() == 1 && self.input[0].previous_output.is_null()
     }
 
+    /// Returns `true` if the outputs of this [`Transaction`] may be spent in the block following
+    /// `tip_height`, given that it was confirmed in the block at `confirm_height`.
+    ///
+    /// Outputs of a coinbase transaction can only be spent once the coinbase has at least
+    /// [`COINBASE_MATURITY`] confirmations, outputs of other transactions are always mature.
+    pub fn is_coinbase_mature(&self, confirm_height: Height, tip_height: Height) -> bool {
+        if !self.is_coinbase() {
+            return true;
+        }
+        let spend_height = u64::from(tip_height.to_consensus_u32()) + 1;
+        let confirm_height = u64::from(confirm_height.to_consensus_u32());
+        spend_height.saturating_sub(confirm_height) >= u64::from(COINBASE_MATURITY)
+    }
+
     /// Returns `true` if the transaction itself opted in to be BIP-125-replaceable (RBF).
     ///
     /// # Warning
@@ -905,6 +1019,132 @@ impl Transaction {
         self.lock_time.is_satisfied_by(height, time)
     }
 
+    /// Returns true if this [`Transaction`] is final i.e., it may be included in a block at
+    /// `height` whose median time past is `mtp`.
+    ///
+    /// This implements the `IsFinalTx` logic of Bitcoin Core: a transaction is final if its lock
+    /// time is zero, if its lock time is strictly less than `height` (for a lock-by-blockheight) or
+    /// `mtp` (for a lock-by-blocktime), or if every input has a sequence number of
+    /// [`Sequence::MAX`] (thereby disabling the lock time).
+    ///
+    /// Note that `height` is the height of the block the transaction is to be included in, when
+    /// checking against the current chain tip this is the tip height plus one. Similarly, `mtp` is
+    /// the median time past of the current chain tip ([BIP-113]).
+    ///
+    /// [BIP-113]: <https://github.com/bitcoin/bips/blob/master/bip-0113.mediawiki>
+    pub fn is_final(&self, height: Height, mtp: Mtp) -> bool {
+        use absolute::LockTime::*;
+
+        if self.lock_time == absolute::LockTime::ZERO {
+            return true;
+        }
+        let lock_time_passed = match self.lock_time {
+            Blocks(n) => n < height,
+            Seconds(n) => n.to_consensus_u32() < mtp.to_consensus_u32(),
+        };
+        lock_time_passed || !self.is_lock_time_enabled()
+    }
+
+    /// Calculates the earliest chain position at which the BIP-68 relative lock times of all inputs
+    /// of this [`Transaction`] are satisfied.
+    ///
+    /// This mirrors `CalculateSequenceLocks` in Bitcoin Core. `prevout_heights` and `prevout_mtps`
+    /// must contain, for each input in order, the height of the block in which the output being
+    /// spent was confirmed and the median time past of the block preceding it.
+    ///
+    /// Relative lock times are only enforced for transactions with version 2 or greater, compared
+    /// as an unsigned integer. For other transactions [`relative::LockTimeBound::NONE`] is
+    /// returned. Inputs with the disable flag set in their sequence number do not constrain the
+    /// bound.
+    ///
+    /// # Panics
+    ///
+    /// If the length of `prevout_heights` or `prevout_mtps` differs from the number of inputs.
+    ///
+    /// [BIP-68]: <https://github.com/bitcoin/bips/blob/master/bip-0068.mediawiki>
+    pub fn calculate_sequence_locks(
+        &self,
+        prevout_heights: &[Height],
+        prevout_mtps: &[Mtp],
+    ) -> relative::LockTimeBound {
+        assert_eq!(prevout_heights.len(), self.input.len(), "one prevout height per input");
+        assert_eq!(prevout_mtps.len(), self.input.len(), "one prevout median time past per input");
+
+        let mut bound = relative::LockTimeBound::NONE;
+        if (self.version.0 as u32) < 2 {
+            return bound;
+        }
+        let prevouts = prevout_heights.iter().zip(prevout_mtps);
+        for (txin, (&height, &mtp)) in self.input.iter().zip(prevouts) {
+            if let Some(lock) = txin.sequence.to_relative_lock_time() {
+                let confirmed_in = relative::ChainPosition::new(height, mtp);
+                bound = bound.combine(relative::LockTimeBound::from_lock(lock, confirmed_in));
+            }
+        }
+        bound
+    }
+
+    /// Computes the fee paid by this transaction i.e., the sum of the values of the outputs spent
+    /// by the inputs minus the sum of the values of the outputs.
+    ///
+    /// `prevouts` must yield the output spent by each input, in input order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the number of prevouts differs from the number of inputs, if summing
+    /// the values overflows, or if the outputs spend more than the inputs provide.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bitcoin::{absolute, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut};
+    /// # use bitcoin::transaction::Version;
+    /// # let txout = |sat| TxOut { value: Amount::from_sat(sat), script_pubkey: ScriptBuf::new() };
+    /// # let tx = Transaction {
+    /// #     version: Version::TWO,
+    /// #     lock_time: absolute::LockTime::ZERO,
+    /// #     input: vec![TxIn::default()],
+    /// #     output: vec![txout(9_000)],
+    /// # };
+    /// let prevouts = vec![txout(10_000)];
+    /// assert_eq!(tx.fee(&prevouts), Ok(Amount::from_sat(1_000)));
+    /// ```
+    pub fn fee<'a, I>(&self, prevouts: I) -> Result<Amount, FeeError>
+    where
+        I: IntoIterator<Item = &'a TxOut>,
+    {
+        let mut prevouts = prevouts.into_iter();
+        let mut input_value = Amount::ZERO;
+        for index in 0..self.input.len() {
+            let prevout = prevouts.next().ok_or(FeeError::MissingPrevout(index))?;
+            input_value = input_value.checked_add(prevout.value).ok_or(FeeError::ValueOverflow)?;
+        }
+        if prevouts.next().is_some() {
+            return Err(FeeError::TooManyPrevouts);
+        }
+
+        let output_value = self
+            .output
+            .iter()
+            .try_fold(Amount::ZERO, |sum, txout| sum.checked_add(txout.value))
+            .ok_or(FeeError::ValueOverflow)?;
+
+        input_value
+            .checked_sub(output_value)
+            .ok_or(FeeError::NegativeFee { input_value, output_value })
+    }
+
+    /// Sorts the inputs and outputs of this transaction according to [BIP-69].
+    ///
+    /// Sorting is stable and uses [`bip69_input_cmp`] and [`bip69_output_cmp`]. Note that sorting
+    /// invalidates any existing signatures, this should be done before the transaction is signed.
+    ///
+    /// [BIP-69]: <https://github.com/bitcoin/bips/blob/master/bip-0069.mediawiki>
+    pub fn sort_bip69(&mut self) {
+        self.input.sort_by(bip69_input_cmp);
+        self.output.sort_by(bip69_output_cmp);
+    }
+
     /// Returns `true` if this transactions nLockTime is enabled ([BIP-65]).
     ///
     /// [BIP-65]: https://github.com/bitcoin/bips/blob/master/bip-0065.mediawiki
@@ -928,16 +1168,44 @@ impl Transaction {
     /// > ref: <https://bitcoin.stackexchange.com/questions/117356/what-is-sigop-signature-operation#117359>
     ///
     /// The `spent` parameter is a closure/function that looks up the output being spent by each input
-    /// It takes in an [`OutPoint`] and returns a [`TxOut`]. If you can't provide this, a placeholder of
-    /// `|_| None` can be used. Without access to the previous [`TxOut`], any sigops in a redeemScript (P2SH)
-    /// as well as any segwit sigops will not be counted for that input.
-    pub fn total_sigop_cost<S>(&self, mut spent: S) -> usize
+    /// It takes in an [`OutPoint`] and returns a [`TxOut`], either owned or borrowed. If you can't
+    /// provide this, a placeholder of `|_| None::<TxOut>` can be used. Without access to the
+    /// previous [`TxOut`], any sigops in a redeemScript (P2SH) as well as any segwit sigops will not
+    /// be counted for that input.
+    ///
+    /// Legacy and P2SH sigops are scaled by [`WITNESS_SCALE_FACTOR`], witness sigops are not. Only
+    /// the legacy sigops are counted for a coinbase transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use bitcoin::{OutPoint, Transaction, TxOut};
+    /// # use bitcoin::blockdata::locktime::absolute;
+    /// # use bitcoin::transaction::Version;
+    /// # let tx = Transaction {
+    /// #     version: Version::TWO,
+    /// #     lock_time: absolute::LockTime::ZERO,
+    /// #     input: vec![],
+    /// #     output: vec![],
+    /// # };
+    /// # let prevouts: Vec<(OutPoint, TxOut)> = vec![];
+    /// let cost = tx.total_sigop_cost(|outpoint: &OutPoint| {
+    ///     prevouts.iter().find(|(op, _)| op == outpoint).map(|(_, txout)| txout)
+    /// });
+    /// # assert_eq!(cost, 0);
+    /// ```
+    ///
+    /// [`WITNESS_SCALE_FACTOR`]: crate::constants::WITNESS_SCALE_FACTOR
+    pub fn total_sigop_cost<S, T>(&self, mut spent: S) -> usize
     where
-        S: FnMut(&OutPoint) -> Option<TxOut>,
+        S: FnMut(&OutPoint) -> Option<T>,
+        T: Borrow<TxOut>,
     {
         let mut cost = self.count_p2pk_p2pkh_sigops().saturating_mul(4);
+        if self.is_coinbase() {
+            return cost;
+        }
 
-        // coinbase tx is correctly handled because `spent` will always returns None.
         cost = cost.saturating_add(self.count_p2sh_sigops(&mut spent).saturating_mul(4));
         cost.saturating_add(self.count_witness_sigops(&mut spent))
     }
@@ -960,13 +1228,15 @@ impl Transaction {
     }
 
     /// Does not include wrapped segwit (see `count_witness_sigops`).
-    fn count_p2sh_sigops<S>(&self, spent: &mut S) -> usize
+    fn count_p2sh_sigops<S, T>(&self, spent: &mut S) -> usize
     where
-        S: FnMut(&OutPoint) -> Option<TxOut>,
+        S: FnMut(&OutPoint) -> Option<T>,
+        T: Borrow<TxOut>,
     {
         fn count_sigops(prevout: &TxOut, input: &TxIn) -> usize {
             let mut count: usize = 0;
-            if prevout.script_pubkey.is_p2sh() {
+            // Like Bitcoin Core, a scriptSig which is not push only has no redeemScript.
+            if prevout.script_pubkey.is_p2sh() && input.script_sig.is_push_only() {
                 if let Some(redeem) = input.script_sig.last_pushdata() {
                     count =
                         count.saturating_add(Script::from_bytes(redeem.as_bytes()).count_sigops());
@@ -978,16 +1248,17 @@ impl Transaction {
         let mut count: usize = 0;
         for input in &self.input {
             if let Some(prevout) = spent(&input.previous_output) {
-                count = count.saturating_add(count_sigops(&prevout, input));
+                count = count.saturating_add(count_sigops(prevout.borrow(), input));
             }
         }
         count
     }
 
     /// Includes wrapped segwit (returns 0 for taproot spends).
-    fn count_witness_sigops<S>(&self, spent: &mut S) -> usize
+    fn count_witness_sigops<S, T>(&self, spent: &mut S) -> usize
     where
-        S: FnMut(&OutPoint) -> Option<TxOut>,
+        S: FnMut(&OutPoint) -> Option<T>,
+        T: Borrow<TxOut>,
     {
         fn count_sigops_with_witness_program(witness: &Witness, witness_program: &Script) -> usize {
             if witness_program.is_p2wpkh() {
@@ -1004,7 +1275,7 @@ impl Transaction {
             }
         }
 
-        fn count_sigops(prevout: TxOut, input: &TxIn) -> usize {
+        fn count_sigops(prevout: &TxOut, input: &TxIn) -> usize {
             let script_sig = &input.script_sig;
             let witness = &input.witness;
 
@@ -1029,7 +1300,7 @@ impl Transaction {
         let mut count: usize = 0;
         for input in &self.input {
             if let Some(prevout) = spent(&input.previous_output) {
-                count = count.saturating_add(count_sigops(prevout, input));
+                count = count.saturating_add(count_sigops(prevout.borrow(), input));
             }
         }
         count
@@ -1121,6 +1392,257 @@ impl std::error::Error for IndexOutOfBoundsError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
 }
 
+/// Builds an unsigned [`Transaction`].
+///
+/// Inputs are created with an empty `script_sig` and witness, ready to be signed. Prevout data can
+/// optionally be supplied for each input, if it is supplied for all inputs the builder checks that
+/// the transaction does not spend more than it receives.
+///
+/// # Examples
+///
+/// ```
+/// # use bitcoin::{absolute, Amount, OutPoint, ScriptBuf, Sequence, TxOut};
+/// # use bitcoin::transaction::{TransactionBuilder, Version};
+/// # let script_pubkey = ScriptBuf::new_op_return([]);
+/// # let prevout = TxOut { value: Amount::from_sat(10_000), script_pubkey: script_pubkey.clone() };
+/// let tx = TransactionBuilder::new()
+///     .version(Version::TWO)
+///     .lock_time(absolute::LockTime::ZERO)
+///     .add_input_with_prevout(OutPoint::null(), Sequence::ENABLE_RBF_NO_LOCKTIME, prevout)
+///     .add_output(TxOut { value: Amount::from_sat(9_000), script_pubkey })
+///     .build()
+///     .expect("valid transaction");
+/// assert_eq!(tx.input.len(), 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionBuilder {
+    version: Version,
+    lock_time: absolute::LockTime,
+    input: Vec<TxIn>,
+    prevouts: Vec<Option<TxOut>>,
+    output: Vec<TxOut>,
+}
+
+impl TransactionBuilder {
+    /// Creates a new builder for a version 2 transaction with a zero lock time.
+    pub fn new() -> Self {
+        TransactionBuilder {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: Vec::new(),
+            prevouts: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    /// Sets the transaction version.
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the transaction lock time.
+    pub fn lock_time(mut self, lock_time: absolute::LockTime) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// Adds an input spending `previous_output`, without any information about the output spent.
+    pub fn add_input(self, previous_output: OutPoint, sequence: Sequence) -> Self {
+        self.push_input(previous_output, sequence, None)
+    }
+
+    /// Adds an input spending `previous_output`, which is the output `prevout`.
+    pub fn add_input_with_prevout(
+        self,
+        previous_output: OutPoint,
+        sequence: Sequence,
+        prevout: TxOut,
+    ) -> Self {
+        self.push_input(previous_output, sequence, Some(prevout))
+    }
+
+    fn push_input(
+        mut self,
+        previous_output: OutPoint,
+        sequence: Sequence,
+        prevout: Option<TxOut>,
+    ) -> Self {
+        self.input.push(TxIn {
+            previous_output,
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::default(),
+        });
+        self.prevouts.push(prevout);
+        self
+    }
+
+    /// Adds an output.
+    pub fn add_output(mut self, output: TxOut) -> Self {
+        self.output.push(output);
+        self
+    }
+
+    /// Validates the accumulated data and builds the unsigned transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the transaction has no inputs or outputs, spends the same output twice,
+    /// has a (non-`OP_RETURN`) dust output, exceeds [`Transaction::MAX_STANDARD_WEIGHT`] before
+    /// signing, sets a lock time that is disabled by the sequence numbers of all inputs, or spends
+    /// more than the value of the prevouts (if prevouts are known for all inputs).
+    pub fn build(self) -> Result<Transaction, BuildError> {
+        use BuildError::*;
+
+        if self.input.is_empty() {
+            return Err(NoInputs);
+        }
+        if self.output.is_empty() {
+            return Err(NoOutputs);
+        }
+
+        let mut spent = BTreeSet::new();
+        for txin in &self.input {
+            if !spent.insert(txin.previous_output) {
+                return Err(DuplicateInput(txin.previous_output));
+            }
+        }
+
+        for (index, txout) in self.output.iter().enumerate() {
+            if txout.is_dust(FeeRate::DUST) {
+                return Err(DustOutput(index));
+            }
+        }
+
+        let tx = Transaction {
+            version: self.version,
+            lock_time: self.lock_time,
+            input: self.input,
+            output: self.output,
+        };
+
+        if tx.lock_time != absolute::LockTime::ZERO && !tx.is_lock_time_enabled() {
+            return Err(LockTimeDisabled);
+        }
+
+        let weight = tx.weight();
+        if weight > Transaction::MAX_STANDARD_WEIGHT {
+            return Err(ExcessiveWeight(weight));
+        }
+
+        if self.prevouts.iter().all(Option::is_some) {
+            tx.fee(self.prevouts.iter().flatten()).map_err(Fee)?;
+        }
+
+        Ok(tx)
+    }
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self { TransactionBuilder::new() }
+}
+
+/// Error building a transaction with [`TransactionBuilder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The transaction has no inputs.
+    NoInputs,
+    /// The transaction has no outputs.
+    NoOutputs,
+    /// The same outpoint is spent by more than one input.
+    DuplicateInput(OutPoint),
+    /// The output at this index is below the dust limit.
+    DustOutput(usize),
+    /// The lock time is non-zero but all inputs disable it with [`Sequence::MAX`].
+    LockTimeDisabled,
+    /// The unsigned transaction already exceeds [`Transaction::MAX_STANDARD_WEIGHT`].
+    ExcessiveWeight(Weight),
+    /// Prevouts are known for all inputs but the fee could not be computed.
+    Fee(FeeError),
+}
+
+internals::impl_from_infallible!(BuildError);
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BuildError::*;
+
+        match *self {
+            NoInputs => f.write_str("transaction has no inputs"),
+            NoOutputs => f.write_str("transaction has no outputs"),
+            DuplicateInput(ref outpoint) =>
+                write!(f, "outpoint {} is spent more than once", outpoint),
+            DustOutput(index) => write!(f, "output {} is below the dust limit", index),
+            LockTimeDisabled =>
+                f.write_str("lock time is set but all input sequence numbers disable it"),
+            ExcessiveWeight(weight) =>
+                write!(f, "unsigned transaction weight {} exceeds the standard limit", weight),
+            Fee(ref e) => write_err!(f, "invalid fee"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use BuildError::*;
+
+        match *self {
+            NoInputs | NoOutputs | DuplicateInput(_) | DustOutput(_) | LockTimeDisabled
+            | ExcessiveWeight(_) => None,
+            Fee(ref e) => Some(e),
+        }
+    }
+}
+
+/// Error computing the fee of a transaction with [`Transaction::fee`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FeeError {
+    /// No prevout was supplied for the input at this index.
+    MissingPrevout(usize),
+    /// More prevouts were supplied than the transaction has inputs.
+    TooManyPrevouts,
+    /// The sum of input or output values overflowed.
+    ValueOverflow,
+    /// The outputs spend more than the inputs provide.
+    NegativeFee {
+        /// Sum of the values of the prevouts.
+        input_value: Amount,
+        /// Sum of the values of the outputs.
+        output_value: Amount,
+    },
+}
+
+internals::impl_from_infallible!(FeeError);
+
+impl fmt::Display for FeeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use FeeError::*;
+
+        match *self {
+            MissingPrevout(index) => write!(f, "missing prevout for input {}", index),
+            TooManyPrevouts => f.write_str("more prevouts supplied than transaction inputs"),
+            ValueOverflow => f.write_str("sum of values overflowed"),
+            NegativeFee { input_value, output_value } =>
+                write!(f, "outputs ({}) spend more than the inputs ({})", output_value, input_value),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FeeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use FeeError::*;
+
+        match *self {
+            MissingPrevout(_) | TooManyPrevouts | ValueOverflow | NegativeFee { .. } => None,
+        }
+    }
+}
+
 /// The transaction version.
 ///
 /// Currently, as specified by [BIP-68], only version 1 and 2 are considered standard.
@@ -1300,6 +1822,111 @@ impl From<&Transaction> for Wtxid {
     fn from(tx: &Transaction) -> Wtxid { tx.compute_wtxid() }
 }
 
+/// A [`Transaction`] together with its [`Txid`] and [`Wtxid`].
+///
+/// Both identifiers are computed once, when the `CachedTransaction` is created, instead of hashing
+/// the whole transaction every time they are needed. The transaction can not be mutated through
+/// this type so the cached values are always correct, use [`into_transaction`] to modify it.
+///
+/// [`into_transaction`]: CachedTransaction::into_transaction
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct CachedTransaction {
+    tx: Transaction,
+    txid: Txid,
+    wtxid: Wtxid,
+}
+
+impl CachedTransaction {
+    /// Creates a new `CachedTransaction`, computing the transaction identifiers.
+    pub fn new(tx: Transaction) -> Self {
+        let txid = tx.compute_txid();
+        // The wtxid only differs from the txid if there is witness data to hash.
+        let wtxid = if tx.uses_segwit_serialization() {
+            tx.compute_wtxid()
+        } else {
+            Wtxid::from_raw_hash(txid.to_raw_hash())
+        };
+        CachedTransaction { tx, txid, wtxid }
+    }
+
+    /// Returns the cached [`Txid`].
+    pub fn txid(&self) -> Txid { self.txid }
+
+    /// Returns the cached [`Wtxid`].
+    pub fn wtxid(&self) -> Wtxid { self.wtxid }
+
+    /// Returns a reference to the transaction.
+    pub fn as_transaction(&self) -> &Transaction { &self.tx }
+
+    /// Returns the transaction, discarding the cached identifiers.
+    pub fn into_transaction(self) -> Transaction { self.tx }
+}
+
+impl core::ops::Deref for CachedTransaction {
+    type Target = Transaction;
+
+    fn deref(&self) -> &Self::Target { &self.tx }
+}
+
+impl AsRef<Transaction> for CachedTransaction {
+    fn as_ref(&self) -> &Transaction { &self.tx }
+}
+
+impl From<Transaction> for CachedTransaction {
+    fn from(tx: Transaction) -> Self { Self::new(tx) }
+}
+
+impl From<CachedTransaction> for Transaction {
+    fn from(tx: CachedTransaction) -> Self { tx.tx }
+}
+
+impl From<&CachedTransaction> for Txid {
+    fn from(tx: &CachedTransaction) -> Txid { tx.txid }
+}
+
+impl From<&CachedTransaction> for Wtxid {
+    fn from(tx: &CachedTransaction) -> Wtxid { tx.wtxid }
+}
+
+impl Encodable for CachedTransaction {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        self.tx.consensus_encode(w)
+    }
+}
+
+impl Decodable for CachedTransaction {
+    fn consensus_decode_from_finite_reader<R: BufRead + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        Transaction::consensus_decode_from_finite_reader(r).map(Self::new)
+    }
+}
+
+/// Compares two inputs according to [BIP-69] lexicographical ordering.
+///
+/// Inputs are ordered by the previous transaction hash, compared in reversed byte order (i.e. as
+/// the hash is usually displayed in hex), and then by the previous output index.
+///
+/// [BIP-69]: <https://github.com/bitcoin/bips/blob/master/bip-0069.mediawiki>
+pub fn bip69_input_cmp(a: &TxIn, b: &TxIn) -> cmp::Ordering {
+    let a_txid = a.previous_output.txid.to_byte_array();
+    let b_txid = b.previous_output.txid.to_byte_array();
+    a_txid
+        .iter()
+        .rev()
+        .cmp(b_txid.iter().rev())
+        .then(a.previous_output.vout.cmp(&b.previous_output.vout))
+}
+
+/// Compares two outputs according to [BIP-69] lexicographical ordering.
+///
+/// Outputs are ordered by value and then by the bytes of the `script_pubkey`.
+///
+/// [BIP-69]: <https://github.com/bitcoin/bips/blob/master/bip-0069.mediawiki>
+pub fn bip69_output_cmp(a: &TxOut, b: &TxOut) -> cmp::Ordering {
+    a.value.cmp(&b.value).then_with(|| a.script_pubkey.as_bytes().cmp(b.script_pubkey.as_bytes()))
+}
+
 /// Computes the value of an output accounting for the cost of spending it.
 ///
 /// The effective value is the value of an output value minus the amount to spend it.  That is, the
@@ -1323,6 +1950,42 @@ pub fn effective_value(
     value.to_signed().ok()?.checked_sub(signed_input_fee)
 }
 
+/// Computes the waste metric of a coin selection, as used by Bitcoin Core to compare selections.
+///
+/// The waste is the cost of spending the selected inputs now rather than at the long term fee
+/// rate, plus either the cost of creating and later spending the change output or, for a
+/// selection without change, the excess value that is dropped to fees. A lower waste is better.
+///
+/// Returns `None` if overflow occurred.
+///
+/// # Arguments
+///
+/// * `satisfaction_weights` - satisfied spending conditions weight of each selected input.
+/// * `fee_rate` - the fee rate of the transaction being created.
+/// * `long_term_fee_rate` - the fee rate at which the inputs are expected to be spendable later.
+/// * `change_cost` - the cost of the change output, `None` if the selection has no change.
+/// * `excess` - the effective value of the selection in excess of the target, only counted if
+///   the selection has no change.
+pub fn waste<I>(
+    satisfaction_weights: I,
+    fee_rate: FeeRate,
+    long_term_fee_rate: FeeRate,
+    change_cost: Option<Amount>,
+    excess: Amount,
+) -> Option<SignedAmount>
+where
+    I: IntoIterator<Item = Weight>,
+{
+    let mut waste = SignedAmount::ZERO;
+    for satisfaction_weight in satisfaction_weights {
+        let weight = satisfaction_weight.checked_add(TxIn::BASE_WEIGHT)?;
+        let fee = fee_rate.checked_mul_by_weight(weight)?.to_signed().ok()?;
+        let long_term_fee = long_term_fee_rate.checked_mul_by_weight(weight)?.to_signed().ok()?;
+        waste = waste.checked_add(fee.checked_sub(long_term_fee)?)?;
+    }
+    waste.checked_add(change_cost.unwrap_or(excess).to_signed().ok()?)
+}
+
 /// Predicts the weight of a to-be-constructed transaction.
 ///
 /// This function computes the weight of a transaction which is not fully known. All that is needed
@@ -1504,6 +2167,19 @@ impl InputWeightPrediction {
     /// [signature grinding]: https://bitcoin.stackexchange.com/questions/111660/what-is-signature-grinding
     pub const P2WPKH_MAX: Self = InputWeightPrediction::from_slice(0, &[72, 33]);
 
+    /// Input weight prediction corresponding to spending of a P2SH-wrapped P2WPKH output with the
+    /// largest possible DER-encoded signature.
+    ///
+    /// If the input in your transaction uses nested P2WPKH you can use this instead of
+    /// [`InputWeightPrediction::new`].
+    ///
+    /// This is useful when you **do not** use [signature grinding] and want to ensure you are not
+    /// under-paying. See [`ground_p2sh_p2wpkh`](Self::ground_p2sh_p2wpkh) if you do use signature
+    /// grinding.
+    ///
+    /// [signature grinding]: https://bitcoin.stackexchange.com/questions/111660/what-is-signature-grinding
+    pub const P2SH_P2WPKH_MAX: Self = InputWeightPrediction::from_slice(23, &[72, 33]);
+
     /// Input weight prediction corresponding to spending of a P2PKH output with the largest possible
     /// DER-encoded signature, and a compressed public key.
     ///
@@ -1558,6 +2234,27 @@ impl InputWeightPrediction {
         InputWeightPrediction::from_slice(0, &[der_signature_size, 33])
     }
 
+    /// Input weight prediction corresponding to spending of a P2SH-wrapped P2WPKH output using
+    /// [signature grinding].
+    ///
+    /// If the input in your transaction uses nested P2WPKH and you use signature grinding you can
+    /// use this instead of [`InputWeightPrediction::new`]. See
+    /// [`P2SH_P2WPKH_MAX`](Self::P2SH_P2WPKH_MAX) if you don't use signature grinding.
+    ///
+    /// Note: `bytes_to_grind` is usually `1` because of exponential cost of higher values.
+    ///
+    /// # Panics
+    ///
+    /// The funcion panics in const context and debug builds if `bytes_to_grind` is higher than 62.
+    ///
+    /// [signature grinding]: https://bitcoin.stackexchange.com/questions/111660/what-is-signature-grinding
+    pub const fn ground_p2sh_p2wpkh(bytes_to_grind: usize) -> Self {
+        // Written to trigger const/debug panic for unreasonably high values.
+        let der_signature_size = 10 + (62 - bytes_to_grind);
+        // The scriptSig pushes the 22 byte P2WPKH witness program.
+        InputWeightPrediction::from_slice(23, &[der_signature_size, 33])
+    }
+
     /// Input weight prediction corresponding to spending of a P2PKH output using [signature
     /// grinding], and a compressed public key.
     ///
@@ -1729,6 +2426,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn outpoint_display_round_trip() {
+        let s = "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456:42";
+        let outpoint = OutPoint::from_str(s).unwrap();
+        assert_eq!(outpoint.to_string(), s);
+        assert_eq!(OutPoint::from_str(&outpoint.to_string()), Ok(outpoint));
+
+        let coinbase = OutPoint::new_coinbase();
+        assert!(coinbase.is_null());
+        assert_eq!(coinbase, OutPoint::null());
+        assert_eq!(OutPoint::from_str(&coinbase.to_string()), Ok(coinbase));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn outpoint_serde_string() {
+        let s = "5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456:42";
+        let outpoint = OutPoint::from_str(s).unwrap();
+        let json = serde_json::to_string(&outpoint).unwrap();
+        assert_eq!(json, format!("\"{}\"", s));
+        assert_eq!(serde_json::from_str::<OutPoint>(&json).unwrap(), outpoint);
+        serde_round_trip!(outpoint);
+    }
+
     #[test]
     fn txin() {
         let txin: Result<TxIn, _> = deserialize(&hex!("a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff"));
@@ -2138,6 +2859,276 @@ mod tests {
         assert!(!lock_time_disabled.is_relative_lock_time());
     }
 
+    #[test]
+    fn sequence_rbf_and_relative_lock_time() {
+        use crate::locktime::relative::{Height, LockTime, Time};
+
+        assert!(Sequence::ZERO.enables_locktime_and_rbf());
+        assert!(Sequence::ENABLE_RBF_NO_LOCKTIME.enables_locktime_and_rbf());
+        assert!(!Sequence::ENABLE_LOCKTIME_NO_RBF.enables_locktime_and_rbf());
+        assert!(!Sequence::MAX.enables_locktime_and_rbf());
+
+        let height = Sequence::rbf_with_height(144);
+        assert!(height.enables_locktime_and_rbf());
+        assert_eq!(height.to_relative_lock_time(), Some(LockTime::from(Height::from(144))));
+
+        let time = Sequence::rbf_with_512_second_intervals(70);
+        assert!(time.enables_locktime_and_rbf());
+        let lock = LockTime::from(Time::from_512_second_intervals(70));
+        assert_eq!(time.to_relative_lock_time(), Some(lock));
+
+        // Bits without consensus meaning are preserved, the disable flag is cleared.
+        let sequence = Sequence::from_consensus(0x8100_0000).with_relative_lock_time(lock);
+        assert_eq!(sequence, Sequence::from_consensus(0x0140_0046));
+        let sequence = time.with_relative_lock_time(LockTime::from(Height::from(1)));
+        assert_eq!(sequence, Sequence::from_consensus(1));
+    }
+
+    #[test]
+    fn transaction_is_final() {
+        let height = |n| Height::from_consensus(n).unwrap();
+        let mtp = Mtp::from_consensus(1_700_000_000);
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn { sequence: Sequence::ZERO, ..Default::default() }],
+            output: vec![],
+        };
+        assert!(tx.is_final(Height::ZERO, mtp));
+
+        tx.lock_time = absolute::LockTime::from_consensus(800_000);
+        assert!(!tx.is_final(height(800_000), mtp));
+        assert!(tx.is_final(height(800_001), mtp));
+
+        tx.lock_time = absolute::LockTime::from_consensus(1_700_000_000);
+        assert!(!tx.is_final(height(800_001), mtp));
+        assert!(tx.is_final(height(800_001), Mtp::from_consensus(1_700_000_001)));
+
+        // Lock time is disabled if all inputs have a final sequence number.
+        tx.input[0].sequence = Sequence::MAX;
+        assert!(tx.is_final(height(800_001), mtp));
+    }
+
+    #[test]
+    fn calculate_sequence_locks() {
+        use crate::locktime::relative::{ChainPosition, LockTimeBound};
+
+        let height = |n| Height::from_consensus(n).unwrap();
+        let mtp = Mtp::from_consensus;
+        let position = |h, t| ChainPosition::new(height(h), mtp(t));
+        let txin = |sequence| TxIn { sequence, ..Default::default() };
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![
+                txin(Sequence::from_height(10)),
+                txin(Sequence::from_height(5)),
+                txin(Sequence::from_512_second_intervals(2)),
+                txin(Sequence::MAX),
+            ],
+            output: vec![],
+        };
+        let heights = [height(100), height(110), height(100), height(200)];
+        let mtps = [mtp(1_700_000_000), mtp(1_700_001_000), mtp(1_700_000_000), mtp(1_700_009_000)];
+
+        let bound = tx.calculate_sequence_locks(&heights, &mtps);
+        assert_eq!(bound.height, height(115));
+        assert_eq!(bound.median_time_past, mtp(1_700_001_024));
+        assert!(!bound.is_satisfied_by(position(114, 1_700_001_024)));
+        assert!(!bound.is_satisfied_by(position(115, 1_700_001_023)));
+        assert!(bound.is_satisfied_by(position(115, 1_700_001_024)));
+
+        // Relative lock times are not enforced before version 2.
+        tx.version = Version::ONE;
+        assert_eq!(tx.calculate_sequence_locks(&heights, &mtps), LockTimeBound::NONE);
+        // The version is compared as an unsigned integer, like Bitcoin Core does.
+        tx.version = Version(-1);
+        assert_ne!(tx.calculate_sequence_locks(&heights, &mtps), LockTimeBound::NONE);
+    }
+
+    #[test]
+    #[should_panic]
+    fn calculate_sequence_locks_prevouts_length() {
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![],
+        };
+        tx.calculate_sequence_locks(&[], &[]);
+    }
+
+    #[test]
+    fn transaction_builder() {
+        let outpoint = |vout| OutPoint::new(Txid::all_zeros(), vout);
+        let script_pubkey =
+            ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let txout =
+            |sat| TxOut { value: Amount::from_sat(sat), script_pubkey: script_pubkey.clone() };
+
+        let tx = TransactionBuilder::new()
+            .add_input_with_prevout(outpoint(0), Sequence::ENABLE_RBF_NO_LOCKTIME, txout(10_000))
+            .add_input(outpoint(1), Sequence::MAX)
+            .add_output(txout(20_000))
+            .build()
+            .unwrap();
+        assert_eq!(tx.version, Version::TWO);
+        assert_eq!(tx.lock_time, absolute::LockTime::ZERO);
+        assert_eq!(tx.input[1].previous_output, outpoint(1));
+        assert!(tx.input.iter().all(|txin| txin.script_sig.is_empty() && txin.witness.is_empty()));
+        assert_eq!(tx.output, vec![txout(20_000)]);
+
+        let builder = TransactionBuilder::new().add_input_with_prevout(
+            outpoint(0),
+            Sequence::MAX,
+            txout(10_000),
+        );
+        assert_eq!(builder.clone().build(), Err(BuildError::NoOutputs));
+        let no_inputs = TransactionBuilder::new().add_output(txout(1_000));
+        assert_eq!(no_inputs.build(), Err(BuildError::NoInputs));
+        assert_eq!(
+            builder.clone().add_output(txout(10_001)).build(),
+            Err(BuildError::Fee(FeeError::NegativeFee {
+                input_value: Amount::from_sat(10_000),
+                output_value: Amount::from_sat(10_001)
+            }))
+        );
+        assert_eq!(builder.clone().add_output(txout(1)).build(), Err(BuildError::DustOutput(0)));
+        let op_return = TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return([]) };
+        assert!(builder.clone().add_output(op_return).build().is_ok());
+        assert_eq!(
+            builder.clone().add_input(outpoint(0), Sequence::MAX).add_output(txout(1_000)).build(),
+            Err(BuildError::DuplicateInput(outpoint(0)))
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .lock_time(absolute::LockTime::from_consensus(800_000))
+                .add_output(txout(1_000))
+                .build(),
+            Err(BuildError::LockTimeDisabled)
+        );
+    }
+
+    #[test]
+    fn txout_is_dust() {
+        let p2wpkh = ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let p2pkh =
+            ScriptBuf::from_hex("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").unwrap();
+        let txout = |sat, script_pubkey: &ScriptBuf| TxOut {
+            value: Amount::from_sat(sat),
+            script_pubkey: script_pubkey.clone(),
+        };
+
+        assert!(txout(293, &p2wpkh).is_dust(FeeRate::DUST));
+        assert!(!txout(294, &p2wpkh).is_dust(FeeRate::DUST));
+        assert!(txout(545, &p2pkh).is_dust(FeeRate::DUST));
+        assert!(!txout(546, &p2pkh).is_dust(FeeRate::DUST));
+        assert!(txout(546, &p2pkh).is_dust(FeeRate::from_sat_per_vb_unchecked(6)));
+        assert!(!txout(0, &p2pkh).is_dust(FeeRate::ZERO));
+        assert!(!txout(0, &ScriptBuf::new_op_return([])).is_dust(FeeRate::DUST));
+    }
+
+    #[test]
+    fn fee() {
+        let txout = |sat| TxOut { value: Amount::from_sat(sat), script_pubkey: ScriptBuf::new() };
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![txout(5_000), txout(4_000)],
+        };
+
+        assert_eq!(tx.fee(&[txout(6_000), txout(4_000)]), Ok(Amount::from_sat(1_000)));
+        assert_eq!(tx.fee(&[txout(5_000), txout(4_000)]), Ok(Amount::ZERO));
+        assert_eq!(tx.fee(&[txout(6_000)]), Err(FeeError::MissingPrevout(1)));
+        assert_eq!(tx.fee(&[txout(1), txout(1), txout(1)]), Err(FeeError::TooManyPrevouts));
+        assert_eq!(tx.fee(&[txout(u64::MAX), txout(1)]), Err(FeeError::ValueOverflow));
+        assert_eq!(
+            tx.fee(&[txout(4_000), txout(4_000)]),
+            Err(FeeError::NegativeFee {
+                input_value: Amount::from_sat(8_000),
+                output_value: Amount::from_sat(9_000)
+            })
+        );
+    }
+
+    #[test]
+    fn sort_bip69() {
+        // Txids differing only in the first and last byte, compared in reversed byte order.
+        let txid = |first, last| {
+            let mut bytes = [0; 32];
+            bytes[0] = first;
+            bytes[31] = last;
+            Txid::from_byte_array(bytes)
+        };
+        let txin =
+            |txid, vout| TxIn { previous_output: OutPoint::new(txid, vout), ..Default::default() };
+        let txout = |sat, script: &[u8]| TxOut {
+            value: Amount::from_sat(sat),
+            script_pubkey: ScriptBuf::from_bytes(script.to_vec()),
+        };
+
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![txin(txid(0, 1), 0), txin(txid(1, 0), 1), txin(txid(1, 0), 0)],
+            output: vec![txout(2, &[0x00]), txout(1, &[0x01]), txout(1, &[0x00, 0x01])],
+        };
+        tx.sort_bip69();
+
+        let txids = tx.input.iter().map(|i| i.previous_output).collect::<Vec<_>>();
+        assert_eq!(
+            txids,
+            vec![
+                OutPoint::new(txid(1, 0), 0),
+                OutPoint::new(txid(1, 0), 1),
+                OutPoint::new(txid(0, 1), 0)
+            ]
+        );
+        assert_eq!(tx.output, vec![txout(1, &[0x00, 0x01]), txout(1, &[0x01]), txout(2, &[0x00])]);
+        // Hex display order matches BIP-69 order.
+        assert!(txid(1, 0).to_string() < txid(0, 1).to_string());
+    }
+
+    #[test]
+    fn coinbase_maturity() {
+        let height = |n| Height::from_consensus(n).unwrap();
+        let genesis = crate::blockdata::constants::genesis_block(crate::Network::Bitcoin);
+        let coinbase = &genesis.txdata[0];
+        assert!(!coinbase.is_coinbase_mature(height(100), height(100)));
+        assert!(!coinbase.is_coinbase_mature(height(100), height(198)));
+        assert!(coinbase.is_coinbase_mature(height(100), height(199)));
+        assert!(!coinbase.is_coinbase_mature(height(200), height(100)));
+
+        let tx: Transaction = deserialize(&hex!(SOME_TX)).unwrap();
+        assert!(tx.is_coinbase_mature(height(100), height(100)));
+    }
+
+    #[test]
+    fn cached_transaction() {
+        let tx: Transaction = deserialize(&hex!(SOME_TX)).unwrap();
+        let cached = CachedTransaction::new(tx.clone());
+        assert_eq!(cached.txid(), tx.compute_txid());
+        assert_eq!(cached.wtxid(), tx.compute_wtxid());
+        assert_eq!(cached.weight(), tx.weight());
+
+        let raw = hex!(
+            "02000000000101595895ea20179de87052b4046dfe6fd515860505d6511a9004cf12a1f93cac7c01\
+             00000000ffffffff01deb807000000000017a9140f3444e271620c736808aa7b33e370bd87cb5a07\
+             8702483045022100fb60dad8df4af2841adc0346638c16d0b8035f5e3f3753b88db122e70c79f937\
+             0220756e6633b17fd2710e626347d28d60b0a2d6cbb41de51740644b9fb3ba7751040121028fa937\
+             ca8cba2197a37c007176ed8941055d3bcb8627d085e94553e62f057dcc00000000"
+        );
+        let cached: CachedTransaction = deserialize(&raw).unwrap();
+        let tx: Transaction = deserialize(&raw).unwrap();
+        assert_eq!(cached.txid(), tx.compute_txid());
+        assert_eq!(cached.wtxid(), tx.compute_wtxid());
+        assert_ne!(cached.txid().to_raw_hash(), cached.wtxid().to_raw_hash());
+        assert_eq!(serialize(&cached), raw);
+        assert_eq!(cached.into_transaction(), tx);
+    }
+
     #[test]
     fn sequence_from_hex_lower() {
         let sequence = Sequence::from_hex("0xffffffff").unwrap();
@@ -2200,6 +3191,42 @@ mod tests {
         assert!(eff_value.is_none());
     }
 
+    #[test]
+    fn txout_effective_value() {
+        let txout = TxOut { value: Amount::from_sat(1_000_000), script_pubkey: ScriptBuf::new() };
+        let fee_rate = FeeRate::from_sat_per_kwu(10);
+        let satisfaction_weight = Weight::from_wu(204);
+        assert_eq!(
+            txout.effective_value(fee_rate, satisfaction_weight),
+            effective_value(fee_rate, satisfaction_weight, txout.value)
+        );
+        assert!(txout.effective_value(FeeRate::MAX, Weight::ZERO).is_none());
+    }
+
+    #[test]
+    fn waste_metric() {
+        let weights = [Weight::from_wu(272), Weight::from_wu(272)];
+        // Each input weighs 272 + 160 = 432 wu, 108 vB.
+        let fee_rate = FeeRate::from_sat_per_vb(10).unwrap();
+        let long_term_fee_rate = FeeRate::from_sat_per_vb(5).unwrap();
+        let change_cost = Amount::from_sat(1_000);
+        let excess = Amount::from_sat(300);
+
+        // 2 * 108 * (10 - 5) = 1080
+        let waste_with_change =
+            waste(weights, fee_rate, long_term_fee_rate, Some(change_cost), excess).unwrap();
+        assert_eq!(waste_with_change, SignedAmount::from_sat(1_080 + 1_000));
+        let waste_without_change =
+            waste(weights, fee_rate, long_term_fee_rate, None, excess).unwrap();
+        assert_eq!(waste_without_change, SignedAmount::from_sat(1_080 + 300));
+
+        // Spending now is cheaper than spending later.
+        let waste = waste(weights, long_term_fee_rate, fee_rate, None, Amount::ZERO).unwrap();
+        assert_eq!(waste, SignedAmount::from_sat(-1_080));
+
+        assert!(super::waste([Weight::MAX], fee_rate, fee_rate, None, Amount::ZERO).is_none());
+    }
+
     #[test]
     fn txin_txout_weight() {
         // [(is_segwit, tx_hex, expected_weight)]
@@ -2393,6 +3420,61 @@ mod tests {
         }
     }
 
+    #[test]
+    fn tx_sigop_count_borrowed_prevouts() {
+        // 5 sigops (p2wpkh in + p2pkh out (x4))
+        let tx: Transaction = deserialize(&hex!(
+            "020000000001018c47330b1c4d30e7e2244e8ccb56d411b71e10073bb42fa1813f3f01\
+            e144cc4d0100000000fdffffff01f7e30300000000001976a9143b49fd16f7562cfeedc\
+            6a4ba84805f8c2f8e1a2c88ac024830450221009a4dbf077a63f6e4c3628a5fef2a09ec\
+            6f7ca4a4d95bc8bb69195b6b671e9272022074da9ffff5a677fc7b37d66bb4ff1f316c9\
+            dbacb92058291d84cd4b83f7c63c9012103d013e9e53c9ca8dd2ddffab1e9df27811503\
+            feea7eb0700ff058851bbb37d99000000000"
+        ))
+        .unwrap();
+        let prevout: TxOut =
+            deserialize(&hex!("e695779d000000001600141c6977423aa4b82a0d7f8496cdf3fc2f8b4f580c"))
+                .unwrap();
+        let prevouts = [(tx.input[0].previous_output, prevout)];
+
+        let cost = tx.total_sigop_cost(|outpoint: &OutPoint| {
+            prevouts.iter().find(|(op, _)| op == outpoint).map(|(_, txout)| txout)
+        });
+        assert_eq!(cost, 5);
+    }
+
+    #[test]
+    fn tx_sigop_count_p2sh_not_push_only() {
+        // The redeemScript is not counted if the scriptSig is not push only.
+        let redeem_script = crate::script::Builder::new()
+            .push_opcode(crate::opcodes::all::OP_CHECKSIG)
+            .into_script();
+        let script_sig = crate::script::Builder::new()
+            .push_opcode(crate::opcodes::all::OP_NOP)
+            .push_slice(<&crate::script::PushBytes>::try_from(redeem_script.as_bytes()).unwrap())
+            .into_script();
+        let mut tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                script_sig,
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+        let prevout = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_p2sh(&redeem_script.script_hash()),
+        };
+        assert_eq!(tx.total_sigop_cost(|_: &OutPoint| Some(&prevout)), 0);
+
+        tx.input[0].script_sig = crate::script::Builder::new()
+            .push_slice(<&crate::script::PushBytes>::try_from(redeem_script.as_bytes()).unwrap())
+            .into_script();
+        assert_eq!(tx.total_sigop_cost(|_: &OutPoint| Some(&prevout)), 4);
+    }
+
     #[test]
     fn weight_predictions() {
         // TXID 3d3381f968e3a73841cba5e73bf47dcea9f25a9f7663c51c81f1db8229a309a0
@@ -2441,6 +3523,30 @@ mod tests {
             InputWeightPrediction::ground_p2pkh_compressed(0).weight(),
             InputWeightPrediction::P2PKH_COMPRESSED_MAX.weight()
         );
+        assert_eq!(
+            InputWeightPrediction::ground_p2sh_p2wpkh(0).weight(),
+            InputWeightPrediction::P2SH_P2WPKH_MAX.weight()
+        );
+
+        // A nested P2WPKH spend, the scriptSig pushes the P2WPKH witness program.
+        let redeem_script =
+            ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        let script_sig = crate::script::Builder::new()
+            .push_slice(<&crate::script::PushBytes>::try_from(redeem_script.as_bytes()).unwrap())
+            .into_script();
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                script_sig,
+                witness: Witness::from_slice(&[vec![0; 72], vec![0; 33]]),
+                ..Default::default()
+            }],
+            output: vec![TxOut { value: Amount::ZERO, script_pubkey: redeem_script }],
+        };
+        let predicted =
+            predict_weight([InputWeightPrediction::P2SH_P2WPKH_MAX], tx.script_pubkey_lens());
+        assert_eq!(predicted, tx.weight());
     }
 
     #[test]
@@ -10,6 +10,7 @@ pub mod encode;
 pub mod params;
 #[cfg(feature = "serde")]
 pub mod serde;
+pub mod transaction_ref;
 #[cfg(feature = "bitcoinconsensus")]
 pub mod validation;
 
@@ -18,6 +19,7 @@ pub mod validation;
 pub use self::{
     encode::{deserialize, deserialize_partial, serialize, Decodable, Encodable, ReadExt, WriteExt},
     params::Params,
+    transaction_ref::TransactionRef,
 };
 
 #[cfg(feature = "bitcoinconsensus")]
@@ -6,8 +6,13 @@
 //! chains (such as mainnet, testnet).
 //!
 
+use crate::blockdata::block::Version;
+use crate::blockdata::constants::DEFAULT_SIGNET_CHALLENGE;
+use crate::blockdata::script::{Script, ScriptBuf};
 use crate::network::Network;
+use crate::p2p::Magic;
 use crate::pow::Target;
+use crate::prelude::*;
 
 /// Parameters that influence chain consensus.
 #[non_exhaustive]
@@ -23,12 +28,20 @@ pub struct Params {
     pub bip65_height: u32,
     /// Block height at which BIP66 becomes active.
     pub bip66_height: u32,
+    /// Block height at which CSV (BIP68, BIP112 and BIP113) becomes active.
+    pub csv_height: u32,
+    /// Block height at which segwit (BIP141, BIP143 and BIP147) becomes active.
+    pub segwit_height: u32,
+    /// The BIP9 deployment of taproot (BIP340, BIP341 and BIP342).
+    pub taproot: Bip9Deployment,
     /// Minimum blocks including miner confirmation of the total of 2016 blocks in a retargeting period,
     /// (nPowTargetTimespan / nPowTargetSpacing) which is also used for BIP9 deployments.
     /// Examples: 1916 for 95%, 1512 for testchains.
     pub rule_change_activation_threshold: u32,
     /// Number of blocks with the same set of rules.
     pub miner_confirmation_window: u32,
+    /// Number of blocks between block subsidy halvings.
+    pub subsidy_halving_interval: u32,
     /// Proof of work limit value. It contains the lowest possible difficulty.
     ///
     /// Note that this value differs from Bitcoin Core's powLimit field in that this value is
@@ -46,6 +59,8 @@ pub struct Params {
     pub allow_min_difficulty_blocks: bool,
     /// Determines whether retargeting is disabled for this network or not.
     pub no_pow_retargeting: bool,
+    /// The block challenge script of a signet.
+    signet_challenge: Option<Cow<'static, [u8]>>,
 }
 
 impl Params {
@@ -55,33 +70,53 @@ impl Params {
     /// The mainnet parameters.
     pub const MAINNET: Params = Params {
         network: Network::Bitcoin,
-        bip16_time: 1333238400,                 // Apr 1 2012
-        bip34_height: 227931, // 000000000000024b89b42a942fe0d9fea3bb44ab7bd1b19115dd6a759c0808b8
-        bip65_height: 388381, // 000000000000000004c2b624ed5d7756c508d90fd0da2c7c679febfa6c4735f0
-        bip66_height: 363725, // 00000000000000000379eaa19dce8c9b722d46ae6a57c2f1a988119488b50931
+        bip16_time: 1333238400, // Apr 1 2012
+        bip34_height: 227931,   // 000000000000024b89b42a942fe0d9fea3bb44ab7bd1b19115dd6a759c0808b8
+        bip65_height: 388381,   // 000000000000000004c2b624ed5d7756c508d90fd0da2c7c679febfa6c4735f0
+        bip66_height: 363725,   // 00000000000000000379eaa19dce8c9b722d46ae6a57c2f1a988119488b50931
+        csv_height: 419328,     // 000000000000000004a1b34462cb8aeebd5799177f7a29cf28f2d1961716b5b5
+        segwit_height: 481824,  // 0000000000000000001c8018d9cb3b742ef25114f27563e3fc4a1902167f9893
+        taproot: Bip9Deployment {
+            bit: 2,
+            start_time: 1619222400, // April 24th, 2021
+            timeout: 1628640000,    // August 11th, 2021
+            min_activation_height: 709632,
+        },
         rule_change_activation_threshold: 1916, // 95%
         miner_confirmation_window: 2016,
+        subsidy_halving_interval: 210_000,
         pow_limit: Target::MAX_ATTAINABLE_MAINNET,
         pow_target_spacing: 10 * 60,            // 10 minutes.
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: false,
         no_pow_retargeting: false,
+        signet_challenge: None,
     };
 
     /// The testnet parameters.
     pub const TESTNET: Params = Params {
         network: Network::Testnet,
-        bip16_time: 1333238400,                 // Apr 1 2012
-        bip34_height: 21111, // 0000000023b3a96d3484e5abb3755c413e7d41500f8e2a5c3f0dd01299cd8ef8
-        bip65_height: 581885, // 00000000007f6655f22f98e72ed80d8b06dc761d5da09df0fa1dc4be4f861eb6
-        bip66_height: 330776, // 000000002104c8c45e99a8853285a3b592602a3ccde2b832481da85e9e4ba182
+        bip16_time: 1333238400, // Apr 1 2012
+        bip34_height: 21111,    // 0000000023b3a96d3484e5abb3755c413e7d41500f8e2a5c3f0dd01299cd8ef8
+        bip65_height: 581885,   // 00000000007f6655f22f98e72ed80d8b06dc761d5da09df0fa1dc4be4f861eb6
+        bip66_height: 330776,   // 000000002104c8c45e99a8853285a3b592602a3ccde2b832481da85e9e4ba182
+        csv_height: 770112,     // 00000000025e930139bac5c6c31a403776da130831ab85be56578f3fa75369bb
+        segwit_height: 834624,  // 00000000002b980fcd729daaa248fd9316a5200e9b367f4ff2c42453e84201ca
+        taproot: Bip9Deployment {
+            bit: 2,
+            start_time: 1619222400, // April 24th, 2021
+            timeout: 1628640000,    // August 11th, 2021
+            min_activation_height: 0,
+        },
         rule_change_activation_threshold: 1512, // 75%
         miner_confirmation_window: 2016,
+        subsidy_halving_interval: 210_000,
         pow_limit: Target::MAX_ATTAINABLE_TESTNET,
         pow_target_spacing: 10 * 60,            // 10 minutes.
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: true,
         no_pow_retargeting: false,
+        signet_challenge: None,
     };
 
     /// The signet parameters.
@@ -91,13 +126,18 @@ impl Params {
         bip34_height: 1,
         bip65_height: 1,
         bip66_height: 1,
+        csv_height: 1,
+        segwit_height: 1,
+        taproot: Bip9Deployment::ALWAYS_ACTIVE,
         rule_change_activation_threshold: 1916, // 95%
         miner_confirmation_window: 2016,
+        subsidy_halving_interval: 210_000,
         pow_limit: Target::MAX_ATTAINABLE_SIGNET,
         pow_target_spacing: 10 * 60,            // 10 minutes.
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: false,
         no_pow_retargeting: false,
+        signet_challenge: Some(Cow::Borrowed(&DEFAULT_SIGNET_CHALLENGE)),
     };
 
     /// The regtest parameters.
@@ -106,17 +146,22 @@ impl Params {
         bip16_time: 1333238400,  // Apr 1 2012
         bip34_height: 100000000, // not activated on regtest
         bip65_height: 1351,
-        bip66_height: 1251,                    // used only in rpc tests
+        bip66_height: 1251, // used only in rpc tests
+        csv_height: 1,
+        segwit_height: 0, // always active
+        taproot: Bip9Deployment::ALWAYS_ACTIVE,
         rule_change_activation_threshold: 108, // 75%
         miner_confirmation_window: 144,
+        subsidy_halving_interval: 150,
         pow_limit: Target::MAX_ATTAINABLE_REGTEST,
         pow_target_spacing: 10 * 60,            // 10 minutes.
         pow_target_timespan: 14 * 24 * 60 * 60, // 2 weeks.
         allow_min_difficulty_blocks: true,
         no_pow_retargeting: true,
+        signet_challenge: None,
     };
 
-    /// Creates parameters set for the given network.    /// Creates parameters set for the given network.
+    /// Creates parameters set for the given network.
     pub const fn new(network: Network) -> Self {
         match network {
             Network::Bitcoin => Params::MAINNET,
@@ -126,17 +171,102 @@ impl Params {
         }
     }
 
+    /// Creates parameters for a custom signet with block challenge script `challenge`.
+    ///
+    /// Custom signets share the consensus rules and the genesis block of the default signet, only
+    /// the challenge, and thus the network magic, differ.
+    pub fn custom_signet(challenge: ScriptBuf) -> Self {
+        Params { signet_challenge: Some(Cow::Owned(challenge.into_bytes())), ..Params::SIGNET }
+    }
+
+    /// Returns the block challenge script if these are signet parameters.
+    pub fn signet_challenge(&self) -> Option<&Script> {
+        self.signet_challenge.as_deref().map(Script::from_bytes)
+    }
+
+    /// Returns the network magic, which for signets is computed from the challenge.
+    pub fn magic(&self) -> Magic {
+        match self.signet_challenge() {
+            Some(challenge) => Magic::from_signet_challenge(challenge),
+            None => Magic::from(self.network),
+        }
+    }
+
     /// Calculates the number of blocks between difficulty adjustments.
     pub fn difficulty_adjustment_interval(&self) -> u64 {
         self.pow_target_timespan / self.pow_target_spacing
     }
+
+    /// Returns true if BIP16 (P2SH) is active for a block with timestamp `time`.
+    pub fn is_bip16_active(&self, time: u32) -> bool { time >= self.bip16_time }
+
+    /// Returns true if BIP34 (block height in coinbase) is active at `height`.
+    pub fn is_bip34_active(&self, height: u32) -> bool { height >= self.bip34_height }
+
+    /// Returns true if BIP65 (`OP_CHECKLOCKTIMEVERIFY`) is active at `height`.
+    pub fn is_bip65_active(&self, height: u32) -> bool { height >= self.bip65_height }
+
+    /// Returns true if BIP66 (strict DER signatures) is active at `height`.
+    pub fn is_bip66_active(&self, height: u32) -> bool { height >= self.bip66_height }
+
+    /// Returns true if CSV (BIP68, BIP112 and BIP113) is active at `height`.
+    pub fn is_csv_active(&self, height: u32) -> bool { height >= self.csv_height }
+
+    /// Returns true if segwit (BIP141, BIP143 and BIP147) is active at `height`.
+    pub fn is_segwit_active(&self, height: u32) -> bool { height >= self.segwit_height }
+}
+
+/// The parameters of a BIP9 version bits deployment.
+///
+/// Whether a BIP9 deployment is active depends on the blocks signalling for it, so unlike buried
+/// deployments it can't be determined from the height alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bip9Deployment {
+    /// The bit of the block version used to signal for the deployment.
+    pub bit: u8,
+    /// The median time past after which signalling starts, or one of the special values
+    /// [`Bip9Deployment::ALWAYS_ACTIVE_TIME`] and [`Bip9Deployment::NEVER_ACTIVE_TIME`].
+    pub start_time: i64,
+    /// The median time past after which the deployment fails if it isn't locked in.
+    pub timeout: i64,
+    /// The height before which the deployment can't activate, even if locked in.
+    pub min_activation_height: u32,
+}
+
+impl Bip9Deployment {
+    /// The start time of a deployment that is always active.
+    pub const ALWAYS_ACTIVE_TIME: i64 = -1;
+
+    /// The start time of a deployment that is never active.
+    pub const NEVER_ACTIVE_TIME: i64 = -2;
+
+    /// The timeout of a deployment that doesn't time out.
+    pub const NO_TIMEOUT: i64 = i64::MAX;
+
+    /// A deployment that is active from the genesis block.
+    pub const ALWAYS_ACTIVE: Bip9Deployment = Bip9Deployment {
+        bit: 28,
+        start_time: Bip9Deployment::ALWAYS_ACTIVE_TIME,
+        timeout: Bip9Deployment::NO_TIMEOUT,
+        min_activation_height: 0,
+    };
+
+    /// Returns true if the deployment is active regardless of signalling.
+    pub fn is_always_active(&self) -> bool { self.start_time == Self::ALWAYS_ACTIVE_TIME }
+
+    /// Returns true if the deployment can never become active.
+    pub fn is_never_active(&self) -> bool { self.start_time == Self::NEVER_ACTIVE_TIME }
+
+    /// Returns true if a block with `version` signals for the deployment.
+    pub fn is_signalled_by(&self, version: Version) -> bool {
+        version.is_signalling_soft_fork(self.bit)
+    }
 }
 
 impl From<Network> for Params {
     fn from(value: Network) -> Self { Self::new(value) }
 }
 
-
 impl From<&Network> for Params {
     fn from(value: &Network) -> Self { Self::new(*value) }
 }
@@ -148,3 +278,65 @@ impl From<Network> for &'static Params {
 impl From<&Network> for &'static Params {
     fn from(value: &Network) -> Self { value.params() }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buried_deployments() {
+        let params = Params::MAINNET;
+        assert!(!params.is_bip34_active(227930));
+        assert!(params.is_bip34_active(227931));
+        assert!(!params.is_bip65_active(388380));
+        assert!(params.is_bip65_active(388381));
+        assert!(!params.is_bip66_active(363724));
+        assert!(params.is_bip66_active(363725));
+        assert!(!params.is_csv_active(419327));
+        assert!(params.is_csv_active(419328));
+        assert!(!params.is_segwit_active(481823));
+        assert!(params.is_segwit_active(481824));
+        assert!(!params.is_bip16_active(1333238399));
+        assert!(params.is_bip16_active(1333238400));
+
+        assert!(Params::REGTEST.is_segwit_active(0));
+        assert!(!Params::REGTEST.is_bip34_active(1));
+        assert!(Params::SIGNET.is_bip65_active(1));
+    }
+
+    #[test]
+    fn bip9_deployments() {
+        let taproot = Params::MAINNET.taproot;
+        assert!(!taproot.is_always_active());
+        assert!(!taproot.is_never_active());
+        assert!(taproot.is_signalled_by(Version::from_consensus(0x20000004)));
+        assert!(!taproot.is_signalled_by(Version::from_consensus(0x20000000)));
+        assert!(!taproot.is_signalled_by(Version::from_consensus(0x00000004)));
+
+        assert!(Params::SIGNET.taproot.is_always_active());
+        assert!(Params::REGTEST.taproot.is_always_active());
+    }
+
+    #[test]
+    fn signet_magic() {
+        for network in [Network::Bitcoin, Network::Testnet, Network::Signet, Network::Regtest] {
+            assert_eq!(Params::new(network).magic(), network.magic());
+        }
+        assert_eq!(
+            Params::SIGNET.signet_challenge().unwrap().as_bytes(),
+            &DEFAULT_SIGNET_CHALLENGE[..]
+        );
+        assert_eq!(Params::MAINNET.signet_challenge(), None);
+
+        // A 1-of-1 multisig challenge.
+        let challenge = ScriptBuf::from_hex(
+            "512102f7561d208dd9ae99bf497273e16f389bdbd6c4742ddb8e6b216e64fa2928ad8f51ae",
+        )
+        .unwrap();
+        let params = Params::custom_signet(challenge.clone());
+        assert_eq!(params.network, Network::Signet);
+        assert_eq!(params.signet_challenge(), Some(challenge.as_script()));
+        assert_eq!(params.magic(), Magic::from_signet_challenge(&challenge));
+        assert_ne!(params.magic(), Magic::SIGNET);
+    }
+}
@@ -0,0 +1,556 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Zero-copy transaction decoding.
+//!
+//! This module provides [`TransactionRef`], a view of a consensus encoded transaction which is
+//! validated once when decoded and then read in place. Scripts and witness elements are exposed as
+//! slices of the encoded data so no allocation is needed, which makes it suitable for scanning
+//! large numbers of transactions, e.g. when indexing blocks.
+
+use core::fmt;
+
+use hashes::{Hash, HashEngine};
+use io::ErrorKind;
+
+use crate::blockdata::locktime::absolute;
+use crate::blockdata::script::Script;
+use crate::blockdata::transaction::{
+    self, OutPoint, Sequence, Transaction, TxIn, TxOut, Txid, Wtxid,
+};
+use crate::blockdata::witness::Witness;
+use crate::consensus::encode::{self, Decodable};
+use crate::prelude::*;
+use crate::{Amount, VarInt, Weight};
+
+/// A consensus encoded transaction, decoded in place.
+///
+/// The encoding is fully validated when decoding, accepting exactly the transactions accepted by
+/// the [`Decodable`] implementation of [`Transaction`].
+///
+/// # Examples
+///
+/// ```
+/// # use bitcoin::consensus::TransactionRef;
+/// # use bitcoin::hex::FromHex;
+/// let raw = Vec::from_hex("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000")?;
+/// let tx = TransactionRef::from_slice(&raw)?;
+///
+/// for output in tx.outputs() {
+///     assert!(output.script_pubkey.is_p2pkh());
+/// }
+/// assert_eq!(tx.compute_txid(), tx.to_transaction().compute_txid());
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct TransactionRef<'a> {
+    /// The whole encoded transaction.
+    data: &'a [u8],
+    version: transaction::Version,
+    lock_time: absolute::LockTime,
+    input_count: usize,
+    output_count: usize,
+    /// Start of the input count, this follows the segwit marker and flag if present.
+    inputs_start: usize,
+    /// Start of the output count.
+    outputs_start: usize,
+    /// End of the outputs, this is the start of the witnesses if present.
+    outputs_end: usize,
+    /// Whether the transaction is encoded using the BIP-144 segwit serialization.
+    segwit: bool,
+}
+
+impl<'a> TransactionRef<'a> {
+    /// Decodes a transaction from `data`, which must not contain any data after the transaction.
+    pub fn from_slice(data: &'a [u8]) -> Result<Self, encode::Error> {
+        let (tx, consumed) = Self::from_slice_partial(data)?;
+        if consumed == data.len() {
+            Ok(tx)
+        } else {
+            Err(encode::Error::ParseFailed(
+                "data not consumed entirely when explicitly deserializing",
+            ))
+        }
+    }
+
+    /// Decodes a transaction from the start of `data`, returning it along with the number of bytes
+    /// consumed.
+    ///
+    /// This is useful to decode transactions one after another, e.g. from a block.
+    pub fn from_slice_partial(data: &'a [u8]) -> Result<(Self, usize), encode::Error> {
+        let mut r = Reader::new(data);
+        let version = r.read(4)?;
+
+        let mut inputs_start = r.pos;
+        let mut input_count = r.read_count()?;
+        let mut segwit = false;
+        if input_count == 0 {
+            match r.read_u8()? {
+                // BIP144 input witnesses
+                1 => {
+                    segwit = true;
+                    inputs_start = r.pos;
+                    input_count = r.read_count()?;
+                }
+                // We don't support anything else
+                x => return Err(encode::Error::UnsupportedSegwitFlag(x)),
+            }
+        }
+        for _ in 0..input_count {
+            r.read_txin()?;
+        }
+
+        let outputs_start = r.pos;
+        let output_count = r.read_count()?;
+        for _ in 0..output_count {
+            r.read_txout()?;
+        }
+        let outputs_end = r.pos;
+
+        if segwit {
+            let mut all_empty = true;
+            for _ in 0..input_count {
+                all_empty &= r.read_witness()?.is_empty();
+            }
+            if input_count > 0 && all_empty {
+                return Err(encode::Error::ParseFailed(
+                    "witness flag set but no witnesses present",
+                ));
+            }
+        }
+
+        let lock_time = r.read(4)?;
+
+        let tx = TransactionRef {
+            data: &data[..r.pos],
+            version,
+            lock_time,
+            input_count,
+            output_count,
+            inputs_start,
+            outputs_start,
+            outputs_end,
+            segwit,
+        };
+        Ok((tx, r.pos))
+    }
+
+    /// Returns the encoded transaction.
+    pub fn as_bytes(&self) -> &'a [u8] { self.data }
+
+    /// Returns the protocol version of the transaction.
+    pub fn version(&self) -> transaction::Version { self.version }
+
+    /// Returns the lock time of the transaction.
+    pub fn lock_time(&self) -> absolute::LockTime { self.lock_time }
+
+    /// Returns `true` if the transaction is encoded using the segwit serialization ([BIP-144]).
+    ///
+    /// [BIP-144]: <https://github.com/bitcoin/bips/blob/master/bip-0144.mediawiki>
+    pub fn is_segwit(&self) -> bool { self.segwit }
+
+    /// Returns the number of inputs.
+    pub fn input_count(&self) -> usize { self.input_count }
+
+    /// Returns the number of outputs.
+    pub fn output_count(&self) -> usize { self.output_count }
+
+    /// Returns an iterator over the inputs of the transaction, including their witnesses.
+    pub fn inputs(&self) -> Inputs<'a> {
+        let inputs = Reader::at(self.data, self.inputs_start);
+        let witnesses = Reader::at(self.data, self.outputs_end);
+        let remaining = self.input_count;
+        let mut inputs = Inputs { inputs, witnesses, remaining, segwit: self.segwit };
+        inputs.inputs.read_count().expect("validated when decoded");
+        inputs
+    }
+
+    /// Returns an iterator over the outputs of the transaction.
+    pub fn outputs(&self) -> Outputs<'a> {
+        let mut outputs = Reader::at(&self.data[..self.outputs_end], self.outputs_start);
+        outputs.read_count().expect("validated when decoded");
+        Outputs { outputs, remaining: self.output_count }
+    }
+
+    /// Returns the size of the transaction, in bytes, without the witness data.
+    pub fn base_size(&self) -> usize {
+        if self.segwit {
+            // Version, inputs and outputs, lock time.
+            4 + (self.outputs_end - self.inputs_start) + 4
+        } else {
+            self.data.len()
+        }
+    }
+
+    /// Returns the size of the transaction, in bytes, including the witness data.
+    pub fn total_size(&self) -> usize { self.data.len() }
+
+    /// Returns the weight of the transaction, as defined by BIP-141.
+    pub fn weight(&self) -> Weight {
+        Weight::from_wu_usize(self.base_size() * 3 + self.total_size())
+    }
+
+    /// Computes the [`Txid`], hashing the encoded data without the witnesses.
+    pub fn compute_txid(&self) -> Txid {
+        if !self.segwit {
+            return Txid::hash(self.data);
+        }
+        let mut engine = Txid::engine();
+        engine.input(&self.data[..4]);
+        engine.input(&self.data[self.inputs_start..self.outputs_end]);
+        engine.input(&self.data[self.data.len() - 4..]);
+        Txid::from_engine(engine)
+    }
+
+    /// Computes the [`Wtxid`], hashing all of the encoded data.
+    pub fn compute_wtxid(&self) -> Wtxid { Wtxid::hash(self.data) }
+
+    /// Converts to an owned [`Transaction`].
+    pub fn to_transaction(&self) -> Transaction {
+        Transaction {
+            version: self.version,
+            lock_time: self.lock_time,
+            input: self.inputs().map(|input| input.to_txin()).collect(),
+            output: self.outputs().map(|output| output.to_txout()).collect(),
+        }
+    }
+}
+
+impl fmt::Debug for TransactionRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransactionRef")
+            .field("version", &self.version)
+            .field("lock_time", &self.lock_time)
+            .field("input", &self.inputs().collect::<Vec<_>>())
+            .field("output", &self.outputs().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl From<TransactionRef<'_>> for Transaction {
+    fn from(tx: TransactionRef<'_>) -> Self { tx.to_transaction() }
+}
+
+/// An input of a [`TransactionRef`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxInRef<'a> {
+    /// The reference to the previous output that is being used as an input.
+    pub previous_output: OutPoint,
+    /// The script which pushes values on the stack which will cause the referenced output's
+    /// script to be accepted.
+    pub script_sig: &'a Script,
+    /// The sequence number.
+    pub sequence: Sequence,
+    /// The witness, empty for transactions not using the segwit serialization.
+    pub witness: WitnessRef<'a>,
+}
+
+impl TxInRef<'_> {
+    /// Converts to an owned [`TxIn`].
+    pub fn to_txin(&self) -> TxIn {
+        TxIn {
+            previous_output: self.previous_output,
+            script_sig: self.script_sig.to_owned(),
+            sequence: self.sequence,
+            witness: self.witness.to_witness(),
+        }
+    }
+}
+
+/// An output of a [`TransactionRef`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxOutRef<'a> {
+    /// The value of the output.
+    pub value: Amount,
+    /// The script which must be satisfied for the output to be spent.
+    pub script_pubkey: &'a Script,
+}
+
+impl TxOutRef<'_> {
+    /// Converts to an owned [`TxOut`].
+    pub fn to_txout(&self) -> TxOut {
+        TxOut { value: self.value, script_pubkey: self.script_pubkey.to_owned() }
+    }
+}
+
+/// The witness of a [`TxInRef`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct WitnessRef<'a> {
+    /// The encoded elements, excluding the element count.
+    data: &'a [u8],
+    len: usize,
+}
+
+impl<'a> WitnessRef<'a> {
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize { self.len }
+
+    /// Returns `true` if the witness has no elements.
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> WitnessIter<'a> {
+        WitnessIter { elements: Reader::new(self.data), remaining: self.len }
+    }
+
+    /// Converts to an owned [`Witness`].
+    pub fn to_witness(&self) -> Witness { Witness::from_slice(&self.iter().collect::<Vec<_>>()) }
+}
+
+impl fmt::Debug for WitnessRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter().map(|element| element.as_hex())).finish()
+    }
+}
+
+impl<'a> IntoIterator for WitnessRef<'a> {
+    type Item = &'a [u8];
+    type IntoIter = WitnessIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter { self.iter() }
+}
+
+/// An iterator over the inputs of a [`TransactionRef`].
+#[derive(Clone)]
+pub struct Inputs<'a> {
+    inputs: Reader<'a>,
+    witnesses: Reader<'a>,
+    remaining: usize,
+    segwit: bool,
+}
+
+impl<'a> Iterator for Inputs<'a> {
+    type Item = TxInRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let (previous_output, script_sig, sequence) =
+            self.inputs.read_txin().expect("validated when decoded");
+        let witness = if self.segwit {
+            self.witnesses.read_witness().expect("validated when decoded")
+        } else {
+            WitnessRef { data: &[], len: 0 }
+        };
+        Some(TxInRef { previous_output, script_sig, sequence, witness })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.remaining, Some(self.remaining)) }
+}
+
+impl ExactSizeIterator for Inputs<'_> {}
+
+/// An iterator over the outputs of a [`TransactionRef`].
+#[derive(Clone)]
+pub struct Outputs<'a> {
+    outputs: Reader<'a>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for Outputs<'a> {
+    type Item = TxOutRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let (value, script_pubkey) = self.outputs.read_txout().expect("validated when decoded");
+        Some(TxOutRef { value, script_pubkey })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.remaining, Some(self.remaining)) }
+}
+
+impl ExactSizeIterator for Outputs<'_> {}
+
+/// An iterator over the elements of a [`WitnessRef`].
+#[derive(Clone)]
+pub struct WitnessIter<'a> {
+    elements: Reader<'a>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for WitnessIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(self.elements.read_var_slice().expect("validated when decoded"))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.remaining, Some(self.remaining)) }
+}
+
+impl ExactSizeIterator for WitnessIter<'_> {}
+
+/// Reads consensus encoded values from a slice without copying.
+#[derive(Clone)]
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self { Reader { data, pos: 0 } }
+
+    fn at(data: &'a [u8], pos: usize) -> Self { Reader { data, pos } }
+
+    /// Returns the data which has not been read yet.
+    fn remaining(&self) -> &'a [u8] { &self.data[self.pos..] }
+
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8], encode::Error> {
+        let slice = self.remaining().get(..len).ok_or(io::Error::from(ErrorKind::UnexpectedEof))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, encode::Error> { Ok(self.read_slice(1)?[0]) }
+
+    fn read<T: Decodable>(&mut self, len: usize) -> Result<T, encode::Error> {
+        let mut slice = self.read_slice(len)?;
+        T::consensus_decode(&mut slice)
+    }
+
+    fn read_count(&mut self) -> Result<usize, encode::Error> {
+        let mut remaining = self.remaining();
+        let count = VarInt::consensus_decode(&mut remaining)?.0;
+        self.pos = self.data.len() - remaining.len();
+        usize::try_from(count).map_err(|_| encode::Error::ParseFailed("count overflows usize"))
+    }
+
+    fn read_var_slice(&mut self) -> Result<&'a [u8], encode::Error> {
+        let len = self.read_count()?;
+        self.read_slice(len)
+    }
+
+    fn read_txin(&mut self) -> Result<(OutPoint, &'a Script, Sequence), encode::Error> {
+        let previous_output = self.read(36)?;
+        let script_sig = Script::from_bytes(self.read_var_slice()?);
+        let sequence = self.read(4)?;
+        Ok((previous_output, script_sig, sequence))
+    }
+
+    fn read_txout(&mut self) -> Result<(Amount, &'a Script), encode::Error> {
+        let value = self.read(8)?;
+        let script_pubkey = Script::from_bytes(self.read_var_slice()?);
+        Ok((value, script_pubkey))
+    }
+
+    fn read_witness(&mut self) -> Result<WitnessRef<'a>, encode::Error> {
+        let len = self.read_count()?;
+        let start = self.pos;
+        for _ in 0..len {
+            self.read_var_slice()?;
+        }
+        Ok(WitnessRef { data: &self.data[start..self.pos], len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::test_hex_unwrap as hex;
+
+    use super::*;
+    use crate::consensus::encode::{deserialize, serialize};
+
+    const SOME_TX: &str = "0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000";
+    // TXID 3d3381f968e3a73841cba5e73bf47dcea9f25a9f7663c51c81f1db8229a309a0
+    const SEGWIT_TX: &str = "01000000000103fc9aa70afba04da865f9821734b556cca9fb5710fc1338b97fba811033f755e308000000000000000019b37457784dd04936f011f733b8016c247a9ef08d40007a54a5159d1fc62ee21600000000000000004c4f2937c6ccf8256d9711a19df1ae621722970bf46be925ff15f490efa1633d01000000000000000002c0e1e4000000000017a9146983f776902c1d1d0355ae0962cb7bc69e9afbde8706a1e600000000001600144257782711458506b89f255202d645e25c41144702483045022100dcada0499865a49d0aab8cb113c5f83fd5a97abc793f97f3f53aa4b9d1192ed702202094c7934666a30d6adb1cc9e3b6bc14d2ffebd3200f3908c40053ef2df640b501210315434bb59b615a383ae87316e784fc11835bb97fab33fdd2578025e9968d516e0247304402201d90b3197650569eba4bc0e0b1e2dca77dfac7b80d4366f335b67e92e0546e4402203b4be1d443ad7e3a5ea92aafbcdc027bf9ccf5fe68c0bc8f3ebb6ab806c5464c012103e00d92b0fe60731a54fdbcc6920934159db8ffd69d55564579b69a22ec5bb7530247304402205ab83b734df818e64d8b9e86a8a75f9d005c0c6e1b988d045604853ab9ccbde002205a580235841df609d6bd67534bdcd301999b18e74e197e9e476cdef5fdcbf822012102ebb3e8a4638ede4721fb98e44e3a3cd61fecfe744461b85e0b6a6a10175d5aca00000000";
+
+    fn check_matches_owned(raw: &[u8]) {
+        let tx = TransactionRef::from_slice(raw).unwrap();
+        let owned: Transaction = deserialize(raw).unwrap();
+
+        assert_eq!(tx.to_transaction(), owned);
+        assert_eq!(tx.version(), owned.version);
+        assert_eq!(tx.lock_time(), owned.lock_time);
+        assert_eq!(tx.inputs().len(), owned.input.len());
+        assert_eq!(tx.outputs().len(), owned.output.len());
+        for (input, txin) in tx.inputs().zip(&owned.input) {
+            assert_eq!(input.script_sig, txin.script_sig.as_script());
+            assert_eq!(input.witness.len(), txin.witness.len());
+            assert!(input.witness.iter().eq(txin.witness.iter()));
+        }
+        for (output, txout) in tx.outputs().zip(&owned.output) {
+            assert_eq!(output.to_txout(), *txout);
+        }
+        assert_eq!(tx.compute_txid(), owned.compute_txid());
+        assert_eq!(tx.compute_wtxid(), owned.compute_wtxid());
+        assert_eq!(tx.base_size(), owned.base_size());
+        assert_eq!(tx.total_size(), owned.total_size());
+        assert_eq!(tx.weight(), owned.weight());
+        assert_eq!(tx.as_bytes(), &serialize(&owned)[..]);
+    }
+
+    #[test]
+    fn legacy_transaction() {
+        let raw = hex!(SOME_TX);
+        check_matches_owned(&raw);
+        assert!(!TransactionRef::from_slice(&raw).unwrap().is_segwit());
+    }
+
+    #[test]
+    fn segwit_transaction() {
+        let raw = hex!(SEGWIT_TX);
+        check_matches_owned(&raw);
+
+        let tx = TransactionRef::from_slice(&raw).unwrap();
+        assert!(tx.is_segwit());
+        assert_eq!(
+            tx.compute_txid().to_string(),
+            "3d3381f968e3a73841cba5e73bf47dcea9f25a9f7663c51c81f1db8229a309a0"
+        );
+    }
+
+    #[test]
+    fn partial() {
+        let mut raw = hex!(SOME_TX);
+        let len = raw.len();
+        raw.extend_from_slice(&hex!(SEGWIT_TX));
+
+        let (first, consumed) = TransactionRef::from_slice_partial(&raw).unwrap();
+        assert_eq!(consumed, len);
+        assert_eq!(first.as_bytes(), &raw[..len]);
+        let second = TransactionRef::from_slice(&raw[consumed..]).unwrap();
+        assert!(second.is_segwit());
+
+        assert!(matches!(TransactionRef::from_slice(&raw), Err(encode::Error::ParseFailed(_))));
+    }
+
+    #[test]
+    fn invalid() {
+        let raw = hex!(SEGWIT_TX);
+        for len in 0..raw.len() {
+            assert!(TransactionRef::from_slice(&raw[..len]).is_err());
+        }
+
+        // Segwit flag other than 1.
+        let mut bad_flag = raw.clone();
+        bad_flag[5] = 2;
+        assert!(matches!(
+            TransactionRef::from_slice(&bad_flag),
+            Err(encode::Error::UnsupportedSegwitFlag(2))
+        ));
+
+        // Segwit flag set but all witnesses empty.
+        let mut owned: Transaction = deserialize(&raw).unwrap();
+        for input in &mut owned.input {
+            input.witness = Witness::new();
+        }
+        let mut no_witness = serialize(&owned);
+        // Re-insert the marker and flag with zero-element witnesses before the lock time.
+        let lock_time = no_witness.split_off(no_witness.len() - 4);
+        no_witness.splice(4..4, [0, 1]);
+        no_witness.extend(core::iter::repeat(0).take(owned.input.len()));
+        no_witness.extend(lock_time);
+        assert_eq!(
+            deserialize::<Transaction>(&no_witness).unwrap_err().to_string(),
+            TransactionRef::from_slice(&no_witness).unwrap_err().to_string()
+        );
+    }
+}
@@ -0,0 +1,579 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Adaptor signatures.
+//!
+//! An adaptor signature is a signature "encrypted" to an encryption (adaptor) point `Y = y * G`.
+//! It can be verified without knowing `y`, decrypted into a valid signature by anyone knowing `y`,
+//! and once the decrypted signature is published, `y` can be recovered from both signatures. This
+//! is the building block of discreet log contracts and atomic swaps.
+//!
+//! [`EcdsaAdaptorSignature`] implements the ECDSA adaptor signatures of the DLC specification,
+//! which are also implemented by the `ecdsa_adaptor` module of `secp256k1-zkp`. The 162 byte
+//! encoding, the DLEQ proof and the verification, decryption and recovery algorithms are the same,
+//! so these adaptor signatures can be exchanged with other implementations. Only the derivation of
+//! the signer's nonces differs, which is not observable by other parties.
+//!
+//! [`AdaptorSignature`] is a Schnorr adaptor signature for taproot key path spends. It signs a
+//! [`TapSighash`] with a [`TweakedKeypair`], so the decrypted signature is valid for a key path
+//! spend of the corresponding [`TweakedPublicKey`].
+//!
+//! ### Compatibility
+//!
+//! There is no standard for Schnorr adaptor signatures yet and [`AdaptorSignature`] does not
+//! interoperate with the adaptor APIs of `secp256k1-zkp`. Verification, decryption and secret
+//! recovery use the usual adaptor equations, but the nonce is derived with this crate's own
+//! `AdaptorSignature/nonce` tagged hash and the 65 byte encoding (compressed nonce followed by the
+//! scalar) is specific to this crate. Schnorr adaptor signatures should only be exchanged between
+//! implementations of this scheme.
+
+use core::fmt;
+
+use hashes::Hash;
+use secp256k1::{schnorr, Message, Parity, Secp256k1, SecretKey, Verification};
+
+use crate::crypto::ec::{
+    base_mul, challenge, derive_nonce, has_even_y, point_add, point_mul, tagged_hash, ModN,
+};
+use crate::crypto::ecdsa;
+use crate::crypto::key::{CompressedPublicKey, TweakedKeypair, TweakedPublicKey};
+use crate::crypto::sighash::{EcdsaSighashType, TapSighash, TapSighashType};
+use crate::crypto::taproot::Signature;
+
+/// A Schnorr adaptor signature, a BIP 340 signature encrypted to an adaptor point.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AdaptorSignature {
+    /// The final signature nonce, the sum of the signer's nonce and the adaptor point.
+    nonce: secp256k1::PublicKey,
+    /// The signature scalar without the adaptor secret.
+    s: ModN,
+}
+
+impl AdaptorSignature {
+    /// Creates an adaptor signature for `sighash` encrypted to `adaptor_point`.
+    ///
+    /// Like in BIP 340 the nonce is derived from the secret key, the message and `aux_rand`, which
+    /// should be fresh randomness. The adaptor point is committed to as well.
+    pub fn sign<C: Verification>(
+        secp: &Secp256k1<C>,
+        sighash: &TapSighash,
+        keypair: &TweakedKeypair,
+        adaptor_point: &CompressedPublicKey,
+        aux_rand: &[u8; 32],
+    ) -> AdaptorSignature {
+        let keypair = keypair.to_inner();
+        let (public_key, parity) = keypair.x_only_public_key();
+        let d = ModN(Some(keypair.secret_key())).negate_if(parity == Parity::Odd);
+        let msg = sighash.to_byte_array();
+
+        // This tag is specific to this crate, see the module documentation.
+        let k = derive_nonce(
+            "AdaptorSignature/nonce",
+            d,
+            aux_rand,
+            &[&adaptor_point.to_bytes(), &public_key.serialize(), &msg],
+        );
+
+        let nonce = point_add(base_mul(secp, k), Some(adaptor_point.0))
+            .expect("negligible probability of an infinite nonce");
+        // The final nonce has an even y coordinate, negate the signer's part if `nonce` has not.
+        let k = k.negate_if(!has_even_y(&nonce));
+        let e = challenge(&nonce, &public_key.serialize(), &msg);
+
+        AdaptorSignature { nonce, s: k.add(e.mul(d)) }
+    }
+
+    /// Verifies that this is an adaptor signature of `sighash` by `output_key` encrypted to
+    /// `adaptor_point`.
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        sighash: &TapSighash,
+        output_key: &TweakedPublicKey,
+        adaptor_point: &CompressedPublicKey,
+    ) -> Result<(), Error> {
+        let public_key = output_key.to_inner();
+        let e = challenge(&self.nonce, &public_key.serialize(), &sighash.to_byte_array());
+        let public_key = secp256k1::PublicKey::from_x_only_public_key(public_key, Parity::Even);
+
+        let nonce = point_add(Some(self.nonce), Some(adaptor_point.0.negate(secp)));
+        let nonce = if has_even_y(&self.nonce) { nonce } else { nonce.map(|r| r.negate(secp)) };
+        let expected = point_add(nonce, point_mul(secp, Some(public_key), e));
+        if base_mul(secp, self.s) != expected {
+            return Err(Error::VerificationFailed);
+        }
+        Ok(())
+    }
+
+    /// Decrypts this adaptor signature into a taproot signature using the adaptor secret.
+    ///
+    /// The result is only valid if `adaptor_secret` corresponds to the adaptor point this
+    /// signature was encrypted to.
+    pub fn decrypt(&self, adaptor_secret: &SecretKey, sighash_type: TapSighashType) -> Signature {
+        let t = ModN(Some(*adaptor_secret)).negate_if(!has_even_y(&self.nonce));
+        let s = self.s.add(t);
+
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(&self.nonce.x_only_public_key().0.serialize());
+        bytes[32..].copy_from_slice(&s.to_bytes());
+        let signature = schnorr::Signature::from_slice(&bytes).expect("64 bytes");
+        Signature { signature, sighash_type }
+    }
+
+    /// Recovers the adaptor secret from this adaptor signature and the decrypted `signature`.
+    pub fn recover_secret<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        signature: &Signature,
+        adaptor_point: &CompressedPublicKey,
+    ) -> Result<SecretKey, Error> {
+        let s = signature.signature.as_ref()[32..].try_into().expect("32 bytes");
+        let s = ModN::from_bytes(s).ok_or(Error::SecretMismatch)?;
+        let t = s.add(self.s.negate()).negate_if(!has_even_y(&self.nonce));
+
+        match t.0 {
+            Some(t) if base_mul(secp, ModN(Some(t))) == Some(adaptor_point.0) => Ok(t),
+            _ => Err(Error::SecretMismatch),
+        }
+    }
+
+    /// Serializes the adaptor signature as the compressed nonce followed by the scalar.
+    pub fn serialize(&self) -> [u8; 65] {
+        let mut bytes = [0; 65];
+        bytes[..33].copy_from_slice(&self.nonce.serialize());
+        bytes[33..].copy_from_slice(&self.s.to_bytes());
+        bytes
+    }
+
+    /// Deserializes an adaptor signature.
+    pub fn from_slice(data: &[u8]) -> Result<AdaptorSignature, Error> {
+        if data.len() != 65 {
+            return Err(Error::InvalidEncoding);
+        }
+        let nonce =
+            secp256k1::PublicKey::from_slice(&data[..33]).map_err(|_| Error::InvalidEncoding)?;
+        let s = data[33..].try_into().expect("32 bytes");
+        let s = ModN::from_bytes(s).ok_or(Error::InvalidEncoding)?;
+        Ok(AdaptorSignature { nonce, s })
+    }
+}
+
+/// An ECDSA adaptor signature, an ECDSA signature encrypted to an encryption key.
+///
+/// This is the adaptor signature scheme of the DLC specification and `secp256k1-zkp`, see the
+/// module documentation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct EcdsaAdaptorSignature {
+    /// The final signature nonce `R = k * Y`.
+    nonce: secp256k1::PublicKey,
+    /// The signer's nonce `R_a = k * G`.
+    signer_nonce: secp256k1::PublicKey,
+    /// The encrypted signature scalar, never zero.
+    s: ModN,
+    /// The challenge of the proof that `R` and `R_a` have the same discrete logarithm.
+    proof_challenge: ModN,
+    /// The response of the discrete logarithm equality proof.
+    proof_response: ModN,
+}
+
+impl EcdsaAdaptorSignature {
+    /// Creates an adaptor signature for `msg` encrypted to `encryption_key`.
+    ///
+    /// The nonce is derived from the secret key, the message, the encryption key and `aux_rand`,
+    /// which should be fresh randomness.
+    pub fn encrypt<C: Verification>(
+        secp: &Secp256k1<C>,
+        msg: &Message,
+        secret_key: &SecretKey,
+        encryption_key: &CompressedPublicKey,
+        aux_rand: &[u8; 32],
+    ) -> EcdsaAdaptorSignature {
+        let x = ModN(Some(*secret_key));
+        let public_key = base_mul(secp, x).expect("non-zero secret key").serialize();
+        let msg = msg.as_ref();
+
+        // These tags are specific to this crate, see the module documentation.
+        let k = derive_nonce(
+            "EcdsaAdaptorSignature/nonce",
+            x,
+            aux_rand,
+            &[&encryption_key.to_bytes(), &public_key, msg],
+        );
+        let signer_nonce = base_mul(secp, k).expect("non-zero nonce");
+        let nonce = point_mul(secp, Some(encryption_key.0), k).expect("non-zero nonce");
+
+        let r = nonce_x(&nonce);
+        let s = k.invert().expect("non-zero nonce").mul(message_scalar(msg).add(r.mul(x)));
+        assert!(s != ModN::ZERO, "negligible probability of a zero signature scalar");
+
+        // Prove that `nonce` and `signer_nonce` share the discrete logarithm `k`.
+        let a = derive_nonce(
+            "EcdsaAdaptorSignature/dleq",
+            k,
+            aux_rand,
+            &[&signer_nonce.serialize(), &encryption_key.to_bytes(), &nonce.serialize()],
+        );
+        let proof_challenge = dleq_challenge(
+            &signer_nonce,
+            &encryption_key.0,
+            &nonce,
+            &base_mul(secp, a).expect("non-zero nonce"),
+            &point_mul(secp, Some(encryption_key.0), a).expect("non-zero nonce"),
+        );
+        let proof_response = a.add(proof_challenge.mul(k));
+
+        EcdsaAdaptorSignature { nonce, signer_nonce, s, proof_challenge, proof_response }
+    }
+
+    /// Verifies that this is an adaptor signature of `msg` by `public_key` encrypted to
+    /// `encryption_key`.
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        msg: &Message,
+        public_key: &CompressedPublicKey,
+        encryption_key: &CompressedPublicKey,
+    ) -> Result<(), Error> {
+        // Recompute the proof announcements `A_G = s * G - e * R_a` and `A_Y = s * Y - e * R`.
+        let e = self.proof_challenge.negate();
+        let announcement_g = point_add(
+            base_mul(secp, self.proof_response),
+            point_mul(secp, Some(self.signer_nonce), e),
+        );
+        let announcement_y = point_add(
+            point_mul(secp, Some(encryption_key.0), self.proof_response),
+            point_mul(secp, Some(self.nonce), e),
+        );
+        match (announcement_g, announcement_y) {
+            (Some(announcement_g), Some(announcement_y))
+                if dleq_challenge(
+                    &self.signer_nonce,
+                    &encryption_key.0,
+                    &self.nonce,
+                    &announcement_g,
+                    &announcement_y,
+                ) == self.proof_challenge => {}
+            _ => return Err(Error::VerificationFailed),
+        }
+
+        // Check `R_a = s^-1 * (m * G + r * X)`.
+        let s_inv = self.s.invert().expect("non-zero signature scalar");
+        let expected = point_add(
+            base_mul(secp, message_scalar(msg.as_ref()).mul(s_inv)),
+            point_mul(secp, Some(public_key.0), nonce_x(&self.nonce).mul(s_inv)),
+        );
+        if expected != Some(self.signer_nonce) {
+            return Err(Error::VerificationFailed);
+        }
+        Ok(())
+    }
+
+    /// Decrypts this adaptor signature into a low-s ECDSA signature using the decryption key.
+    ///
+    /// The result is only valid if `decryption_key` corresponds to the encryption key this
+    /// signature was encrypted to.
+    pub fn decrypt(
+        &self,
+        decryption_key: &SecretKey,
+        sighash_type: EcdsaSighashType,
+    ) -> ecdsa::Signature {
+        let y_inv = ModN(Some(*decryption_key)).invert().expect("non-zero secret key");
+        let s = self.s.mul(y_inv);
+
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(&nonce_x(&self.nonce).to_bytes());
+        bytes[32..].copy_from_slice(&s.to_bytes());
+        let mut signature =
+            secp256k1::ecdsa::Signature::from_compact(&bytes).expect("valid scalars");
+        signature.normalize_s();
+        ecdsa::Signature { signature, sighash_type }
+    }
+
+    /// Recovers the decryption key from this adaptor signature and the decrypted `signature`.
+    ///
+    /// The signature may be high-s, for example if a third party negated it.
+    pub fn recover_decryption_key<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        signature: &ecdsa::Signature,
+        encryption_key: &CompressedPublicKey,
+    ) -> Result<SecretKey, Error> {
+        let bytes = signature.signature.serialize_compact();
+        if bytes[..32] != nonce_x(&self.nonce).to_bytes() {
+            return Err(Error::SecretMismatch);
+        }
+        let s = ModN::from_bytes(bytes[32..].try_into().expect("32 bytes"));
+        let y = match s.and_then(ModN::invert) {
+            Some(s_inv) => s_inv.mul(self.s),
+            None => return Err(Error::SecretMismatch),
+        };
+
+        // Negating the signature negates the recovered key, try both.
+        let point = base_mul(secp, y);
+        let y = if point == Some(encryption_key.0) {
+            y
+        } else if point.map(|p| p.negate(secp)) == Some(encryption_key.0) {
+            y.negate()
+        } else {
+            return Err(Error::SecretMismatch);
+        };
+        Ok(y.0.expect("non-zero key"))
+    }
+
+    /// Serializes the adaptor signature as `R || R_a || s || e || s_proof`, the 162 byte encoding
+    /// of the DLC specification.
+    pub fn serialize(&self) -> [u8; 162] {
+        let mut bytes = [0; 162];
+        bytes[..33].copy_from_slice(&self.nonce.serialize());
+        bytes[33..66].copy_from_slice(&self.signer_nonce.serialize());
+        bytes[66..98].copy_from_slice(&self.s.to_bytes());
+        bytes[98..130].copy_from_slice(&self.proof_challenge.to_bytes());
+        bytes[130..].copy_from_slice(&self.proof_response.to_bytes());
+        bytes
+    }
+
+    /// Deserializes an adaptor signature.
+    pub fn from_slice(data: &[u8]) -> Result<EcdsaAdaptorSignature, Error> {
+        if data.len() != 162 {
+            return Err(Error::InvalidEncoding);
+        }
+        let point = |data: &[u8]| {
+            secp256k1::PublicKey::from_slice(data).map_err(|_| Error::InvalidEncoding)
+        };
+        let scalar = |data: &[u8]| {
+            ModN::from_bytes(data.try_into().expect("32 bytes")).ok_or(Error::InvalidEncoding)
+        };
+        let s = scalar(&data[66..98])?;
+        if s == ModN::ZERO {
+            return Err(Error::InvalidEncoding);
+        }
+        Ok(EcdsaAdaptorSignature {
+            nonce: point(&data[..33])?,
+            signer_nonce: point(&data[33..66])?,
+            s,
+            proof_challenge: scalar(&data[98..130])?,
+            proof_response: scalar(&data[130..])?,
+        })
+    }
+}
+
+/// Returns the x coordinate of `nonce` reduced modulo the curve order.
+fn nonce_x(nonce: &secp256k1::PublicKey) -> ModN {
+    ModN::from_bytes_reduced(nonce.serialize()[1..].try_into().expect("32 bytes"))
+}
+
+/// Returns the message hash reduced modulo the curve order.
+fn message_scalar(msg: &[u8; 32]) -> ModN { ModN::from_bytes_reduced(*msg) }
+
+/// Computes the Fiat-Shamir challenge of the proof that `nonce = k * Y` and `signer_nonce = k * G`
+/// for the same `k`, as defined by the DLC specification.
+fn dleq_challenge(
+    signer_nonce: &secp256k1::PublicKey,
+    encryption_key: &secp256k1::PublicKey,
+    nonce: &secp256k1::PublicKey,
+    announcement_g: &secp256k1::PublicKey,
+    announcement_y: &secp256k1::PublicKey,
+) -> ModN {
+    ModN::from_bytes_reduced(tagged_hash(
+        "DLEQ",
+        &[
+            &signer_nonce.serialize(),
+            &encryption_key.serialize(),
+            &nonce.serialize(),
+            &announcement_g.serialize(),
+            &announcement_y.serialize(),
+        ],
+    ))
+}
+
+/// Adaptor signature error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Invalid adaptor signature encoding.
+    InvalidEncoding,
+    /// The adaptor signature is not valid.
+    VerificationFailed,
+    /// The signature is not the decryption of the adaptor signature for the adaptor point.
+    SecretMismatch,
+}
+
+internals::impl_from_infallible!(Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            InvalidEncoding => f.write_str("invalid adaptor signature encoding"),
+            VerificationFailed => f.write_str("adaptor signature verification failed"),
+            SecretMismatch => f.write_str(
+                "signature does not decrypt the adaptor signature for the adaptor point",
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            InvalidEncoding | VerificationFailed | SecretMismatch => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::Keypair;
+
+    use super::*;
+    use crate::crypto::key::TapTweak;
+
+    #[test]
+    fn adaptor_signature_roundtrip() {
+        let secp = Secp256k1::new();
+        let sighash = TapSighash::from_byte_array([0x42; 32]);
+        let adaptor_secret = SecretKey::from_slice(&[0x07; 32]).unwrap();
+        let adaptor_point = CompressedPublicKey(adaptor_secret.public_key(&secp));
+
+        for seed in 1..=4u8 {
+            let keypair = Keypair::from_seckey_slice(&secp, &[seed; 32]).unwrap();
+            let keypair = keypair.tap_tweak(&secp, None);
+            let output_key = TweakedPublicKey::from_keypair(keypair);
+
+            let adaptor_sig =
+                AdaptorSignature::sign(&secp, &sighash, &keypair, &adaptor_point, &[seed; 32]);
+            adaptor_sig.verify(&secp, &sighash, &output_key, &adaptor_point).unwrap();
+            let serialized = adaptor_sig.serialize();
+            assert_eq!(AdaptorSignature::from_slice(&serialized), Ok(adaptor_sig));
+
+            // The adaptor signature is not valid for any other adaptor point.
+            let other_point = CompressedPublicKey(keypair.to_inner().public_key());
+            assert_eq!(
+                adaptor_sig.verify(&secp, &sighash, &output_key, &other_point),
+                Err(Error::VerificationFailed)
+            );
+
+            let sig = adaptor_sig.decrypt(&adaptor_secret, TapSighashType::Default);
+            let msg = Message::from_digest(sighash.to_byte_array());
+            secp.verify_schnorr(&sig.signature, &msg, &output_key.to_inner()).unwrap();
+
+            assert_eq!(adaptor_sig.recover_secret(&secp, &sig, &adaptor_point), Ok(adaptor_secret));
+            assert_eq!(
+                adaptor_sig.recover_secret(&secp, &sig, &other_point),
+                Err(Error::SecretMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn ecdsa_adaptor_signature_roundtrip() {
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([0x42; 32]);
+        let decryption_key = SecretKey::from_slice(&[0x07; 32]).unwrap();
+        let encryption_key = CompressedPublicKey(decryption_key.public_key(&secp));
+
+        for seed in 1..=4u8 {
+            let secret_key = SecretKey::from_slice(&[seed; 32]).unwrap();
+            let public_key = CompressedPublicKey(secret_key.public_key(&secp));
+
+            let adaptor_sig = EcdsaAdaptorSignature::encrypt(
+                &secp,
+                &msg,
+                &secret_key,
+                &encryption_key,
+                &[seed; 32],
+            );
+            adaptor_sig.verify(&secp, &msg, &public_key, &encryption_key).unwrap();
+            let serialized = adaptor_sig.serialize();
+            assert_eq!(EcdsaAdaptorSignature::from_slice(&serialized), Ok(adaptor_sig));
+
+            // The adaptor signature is not valid for any other encryption key.
+            assert_eq!(
+                adaptor_sig.verify(&secp, &msg, &public_key, &public_key),
+                Err(Error::VerificationFailed)
+            );
+
+            let sig = adaptor_sig.decrypt(&decryption_key, EcdsaSighashType::All);
+            secp.verify_ecdsa(&msg, &sig.signature, &public_key.0).unwrap();
+
+            assert_eq!(
+                adaptor_sig.recover_decryption_key(&secp, &sig, &encryption_key),
+                Ok(decryption_key)
+            );
+            assert_eq!(
+                adaptor_sig.recover_decryption_key(&secp, &sig, &public_key),
+                Err(Error::SecretMismatch)
+            );
+        }
+    }
+
+    // The ECDSA adaptor signature test vectors of the DLC specification.
+    #[test]
+    fn ecdsa_adaptor_signature_vectors() {
+        use hex::test_hex_unwrap as hex;
+
+        let secp = Secp256k1::verification_only();
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../../tests/data/ecdsa_adaptor_vectors.json"))
+                .unwrap();
+        let field = |v: &serde_json::Value, name: &str| hex!(v[name].as_str().unwrap());
+        let key = |v: &serde_json::Value, name: &str| {
+            CompressedPublicKey::from_slice(&field(v, name)).unwrap()
+        };
+        let signature = |v: &serde_json::Value| ecdsa::Signature {
+            signature: secp256k1::ecdsa::Signature::from_compact(&field(v, "signature")).unwrap(),
+            sighash_type: EcdsaSighashType::All,
+        };
+
+        for v in vectors.as_array().unwrap() {
+            let valid = v["error"].is_null();
+            let adaptor_sig = EcdsaAdaptorSignature::from_slice(&field(v, "adaptor_sig"));
+
+            match v["kind"].as_str().unwrap() {
+                "verification" => {
+                    let adaptor_sig = adaptor_sig.unwrap();
+                    let msg = Message::from_digest_slice(&field(v, "message_hash")).unwrap();
+                    let public_key = key(v, "public_signing_key");
+                    let encryption_key = key(v, "encryption_key");
+                    let result = adaptor_sig.verify(&secp, &msg, &public_key, &encryption_key);
+                    assert_eq!(result.is_ok(), valid, "{}", v);
+                    if !valid {
+                        continue;
+                    }
+
+                    let decryption_key =
+                        SecretKey::from_slice(&field(v, "decryption_key")).unwrap();
+                    let sig = adaptor_sig.decrypt(&decryption_key, EcdsaSighashType::All);
+                    assert_eq!(sig, signature(v));
+                    assert_eq!(
+                        adaptor_sig.recover_decryption_key(&secp, &sig, &encryption_key),
+                        Ok(decryption_key)
+                    );
+                }
+                "recovery" => {
+                    let recovered = adaptor_sig.unwrap().recover_decryption_key(
+                        &secp,
+                        &signature(v),
+                        &key(v, "encryption_key"),
+                    );
+                    if valid {
+                        let expected = SecretKey::from_slice(&field(v, "decryption_key")).unwrap();
+                        assert_eq!(recovered, Ok(expected));
+                    } else {
+                        assert_eq!(recovered, Err(Error::SecretMismatch));
+                    }
+                }
+                "serialization" => {
+                    assert_eq!(adaptor_sig.is_ok(), valid, "{}", v);
+                    if let Ok(adaptor_sig) = adaptor_sig {
+                        assert_eq!(adaptor_sig.serialize().to_vec(), field(v, "adaptor_sig"));
+                    }
+                }
+                kind => panic!("unknown test vector kind {}", kind),
+            }
+        }
+    }
+}
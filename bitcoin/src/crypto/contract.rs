@@ -0,0 +1,229 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Pay-to-contract and sign-to-contract commitments.
+//!
+//! Both commit to arbitrary data without revealing it, which is used by timestamping and other
+//! commitment protocols:
+//!
+//! * Pay-to-contract commits to data in a public key, `Q = P + H(P || data) * G`, similar to how
+//!   [`TapTweak`] commits to a script tree. Funds sent to `Q` are spendable with the tweaked
+//!   secret key.
+//! * Sign-to-contract commits to data in the nonce of a BIP 340 signature,
+//!   `R' = R + H(R || data) * G`. The signature stays an ordinary valid signature, revealing the
+//!   original nonce `R` opens the commitment.
+//!
+//! [`TapTweak`]: crate::key::TapTweak
+
+use core::fmt;
+
+use hashes::{sha256t_hash_newtype, Hash, HashEngine};
+use secp256k1::{
+    schnorr, Keypair, Message, Parity, PublicKey, Scalar, Secp256k1, SecretKey, Verification,
+};
+
+use crate::crypto::ec::{base_mul, challenge, derive_nonce, has_even_y, ModN};
+
+sha256t_hash_newtype! {
+    pub struct PayToContractTag = hash_str("PayToContract");
+
+    /// Tagged hash with tag \"PayToContract\".
+    ///
+    /// This hash type is used to tweak a public key with contract data.
+    #[hash_newtype(forward)]
+    pub struct PayToContractHash(_);
+
+    pub struct SignToContractTag = hash_str("SignToContract");
+
+    /// Tagged hash with tag \"SignToContract\".
+    ///
+    /// This hash type is used to tweak a signature nonce with contract data.
+    #[hash_newtype(forward)]
+    pub struct SignToContractHash(_);
+}
+
+impl PayToContractHash {
+    /// Computes `H_PayToContract(P || data)` where `P` is the compressed public key.
+    pub fn from_key_and_data(key: &PublicKey, data: &[u8]) -> PayToContractHash {
+        let mut eng = PayToContractHash::engine();
+        eng.input(&key.serialize());
+        eng.input(data);
+        PayToContractHash::from_engine(eng)
+    }
+
+    /// Converts a `PayToContractHash` into a `Scalar` ready for use with key tweaking API.
+    pub fn to_scalar(self) -> Scalar {
+        // This is statistically extremely unlikely to panic.
+        Scalar::from_be_bytes(self.to_byte_array()).expect("hash value greater than curve order")
+    }
+}
+
+impl SignToContractHash {
+    /// Computes `H_SignToContract(R || data)` where `R` is the compressed original nonce.
+    pub fn from_nonce_and_data(nonce: &PublicKey, data: &[u8]) -> SignToContractHash {
+        let mut eng = SignToContractHash::engine();
+        eng.input(&nonce.serialize());
+        eng.input(data);
+        SignToContractHash::from_engine(eng)
+    }
+
+    /// Converts a `SignToContractHash` into a `Scalar` ready for use with key tweaking API.
+    pub fn to_scalar(self) -> Scalar {
+        // This is statistically extremely unlikely to panic.
+        Scalar::from_be_bytes(self.to_byte_array()).expect("hash value greater than curve order")
+    }
+}
+
+/// Commits to `data` in `key`, returning `P + H(P || data) * G`.
+pub fn pay_to_contract_tweak<C: Verification>(
+    secp: &Secp256k1<C>,
+    key: &PublicKey,
+    data: &[u8],
+) -> PublicKey {
+    let tweak = PayToContractHash::from_key_and_data(key, data).to_scalar();
+    key.add_exp_tweak(secp, &tweak).expect("Pay-to-contract tweak failed")
+}
+
+/// Tweaks `secret_key` to the secret key of [`pay_to_contract_tweak`] of its public key.
+pub fn pay_to_contract_tweak_secret<C: secp256k1::Signing>(
+    secp: &Secp256k1<C>,
+    secret_key: &SecretKey,
+    data: &[u8],
+) -> SecretKey {
+    let tweak = PayToContractHash::from_key_and_data(&secret_key.public_key(secp), data);
+    secret_key.add_tweak(&tweak.to_scalar()).expect("Pay-to-contract tweak failed")
+}
+
+/// Checks that `tweaked` is the pay-to-contract commitment to `data` in `key`.
+pub fn verify_pay_to_contract<C: Verification>(
+    secp: &Secp256k1<C>,
+    key: &PublicKey,
+    tweaked: &PublicKey,
+    data: &[u8],
+) -> bool {
+    pay_to_contract_tweak(secp, key, data) == *tweaked
+}
+
+/// Creates a BIP 340 signature of `msg` whose nonce commits to `data`.
+///
+/// Returns the signature along with the opening required to prove the commitment. Like in
+/// BIP 340 the nonce is derived from the secret key, the message and `aux_rand`, which should be
+/// fresh randomness. The data is committed to as well.
+pub fn sign_schnorr_to_contract<C: Verification>(
+    secp: &Secp256k1<C>,
+    msg: &Message,
+    keypair: &Keypair,
+    data: &[u8],
+    aux_rand: &[u8; 32],
+) -> (schnorr::Signature, SignToContractOpening) {
+    let (public_key, parity) = keypair.x_only_public_key();
+    let d = ModN(Some(keypair.secret_key())).negate_if(parity == Parity::Odd);
+    let msg = msg.as_ref();
+    let data_hash = hashes::sha256::Hash::hash(data);
+
+    let k = derive_nonce(
+        "SignToContract/nonce",
+        d,
+        aux_rand,
+        &[&public_key.serialize(), msg, data_hash.as_ref()],
+    );
+    let original_nonce = base_mul(secp, k).expect("non-zero nonce");
+    let tweak = SignToContractHash::from_nonce_and_data(&original_nonce, data).to_scalar();
+    let k = k.add(ModN::from_bytes(tweak.to_be_bytes()).expect("valid scalar"));
+    let nonce = base_mul(secp, k).expect("negligible probability of a zero nonce");
+    let k = k.negate_if(!has_even_y(&nonce));
+
+    let e = challenge(&nonce, &public_key.serialize(), msg);
+    let mut bytes = [0; 64];
+    bytes[..32].copy_from_slice(&nonce.x_only_public_key().0.serialize());
+    bytes[32..].copy_from_slice(&k.add(e.mul(d)).to_bytes());
+    let signature = schnorr::Signature::from_slice(&bytes).expect("64 bytes");
+    (signature, SignToContractOpening { original_nonce })
+}
+
+/// The opening of a sign-to-contract commitment: the nonce before committing to the data.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SignToContractOpening {
+    original_nonce: PublicKey,
+}
+
+impl SignToContractOpening {
+    /// Creates an opening from the original nonce.
+    pub fn new(original_nonce: PublicKey) -> Self { SignToContractOpening { original_nonce } }
+
+    /// Returns the nonce before committing to the data.
+    pub fn original_nonce(&self) -> PublicKey { self.original_nonce }
+
+    /// Checks that the nonce of `signature` commits to `data`.
+    ///
+    /// This does not verify the signature itself.
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        signature: &schnorr::Signature,
+        data: &[u8],
+    ) -> Result<(), CommitmentError> {
+        let tweak = SignToContractHash::from_nonce_and_data(&self.original_nonce, data);
+        let nonce = self
+            .original_nonce
+            .add_exp_tweak(secp, &tweak.to_scalar())
+            .map_err(|_| CommitmentError)?;
+        if nonce.x_only_public_key().0.serialize() != signature.as_ref()[..32] {
+            return Err(CommitmentError);
+        }
+        Ok(())
+    }
+}
+
+/// The signature nonce does not commit to the data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CommitmentError;
+
+impl fmt::Display for CommitmentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("signature nonce does not commit to the data")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CommitmentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pay_to_contract() {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let key = secret_key.public_key(&secp);
+
+        let tweaked = pay_to_contract_tweak(&secp, &key, b"contract");
+        assert_ne!(tweaked, key);
+        assert!(verify_pay_to_contract(&secp, &key, &tweaked, b"contract"));
+        assert!(!verify_pay_to_contract(&secp, &key, &tweaked, b"other contract"));
+
+        let tweaked_secret = pay_to_contract_tweak_secret(&secp, &secret_key, b"contract");
+        assert_eq!(tweaked_secret.public_key(&secp), tweaked);
+    }
+
+    #[test]
+    fn sign_to_contract() {
+        let secp = Secp256k1::new();
+        let msg = Message::from_digest([0x42; 32]);
+
+        for seed in 1..=4u8 {
+            let keypair = Keypair::from_seckey_slice(&secp, &[seed; 32]).unwrap();
+            let (sig, opening) =
+                sign_schnorr_to_contract(&secp, &msg, &keypair, b"timestamp", &[seed; 32]);
+
+            secp.verify_schnorr(&sig, &msg, &keypair.x_only_public_key().0).unwrap();
+            assert_eq!(opening.verify(&secp, &sig, b"timestamp"), Ok(()));
+            assert_eq!(opening.verify(&secp, &sig, b"other"), Err(CommitmentError));
+            let opening = SignToContractOpening::new(keypair.public_key());
+            assert_eq!(opening.verify(&secp, &sig, b"timestamp"), Err(CommitmentError));
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Elliptic curve arithmetic helpers.
+//!
+//! Scalars modulo the curve order and curve points built on the public secp256k1 API, with
+//! explicit representations of zero and the point at infinity. Used to implement signature
+//! schemes that libsecp256k1 does not provide.
+
+use core::fmt;
+
+use hashes::{sha256, Hash, HashEngine};
+use hex::DisplayHex;
+use secp256k1::{constants, Parity, Secp256k1, SecretKey, Verification};
+
+/// Computes the BIP 340 tagged hash of the concatenation of `data`.
+pub(crate) fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag_hash.as_ref());
+    engine.input(tag_hash.as_ref());
+    for data in data {
+        engine.input(data);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// An integer modulo the curve order, `None` represents zero.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) struct ModN(pub(crate) Option<SecretKey>);
+
+impl ModN {
+    pub(crate) const ZERO: ModN = ModN(None);
+
+    pub(crate) fn one() -> ModN {
+        ModN(Some(SecretKey::from_slice(&constants::ONE).expect("one is valid")))
+    }
+
+    /// Interprets `bytes` as a big endian integer, fails if it is not less than the curve order.
+    pub(crate) fn from_bytes(bytes: [u8; 32]) -> Option<ModN> {
+        if bytes == constants::ZERO {
+            Some(ModN::ZERO)
+        } else {
+            SecretKey::from_slice(&bytes).ok().map(|k| ModN(Some(k)))
+        }
+    }
+
+    /// Interprets `bytes` as a big endian integer and reduces it modulo the curve order.
+    pub(crate) fn from_bytes_reduced(mut bytes: [u8; 32]) -> ModN {
+        // Since 2^256 < 2n a single subtraction of n suffices.
+        if bytes >= constants::CURVE_ORDER {
+            let mut borrow = 0u16;
+            for (b, n) in bytes.iter_mut().zip(constants::CURVE_ORDER.iter()).rev() {
+                let diff = u16::from(*b).wrapping_sub(u16::from(*n)).wrapping_sub(borrow);
+                *b = diff as u8;
+                borrow = (diff >> 8) & 1;
+            }
+        }
+        ModN::from_bytes(bytes).expect("reduced")
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 32] {
+        self.0.map_or(constants::ZERO, |k| k.secret_bytes())
+    }
+
+    pub(crate) fn add(self, other: ModN) -> ModN {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => ModN(a.add_tweak(&b.into()).ok()),
+            (a, None) | (None, a) => ModN(a),
+        }
+    }
+
+    pub(crate) fn mul(self, other: ModN) -> ModN {
+        match (self.0, other.0) {
+            // The curve order is prime so the product of non-zero values is non-zero.
+            (Some(a), Some(b)) => ModN(Some(a.mul_tweak(&b.into()).expect("non-zero product"))),
+            _ => ModN::ZERO,
+        }
+    }
+
+    pub(crate) fn negate(self) -> ModN { ModN(self.0.map(SecretKey::negate)) }
+
+    /// Returns the multiplicative inverse, `None` if `self` is zero.
+    pub(crate) fn invert(self) -> Option<ModN> {
+        self.0?;
+        // The curve order is prime so the inverse is `self^(n - 2)`.
+        let mut exponent = constants::CURVE_ORDER;
+        exponent[31] -= 2;
+        let mut inverse = ModN::one();
+        for byte in exponent {
+            for i in (0..8).rev() {
+                inverse = inverse.mul(inverse);
+                if byte >> i & 1 == 1 {
+                    inverse = inverse.mul(self);
+                }
+            }
+        }
+        Some(inverse)
+    }
+
+    /// Returns `self` if `negate` is false, `-self` otherwise.
+    pub(crate) fn negate_if(self, negate: bool) -> ModN {
+        if negate {
+            self.negate()
+        } else {
+            self
+        }
+    }
+}
+
+impl fmt::Debug for ModN {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.to_bytes().as_hex(), f)
+    }
+}
+
+/// Returns the generator point.
+pub(crate) fn generator() -> secp256k1::PublicKey {
+    let mut bytes = [0x04; 65];
+    bytes[1..33].copy_from_slice(&constants::GENERATOR_X);
+    bytes[33..].copy_from_slice(&constants::GENERATOR_Y);
+    secp256k1::PublicKey::from_slice(&bytes).expect("valid generator")
+}
+
+// Curve points, `None` represents the point at infinity.
+
+pub(crate) fn point_add(
+    a: Option<secp256k1::PublicKey>,
+    b: Option<secp256k1::PublicKey>,
+) -> Option<secp256k1::PublicKey> {
+    match (a, b) {
+        // Combining only fails if the sum is the point at infinity.
+        (Some(a), Some(b)) => a.combine(&b).ok(),
+        (a, None) | (None, a) => a,
+    }
+}
+
+pub(crate) fn point_mul<C: Verification>(
+    secp: &Secp256k1<C>,
+    point: Option<secp256k1::PublicKey>,
+    scalar: ModN,
+) -> Option<secp256k1::PublicKey> {
+    match (point, scalar.0) {
+        (Some(point), Some(scalar)) =>
+            Some(point.mul_tweak(secp, &scalar.into()).expect("non-zero scalar")),
+        _ => None,
+    }
+}
+
+pub(crate) fn base_mul<C: Verification>(
+    secp: &Secp256k1<C>,
+    scalar: ModN,
+) -> Option<secp256k1::PublicKey> {
+    point_mul(secp, Some(generator()), scalar)
+}
+
+pub(crate) fn has_even_y(point: &secp256k1::PublicKey) -> bool {
+    point.x_only_public_key().1 == Parity::Even
+}
+
+/// Derives a BIP 340 style nonce by hashing `secret`, masked with the hash of `aux_rand`,
+/// followed by `data` using `tag`.
+///
+/// Each scheme must use its own tag so that nonces are never shared with plain BIP 340 signing.
+pub(crate) fn derive_nonce(tag: &str, secret: ModN, aux_rand: &[u8; 32], data: &[&[u8]]) -> ModN {
+    let mut masked = tagged_hash("BIP0340/aux", &[aux_rand]);
+    masked.iter_mut().zip(&secret.to_bytes()).for_each(|(m, s)| *m ^= s);
+
+    let mut input = vec![&masked[..]];
+    input.extend_from_slice(data);
+    let nonce = ModN::from_bytes_reduced(tagged_hash(tag, &input));
+    assert!(nonce != ModN::ZERO, "negligible probability of a zero nonce");
+    nonce
+}
+
+/// Computes the BIP 340 challenge for `nonce`, the x-only `public_key` and `msg`.
+pub(crate) fn challenge(
+    nonce: &secp256k1::PublicKey,
+    public_key: &[u8; 32],
+    msg: &[u8; 32],
+) -> ModN {
+    let nonce = nonce.x_only_public_key().0.serialize();
+    ModN::from_bytes_reduced(tagged_hash("BIP0340/challenge", &[&nonce, public_key, msg]))
+}
@@ -77,6 +77,15 @@ impl PublicKey {
         Ok(key.p2wpkh_script_code())
     }
 
+    /// Converts this key into a [`CompressedPublicKey`], as required for segwit.
+    ///
+    /// # Errors
+    ///
+    /// If the key is uncompressed.
+    pub fn try_into_compressed(self) -> Result<CompressedPublicKey, UncompressedPublicKeyError> {
+        CompressedPublicKey::try_from(self)
+    }
+
     /// Write the public key into a writer
     pub fn write_into<W: Write + ?Sized>(&self, writer: &mut W) -> Result<(), io::Error> {
         self.with_serialized(|bytes| writer.write_all(bytes))
@@ -395,6 +404,75 @@ impl From<&CompressedPublicKey> for WPubkeyHash {
     fn from(key: &CompressedPublicKey) -> Self { key.wpubkey_hash() }
 }
 
+/// A Bitcoin ECDSA public key for legacy (pre-segwit) contexts, may be uncompressed.
+///
+/// This is a thin wrapper around [`PublicKey`] that documents in the type system that
+/// uncompressed keys are acceptable, e.g. for P2PKH or legacy multisig. Segwit contexts use
+/// [`CompressedPublicKey`], convert with [`LegacyPublicKey::try_into_compressed`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LegacyPublicKey(pub PublicKey);
+
+impl LegacyPublicKey {
+    /// Returns true if this key is serialized in compressed format.
+    pub fn is_compressed(&self) -> bool { self.0.compressed }
+
+    /// Returns bitcoin 160-bit hash of the public key
+    pub fn pubkey_hash(&self) -> PubkeyHash { self.0.pubkey_hash() }
+
+    /// Serialize the public key to bytes
+    pub fn to_bytes(self) -> Vec<u8> { self.0.to_bytes() }
+
+    /// Deserialize a public key from a slice
+    pub fn from_slice(data: &[u8]) -> Result<Self, FromSliceError> {
+        PublicKey::from_slice(data).map(LegacyPublicKey)
+    }
+
+    /// Converts this key into a [`CompressedPublicKey`], as required for segwit.
+    ///
+    /// # Errors
+    ///
+    /// If the key is uncompressed.
+    pub fn try_into_compressed(self) -> Result<CompressedPublicKey, UncompressedPublicKeyError> {
+        self.0.try_into_compressed()
+    }
+}
+
+impl fmt::Display for LegacyPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self.0, f) }
+}
+
+impl FromStr for LegacyPublicKey {
+    type Err = ParsePublicKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> { PublicKey::from_str(s).map(LegacyPublicKey) }
+}
+
+impl From<PublicKey> for LegacyPublicKey {
+    fn from(pk: PublicKey) -> Self { LegacyPublicKey(pk) }
+}
+
+impl From<CompressedPublicKey> for LegacyPublicKey {
+    fn from(pk: CompressedPublicKey) -> Self { LegacyPublicKey(pk.into()) }
+}
+
+impl From<LegacyPublicKey> for PublicKey {
+    fn from(pk: LegacyPublicKey) -> Self { pk.0 }
+}
+
+impl TryFrom<LegacyPublicKey> for CompressedPublicKey {
+    type Error = UncompressedPublicKeyError;
+
+    fn try_from(pk: LegacyPublicKey) -> Result<Self, Self::Error> { pk.try_into_compressed() }
+}
+
+impl From<LegacyPublicKey> for PubkeyHash {
+    fn from(key: LegacyPublicKey) -> Self { key.pubkey_hash() }
+}
+
+impl From<&LegacyPublicKey> for PubkeyHash {
+    fn from(key: &LegacyPublicKey) -> Self { key.pubkey_hash() }
+}
+
 /// A Bitcoin ECDSA private key
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct PrivateKey {
@@ -1141,6 +1219,30 @@ mod tests {
         assert!(upk.wpubkey_hash().is_err());
     }
 
+    #[test]
+    fn compressed_key_policy() {
+        let pk = PublicKey::from_str(
+            "032e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af",
+        )
+        .unwrap();
+        let upk = PublicKey::from_str("042e58afe51f9ed8ad3cc7897f634d881fdbe49a81564629ded8156bebd2ffd1af191923a2964c177f5b5923ae500fca49e99492d534aa3759d6b25a8bc971b133").unwrap();
+
+        let compressed = pk.try_into_compressed().unwrap();
+        assert_eq!(PublicKey::from(compressed), pk);
+        assert_eq!(upk.try_into_compressed(), Err(UncompressedPublicKeyError));
+
+        let legacy = LegacyPublicKey::from(upk);
+        assert!(!legacy.is_compressed());
+        assert_eq!(legacy.pubkey_hash(), upk.pubkey_hash());
+        assert_eq!(legacy.to_string().parse::<LegacyPublicKey>().unwrap(), legacy);
+        assert_eq!(CompressedPublicKey::try_from(legacy), Err(UncompressedPublicKeyError));
+
+        let legacy = LegacyPublicKey::from(compressed);
+        assert!(legacy.is_compressed());
+        assert_eq!(legacy.try_into_compressed(), Ok(compressed));
+        assert_eq!(LegacyPublicKey::from_slice(&legacy.to_bytes()).unwrap(), legacy);
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn test_key_serde() {
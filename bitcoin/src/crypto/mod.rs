@@ -5,6 +5,9 @@
 //! Cryptography related functionality: keys and signatures.
 //!
 
+pub mod adaptor;
+pub mod contract;
+pub(crate) mod ec;
 pub mod ecdsa;
 pub mod key;
 pub mod sighash;
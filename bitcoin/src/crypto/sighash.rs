@@ -17,10 +17,13 @@ use hashes::{hash_newtype, sha256, sha256d, sha256t_hash_newtype, Hash};
 use internals::write_err;
 use io::Write;
 
+use crate::blockdata::opcodes::all::OP_CODESEPARATOR;
+use crate::blockdata::script::Instruction;
 use crate::blockdata::witness::Witness;
 use crate::consensus::{encode, Encodable};
 use crate::taproot::{LeafVersion, TapLeafHash, TAPROOT_ANNEX_PREFIX};
 use crate::prelude::*;
+use crate::psbt::{Psbt, PsbtSighashType, SignError, SigningAlgorithm};
 use crate::{transaction, Amount, Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut};
 
 /// Used for signature hash for invalid use of SIGHASH_SINGLE.
@@ -84,7 +87,7 @@ pub struct SighashCache<T: Borrow<Transaction>> {
 }
 
 /// Common values cached between segwit and taproot inputs.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 struct CommonCache {
     prevouts: sha256::Hash,
     sequences: sha256::Hash,
@@ -94,21 +97,134 @@ struct CommonCache {
     outputs: sha256::Hash,
 }
 
+impl CommonCache {
+    fn new(tx: &Transaction) -> Self {
+        let mut enc_prevouts = sha256::Hash::engine();
+        let mut enc_sequences = sha256::Hash::engine();
+        for txin in tx.input.iter() {
+            txin.previous_output.consensus_encode(&mut enc_prevouts).unwrap();
+            txin.sequence.consensus_encode(&mut enc_sequences).unwrap();
+        }
+        CommonCache {
+            prevouts: sha256::Hash::from_engine(enc_prevouts),
+            sequences: sha256::Hash::from_engine(enc_sequences),
+            outputs: {
+                let mut enc = sha256::Hash::engine();
+                for txout in tx.output.iter() {
+                    txout.consensus_encode(&mut enc).unwrap();
+                }
+                sha256::Hash::from_engine(enc)
+            },
+        }
+    }
+}
+
 /// Values cached for segwit inputs, equivalent to [`CommonCache`] plus another round of `sha256`.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 struct SegwitCache {
     prevouts: sha256d::Hash,
     sequences: sha256d::Hash,
     outputs: sha256d::Hash,
 }
 
+impl SegwitCache {
+    fn new(common_cache: &CommonCache) -> Self {
+        SegwitCache {
+            prevouts: common_cache.prevouts.hash_again(),
+            sequences: common_cache.sequences.hash_again(),
+            outputs: common_cache.outputs.hash_again(),
+        }
+    }
+}
+
 /// Values cached for taproot inputs.
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 struct TaprootCache {
     amounts: sha256::Hash,
     script_pubkeys: sha256::Hash,
 }
 
+impl TaprootCache {
+    fn new<T: Borrow<TxOut>>(prevouts: &[T]) -> Self {
+        let mut enc_amounts = sha256::Hash::engine();
+        let mut enc_script_pubkeys = sha256::Hash::engine();
+        for prevout in prevouts {
+            prevout.borrow().value.consensus_encode(&mut enc_amounts).unwrap();
+            prevout.borrow().script_pubkey.consensus_encode(&mut enc_script_pubkeys).unwrap();
+        }
+        TaprootCache {
+            amounts: sha256::Hash::from_engine(enc_amounts),
+            script_pubkeys: sha256::Hash::from_engine(enc_script_pubkeys),
+        }
+    }
+}
+
+/// The sighash components shared by all inputs of a transaction.
+///
+/// Computing these requires hashing all inputs and outputs of the transaction (and for taproot
+/// all spent outputs) so they are worth computing only once. Unlike [`SighashCache`] this does
+/// not borrow the transaction, it can be cloned and sent to other threads, each of which creates
+/// its own cache using [`SighashCache::with_midstates`] to sign a different input.
+///
+/// The midstates are only valid for the transaction (and spent outputs) they were computed from,
+/// using them with any other transaction produces invalid sighashes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SighashMidstates {
+    common: CommonCache,
+    segwit: SegwitCache,
+    taproot: Option<TaprootCache>,
+}
+
+impl SighashMidstates {
+    /// Computes the midstates used by segwit v0 inputs and taproot inputs not committing to all
+    /// spent outputs.
+    pub fn new(tx: &Transaction) -> Self {
+        let common = CommonCache::new(tx);
+        SighashMidstates { common, segwit: SegwitCache::new(&common), taproot: None }
+    }
+
+    /// Computes the midstates used by all segwit v0 and taproot inputs.
+    ///
+    /// `prevouts` are the outputs spent by the transaction, in the order of its inputs.
+    pub fn with_prevouts<T: Borrow<TxOut>>(
+        tx: &Transaction,
+        prevouts: &[T],
+    ) -> Result<Self, PrevoutsSizeError> {
+        if prevouts.len() != tx.input.len() {
+            return Err(PrevoutsSizeError);
+        }
+        Ok(SighashMidstates { taproot: Some(TaprootCache::new(prevouts)), ..Self::new(tx) })
+    }
+
+    /// Returns the single SHA256 of all outpoints spent by the transaction (BIP 341 `sha_prevouts`).
+    pub fn sha_prevouts(&self) -> sha256::Hash { self.common.prevouts }
+
+    /// Returns the single SHA256 of all input sequence numbers (BIP 341 `sha_sequences`).
+    pub fn sha_sequences(&self) -> sha256::Hash { self.common.sequences }
+
+    /// Returns the single SHA256 of all transaction outputs (BIP 341 `sha_outputs`).
+    pub fn sha_outputs(&self) -> sha256::Hash { self.common.outputs }
+
+    /// Returns the single SHA256 of all spent amounts (BIP 341 `sha_amounts`), if computed.
+    pub fn sha_amounts(&self) -> Option<sha256::Hash> { self.taproot.map(|t| t.amounts) }
+
+    /// Returns the single SHA256 of all spent script pubkeys (BIP 341 `sha_scriptpubkeys`), if
+    /// computed.
+    pub fn sha_script_pubkeys(&self) -> Option<sha256::Hash> {
+        self.taproot.map(|t| t.script_pubkeys)
+    }
+
+    /// Returns the double SHA256 of all outpoints spent by the transaction (BIP 143
+    /// `hashPrevouts`).
+    pub fn hash_prevouts(&self) -> sha256d::Hash { self.segwit.prevouts }
+
+    /// Returns the double SHA256 of all input sequence numbers (BIP 143 `hashSequence`).
+    pub fn hash_sequences(&self) -> sha256d::Hash { self.segwit.sequences }
+
+    /// Returns the double SHA256 of all transaction outputs (BIP 143 `hashOutputs`).
+    pub fn hash_outputs(&self) -> sha256d::Hash { self.segwit.outputs }
+}
+
 /// Contains outputs of previous transactions. In the case [`TapSighashType`] variant is
 /// `SIGHASH_ANYONECANPAY`, [`Prevouts::One`] may be used.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -127,6 +243,15 @@ where
 
 const KEY_VERSION_0: u8 = 0u8;
 
+/// Key version of BIP118 public keys, committed to by their signatures.
+#[cfg(feature = "anyprevout")]
+const KEY_VERSION_1: u8 = 1u8;
+
+/// Bits of the taproot sighash type selecting which data about the input is signed.
+const INPUT_FLAG_MASK: u8 = 0xc0;
+const SIGHASH_ANYONECANPAY: u8 = 0x80;
+const SIGHASH_ANYPREVOUTANYSCRIPT: u8 = 0xc0;
+
 /// Information related to the script path spending.
 ///
 /// This can be hashed into a [`TapLeafHash`].
@@ -503,6 +628,128 @@ impl TapSighashType {
     }
 }
 
+/// Hashtype of a signature by a BIP118 public key, encoded in the last byte of the signature.
+///
+/// BIP118 public keys can sign with any of the BIP341 sighash types as well as with the
+/// `SIGHASH_ANYPREVOUT` and `SIGHASH_ANYPREVOUTANYSCRIPT` flags, which no other key accepts.
+#[cfg(feature = "anyprevout")]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum AnyPrevoutSighashType {
+    /// 0x0: Used when not explicitly specified, defaults to [`AnyPrevoutSighashType::All`]
+    Default = 0x00,
+    /// 0x1: Sign all outputs.
+    All = 0x01,
+    /// 0x2: Sign no outputs --- anyone can choose the destination.
+    None = 0x02,
+    /// 0x3: Sign the output whose index matches this input's index.
+    Single = 0x03,
+    /// 0x81: Sign all outputs but only this input.
+    AllPlusAnyoneCanPay = 0x81,
+    /// 0x82: Sign no outputs and only this input.
+    NonePlusAnyoneCanPay = 0x82,
+    /// 0x83: Sign one output and only this input.
+    SinglePlusAnyoneCanPay = 0x83,
+    /// 0x41: Sign all outputs and this input without its outpoint.
+    AllPlusAnyPrevout = 0x41,
+    /// 0x42: Sign no outputs and this input without its outpoint.
+    NonePlusAnyPrevout = 0x42,
+    /// 0x43: Sign one output and this input without its outpoint.
+    SinglePlusAnyPrevout = 0x43,
+    /// 0xc1: Sign all outputs and only the sequence of this input.
+    AllPlusAnyPrevoutAnyScript = 0xc1,
+    /// 0xc2: Sign no outputs and only the sequence of this input.
+    NonePlusAnyPrevoutAnyScript = 0xc2,
+    /// 0xc3: Sign one output and only the sequence of this input.
+    SinglePlusAnyPrevoutAnyScript = 0xc3,
+}
+#[cfg(all(feature = "anyprevout", feature = "serde"))]
+crate::serde_utils::serde_string_impl!(AnyPrevoutSighashType, "a AnyPrevoutSighashType data");
+
+#[cfg(feature = "anyprevout")]
+impl fmt::Display for AnyPrevoutSighashType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AnyPrevoutSighashType::*;
+
+        let s = match self {
+            Default => "SIGHASH_DEFAULT",
+            All => "SIGHASH_ALL",
+            None => "SIGHASH_NONE",
+            Single => "SIGHASH_SINGLE",
+            AllPlusAnyoneCanPay => "SIGHASH_ALL|SIGHASH_ANYONECANPAY",
+            NonePlusAnyoneCanPay => "SIGHASH_NONE|SIGHASH_ANYONECANPAY",
+            SinglePlusAnyoneCanPay => "SIGHASH_SINGLE|SIGHASH_ANYONECANPAY",
+            AllPlusAnyPrevout => "SIGHASH_ALL|SIGHASH_ANYPREVOUT",
+            NonePlusAnyPrevout => "SIGHASH_NONE|SIGHASH_ANYPREVOUT",
+            SinglePlusAnyPrevout => "SIGHASH_SINGLE|SIGHASH_ANYPREVOUT",
+            AllPlusAnyPrevoutAnyScript => "SIGHASH_ALL|SIGHASH_ANYPREVOUTANYSCRIPT",
+            NonePlusAnyPrevoutAnyScript => "SIGHASH_NONE|SIGHASH_ANYPREVOUTANYSCRIPT",
+            SinglePlusAnyPrevoutAnyScript => "SIGHASH_SINGLE|SIGHASH_ANYPREVOUTANYSCRIPT",
+        };
+        f.write_str(s)
+    }
+}
+
+#[cfg(feature = "anyprevout")]
+impl str::FromStr for AnyPrevoutSighashType {
+    type Err = SighashTypeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use AnyPrevoutSighashType::*;
+
+        match s {
+            "SIGHASH_DEFAULT" => Ok(Default),
+            "SIGHASH_ALL" => Ok(All),
+            "SIGHASH_NONE" => Ok(None),
+            "SIGHASH_SINGLE" => Ok(Single),
+            "SIGHASH_ALL|SIGHASH_ANYONECANPAY" => Ok(AllPlusAnyoneCanPay),
+            "SIGHASH_NONE|SIGHASH_ANYONECANPAY" => Ok(NonePlusAnyoneCanPay),
+            "SIGHASH_SINGLE|SIGHASH_ANYONECANPAY" => Ok(SinglePlusAnyoneCanPay),
+            "SIGHASH_ALL|SIGHASH_ANYPREVOUT" => Ok(AllPlusAnyPrevout),
+            "SIGHASH_NONE|SIGHASH_ANYPREVOUT" => Ok(NonePlusAnyPrevout),
+            "SIGHASH_SINGLE|SIGHASH_ANYPREVOUT" => Ok(SinglePlusAnyPrevout),
+            "SIGHASH_ALL|SIGHASH_ANYPREVOUTANYSCRIPT" => Ok(AllPlusAnyPrevoutAnyScript),
+            "SIGHASH_NONE|SIGHASH_ANYPREVOUTANYSCRIPT" => Ok(NonePlusAnyPrevoutAnyScript),
+            "SIGHASH_SINGLE|SIGHASH_ANYPREVOUTANYSCRIPT" => Ok(SinglePlusAnyPrevoutAnyScript),
+            _ => Err(SighashTypeParseError { unrecognized: s.to_owned() }),
+        }
+    }
+}
+
+#[cfg(feature = "anyprevout")]
+impl From<TapSighashType> for AnyPrevoutSighashType {
+    fn from(s: TapSighashType) -> Self {
+        use AnyPrevoutSighashType::*;
+
+        match s {
+            TapSighashType::Default => Default,
+            TapSighashType::All => All,
+            TapSighashType::None => None,
+            TapSighashType::Single => Single,
+            TapSighashType::AllPlusAnyoneCanPay => AllPlusAnyoneCanPay,
+            TapSighashType::NonePlusAnyoneCanPay => NonePlusAnyoneCanPay,
+            TapSighashType::SinglePlusAnyoneCanPay => SinglePlusAnyoneCanPay,
+        }
+    }
+}
+
+#[cfg(feature = "anyprevout")]
+impl AnyPrevoutSighashType {
+    /// Constructs an [`AnyPrevoutSighashType`] from a raw `u8`.
+    pub fn from_consensus_u8(sighash_type: u8) -> Result<Self, InvalidSighashTypeError> {
+        use AnyPrevoutSighashType::*;
+
+        Ok(match sighash_type {
+            0x41 => AllPlusAnyPrevout,
+            0x42 => NonePlusAnyPrevout,
+            0x43 => SinglePlusAnyPrevout,
+            0xc1 => AllPlusAnyPrevoutAnyScript,
+            0xc2 => NonePlusAnyPrevoutAnyScript,
+            0xc3 => SinglePlusAnyPrevoutAnyScript,
+            x => TapSighashType::from_consensus_u8(x)?.into(),
+        })
+    }
+}
+
 /// Integer is not a consensus valid sighash type.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InvalidSighashTypeError(pub u32);
@@ -565,6 +812,41 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
         SighashCache { tx, common_cache: None, taproot_cache: None, segwit_cache: None }
     }
 
+    /// Constructs a new `SighashCache` from an unsigned transaction and its precomputed
+    /// [`SighashMidstates`].
+    ///
+    /// The midstates must have been computed from the same transaction, see
+    /// [`SighashCache::new`] for which fields may change.
+    pub fn with_midstates(tx: R, midstates: SighashMidstates) -> Self {
+        SighashCache {
+            tx,
+            common_cache: Some(midstates.common),
+            segwit_cache: Some(midstates.segwit),
+            taproot_cache: midstates.taproot,
+        }
+    }
+
+    /// Computes the midstates shared by all inputs, reusing the ones already cached.
+    ///
+    /// If `prevouts` is provided, the midstates used by taproot inputs are included.
+    pub fn midstates<T: Borrow<TxOut>>(
+        &mut self,
+        prevouts: Option<&[T]>,
+    ) -> Result<SighashMidstates, PrevoutsSizeError> {
+        let taproot = match prevouts {
+            Some(prevouts) => {
+                if prevouts.len() != self.tx.borrow().input.len() {
+                    return Err(PrevoutsSizeError);
+                }
+                Some(*self.taproot_cache(prevouts))
+            }
+            None => self.taproot_cache,
+        };
+        let segwit = *self.segwit_cache();
+        let common = *self.common_cache();
+        Ok(SighashMidstates { common, segwit, taproot })
+    }
+
     /// Returns the reference to the cached transaction.
     pub fn transaction(&self) -> &Transaction { self.tx.borrow() }
 
@@ -581,17 +863,50 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
         annex: Option<Annex>,
         leaf_hash_code_separator: Option<(TapLeafHash, u32)>,
         sighash_type: TapSighashType,
+    ) -> Result<(), SigningDataError<TaprootError>> {
+        self.taproot_encode_signing_data_with_key_version_to(
+            writer,
+            input_index,
+            prevouts,
+            annex,
+            leaf_hash_code_separator,
+            sighash_type as u8,
+            KEY_VERSION_0,
+        )
+    }
+
+    /// Encodes the signing data for the raw `hash_type`, which must be a valid BIP341 or (for
+    /// script path spends) BIP118 hash type.
+    #[allow(clippy::too_many_arguments)]
+    fn taproot_encode_signing_data_with_key_version_to<W: Write + ?Sized, T: Borrow<TxOut>>(
+        &mut self,
+        writer: &mut W,
+        input_index: usize,
+        prevouts: &Prevouts<T>,
+        annex: Option<Annex>,
+        leaf_hash_code_separator: Option<(TapLeafHash, u32)>,
+        hash_type: u8,
+        key_version: u8,
     ) -> Result<(), SigningDataError<TaprootError>> {
         prevouts.check_all(self.tx.borrow()).map_err(SigningDataError::sighash)?;
 
-        let (sighash, anyone_can_pay) = sighash_type.split_anyonecanpay_flag();
+        // The BIP118 hash types sign the same outputs as the BIP341 ones with the same low bits and,
+        // like `SIGHASH_ANYONECANPAY`, only this input.
+        let input_flag = hash_type & INPUT_FLAG_MASK;
+        let bip341_type = match input_flag {
+            0 => hash_type,
+            _ => hash_type & !INPUT_FLAG_MASK | SIGHASH_ANYONECANPAY,
+        };
+        let (sighash, anyone_can_pay) = TapSighashType::from_consensus_u8(bip341_type)
+            .expect("valid hash type")
+            .split_anyonecanpay_flag();
 
         // epoch
         0u8.consensus_encode(writer)?;
 
         // * Control:
         // hash_type (1).
-        (sighash_type as u8).consensus_encode(writer)?;
+        hash_type.consensus_encode(writer)?;
 
         // * Transaction Data:
         // nVersion (4): the nVersion of the transaction.
@@ -635,12 +950,19 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
         //      amount (8): value of the previous output spent by this input.
         //      scriptPubKey (35): scriptPubKey of the previous output spent by this input, serialized as script inside CTxOut. Its size is always 35 bytes.
         //      nSequence (4): nSequence of this input.
+        // BIP118 omits the outpoint for SIGHASH_ANYPREVOUT and additionally the amount and
+        // scriptPubKey for SIGHASH_ANYPREVOUTANYSCRIPT.
         if anyone_can_pay {
             let txin = &self.tx.borrow().tx_in(input_index).map_err(SigningDataError::sighash)?;
-            let previous_output = prevouts.get(input_index).map_err(SigningDataError::sighash)?;
-            txin.previous_output.consensus_encode(writer)?;
-            previous_output.value.consensus_encode(writer)?;
-            previous_output.script_pubkey.consensus_encode(writer)?;
+            if input_flag == SIGHASH_ANYONECANPAY {
+                txin.previous_output.consensus_encode(writer)?;
+            }
+            if input_flag != SIGHASH_ANYPREVOUTANYSCRIPT {
+                let previous_output =
+                    prevouts.get(input_index).map_err(SigningDataError::sighash)?;
+                previous_output.value.consensus_encode(writer)?;
+                previous_output.script_pubkey.consensus_encode(writer)?;
+            }
             txin.sequence.consensus_encode(writer)?;
         } else {
             (input_index as u32).consensus_encode(writer)?;
@@ -676,11 +998,14 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
 
         //     if (scriptpath):
         //         ss += TaggedHash("TapLeaf", bytes([leaf_ver]) + ser_string(script))
-        //         ss += bytes([0])
+        //         ss += bytes([key_version])
         //         ss += struct.pack("<i", codeseparator_pos)
+        // BIP118 omits the leaf hash for SIGHASH_ANYPREVOUTANYSCRIPT.
         if let Some((hash, code_separator_pos)) = leaf_hash_code_separator {
-            hash.as_byte_array().consensus_encode(writer)?;
-            KEY_VERSION_0.consensus_encode(writer)?;
+            if input_flag != SIGHASH_ANYPREVOUTANYSCRIPT {
+                hash.as_byte_array().consensus_encode(writer)?;
+            }
+            key_version.consensus_encode(writer)?;
             code_separator_pos.consensus_encode(writer)?;
         }
 
@@ -750,6 +1075,33 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
         Ok(TapSighash::from_engine(enc))
     }
 
+    /// Computes the BIP118 sighash for a script spend signature by a BIP118 public key.
+    ///
+    /// Unlike [`SighashCache::taproot_signature_hash`] this commits to the BIP118 key version, as
+    /// required for any signature by a BIP118 public key.
+    #[cfg(feature = "anyprevout")]
+    pub fn taproot_anyprevout_signature_hash<T: Borrow<TxOut>>(
+        &mut self,
+        input_index: usize,
+        prevouts: &Prevouts<T>,
+        annex: Option<Annex>,
+        leaf_hash_code_separator: (TapLeafHash, u32),
+        sighash_type: AnyPrevoutSighashType,
+    ) -> Result<TapSighash, TaprootError> {
+        let mut enc = TapSighash::engine();
+        self.taproot_encode_signing_data_with_key_version_to(
+            &mut enc,
+            input_index,
+            prevouts,
+            annex,
+            Some(leaf_hash_code_separator),
+            sighash_type as u8,
+            KEY_VERSION_1,
+        )
+        .map_err(SigningDataError::unwrap_sighash)?;
+        Ok(TapSighash::from_engine(enc))
+    }
+
     /// Encodes the BIP143 signing data for any flag type into a given object implementing the
     /// [`std::io::Write`] trait.
     ///
@@ -998,7 +1350,8 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
     ///
     /// Does NOT attempt to support OP_CODESEPARATOR. In general this would require evaluating
     /// `script_pubkey` to determine which separators get evaluated and which don't, which we don't
-    /// have the information to determine.
+    /// have the information to determine. Use
+    /// [`SighashCache::legacy_signature_hash_with_codeseparator`] if the executed separator is known.
     pub fn legacy_signature_hash(
         &self,
         input_index: usize,
@@ -1016,6 +1369,39 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
         }
     }
 
+    /// Computes a legacy signature hash for a given input index with a given sighash flag, taking
+    /// the executed `OP_CODESEPARATOR` into account.
+    ///
+    /// `codeseparator_position` is the opcode index in `script_pubkey` of the last executed
+    /// `OP_CODESEPARATOR`, if any. Like in Bitcoin Core, the signed script code is the part of
+    /// `script_pubkey` after that separator with all remaining `OP_CODESEPARATOR`s removed.
+    ///
+    /// Unlike [`SighashCache::legacy_signature_hash`], the SIGHASH_SINGLE bug is returned as
+    /// [`LegacySighashOutcome::SighashSingleBug`].
+    ///
+    /// # Warning
+    ///
+    /// The caller is responsible for removing the signature itself from `script_pubkey` (Bitcoin
+    /// Core's `FindAndDelete`), if required.
+    pub fn legacy_signature_hash_with_codeseparator(
+        &self,
+        input_index: usize,
+        script_pubkey: &Script,
+        codeseparator_position: Option<u32>,
+        sighash_type: u32,
+    ) -> Result<LegacySighashOutcome, LegacySighashError> {
+        let script_code = legacy_script_code(script_pubkey, codeseparator_position)?;
+        let mut engine = LegacySighash::engine();
+        match self
+            .legacy_encode_signing_data_to(&mut engine, input_index, &script_code, sighash_type)
+            .is_sighash_single_bug()
+        {
+            Ok(true) => Ok(LegacySighashOutcome::SighashSingleBug),
+            Ok(false) => Ok(LegacySighashOutcome::Hash(LegacySighash::from_engine(engine))),
+            Err(e) => Err(e.unwrap_sighash().into()),
+        }
+    }
+
     #[inline]
     fn common_cache(&mut self) -> &CommonCache {
         Self::common_cache_minimal_borrow(&mut self.common_cache, self.tx.borrow())
@@ -1025,53 +1411,19 @@ impl<R: Borrow<Transaction>> SighashCache<R> {
         common_cache: &'a mut Option<CommonCache>,
         tx: &Transaction,
     ) -> &'a CommonCache {
-        common_cache.get_or_insert_with(|| {
-            let mut enc_prevouts = sha256::Hash::engine();
-            let mut enc_sequences = sha256::Hash::engine();
-            for txin in tx.input.iter() {
-                txin.previous_output.consensus_encode(&mut enc_prevouts).unwrap();
-                txin.sequence.consensus_encode(&mut enc_sequences).unwrap();
-            }
-            CommonCache {
-                prevouts: sha256::Hash::from_engine(enc_prevouts),
-                sequences: sha256::Hash::from_engine(enc_sequences),
-                outputs: {
-                    let mut enc = sha256::Hash::engine();
-                    for txout in tx.output.iter() {
-                        txout.consensus_encode(&mut enc).unwrap();
-                    }
-                    sha256::Hash::from_engine(enc)
-                },
-            }
-        })
+        common_cache.get_or_insert_with(|| CommonCache::new(tx))
     }
 
     fn segwit_cache(&mut self) -> &SegwitCache {
         let common_cache = &mut self.common_cache;
         let tx = self.tx.borrow();
         self.segwit_cache.get_or_insert_with(|| {
-            let common_cache = Self::common_cache_minimal_borrow(common_cache, tx);
-            SegwitCache {
-                prevouts: common_cache.prevouts.hash_again(),
-                sequences: common_cache.sequences.hash_again(),
-                outputs: common_cache.outputs.hash_again(),
-            }
+            SegwitCache::new(Self::common_cache_minimal_borrow(common_cache, tx))
         })
     }
 
     fn taproot_cache<T: Borrow<TxOut>>(&mut self, prevouts: &[T]) -> &TaprootCache {
-        self.taproot_cache.get_or_insert_with(|| {
-            let mut enc_amounts = sha256::Hash::engine();
-            let mut enc_script_pubkeys = sha256::Hash::engine();
-            for prevout in prevouts {
-                prevout.borrow().value.consensus_encode(&mut enc_amounts).unwrap();
-                prevout.borrow().script_pubkey.consensus_encode(&mut enc_script_pubkeys).unwrap();
-            }
-            TaprootCache {
-                amounts: sha256::Hash::from_engine(enc_amounts),
-                script_pubkeys: sha256::Hash::from_engine(enc_script_pubkeys),
-            }
-        })
+        self.taproot_cache.get_or_insert_with(|| TaprootCache::new(prevouts))
     }
 }
 
@@ -1243,6 +1595,46 @@ impl std::error::Error for P2wpkhError {
     }
 }
 
+/// Error computing a legacy sighash with [`SighashCache::legacy_signature_hash_with_codeseparator`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LegacySighashError {
+    /// Error computing the sighash.
+    Sighash(transaction::InputsIndexError),
+    /// There is no `OP_CODESEPARATOR` at the given opcode index.
+    InvalidCodeSeparatorPosition(u32),
+}
+
+internals::impl_from_infallible!(LegacySighashError);
+
+impl From<transaction::InputsIndexError> for LegacySighashError {
+    fn from(value: transaction::InputsIndexError) -> Self { LegacySighashError::Sighash(value) }
+}
+
+impl fmt::Display for LegacySighashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use LegacySighashError::*;
+
+        match *self {
+            Sighash(ref e) => write_err!(f, "error encoding legacy signing data"; e),
+            InvalidCodeSeparatorPosition(pos) =>
+                write!(f, "no OP_CODESEPARATOR at opcode index {}", pos),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LegacySighashError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use LegacySighashError::*;
+
+        match *self {
+            Sighash(ref e) => Some(e),
+            InvalidCodeSeparatorPosition(_) => None,
+        }
+    }
+}
+
 /// Using `SIGHASH_SINGLE` requires an output at the same index as the input.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -1304,11 +1696,119 @@ impl std::error::Error for AnnexError {
     }
 }
 
+/// Computes the message to sign for the input at `input_index` of `psbt`, along with the
+/// sighash type.
+///
+/// The signature algorithm is determined from the spent output and the populated PSBT fields:
+///
+/// * Legacy, segwit v0 and nested segwit inputs use the redeem and witness scripts of the input.
+/// * Taproot inputs are signed for the key path if the input has no leaf scripts (`tap_scripts`
+///   or `tap_script_sigs`), and for the script path of the leaf if it has exactly one and no key
+///   path data (`tap_internal_key` or `tap_key_sig`).
+///
+/// Uses the sighash type of the input if one is specified, `SIGHASH_ALL` for ECDSA inputs and
+/// `SIGHASH_DEFAULT` for taproot inputs otherwise. When signing several inputs of the same
+/// transaction, [`Psbt::sighash_ecdsa`] and [`Psbt::sighash_taproot`] allow sharing a
+/// [`SighashCache`] between them.
+///
+/// # Errors
+///
+/// Returns [`SignError::AmbiguousTaprootSpend`] if the spend path of a taproot input can't be
+/// determined, use [`Psbt::sighash_taproot`] to choose it explicitly.
+pub fn from_psbt_input(
+    psbt: &Psbt,
+    input_index: usize,
+) -> Result<(secp256k1::Message, PsbtSighashType), SignError> {
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    match psbt.signing_algorithm(input_index)? {
+        SigningAlgorithm::Ecdsa => {
+            let (msg, sighash_type) = psbt.sighash_ecdsa(input_index, &mut cache)?;
+            Ok((msg, sighash_type.into()))
+        }
+        SigningAlgorithm::Schnorr => {
+            let input = &psbt.inputs[input_index];
+            let key_path = input.tap_internal_key.is_some() || input.tap_key_sig.is_some();
+            let leaves = input
+                .tap_scripts
+                .values()
+                .map(|(script, version)| TapLeafHash::from_script(script, *version))
+                .chain(input.tap_script_sigs.keys().map(|&(_, leaf_hash)| leaf_hash))
+                .collect::<BTreeSet<_>>();
+            let leaf_hash = match (key_path, leaves.len()) {
+                (_, 0) => None,
+                (false, 1) => leaves.into_iter().next(),
+                _ => return Err(SignError::AmbiguousTaprootSpend),
+            };
+            let (msg, sighash_type) = psbt.sighash_taproot(input_index, &mut cache, leaf_hash)?;
+            Ok((msg, sighash_type.into()))
+        }
+    }
+}
+
 fn is_invalid_use_of_sighash_single(sighash: u32, input_index: usize, outputs_len: usize) -> bool {
     let ty = EcdsaSighashType::from_consensus(sighash);
     ty == EcdsaSighashType::Single && input_index >= outputs_len
 }
 
+/// Returns the part of `script` after the `OP_CODESEPARATOR` at opcode index
+/// `codeseparator_position` with all `OP_CODESEPARATOR`s removed.
+fn legacy_script_code(
+    script: &Script,
+    codeseparator_position: Option<u32>,
+) -> Result<ScriptBuf, LegacySighashError> {
+    let bytes = script.as_bytes();
+    let mut instructions = script.instruction_indices();
+    let mut start = 0;
+    if let Some(position) = codeseparator_position {
+        instructions.by_ref().take(position as usize).for_each(drop);
+        match instructions.next() {
+            Some(Ok((index, Instruction::Op(op)))) if op == OP_CODESEPARATOR => start = index + 1,
+            _ => return Err(LegacySighashError::InvalidCodeSeparatorPosition(position)),
+        }
+    }
+
+    // Like Bitcoin Core, separators are removed up to the first parse error and the rest of the
+    // script is kept as is.
+    let mut script_code = Vec::with_capacity(bytes.len() - start);
+    for instruction in instructions {
+        match instruction {
+            Ok((index, Instruction::Op(op))) if op == OP_CODESEPARATOR => {
+                script_code.extend_from_slice(&bytes[start..index]);
+                start = index + 1;
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    script_code.extend_from_slice(&bytes[start..]);
+    Ok(ScriptBuf::from_bytes(script_code))
+}
+
+/// A legacy signature hash, accounting for the SIGHASH_SINGLE bug.
+///
+/// Signing an input with `SIGHASH_SINGLE` when there is no output at the same index does not hash
+/// the transaction at all, consensus rules sign the value `1` instead.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum LegacySighashOutcome {
+    /// The signature hash of the transaction.
+    Hash(LegacySighash),
+    /// Input is an instance of the SIGHASH_SINGLE bug.
+    SighashSingleBug,
+}
+
+impl LegacySighashOutcome {
+    /// Returns the hash to sign, which is `1` for the SIGHASH_SINGLE bug.
+    pub fn to_sighash(self) -> LegacySighash {
+        match self {
+            LegacySighashOutcome::Hash(hash) => hash,
+            LegacySighashOutcome::SighashSingleBug => LegacySighash::from_byte_array(UINT256_ONE),
+        }
+    }
+
+    /// Returns true if this is an instance of the SIGHASH_SINGLE bug.
+    pub fn is_sighash_single_bug(self) -> bool { self == LegacySighashOutcome::SighashSingleBug }
+}
+
 /// Result of [`SighashCache::legacy_encode_signing_data_to`].
 ///
 /// This type forces the caller to handle SIGHASH_SINGLE bug case.
@@ -1507,6 +2007,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn legacy_sighash_with_codeseparator() {
+        use crate::blockdata::opcodes::all::*;
+        use crate::blockdata::script::Builder;
+
+        let tx = deserialize::<Transaction>(&hex!("0100000001a15d57094aa7a21a28cb20b59aab8fc7d1149a3bdbcddba9c622e4f5f6a99ece010000006c493046022100f93bb0e7d8db7bd46e40132d1f8242026e045f03a0efe71bbb8e3f475e970d790221009337cd7f1f929f00cc6ff01f03729b069a7c21b59b1736ddfee5db5946c5da8c0121033b9b137ee87d5a812d6f506efdd37f0affa7ffc310711c06c7f3e097c9447c52ffffffff0100e1f505000000001976a9140389035a9225b3839e2bbf32d826a1e222031fd888ac00000000")).unwrap();
+        let cache = SighashCache::new(&tx);
+        let script = Builder::new()
+            .push_opcode(OP_DUP)
+            .push_opcode(OP_CODESEPARATOR)
+            .push_slice([0xab; 2])
+            .push_opcode(OP_CODESEPARATOR)
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let sighash = |script: &Script, position| {
+            cache.legacy_signature_hash_with_codeseparator(0, script, position, 1)
+        };
+        let expected = |script: ScriptBuf| {
+            LegacySighashOutcome::Hash(cache.legacy_signature_hash(0, &script, 1).unwrap())
+        };
+
+        // Separators are removed, pushed data is kept.
+        let stripped = Builder::new().push_opcode(OP_DUP).push_slice([0xab; 2]);
+        let stripped = stripped.push_opcode(OP_CHECKSIG).into_script();
+        assert_eq!(sighash(&script, None), Ok(expected(stripped)));
+        let stripped = Builder::new().push_slice([0xab; 2]).push_opcode(OP_CHECKSIG).into_script();
+        assert_eq!(sighash(&script, Some(1)), Ok(expected(stripped)));
+        let stripped = Builder::new().push_opcode(OP_CHECKSIG).into_script();
+        assert_eq!(sighash(&script, Some(3)), Ok(expected(stripped)));
+        // Scripts without separators are signed as is.
+        assert_eq!(
+            sighash(&tx.input[0].script_sig, None),
+            Ok(expected(tx.input[0].script_sig.clone()))
+        );
+
+        for position in [0, 2, 5] {
+            assert_eq!(
+                sighash(&script, Some(position)),
+                Err(LegacySighashError::InvalidCodeSeparatorPosition(position))
+            );
+        }
+        assert!(matches!(
+            cache.legacy_signature_hash_with_codeseparator(1, &script, None, 1),
+            Err(LegacySighashError::Sighash(_))
+        ));
+
+        // SIGHASH_SINGLE without a corresponding output signs `1`.
+        let mut tx = tx;
+        tx.output.clear();
+        let cache = SighashCache::new(&tx);
+        let outcome = cache.legacy_signature_hash_with_codeseparator(0, &script, None, 3).unwrap();
+        assert!(outcome.is_sighash_single_bug());
+        assert_eq!(outcome.to_sighash(), LegacySighash::from_byte_array(UINT256_ONE));
+        assert_eq!(outcome.to_sighash(), cache.legacy_signature_hash(0, &script, 3).unwrap());
+    }
+
     #[test]
     fn test_tap_sighash_hash() {
         let bytes = hex!("00011b96877db45ffa23b307e9f0ac87b80ef9a80b4c5f0db3fbe734422453e83cc5576f3d542c5d4898fb2b696c15d43332534a7c1d1255fda38993545882df92c3e353ff6d36fbfadc4d168452afd8467f02fe53d71714fcea5dfe2ea759bd00185c4cb02bc76d42620393ca358a1a713f4997f9fc222911890afb3fe56c6a19b202df7bffdcfad08003821294279043746631b00e2dc5e52a111e213bbfe6ef09a19428d418dab0d50000000000");
@@ -1578,6 +2134,154 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(feature = "anyprevout")]
+    fn anyprevout_sighashes() {
+        use crate::{Amount, OutPoint, Sequence, TxIn, Txid};
+
+        let tx = |outpoint: OutPoint, sequence: u32| Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: outpoint,
+                sequence: Sequence(sequence),
+                ..Default::default()
+            }],
+            output: vec![TxOut { value: Amount::from_sat(1000), script_pubkey: ScriptBuf::new() }],
+        };
+        let prevout = |sat: u64| TxOut {
+            value: Amount::from_sat(sat),
+            script_pubkey: ScriptBuf::from_hex(
+                "51200000000000000000000000000000000000000000000000000000000000000000",
+            )
+            .unwrap(),
+        };
+        let sighash = |tx: Transaction, prevout: TxOut, leaf: u8, ty: AnyPrevoutSighashType| {
+            let leaf = (TapLeafHash::from_byte_array([leaf; 32]), 0xFFFFFFFF);
+            SighashCache::new(tx)
+                .taproot_anyprevout_signature_hash(0, &Prevouts::One(0, prevout), None, leaf, ty)
+                .unwrap()
+        };
+        let outpoint = OutPoint::new(Txid::from_byte_array([1; 32]), 0);
+        let other_outpoint = OutPoint::new(Txid::from_byte_array([2; 32]), 1);
+
+        let apo = AnyPrevoutSighashType::AllPlusAnyPrevout;
+        let base = sighash(tx(outpoint, 0), prevout(2000), 0, apo);
+        assert_eq!(sighash(tx(other_outpoint, 0), prevout(2000), 0, apo), base);
+        assert_ne!(sighash(tx(outpoint, 0), prevout(3000), 0, apo), base);
+        assert_ne!(sighash(tx(outpoint, 0), prevout(2000), 1, apo), base);
+        assert_ne!(sighash(tx(outpoint, 1), prevout(2000), 0, apo), base);
+
+        let apoas = AnyPrevoutSighashType::AllPlusAnyPrevoutAnyScript;
+        let base = sighash(tx(outpoint, 0), prevout(2000), 0, apoas);
+        assert_eq!(sighash(tx(other_outpoint, 0), prevout(3000), 1, apoas), base);
+        assert_ne!(sighash(tx(outpoint, 1), prevout(2000), 0, apoas), base);
+
+        let acp = AnyPrevoutSighashType::AllPlusAnyoneCanPay;
+        let base = sighash(tx(outpoint, 0), prevout(2000), 0, acp);
+        assert_ne!(sighash(tx(other_outpoint, 0), prevout(2000), 0, acp), base);
+
+        // BIP118 public keys commit to their key version for the BIP341 sighash types too.
+        let mut cache = SighashCache::new(tx(outpoint, 0));
+        let leaf = (TapLeafHash::from_byte_array([0; 32]), 0xFFFFFFFF);
+        let prevouts = Prevouts::All(&[prevout(2000)]);
+        let all = TapSighashType::All;
+        assert_ne!(
+            cache.taproot_anyprevout_signature_hash(0, &prevouts, None, leaf, all.into()).unwrap(),
+            cache.taproot_signature_hash(0, &prevouts, None, Some(leaf), all).unwrap()
+        );
+
+        // Only BIP118 public keys accept the BIP118 sighash types.
+        assert_eq!(TapSighashType::from_consensus_u8(0x41), Err(InvalidSighashTypeError(0x41)));
+        assert_eq!(
+            AnyPrevoutSighashType::from_consensus_u8(0x44),
+            Err(InvalidSighashTypeError(0x44))
+        );
+        for ty in [apo, apoas, AnyPrevoutSighashType::SinglePlusAnyPrevoutAnyScript, acp] {
+            assert_eq!(AnyPrevoutSighashType::from_consensus_u8(ty as u8), Ok(ty));
+            assert_eq!(ty.to_string().parse::<AnyPrevoutSighashType>(), Ok(ty));
+        }
+    }
+
+    // BIP118 does not come with sighash test vectors. These were computed with a separate Python
+    // implementation of the signature message written from BIP341 and BIP118, which reproduces the
+    // BIP341 script path sighash of `test_sighashes_with_script_path_raw_hash`.
+    #[test]
+    #[cfg(feature = "anyprevout")]
+    fn anyprevout_sighash_vectors() {
+        let tx: Transaction = deserialize(&hex!("020000000211111111111111111111111111111111111111111111111111111111111111110000000000fdffffff22222222222222222222222222222222222222222222222222222222222222220700000000100000000250c3000000000000160014333333333333333333333333333333333333333360ea000000000000225120444444444444444444444444444444444444444444444444444444444444444420a10700")).unwrap();
+        let prevouts: Vec<TxOut> = deserialize(&hex!("027011010000000000225120555555555555555555555555555555555555555555555555555555555555555580380100000000002251206666666666666666666666666666666666666666666666666666666666666666")).unwrap();
+        let prevouts = Prevouts::All(&prevouts);
+        let leaf_hash = TapLeafHash::from_byte_array(
+            <[u8; 32]>::from_hex(
+                "d2b8933037b8567deb76542d48f01f8ac8ad73beb55de301954da91821019332",
+            )
+            .unwrap(),
+        );
+        let mut cache = SighashCache::new(&tx);
+
+        let vectors = [
+            (0x00, "c9120ed3981117fdb2e5a75f904895575091ec0d5e4ae06c365c53e7d3aaceba"),
+            (0x01, "6bc195d6f7f7484b15081ad69f88964e7134809d978f266427c636f4367eba1e"),
+            (0x02, "e2e69cf94f0ff941939a41dc025263586b40160eeeae2c7e814821f4659eef35"),
+            (0x03, "5fec2c0596085ba4e245ac9ccc5d54087faab6969cfe53df64dcd1101fdbcf9f"),
+            (0x81, "a2a952e04954cc107ba0783bb161ae97add7312b828437cb15a178a1a5615a37"),
+            (0x82, "905b6fb85e9132a2db689a69c5d943af02145604474511e26690429c93c14f72"),
+            (0x83, "8f5a4a0f6326f5688fda236c5d230f3e1874a5b2443b12b1c3a11da40b398f39"),
+            (0x41, "8558505df72a9b72044a08fae67568abae294cb1aae56453da69879a8cb4f6ec"),
+            (0x42, "aac718c777e0e7daf96f8789ef34e62f9eb7352e6c777f44452988fd12baf2ef"),
+            (0x43, "208a3501fe4659df55b4ad458a55d077c99074079b10d7365f7983ef83a70094"),
+            (0xc1, "d7c5e826d5d7414346afbd1edfa0f5123796a55aab275aaeb3a3b6d50c8c7aba"),
+            (0xc2, "d9ab3b510910c0e4bbd6568c23c2bed4e86809234781ea9b50c48e6ae652b4e7"),
+            (0xc3, "4a172ee4cb1ca4d03f73b2da1d8986f165d45b5d7488a1a688c96d51d0ca941a"),
+        ];
+        for (hash_type, expected) in vectors {
+            let hash_type = AnyPrevoutSighashType::from_consensus_u8(hash_type).unwrap();
+            let sighash = cache
+                .taproot_anyprevout_signature_hash(
+                    1,
+                    &prevouts,
+                    None,
+                    (leaf_hash, 0xFFFFFFFF),
+                    hash_type,
+                )
+                .unwrap();
+            assert_eq!(sighash.to_byte_array(), <[u8; 32]>::from_hex(expected).unwrap());
+        }
+
+        let annex = Annex::new(&[0x50, 0xaa]).unwrap();
+        let vectors = [
+            (0xc3, "f028f60160dbcccabe12917e5f1f349063ec934262332f8e8092ff00140d5445"),
+            (0x41, "267a9aa540a71d4e00e08310fae21535bfeb434c032652af03a50fbd3d33a841"),
+        ];
+        for (hash_type, expected) in vectors {
+            let hash_type = AnyPrevoutSighashType::from_consensus_u8(hash_type).unwrap();
+            let sighash = cache
+                .taproot_anyprevout_signature_hash(
+                    1,
+                    &prevouts,
+                    Some(annex.clone()),
+                    (leaf_hash, 3),
+                    hash_type,
+                )
+                .unwrap();
+            assert_eq!(sighash.to_byte_array(), <[u8; 32]>::from_hex(expected).unwrap());
+        }
+
+        // The same implementation agrees with ours for BIP341 signatures.
+        let sighash = cache
+            .taproot_signature_hash(
+                1,
+                &prevouts,
+                None,
+                Some((leaf_hash, 0xFFFFFFFF)),
+                TapSighashType::All,
+            )
+            .unwrap();
+        let expected = "0eff6513178a11fa6ab581ce88b205bf0409a51d89084373fee54eac3e3ea26e";
+        assert_eq!(sighash.to_byte_array(), <[u8; 32]>::from_hex(expected).unwrap());
+    }
+
     #[test]
     fn test_sighashes_with_annex() {
         test_taproot_sighash(
@@ -2024,6 +2728,67 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sighash_midstates() {
+        let tx = deserialize::<Transaction>(
+            &hex!(
+                "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f000000\
+                0000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a01000000\
+                00ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093\
+                510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000"
+            ),
+        ).unwrap();
+        let spk = ScriptBuf::from_hex("00141d0f172a0ecb48aee1be1f2687d2963ae33f71a1").unwrap();
+        let prevouts = [
+            TxOut {
+                value: Amount::from_sat(1000),
+                script_pubkey: ScriptBuf::from_hex(
+                    "51200000000000000000000000000000000000000000000000000000000000000000",
+                )
+                .unwrap(),
+            },
+            TxOut { value: Amount::from_sat(600_000_000), script_pubkey: spk.clone() },
+        ];
+
+        let midstates = SighashMidstates::with_prevouts(&tx, &prevouts).unwrap();
+        assert_eq!(SighashMidstates::with_prevouts(&tx, &prevouts[..1]), Err(PrevoutsSizeError));
+        assert_eq!(
+            midstates.hash_prevouts().as_byte_array(),
+            &Vec::from_hex("96b827c8483d4e9b96712b6713a7b68d6e8003a781feba36c31143470b4efd37")
+                .unwrap()[..],
+        );
+        assert_eq!(midstates.sha_amounts(), Some(TaprootCache::new(&prevouts).amounts));
+        assert_eq!(SighashMidstates::new(&tx).sha_amounts(), None);
+
+        let mut cache = SighashCache::new(&tx);
+        assert_eq!(cache.midstates(Some(&prevouts)), Ok(midstates));
+        assert_eq!(cache.midstates::<TxOut>(None), Ok(midstates));
+
+        let ty = TapSighashType::All;
+        let expected_taproot =
+            cache.taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), ty).unwrap();
+        let expected_segwit =
+            cache.p2wpkh_signature_hash(1, &spk, prevouts[1].value, EcdsaSighashType::All).unwrap();
+
+        // Each thread signs its own input without recomputing the midstates.
+        let taproot = {
+            let (tx, prevouts) = (tx.clone(), prevouts.clone());
+            std::thread::spawn(move || {
+                let mut cache = SighashCache::with_midstates(&tx, midstates);
+                cache.taproot_key_spend_signature_hash(0, &Prevouts::All(&prevouts), ty).unwrap()
+            })
+        };
+        let segwit = {
+            let (tx, value) = (tx.clone(), prevouts[1].value);
+            std::thread::spawn(move || {
+                let mut cache = SighashCache::with_midstates(&tx, midstates);
+                cache.p2wpkh_signature_hash(1, &spk, value, EcdsaSighashType::All).unwrap()
+            })
+        };
+        assert_eq!(taproot.join().unwrap(), expected_taproot);
+        assert_eq!(segwit.join().unwrap(), expected_segwit);
+    }
+
     #[test]
     fn bip143_p2wpkh_nested_in_p2sh() {
         let tx = deserialize::<Transaction>(
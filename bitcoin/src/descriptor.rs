@@ -0,0 +1,523 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Output script descriptors.
+//!
+//! Implements parsing and serialization of the non-miniscript output descriptors defined in
+//! [BIP-380] and the following BIPs, together with the descriptor checksum. Only single hex
+//! encoded public keys are supported, key origins and extended keys are not. For the full
+//! descriptor language use the `miniscript` crate.
+//!
+//! [BIP-380]: <https://github.com/bitcoin/bips/blob/master/bip-0380.mediawiki>
+
+use core::fmt;
+use core::str::FromStr;
+
+use internals::write_err;
+use secp256k1::{Secp256k1, Verification, XOnlyPublicKey};
+
+use crate::address::{self, Address, NetworkUnchecked};
+use crate::blockdata::opcodes::all::OP_CHECKMULTISIG;
+use crate::blockdata::script::{Builder, ScriptBuf};
+use crate::crypto::key::{
+    CompressedPublicKey, ParseCompressedPublicKeyError, ParsePublicKeyError, PublicKey,
+};
+use crate::network::Network;
+use crate::prelude::*;
+
+/// Maximum number of keys in a `multi()` expression inside `wsh()`.
+const MAX_MULTISIG_KEYS: usize = 20;
+
+/// Characters allowed in a descriptor, ordered as required by the checksum algorithm.
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+
+/// Characters used to encode the checksum.
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Length of the descriptor checksum.
+const CHECKSUM_LENGTH: usize = 8;
+
+/// An output script descriptor.
+///
+/// Parsing accepts descriptors with or without a checksum, [`fmt::Display`] always appends it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Descriptor {
+    /// `pk(KEY)`: pay to public key.
+    Pk(PublicKey),
+    /// `pkh(KEY)`: pay to public key hash.
+    Pkh(PublicKey),
+    /// `wpkh(KEY)`: pay to witness public key hash.
+    Wpkh(CompressedPublicKey),
+    /// `sh(wpkh(KEY))`: pay to witness public key hash nested in P2SH.
+    ShWpkh(CompressedPublicKey),
+    /// `wsh(multi(k,KEY_1,...,KEY_n))`: `k`-of-`n` multisig in pay to witness script hash.
+    WshMulti {
+        /// Number of signatures required to spend.
+        threshold: usize,
+        /// The public keys in script order.
+        keys: Vec<CompressedPublicKey>,
+    },
+    /// `tr(KEY)`: pay to taproot with key path spending only.
+    ///
+    /// A 33 byte key is accepted when parsing but serialized as an x-only key.
+    Tr(XOnlyPublicKey),
+    /// `addr(ADDR)`: the script pubkey of an address.
+    Addr(Address<NetworkUnchecked>),
+    /// `raw(HEX)`: a raw script pubkey.
+    Raw(ScriptBuf),
+}
+
+impl Descriptor {
+    /// Returns the script pubkey described by this descriptor.
+    pub fn script_pubkey<C: Verification>(&self, secp: &Secp256k1<C>) -> ScriptBuf {
+        use Descriptor::*;
+
+        match *self {
+            Pk(ref pk) => ScriptBuf::new_p2pk(pk),
+            Pkh(ref pk) => ScriptBuf::new_p2pkh(&pk.pubkey_hash()),
+            Wpkh(ref pk) => ScriptBuf::new_p2wpkh(&pk.wpubkey_hash()),
+            ShWpkh(ref pk) =>
+                ScriptBuf::new_p2sh(&ScriptBuf::new_p2wpkh(&pk.wpubkey_hash()).script_hash()),
+            WshMulti { threshold, ref keys } =>
+                ScriptBuf::new_p2wsh(&multisig_script(threshold, keys).wscript_hash()),
+            Tr(key) => ScriptBuf::new_p2tr(secp, key, None),
+            Addr(ref address) => address.assume_checked_ref().script_pubkey(),
+            Raw(ref script) => script.clone(),
+        }
+    }
+
+    /// Returns the redeem script of `sh()` descriptors.
+    pub fn redeem_script(&self) -> Option<ScriptBuf> {
+        match *self {
+            Descriptor::ShWpkh(ref pk) => Some(ScriptBuf::new_p2wpkh(&pk.wpubkey_hash())),
+            _ => None,
+        }
+    }
+
+    /// Returns the witness script of `wsh()` descriptors.
+    pub fn witness_script(&self) -> Option<ScriptBuf> {
+        match *self {
+            Descriptor::WshMulti { threshold, ref keys } => Some(multisig_script(threshold, keys)),
+            _ => None,
+        }
+    }
+
+    /// Returns the address for this descriptor on `network`.
+    ///
+    /// Returns `None` for `pk()` descriptors, `raw()` descriptors without an address form and
+    /// `addr()` descriptors with an address that is not valid for `network`.
+    pub fn address<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        network: Network,
+    ) -> Option<Address> {
+        use Descriptor::*;
+
+        match *self {
+            Pk(_) => None,
+            Pkh(ref pk) => Some(Address::p2pkh(pk, network)),
+            Wpkh(ref pk) => Some(Address::p2wpkh(pk, network)),
+            ShWpkh(ref pk) => Some(Address::p2shwpkh(pk, network)),
+            WshMulti { threshold, ref keys } =>
+                Some(Address::p2wsh(&multisig_script(threshold, keys), network)),
+            Tr(key) => Some(Address::p2tr(secp, key, None, network)),
+            Addr(ref address) => address.clone().require_network(network).ok(),
+            Raw(ref script) => Address::from_script(script, network).ok(),
+        }
+    }
+
+    /// Writes the descriptor without the checksum.
+    fn fmt_body(&self, f: &mut dyn fmt::Write) -> fmt::Result {
+        use Descriptor::*;
+
+        match *self {
+            Pk(ref pk) => write!(f, "pk({})", pk),
+            Pkh(ref pk) => write!(f, "pkh({})", pk),
+            Wpkh(ref pk) => write!(f, "wpkh({})", pk),
+            ShWpkh(ref pk) => write!(f, "sh(wpkh({}))", pk),
+            WshMulti { threshold, ref keys } => {
+                write!(f, "wsh(multi({}", threshold)?;
+                for key in keys {
+                    write!(f, ",{}", key)?;
+                }
+                f.write_str("))")
+            }
+            Tr(ref key) => write!(f, "tr({})", key),
+            Addr(ref address) => write!(f, "addr({})", address.assume_checked_ref()),
+            Raw(ref script) => write!(f, "raw({:x})", script.as_bytes().as_hex()),
+        }
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut body = String::new();
+        self.fmt_body(&mut body)?;
+        let checksum = checksum(&body).expect("serialized descriptors only use valid characters");
+        write!(f, "{}#{}", body, checksum)
+    }
+}
+
+impl FromStr for Descriptor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let desc = match s.split_once('#') {
+            Some((desc, found)) => {
+                if found.len() != CHECKSUM_LENGTH {
+                    return Err(Error::InvalidChecksumLength(found.len()));
+                }
+                let expected = checksum(desc)?;
+                if expected != found {
+                    return Err(Error::ChecksumMismatch { expected, found: found.to_owned() });
+                }
+                desc
+            }
+            None => {
+                checksum(s)?;
+                s
+            }
+        };
+
+        let (name, args) = split_call(desc)?;
+        match name {
+            "pk" => Ok(Descriptor::Pk(args.parse()?)),
+            "pkh" => Ok(Descriptor::Pkh(args.parse()?)),
+            "wpkh" => Ok(Descriptor::Wpkh(args.parse()?)),
+            "sh" => match split_call(args)? {
+                ("wpkh", key) => Ok(Descriptor::ShWpkh(key.parse()?)),
+                (inner, _) => Err(Error::UnsupportedExpression(format!("sh({})", inner))),
+            },
+            "wsh" => match split_call(args)? {
+                ("multi", args) => parse_multi(args),
+                (inner, _) => Err(Error::UnsupportedExpression(format!("wsh({})", inner))),
+            },
+            "tr" => {
+                if args.contains(',') {
+                    return Err(Error::UnsupportedExpression("tr() with script tree".to_owned()));
+                }
+                Ok(Descriptor::Tr(parse_x_only_key(args)?))
+            }
+            "addr" => Ok(Descriptor::Addr(args.parse()?)),
+            "raw" => Ok(Descriptor::Raw(ScriptBuf::from_hex(args)?)),
+            name => Err(Error::UnsupportedExpression(name.to_owned())),
+        }
+    }
+}
+
+/// Computes the checksum of a descriptor (without a `#` suffix).
+pub fn checksum(desc: &str) -> Result<String, Error> {
+    let mut c = 1;
+    let mut class = 0;
+    let mut class_count = 0;
+    for ch in desc.chars() {
+        let pos = INPUT_CHARSET.find(ch).ok_or(Error::InvalidCharacter(ch))? as u64;
+        c = poly_mod(c, pos & 31);
+        class = class * 3 + (pos >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = poly_mod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = poly_mod(c, class);
+    }
+    for _ in 0..CHECKSUM_LENGTH {
+        c = poly_mod(c, 0);
+    }
+    c ^= 1;
+
+    let checksum = (0..CHECKSUM_LENGTH)
+        .map(|i| char::from(CHECKSUM_CHARSET[((c >> (5 * (7 - i))) & 31) as usize]))
+        .collect();
+    Ok(checksum)
+}
+
+/// The BCH code polynomial used by the descriptor checksum.
+fn poly_mod(mut c: u64, val: u64) -> u64 {
+    let c0 = c >> 35;
+    c = ((c & 0x7ffffffff) << 5) ^ val;
+    if c0 & 1 > 0 {
+        c ^= 0xf5dee51989;
+    }
+    if c0 & 2 > 0 {
+        c ^= 0xa9fdca3312;
+    }
+    if c0 & 4 > 0 {
+        c ^= 0x1bab10e32d;
+    }
+    if c0 & 8 > 0 {
+        c ^= 0x3706b1677a;
+    }
+    if c0 & 16 > 0 {
+        c ^= 0x644d626ffd;
+    }
+    c
+}
+
+/// Splits `name(args)` into `name` and `args`.
+fn split_call(s: &str) -> Result<(&str, &str), Error> {
+    match s.find('(') {
+        Some(open) if s.ends_with(')') => Ok((&s[..open], &s[open + 1..s.len() - 1])),
+        _ => Err(Error::Malformed),
+    }
+}
+
+/// Parses the arguments of `multi(k,KEY_1,...,KEY_n)`.
+fn parse_multi(args: &str) -> Result<Descriptor, Error> {
+    let mut args = args.split(',');
+    let threshold = args.next().and_then(|k| k.parse().ok()).ok_or(Error::Malformed)?;
+    let keys = args.map(CompressedPublicKey::from_str).collect::<Result<Vec<_>, _>>()?;
+    if keys.len() > MAX_MULTISIG_KEYS || threshold == 0 || threshold > keys.len() {
+        return Err(Error::InvalidMultisig { threshold, keys: keys.len() });
+    }
+    Ok(Descriptor::WshMulti { threshold, keys })
+}
+
+/// Parses a taproot internal key given either as an x-only or as a compressed key.
+fn parse_x_only_key(s: &str) -> Result<XOnlyPublicKey, Error> {
+    if s.len() == 66 {
+        Ok(CompressedPublicKey::from_str(s)?.into())
+    } else {
+        XOnlyPublicKey::from_str(s).map_err(Error::XOnlyPublicKey)
+    }
+}
+
+/// Builds the witness script of a `multi()` expression.
+fn multisig_script(threshold: usize, keys: &[CompressedPublicKey]) -> ScriptBuf {
+    let mut builder = Builder::new().push_int(threshold as i64);
+    for key in keys {
+        builder = builder.push_slice(key.to_bytes());
+    }
+    builder.push_int(keys.len() as i64).push_opcode(OP_CHECKMULTISIG).into_script()
+}
+
+/// Error parsing a [`Descriptor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The descriptor contains a character not allowed in descriptors.
+    InvalidCharacter(char),
+    /// The checksum following `#` is not 8 characters long.
+    InvalidChecksumLength(usize),
+    /// The checksum does not match the descriptor.
+    ChecksumMismatch {
+        /// The checksum computed from the descriptor.
+        expected: String,
+        /// The checksum included in the string.
+        found: String,
+    },
+    /// The script expression is valid but not supported by this module.
+    UnsupportedExpression(String),
+    /// The descriptor is not a well-formed script expression.
+    Malformed,
+    /// Invalid `multi()` threshold or number of keys.
+    InvalidMultisig {
+        /// The required number of signatures.
+        threshold: usize,
+        /// The number of public keys.
+        keys: usize,
+    },
+    /// Invalid public key.
+    PublicKey(ParsePublicKeyError),
+    /// Invalid public key where a compressed key is required.
+    CompressedPublicKey(ParseCompressedPublicKeyError),
+    /// Invalid x-only public key.
+    XOnlyPublicKey(secp256k1::Error),
+    /// Invalid address in `addr()`.
+    Address(address::ParseError),
+    /// Invalid hex in `raw()`.
+    Hex(hex::HexToBytesError),
+}
+
+internals::impl_from_infallible!(Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            InvalidCharacter(c) => write!(f, "invalid character in descriptor: {:?}", c),
+            InvalidChecksumLength(len) =>
+                write!(f, "descriptor checksum must be 8 characters: length={}", len),
+            ChecksumMismatch { ref expected, ref found } =>
+                write!(f, "descriptor checksum mismatch: expected {} found {}", expected, found),
+            UnsupportedExpression(ref s) => write!(f, "unsupported script expression: {}", s),
+            Malformed => f.write_str("malformed descriptor"),
+            InvalidMultisig { threshold, keys } =>
+                write!(f, "invalid multisig threshold {} for {} keys", threshold, keys),
+            PublicKey(ref e) => write_err!(f, "invalid public key"; e),
+            CompressedPublicKey(ref e) => write_err!(f, "invalid compressed public key"; e),
+            XOnlyPublicKey(ref e) => write_err!(f, "invalid x-only public key"; e),
+            Address(ref e) => write_err!(f, "invalid address"; e),
+            Hex(ref e) => write_err!(f, "invalid raw script hex"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            PublicKey(ref e) => Some(e),
+            CompressedPublicKey(ref e) => Some(e),
+            XOnlyPublicKey(ref e) => Some(e),
+            Address(ref e) => Some(e),
+            Hex(ref e) => Some(e),
+            InvalidCharacter(_)
+            | InvalidChecksumLength(_)
+            | ChecksumMismatch { .. }
+            | UnsupportedExpression(_)
+            | Malformed
+            | InvalidMultisig { .. } => None,
+        }
+    }
+}
+
+impl From<ParsePublicKeyError> for Error {
+    fn from(e: ParsePublicKeyError) -> Self { Self::PublicKey(e) }
+}
+
+impl From<ParseCompressedPublicKeyError> for Error {
+    fn from(e: ParseCompressedPublicKeyError) -> Self { Self::CompressedPublicKey(e) }
+}
+
+impl From<address::ParseError> for Error {
+    fn from(e: address::ParseError) -> Self { Self::Address(e) }
+}
+
+impl From<hex::HexToBytesError> for Error {
+    fn from(e: hex::HexToBytesError) -> Self { Self::Hex(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn descriptor_checksum() {
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").unwrap(), "02wpgw69");
+        assert_eq!(checksum("raw(deadbeef)\u{e9}"), Err(Error::InvalidCharacter('\u{e9}')));
+
+        assert!("raw(deadbeef)#89f8spxm".parse::<Descriptor>().is_ok());
+        assert!("raw(deadbeef)".parse::<Descriptor>().is_ok());
+        assert_eq!(
+            "raw(deadbeef)#89f8spxx".parse::<Descriptor>(),
+            Err(Error::ChecksumMismatch {
+                expected: "89f8spxm".to_owned(),
+                found: "89f8spxx".to_owned()
+            })
+        );
+        assert_eq!(
+            "raw(deadbeef)#89f8spx".parse::<Descriptor>(),
+            Err(Error::InvalidChecksumLength(7))
+        );
+    }
+
+    #[test]
+    fn descriptor_roundtrip() {
+        let secp = Secp256k1::verification_only();
+        let test_vectors = [
+            (
+                "pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)#gn28ywm7",
+                "210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798ac",
+            ),
+            (
+                "pkh(02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5)#8fhd9pwu",
+                "76a91406afd46bcdfd22ef94ac122aa11f241244a37ecc88ac",
+            ),
+            (
+                "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)#8zl0zxma",
+                "00147dd65592d0ab2fe0d0257d571abf032cd9db93dc",
+            ),
+            (
+                "sh(wpkh(03fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a1460297556))#qkrrc7je",
+                "a914cc6ffbc0bf31af759451068f90ba7a0272b6b33287",
+            ),
+            (
+                "tr(a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd)#dh4fyxrd",
+                "512077aab6e066f8a7419c5ab714c12c67d25007ed55a43cadcacb4d7a970a093f11",
+            ),
+            ("raw(deadbeef)#89f8spxm", "deadbeef"),
+            (
+                "addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)#02wpgw69",
+                "76a914399c39ac90dac26965fb55fdb2035e6715fdac4e88ac",
+            ),
+        ];
+        for (desc, script_pubkey) in test_vectors {
+            let parsed = desc.parse::<Descriptor>().unwrap();
+            assert_eq!(parsed.to_string(), desc);
+            assert_eq!(parsed.script_pubkey(&secp), ScriptBuf::from_hex(script_pubkey).unwrap());
+        }
+    }
+
+    #[test]
+    fn descriptor_wsh_multi() {
+        let secp = Secp256k1::verification_only();
+        let desc = "wsh(multi(2,03a0434d9e47f3c86235477c7b1ae6ae5d3442d49b1943c2b752a68e2a47e247c7,03774ae7f858a9411e5ef4246b70c65aac5649980be5c17891bbec17895da008cb,03d01115d548e7561b15c38f004d734633687cf4419620095bc5b0f47070afe85a))#en3tu306";
+        let parsed = desc.parse::<Descriptor>().unwrap();
+        assert_eq!(parsed.to_string(), desc);
+        match parsed {
+            Descriptor::WshMulti { threshold, ref keys } => {
+                assert_eq!(threshold, 2);
+                assert_eq!(keys.len(), 3);
+            }
+            _ => panic!("expected wsh(multi())"),
+        }
+        assert!(parsed.script_pubkey(&secp).is_p2wsh());
+
+        let key = "03a0434d9e47f3c86235477c7b1ae6ae5d3442d49b1943c2b752a68e2a47e247c7";
+        assert_eq!(
+            format!("wsh(multi(2,{}))", key).parse::<Descriptor>(),
+            Err(Error::InvalidMultisig { threshold: 2, keys: 1 })
+        );
+        assert_eq!(
+            format!("wsh(multi(0,{}))", key).parse::<Descriptor>(),
+            Err(Error::InvalidMultisig { threshold: 0, keys: 1 })
+        );
+    }
+
+    #[test]
+    fn descriptor_address() {
+        let secp = Secp256k1::verification_only();
+        let wpkh = "wpkh(02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9)"
+            .parse::<Descriptor>()
+            .unwrap();
+        assert_eq!(
+            wpkh.address(&secp, Network::Bitcoin).unwrap().to_string(),
+            "bc1q0ht9tyks4vh7p5p904t340cr9nvahy7u3re7zg"
+        );
+
+        let addr = "addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)".parse::<Descriptor>().unwrap();
+        assert!(addr.address(&secp, Network::Testnet).is_some());
+        assert!(addr.address(&secp, Network::Bitcoin).is_none());
+
+        let pk = "pk(0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798)"
+            .parse::<Descriptor>()
+            .unwrap();
+        assert!(pk.address(&secp, Network::Bitcoin).is_none());
+    }
+
+    #[test]
+    fn descriptor_parse_errors() {
+        let key = "02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+        assert_eq!(
+            format!("sh(pk({}))", key).parse::<Descriptor>(),
+            Err(Error::UnsupportedExpression("sh(pk)".to_owned()))
+        );
+        assert_eq!(
+            format!("combo({})", key).parse::<Descriptor>(),
+            Err(Error::UnsupportedExpression("combo".to_owned()))
+        );
+        assert_eq!(format!("pk({}", key).parse::<Descriptor>(), Err(Error::Malformed));
+        assert!(matches!(
+            "wpkh(04a34b99f22c790c4e36b2b3c2c35a36db06226e41c692fc82b8b56ac1c540c5bd5b8dec5235a0fa8722476c7709c02559e3aa73aa03918ba2d492eea75abea235)".parse::<Descriptor>(),
+            Err(Error::CompressedPublicKey(_))
+        ));
+        assert!(matches!("raw(xyz)".parse::<Descriptor>(), Err(Error::Hex(_))));
+    }
+}
@@ -27,6 +27,13 @@
 //!                            `std::error::Error`. At this time there's a hack to
 //!                            achieve the same without this feature but it could
 //!                            happen the implementations diverge one day.
+//! * `interpreter` - enables the pure Rust script interpreter in [`script::interpreter`].
+//! * `slip132` - enables SLIP-132 extended key version bytes (`ypub`, `zpub`, ...) in [`slip132`].
+//! * `bip38` - enables BIP-38 passphrase-protected private keys in [`bip38`].
+//! * `anyprevout` - enables the BIP-118 `SIGHASH_ANYPREVOUT` sighash types in [`sighash`].
+//! * `auxpow` - enables parsing of merged mining headers in [`auxpow`].
+//! * `async` - (dependency), enables asynchronous reading and writing of P2P messages.
+//! * `v2transport` - (dependency), enables the BIP-324 encrypted transport in [`p2p::v2transport`].
 
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
@@ -96,21 +103,30 @@ mod serde_utils;
 #[macro_use]
 pub mod p2p;
 pub mod address;
+#[cfg(feature = "auxpow")]
+pub mod auxpow;
 pub mod bip152;
 pub mod bip158;
 pub mod bip32;
+#[cfg(feature = "bip38")]
+pub mod bip38;
+pub mod bip85;
 pub mod blockdata;
 pub mod consensus;
+pub mod descriptor;
 // Private until we either make this a crate or flatten it - still to be decided.
 pub(crate) mod crypto;
 pub mod error;
 pub mod hash_types;
 pub mod merkle_tree;
+pub mod musig;
 pub mod network;
 pub mod policy;
 pub mod pow;
 pub mod psbt;
 pub mod sign_message;
+#[cfg(feature = "slip132")]
+pub mod slip132;
 pub mod taproot;
 
 #[rustfmt::skip]                // Keep public re-exports separate.
@@ -123,6 +139,7 @@ pub use crate::{
     blockdata::block::{self, Block, BlockHash, TxMerkleNode, WitnessMerkleNode, WitnessCommitment},
     blockdata::constants,
     blockdata::fee_rate::FeeRate,
+    blockdata::header_chain::{self, HeaderChain},
     blockdata::locktime::{self, absolute, relative},
     blockdata::opcodes::{self, Opcode},
     blockdata::script::witness_program::{self, WitnessProgram},
@@ -132,12 +149,14 @@ pub use crate::{
     blockdata::weight::Weight,
     blockdata::witness::{self, Witness},
     consensus::encode::VarInt,
+    crypto::adaptor,
+    crypto::contract,
     crypto::ecdsa,
-    crypto::key::{self, PrivateKey, PubkeyHash, PublicKey, CompressedPublicKey, WPubkeyHash, XOnlyPublicKey},
+    crypto::key::{self, PrivateKey, PubkeyHash, PublicKey, CompressedPublicKey, LegacyPublicKey, WPubkeyHash, XOnlyPublicKey},
     crypto::sighash::{self, LegacySighash, SegwitV0Sighash, TapSighash, TapSighashTag},
     merkle_tree::MerkleBlock,
     network::{Network, NetworkKind},
-    pow::{CompactTarget, Target, Work},
+    pow::{ChainWork, CompactTarget, Target, Work},
     psbt::Psbt,
     sighash::{EcdsaSighashType, TapSighashType},
     taproot::{TapBranchTag, TapLeafHash, TapLeafTag, TapNodeHash, TapTweakHash, TapTweakTag},
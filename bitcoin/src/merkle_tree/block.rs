@@ -38,14 +38,17 @@
 //! assert_eq!(1, index[0]);
 //! ```
 
+use core::borrow::Borrow;
 use core::fmt;
 
 use hashes::Hash;
 use io::{BufRead, Write};
 
 use self::MerkleBlockError::*;
+use crate::bip158;
 use crate::blockdata::block::{self, Block, TxMerkleNode};
-use crate::blockdata::transaction::{Transaction, Txid};
+use crate::blockdata::script::Script;
+use crate::blockdata::transaction::{OutPoint, Transaction, Txid};
 use crate::blockdata::weight::Weight;
 use crate::consensus::encode::{self, Decodable, Encodable};
 use crate::prelude::*;
@@ -125,6 +128,47 @@ impl MerkleBlock {
         MerkleBlock { header: *header, txn: pmt }
     }
 
+    /// Create a MerkleBlock from a block, that contains proofs for the transactions matching a
+    /// BIP 158 style script predicate.
+    ///
+    /// A transaction matches if `match_script` returns true for any of the scripts that a BIP 158
+    /// script filter includes for it: its outputs' scripts, excluding OP_RETURN scripts, and the
+    /// scripts of the outputs it spends, which are looked up with `script_for_coin` (see
+    /// [`BlockFilter::new_script_filter`]). This can be used to prove the transactions relevant
+    /// to a client after its filter query matched the block.
+    ///
+    /// [`BlockFilter::new_script_filter`]: crate::bip158::BlockFilter::new_script_filter
+    pub fn from_block_with_script_predicate<M, S, F>(
+        block: &Block,
+        script_for_coin: M,
+        match_script: F,
+    ) -> Result<Self, bip158::Error>
+    where
+        M: Fn(&OutPoint) -> Result<S, bip158::Error>,
+        S: Borrow<Script>,
+        F: Fn(&Script) -> bool,
+    {
+        let mut block_txids = Vec::with_capacity(block.txdata.len());
+        let mut matches = Vec::with_capacity(block.txdata.len());
+        for tx in &block.txdata {
+            let mut matched = tx.output.iter().any(|output| {
+                !output.script_pubkey.is_op_return() && match_script(&output.script_pubkey)
+            });
+            if !tx.is_coinbase() {
+                for input in &tx.input {
+                    // Look up every spent script, like the filter does, so errors are reported.
+                    let script = script_for_coin(&input.previous_output)?;
+                    matched |= match_script(script.borrow());
+                }
+            }
+            block_txids.push(tx.compute_txid());
+            matches.push(matched);
+        }
+
+        let pmt = PartialMerkleTree::from_txids(&block_txids, &matches);
+        Ok(MerkleBlock { header: block.header, txn: pmt })
+    }
+
     /// Extract the matching txid's represented by this partial merkle tree
     /// and their respective indices within the partial tree.
     /// returns Ok(()) on success, or error in case of failure
@@ -141,6 +185,17 @@ impl MerkleBlock {
             Err(MerkleRootMismatch)
         }
     }
+
+    /// Returns the matching txids along with their positions in the block.
+    ///
+    /// This is the same as [`MerkleBlock::extract_matches`] but returns each txid paired with its
+    /// index in the block, in block order.
+    pub fn matches(&self) -> Result<Vec<(u32, Txid)>, MerkleBlockError> {
+        let mut matches = vec![];
+        let mut indexes = vec![];
+        self.extract_matches(&mut matches, &mut indexes)?;
+        Ok(indexes.into_iter().zip(matches).collect())
+    }
 }
 
 impl Encodable for MerkleBlock {
@@ -720,6 +775,49 @@ mod tests {
 
         assert_eq!(matches[1], txid1);
         assert_eq!(index[1], 8);
+
+        assert_eq!(merkle_block.matches().unwrap(), vec![(1, txid2), (8, txid1)]);
+    }
+
+    #[test]
+    fn merkleblock_construct_from_script_predicate() {
+        use crate::bip158;
+        use crate::blockdata::script::ScriptBuf;
+
+        let block = get_block_13b8a();
+        let spent_script = ScriptBuf::from_bytes(vec![0x51]);
+        let script = block.txdata[3].output[0].script_pubkey.clone();
+
+        let merkle_block = MerkleBlock::from_block_with_script_predicate(
+            &block,
+            |_| Ok(spent_script.clone()),
+            |s| s == script.as_script(),
+        )
+        .unwrap();
+        let matches = merkle_block.matches().unwrap();
+        assert!(matches.contains(&(3, block.txdata[3].compute_txid())));
+        for (index, _) in &matches {
+            let tx = &block.txdata[*index as usize];
+            assert!(tx.output.iter().any(|output| output.script_pubkey == script));
+        }
+
+        // Every transaction spends the script, except the coinbase.
+        let merkle_block = MerkleBlock::from_block_with_script_predicate(
+            &block,
+            |_| Ok(spent_script.clone()),
+            |s| s == spent_script.as_script(),
+        )
+        .unwrap();
+        let indexes = merkle_block.matches().unwrap().iter().map(|m| m.0).collect::<Vec<_>>();
+        assert_eq!(indexes, (1..block.txdata.len() as u32).collect::<Vec<_>>());
+
+        let err = MerkleBlock::from_block_with_script_predicate(
+            &block,
+            |outpoint| Err::<ScriptBuf, _>(bip158::Error::UtxoMissing(*outpoint)),
+            |_| true,
+        )
+        .unwrap_err();
+        assert!(matches!(err, bip158::Error::UtxoMissing(_)));
     }
 
     /// Create a CMerkleBlock using a list of txids which will not be found in the given block
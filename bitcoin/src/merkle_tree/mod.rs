@@ -14,6 +14,7 @@
 //! ```
 
 mod block;
+mod proof;
 
 use core::cmp::min;
 use core::iter;
@@ -26,7 +27,10 @@ use crate::prelude::*;
 
 #[rustfmt::skip]
 #[doc(inline)]
-pub use self::block::{MerkleBlock, MerkleBlockError, PartialMerkleTree};
+pub use self::{
+    block::{MerkleBlock, MerkleBlockError, PartialMerkleTree},
+    proof::MerkleProof,
+};
 
 /// Calculates the merkle root of a list of *hashes*, inline (in place) in `hashes`.
 ///
@@ -52,39 +56,107 @@ where
 
 /// Calculates the merkle root of an iterator of *hashes*.
 ///
+/// The root is computed while iterating, using scratch space for only one hash per level of the
+/// tree, so the hashes don't need to be collected first.
+///
 /// # Returns
 /// - `None` if `hashes` is empty. The merkle root of an empty tree of hashes is undefined.
 /// - `Some(hash)` if `hashes` contains one element. A single hash is by definition the merkle root.
 /// - `Some(merkle_root)` if length of `hashes` is greater than one.
-pub fn calculate_root<T, I>(mut hashes: I) -> Option<T>
+pub fn calculate_root<T, I>(hashes: I) -> Option<T>
+where
+    T: Hash + Encodable,
+    <T as Hash>::Engine: Write,
+    I: Iterator<Item = T>,
+{
+    // `inner[level]` holds the pending left hash at `level`, which is set iff bit `level` of
+    // `count` is set. This is the algorithm used by Bitcoin Core.
+    let mut inner = [T::all_zeros(); 64];
+    let mut count = 0_u64;
+
+    for mut hash in hashes {
+        count += 1;
+        let mut level = 0;
+        while count & (1 << level) == 0 {
+            hash = combine(&inner[level], &hash);
+            level += 1;
+        }
+        inner[level] = hash;
+    }
+    if count == 0 {
+        return None;
+    }
+
+    // Complete the tree, duplicating the last hash of each level with an odd number of hashes.
+    let mut level = count.trailing_zeros() as usize;
+    let mut hash = inner[level];
+    while count != 1 << level {
+        hash = combine(&hash, &hash);
+        count += 1 << level;
+        level += 1;
+        while count & (1 << level) == 0 {
+            hash = combine(&inner[level], &hash);
+            level += 1;
+        }
+    }
+    Some(hash)
+}
+
+/// Calculates the merkle root of an iterator of *hashes* along with the intermediate levels.
+///
+/// The levels are ordered from the one directly above the leaves (`hashes`) up to the one directly
+/// below the root, each level listing the hashes of its nodes from left to right.
+///
+/// # Returns
+/// - `None` if `hashes` is empty. The merkle root of an empty tree of hashes is undefined.
+/// - `Some((merkle_root, levels))` otherwise, `levels` is empty if there are at most two hashes.
+pub fn calculate_root_with_levels<T, I>(hashes: I) -> Option<(T, Vec<Vec<T>>)>
 where
     T: Hash + Encodable,
     <T as Hash>::Engine: Write,
     I: Iterator<Item = T>,
 {
+    let mut hashes = hashes.peekable();
     let first = hashes.next()?;
-    let second = match hashes.next() {
-        Some(second) => second,
-        None => return Some(first),
-    };
+    if hashes.peek().is_none() {
+        return Some((first, vec![]));
+    }
 
-    let mut hashes = iter::once(first).chain(iter::once(second)).chain(hashes);
+    let mut levels = vec![next_level(iter::once(first).chain(hashes))];
+    while levels[levels.len() - 1].len() > 1 {
+        let level = next_level(levels[levels.len() - 1].iter().copied());
+        levels.push(level);
+    }
+    let root = levels.pop().expect("at least one level")[0];
+    Some((root, levels))
+}
 
-    // We need a local copy to pass to `merkle_root_r`. It's more efficient to do the first loop of
-    // processing as we make the copy instead of copying the whole iterator.
+// Hashes pairs of `hashes`, duplicating the last one if there is an odd number of them.
+fn next_level<T, I>(mut hashes: I) -> Vec<T>
+where
+    T: Hash + Encodable,
+    <T as Hash>::Engine: Write,
+    I: Iterator<Item = T>,
+{
     let (min, max) = hashes.size_hint();
-    let mut alloc = Vec::with_capacity(max.unwrap_or(min) / 2 + 1);
-
+    let mut level = Vec::with_capacity(max.unwrap_or(min) / 2 + 1);
     while let Some(hash1) = hashes.next() {
-        // If the size is odd, use the last element twice.
         let hash2 = hashes.next().unwrap_or(hash1);
-        let mut encoder = T::engine();
-        hash1.consensus_encode(&mut encoder).expect("in-memory writers don't error");
-        hash2.consensus_encode(&mut encoder).expect("in-memory writers don't error");
-        alloc.push(T::from_engine(encoder));
+        level.push(combine(&hash1, &hash2));
     }
+    level
+}
 
-    Some(merkle_root_r(&mut alloc))
+// Hashes the concatenation of `hash1` and `hash2`, the parent of two nodes.
+pub(crate) fn combine<T>(hash1: &T, hash2: &T) -> T
+where
+    T: Hash + Encodable,
+    <T as Hash>::Engine: Write,
+{
+    let mut encoder = T::engine();
+    hash1.consensus_encode(&mut encoder).expect("in-memory writers don't error");
+    hash2.consensus_encode(&mut encoder).expect("in-memory writers don't error");
+    T::from_engine(encoder)
 }
 
 // `hashes` must contain at least one hash.
@@ -100,10 +172,7 @@ where
     for idx in 0..((hashes.len() + 1) / 2) {
         let idx1 = 2 * idx;
         let idx2 = min(idx1 + 1, hashes.len() - 1);
-        let mut encoder = T::engine();
-        hashes[idx1].consensus_encode(&mut encoder).expect("in-memory writers don't error");
-        hashes[idx2].consensus_encode(&mut encoder).expect("in-memory writers don't error");
-        hashes[idx] = T::from_engine(encoder);
+        hashes[idx] = combine(&hashes[idx1], &hashes[idx2]);
     }
     let half_len = hashes.len() / 2 + hashes.len() % 2;
 
@@ -136,4 +205,28 @@ mod tests {
         let from_array = calculate_root_inline(&mut hashes_array);
         assert_eq!(from_iter, from_array);
     }
+
+    #[test]
+    fn calculate_root_matches_inline() {
+        let hashes = (0..40_u8).map(|i| sha256d::Hash::hash(&[i])).collect::<Vec<_>>();
+
+        assert_eq!(calculate_root(hashes.iter().copied().take(0)), None);
+        assert_eq!(calculate_root_with_levels(hashes.iter().copied().take(0)), None);
+        for n in 1..=hashes.len() {
+            let mut inline = hashes[..n].to_vec();
+            let root = calculate_root_inline(&mut inline).unwrap();
+            assert_eq!(calculate_root(hashes[..n].iter().copied()), Some(root));
+
+            let (root_with_levels, levels) =
+                calculate_root_with_levels(hashes[..n].iter().copied()).unwrap();
+            assert_eq!(root_with_levels, root);
+            let mut below = &hashes[..n];
+            for level in &levels {
+                assert_eq!(level.len(), (below.len() + 1) / 2);
+                assert_eq!(level[0], combine(&below[0], below.get(1).unwrap_or(&below[0])));
+                below = level;
+            }
+            assert!(below.len() <= 2);
+        }
+    }
 }
@@ -0,0 +1,179 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Merkle inclusion proofs.
+//!
+//! A [`MerkleProof`] proves that a single transaction is committed to by a block's merkle root. It
+//! consists of the position of the transaction in the block and the hashes of the siblings along
+//! the path from the transaction to the root, which is more compact than a BIP 37
+//! [`MerkleBlock`](super::MerkleBlock) and does not include the block header.
+//!
+//! # Examples
+//!
+//! ```
+//! # use bitcoin::hashes::Hash;
+//! # use bitcoin::merkle_tree::MerkleProof;
+//! # use bitcoin::{merkle_tree, TxMerkleNode, Txid};
+//! let txids = (0..5_u8).map(|i| Txid::hash(&[i])).collect::<Vec<_>>();
+//! let merkle_root: TxMerkleNode =
+//!     merkle_tree::calculate_root(txids.iter().map(|txid| txid.to_raw_hash())).unwrap().into();
+//!
+//! let proof = MerkleProof::from_txids(&txids, &txids[3]).expect("txid is in the block");
+//! assert!(proof.verify(txids[3], merkle_root));
+//! assert!(!proof.verify(txids[2], merkle_root));
+//! ```
+
+use core::iter;
+
+use io::{BufRead, Write};
+
+use super::{calculate_root_with_levels, combine};
+use crate::blockdata::block::TxMerkleNode;
+use crate::blockdata::transaction::Txid;
+use crate::consensus::encode::{self, Decodable, Encodable};
+use crate::prelude::*;
+
+/// The maximum number of hashes in a proof, a proof can't be deeper than the index has bits.
+const MAX_BRANCH_LEN: usize = 32;
+
+/// A proof that a transaction is included in a block.
+///
+/// Verifying a proof only shows that the transaction hashes to the merkle root together with the
+/// branch. Since the merkle tree doesn't commit to its depth, a verifier that knows the number of
+/// transactions in the block should also check that [`MerkleProof::branch`] has the depth of the
+/// tree, otherwise an inner node may be passed off as a transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MerkleProof {
+    /// The position of the transaction in the block.
+    index: u32,
+    /// The sibling hashes from the transaction up to, but not including, the root.
+    branch: Vec<TxMerkleNode>,
+}
+
+impl MerkleProof {
+    /// Creates a proof that `txid` is included in the block with transactions `block_txids`.
+    ///
+    /// Returns `None` if `txid` is not one of `block_txids`. If it occurs more than once the proof
+    /// is for the first occurrence.
+    pub fn from_txids(block_txids: &[Txid], txid: &Txid) -> Option<MerkleProof> {
+        let index = block_txids.iter().position(|t| t == txid)?;
+        let leaves = block_txids.iter().map(|txid| TxMerkleNode::from_raw_hash(txid.to_raw_hash()));
+        let (_, levels) = calculate_root_with_levels(leaves.clone())?;
+
+        let leaves = leaves.collect::<Vec<_>>();
+        let mut branch = Vec::with_capacity(levels.len() + 1);
+        let mut position = index;
+        // A single transaction is the root itself and needs no branch.
+        for level in iter::once(&leaves).chain(&levels).take_while(|level| level.len() > 1) {
+            // If the level has an odd number of nodes the last one is hashed with itself.
+            let sibling = level.get(position ^ 1).unwrap_or(&level[position]);
+            branch.push(*sibling);
+            position /= 2;
+        }
+
+        Some(MerkleProof { index: index as u32, branch })
+    }
+
+    /// Returns the position of the transaction in the block.
+    pub fn index(&self) -> u32 { self.index }
+
+    /// Returns the sibling hashes from the transaction up to, but not including, the root.
+    pub fn branch(&self) -> &[TxMerkleNode] { &self.branch }
+
+    /// Computes the merkle root of a block including `txid` at the position of this proof.
+    pub fn compute_root(&self, txid: Txid) -> TxMerkleNode {
+        let mut node = TxMerkleNode::from_raw_hash(txid.to_raw_hash());
+        for (level, sibling) in self.branch.iter().enumerate() {
+            node = if self.index & (1 << level) == 0 {
+                combine(&node, sibling)
+            } else {
+                combine(sibling, &node)
+            };
+        }
+        node
+    }
+
+    /// Checks that `txid` is included in the block with merkle root `merkle_root`.
+    ///
+    /// Typically `merkle_root` is the [`merkle_root`](crate::block::Header::merkle_root) of a block
+    /// header that has already been validated.
+    pub fn verify(&self, txid: Txid, merkle_root: TxMerkleNode) -> bool {
+        self.compute_root(txid) == merkle_root
+    }
+}
+
+impl Encodable for MerkleProof {
+    fn consensus_encode<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let len = self.index.consensus_encode(w)? + self.branch.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for MerkleProof {
+    fn consensus_decode_from_finite_reader<R: BufRead + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let index: u32 = Decodable::consensus_decode_from_finite_reader(r)?;
+        let branch: Vec<TxMerkleNode> = Decodable::consensus_decode_from_finite_reader(r)?;
+
+        if branch.len() > MAX_BRANCH_LEN {
+            return Err(encode::Error::ParseFailed("merkle proof branch too long"));
+        }
+        if branch.len() < MAX_BRANCH_LEN && index >> branch.len() != 0 {
+            return Err(encode::Error::ParseFailed("merkle proof index out of range"));
+        }
+        Ok(MerkleProof { index, branch })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hashes::Hash;
+
+    use super::*;
+    use crate::blockdata::block::Block;
+    use crate::consensus::encode::{deserialize, serialize};
+    use crate::merkle_tree::calculate_root;
+
+    #[test]
+    fn merkle_proof() {
+        for n in 1..=20_u8 {
+            let txids = (0..n).map(|i| Txid::hash(&[i])).collect::<Vec<_>>();
+            let root: TxMerkleNode =
+                calculate_root(txids.iter().map(|txid| txid.to_raw_hash())).unwrap().into();
+
+            for (index, txid) in txids.iter().enumerate() {
+                let proof = MerkleProof::from_txids(&txids, txid).unwrap();
+                assert_eq!(proof.index(), index as u32);
+                assert_eq!(proof.branch().len(), (n as f64).log2().ceil() as usize);
+                assert!(proof.verify(*txid, root));
+                assert_eq!(proof.verify(txids[0], root), index == 0);
+
+                let serialized = serialize(&proof);
+                assert_eq!(serialized.len(), 4 + 1 + 32 * proof.branch().len());
+                assert_eq!(deserialize::<MerkleProof>(&serialized).unwrap(), proof);
+            }
+        }
+        assert_eq!(MerkleProof::from_txids(&[Txid::hash(&[0])], &Txid::hash(&[1])), None);
+    }
+
+    #[test]
+    fn merkle_proof_block() {
+        // testnet block 000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b
+        let segwit_block = include_bytes!("../../tests/data/testnet_block_000000000000045e0b1660b6445b5e5c5ab63c9a4f956be7e1e69be04fa4497b.raw");
+        let block: Block = deserialize(&segwit_block[..]).unwrap();
+        let txids = block.txdata.iter().map(|tx| tx.compute_txid()).collect::<Vec<_>>();
+
+        let proof = MerkleProof::from_txids(&txids, &txids[6]).unwrap();
+        assert!(proof.verify(txids[6], block.header.merkle_root));
+        assert!(!proof.verify(txids[7], block.header.merkle_root));
+    }
+
+    #[test]
+    fn merkle_proof_decode_invalid() {
+        let proof = MerkleProof { index: 4, branch: vec![TxMerkleNode::all_zeros(); 2] };
+        assert!(deserialize::<MerkleProof>(&serialize(&proof)).is_err());
+
+        let proof = MerkleProof { index: 0, branch: vec![TxMerkleNode::all_zeros(); 33] };
+        assert!(deserialize::<MerkleProof>(&serialize(&proof)).is_err());
+    }
+}
@@ -0,0 +1,877 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP 327 MuSig2 multi-signatures.
+//!
+//! Implementation of the MuSig2 protocol for aggregating the public keys of several signers into a
+//! single BIP 340 (taproot) key and jointly producing a single BIP 340 signature for it.
+//!
+//! A signing session goes as follows:
+//!
+//! 1. All signers aggregate their public keys with [`KeyAggContext::new`], optionally applying a
+//!    taproot tweak with [`KeyAggContext::tap_tweak`].
+//! 2. Each signer generates a nonce pair with [`SecretNonce::generate`] and sends the
+//!    [`PublicNonce`] to the others.
+//! 3. The public nonces are aggregated with [`AggregatedNonce::new`] and each signer creates a
+//!    [`Session`] for the message, then signs with [`Session::partial_sign`].
+//! 4. The partial signatures are checked with [`Session::partial_verify`] and combined into the
+//!    final signature with [`Session::aggregate`].
+//!
+//! A secret nonce must never be used more than once, doing so leaks the secret key. This is why
+//! [`SecretNonce`] is neither `Copy` nor `Clone` and is consumed when signing.
+//!
+//! Key aggregation, nonce generation and signing use the tagged hashes specified by BIP 327, so
+//! sessions interoperate with other BIP 327 implementations such as the `musig` module of
+//! libsecp256k1. This is checked against the BIP's test vectors.
+//!
+//! ### Relevant BIPS
+//!
+//! * [BIP 327 - MuSig2 for BIP340-compatible Multi-Signatures](https://github.com/bitcoin/bips/blob/master/bip-0327.mediawiki)
+
+use core::fmt;
+
+use secp256k1::{constants, schnorr, Secp256k1, SecretKey, Verification, XOnlyPublicKey};
+
+use crate::crypto::ec::{base_mul, generator, has_even_y, point_add, point_mul, tagged_hash, ModN};
+use crate::crypto::key::{CompressedPublicKey, TweakedPublicKey};
+use crate::prelude::*;
+use crate::taproot::{TapNodeHash, TapTweakHash};
+
+/// Sorts `public_keys` lexicographically by their serialization (`KeySort` in BIP 327).
+///
+/// Sorting makes the aggregated key independent of the order in which signers provide their keys.
+pub fn sort_public_keys(public_keys: &mut [CompressedPublicKey]) {
+    public_keys.sort_unstable_by_key(CompressedPublicKey::to_bytes);
+}
+
+/// The key aggregation context of a set of signers.
+///
+/// Holds the aggregated public key along with the information required to sign for it, including
+/// any tweaks applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyAggContext {
+    public_keys: Vec<CompressedPublicKey>,
+    list_hash: [u8; 32],
+    second_key: Option<CompressedPublicKey>,
+    agg_key: secp256k1::PublicKey,
+    /// Whether the accumulated sign factor (`gacc` in BIP 327) is -1.
+    negated: bool,
+    /// The accumulated tweak (`tacc` in BIP 327).
+    tweak: [u8; 32],
+}
+
+impl KeyAggContext {
+    /// Aggregates `public_keys` (`KeyAgg` in BIP 327).
+    ///
+    /// The order of the keys matters, use [`sort_public_keys`] to obtain an order independent
+    /// result.
+    pub fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        public_keys: &[CompressedPublicKey],
+    ) -> Result<KeyAggContext, Error> {
+        let first = public_keys.first().ok_or(Error::NoKeys)?;
+        let serialized = public_keys.iter().map(CompressedPublicKey::to_bytes).collect::<Vec<_>>();
+        let list_hash =
+            tagged_hash("KeyAgg list", &serialized.iter().map(|pk| &pk[..]).collect::<Vec<_>>());
+        let second_key = public_keys.iter().find(|pk| *pk != first).copied();
+
+        let mut ctx = KeyAggContext {
+            public_keys: public_keys.to_vec(),
+            list_hash,
+            second_key,
+            agg_key: first.0,
+            negated: false,
+            tweak: constants::ZERO,
+        };
+        let agg_key = public_keys
+            .iter()
+            .fold(None, |acc, pk| point_add(acc, point_mul(secp, Some(pk.0), ctx.coefficient(pk))));
+        ctx.agg_key = agg_key.ok_or(Error::PointAtInfinity)?;
+        Ok(ctx)
+    }
+
+    /// Returns the public keys of the signers, in the order they were aggregated.
+    pub fn public_keys(&self) -> &[CompressedPublicKey] { &self.public_keys }
+
+    /// Returns the x-only aggregated public key, including any tweaks applied.
+    ///
+    /// This is the key signatures are valid for.
+    pub fn aggregated_key(&self) -> XOnlyPublicKey { self.agg_key.x_only_public_key().0 }
+
+    /// Returns the full aggregated public key, including any tweaks applied.
+    pub fn aggregated_public_key(&self) -> secp256k1::PublicKey { self.agg_key }
+
+    /// Adds `tweak` times the generator to the aggregated public key (plain tweaking).
+    ///
+    /// This is used for BIP 32 derivation from the aggregated key.
+    pub fn plain_tweak<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        tweak: &secp256k1::Scalar,
+    ) -> Result<(), Error> {
+        self.apply_tweak(secp, tweak, false)
+    }
+
+    /// Adds `tweak` times the generator to the x-only aggregated public key (x-only tweaking).
+    pub fn xonly_tweak<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        tweak: &secp256k1::Scalar,
+    ) -> Result<(), Error> {
+        self.apply_tweak(secp, tweak, true)
+    }
+
+    /// Applies the taproot tweak committing to `merkle_root` to the aggregated public key.
+    ///
+    /// The current aggregated key is the taproot internal key, the returned key is the output key
+    /// and the same as returned by [`TapTweak::tap_tweak`] on the internal key. Signatures
+    /// produced afterwards are valid for key path spends of the output.
+    ///
+    /// [`TapTweak::tap_tweak`]: crate::key::TapTweak::tap_tweak
+    pub fn tap_tweak<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        merkle_root: Option<TapNodeHash>,
+    ) -> Result<TweakedPublicKey, Error> {
+        let tweak = TapTweakHash::from_key_and_tweak(self.aggregated_key(), merkle_root);
+        self.xonly_tweak(secp, &tweak.to_scalar())?;
+        Ok(TweakedPublicKey::dangerous_assume_tweaked(self.aggregated_key()))
+    }
+
+    /// Implements `ApplyTweak` from BIP 327.
+    fn apply_tweak<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        tweak: &secp256k1::Scalar,
+        is_xonly: bool,
+    ) -> Result<(), Error> {
+        let negate = is_xonly && !has_even_y(&self.agg_key);
+        let t = ModN::from_bytes(tweak.to_be_bytes()).expect("scalar is less than the order");
+        let g = ModN::one().negate_if(negate);
+        let agg_key = point_add(point_mul(secp, Some(self.agg_key), g), base_mul(secp, t));
+
+        self.agg_key = agg_key.ok_or(Error::PointAtInfinity)?;
+        self.negated ^= negate;
+        let tacc = ModN::from_bytes(self.tweak).expect("valid tweak");
+        self.tweak = t.add(tacc.negate_if(negate)).to_bytes();
+        Ok(())
+    }
+
+    /// Returns the key aggregation coefficient of `public_key` (`KeyAggCoeffInternal`).
+    fn coefficient(&self, public_key: &CompressedPublicKey) -> ModN {
+        if Some(*public_key) == self.second_key {
+            ModN::one()
+        } else {
+            let hash =
+                tagged_hash("KeyAgg coefficient", &[&self.list_hash, &public_key.to_bytes()]);
+            ModN::from_bytes_reduced(hash)
+        }
+    }
+
+    /// Returns the coefficient of `public_key` after checking it is part of the aggregation.
+    fn signer_coefficient(&self, public_key: &CompressedPublicKey) -> Result<ModN, Error> {
+        if !self.public_keys.contains(public_key) {
+            return Err(Error::UnknownKey);
+        }
+        Ok(self.coefficient(public_key))
+    }
+}
+
+/// The secret part of a signer's nonce pair.
+///
+/// Must be used for a single signature only and is consumed by [`Session::partial_sign`].
+pub struct SecretNonce {
+    k1: SecretKey,
+    k2: SecretKey,
+    public_key: CompressedPublicKey,
+}
+
+impl SecretNonce {
+    /// Generates a nonce pair for the signer with `public_key` (`NonceGen` in BIP 327).
+    ///
+    /// `session_rand` must be 32 bytes freshly drawn from a cryptographically secure random number
+    /// generator for each call. The optional arguments provide defense in depth against a bad
+    /// random number generator: the signer's secret key, the aggregated key, the message and any
+    /// extra input that is unique to the session.
+    pub fn generate<C: Verification>(
+        secp: &Secp256k1<C>,
+        session_rand: [u8; 32],
+        public_key: CompressedPublicKey,
+        secret_key: Option<&SecretKey>,
+        agg_key: Option<&XOnlyPublicKey>,
+        msg: Option<&[u8]>,
+        extra_input: Option<&[u8]>,
+    ) -> (SecretNonce, PublicNonce) {
+        let mut rand = session_rand;
+        if let Some(secret_key) = secret_key {
+            let aux = tagged_hash("MuSig/aux", &[&session_rand]);
+            for ((r, s), a) in rand.iter_mut().zip(&secret_key.secret_bytes()).zip(&aux) {
+                *r = s ^ a;
+            }
+        }
+
+        let public_key_bytes = public_key.to_bytes();
+        let agg_key_bytes = agg_key.map(XOnlyPublicKey::serialize);
+        let agg_key_bytes = agg_key_bytes.as_ref().map_or(&[][..], |k| &k[..]);
+        let msg_prefix = match msg {
+            Some(msg) => {
+                let mut prefix = vec![1];
+                prefix.extend_from_slice(&(msg.len() as u64).to_be_bytes());
+                prefix
+            }
+            None => vec![0],
+        };
+        let extra_input = extra_input.unwrap_or(&[]);
+
+        let nonce = |i: u8| {
+            let hash = tagged_hash(
+                "MuSig/nonce",
+                &[
+                    &rand,
+                    &[public_key_bytes.len() as u8],
+                    &public_key_bytes,
+                    &[agg_key_bytes.len() as u8],
+                    agg_key_bytes,
+                    &msg_prefix,
+                    msg.unwrap_or(&[]),
+                    &(extra_input.len() as u32).to_be_bytes(),
+                    extra_input,
+                    &[i],
+                ],
+            );
+            ModN::from_bytes_reduced(hash).0.expect("negligible probability of a zero nonce")
+        };
+        let (k1, k2) = (nonce(0), nonce(1));
+
+        let public_nonce = PublicNonce {
+            r1: base_mul(secp, ModN(Some(k1))).expect("non-zero nonce"),
+            r2: base_mul(secp, ModN(Some(k2))).expect("non-zero nonce"),
+        };
+        (SecretNonce { k1, k2, public_key }, public_nonce)
+    }
+}
+
+impl fmt::Debug for SecretNonce {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("SecretNonce(..)") }
+}
+
+/// The public part of a signer's nonce pair, to be shared with the other signers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PublicNonce {
+    r1: secp256k1::PublicKey,
+    r2: secp256k1::PublicKey,
+}
+
+impl PublicNonce {
+    /// Serializes the nonce as two compressed points.
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0; 66];
+        bytes[..33].copy_from_slice(&self.r1.serialize());
+        bytes[33..].copy_from_slice(&self.r2.serialize());
+        bytes
+    }
+
+    /// Deserializes a nonce from two compressed points.
+    pub fn from_slice(data: &[u8]) -> Result<PublicNonce, Error> {
+        if data.len() != 66 {
+            return Err(Error::InvalidPublicNonce);
+        }
+        let point = |bytes| {
+            CompressedPublicKey::from_slice(bytes)
+                .map(|pk| pk.0)
+                .map_err(|_| Error::InvalidPublicNonce)
+        };
+        Ok(PublicNonce { r1: point(&data[..33])?, r2: point(&data[33..])? })
+    }
+}
+
+/// The sum of the public nonces of all signers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AggregatedNonce {
+    r1: Option<secp256k1::PublicKey>,
+    r2: Option<secp256k1::PublicKey>,
+}
+
+impl AggregatedNonce {
+    /// Aggregates the public nonces of all signers (`NonceAgg` in BIP 327).
+    pub fn new(nonces: &[PublicNonce]) -> AggregatedNonce {
+        nonces.iter().fold(AggregatedNonce { r1: None, r2: None }, |acc, nonce| AggregatedNonce {
+            r1: point_add(acc.r1, Some(nonce.r1)),
+            r2: point_add(acc.r2, Some(nonce.r2)),
+        })
+    }
+
+    /// Serializes the nonce as two compressed points, the point at infinity is encoded as zeros.
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0; 66];
+        if let Some(r1) = self.r1 {
+            bytes[..33].copy_from_slice(&r1.serialize());
+        }
+        if let Some(r2) = self.r2 {
+            bytes[33..].copy_from_slice(&r2.serialize());
+        }
+        bytes
+    }
+
+    /// Deserializes a nonce from two compressed points, either of which may be all zeros.
+    pub fn from_slice(data: &[u8]) -> Result<AggregatedNonce, Error> {
+        if data.len() != 66 {
+            return Err(Error::InvalidAggregatedNonce);
+        }
+        let point = |bytes: &[u8]| {
+            if bytes == [0; 33] {
+                Ok(None)
+            } else {
+                CompressedPublicKey::from_slice(bytes)
+                    .map(|pk| Some(pk.0))
+                    .map_err(|_| Error::InvalidAggregatedNonce)
+            }
+        };
+        Ok(AggregatedNonce { r1: point(&data[..33])?, r2: point(&data[33..])? })
+    }
+}
+
+/// A signer's share of the final signature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PartialSignature(ModN);
+
+impl PartialSignature {
+    /// Serializes the partial signature as a 32 byte big endian integer.
+    pub fn serialize(&self) -> [u8; 32] { self.0.to_bytes() }
+
+    /// Deserializes a partial signature, fails if it is not less than the curve order.
+    pub fn from_slice(data: &[u8]) -> Result<PartialSignature, Error> {
+        let bytes = data.try_into().map_err(|_| Error::InvalidPartialSignature)?;
+        ModN::from_bytes(bytes).map(PartialSignature).ok_or(Error::InvalidPartialSignature)
+    }
+}
+
+/// A signing session for a single message, shared by all signers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    key_agg: KeyAggContext,
+    nonce_coefficient: ModN,
+    nonce: secp256k1::PublicKey,
+    challenge: ModN,
+}
+
+impl Session {
+    /// Creates the session for signing `msg` with the aggregated key of `key_agg`.
+    ///
+    /// For taproot key path spends `msg` is the BIP 341 sighash.
+    pub fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        key_agg: &KeyAggContext,
+        agg_nonce: &AggregatedNonce,
+        msg: &[u8],
+    ) -> Session {
+        let agg_key = key_agg.aggregated_key().serialize();
+        let hash = tagged_hash("MuSig/noncecoef", &[&agg_nonce.serialize(), &agg_key, msg]);
+        let nonce_coefficient = ModN::from_bytes_reduced(hash);
+
+        let nonce = point_add(agg_nonce.r1, point_mul(secp, agg_nonce.r2, nonce_coefficient))
+            .unwrap_or_else(generator);
+        let nonce_x = nonce.x_only_public_key().0.serialize();
+        let hash = tagged_hash("BIP0340/challenge", &[&nonce_x, &agg_key, msg]);
+        let challenge = ModN::from_bytes_reduced(hash);
+
+        Session { key_agg: key_agg.clone(), nonce_coefficient, nonce, challenge }
+    }
+
+    /// Creates the partial signature of the signer with `secret_key` (`Sign` in BIP 327).
+    ///
+    /// Consumes `sec_nonce` so that it can not be reused.
+    pub fn partial_sign<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        sec_nonce: SecretNonce,
+        secret_key: &SecretKey,
+    ) -> Result<PartialSignature, Error> {
+        let negate_nonce = !has_even_y(&self.nonce);
+        let k1 = ModN(Some(sec_nonce.k1)).negate_if(negate_nonce);
+        let k2 = ModN(Some(sec_nonce.k2)).negate_if(negate_nonce);
+
+        let d = ModN(Some(*secret_key));
+        let public_key = CompressedPublicKey(base_mul(secp, d).expect("non-zero secret key"));
+        if public_key != sec_nonce.public_key {
+            return Err(Error::NonceKeyMismatch);
+        }
+        let a = self.key_agg.signer_coefficient(&public_key)?;
+        let d = d.negate_if(self.key_sign_negated());
+
+        let s = k1.add(self.nonce_coefficient.mul(k2)).add(self.challenge.mul(a).mul(d));
+        Ok(PartialSignature(s))
+    }
+
+    /// Verifies the partial signature of the signer with `public_key` and `pub_nonce`.
+    pub fn partial_verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        partial_sig: &PartialSignature,
+        pub_nonce: &PublicNonce,
+        public_key: &CompressedPublicKey,
+    ) -> Result<(), Error> {
+        let nonce = point_add(
+            Some(pub_nonce.r1),
+            point_mul(secp, Some(pub_nonce.r2), self.nonce_coefficient),
+        );
+        let nonce = if has_even_y(&self.nonce) { nonce } else { nonce.map(|r| r.negate(secp)) };
+
+        let a = self.key_agg.signer_coefficient(public_key)?;
+        let key = ModN::one().negate_if(self.key_sign_negated());
+        let key = point_mul(secp, Some(public_key.0), key);
+
+        let expected = point_add(nonce, point_mul(secp, key, self.challenge.mul(a)));
+        if base_mul(secp, partial_sig.0) != expected {
+            return Err(Error::VerificationFailed);
+        }
+        Ok(())
+    }
+
+    /// Aggregates the partial signatures of all signers into a BIP 340 signature for the
+    /// aggregated key (`PartialSigAgg` in BIP 327).
+    ///
+    /// The result is only valid if all partial signatures are, use [`Session::partial_verify`] to
+    /// identify misbehaving signers.
+    pub fn aggregate(&self, partial_sigs: &[PartialSignature]) -> schnorr::Signature {
+        let tweak = ModN::from_bytes(self.key_agg.tweak).expect("valid tweak");
+        let tweak = self.challenge.mul(tweak).negate_if(!has_even_y(&self.key_agg.agg_key));
+        let s = partial_sigs.iter().fold(tweak, |acc, sig| acc.add(sig.0));
+
+        let mut bytes = [0; 64];
+        bytes[..32].copy_from_slice(&self.nonce.x_only_public_key().0.serialize());
+        bytes[32..].copy_from_slice(&s.to_bytes());
+        schnorr::Signature::from_slice(&bytes).expect("64 bytes")
+    }
+
+    /// Returns whether secret keys have to be negated, i.e. whether `g * gacc` is -1.
+    fn key_sign_negated(&self) -> bool { !has_even_y(&self.key_agg.agg_key) ^ self.key_agg.negated }
+}
+
+/// MuSig2 error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// No public keys were provided for aggregation.
+    NoKeys,
+    /// The aggregated key is the point at infinity (negligible probability unless malicious).
+    PointAtInfinity,
+    /// The public key is not part of the key aggregation.
+    UnknownKey,
+    /// The secret nonce was generated for a different key than the one signing.
+    NonceKeyMismatch,
+    /// Invalid public nonce encoding.
+    InvalidPublicNonce,
+    /// Invalid aggregated nonce encoding.
+    InvalidAggregatedNonce,
+    /// Invalid partial signature encoding.
+    InvalidPartialSignature,
+    /// The partial signature is not valid.
+    VerificationFailed,
+}
+
+internals::impl_from_infallible!(Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            NoKeys => f.write_str("no public keys to aggregate"),
+            PointAtInfinity => f.write_str("aggregated public key is the point at infinity"),
+            UnknownKey => f.write_str("public key is not part of the key aggregation"),
+            NonceKeyMismatch => f.write_str("secret nonce was generated for a different key"),
+            InvalidPublicNonce => f.write_str("invalid public nonce"),
+            InvalidAggregatedNonce => f.write_str("invalid aggregated nonce"),
+            InvalidPartialSignature => f.write_str("invalid partial signature encoding"),
+            VerificationFailed => f.write_str("partial signature verification failed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            NoKeys
+            | PointAtInfinity
+            | UnknownKey
+            | NonceKeyMismatch
+            | InvalidPublicNonce
+            | InvalidAggregatedNonce
+            | InvalidPartialSignature
+            | VerificationFailed => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use hashes::Hash;
+    use hex::FromHex;
+
+    use super::*;
+    use crate::key::TapTweak;
+
+    // Public keys from the BIP 327 key aggregation test vectors.
+    const KEYS: [&str; 3] = [
+        "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+        "03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659",
+        "023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66",
+    ];
+
+    fn keys(indices: &[usize]) -> Vec<CompressedPublicKey> {
+        indices.iter().map(|i| CompressedPublicKey::from_str(KEYS[*i]).unwrap()).collect()
+    }
+
+    #[test]
+    fn key_agg_vectors() {
+        let secp = Secp256k1::verification_only();
+        let vectors: [(&[usize], &str); 3] = [
+            (&[0, 1, 2], "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c"),
+            (&[2, 1, 0], "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b"),
+            (&[0, 0, 0], "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935"),
+        ];
+        for (indices, expected) in vectors {
+            let ctx = KeyAggContext::new(&secp, &keys(indices)).unwrap();
+            assert_eq!(ctx.aggregated_key().to_string(), expected);
+        }
+
+        assert_eq!(KeyAggContext::new(&secp, &[]), Err(Error::NoKeys));
+
+        let mut sorted = keys(&[2, 1, 0]);
+        sort_public_keys(&mut sorted);
+        assert_eq!(sorted, keys(&[2, 0, 1]));
+    }
+
+    fn sign(key_agg: &KeyAggContext, secret_keys: &[SecretKey], msg: &[u8]) -> schnorr::Signature {
+        let secp = Secp256k1::new();
+        let (sec_nonces, pub_nonces): (Vec<_>, Vec<_>) = secret_keys
+            .iter()
+            .enumerate()
+            .map(|(i, sk)| {
+                let pk = CompressedPublicKey(sk.public_key(&secp));
+                let agg_key = key_agg.aggregated_key();
+                SecretNonce::generate(
+                    &secp,
+                    [i as u8; 32],
+                    pk,
+                    Some(sk),
+                    Some(&agg_key),
+                    Some(msg),
+                    None,
+                )
+            })
+            .unzip();
+        let agg_nonce = AggregatedNonce::new(&pub_nonces);
+        assert_eq!(AggregatedNonce::from_slice(&agg_nonce.serialize()), Ok(agg_nonce));
+
+        let session = Session::new(&secp, key_agg, &agg_nonce, msg);
+        let partial_sigs = sec_nonces
+            .into_iter()
+            .zip(secret_keys)
+            .zip(&pub_nonces)
+            .map(|((sec_nonce, sk), pub_nonce)| {
+                let sig = session.partial_sign(&secp, sec_nonce, sk).unwrap();
+                let pk = CompressedPublicKey(sk.public_key(&secp));
+                session.partial_verify(&secp, &sig, pub_nonce, &pk).unwrap();
+                assert_eq!(
+                    session.partial_verify(&secp, &sig, &pub_nonces[0], &pk).is_ok(),
+                    pub_nonce == &pub_nonces[0]
+                );
+                assert_eq!(PartialSignature::from_slice(&sig.serialize()), Ok(sig));
+                sig
+            })
+            .collect::<Vec<_>>();
+        session.aggregate(&partial_sigs)
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let secp = Secp256k1::new();
+        let secret_keys =
+            (1..=3u8).map(|i| SecretKey::from_slice(&[i; 32]).unwrap()).collect::<Vec<_>>();
+        let public_keys = secret_keys
+            .iter()
+            .map(|sk| CompressedPublicKey(sk.public_key(&secp)))
+            .collect::<Vec<_>>();
+        let msg = [0x42; 32];
+
+        let key_agg = KeyAggContext::new(&secp, &public_keys).unwrap();
+        let sig = sign(&key_agg, &secret_keys, &msg);
+        let msg = secp256k1::Message::from_digest(msg);
+        secp.verify_schnorr(&sig, &msg, &key_agg.aggregated_key()).unwrap();
+    }
+
+    #[test]
+    fn sign_taproot_output() {
+        let secp = Secp256k1::new();
+        let secret_keys =
+            (4..=5u8).map(|i| SecretKey::from_slice(&[i; 32]).unwrap()).collect::<Vec<_>>();
+        let public_keys = secret_keys
+            .iter()
+            .map(|sk| CompressedPublicKey(sk.public_key(&secp)))
+            .collect::<Vec<_>>();
+        let msg = [0x17; 32];
+
+        let mut key_agg = KeyAggContext::new(&secp, &public_keys).unwrap();
+        let internal_key = key_agg.aggregated_key();
+        let merkle_root = TapNodeHash::from_byte_array([0x99; 32]);
+        let output_key = key_agg.tap_tweak(&secp, Some(merkle_root)).unwrap();
+        assert_eq!(output_key, internal_key.tap_tweak(&secp, Some(merkle_root)).0);
+
+        let sig = sign(&key_agg, &secret_keys, &msg);
+        let msg = secp256k1::Message::from_digest(msg);
+        secp.verify_schnorr(&sig, &msg, &output_key.to_inner()).unwrap();
+    }
+
+    #[test]
+    fn nonce_key_mismatch() {
+        let secp = Secp256k1::new();
+        let sk1 = SecretKey::from_slice(&[1; 32]).unwrap();
+        let sk2 = SecretKey::from_slice(&[2; 32]).unwrap();
+        let pk1 = CompressedPublicKey(sk1.public_key(&secp));
+        let pk2 = CompressedPublicKey(sk2.public_key(&secp));
+
+        let key_agg = KeyAggContext::new(&secp, &[pk1, pk2]).unwrap();
+        let (sec_nonce, pub_nonce) =
+            SecretNonce::generate(&secp, [7; 32], pk1, None, None, None, None);
+        assert_eq!(PublicNonce::from_slice(&pub_nonce.serialize()), Ok(pub_nonce));
+        let session = Session::new(&secp, &key_agg, &AggregatedNonce::new(&[pub_nonce]), b"msg");
+        assert_eq!(session.partial_sign(&secp, sec_nonce, &sk2), Err(Error::NonceKeyMismatch));
+    }
+
+    // The following test vectors are from BIP 327, as included in libsecp256k1 which only keeps
+    // the cases with 32 byte messages.
+
+    fn vector_bytes(value: &serde_json::Value) -> Vec<u8> {
+        Vec::from_hex(value.as_str().unwrap()).unwrap()
+    }
+
+    /// Returns the hex strings in `items` at the positions listed in `indices`.
+    fn select(items: &serde_json::Value, indices: &serde_json::Value) -> Vec<Vec<u8>> {
+        let items = items.as_array().unwrap();
+        indices
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|i| vector_bytes(&items[i.as_u64().unwrap() as usize]))
+            .collect()
+    }
+
+    fn public_keys(bytes: &[Vec<u8>]) -> Option<Vec<CompressedPublicKey>> {
+        bytes.iter().map(|pk| CompressedPublicKey::from_slice(pk).ok()).collect()
+    }
+
+    fn public_nonces(bytes: &[Vec<u8>]) -> Option<Vec<PublicNonce>> {
+        bytes.iter().map(|nonce| PublicNonce::from_slice(nonce).ok()).collect()
+    }
+
+    /// Parses a secret nonce serialized as `k1 || k2 || pk`.
+    fn secret_nonce(bytes: &[u8]) -> Option<SecretNonce> {
+        Some(SecretNonce {
+            k1: SecretKey::from_slice(&bytes[..32]).ok()?,
+            k2: SecretKey::from_slice(&bytes[32..64]).ok()?,
+            public_key: CompressedPublicKey::from_slice(&bytes[64..]).ok()?,
+        })
+    }
+
+    /// Applies the tweaks of a test case in order, fails if a tweak is invalid.
+    fn apply_tweaks(
+        key_agg: &mut KeyAggContext,
+        vectors: &serde_json::Value,
+        case: &serde_json::Value,
+    ) -> Option<()> {
+        let secp = Secp256k1::verification_only();
+        let tweaks = select(&vectors["tweaks"], &case["tweak_indices"]);
+        for (tweak, is_xonly) in tweaks.iter().zip(case["is_xonly"].as_array().unwrap()) {
+            let tweak = secp256k1::Scalar::from_be_bytes(tweak[..].try_into().unwrap()).ok()?;
+            if is_xonly.as_bool().unwrap() {
+                key_agg.xonly_tweak(&secp, &tweak).ok()?;
+            } else {
+                key_agg.plain_tweak(&secp, &tweak).ok()?;
+            }
+        }
+        Some(())
+    }
+
+    #[test]
+    fn nonce_gen_vectors() {
+        let secp = Secp256k1::new();
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/data/bip327_nonce_gen_vectors.json"))
+                .unwrap();
+        for case in vectors["test_cases"].as_array().unwrap() {
+            let optional = |key| case[key].as_str().map(|_| vector_bytes(&case[key]));
+            let rand = vector_bytes(&case["rand_"]).try_into().unwrap();
+            let public_key = CompressedPublicKey::from_slice(&vector_bytes(&case["pk"])).unwrap();
+            let secret_key = optional("sk").map(|sk| SecretKey::from_slice(&sk).unwrap());
+            let agg_key = optional("aggpk").map(|pk| XOnlyPublicKey::from_slice(&pk).unwrap());
+            let msg = optional("msg");
+            let extra_input = optional("extra_in");
+
+            let (sec_nonce, pub_nonce) = SecretNonce::generate(
+                &secp,
+                rand,
+                public_key,
+                secret_key.as_ref(),
+                agg_key.as_ref(),
+                msg.as_deref(),
+                extra_input.as_deref(),
+            );
+            let expected = vector_bytes(&case["expected_secnonce"]);
+            assert_eq!(sec_nonce.k1.secret_bytes(), expected[..32]);
+            assert_eq!(sec_nonce.k2.secret_bytes(), expected[32..64]);
+            assert_eq!(sec_nonce.public_key.to_bytes(), expected[64..]);
+            assert_eq!(pub_nonce.serialize()[..], vector_bytes(&case["expected_pubnonce"]));
+        }
+    }
+
+    #[test]
+    fn nonce_agg_vectors() {
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/data/bip327_nonce_agg_vectors.json"))
+                .unwrap();
+        for case in vectors["valid_test_cases"].as_array().unwrap() {
+            let pnonces = select(&vectors["pnonces"], &case["pnonce_indices"]);
+            let agg_nonce = AggregatedNonce::new(&public_nonces(&pnonces).unwrap());
+            assert_eq!(agg_nonce.serialize()[..], vector_bytes(&case["expected"]));
+        }
+        for case in vectors["error_test_cases"].as_array().unwrap() {
+            let pnonces = select(&vectors["pnonces"], &case["pnonce_indices"]);
+            assert!(public_nonces(&pnonces).is_none());
+        }
+    }
+
+    #[test]
+    fn sign_verify_vectors() {
+        let secp = Secp256k1::new();
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/data/bip327_sign_verify_vectors.json"))
+                .unwrap();
+        let secret_key = SecretKey::from_slice(&vector_bytes(&vectors["sk"])).unwrap();
+        let msgs = vectors["msgs"].as_array().unwrap();
+        let secnonces = vectors["secnonces"].as_array().unwrap();
+        let aggnonces = vectors["aggnonces"].as_array().unwrap();
+        let pnonces = vectors["pnonces"].as_array().unwrap();
+
+        for case in vectors["valid_test_cases"].as_array().unwrap() {
+            let keys = public_keys(&select(&vectors["pubkeys"], &case["key_indices"])).unwrap();
+            let key_agg = KeyAggContext::new(&secp, &keys).unwrap();
+            let agg_nonce =
+                vector_bytes(&aggnonces[case["aggnonce_index"].as_u64().unwrap() as usize]);
+            let agg_nonce = AggregatedNonce::from_slice(&agg_nonce).unwrap();
+            let msg = vector_bytes(&msgs[case["msg_index"].as_u64().unwrap() as usize]);
+            let session = Session::new(&secp, &key_agg, &agg_nonce, &msg);
+
+            let sec_nonce = secret_nonce(&vector_bytes(&secnonces[0])).unwrap();
+            let sig = session.partial_sign(&secp, sec_nonce, &secret_key).unwrap();
+            assert_eq!(sig.serialize()[..], vector_bytes(&case["expected"]));
+
+            // The secret nonce used for signing belongs to the first public nonce.
+            let pub_nonce = PublicNonce::from_slice(&vector_bytes(&pnonces[0])).unwrap();
+            let signer = case["signer_index"].as_u64().unwrap() as usize;
+            session.partial_verify(&secp, &sig, &pub_nonce, &keys[signer]).unwrap();
+        }
+
+        for case in vectors["sign_error_test_cases"].as_array().unwrap() {
+            let result = (|| {
+                let keys = public_keys(&select(&vectors["pubkeys"], &case["key_indices"]))?;
+                let key_agg = KeyAggContext::new(&secp, &keys).ok()?;
+                let agg_nonce =
+                    vector_bytes(&aggnonces[case["aggnonce_index"].as_u64().unwrap() as usize]);
+                let agg_nonce = AggregatedNonce::from_slice(&agg_nonce).ok()?;
+                let msg = vector_bytes(&msgs[case["msg_index"].as_u64().unwrap() as usize]);
+                let session = Session::new(&secp, &key_agg, &agg_nonce, &msg);
+                let sec_nonce =
+                    vector_bytes(&secnonces[case["secnonce_index"].as_u64().unwrap() as usize]);
+                let sec_nonce = secret_nonce(&sec_nonce)?;
+                session.partial_sign(&secp, sec_nonce, &secret_key).ok()
+            })();
+            assert!(result.is_none(), "{}", case);
+        }
+
+        let verify_cases = vectors["verify_fail_test_cases"].as_array().unwrap().iter();
+        for case in verify_cases.chain(vectors["verify_error_test_cases"].as_array().unwrap()) {
+            let result = (|| {
+                let keys = public_keys(&select(&vectors["pubkeys"], &case["key_indices"]))?;
+                let nonces = public_nonces(&select(&vectors["pnonces"], &case["nonce_indices"]))?;
+                let key_agg = KeyAggContext::new(&secp, &keys).ok()?;
+                let msg = vector_bytes(&msgs[case["msg_index"].as_u64().unwrap() as usize]);
+                let session = Session::new(&secp, &key_agg, &AggregatedNonce::new(&nonces), &msg);
+                let sig = PartialSignature::from_slice(&vector_bytes(&case["sig"])).ok()?;
+                let signer = case["signer_index"].as_u64().unwrap() as usize;
+                session.partial_verify(&secp, &sig, &nonces[signer], &keys[signer]).ok()
+            })();
+            assert!(result.is_none(), "{}", case);
+        }
+    }
+
+    #[test]
+    fn tweak_vectors() {
+        let secp = Secp256k1::new();
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/data/bip327_tweak_vectors.json")).unwrap();
+        let secret_key = SecretKey::from_slice(&vector_bytes(&vectors["sk"])).unwrap();
+        let agg_nonce = AggregatedNonce::from_slice(&vector_bytes(&vectors["aggnonce"])).unwrap();
+        let msg = vector_bytes(&vectors["msg"]);
+
+        for case in vectors["valid_test_cases"].as_array().unwrap() {
+            let keys = public_keys(&select(&vectors["pubkeys"], &case["key_indices"])).unwrap();
+            let nonces =
+                public_nonces(&select(&vectors["pnonces"], &case["nonce_indices"])).unwrap();
+            assert_eq!(AggregatedNonce::new(&nonces), agg_nonce);
+            let mut key_agg = KeyAggContext::new(&secp, &keys).unwrap();
+            apply_tweaks(&mut key_agg, &vectors, case).unwrap();
+            let session = Session::new(&secp, &key_agg, &agg_nonce, &msg);
+
+            let sec_nonce = secret_nonce(&vector_bytes(&vectors["secnonce"])).unwrap();
+            let sig = session.partial_sign(&secp, sec_nonce, &secret_key).unwrap();
+            assert_eq!(sig.serialize()[..], vector_bytes(&case["expected"]));
+
+            let signer = case["signer_index"].as_u64().unwrap() as usize;
+            session.partial_verify(&secp, &sig, &nonces[signer], &keys[signer]).unwrap();
+        }
+
+        for case in vectors["error_test_cases"].as_array().unwrap() {
+            let keys = public_keys(&select(&vectors["pubkeys"], &case["key_indices"])).unwrap();
+            let mut key_agg = KeyAggContext::new(&secp, &keys).unwrap();
+            assert!(apply_tweaks(&mut key_agg, &vectors, case).is_none());
+        }
+    }
+
+    #[test]
+    fn sig_agg_vectors() {
+        let secp = Secp256k1::verification_only();
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/data/bip327_sig_agg_vectors.json"))
+                .unwrap();
+        let msg = vector_bytes(&vectors["msg"]);
+
+        for case in vectors["valid_test_cases"].as_array().unwrap() {
+            let keys = public_keys(&select(&vectors["pubkeys"], &case["key_indices"])).unwrap();
+            let mut key_agg = KeyAggContext::new(&secp, &keys).unwrap();
+            apply_tweaks(&mut key_agg, &vectors, case).unwrap();
+            let agg_nonce = AggregatedNonce::from_slice(&vector_bytes(&case["aggnonce"])).unwrap();
+            let session = Session::new(&secp, &key_agg, &agg_nonce, &msg);
+
+            let partial_sigs = select(&vectors["psigs"], &case["psig_indices"])
+                .iter()
+                .map(|sig| PartialSignature::from_slice(sig).unwrap())
+                .collect::<Vec<_>>();
+            let sig = session.aggregate(&partial_sigs);
+            assert_eq!(sig.as_ref()[..], vector_bytes(&case["expected"]));
+            let msg = secp256k1::Message::from_digest_slice(&msg).unwrap();
+            secp.verify_schnorr(&sig, &msg, &key_agg.aggregated_key()).unwrap();
+        }
+
+        for case in vectors["error_test_cases"].as_array().unwrap() {
+            let psigs = select(&vectors["psigs"], &case["psig_indices"]);
+            let signer = case["error"]["signer"].as_u64().unwrap() as usize;
+            assert!(PartialSignature::from_slice(&psigs[signer]).is_err());
+        }
+    }
+}
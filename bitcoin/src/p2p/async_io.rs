@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Asynchronous reading and writing of network messages.
+//!
+//! Implements decoding and encoding of [`RawNetworkMessage`] over the `futures-io`
+//! [`AsyncRead`] and [`AsyncWrite`] traits. Readers and writers of other runtimes, such as tokio,
+//! can be used through their compatibility layers.
+
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use futures_io::{AsyncRead, AsyncWrite};
+
+use crate::consensus::encode;
+use crate::p2p::message::{RawNetworkMessage, HEADER_LEN, MAX_MSG_SIZE};
+
+impl RawNetworkMessage {
+    /// Reads a message from an asynchronous reader.
+    ///
+    /// The header is read first, then exactly the number of payload bytes it declares, so nothing
+    /// past the end of the message is read. Payloads longer than [`MAX_MSG_SIZE`] are rejected
+    /// before they are read.
+    pub async fn consensus_decode_async<R: AsyncRead + Unpin + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let mut message = vec![0u8; HEADER_LEN];
+        ReadExact { reader: r, buf: &mut message[..] }.await.map_err(io::Error::from)?;
+
+        let payload_len = u32::from_le_bytes([message[16], message[17], message[18], message[19]]);
+        let payload_len = payload_len as usize;
+        if payload_len > MAX_MSG_SIZE {
+            return Err(encode::Error::OversizedVectorAllocation {
+                requested: payload_len,
+                max: MAX_MSG_SIZE,
+            });
+        }
+        message.resize(HEADER_LEN + payload_len, 0);
+        ReadExact { reader: r, buf: &mut message[HEADER_LEN..] }.await.map_err(io::Error::from)?;
+
+        encode::deserialize(&message)
+    }
+
+    /// Writes the message to an asynchronous writer, returning the number of bytes written.
+    ///
+    /// The writer is not flushed.
+    pub async fn consensus_encode_async<W: AsyncWrite + Unpin + ?Sized>(
+        &self,
+        w: &mut W,
+    ) -> Result<usize, std::io::Error> {
+        let message = encode::serialize(self);
+        WriteAll { writer: w, buf: &message }.await?;
+        Ok(message.len())
+    }
+}
+
+/// A future filling `buf` from `reader`.
+struct ReadExact<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadExact<'_, R> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        while !this.buf.is_empty() {
+            let n = match Pin::new(&mut *this.reader).poll_read(cx, this.buf) {
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            if n == 0 {
+                return Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            this.buf = &mut mem::take(&mut this.buf)[n..];
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A future writing all of `buf` to `writer`.
+struct WriteAll<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8],
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for WriteAll<'_, W> {
+    type Output = Result<(), std::io::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        while !this.buf.is_empty() {
+            let n = match Pin::new(&mut *this.writer).poll_write(cx, this.buf) {
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            if n == 0 {
+                return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+            }
+            this.buf = &this.buf[n..];
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::ptr;
+    use core::task::{RawWaker, RawWakerVTable, Waker};
+
+    use super::*;
+    use crate::p2p::message::NetworkMessage;
+    use crate::p2p::Magic;
+
+    // Polls `future` until it is ready, the readers and writers below never block.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        fn raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker { raw_waker() }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(ptr::null(), &VTABLE)
+        }
+        let waker = unsafe { Waker::from_raw(raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    // Returns one byte at a time, and is pending before each of them.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        pending: bool,
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<Result<usize, std::io::Error>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            if self.data.is_empty() || buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            buf[0] = self.data[0];
+            self.data = &self.data[1..];
+            Poll::Ready(Ok(1))
+        }
+    }
+
+    #[test]
+    fn async_round_trip() {
+        let ping = RawNetworkMessage::new(Magic::BITCOIN, NetworkMessage::Ping(42));
+        let verack = RawNetworkMessage::new(Magic::BITCOIN, NetworkMessage::Verack);
+        let mut bytes = Vec::new();
+        let len = block_on(ping.consensus_encode_async(&mut bytes)).unwrap();
+        assert_eq!(len, bytes.len());
+        block_on(verack.consensus_encode_async(&mut bytes)).unwrap();
+        let expected = [encode::serialize(&ping), encode::serialize(&verack)].concat();
+        assert_eq!(bytes, expected);
+
+        let mut reader = Trickle { data: &bytes, pending: false };
+        assert_eq!(block_on(RawNetworkMessage::consensus_decode_async(&mut reader)).unwrap(), ping);
+        assert_eq!(
+            block_on(RawNetworkMessage::consensus_decode_async(&mut reader)).unwrap(),
+            verack
+        );
+        assert!(reader.data.is_empty());
+
+        let mut truncated = &bytes[..bytes.len() - 1];
+        block_on(RawNetworkMessage::consensus_decode_async(&mut truncated)).unwrap();
+        assert!(matches!(
+            block_on(RawNetworkMessage::consensus_decode_async(&mut truncated)),
+            Err(encode::Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn async_oversized_payload() {
+        let mut bytes =
+            encode::serialize(&RawNetworkMessage::new(Magic::BITCOIN, NetworkMessage::Verack));
+        bytes[16..20].copy_from_slice(&(MAX_MSG_SIZE as u32 + 1).to_le_bytes());
+        assert!(matches!(
+            block_on(RawNetworkMessage::consensus_decode_async(&mut &bytes[..])),
+            Err(encode::Error::OversizedVectorAllocation { .. })
+        ));
+    }
+}
@@ -0,0 +1,528 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Bitcoin p2p version handshake and message framing.
+//!
+//! This module defines [`PeerStateMachine`], which negotiates a connection with a peer through
+//! the `version`, `verack`, `wtxidrelay` and `sendaddrv2` messages, and [`MessageDecoder`], which
+//! splits the bytes received from a peer into messages. Neither performs any I/O, instead they
+//! are given what was received from the peer and return the messages and [`Action`]s to handle,
+//! so they can be driven by blocking and asynchronous code alike.
+
+use core::fmt;
+
+use internals::write_err;
+
+use crate::consensus::encode;
+use crate::p2p::message::{NetworkMessage, RawNetworkMessage, HEADER_LEN, MAX_MSG_SIZE};
+use crate::p2p::message_network::VersionMessage;
+use crate::p2p::{Magic, ServiceFlags};
+use crate::prelude::*;
+
+/// The lowest protocol version of the peers we connect to.
+pub const MIN_PEER_PROTO_VERSION: u32 = 31800;
+
+/// The protocol version from which `wtxidrelay` is sent between `version` and `verack`.
+pub const WTXID_RELAY_VERSION: u32 = 70016;
+
+/// The protocol version from which `sendaddrv2` is sent between `version` and `verack`.
+///
+/// BIP 155 allows it for any version, but like Bitcoin Core we don't send it to older peers as
+/// some implementations reject messages they don't know.
+pub const SEND_ADDR_V2_VERSION: u32 = 70016;
+
+/// The direction of a connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// We opened the connection, and send our `version` first.
+    Outbound,
+    /// The peer opened the connection, and sends its `version` first.
+    Inbound,
+}
+
+/// What the peer supports, as negotiated by the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerInfo {
+    /// The protocol version used with the peer, the lower of ours and the peer's.
+    pub version: u32,
+    /// The services supported by the peer.
+    pub services: ServiceFlags,
+    /// The user agent of the peer.
+    pub user_agent: String,
+    /// The height of the best chain of the peer when it connected.
+    pub start_height: i32,
+    /// Whether the peer wants transactions relayed to it.
+    pub relay: bool,
+    /// Whether transactions are announced by wtxid rather than txid, as specified by BIP 339.
+    pub wtxid_relay: bool,
+    /// Whether the peer accepts `addrv2` messages, as specified by BIP 155.
+    pub addr_v2: bool,
+}
+
+/// An action to take in response to the handshake progressing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Send the message to the peer.
+    Send(NetworkMessage),
+    /// The `version` of the peer was received, advertising its services.
+    SetServices(ServiceFlags),
+    /// The handshake is complete.
+    Complete(PeerInfo),
+}
+
+/// The state machine of the version handshake with a peer.
+///
+/// Outbound connections [`start`](Self::start) by sending our `version`. Every message received
+/// during the handshake is then passed to [`receive`](Self::receive), until it returns
+/// [`Action::Complete`]. Messages other than those of the handshake are ignored, like Bitcoin
+/// Core does.
+#[derive(Debug, Clone)]
+pub struct PeerStateMachine {
+    our_version: VersionMessage,
+    direction: Direction,
+    version_sent: bool,
+    /// The peer's info, once its `version` is received.
+    peer: Option<PeerInfo>,
+    verack_received: bool,
+}
+
+impl PeerStateMachine {
+    /// Creates the state machine of a handshake sending `our_version`.
+    pub fn new(our_version: VersionMessage, direction: Direction) -> PeerStateMachine {
+        PeerStateMachine {
+            our_version,
+            direction,
+            version_sent: false,
+            peer: None,
+            verack_received: false,
+        }
+    }
+
+    /// Returns the direction of the connection.
+    pub fn direction(&self) -> Direction { self.direction }
+
+    /// Starts the handshake once connected, sending our `version` on outbound connections.
+    pub fn start(&mut self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.direction == Direction::Outbound {
+            self.send_version(&mut actions);
+        }
+        actions
+    }
+
+    /// Handles `message` received from the peer.
+    pub fn receive(&mut self, message: &NetworkMessage) -> Result<Vec<Action>, HandshakeError> {
+        let mut actions = Vec::new();
+        match message {
+            NetworkMessage::Version(version) => {
+                if self.peer.is_some() {
+                    return Err(HandshakeError::DuplicateVersion);
+                }
+                if version.version < MIN_PEER_PROTO_VERSION {
+                    return Err(HandshakeError::ObsoleteVersion(version.version));
+                }
+                if self.direction == Direction::Inbound
+                    && version.nonce != 0
+                    && version.nonce == self.our_version.nonce
+                {
+                    return Err(HandshakeError::SelfConnection);
+                }
+
+                self.send_version(&mut actions);
+                let common_version = self.our_version.version.min(version.version);
+                self.peer = Some(PeerInfo {
+                    version: common_version,
+                    services: version.services,
+                    user_agent: version.user_agent.clone(),
+                    start_height: version.start_height,
+                    relay: version.relay,
+                    wtxid_relay: false,
+                    addr_v2: false,
+                });
+                actions.push(Action::SetServices(version.services));
+                if common_version >= WTXID_RELAY_VERSION {
+                    actions.push(Action::Send(NetworkMessage::WtxidRelay));
+                }
+                if common_version >= SEND_ADDR_V2_VERSION {
+                    actions.push(Action::Send(NetworkMessage::SendAddrV2));
+                }
+                actions.push(Action::Send(NetworkMessage::Verack));
+            }
+            NetworkMessage::Verack => {
+                if self.verack_received {
+                    return Err(HandshakeError::DuplicateVerack);
+                }
+                let peer = self.peer.as_ref().ok_or(HandshakeError::NoVersion("verack"))?;
+                self.verack_received = true;
+                actions.push(Action::Complete(peer.clone()));
+            }
+            NetworkMessage::WtxidRelay => {
+                let peer = self.negotiating("wtxidrelay")?;
+                // Ignored from peers which can't have negotiated it.
+                if peer.version >= WTXID_RELAY_VERSION {
+                    peer.wtxid_relay = true;
+                }
+            }
+            NetworkMessage::SendAddrV2 => self.negotiating("sendaddrv2")?.addr_v2 = true,
+            _ => {}
+        }
+        Ok(actions)
+    }
+
+    /// Returns whether the handshake is complete.
+    pub fn is_complete(&self) -> bool { self.verack_received }
+
+    /// Returns the info of the peer, once its `version` is received.
+    ///
+    /// The info may still change until the handshake is complete.
+    pub fn peer_info(&self) -> Option<&PeerInfo> { self.peer.as_ref() }
+
+    fn send_version(&mut self, actions: &mut Vec<Action>) {
+        if !self.version_sent {
+            self.version_sent = true;
+            actions.push(Action::Send(NetworkMessage::Version(self.our_version.clone())));
+        }
+    }
+
+    /// Returns the peer's info for a message only allowed between `version` and `verack`.
+    fn negotiating(&mut self, command: &'static str) -> Result<&mut PeerInfo, HandshakeError> {
+        if self.verack_received {
+            return Err(HandshakeError::AfterVerack(command));
+        }
+        self.peer.as_mut().ok_or(HandshakeError::NoVersion(command))
+    }
+}
+
+/// Splits the bytes received from a peer into [`RawNetworkMessage`]s.
+///
+/// Bytes are appended with [`push_bytes`](Self::push_bytes) as they arrive, in chunks of any
+/// size, and complete messages are taken out with [`next_message`](Self::next_message). The
+/// header of a message is checked as soon as it is received, so that a peer can't make us buffer
+/// a payload longer than [`MAX_MSG_SIZE`] or sent for another network.
+#[derive(Debug, Clone)]
+pub struct MessageDecoder {
+    magic: Magic,
+    /// Received bytes not yet decoded into a message.
+    buffer: Vec<u8>,
+}
+
+impl MessageDecoder {
+    /// Creates a decoder of messages for the network identified by `magic`.
+    pub fn new(magic: Magic) -> MessageDecoder { MessageDecoder { magic, buffer: Vec::new() } }
+
+    /// Appends `bytes` received from the peer.
+    pub fn push_bytes(&mut self, bytes: &[u8]) { self.buffer.extend_from_slice(bytes); }
+
+    /// Returns the next complete message, or `None` if more bytes are needed.
+    ///
+    /// This should be called until it returns `None` after every call to
+    /// [`push_bytes`](Self::push_bytes), as a single chunk may contain several messages.
+    ///
+    /// # Errors
+    ///
+    /// If the message is malformed. The stream can't be resynchronized afterwards, the peer should
+    /// be disconnected.
+    pub fn next_message(&mut self) -> Result<Option<RawNetworkMessage>, FramingError> {
+        if self.buffer.len() >= 4 {
+            let magic = Magic::from_bytes(self.buffer[..4].try_into().expect("4 bytes"));
+            if magic != self.magic {
+                return Err(FramingError::UnexpectedMagic(magic));
+            }
+        }
+        if self.buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let payload_len = u32::from_le_bytes(self.buffer[16..20].try_into().expect("4 bytes"));
+        let payload_len = payload_len as usize;
+        if payload_len > MAX_MSG_SIZE {
+            return Err(FramingError::OversizedPayload(payload_len));
+        }
+        let message_len = HEADER_LEN + payload_len;
+        if self.buffer.len() < message_len {
+            return Ok(None);
+        }
+
+        let message = encode::deserialize(&self.buffer[..message_len])?;
+        self.buffer.drain(..message_len);
+        Ok(Some(message))
+    }
+
+    /// Returns the number of received bytes not yet decoded into a message.
+    pub fn buffered_len(&self) -> usize { self.buffer.len() }
+}
+
+/// An error in the version handshake, after which the peer should be disconnected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HandshakeError {
+    /// The peer sent a second `version`.
+    DuplicateVersion,
+    /// The peer sent a second `verack`.
+    DuplicateVerack,
+    /// The peer's protocol version is lower than [`MIN_PEER_PROTO_VERSION`].
+    ObsoleteVersion(u32),
+    /// The peer's `version` has our nonce, we connected to ourselves.
+    SelfConnection,
+    /// The peer sent the message with this command before its `version`.
+    NoVersion(&'static str),
+    /// The peer sent the message with this command after its `verack`.
+    AfterVerack(&'static str),
+}
+
+internals::impl_from_infallible!(HandshakeError);
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use HandshakeError::*;
+
+        match *self {
+            DuplicateVersion => f.write_str("duplicate version message"),
+            DuplicateVerack => f.write_str("duplicate verack message"),
+            ObsoleteVersion(version) => write!(f, "obsolete protocol version {}", version),
+            SelfConnection => f.write_str("connected to self"),
+            NoVersion(command) => write!(f, "{} message received before version", command),
+            AfterVerack(command) => write!(f, "{} message received after verack", command),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HandshakeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use HandshakeError::*;
+
+        match *self {
+            DuplicateVersion | DuplicateVerack | ObsoleteVersion(_) | SelfConnection
+            | NoVersion(_) | AfterVerack(_) => None,
+        }
+    }
+}
+
+/// An error splitting the bytes received from a peer into messages.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FramingError {
+    /// The message starts with the magic of another network.
+    UnexpectedMagic(Magic),
+    /// The message header declares a payload longer than [`MAX_MSG_SIZE`].
+    OversizedPayload(usize),
+    /// The message couldn't be decoded, for example because its checksum is wrong.
+    Decode(encode::Error),
+}
+
+internals::impl_from_infallible!(FramingError);
+
+impl fmt::Display for FramingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use FramingError::*;
+
+        match *self {
+            UnexpectedMagic(magic) => write!(f, "unexpected network magic {}", magic),
+            OversizedPayload(len) =>
+                write!(f, "payload of {} bytes exceeds the maximum of {}", len, MAX_MSG_SIZE),
+            Decode(ref e) => write_err!(f, "invalid message"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FramingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use FramingError::*;
+
+        match *self {
+            UnexpectedMagic(_) | OversizedPayload(_) => None,
+            Decode(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<encode::Error> for FramingError {
+    fn from(e: encode::Error) -> Self { Self::Decode(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::Network;
+    use crate::p2p::Address;
+
+    fn version(version: u32, nonce: u64) -> VersionMessage {
+        let address = Address::new(&([127, 0, 0, 1], 8333).into(), ServiceFlags::NONE);
+        let mut message = VersionMessage::new(
+            ServiceFlags::NETWORK,
+            0,
+            address.clone(),
+            address,
+            nonce,
+            "/test/".to_owned(),
+            100,
+        );
+        message.version = version;
+        message
+    }
+
+    // Delivers the messages sent in `actions` to `to`, returning its replies and other actions.
+    fn deliver(actions: Vec<Action>, to: &mut PeerStateMachine) -> (Vec<Action>, Vec<Action>) {
+        let mut replies = Vec::new();
+        let mut others = Vec::new();
+        for action in actions {
+            if let Action::Send(message) = action {
+                for action in to.receive(&message).unwrap() {
+                    match action {
+                        Action::Send(_) => replies.push(action),
+                        _ => others.push(action),
+                    }
+                }
+            }
+        }
+        (replies, others)
+    }
+
+    #[test]
+    fn handshake() {
+        let mut outbound = PeerStateMachine::new(version(70016, 1), Direction::Outbound);
+        let mut inbound = PeerStateMachine::new(version(70016, 2), Direction::Inbound);
+        assert_eq!(inbound.start(), vec![]);
+
+        let (replies, others) = deliver(outbound.start(), &mut inbound);
+        assert_eq!(
+            replies,
+            vec![
+                Action::Send(NetworkMessage::Version(version(70016, 2))),
+                Action::Send(NetworkMessage::WtxidRelay),
+                Action::Send(NetworkMessage::SendAddrV2),
+                Action::Send(NetworkMessage::Verack),
+            ]
+        );
+        assert_eq!(others, vec![Action::SetServices(ServiceFlags::NETWORK)]);
+
+        let (replies, others) = deliver(replies, &mut outbound);
+        assert_eq!(replies.len(), 3);
+        let info = PeerInfo {
+            version: 70016,
+            services: ServiceFlags::NETWORK,
+            user_agent: "/test/".to_owned(),
+            start_height: 100,
+            relay: false,
+            wtxid_relay: true,
+            addr_v2: true,
+        };
+        assert_eq!(
+            others,
+            vec![Action::SetServices(ServiceFlags::NETWORK), Action::Complete(info.clone())]
+        );
+        assert!(outbound.is_complete());
+        assert!(!inbound.is_complete());
+
+        let (_, others) = deliver(replies, &mut inbound);
+        assert_eq!(others, vec![Action::Complete(info)]);
+        assert!(inbound.is_complete());
+
+        assert_eq!(inbound.receive(&NetworkMessage::Ping(1)), Ok(vec![]));
+        assert_eq!(
+            inbound.receive(&NetworkMessage::WtxidRelay),
+            Err(HandshakeError::AfterVerack("wtxidrelay"))
+        );
+        assert_eq!(inbound.receive(&NetworkMessage::Verack), Err(HandshakeError::DuplicateVerack));
+        let message = NetworkMessage::Version(version(70016, 1));
+        assert_eq!(inbound.receive(&message), Err(HandshakeError::DuplicateVersion));
+    }
+
+    #[test]
+    fn handshake_old_peer() {
+        let mut outbound = PeerStateMachine::new(version(70016, 1), Direction::Outbound);
+        outbound.start();
+        assert_eq!(
+            outbound.receive(&NetworkMessage::Verack),
+            Err(HandshakeError::NoVersion("verack"))
+        );
+
+        // No wtxidrelay is sent to a peer which doesn't support it, nor accepted from it.
+        let actions = outbound.receive(&NetworkMessage::Version(version(70015, 2))).unwrap();
+        assert!(!actions.contains(&Action::Send(NetworkMessage::WtxidRelay)));
+        assert!(!actions.contains(&Action::Send(NetworkMessage::SendAddrV2)));
+        assert_eq!(actions.last(), Some(&Action::Send(NetworkMessage::Verack)));
+        outbound.receive(&NetworkMessage::WtxidRelay).unwrap();
+        let actions = outbound.receive(&NetworkMessage::Verack).unwrap();
+        match &actions[..] {
+            [Action::Complete(info)] => {
+                assert_eq!(info.version, 70015);
+                assert!(!info.wtxid_relay);
+                assert!(!info.addr_v2);
+            }
+            _ => panic!("handshake not complete: {:?}", actions),
+        }
+
+        let mut inbound = PeerStateMachine::new(version(70016, 1), Direction::Inbound);
+        let message = NetworkMessage::Version(version(MIN_PEER_PROTO_VERSION - 1, 2));
+        assert_eq!(
+            inbound.receive(&message),
+            Err(HandshakeError::ObsoleteVersion(MIN_PEER_PROTO_VERSION - 1))
+        );
+        let message = NetworkMessage::Version(version(70016, 1));
+        assert_eq!(inbound.receive(&message), Err(HandshakeError::SelfConnection));
+    }
+
+    #[test]
+    fn decode_messages() {
+        let magic = Magic::from(Network::Bitcoin);
+        let version = RawNetworkMessage::new(magic, NetworkMessage::Version(version(70016, 1)));
+        let verack = RawNetworkMessage::new(magic, NetworkMessage::Verack);
+        let mut bytes = encode::serialize(&version);
+        bytes.extend(encode::serialize(&verack));
+
+        // Byte by byte.
+        let mut decoder = MessageDecoder::new(magic);
+        let mut messages = Vec::new();
+        for byte in &bytes {
+            decoder.push_bytes(&[*byte]);
+            while let Some(message) = decoder.next_message().unwrap() {
+                messages.push(message);
+            }
+        }
+        assert_eq!(messages, vec![version.clone(), verack.clone()]);
+        assert_eq!(decoder.buffered_len(), 0);
+
+        // Both messages at once, followed by the start of another one.
+        let mut decoder = MessageDecoder::new(magic);
+        decoder.push_bytes(&bytes);
+        decoder.push_bytes(&bytes[..10]);
+        assert_eq!(decoder.next_message().unwrap(), Some(version));
+        assert_eq!(decoder.next_message().unwrap(), Some(verack));
+        assert_eq!(decoder.next_message().unwrap(), None);
+        assert_eq!(decoder.buffered_len(), 10);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let magic = Magic::from(Network::Bitcoin);
+        let bytes = encode::serialize(&RawNetworkMessage::new(magic, NetworkMessage::Ping(1)));
+
+        // The magic is checked before the rest of the header arrives.
+        let mut decoder = MessageDecoder::new(Magic::from(Network::Testnet));
+        decoder.push_bytes(&bytes[..4]);
+        assert!(
+            matches!(decoder.next_message(), Err(FramingError::UnexpectedMagic(m)) if m == magic)
+        );
+
+        // The payload length is checked before the payload arrives.
+        let mut oversized = bytes[..HEADER_LEN].to_vec();
+        oversized[16..20].copy_from_slice(&(MAX_MSG_SIZE as u32 + 1).to_le_bytes());
+        let mut decoder = MessageDecoder::new(magic);
+        decoder.push_bytes(&oversized);
+        assert!(matches!(
+            decoder.next_message(),
+            Err(FramingError::OversizedPayload(len)) if len == MAX_MSG_SIZE + 1
+        ));
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        let mut decoder = MessageDecoder::new(magic);
+        decoder.push_bytes(&corrupted);
+        assert!(matches!(
+            decoder.next_message(),
+            Err(FramingError::Decode(encode::Error::InvalidChecksum { .. }))
+        ));
+    }
+}
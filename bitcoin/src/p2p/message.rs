@@ -30,6 +30,9 @@ pub const MAX_INV_SIZE: usize = 50_000;
 /// This by neccessity should be larger tham `MAX_VEC_SIZE`
 pub const MAX_MSG_SIZE: usize = 5_000_000;
 
+/// The length of the message header: magic, command, payload length and checksum.
+pub(crate) const HEADER_LEN: usize = 24;
+
 /// Serializer for command string
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct CommandString(Cow<'static, str>);
@@ -428,17 +431,12 @@ impl Decodable for HeaderDeserializationWrapper {
     }
 }
 
-impl Decodable for RawNetworkMessage {
-    fn consensus_decode_from_finite_reader<R: BufRead + ?Sized>(
-        r: &mut R,
-    ) -> Result<Self, encode::Error> {
-        let magic = Decodable::consensus_decode_from_finite_reader(r)?;
-        let cmd = CommandString::consensus_decode_from_finite_reader(r)?;
-        let checked_data = CheckedData::consensus_decode_from_finite_reader(r)?;
-        let checksum = checked_data.checksum();
-        let raw_payload = checked_data.into_data();
-        let payload_len = raw_payload.len() as u32;
-
+impl NetworkMessage {
+    /// Decodes the payload of a message with command `cmd`.
+    pub(crate) fn decode_payload(
+        cmd: CommandString,
+        raw_payload: Vec<u8>,
+    ) -> Result<NetworkMessage, encode::Error> {
         let mut mem_d = raw_payload.as_slice();
         let payload = match &cmd.0[..] {
             "version" =>
@@ -524,6 +522,22 @@ impl Decodable for RawNetworkMessage {
             "sendaddrv2" => NetworkMessage::SendAddrV2,
             _ => NetworkMessage::Unknown { command: cmd, payload: raw_payload },
         };
+        Ok(payload)
+    }
+}
+
+impl Decodable for RawNetworkMessage {
+    fn consensus_decode_from_finite_reader<R: BufRead + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let magic = Decodable::consensus_decode_from_finite_reader(r)?;
+        let cmd = CommandString::consensus_decode_from_finite_reader(r)?;
+        let checked_data = CheckedData::consensus_decode_from_finite_reader(r)?;
+        let checksum = checked_data.checksum();
+        let raw_payload = checked_data.into_data();
+        let payload_len = raw_payload.len() as u32;
+
+        let payload = NetworkMessage::decode_payload(cmd, raw_payload)?;
         Ok(RawNetworkMessage { magic, payload, payload_len, checksum })
     }
 
@@ -5,9 +5,22 @@
 //! This module describes BIP157 Client Side Block Filtering network messages.
 //!
 
-use crate::bip158::{FilterHash, FilterHeader};
+use crate::bip158::{BlockFilter, FilterHash, FilterHeader};
 use crate::blockdata::block::BlockHash;
 use crate::internal_macros::impl_consensus_encoding;
+use crate::prelude::*;
+
+/// The filter type of the basic filter defined in BIP158.
+pub const BASIC_FILTER_TYPE: u8 = 0x00;
+
+/// The maximum number of filters requested by a getcfilters message.
+pub const MAX_GETCFILTERS_SIZE: u32 = 1000;
+
+/// The maximum number of filter headers requested by a getcfheaders message.
+pub const MAX_GETCFHEADERS_SIZE: u32 = 2000;
+
+/// The number of blocks between the filter headers of a cfcheckpt message.
+pub const CFCHECKPT_INTERVAL: u32 = 1000;
 
 /// getcfilters message
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -33,6 +46,16 @@ pub struct CFilter {
 }
 impl_consensus_encoding!(CFilter, filter_type, block_hash, filter);
 
+impl CFilter {
+    /// Creates a cfilter message returning the basic `filter` of the block with `block_hash`.
+    pub fn new_basic(block_hash: BlockHash, filter: &BlockFilter) -> CFilter {
+        CFilter { filter_type: BASIC_FILTER_TYPE, block_hash, filter: filter.content.clone() }
+    }
+
+    /// Returns the filter of this message as a [`BlockFilter`].
+    pub fn block_filter(&self) -> BlockFilter { BlockFilter::new(&self.filter) }
+}
+
 /// getcfheaders message
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct GetCFHeaders {
@@ -59,6 +82,22 @@ pub struct CFHeaders {
 }
 impl_consensus_encoding!(CFHeaders, filter_type, stop_hash, previous_filter_header, filter_hashes);
 
+impl CFHeaders {
+    /// Computes the filter headers of the blocks in the requested range from the filter hashes.
+    ///
+    /// The last filter header belongs to the block with `stop_hash`.
+    pub fn filter_headers(&self) -> Vec<FilterHeader> {
+        let mut previous = self.previous_filter_header;
+        self.filter_hashes
+            .iter()
+            .map(|filter_hash| {
+                previous = filter_hash.filter_header(&previous);
+                previous
+            })
+            .collect()
+    }
+}
+
 /// getcfcheckpt message
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct GetCFCheckpt {
@@ -80,3 +119,35 @@ pub struct CFCheckpt {
     pub filter_headers: Vec<FilterHeader>,
 }
 impl_consensus_encoding!(CFCheckpt, filter_type, stop_hash, filter_headers);
+
+#[cfg(test)]
+mod tests {
+    use hashes::Hash;
+
+    use super::*;
+    use crate::bip158;
+
+    #[test]
+    fn cfheaders_filter_headers() {
+        let filters = [BlockFilter::new(&[0]), BlockFilter::new(&[1, 2]), BlockFilter::new(&[3])];
+        let previous_filter_header = FilterHeader::hash(&[42]);
+        let cfheaders = CFHeaders {
+            filter_type: BASIC_FILTER_TYPE,
+            stop_hash: BlockHash::all_zeros(),
+            previous_filter_header,
+            filter_hashes: filters.iter().map(|f| FilterHash::hash(&f.content)).collect(),
+        };
+        assert_eq!(
+            cfheaders.filter_headers(),
+            bip158::filter_headers(previous_filter_header, &filters)
+        );
+    }
+
+    #[test]
+    fn cfilter_block_filter() {
+        let filter = BlockFilter::new(&[1, 2, 3]);
+        let cfilter = CFilter::new_basic(BlockHash::all_zeros(), &filter);
+        assert_eq!(cfilter.filter_type, BASIC_FILTER_TYPE);
+        assert_eq!(cfilter.block_filter(), filter);
+    }
+}
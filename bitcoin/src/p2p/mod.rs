@@ -7,6 +7,10 @@
 
 #[cfg(feature = "std")]
 pub mod address;
+#[cfg(feature = "async")]
+mod async_io;
+#[cfg(feature = "std")]
+pub mod handshake;
 #[cfg(feature = "std")]
 pub mod message;
 #[cfg(feature = "std")]
@@ -19,14 +23,18 @@ pub mod message_compact_blocks;
 pub mod message_filter;
 #[cfg(feature = "std")]
 pub mod message_network;
+#[cfg(feature = "v2transport")]
+pub mod v2transport;
 
 use core::str::FromStr;
 use core::{fmt, ops};
 
+use hashes::{sha256d, Hash};
 use hex::FromHex;
 use internals::{debug_from_display, write_err};
 use io::{BufRead, Write};
 
+use crate::blockdata::script::Script;
 use crate::consensus::encode::{self, Decodable, Encodable};
 use crate::prelude::*;
 use crate::Network;
@@ -224,6 +232,16 @@ impl Magic {
     /// Create network magic from bytes.
     pub fn from_bytes(bytes: [u8; 4]) -> Magic { Magic(bytes) }
 
+    /// Computes the network magic of the signet with block challenge `challenge`.
+    ///
+    /// The magic is the first four bytes of the double SHA256 hash of the serialized challenge.
+    pub fn from_signet_challenge(challenge: &Script) -> Magic {
+        let mut engine = sha256d::Hash::engine();
+        challenge.consensus_encode(&mut engine).expect("engines don't error");
+        let hash = sha256d::Hash::from_engine(engine).to_byte_array();
+        Magic([hash[0], hash[1], hash[2], hash[3]])
+    }
+
     /// Get network magic bytes.
     pub fn to_bytes(self) -> [u8; 4] { self.0 }
 }
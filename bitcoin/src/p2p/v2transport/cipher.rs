@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The ciphers used by the v2 transport.
+//!
+//! The forward secure wrappers specified in BIP 324 around the ChaCha20 and ChaCha20-Poly1305
+//! ciphers of [RFC 8439], which replace their key every [`REKEY_INTERVAL`] messages.
+//!
+//! [RFC 8439]: <https://datatracker.ietf.org/doc/html/rfc8439>
+
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::{ChaCha20, Key, Nonce};
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, KeyInit, Tag};
+
+/// The number of messages encrypted with a key before it is replaced.
+const REKEY_INTERVAL: u32 = 224;
+
+/// The length of a Poly1305 authentication tag.
+pub(super) const TAG_LEN: usize = 16;
+
+/// The authentication tag of a packet is invalid.
+#[derive(Debug)]
+pub(super) struct InvalidTag;
+
+/// The nonce made of a 32 bit counter and a 64 bit counter used by the forward secure ciphers.
+fn nonce(counter: u32, rekey_counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[..4].copy_from_slice(&counter.to_le_bytes());
+    nonce[4..].copy_from_slice(&rekey_counter.to_le_bytes());
+    nonce.into()
+}
+
+/// The forward secure ChaCha20 cipher encrypting the lengths of packets.
+pub(super) struct FsChaCha20 {
+    cipher: ChaCha20,
+    chunk_counter: u32,
+    rekey_counter: u64,
+}
+
+impl FsChaCha20 {
+    pub(super) fn new(key: [u8; 32]) -> FsChaCha20 {
+        FsChaCha20 {
+            cipher: ChaCha20::new(&Key::from(key), &nonce(0, 0)),
+            chunk_counter: 0,
+            rekey_counter: 0,
+        }
+    }
+
+    /// Encrypts or decrypts the chunk `data` in place.
+    pub(super) fn crypt(&mut self, data: &mut [u8]) {
+        self.cipher.apply_keystream(data);
+        self.chunk_counter += 1;
+        if self.chunk_counter == REKEY_INTERVAL {
+            // The new key is the next 32 bytes of the keystream.
+            let mut key = Key::default();
+            self.cipher.apply_keystream(&mut key);
+            self.chunk_counter = 0;
+            self.rekey_counter += 1;
+            self.cipher = ChaCha20::new(&key, &nonce(0, self.rekey_counter));
+        }
+    }
+}
+
+/// The forward secure ChaCha20-Poly1305 AEAD encrypting the contents of packets.
+pub(super) struct FsChaCha20Poly1305 {
+    aead: ChaCha20Poly1305,
+    packet_counter: u32,
+    rekey_counter: u64,
+}
+
+impl FsChaCha20Poly1305 {
+    pub(super) fn new(key: [u8; 32]) -> FsChaCha20Poly1305 {
+        FsChaCha20Poly1305 {
+            aead: ChaCha20Poly1305::new(&Key::from(key)),
+            packet_counter: 0,
+            rekey_counter: 0,
+        }
+    }
+
+    /// Encrypts `data` in place and returns the authentication tag over it and `aad`.
+    pub(super) fn encrypt(&mut self, aad: &[u8], data: &mut [u8]) -> [u8; TAG_LEN] {
+        let tag = self
+            .aead
+            .encrypt_in_place_detached(&self.nonce(), aad, data)
+            .expect("packets are shorter than the AEAD length limit");
+        self.next_packet();
+        tag.into()
+    }
+
+    /// Authenticates `data` and `aad` against `tag`, and decrypts `data` in place if valid.
+    pub(super) fn decrypt(
+        &mut self,
+        aad: &[u8],
+        data: &mut [u8],
+        tag: &[u8; TAG_LEN],
+    ) -> Result<(), InvalidTag> {
+        let result = self
+            .aead
+            .decrypt_in_place_detached(&self.nonce(), aad, data, &Tag::from(*tag))
+            .map_err(|_| InvalidTag);
+        self.next_packet();
+        result
+    }
+
+    fn nonce(&self) -> Nonce { nonce(self.packet_counter, self.rekey_counter) }
+
+    fn next_packet(&mut self) {
+        self.packet_counter += 1;
+        if self.packet_counter == REKEY_INTERVAL {
+            // The new key is the encryption of 32 zero bytes with the rekey nonce, that is the
+            // keystream starting at block 1 like any other encryption.
+            let mut key = Key::default();
+            self.aead
+                .encrypt_in_place_detached(&nonce(u32::MAX, self.rekey_counter), &[], &mut key)
+                .expect("the key is shorter than the AEAD length limit");
+            self.aead = ChaCha20Poly1305::new(&key);
+            self.packet_counter = 0;
+            self.rekey_counter += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fschacha20_rekey() {
+        let key = [7; 32];
+        let mut encrypter = FsChaCha20::new(key);
+        let mut decrypter = FsChaCha20::new(key);
+        let mut previous = [0u8; 3];
+        for i in 0..(2 * REKEY_INTERVAL + 1) {
+            let mut chunk = [1, 2, 3];
+            encrypter.crypt(&mut chunk);
+            assert_ne!(chunk, previous);
+            previous = chunk;
+            decrypter.crypt(&mut chunk);
+            assert_eq!(chunk, [1, 2, 3], "chunk {}", i);
+        }
+        assert_eq!(encrypter.rekey_counter, 2);
+    }
+
+    #[test]
+    fn fschacha20poly1305_rekey() {
+        let key = [7; 32];
+        let mut encrypter = FsChaCha20Poly1305::new(key);
+        let mut decrypter = FsChaCha20Poly1305::new(key);
+        for i in 0..(2 * REKEY_INTERVAL + 1) {
+            let mut data = *b"contents";
+            let tag = encrypter.encrypt(b"aad", &mut data);
+            decrypter.decrypt(b"aad", &mut data, &tag).unwrap();
+            assert_eq!(&data, b"contents", "packet {}", i);
+        }
+        assert_eq!(encrypter.rekey_counter, 2);
+
+        // A failed decryption still moves on to the next nonce.
+        let mut data = *b"contents";
+        let tag = encrypter.encrypt(b"", &mut data);
+        assert!(decrypter.decrypt(b"", &mut data, &[0; TAG_LEN]).is_err());
+        let mut data = *b"contents";
+        let tag_next = encrypter.encrypt(b"", &mut data);
+        assert_ne!(tag, tag_next);
+        decrypter.decrypt(b"", &mut data, &tag_next).unwrap();
+    }
+}
@@ -0,0 +1,399 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The v2 transport handshake.
+//!
+//! The handshake establishes the session keys with an ElligatorSwift encoded ECDH key exchange,
+//! then authenticates the garbage each side sent with its first packet.
+
+use core::mem;
+
+use hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
+use secp256k1::ellswift::ElligatorSwift;
+use secp256k1::{ecdh, PublicKey, Secp256k1, SecretKey, Verification};
+
+use super::packet::{PacketReader, PacketWriter};
+use super::{Error, Role, GARBAGE_TERMINATOR_LEN, LENGTH_LEN, MAX_GARBAGE_LEN};
+use crate::p2p::Magic;
+use crate::prelude::*;
+
+/// The first step of the handshake, exchanging public keys.
+///
+/// Both sides send [`key_and_garbage`](Self::key_and_garbage) and receive the 64 byte public key
+/// of the peer. A responder should wait for the initiator's key before sending its own, so it can
+/// fall back to the v1 protocol if [`receive_key`](Self::receive_key) returns
+/// [`Error::V1Peer`].
+#[derive(Clone)]
+pub struct Handshake {
+    magic: Magic,
+    role: Role,
+    secret_key: SecretKey,
+    key: ElligatorSwift,
+    garbage: Vec<u8>,
+}
+
+impl Handshake {
+    /// Starts a handshake on the network with `magic`.
+    ///
+    /// The `secret_key` must be freshly generated for every connection, and the `garbage` of up to
+    /// [`MAX_GARBAGE_LEN`] bytes should be random with a random length.
+    pub fn new<C: Verification>(
+        secp: &Secp256k1<C>,
+        magic: Magic,
+        role: Role,
+        secret_key: SecretKey,
+        garbage: Vec<u8>,
+    ) -> Result<Handshake, Error> {
+        if garbage.len() > MAX_GARBAGE_LEN {
+            return Err(Error::GarbageTooLong(garbage.len()));
+        }
+        let key = ElligatorSwift::from_seckey(secp, secret_key, None);
+        Ok(Handshake { magic, role, secret_key, key, garbage })
+    }
+
+    /// Returns the bytes to send first, our encoded public key followed by our garbage.
+    pub fn key_and_garbage(&self) -> Vec<u8> {
+        let mut bytes = self.key.to_array().to_vec();
+        bytes.extend_from_slice(&self.garbage);
+        bytes
+    }
+
+    /// Receives the encoded public key of the peer and derives the session keys.
+    ///
+    /// Returns the next step of the handshake and the bytes to send after
+    /// [`key_and_garbage`](Self::key_and_garbage): our garbage terminator, a decoy packet with
+    /// each of the `decoys` contents and the version packet.
+    pub fn receive_key(
+        self,
+        their_key: [u8; 64],
+        decoys: &[&[u8]],
+    ) -> Result<(Authentication, Vec<u8>), Error> {
+        if self.role == Role::Responder && their_key[..V1_PREFIX_LEN] == v1_prefix(self.magic) {
+            return Err(Error::V1Peer);
+        }
+
+        let secret =
+            ecdh(self.role, &self.secret_key, self.key, ElligatorSwift::from_array(their_key));
+        let keys = SessionKeys::derive(&secret, self.magic);
+        let (send, receive) = match self.role {
+            Role::Initiator => (keys.initiator, keys.responder),
+            Role::Responder => (keys.responder, keys.initiator),
+        };
+
+        let mut writer = PacketWriter::new(send.length_key, send.packet_key);
+        let mut bytes = send.garbage_terminator.to_vec();
+        // Only the first packet authenticates the garbage.
+        let mut aad = &self.garbage[..];
+        for decoy in decoys {
+            bytes.extend(writer.encrypt_packet_with_aad(decoy, true, aad)?);
+            aad = &[];
+        }
+        bytes.extend(writer.encrypt_packet_with_aad(&[], false, aad)?);
+
+        let auth = Authentication {
+            session_id: keys.session_id,
+            reader: PacketReader::new(receive.length_key, receive.packet_key),
+            writer,
+            their_terminator: receive.garbage_terminator,
+            garbage: Vec::new(),
+            terminator_received: false,
+            version_received: false,
+        };
+        Ok((auth, bytes))
+    }
+}
+
+/// The second step of the handshake, authenticating the garbage of the peer.
+///
+/// The bytes following the peer's key are first passed to
+/// [`receive_garbage`](Self::receive_garbage) until the garbage terminator is found. The packets
+/// following it are then received until the version packet, after which the handshake is
+/// [finished](Self::finish).
+pub struct Authentication {
+    session_id: [u8; 32],
+    reader: PacketReader,
+    writer: PacketWriter,
+    their_terminator: [u8; GARBAGE_TERMINATOR_LEN],
+    /// The garbage of the peer received so far, used as associated data of its first packet.
+    garbage: Vec<u8>,
+    terminator_received: bool,
+    version_received: bool,
+}
+
+impl Authentication {
+    /// Receives the bytes following the peer's key, looking for the end of its garbage.
+    ///
+    /// Returns the number of bytes up to and including the garbage terminator once it is found,
+    /// the remaining bytes belong to the packets following it.
+    pub fn receive_garbage(&mut self, bytes: &[u8]) -> Result<Option<usize>, Error> {
+        if self.terminator_received {
+            return Ok(Some(0));
+        }
+        for (i, byte) in bytes.iter().enumerate() {
+            self.garbage.push(*byte);
+            if self.garbage.ends_with(&self.their_terminator) {
+                self.garbage.truncate(self.garbage.len() - GARBAGE_TERMINATOR_LEN);
+                self.terminator_received = true;
+                return Ok(Some(i + 1));
+            }
+            if self.garbage.len() >= MAX_GARBAGE_LEN + GARBAGE_TERMINATOR_LEN {
+                return Err(Error::NoGarbageTerminator);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Decrypts the length of a packet following the garbage terminator.
+    ///
+    /// Returns the number of bytes of the packet following the length.
+    pub fn decrypt_packet_len(&mut self, length: [u8; LENGTH_LEN]) -> usize {
+        self.reader.decrypt_packet_len(length)
+    }
+
+    /// Decrypts the rest of a packet following the garbage terminator.
+    ///
+    /// Returns `true` once the version packet is received, any packets before it are decoys.
+    pub fn receive_packet(&mut self, packet: &[u8]) -> Result<bool, Error> {
+        // Only the first packet authenticates the garbage.
+        let garbage = mem::take(&mut self.garbage);
+        // The contents of the version packet are reserved for future extensions.
+        let contents = self.reader.decrypt_packet_with_aad(packet, &garbage)?;
+        self.version_received |= contents.is_some();
+        Ok(self.version_received)
+    }
+
+    /// Finishes the handshake once the version packet is received.
+    pub fn finish(self) -> Result<Session, Error> {
+        if !self.version_received {
+            return Err(Error::HandshakeIncomplete);
+        }
+        Ok(Session { id: self.session_id, reader: self.reader, writer: self.writer })
+    }
+}
+
+/// An established v2 transport session.
+pub struct Session {
+    id: [u8; 32],
+    reader: PacketReader,
+    writer: PacketWriter,
+}
+
+impl Session {
+    /// Returns the session ID, which is the same for both sides of the connection.
+    ///
+    /// Comparing it out of band detects a man in the middle.
+    pub fn id(&self) -> &[u8; 32] { &self.id }
+
+    /// Returns the reader decrypting the packets received from the peer.
+    pub fn reader(&mut self) -> &mut PacketReader { &mut self.reader }
+
+    /// Returns the writer encrypting the packets sent to the peer.
+    pub fn writer(&mut self) -> &mut PacketWriter { &mut self.writer }
+
+    /// Splits the session into its reader and writer.
+    pub fn into_split(self) -> (PacketReader, PacketWriter) { (self.reader, self.writer) }
+}
+
+/// The length of the start of a v1 version message a responder checks for.
+const V1_PREFIX_LEN: usize = 16;
+
+/// The start of the v1 version message, the network magic followed by the command.
+fn v1_prefix(magic: Magic) -> [u8; V1_PREFIX_LEN] {
+    let mut prefix = [0u8; V1_PREFIX_LEN];
+    prefix[..4].copy_from_slice(&magic.to_bytes());
+    prefix[4..11].copy_from_slice(b"version");
+    prefix
+}
+
+/// Computes the x-only ECDH shared secret hashed with both encoded public keys.
+fn ecdh(
+    role: Role,
+    secret_key: &SecretKey,
+    our_key: ElligatorSwift,
+    their_key: ElligatorSwift,
+) -> [u8; 32] {
+    let point = ecdh::shared_secret_point(&PublicKey::from_ellswift(their_key), secret_key);
+    let (initiator_key, responder_key) = match role {
+        Role::Initiator => (our_key, their_key),
+        Role::Responder => (their_key, our_key),
+    };
+
+    let tag = sha256::Hash::hash(b"bip324_ellswift_xonly_ecdh");
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(&initiator_key.to_array());
+    engine.input(&responder_key.to_array());
+    engine.input(&point[..32]);
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+/// The keys used by one side of the connection to send packets.
+struct SendKeys {
+    length_key: [u8; 32],
+    packet_key: [u8; 32],
+    garbage_terminator: [u8; GARBAGE_TERMINATOR_LEN],
+}
+
+/// The keys derived from the ECDH shared secret.
+struct SessionKeys {
+    initiator: SendKeys,
+    responder: SendKeys,
+    session_id: [u8; 32],
+}
+
+impl SessionKeys {
+    /// Derives the session keys with HKDF-SHA256.
+    fn derive(secret: &[u8; 32], magic: Magic) -> SessionKeys {
+        let mut salt = b"bitcoin_v2_shared_secret".to_vec();
+        salt.extend_from_slice(&magic.to_bytes());
+        let mut engine = HmacEngine::<sha256::Hash>::new(&salt);
+        engine.input(secret);
+        let prk = Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+
+        let expand = |info: &[u8]| {
+            let mut engine = HmacEngine::<sha256::Hash>::new(&prk);
+            engine.input(info);
+            engine.input(&[1]);
+            Hmac::<sha256::Hash>::from_engine(engine).to_byte_array()
+        };
+        let terminators = expand(b"garbage_terminators");
+        let mut initiator_terminator = [0u8; GARBAGE_TERMINATOR_LEN];
+        initiator_terminator.copy_from_slice(&terminators[..GARBAGE_TERMINATOR_LEN]);
+        let mut responder_terminator = [0u8; GARBAGE_TERMINATOR_LEN];
+        responder_terminator.copy_from_slice(&terminators[GARBAGE_TERMINATOR_LEN..]);
+
+        SessionKeys {
+            initiator: SendKeys {
+                length_key: expand(b"initiator_L"),
+                packet_key: expand(b"initiator_P"),
+                garbage_terminator: initiator_terminator,
+            },
+            responder: SendKeys {
+                length_key: expand(b"responder_L"),
+                packet_key: expand(b"responder_P"),
+                garbage_terminator: responder_terminator,
+            },
+            session_id: expand(b"session_id"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::test_hex_unwrap as hex;
+
+    use super::*;
+
+    #[test]
+    fn ecdh_bip324() {
+        // The test vectors of libsecp256k1's ellswift_xdh_bip324, with the initiating flag.
+        let vectors = [
+            (
+                "61062ea5071d800bbfd59e2e8b53d47d194b095ae5a4df04936b49772ef0d4d7",
+                "ec0adff257bbfe500c188c80b4fdd640f6b45a482bbc15fc7cef5931deff0aa186f6eb9bba7b85dc4dcc28b28722de1e3d9108b985e2967045668f66098e475b",
+                "a4a94dfce69b4a2a0a099313d10f9f7e7d649d60501c9e1d274c300e0d89aafaffffffffffffffffffffffffffffffffffffffffffffffffffffffff8faf88d5",
+                true,
+                "c6992a117f5edbea70c3f511d32d26b9798be4b81a62eaee1a5acaa8459a3592",
+            ),
+            (
+                "1f9c581b35231838f0f17cf0c979835baccb7f3abbbb96ffcc318ab71e6e126f",
+                "a1855e10e94e00baa23041d916e259f7044e491da6171269694763f018c7e63693d29575dcb464ac816baa1be353ba12e3876cba7628bd0bd8e755e721eb0140",
+                "fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f0000000000000000000000000000000000000000000000000000000000000000",
+                false,
+                "a0138f564f74d0ad70bc337dacc9d0bf1d2349364caf1188a1e6e8ddb3b7b184",
+            ),
+            (
+                "6c77432d1fda31e9f942f8af44607e10f3ad38a65f8a4bddae823e5eff90dc38",
+                "d2685070c1e6376e633e825296634fd461fa9e5bdf2109bcebd735e5a91f3e587c5cb782abb797fbf6bb5074fd1542a474f2a45b673763ec2db7fb99b737bbb9",
+                "56bd0c06f10352c3a1a9f4b4c92f6fa2b26df124b57878353c1fc691c51abea77c8817daeeb9fa546b77c8daf79d89b22b0e1b87574ece42371f00237aa9d83a",
+                false,
+                "1918b741ef5f9d1d7670b050c152b4a4ead2c31be9aecb0681c0cd4324150853",
+            ),
+        ];
+        for (secret_key, ours, theirs, initiating, shared) in vectors {
+            let secret_key = SecretKey::from_slice(&hex!(secret_key)).unwrap();
+            let ours = ElligatorSwift::from_array(hex!(ours).try_into().unwrap());
+            let theirs = ElligatorSwift::from_array(hex!(theirs).try_into().unwrap());
+            let role = if initiating { Role::Initiator } else { Role::Responder };
+            assert_eq!(ecdh(role, &secret_key, ours, theirs)[..], hex!(shared));
+        }
+    }
+
+    // The first two rows are from the BIP's packet_encoding_test_vectors.csv, the `in_idx` 999
+    // row covers four rekeys of both ciphers. The other rows use the same layout and were
+    // generated with an independent implementation of the BIP's reference code, they add rekey
+    // boundaries, decoy packets and garbage as associated data.
+    #[test]
+    fn packet_encoding_bip324() {
+        let data = include_str!("../../../tests/data/bip324_packet_encoding.csv");
+        let mut lines = data.lines();
+        let columns: Vec<&str> = lines.next().unwrap().split(',').collect();
+        for line in lines {
+            let fields: Vec<&str> = line.split(',').collect();
+            let field = |name: &str| fields[columns.iter().position(|c| *c == name).unwrap()];
+            let array = |name: &str| -> [u8; 32] { hex!(field(name)).try_into().unwrap() };
+
+            let secret_key = SecretKey::from_slice(&hex!(field("in_priv_ours"))).unwrap();
+            let ours =
+                ElligatorSwift::from_array(hex!(field("in_ellswift_ours")).try_into().unwrap());
+            let theirs =
+                ElligatorSwift::from_array(hex!(field("in_ellswift_theirs")).try_into().unwrap());
+            let role =
+                if field("in_initiating") == "1" { Role::Initiator } else { Role::Responder };
+
+            let secret = ecdh(role, &secret_key, ours, theirs);
+            assert_eq!(secret, array("mid_shared_secret"));
+            let keys = SessionKeys::derive(&secret, Magic::BITCOIN);
+            assert_eq!(keys.initiator.length_key, array("mid_initiator_l"));
+            assert_eq!(keys.initiator.packet_key, array("mid_initiator_p"));
+            assert_eq!(keys.responder.length_key, array("mid_responder_l"));
+            assert_eq!(keys.responder.packet_key, array("mid_responder_p"));
+            assert_eq!(keys.session_id, array("out_session_id"));
+            let (send, receive) = match role {
+                Role::Initiator => (keys.initiator, keys.responder),
+                Role::Responder => (keys.responder, keys.initiator),
+            };
+            assert_eq!(send.garbage_terminator[..], hex!(field("mid_send_garbage_terminator")));
+            assert_eq!(receive.garbage_terminator[..], hex!(field("mid_recv_garbage_terminator")));
+
+            // The peer decrypts with our send keys.
+            let mut writer = PacketWriter::new(send.length_key, send.packet_key);
+            let mut reader = PacketReader::new(send.length_key, send.packet_key);
+            for _ in 0..field("in_idx").parse().unwrap() {
+                let packet = writer.encrypt_packet(&[], true).unwrap();
+                reader.decrypt_packet_len(packet[..LENGTH_LEN].try_into().unwrap());
+                assert_eq!(reader.decrypt_packet(&packet[LENGTH_LEN..]).unwrap(), None);
+            }
+
+            let contents = hex!(field("in_contents")).repeat(field("in_multiply").parse().unwrap());
+            let aad = hex!(field("in_aad"));
+            let ignore = field("in_ignore") == "1";
+            let packet = writer.encrypt_packet_with_aad(&contents, ignore, &aad).unwrap();
+            if field("out_ciphertext").is_empty() {
+                assert!(packet.ends_with(&hex!(field("out_ciphertext_endswith"))));
+            } else {
+                assert_eq!(packet, hex!(field("out_ciphertext")));
+            }
+
+            let len = reader.decrypt_packet_len(packet[..LENGTH_LEN].try_into().unwrap());
+            assert_eq!(len, packet.len() - LENGTH_LEN);
+            let decrypted = reader.decrypt_packet_with_aad(&packet[LENGTH_LEN..], &aad).unwrap();
+            assert_eq!(decrypted, if ignore { None } else { Some(contents) });
+        }
+    }
+
+    #[test]
+    fn v1_peer() {
+        let secp = Secp256k1::new();
+        let magic = Magic::BITCOIN;
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let mut key = [0u8; 64];
+        key[..V1_PREFIX_LEN].copy_from_slice(&v1_prefix(magic));
+        assert_eq!(&key[..4], &[0xf9, 0xbe, 0xb4, 0xd9]);
+
+        let responder = Handshake::new(&secp, magic, Role::Responder, secret_key, vec![]).unwrap();
+        assert!(matches!(responder.receive_key(key, &[]), Err(Error::V1Peer)));
+        let initiator = Handshake::new(&secp, magic, Role::Initiator, secret_key, vec![]).unwrap();
+        assert!(initiator.receive_key(key, &[]).is_ok());
+    }
+}
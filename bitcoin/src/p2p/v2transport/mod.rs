@@ -0,0 +1,338 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP 324 v2 encrypted transport.
+//!
+//! Implementation of the v2 P2P transport protocol specified in [BIP 324], which encrypts and
+//! authenticates the connection between two peers.
+//!
+//! The protocol is implemented as state machines which don't perform any I/O, so they can be
+//! driven by blocking and asynchronous code alike:
+//!
+//! 1. A [`Handshake`] exchanges the ElligatorSwift encoded public keys and derives the session
+//!    keys from their ECDH shared secret.
+//! 2. An [`Authentication`] finds the end of the garbage sent by the peer and authenticates it
+//!    with the version packet, skipping any decoy packets before it.
+//! 3. A [`Session`] then encrypts and decrypts the packets with a [`PacketWriter`] and a
+//!    [`PacketReader`].
+//!
+//! [`V2Stream`] drives them over a blocking [`std::io`] stream.
+//!
+//! [BIP 324]: <https://github.com/bitcoin/bips/blob/master/bip-0324.mediawiki>
+
+mod cipher;
+mod handshake;
+mod packet;
+mod stream;
+
+use core::fmt;
+
+use internals::write_err;
+
+use crate::consensus::encode::{self, Decodable, Encodable};
+use crate::p2p::message::{CommandString, NetworkMessage};
+use crate::prelude::*;
+
+#[rustfmt::skip]                // Keep public re-exports separate.
+#[doc(inline)]
+pub use self::{
+    handshake::{Authentication, Handshake, Session},
+    packet::{PacketReader, PacketWriter},
+    stream::V2Stream,
+};
+
+/// The maximum length of the garbage sent after the public key.
+pub const MAX_GARBAGE_LEN: usize = 4095;
+
+/// The length of the encrypted contents length at the start of a packet.
+pub const LENGTH_LEN: usize = 3;
+
+/// The length of the garbage terminator.
+const GARBAGE_TERMINATOR_LEN: usize = 16;
+
+/// The commands of the messages with a one byte message type ID, which is their index plus one.
+///
+/// Other messages are identified by a zero byte followed by their command.
+const SHORT_IDS: [&str; 28] = [
+    "addr",
+    "block",
+    "blocktxn",
+    "cmpctblock",
+    "feefilter",
+    "filteradd",
+    "filterclear",
+    "filterload",
+    "getblocks",
+    "getblocktxn",
+    "getdata",
+    "getheaders",
+    "headers",
+    "inv",
+    "mempool",
+    "merkleblock",
+    "notfound",
+    "ping",
+    "pong",
+    "sendcmpct",
+    "tx",
+    "getcfilters",
+    "cfilter",
+    "getcfheaders",
+    "cfheaders",
+    "getcfcheckpt",
+    "cfcheckpt",
+    "addrv2",
+];
+
+/// The side of the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// The side which opened the connection.
+    Initiator,
+    /// The side which accepted the connection.
+    Responder,
+}
+
+/// Encodes `message` as the contents of a packet.
+pub fn encode_message(message: &NetworkMessage) -> Vec<u8> {
+    let command = message.command();
+    let mut contents = match SHORT_IDS.iter().position(|cmd| *cmd == command.as_ref()) {
+        Some(index) => vec![index as u8 + 1],
+        None => {
+            let mut contents = vec![0];
+            command.consensus_encode(&mut contents).expect("in-memory writers don't error");
+            contents
+        }
+    };
+    match message {
+        // The payload is sent as is, rather than length prefixed.
+        NetworkMessage::Unknown { payload, .. } => contents.extend_from_slice(payload),
+        _ => {
+            message.consensus_encode(&mut contents).expect("in-memory writers don't error");
+        }
+    }
+    contents
+}
+
+/// Decodes the message in the contents of a packet.
+pub fn decode_message(contents: &[u8]) -> Result<NetworkMessage, Error> {
+    let (id, mut payload) = contents.split_first().ok_or(Error::EmptyContents)?;
+    let command = match *id {
+        0 => CommandString::consensus_decode(&mut payload)?,
+        id => {
+            let cmd = SHORT_IDS.get(usize::from(id) - 1).ok_or(Error::UnknownMessageId(id))?;
+            CommandString::try_from_static(cmd).expect("short ID commands are valid")
+        }
+    };
+    Ok(NetworkMessage::decode_payload(command, payload.to_vec())?)
+}
+
+/// An error in the v2 transport.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The garbage to send is longer than [`MAX_GARBAGE_LEN`].
+    GarbageTooLong(usize),
+    /// The peer sent the start of a v1 version message instead of its public key.
+    V1Peer,
+    /// The garbage terminator of the peer was not found within [`MAX_GARBAGE_LEN`] bytes.
+    NoGarbageTerminator,
+    /// The handshake was finished before the version packet was received.
+    HandshakeIncomplete,
+    /// The contents are too long to fit in a packet.
+    ContentsTooLong(usize),
+    /// The packet is shorter than its header and authentication tag.
+    PacketTooShort(usize),
+    /// The authentication tag of the packet is invalid.
+    InvalidTag,
+    /// The contents of the packet are empty, without a message type ID.
+    EmptyContents,
+    /// The message type ID is unknown.
+    UnknownMessageId(u8),
+    /// The message in the packet could not be decoded.
+    Decode(encode::Error),
+}
+
+internals::impl_from_infallible!(Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            GarbageTooLong(len) => write!(f, "garbage of {} bytes is too long", len),
+            V1Peer => f.write_str("peer uses the v1 transport"),
+            NoGarbageTerminator => f.write_str("garbage terminator not found"),
+            HandshakeIncomplete => f.write_str("version packet not received"),
+            ContentsTooLong(len) => write!(f, "packet contents of {} bytes are too long", len),
+            PacketTooShort(len) => write!(f, "packet of {} bytes is too short", len),
+            InvalidTag => f.write_str("invalid packet authentication tag"),
+            EmptyContents => f.write_str("packet contents are empty"),
+            UnknownMessageId(id) => write!(f, "unknown message type ID {}", id),
+            Decode(ref e) => write_err!(f, "message decoding error"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            Decode(ref e) => Some(e),
+            GarbageTooLong(_) | V1Peer | NoGarbageTerminator | HandshakeIncomplete
+            | ContentsTooLong(_) | PacketTooShort(_) | InvalidTag | EmptyContents
+            | UnknownMessageId(_) => None,
+        }
+    }
+}
+
+impl From<encode::Error> for Error {
+    fn from(e: encode::Error) -> Self { Error::Decode(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::{Secp256k1, SecretKey};
+
+    use super::*;
+    use crate::p2p::message_network::VersionMessage;
+    use crate::p2p::{Address, Magic, ServiceFlags};
+
+    fn handshake(role: Role, garbage: Vec<u8>) -> Handshake {
+        let secp = Secp256k1::new();
+        let secret_key = match role {
+            Role::Initiator => SecretKey::from_slice(&[1; 32]).unwrap(),
+            Role::Responder => SecretKey::from_slice(&[2; 32]).unwrap(),
+        };
+        Handshake::new(&secp, Magic::REGTEST, role, secret_key, garbage).unwrap()
+    }
+
+    // Receives the bytes the peer sent after its key, returns whether the handshake finished.
+    fn receive(auth: &mut Authentication, mut bytes: &[u8]) -> bool {
+        let consumed = auth.receive_garbage(bytes).unwrap().expect("terminator found");
+        bytes = &bytes[consumed..];
+        while !bytes.is_empty() {
+            let len = auth.decrypt_packet_len([bytes[0], bytes[1], bytes[2]]);
+            let (packet, rest) = bytes[LENGTH_LEN..].split_at(len);
+            if auth.receive_packet(packet).unwrap() {
+                assert!(rest.is_empty());
+                return true;
+            }
+            bytes = rest;
+        }
+        false
+    }
+
+    fn receive_message(session: &mut Session, packet: &[u8]) -> Option<NetworkMessage> {
+        let len = session.reader().decrypt_packet_len([packet[0], packet[1], packet[2]]);
+        assert_eq!(packet.len(), LENGTH_LEN + len);
+        session.reader().decrypt_message(&packet[LENGTH_LEN..]).unwrap()
+    }
+
+    #[test]
+    fn session() {
+        let initiator = handshake(Role::Initiator, vec![0xab; 100]);
+        let responder = handshake(Role::Responder, vec![]);
+        let initiator_key = initiator.key_and_garbage();
+        let responder_key = responder.key_and_garbage();
+        assert_eq!(initiator_key.len(), 164);
+        assert_eq!(responder_key.len(), 64);
+
+        // The initiator sends decoy packets before the version packet, the first one
+        // authenticating its garbage.
+        let decoys: [&[u8]; 2] = [b"decoy", &[0; 1000]];
+        let (mut initiator, initiator_bytes) =
+            initiator.receive_key(responder_key[..64].try_into().unwrap(), &decoys).unwrap();
+        let (mut responder, responder_bytes) =
+            responder.receive_key(initiator_key[..64].try_into().unwrap(), &[]).unwrap();
+
+        // The garbage can be received in pieces.
+        let mut initiator_sent = initiator_key[64..].to_vec();
+        initiator_sent.extend_from_slice(&initiator_bytes);
+        assert_eq!(responder.receive_garbage(&initiator_sent[..50]).unwrap(), None);
+        assert!(receive(&mut responder, &initiator_sent[50..]));
+        assert!(receive(&mut initiator, &responder_bytes));
+
+        // Tampering with the garbage fails the authentication of the first packet.
+        let (mut tampered, _) = handshake(Role::Responder, vec![])
+            .receive_key(initiator_key[..64].try_into().unwrap(), &[])
+            .unwrap();
+        initiator_sent[0] ^= 1;
+        let consumed = tampered.receive_garbage(&initiator_sent).unwrap().unwrap();
+        let packet = &initiator_sent[consumed..];
+        let len = tampered.decrypt_packet_len([packet[0], packet[1], packet[2]]);
+        let result = tampered.receive_packet(&packet[LENGTH_LEN..LENGTH_LEN + len]);
+        assert!(matches!(result, Err(Error::InvalidTag)));
+
+        let mut initiator = initiator.finish().unwrap();
+        let mut responder = responder.finish().unwrap();
+        assert_eq!(initiator.id(), responder.id());
+
+        let ping = NetworkMessage::Ping(42);
+        for _ in 0..500 {
+            let packet = initiator.writer().encrypt_message(&ping).unwrap();
+            assert_eq!(receive_message(&mut responder, &packet), Some(ping.clone()));
+            let packet = responder.writer().encrypt_packet(b"decoy", true).unwrap();
+            assert_eq!(receive_message(&mut initiator, &packet), None);
+        }
+
+        let mut packet = initiator.writer().encrypt_message(&NetworkMessage::Verack).unwrap();
+        packet[LENGTH_LEN] ^= 1;
+        let len = responder.reader().decrypt_packet_len([packet[0], packet[1], packet[2]]);
+        assert!(matches!(
+            responder.reader().decrypt_packet(&packet[LENGTH_LEN..LENGTH_LEN + len]),
+            Err(Error::InvalidTag)
+        ));
+    }
+
+    #[test]
+    fn garbage() {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let garbage = vec![0; MAX_GARBAGE_LEN + 1];
+        let result = Handshake::new(&secp, Magic::REGTEST, Role::Initiator, secret_key, garbage);
+        assert!(matches!(result, Err(Error::GarbageTooLong(4096))));
+
+        let initiator = handshake(Role::Initiator, vec![]);
+        let responder = handshake(Role::Responder, vec![]);
+        let key = responder.key_and_garbage()[..64].try_into().unwrap();
+        let (mut initiator, _) = initiator.receive_key(key, &[]).unwrap();
+        assert_eq!(initiator.receive_garbage(&[0; MAX_GARBAGE_LEN]).unwrap(), None);
+        assert_eq!(initiator.receive_garbage(&[0; 15]).unwrap(), None);
+        assert!(matches!(initiator.receive_garbage(&[0]), Err(Error::NoGarbageTerminator)));
+        assert!(matches!(initiator.finish(), Err(Error::HandshakeIncomplete)));
+    }
+
+    #[test]
+    fn message_contents() {
+        let ping = NetworkMessage::Ping(42);
+        let contents = encode_message(&ping);
+        assert_eq!(contents, [&[18][..], &42u64.to_le_bytes()].concat());
+        assert_eq!(decode_message(&contents).unwrap(), ping);
+
+        let address = Address::new(&([127, 0, 0, 1], 8333).into(), ServiceFlags::NONE);
+        let version = NetworkMessage::Version(VersionMessage::new(
+            ServiceFlags::NONE,
+            0,
+            address.clone(),
+            address,
+            0,
+            "/test/".to_owned(),
+            0,
+        ));
+        let contents = encode_message(&version);
+        assert_eq!(&contents[..13], b"\0version\0\0\0\0\0");
+        assert_eq!(decode_message(&contents).unwrap(), version);
+
+        let unknown = NetworkMessage::Unknown {
+            command: CommandString::try_from_static("custom").unwrap(),
+            payload: vec![1, 2, 3],
+        };
+        assert_eq!(decode_message(&encode_message(&unknown)).unwrap(), unknown);
+
+        assert!(matches!(decode_message(&[]), Err(Error::EmptyContents)));
+        assert!(matches!(decode_message(&[29]), Err(Error::UnknownMessageId(29))));
+        assert!(matches!(decode_message(&[0, 1, 2]), Err(Error::Decode(_))));
+    }
+}
@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Encryption and decryption of v2 transport packets.
+//!
+//! A packet is the contents length encrypted with the length cipher, followed by the header byte
+//! and the contents encrypted with the packet cipher, and the authentication tag.
+
+use super::cipher::{FsChaCha20, FsChaCha20Poly1305, TAG_LEN};
+use super::{Error, LENGTH_LEN};
+use crate::p2p::message::NetworkMessage;
+use crate::prelude::*;
+
+/// The header bit of a decoy packet, which the receiver ignores.
+const IGNORE_BIT: u8 = 1 << 7;
+
+/// The maximum length of the contents of a packet.
+const MAX_CONTENTS_LEN: usize = (1 << 24) - 1;
+
+/// Encrypts the packets sent to the peer.
+pub struct PacketWriter {
+    length_cipher: FsChaCha20,
+    packet_cipher: FsChaCha20Poly1305,
+}
+
+impl PacketWriter {
+    pub(super) fn new(length_key: [u8; 32], packet_key: [u8; 32]) -> PacketWriter {
+        PacketWriter {
+            length_cipher: FsChaCha20::new(length_key),
+            packet_cipher: FsChaCha20Poly1305::new(packet_key),
+        }
+    }
+
+    /// Encrypts a packet with `contents`, which the peer ignores if `decoy` is set.
+    pub fn encrypt_packet(&mut self, contents: &[u8], decoy: bool) -> Result<Vec<u8>, Error> {
+        self.encrypt_packet_with_aad(contents, decoy, &[])
+    }
+
+    /// Encrypts a packet containing `message`.
+    pub fn encrypt_message(&mut self, message: &NetworkMessage) -> Result<Vec<u8>, Error> {
+        self.encrypt_packet(&super::encode_message(message), false)
+    }
+
+    pub(super) fn encrypt_packet_with_aad(
+        &mut self,
+        contents: &[u8],
+        decoy: bool,
+        aad: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        if contents.len() > MAX_CONTENTS_LEN {
+            return Err(Error::ContentsTooLong(contents.len()));
+        }
+
+        let mut packet = Vec::with_capacity(LENGTH_LEN + 1 + contents.len() + TAG_LEN);
+        packet.extend_from_slice(&(contents.len() as u32).to_le_bytes()[..LENGTH_LEN]);
+        self.length_cipher.crypt(&mut packet);
+        packet.push(if decoy { IGNORE_BIT } else { 0 });
+        packet.extend_from_slice(contents);
+        let tag = self.packet_cipher.encrypt(aad, &mut packet[LENGTH_LEN..]);
+        packet.extend_from_slice(&tag);
+        Ok(packet)
+    }
+}
+
+/// Decrypts the packets received from the peer.
+///
+/// A packet is received in two steps: first the length is decrypted with
+/// [`decrypt_packet_len`](Self::decrypt_packet_len), which gives the number of bytes of the rest
+/// of the packet, then the rest is decrypted with [`decrypt_packet`](Self::decrypt_packet).
+pub struct PacketReader {
+    length_cipher: FsChaCha20,
+    packet_cipher: FsChaCha20Poly1305,
+}
+
+impl PacketReader {
+    pub(super) fn new(length_key: [u8; 32], packet_key: [u8; 32]) -> PacketReader {
+        PacketReader {
+            length_cipher: FsChaCha20::new(length_key),
+            packet_cipher: FsChaCha20Poly1305::new(packet_key),
+        }
+    }
+
+    /// Decrypts the encrypted length at the start of a packet.
+    ///
+    /// Returns the number of bytes of the packet following the length. The length is not
+    /// authenticated until the packet is decrypted, callers should limit how much they are
+    /// willing to receive before that.
+    pub fn decrypt_packet_len(&mut self, length: [u8; LENGTH_LEN]) -> usize {
+        let mut length = length;
+        self.length_cipher.crypt(&mut length);
+        let contents_len = u32::from_le_bytes([length[0], length[1], length[2], 0]) as usize;
+        1 + contents_len + TAG_LEN
+    }
+
+    /// Decrypts the rest of a packet following its length.
+    ///
+    /// Returns the contents of the packet, or `None` if it is a decoy packet.
+    pub fn decrypt_packet(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.decrypt_packet_with_aad(packet, &[])
+    }
+
+    /// Decrypts the rest of a packet following its length and decodes the message it contains.
+    ///
+    /// Returns `None` if it is a decoy packet.
+    pub fn decrypt_message(&mut self, packet: &[u8]) -> Result<Option<NetworkMessage>, Error> {
+        match self.decrypt_packet(packet)? {
+            Some(contents) => super::decode_message(&contents).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub(super) fn decrypt_packet_with_aad(
+        &mut self,
+        packet: &[u8],
+        aad: &[u8],
+    ) -> Result<Option<Vec<u8>>, Error> {
+        if packet.len() < 1 + TAG_LEN {
+            return Err(Error::PacketTooShort(packet.len()));
+        }
+
+        let (data, tag) = packet.split_at(packet.len() - TAG_LEN);
+        let tag = <&[u8; TAG_LEN]>::try_from(tag).expect("split at tag length");
+        let mut data = data.to_vec();
+        self.packet_cipher.decrypt(aad, &mut data, tag).map_err(|_| Error::InvalidTag)?;
+
+        if data[0] & IGNORE_BIT != 0 {
+            Ok(None)
+        } else {
+            data.remove(0);
+            Ok(Some(data))
+        }
+    }
+}
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! The v2 transport over a blocking [`std::io`] stream.
+
+use std::io::{self, Read, Write};
+
+use secp256k1::{Secp256k1, SecretKey, Verification};
+
+use super::{Error, Handshake, Role, Session, LENGTH_LEN};
+use crate::p2p::message::{NetworkMessage, MAX_MSG_SIZE};
+use crate::p2p::Magic;
+use crate::prelude::*;
+
+/// A v2 transport connection over a stream such as a [`std::net::TcpStream`].
+///
+/// The errors of the transport are returned as [`io::ErrorKind::InvalidData`] errors wrapping an
+/// [`Error`].
+pub struct V2Stream<S> {
+    stream: S,
+    session: Session,
+}
+
+impl<S: Read + Write> V2Stream<S> {
+    /// Performs the handshake over `stream`, blocking until it is finished.
+    ///
+    /// See [`Handshake::new`] for the requirements on `secret_key` and `garbage`.
+    pub fn handshake<C: Verification>(
+        secp: &Secp256k1<C>,
+        mut stream: S,
+        magic: Magic,
+        role: Role,
+        secret_key: SecretKey,
+        garbage: Vec<u8>,
+    ) -> io::Result<V2Stream<S>> {
+        let handshake = Handshake::new(secp, magic, role, secret_key, garbage)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let key_and_garbage = handshake.key_and_garbage();
+
+        // The responder waits for the initiator's key in case it is a v1 peer.
+        if role == Role::Initiator {
+            stream.write_all(&key_and_garbage)?;
+            stream.flush()?;
+        }
+        let mut their_key = [0u8; 64];
+        stream.read_exact(&mut their_key)?;
+        let (mut auth, bytes) = handshake.receive_key(their_key, &[]).map_err(invalid_data)?;
+        if role == Role::Responder {
+            stream.write_all(&key_and_garbage)?;
+        }
+        stream.write_all(&bytes)?;
+        stream.flush()?;
+
+        // Read byte by byte to not read past the garbage terminator.
+        let mut byte = [0u8; 1];
+        loop {
+            stream.read_exact(&mut byte)?;
+            if auth.receive_garbage(&byte).map_err(invalid_data)?.is_some() {
+                break;
+            }
+        }
+        loop {
+            let mut length = [0u8; LENGTH_LEN];
+            stream.read_exact(&mut length)?;
+            let packet = read_packet(&mut stream, auth.decrypt_packet_len(length))?;
+            if auth.receive_packet(&packet).map_err(invalid_data)? {
+                break;
+            }
+        }
+
+        let session = auth.finish().map_err(invalid_data)?;
+        Ok(V2Stream { stream, session })
+    }
+
+    /// Returns the session ID, which is the same for both sides of the connection.
+    pub fn session_id(&self) -> &[u8; 32] { self.session.id() }
+
+    /// Sends `message` to the peer.
+    pub fn send(&mut self, message: &NetworkMessage) -> io::Result<()> {
+        let packet = self.session.writer().encrypt_message(message).map_err(invalid_data)?;
+        self.stream.write_all(&packet)?;
+        self.stream.flush()
+    }
+
+    /// Receives the next message from the peer, skipping decoy packets.
+    pub fn receive(&mut self) -> io::Result<NetworkMessage> {
+        loop {
+            let mut length = [0u8; LENGTH_LEN];
+            self.stream.read_exact(&mut length)?;
+            let len = self.session.reader().decrypt_packet_len(length);
+            let packet = read_packet(&mut self.stream, len)?;
+            if let Some(message) =
+                self.session.reader().decrypt_message(&packet).map_err(invalid_data)?
+            {
+                return Ok(message);
+            }
+        }
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> S { self.stream }
+}
+
+/// Reads the `len` bytes of a packet following its length.
+fn read_packet<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    if len > MAX_MSG_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "packet too long"));
+    }
+    let mut packet = vec![0u8; len];
+    reader.read_exact(&mut packet)?;
+    Ok(packet)
+}
+
+fn invalid_data(e: Error) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, e) }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+
+    // One end of an in-memory connection.
+    struct Pipe {
+        sender: mpsc::Sender<Vec<u8>>,
+        receiver: mpsc::Receiver<Vec<u8>>,
+        buffer: Vec<u8>,
+    }
+
+    fn pipe() -> (Pipe, Pipe) {
+        let (a_sender, b_receiver) = mpsc::channel();
+        let (b_sender, a_receiver) = mpsc::channel();
+        (
+            Pipe { sender: a_sender, receiver: a_receiver, buffer: vec![] },
+            Pipe { sender: b_sender, receiver: b_receiver, buffer: vec![] },
+        )
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.buffer.is_empty() {
+                match self.receiver.recv() {
+                    Ok(bytes) => self.buffer = bytes,
+                    Err(_) => return Ok(0),
+                }
+            }
+            let len = buf.len().min(self.buffer.len());
+            buf[..len].copy_from_slice(&self.buffer[..len]);
+            self.buffer.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.sender
+                .send(buf.to_vec())
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn v2_stream() {
+        let (initiator, responder) = pipe();
+        let responder = thread::spawn(move || {
+            let secp = Secp256k1::new();
+            let secret_key = SecretKey::from_slice(&[2; 32]).unwrap();
+            let garbage = vec![7; 10];
+            let mut stream = V2Stream::handshake(
+                &secp,
+                responder,
+                Magic::REGTEST,
+                Role::Responder,
+                secret_key,
+                garbage,
+            )
+            .unwrap();
+            let message = stream.receive().unwrap();
+            stream.send(&message).unwrap();
+            *stream.session_id()
+        });
+
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let mut stream = V2Stream::handshake(
+            &secp,
+            initiator,
+            Magic::REGTEST,
+            Role::Initiator,
+            secret_key,
+            vec![],
+        )
+        .unwrap();
+        stream.send(&NetworkMessage::Ping(42)).unwrap();
+        assert_eq!(stream.receive().unwrap(), NetworkMessage::Ping(42));
+        assert_eq!(responder.join().unwrap(), *stream.session_id());
+    }
+}
@@ -0,0 +1,333 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Bitcoin policy.
+//!
+//! This module exposes some constants and functions used in the reference
+//! implementation and which, as a consequence, define some network rules.
+//!
+//! # *Warning*
+//! While the constants present in this module are very unlikely to change, they do not define
+//! Bitcoin. As such they must not be relied upon as if they were consensus rules.
+//!
+//! These values were taken from bitcoind v0.21.1 (194b9b8792d9b0798fdb570b79fa51f1d1f5ebaf).
+//!
+
+pub mod package;
+
+use core::{cmp, fmt};
+
+use super::blockdata::constants::{MAX_BLOCK_SIGOPS_COST, WITNESS_SCALE_FACTOR};
+use crate::blockdata::opcodes::Opcode;
+use crate::blockdata::script::Script;
+use crate::blockdata::transaction::{Transaction, Version};
+use crate::{FeeRate, Weight};
+
+/// Maximum weight of a transaction for it to be relayed by most nodes on the network
+pub const MAX_STANDARD_TX_WEIGHT: u32 = 400_000;
+
+/// Minimum non-witness size for a standard transaction (1 segwit input + 1 P2WPKH output = 82 bytes)
+pub const MIN_STANDARD_TX_NONWITNESS_SIZE: u32 = 82;
+
+/// Maximum number of sigops in a standard tx.
+pub const MAX_STANDARD_TX_SIGOPS_COST: u32 = MAX_BLOCK_SIGOPS_COST as u32 / 5;
+
+/// The minimum incremental *feerate* (despite the name), in sats per virtual kilobyte for RBF.
+pub const DEFAULT_INCREMENTAL_RELAY_FEE: u32 = 1_000;
+
+/// The number of bytes equivalent per signature operation. Affects transaction relay through the
+/// virtual size computation.
+pub const DEFAULT_BYTES_PER_SIGOP: u32 = 20;
+
+/// The minimum feerate, in sats per kilo-virtualbyte, for defining dust. An output is considered
+/// dust if spending it under this feerate would cost more in fee.
+pub const DUST_RELAY_TX_FEE: u32 = 3_000;
+
+/// Minimum feerate, in sats per virtual kilobyte, for a transaction to be relayed by most nodes on
+/// the network.
+pub const DEFAULT_MIN_RELAY_TX_FEE: u32 = 1_000;
+
+/// Default number of hours for an unconfirmed transaction to expire in most of the network nodes'
+/// mempools.
+pub const DEFAULT_MEMPOOL_EXPIRY: u32 = 336;
+
+/// Maximum size, in bytes, of a standard scriptSig (large enough for a 15-of-15 P2SH multisig
+/// spend using compressed keys).
+pub const MAX_STANDARD_SCRIPTSIG_SIZE: u32 = 1650;
+
+/// Maximum size, in bytes, of a standard `OP_RETURN` (null data) output script, including the
+/// `OP_RETURN` opcode and push opcodes.
+pub const MAX_OP_RETURN_RELAY: u32 = 83;
+
+/// Maximum number of public keys in a standard bare multisig output.
+pub const MAX_STANDARD_BARE_MULTISIG_KEYS: u8 = 3;
+
+/// The virtual transaction size, as computed by default by bitcoind node.
+pub fn get_virtual_tx_size(weight: i64, n_sigops: i64) -> i64 {
+    (cmp::max(weight, n_sigops * DEFAULT_BYTES_PER_SIGOP as i64) + WITNESS_SCALE_FACTOR as i64 - 1)
+        / WITNESS_SCALE_FACTOR as i64
+}
+
+impl Transaction {
+    /// Checks this transaction against the standardness rules of Bitcoin Core (`IsStandardTx`).
+    ///
+    /// The checks that do not require access to the outputs being spent are performed: the version,
+    /// the weight (against `max_weight`, usually [`Transaction::MAX_STANDARD_WEIGHT`]) and minimum
+    /// size, the size of each scriptSig and that it is push-only, that each output script is of a
+    /// standard type (including bare multisig key limits), that no output is dust at
+    /// `dust_relay_fee` (usually [`FeeRate::DUST`]) and that there is at most one `OP_RETURN`
+    /// output of at most [`MAX_OP_RETURN_RELAY`] bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first rule violated, rules are checked in the order listed above.
+    pub fn check_standard(
+        &self,
+        max_weight: Weight,
+        dust_relay_fee: FeeRate,
+    ) -> Result<(), NonStandardError> {
+        use NonStandardError::*;
+
+        if !self.version.is_standard() {
+            return Err(Version(self.version));
+        }
+
+        let weight = self.weight();
+        if weight > max_weight {
+            return Err(ExcessiveWeight(weight));
+        }
+        let base_size = self.base_size();
+        if base_size < MIN_STANDARD_TX_NONWITNESS_SIZE as usize {
+            return Err(TooSmall(base_size));
+        }
+
+        for (index, txin) in self.input.iter().enumerate() {
+            if txin.script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE as usize {
+                return Err(ScriptSigSize(index));
+            }
+            if !txin.script_sig.is_push_only() {
+                return Err(ScriptSigNotPushOnly(index));
+            }
+        }
+
+        let mut op_return_outputs = 0;
+        for (index, txout) in self.output.iter().enumerate() {
+            let script_pubkey = &txout.script_pubkey;
+            if script_pubkey.is_op_return() {
+                if !Script::from_bytes(&script_pubkey.as_bytes()[1..]).is_push_only() {
+                    return Err(NonStandardScriptPubKey(index));
+                }
+                if script_pubkey.len() > MAX_OP_RETURN_RELAY as usize {
+                    return Err(OpReturnSize(index));
+                }
+                op_return_outputs += 1;
+                continue;
+            }
+
+            if script_pubkey.is_multisig() {
+                if !is_standard_bare_multisig(script_pubkey) {
+                    return Err(BareMultisig(index));
+                }
+            } else if !(script_pubkey.is_p2pk()
+                || script_pubkey.is_p2pkh()
+                || script_pubkey.is_p2sh()
+                || script_pubkey.is_witness_program())
+            {
+                return Err(NonStandardScriptPubKey(index));
+            }
+
+            if txout.is_dust(dust_relay_fee) {
+                return Err(Dust(index));
+            }
+        }
+        if op_return_outputs > 1 {
+            return Err(MultipleOpReturn);
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if the bare multisig `script` has at most [`MAX_STANDARD_BARE_MULTISIG_KEYS`] keys.
+///
+/// `script` must already be known to be a multisig script pubkey.
+fn is_standard_bare_multisig(script: &Script) -> bool {
+    let bytes = script.as_bytes();
+    match Opcode::from(bytes[bytes.len() - 2]).decode_pushnum() {
+        Some(n) => n <= MAX_STANDARD_BARE_MULTISIG_KEYS,
+        None => false,
+    }
+}
+
+/// A transaction violates a standardness rule, see [`Transaction::check_standard`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NonStandardError {
+    /// The transaction version is not standard.
+    Version(Version),
+    /// The transaction weight exceeds the maximum.
+    ExcessiveWeight(Weight),
+    /// The size of the transaction without witness data is below the minimum.
+    TooSmall(usize),
+    /// The scriptSig of the input at this index is too large.
+    ScriptSigSize(usize),
+    /// The scriptSig of the input at this index contains opcodes other than pushes.
+    ScriptSigNotPushOnly(usize),
+    /// The script pubkey of the output at this index is not of a standard type.
+    NonStandardScriptPubKey(usize),
+    /// The output at this index is a bare multisig with too many keys.
+    BareMultisig(usize),
+    /// The output at this index is dust.
+    Dust(usize),
+    /// The `OP_RETURN` output at this index is too large.
+    OpReturnSize(usize),
+    /// The transaction has more than one `OP_RETURN` output.
+    MultipleOpReturn,
+}
+
+internals::impl_from_infallible!(NonStandardError);
+
+impl fmt::Display for NonStandardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use NonStandardError::*;
+
+        match *self {
+            Version(ref v) => write!(f, "non-standard transaction version {}", v),
+            ExcessiveWeight(ref w) => write!(f, "transaction weight {} exceeds the maximum", w),
+            TooSmall(size) => write!(f, "transaction non-witness size {} is too small", size),
+            ScriptSigSize(index) => write!(f, "scriptSig of input {} is too large", index),
+            ScriptSigNotPushOnly(index) =>
+                write!(f, "scriptSig of input {} is not push only", index),
+            NonStandardScriptPubKey(index) =>
+                write!(f, "script pubkey of output {} is non-standard", index),
+            BareMultisig(index) => write!(f, "output {} is a non-standard bare multisig", index),
+            Dust(index) => write!(f, "output {} is dust", index),
+            OpReturnSize(index) => write!(f, "OP_RETURN output {} is too large", index),
+            MultipleOpReturn => f.write_str("transaction has more than one OP_RETURN output"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NonStandardError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use hashes::Hash;
+
+    use super::*;
+    use crate::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_1, OP_RETURN};
+    use crate::blockdata::script::{Builder, PushBytes, ScriptBuf};
+    use crate::{absolute, Amount, OutPoint, Sequence, TxIn, TxOut, Txid};
+
+    fn p2wpkh() -> ScriptBuf {
+        ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6").unwrap()
+    }
+
+    fn standard_tx() -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::all_zeros(), 0),
+                sequence: Sequence::MAX,
+                ..Default::default()
+            }],
+            output: vec![TxOut { value: Amount::from_sat(10_000), script_pubkey: p2wpkh() }],
+        }
+    }
+
+    fn check(tx: &Transaction) -> Result<(), NonStandardError> {
+        tx.check_standard(Transaction::MAX_STANDARD_WEIGHT, FeeRate::DUST)
+    }
+
+    fn multisig(m: i64, n: usize) -> ScriptBuf {
+        let mut builder = Builder::new().push_int(m);
+        for _ in 0..n {
+            builder = builder.push_slice([2; 33]);
+        }
+        builder.push_int(n as i64).push_opcode(OP_CHECKMULTISIG).into_script()
+    }
+
+    #[test]
+    fn standard() {
+        assert_eq!(check(&standard_tx()), Ok(()));
+    }
+
+    #[test]
+    fn version_and_weight() {
+        let mut tx = standard_tx();
+        tx.version = Version::non_standard(3);
+        assert_eq!(check(&tx), Err(NonStandardError::Version(tx.version)));
+
+        let tx = standard_tx();
+        let weight = tx.weight();
+        let max = weight - Weight::from_wu(1);
+        assert_eq!(
+            tx.check_standard(max, FeeRate::DUST),
+            Err(NonStandardError::ExcessiveWeight(weight))
+        );
+
+        let mut tx = standard_tx();
+        tx.output[0].script_pubkey = ScriptBuf::new_op_return([]);
+        assert_eq!(check(&tx), Err(NonStandardError::TooSmall(tx.base_size())));
+    }
+
+    #[test]
+    fn script_sig() {
+        let mut tx = standard_tx();
+        tx.input[0].script_sig = Builder::new().push_opcode(OP_RETURN).into_script();
+        assert_eq!(check(&tx), Err(NonStandardError::ScriptSigNotPushOnly(0)));
+
+        tx.input[0].script_sig = ScriptBuf::from_bytes(vec![0; 1651]);
+        assert_eq!(check(&tx), Err(NonStandardError::ScriptSigSize(0)));
+    }
+
+    #[test]
+    fn script_pubkey() {
+        let mut tx = standard_tx();
+        tx.output[0].value = Amount::from_sat(293);
+        assert_eq!(check(&tx), Err(NonStandardError::Dust(0)));
+
+        tx.output[0].script_pubkey = ScriptBuf::from_bytes(vec![OP_PUSHNUM_1.to_u8(); 25]);
+        tx.output[0].value = Amount::from_sat(10_000);
+        assert_eq!(check(&tx), Err(NonStandardError::NonStandardScriptPubKey(0)));
+
+        tx.output[0].script_pubkey = multisig(1, 3);
+        assert_eq!(check(&tx), Ok(()));
+        tx.output[0].script_pubkey = multisig(1, 4);
+        assert_eq!(check(&tx), Err(NonStandardError::BareMultisig(0)));
+        tx.output[0].script_pubkey = multisig(3, 2);
+        assert_eq!(check(&tx), Err(NonStandardError::NonStandardScriptPubKey(0)));
+    }
+
+    #[test]
+    fn op_return() {
+        let mut tx = standard_tx();
+        let op_return = |data: &[u8]| TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_op_return(<&PushBytes>::try_from(data).unwrap()),
+        };
+
+        tx.output.push(op_return(&[0; 80]));
+        assert_eq!(check(&tx), Ok(()));
+
+        tx.output[1] = op_return(&[0; 81]);
+        assert_eq!(check(&tx), Err(NonStandardError::OpReturnSize(1)));
+
+        tx.output[1] = op_return(&[0; 10]);
+        tx.output.push(op_return(&[0; 10]));
+        assert_eq!(check(&tx), Err(NonStandardError::MultipleOpReturn));
+
+        tx.output.truncate(1);
+        tx.output.push(TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new()
+                .push_opcode(OP_RETURN)
+                .push_opcode(OP_RETURN)
+                .into_script(),
+        });
+        assert_eq!(check(&tx), Err(NonStandardError::NonStandardScriptPubKey(1)));
+    }
+}
@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Transaction packages.
+//!
+//! Functions to compute the combined fee rate of a package of related unconfirmed transactions,
+//! for example a parent and a child paying for it (CPFP), and to aggregate the ancestors or
+//! descendants of a transaction within a package.
+//!
+//! Like Bitcoin Core, the size of a package is the sum of the virtual sizes of its transactions,
+//! each rounded up individually, and fee rates are rounded down.
+
+use crate::blockdata::transaction::{Transaction, Txid};
+use crate::prelude::*;
+use crate::{Amount, FeeRate, Weight};
+
+/// The combined fee and size of a set of transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Aggregate {
+    /// The total fee paid by the transactions.
+    pub fee: Amount,
+    /// The sum of the virtual sizes of the transactions.
+    pub vsize: u64,
+    /// The number of transactions.
+    pub count: usize,
+}
+
+impl Aggregate {
+    /// An empty set of transactions.
+    pub const EMPTY: Self = Aggregate { fee: Amount::ZERO, vsize: 0, count: 0 };
+
+    /// Adds a transaction paying `fee`, returns `None` if overflow occurred.
+    pub fn checked_add_tx(self, tx: &Transaction, fee: Amount) -> Option<Self> {
+        Some(Aggregate {
+            fee: self.fee.checked_add(fee)?,
+            vsize: self.vsize.checked_add(tx.vsize() as u64)?,
+            count: self.count + 1,
+        })
+    }
+
+    /// Computes the fee rate of the transactions, returns `None` if there are none or if
+    /// overflow occurred.
+    pub fn fee_rate(&self) -> Option<FeeRate> {
+        let weight = Weight::from_vb(self.vsize)?;
+        let sat_per_kwu = self.fee.to_sat().checked_mul(1000)?.checked_div(weight.to_wu())?;
+        Some(FeeRate::from_sat_per_kwu(sat_per_kwu))
+    }
+}
+
+/// Computes the fee rate of a package, given each transaction with the fee it pays.
+///
+/// Returns `None` if `package` is empty or if overflow occurred.
+///
+/// # Examples
+///
+/// ```
+/// # use bitcoin::{absolute, Amount, FeeRate, Transaction};
+/// # use bitcoin::policy::package::package_feerate;
+/// # use bitcoin::transaction::Version;
+/// # let tx = Transaction {
+/// #     version: Version::TWO,
+/// #     lock_time: absolute::LockTime::ZERO,
+/// #     input: vec![],
+/// #     output: vec![],
+/// # };
+/// // A parent paying no fee and a child paying for both.
+/// let (parent, child) = (tx.clone(), tx);
+/// let vsize = (parent.vsize() + child.vsize()) as u64;
+/// let fee_rate = package_feerate(&[(&parent, Amount::ZERO), (&child, Amount::from_sat(vsize))]);
+/// assert_eq!(fee_rate, FeeRate::from_sat_per_vb(1));
+/// ```
+pub fn package_feerate(package: &[(&Transaction, Amount)]) -> Option<FeeRate> {
+    aggregate(package.iter().copied())?.fee_rate()
+}
+
+/// Aggregates the transaction with `txid` and its ancestors within `package`.
+///
+/// An ancestor is a transaction in `package` spent, directly or indirectly, by the transaction.
+/// Returns `None` if `txid` is not in `package` or if overflow occurred.
+pub fn ancestors(package: &[(&Transaction, Amount)], txid: Txid) -> Option<Aggregate> {
+    let txids = package.iter().map(|(tx, _)| tx.compute_txid()).collect::<Vec<_>>();
+    let start = txids.iter().position(|id| *id == txid)?;
+
+    let mut included = vec![false; package.len()];
+    included[start] = true;
+    let mut stack = vec![start];
+    while let Some(index) = stack.pop() {
+        for input in &package[index].0.input {
+            let parent = txids.iter().position(|id| *id == input.previous_output.txid);
+            if let Some(parent) = parent {
+                if !included[parent] {
+                    included[parent] = true;
+                    stack.push(parent);
+                }
+            }
+        }
+    }
+    aggregate(package.iter().zip(included).filter(|(_, included)| *included).map(|(tx, _)| *tx))
+}
+
+/// Aggregates the transaction with `txid` and its descendants within `package`.
+///
+/// A descendant is a transaction in `package` spending, directly or indirectly, the transaction.
+/// Returns `None` if `txid` is not in `package` or if overflow occurred.
+pub fn descendants(package: &[(&Transaction, Amount)], txid: Txid) -> Option<Aggregate> {
+    let txids = package.iter().map(|(tx, _)| tx.compute_txid()).collect::<Vec<_>>();
+    let start = txids.iter().position(|id| *id == txid)?;
+
+    let mut included = vec![false; package.len()];
+    included[start] = true;
+    let mut stack = vec![start];
+    while let Some(index) = stack.pop() {
+        for (child, (tx, _)) in package.iter().enumerate() {
+            if !included[child]
+                && tx.input.iter().any(|input| input.previous_output.txid == txids[index])
+            {
+                included[child] = true;
+                stack.push(child);
+            }
+        }
+    }
+    aggregate(package.iter().zip(included).filter(|(_, included)| *included).map(|(tx, _)| *tx))
+}
+
+/// Aggregates `txs`, returns `None` if overflow occurred.
+fn aggregate<'a, I>(txs: I) -> Option<Aggregate>
+where
+    I: IntoIterator<Item = (&'a Transaction, Amount)>,
+{
+    txs.into_iter().try_fold(Aggregate::EMPTY, |acc, (tx, fee)| acc.checked_add_tx(tx, fee))
+}
+
+#[cfg(test)]
+mod tests {
+    use hashes::Hash;
+
+    use super::*;
+    use crate::{absolute, transaction, OutPoint, ScriptBuf, TxIn, TxOut};
+
+    fn spending(txids: &[Txid]) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: txids
+                .iter()
+                .map(|txid| TxIn { previous_output: OutPoint::new(*txid, 0), ..Default::default() })
+                .collect(),
+            output: vec![TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::from_hex("0014751e76e8199196d454941c45d1b3a323f1433bd6")
+                    .unwrap(),
+            }],
+        }
+    }
+
+    #[test]
+    fn aggregate_fee_rate() {
+        assert_eq!(Aggregate::EMPTY.fee_rate(), None);
+
+        let aggregate = Aggregate { fee: Amount::from_sat(1_000), vsize: 300, count: 2 };
+        // 1000 sat / 1200 wu, rounded down.
+        assert_eq!(aggregate.fee_rate(), Some(FeeRate::from_sat_per_kwu(833)));
+
+        let aggregate = Aggregate { fee: Amount::MAX, vsize: 1, count: 1 };
+        assert_eq!(aggregate.fee_rate(), None);
+    }
+
+    #[test]
+    fn cpfp_package() {
+        let parent = spending(&[Txid::all_zeros()]);
+        let child = spending(&[parent.compute_txid()]);
+        let unrelated = spending(&[Txid::from_byte_array([1; 32])]);
+        let vsize = parent.vsize() as u64;
+        assert_eq!(child.vsize() as u64, vsize);
+
+        let package = [
+            (&parent, Amount::ZERO),
+            (&child, Amount::from_sat(4 * vsize)),
+            (&unrelated, Amount::from_sat(vsize)),
+        ];
+        assert_eq!(package_feerate(&package[..2]), FeeRate::from_sat_per_vb(2));
+        assert_eq!(package_feerate(&[]), None);
+
+        let ancestors = ancestors(&package, child.compute_txid()).unwrap();
+        assert_eq!(
+            ancestors,
+            Aggregate { fee: Amount::from_sat(4 * vsize), vsize: 2 * vsize, count: 2 }
+        );
+        let parent_ancestors = super::ancestors(&package, parent.compute_txid()).unwrap();
+        assert_eq!(parent_ancestors.count, 1);
+
+        let descendants = descendants(&package, parent.compute_txid()).unwrap();
+        assert_eq!(descendants, ancestors);
+        let child_descendants = super::descendants(&package, child.compute_txid()).unwrap();
+        assert_eq!(child_descendants.count, 1);
+
+        assert_eq!(super::ancestors(&package[..1], child.compute_txid()), None);
+    }
+
+    #[test]
+    fn transitive_ancestors_and_descendants() {
+        let grandparent = spending(&[Txid::all_zeros()]);
+        let parent = spending(&[grandparent.compute_txid()]);
+        let mut uncle = spending(&[grandparent.compute_txid()]);
+        uncle.lock_time = absolute::LockTime::from_consensus(1);
+        let child = spending(&[parent.compute_txid(), uncle.compute_txid()]);
+        let fee = Amount::from_sat(1_000);
+        // Order within the package does not matter.
+        let package = [(&child, fee), (&uncle, fee), (&grandparent, fee), (&parent, fee)];
+
+        assert_eq!(ancestors(&package, child.compute_txid()).unwrap().count, 4);
+        assert_eq!(ancestors(&package, uncle.compute_txid()).unwrap().count, 2);
+        assert_eq!(descendants(&package, grandparent.compute_txid()).unwrap().count, 4);
+        assert_eq!(descendants(&package, parent.compute_txid()).unwrap().count, 2);
+    }
+}
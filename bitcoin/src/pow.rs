@@ -6,6 +6,7 @@
 //! functions here are designed to be fast, by that we mean it is safe to use them to check headers.
 //!
 
+use core::cmp;
 use core::fmt::{self, LowerHex, UpperHex};
 use core::ops::{Add, Div, Mul, Not, Rem, Shl, Shr, Sub};
 
@@ -13,9 +14,8 @@ use io::{BufRead, Write};
 #[cfg(all(test, mutate))]
 use mutagen::mutate;
 
-use crate::blockdata::block::BlockHash;
+use crate::blockdata::block::{BlockHash, Header};
 use crate::consensus::encode::{self, Decodable, Encodable};
-#[cfg(doc)]
 use crate::consensus::Params;
 use crate::error::{PrefixedHexError, UnprefixedHexError, ContainsPrefixError, MissingPrefixError};
 use crate::{parse, Network};
@@ -77,6 +77,39 @@ impl Work {
     /// `log2_work` output in its logs.
     #[cfg(feature = "std")]
     pub fn log2(self) -> f64 { self.0.to_f64().log2() }
+
+    /// Returns a type that displays log2 of this work like Bitcoin Core's `log2_work`.
+    ///
+    /// The value is displayed with 6 decimal places unless a precision is specified.
+    #[cfg(feature = "std")]
+    pub fn display_log2(self) -> impl fmt::Display {
+        struct DisplayLog2(f64);
+
+        impl fmt::Display for DisplayLog2 {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let precision = f.precision().unwrap_or(6);
+                write!(f, "{:.*}", precision, self.0)
+            }
+        }
+
+        DisplayLog2(self.log2())
+    }
+
+    /// Computes `self + rhs`, returning `None` if overflow occurred.
+    pub fn checked_add(self, rhs: Work) -> Option<Work> {
+        match self.0.overflowing_add(rhs.0) {
+            (work, false) => Some(Work(work)),
+            (_, true) => None,
+        }
+    }
+
+    /// Computes `self - rhs`, returning `None` if overflow occurred.
+    pub fn checked_sub(self, rhs: Work) -> Option<Work> {
+        match self.0.overflowing_sub(rhs.0) {
+            (work, false) => Some(Work(work)),
+            (_, true) => None,
+        }
+    }
 }
 do_impl!(Work);
 
@@ -90,6 +123,67 @@ impl Sub for Work {
     fn sub(self, rhs: Self) -> Self { Work(self.0 - rhs.0) }
 }
 
+/// The total work of a chain of block headers.
+///
+/// Summing the [`Work`] of each header, the chain with the most work is the best chain. The sum
+/// saturates instead of overflowing, which can't happen for valid headers.
+///
+/// # Examples
+///
+/// ```
+/// # use bitcoin::blockdata::constants::genesis_block;
+/// # use bitcoin::pow::ChainWork;
+/// # use bitcoin::Network;
+/// let genesis = genesis_block(Network::Bitcoin).header;
+/// let mut work = ChainWork::from_headers(&[genesis]);
+/// let other = work;
+/// work.add_header(&genesis);
+/// assert!(work > other);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
+pub struct ChainWork(Work);
+
+impl ChainWork {
+    /// The work of an empty chain.
+    pub const ZERO: ChainWork = ChainWork(Work(U256::ZERO));
+
+    /// Creates a [`ChainWork`] starting at `work`, e.g. the chain work of a checkpoint.
+    pub fn new(work: Work) -> ChainWork { ChainWork(work) }
+
+    /// Computes the total work of `headers`.
+    pub fn from_headers<'a, I>(headers: I) -> ChainWork
+    where
+        I: IntoIterator<Item = &'a Header>,
+    {
+        let mut chain_work = ChainWork::ZERO;
+        for header in headers {
+            chain_work.add_header(header);
+        }
+        chain_work
+    }
+
+    /// Adds `work` to the total.
+    pub fn add_work(&mut self, work: Work) {
+        self.0 = self.0.checked_add(work).unwrap_or(Work(U256::MAX));
+    }
+
+    /// Adds the work of `header` to the total.
+    pub fn add_header(&mut self, header: &Header) { self.add_work(header.work()) }
+
+    /// Returns the total work.
+    pub fn to_work(self) -> Work { self.0 }
+}
+
+impl From<Work> for ChainWork {
+    fn from(work: Work) -> Self { ChainWork(work) }
+}
+
+impl fmt::Display for ChainWork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self.0, f) }
+}
+
 /// A 256 bit integer representing target.
 ///
 /// The SHA-256 hash of a block's header must be lower than or equal to the current target for the
@@ -163,6 +257,31 @@ impl Target {
         }
     }
 
+    /// Computes the [`Target`] value from a compact representation, rejecting malformed values.
+    ///
+    /// Decodes like Bitcoin Core's `SetCompact` and fails in the cases in which Core reports the
+    /// value as negative or overflowing, which it rejects when checking proof of work.
+    /// [`Target::from_compact`] instead returns [`Target::ZERO`] for negative values and silently
+    /// truncates overflowing ones.
+    pub fn from_compact_checked(c: CompactTarget) -> Result<Target, CompactTargetError> {
+        let size = c.0 >> 24;
+        let word = c.0 & 0x007F_FFFF;
+        let word = if size <= 3 { word >> (8 * (3 - size)) } else { word };
+
+        if word != 0 && c.0 & 0x0080_0000 != 0 {
+            return Err(CompactTargetError::Negative(c));
+        }
+        if word != 0 && (size > 34 || (word > 0xFF && size > 33) || (word > 0xFFFF && size > 32)) {
+            return Err(CompactTargetError::Overflow(c));
+        }
+
+        if size <= 3 {
+            Ok(Target(U256::from(word)))
+        } else {
+            Ok(Target(U256::from(word) << (8 * (size - 3))))
+        }
+    }
+
     /// Computes the compact value from a [`Target`] representation.
     ///
     /// The compact form is by definition lossy, this means that
@@ -255,6 +374,50 @@ impl Target {
     /// The difficulty can only decrease or increase by a factor of 4 max on each difficulty
     /// adjustment period.
     pub fn max_difficulty_transition_threshold(&self) -> Self { Self(self.0 << 2) }
+
+    /// Computes the target of the first block of a difficulty adjustment period.
+    ///
+    /// `self` is the target of the last block of the previous period, `first_timestamp` and
+    /// `last_timestamp` are the timestamps of the first and the last block of that period. Note
+    /// that, like in Bitcoin Core, these span one block less than the adjustment interval.
+    ///
+    /// The timespan is clamped to between a quarter and four times
+    /// [`Params::pow_target_timespan`] and the new target to at most [`Params::pow_limit`]. The
+    /// arithmetic is exact, the result is the same as Bitcoin Core's before it is converted to a
+    /// [`CompactTarget`]. If [`Params::no_pow_retargeting`] is set `self` is returned.
+    pub fn retarget(self, params: &Params, first_timestamp: u32, last_timestamp: u32) -> Target {
+        if params.no_pow_retargeting {
+            return self;
+        }
+        let timespan = i64::from(last_timestamp) - i64::from(first_timestamp);
+        self.retarget_timespan(params, timespan)
+    }
+
+    fn retarget_timespan(self, params: &Params, timespan: i64) -> Target {
+        let target_timespan = params.pow_target_timespan;
+        let timespan = (timespan.max(0) as u64).clamp(target_timespan / 4, target_timespan * 4);
+
+        // Computes `self * timespan / target_timespan` rounding down like Bitcoin Core but without
+        // overflowing for targets close to the maximum.
+        let (quotient, remainder) = self.0.div_rem(target_timespan.into());
+        let remainder = remainder.low_u128() * u128::from(timespan) / u128::from(target_timespan);
+        let (product, overflow) = quotient.mul_u64(timespan);
+        let (target, carry) = product.overflowing_add(remainder.into());
+        let target = if overflow || carry { params.pow_limit } else { Target(target) };
+        cmp::min(target, params.pow_limit)
+    }
+
+    /// Returns whether a block with timestamp `timestamp` may use the minimum difficulty target,
+    /// [`Params::pow_limit`], if it doesn't start a difficulty adjustment period.
+    ///
+    /// This is allowed on networks with [`Params::allow_min_difficulty_blocks`] set, such as
+    /// testnet, if the block's timestamp is more than twice the target spacing after the
+    /// timestamp of the previous block, `prev_timestamp`. Otherwise such a block must use the
+    /// target of the last block that didn't use the minimum difficulty, or started the period.
+    pub fn allows_min_difficulty(params: &Params, prev_timestamp: u32, timestamp: u32) -> bool {
+        params.allow_min_difficulty_blocks
+            && u64::from(timestamp) > u64::from(prev_timestamp) + params.pow_target_spacing * 2
+    }
 }
 do_impl!(Target);
 
@@ -299,6 +462,37 @@ impl CompactTarget {
 
     /// Returns the consensus encoded `u32` representation of this [`CompactTarget`].
     pub fn to_consensus(self) -> u32 { self.0 }
+
+    /// Returns true if this is the canonical encoding of its target.
+    ///
+    /// A target has many compact encodings but Bitcoin Core only produces the one returned by
+    /// [`Target::to_compact_lossy`]. Negative and overflowing values are never normalized.
+    pub fn is_normalized(self) -> bool {
+        match Target::from_compact_checked(self) {
+            Ok(target) => target.to_compact_lossy() == self,
+            Err(_) => false,
+        }
+    }
+
+    /// Computes the target of the first block of a difficulty adjustment period.
+    ///
+    /// `last` is the target of the last block of the previous period and `timespan` is the
+    /// difference between the timestamps of the last and the first block of that period. Note
+    /// that, like in Bitcoin Core, this spans one block less than the adjustment interval.
+    ///
+    /// The timespan is clamped to between a quarter and four times
+    /// [`Params::pow_target_timespan`] and the new target to at most [`Params::pow_limit`]. If
+    /// [`Params::no_pow_retargeting`] is set `last` is returned.
+    pub fn from_next_work_required(
+        last: CompactTarget,
+        timespan: i64,
+        params: &Params,
+    ) -> CompactTarget {
+        if params.no_pow_retargeting {
+            return last;
+        }
+        Target::from_compact(last).retarget_timespan(params, timespan).to_compact_lossy()
+    }
 }
 
 impl From<CompactTarget> for Target {
@@ -736,6 +930,40 @@ impl<T: Into<u128>> From<T> for U256 {
     fn from(x: T) -> Self { U256(0, x.into()) }
 }
 
+/// Error decoding a malformed [`CompactTarget`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompactTargetError {
+    /// The sign bit of the mantissa is set for a non-zero value.
+    Negative(CompactTarget),
+    /// The value doesn't fit in 256 bits.
+    Overflow(CompactTarget),
+}
+
+internals::impl_from_infallible!(CompactTargetError);
+
+impl fmt::Display for CompactTargetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CompactTargetError::*;
+
+        match *self {
+            Negative(c) => write!(f, "compact target {:#010x} is negative", c),
+            Overflow(c) => write!(f, "compact target {:#010x} overflows 256 bits", c),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CompactTargetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use CompactTargetError::*;
+
+        match *self {
+            Negative(_) | Overflow(_) => None,
+        }
+    }
+}
+
 /// Error from `TryFrom<signed type>` implementations, occurs when input is negative.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -1536,6 +1764,110 @@ mod tests {
         assert!(((U256::from(u) << 128) + U256::from(u)).is_max());
     }
 
+    // Test vectors from Bitcoin Core's `pow_tests.cpp`.
+    #[test]
+    fn compact_target_from_next_work_required() {
+        let params = Params::MAINNET;
+        let next = |bits, first_time: i64, last_time: i64| {
+            let bits = CompactTarget::from_consensus(bits);
+            CompactTarget::from_next_work_required(bits, last_time - first_time, &params)
+                .to_consensus()
+        };
+
+        // Blocks 30240 to 32255.
+        assert_eq!(next(0x1d00ffff, 1261130161, 1262152739), 0x1d00d86a);
+        // Blocks 0 to 2015, the target is limited by the proof of work limit.
+        assert_eq!(next(0x1d00ffff, 1231006505, 1233061996), 0x1d00ffff);
+        // Blocks 66528 to 68543, the timespan is clamped to a quarter.
+        assert_eq!(next(0x1c05a3f4, 1279008237, 1279297671), 0x1c0168fd);
+        // Blocks 46368 to 48383, the timespan is clamped to four times.
+        assert_eq!(next(0x1c387f6f, 1263163443, 1269211443), 0x1d00e1fd);
+
+        let bits = CompactTarget::from_consensus(0x1c05a3f4);
+        assert_eq!(CompactTarget::from_next_work_required(bits, 1, &Params::REGTEST), bits);
+    }
+
+    #[test]
+    fn target_retarget() {
+        let params = Params::MAINNET;
+        let target = Target::from_compact(CompactTarget::from_consensus(0x1d00ffff));
+        let retarget = target.retarget(&params, 1261130161, 1262152739);
+        assert_eq!(retarget.to_compact_lossy(), CompactTarget::from_consensus(0x1d00d86a));
+        // Exact arithmetic: the target scaled by the timespan before compact rounding.
+        let timespan = 1262152739 - 1261130161;
+        assert_eq!(
+            retarget,
+            Target(target.0.mul_u64(timespan).0 / U256::from(params.pow_target_timespan))
+        );
+
+        // Timestamps going backwards are clamped like any timespan that is too short.
+        assert_eq!(target.retarget(&params, 2000, 1000), target.retarget(&params, 1000, 1001));
+        assert_eq!(Target::MAX.retarget(&params, 0, u32::MAX), params.pow_limit);
+        assert_eq!(Target::MAX.retarget(&Params::REGTEST, 0, u32::MAX), Target::MAX);
+    }
+
+    #[test]
+    fn target_allows_min_difficulty() {
+        assert!(Target::allows_min_difficulty(&Params::TESTNET, 1000, 2201));
+        assert!(!Target::allows_min_difficulty(&Params::TESTNET, 1000, 2200));
+        assert!(!Target::allows_min_difficulty(&Params::TESTNET, 1000, 0));
+        assert!(!Target::allows_min_difficulty(&Params::MAINNET, 1000, 2201));
+    }
+
+    // Test vectors from Bitcoin Core's `arith_uint256_tests.cpp`.
+    #[test]
+    fn target_from_compact_checked() {
+        let checked = |bits| Target::from_compact_checked(CompactTarget::from_consensus(bits));
+        let ok = |bits, target: u128| assert_eq!(checked(bits), Ok(Target::from(target)));
+        let negative = |bits| {
+            let c = CompactTarget::from_consensus(bits);
+            assert_eq!(checked(bits), Err(CompactTargetError::Negative(c)));
+        };
+        let overflow = |bits| {
+            let c = CompactTarget::from_consensus(bits);
+            assert_eq!(checked(bits), Err(CompactTargetError::Overflow(c)));
+        };
+
+        ok(0, 0);
+        ok(0x00123456, 0);
+        ok(0x01003456, 0);
+        ok(0x02000056, 0);
+        ok(0x03000000, 0);
+        ok(0x04000000, 0);
+        ok(0x00923456, 0);
+        ok(0x01803456, 0);
+        ok(0x02800056, 0);
+        ok(0x03800000, 0);
+        ok(0x04800000, 0);
+        ok(0x01123456, 0x12);
+        negative(0x01fedcba);
+        ok(0x02123456, 0x1234);
+        ok(0x03123456, 0x123456);
+        ok(0x04123456, 0x12345600);
+        negative(0x04923456);
+        ok(0x05009234, 0x92340000);
+        let target = Target::from_compact(CompactTarget::from_consensus(0x20123456));
+        assert_eq!(checked(0x20123456), Ok(target));
+        overflow(0xff123456);
+        // The largest values that don't overflow.
+        assert!(checked(0x22000000 | 0xFF).is_ok());
+        overflow(0x22000100);
+        assert!(checked(0x21000000 | 0xFFFF).is_ok());
+        overflow(0x21010000);
+        assert!(checked(0x20000000 | 0x7FFFFF).is_ok());
+    }
+
+    #[test]
+    fn compact_target_is_normalized() {
+        assert!(CompactTarget::from_consensus(0x1d00ffff).is_normalized());
+        assert!(CompactTarget::from_consensus(0x207fffff).is_normalized());
+        // The same targets with a larger exponent.
+        assert!(!CompactTarget::from_consensus(0x1e0000ff).is_normalized());
+        assert!(!CompactTarget::from_consensus(0x04000001).is_normalized());
+        assert!(!CompactTarget::from_consensus(0x04923456).is_normalized());
+        assert!(!CompactTarget::from_consensus(0xff123456).is_normalized());
+    }
+
     #[test]
     fn compact_target_from_hex_lower() {
         let target = CompactTarget::from_hex("0x010034ab").unwrap();
@@ -1675,6 +2007,40 @@ mod tests {
         assert_eq!(Work(U256::MAX).log2(), 256.0);
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn work_display_log2() {
+        let work = Work::from(0x200020002_u128);
+        assert_eq!(work.display_log2().to_string(), "33.000022");
+        assert_eq!(format!("{:.2}", work.display_log2()), "33.00");
+    }
+
+    #[test]
+    fn work_checked_add_sub() {
+        let one = Work(U256::ONE);
+        assert_eq!(one.checked_add(one), Some(Work::from(2_u32)));
+        assert_eq!(Work(U256::MAX).checked_add(one), None);
+        assert_eq!(one.checked_sub(one), Some(Work(U256::ZERO)));
+        assert_eq!(Work(U256::ZERO).checked_sub(one), None);
+    }
+
+    #[test]
+    fn chain_work() {
+        use crate::blockdata::constants::genesis_block;
+
+        let genesis = genesis_block(Network::Bitcoin).header;
+        assert_eq!(genesis.work(), Work::from(0x100010001_u128));
+
+        let work = ChainWork::from_headers(&[genesis, genesis]);
+        assert_eq!(work.to_work(), Work::from(0x200020002_u128));
+        assert!(work > ChainWork::from_headers(&[genesis]));
+        assert!(ChainWork::from_headers(&[]) == ChainWork::ZERO);
+
+        let mut work = ChainWork::new(Work(U256::MAX));
+        work.add_header(&genesis);
+        assert_eq!(work.to_work(), Work(U256::MAX));
+    }
+
     #[test]
     fn u256_zero_min_max_inverse() {
         assert_eq!(U256::MAX.inverse(), U256::ONE);
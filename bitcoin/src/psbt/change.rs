@@ -0,0 +1,555 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! PSBT change output verification.
+//!
+//! Checks that the outputs of a PSBT claiming to pay back to the wallet really do, so that a
+//! signer can detect a coordinator substituting the change address.
+//!
+
+use core::fmt;
+
+use secp256k1::{Secp256k1, Verification, XOnlyPublicKey};
+
+use crate::bip32::{KeySource, Xpub};
+use crate::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_CHECKSIGADD, OP_NUMEQUAL};
+use crate::blockdata::script::{Instruction, Script, ScriptType};
+use crate::crypto::key::{CompressedPublicKey, PublicKey, TapTweak};
+use crate::prelude::*;
+use crate::psbt::{Output, Psbt};
+use crate::taproot::{LeafVersion, TapTree};
+
+impl Psbt {
+    /// Verifies the outputs of this PSBT claiming to pay to keys of a wallet.
+    ///
+    /// The wallet is given by its account extended public keys, each mapped to its own origin
+    /// like in the global `xpub` field. An output claims to be change if one of its
+    /// `bip32_derivation` or `tap_key_origins` entries has the fingerprint of one of these
+    /// origins. The keys of such entries must derive from the account xpub and the script
+    /// pubkey of the output must commit to them, through the redeem script, witness script or
+    /// taproot tree of the output where needed.
+    ///
+    /// Only outputs which can't be spent without the wallet are accepted: P2PKH and P2WPKH,
+    /// possibly nested in P2SH, sorted multisig whose threshold can't be met by the other keys,
+    /// and taproot outputs with a wallet internal key whose leaves are all sorted `multi_a` or
+    /// single key scripts with the same property.
+    ///
+    /// Returns the indices of the verified change outputs, all other outputs pay to third
+    /// parties.
+    ///
+    /// # Errors
+    ///
+    /// If an output claims to be change but does not pay to the wallet, which signers should
+    /// treat as an attack.
+    pub fn verify_outputs_against<C: Verification>(
+        &self,
+        wallet: &BTreeMap<Xpub, KeySource>,
+        secp: &Secp256k1<C>,
+    ) -> Result<Vec<usize>, ChangeError> {
+        let mut change = vec![];
+
+        for (index, (tx_out, output)) in
+            self.unsigned_tx.output.iter().zip(&self.outputs).enumerate()
+        {
+            let mut ecdsa_keys = vec![];
+            for (key, origin) in &output.bip32_derivation {
+                if let Some(derived) = derive_wallet_key(wallet, origin, secp, index)? {
+                    if derived.public_key != *key {
+                        return Err(ChangeError::KeyMismatch { index });
+                    }
+                    ecdsa_keys.push(*key);
+                }
+            }
+            let mut taproot_keys = vec![];
+            for (key, (_, origin)) in &output.tap_key_origins {
+                if let Some(derived) = derive_wallet_key(wallet, origin, secp, index)? {
+                    if derived.to_x_only_pub() != *key {
+                        return Err(ChangeError::KeyMismatch { index });
+                    }
+                    taproot_keys.push(*key);
+                }
+            }
+
+            if ecdsa_keys.is_empty() && taproot_keys.is_empty() {
+                continue;
+            }
+            if !commits_to_keys(&tx_out.script_pubkey, output, &ecdsa_keys, &taproot_keys, secp) {
+                return Err(ChangeError::ScriptMismatch { index });
+            }
+            change.push(index);
+        }
+
+        Ok(change)
+    }
+}
+
+/// Derives the key with `origin` from the wallet, returns `None` if the origin is not the wallet's.
+fn derive_wallet_key<C: Verification>(
+    wallet: &BTreeMap<Xpub, KeySource>,
+    origin: &KeySource,
+    secp: &Secp256k1<C>,
+    index: usize,
+) -> Result<Option<Xpub>, ChangeError> {
+    let (fingerprint, path) = origin;
+    if !wallet.values().any(|(wallet_fingerprint, _)| wallet_fingerprint == fingerprint) {
+        return Ok(None);
+    }
+
+    for (xpub, (wallet_fingerprint, account_path)) in wallet {
+        if wallet_fingerprint == fingerprint
+            && path.len() >= account_path.len()
+            && path[..account_path.len()] == account_path[..]
+        {
+            if let Ok(derived) = xpub.derive_pub(secp, &&path[account_path.len()..]) {
+                return Ok(Some(derived));
+            }
+        }
+    }
+    Err(ChangeError::UnknownDerivation { index })
+}
+
+/// Checks that `script_pubkey` commits to all of `ecdsa_keys` or all of `taproot_keys`, and can't
+/// be spent without some of them.
+fn commits_to_keys<C: Verification>(
+    script_pubkey: &Script,
+    output: &Output,
+    ecdsa_keys: &[secp256k1::PublicKey],
+    taproot_keys: &[XOnlyPublicKey],
+    secp: &Secp256k1<C>,
+) -> bool {
+    if !taproot_keys.is_empty() {
+        return ecdsa_keys.is_empty()
+            && commits_to_taproot_keys(script_pubkey, output, taproot_keys, secp);
+    }
+
+    match script_pubkey.classify() {
+        ScriptType::P2pkh(hash) =>
+            matches!(ecdsa_keys, [key] if PublicKey::new(*key).pubkey_hash() == hash),
+        ScriptType::P2wpkh(hash) =>
+            matches!(ecdsa_keys, [key] if CompressedPublicKey(*key).wpubkey_hash() == hash),
+        ScriptType::P2sh(hash) => match output.redeem_script {
+            Some(ref redeem_script) if redeem_script.script_hash() == hash =>
+                if redeem_script.is_p2wpkh() || redeem_script.is_p2wsh() {
+                    commits_to_keys(redeem_script, output, ecdsa_keys, taproot_keys, secp)
+                } else {
+                    multisig_commits_to_keys(redeem_script, ecdsa_keys)
+                },
+            _ => false,
+        },
+        ScriptType::P2wsh(hash) => match output.witness_script {
+            Some(ref witness_script) if witness_script.wscript_hash() == hash =>
+                multisig_commits_to_keys(witness_script, ecdsa_keys),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Checks that `script` is a sorted multisig containing all of `ecdsa_keys`, whose threshold
+/// can't be met without some of them.
+fn multisig_commits_to_keys(script: &Script, ecdsa_keys: &[secp256k1::PublicKey]) -> bool {
+    let wallet_keys = ecdsa_keys.iter().map(|key| key.serialize()).collect::<Vec<_>>();
+    match sorted_multisig(script) {
+        Some((threshold, keys)) =>
+            wallet_keys.iter().all(|wallet_key| keys.contains(&&wallet_key[..]))
+                && needs_wallet_keys(threshold, &keys, &wallet_keys),
+        None => false,
+    }
+}
+
+/// Checks that the taproot `script_pubkey` commits to each key as internal key or in a leaf.
+///
+/// The internal key must be one of `taproot_keys`, and each leaf a sorted `multi_a` or single key
+/// script whose threshold can't be met without some of them.
+fn commits_to_taproot_keys<C: Verification>(
+    script_pubkey: &Script,
+    output: &Output,
+    taproot_keys: &[XOnlyPublicKey],
+    secp: &Secp256k1<C>,
+) -> bool {
+    let (output_key, internal_key) = match (script_pubkey.classify(), output.tap_internal_key) {
+        (ScriptType::P2tr(output_key), Some(internal_key)) => (output_key, internal_key),
+        _ => return false,
+    };
+    if !taproot_keys.contains(&internal_key) {
+        return false;
+    }
+    let merkle_root = output.tap_tree.as_ref().map(|tree| tree.root_hash());
+    if internal_key.tap_tweak(secp, merkle_root).0 != output_key {
+        return false;
+    }
+
+    let script_leaves = output.tap_tree.iter().flat_map(TapTree::script_leaves).collect::<Vec<_>>();
+    // Leaves of unknown versions are spendable by anyone.
+    let leaves = script_leaves
+        .iter()
+        .map(|leaf| match leaf.version() {
+            LeafVersion::TapScript => sorted_multi_a(leaf.script()),
+            LeafVersion::Future(_) => None,
+        })
+        .collect::<Option<Vec<_>>>();
+    let leaves = match leaves {
+        Some(leaves) => leaves,
+        None => return false,
+    };
+    let wallet_keys = taproot_keys.iter().map(|key| key.serialize()).collect::<Vec<_>>();
+    leaves.iter().all(|(threshold, keys)| needs_wallet_keys(*threshold, keys, &wallet_keys))
+        && taproot_keys.iter().zip(&wallet_keys).all(|(key, wallet_key)| {
+            *key == internal_key || leaves.iter().any(|(_, keys)| keys.contains(&&wallet_key[..]))
+        })
+}
+
+/// Checks that `threshold` of `keys` can't be met without some of `wallet_keys`.
+fn needs_wallet_keys<K: AsRef<[u8]>>(threshold: usize, keys: &[&[u8]], wallet_keys: &[K]) -> bool {
+    let foreign = keys
+        .iter()
+        .filter(|key| !wallet_keys.iter().any(|wallet_key| wallet_key.as_ref() == **key))
+        .count();
+    foreign < threshold
+}
+
+/// Parses a `sortedmulti` script, `<m> <key>... <n> OP_CHECKMULTISIG` with compressed keys in
+/// ascending order, returning the threshold and the keys.
+fn sorted_multisig(script: &Script) -> Option<(usize, Vec<&[u8]>)> {
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let (threshold, pushes, count) = match instructions[..] {
+        [ref threshold, ref pushes @ .., ref count, Instruction::Op(op)]
+            if op == OP_CHECKMULTISIG =>
+            (pushnum(threshold)?, pushes, pushnum(count)?),
+        _ => return None,
+    };
+    let keys = pushes
+        .iter()
+        .map(|instruction| match *instruction {
+            Instruction::PushBytes(key) if key.len() == 33 => Some(key.as_bytes()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if count != keys.len() {
+        return None;
+    }
+    sorted_threshold(threshold, keys)
+}
+
+/// Parses a `sortedmulti_a` tapscript, `<key> OP_CHECKSIG <key> OP_CHECKSIGADD... <m> OP_NUMEQUAL`
+/// with keys in ascending order, or a single key `<key> OP_CHECKSIG` one, returning the threshold
+/// and the keys.
+fn sorted_multi_a(script: &Script) -> Option<(usize, Vec<&[u8]>)> {
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let (threshold, checks) = match instructions[..] {
+        [_, _] => (1, &instructions[..]),
+        [ref checks @ .., ref threshold, Instruction::Op(op)] if op == OP_NUMEQUAL =>
+            (pushnum(threshold)?, checks),
+        _ => return None,
+    };
+    if checks.len() % 2 != 0 {
+        return None;
+    }
+    let keys = checks
+        .chunks(2)
+        .enumerate()
+        .map(|(i, check)| {
+            let checksig = if i == 0 { OP_CHECKSIG } else { OP_CHECKSIGADD };
+            match *check {
+                [Instruction::PushBytes(key), Instruction::Op(op)]
+                    if key.len() == 32 && op == checksig =>
+                    Some(key.as_bytes()),
+                _ => None,
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    sorted_threshold(threshold, keys)
+}
+
+/// Returns the threshold and keys of a multisig if the threshold is valid and the keys sorted.
+fn sorted_threshold(threshold: usize, keys: Vec<&[u8]>) -> Option<(usize, Vec<&[u8]>)> {
+    if threshold == 0 || threshold > keys.len() || !keys.windows(2).all(|pair| pair[0] < pair[1]) {
+        return None;
+    }
+    Some((threshold, keys))
+}
+
+/// Returns the number pushed by a `OP_PUSHNUM` instruction.
+fn pushnum(instruction: &Instruction) -> Option<usize> {
+    match *instruction {
+        Instruction::Op(op) => op.decode_pushnum().map(usize::from),
+        _ => None,
+    }
+}
+
+/// Error verifying the change outputs of a PSBT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChangeError {
+    /// A key origin has the fingerprint of the wallet but is not derivable from its xpubs.
+    UnknownDerivation {
+        /// Index of the output.
+        index: usize,
+    },
+    /// A key derived from the wallet does not match the key claimed by the output.
+    KeyMismatch {
+        /// Index of the output.
+        index: usize,
+    },
+    /// The script pubkey of the output does not commit to the wallet keys it claims.
+    ScriptMismatch {
+        /// Index of the output.
+        index: usize,
+    },
+}
+
+internals::impl_from_infallible!(ChangeError);
+
+impl fmt::Display for ChangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ChangeError::*;
+
+        match *self {
+            UnknownDerivation { index } =>
+                write!(f, "key origin of output {} is not derivable from the wallet", index),
+            KeyMismatch { index } =>
+                write!(f, "key of output {} does not match its wallet derivation", index),
+            ScriptMismatch { index } =>
+                write!(f, "output {} does not pay to the wallet keys it claims", index),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ChangeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ChangeError::*;
+
+        match *self {
+            UnknownDerivation { .. } | KeyMismatch { .. } | ScriptMismatch { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bip32::{DerivationPath, Fingerprint, Xpriv};
+    use crate::blockdata::locktime::absolute;
+    use crate::blockdata::opcodes::all::OP_DROP;
+    use crate::blockdata::script::ScriptBuf;
+    use crate::blockdata::transaction::{self, Transaction, TxOut};
+    use crate::taproot::TaprootBuilder;
+    use crate::{Amount, NetworkKind};
+
+    struct Wallet {
+        master_fingerprint: Fingerprint,
+        account_path: DerivationPath,
+        account: Xpub,
+    }
+
+    impl Wallet {
+        fn new<C: secp256k1::Signing>(secp: &Secp256k1<C>, seed: u8) -> Self {
+            let master = Xpriv::new_master(NetworkKind::Test, &[seed; 32]).unwrap();
+            let account_path: DerivationPath = "84h/1h/0h".parse().unwrap();
+            let account = Xpub::from_priv(secp, &master.derive_priv(secp, &account_path).unwrap());
+            Wallet { master_fingerprint: master.fingerprint(secp), account_path, account }
+        }
+
+        fn xpubs(&self) -> BTreeMap<Xpub, KeySource> {
+            let mut xpubs = BTreeMap::new();
+            xpubs.insert(self.account, (self.master_fingerprint, self.account_path.clone()));
+            xpubs
+        }
+
+        /// Returns the key at `1/index` with its origin.
+        fn key<C: Verification>(
+            &self,
+            secp: &Secp256k1<C>,
+            index: u32,
+        ) -> (secp256k1::PublicKey, KeySource) {
+            let suffix: DerivationPath = format!("1/{}", index).parse().unwrap();
+            let key = self.account.derive_pub(secp, &suffix).unwrap().public_key;
+            let path = self.account_path.extend(&suffix);
+            (key, (self.master_fingerprint, path))
+        }
+    }
+
+    fn psbt_paying_to(script_pubkeys: Vec<ScriptBuf>) -> Psbt {
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: script_pubkeys
+                .into_iter()
+                .map(|script_pubkey| TxOut { value: Amount::from_sat(1_000), script_pubkey })
+                .collect(),
+        };
+        Psbt::from_unsigned_tx(tx).unwrap()
+    }
+
+    #[test]
+    fn verify_p2wpkh_change() {
+        let secp = Secp256k1::new();
+        let wallet = Wallet::new(&secp, 1);
+        let attacker = Wallet::new(&secp, 2);
+        let (key, origin) = wallet.key(&secp, 0);
+        let (attacker_key, _) = attacker.key(&secp, 0);
+
+        let change = ScriptBuf::new_p2wpkh(&CompressedPublicKey(key).wpubkey_hash());
+        let payment = ScriptBuf::new_p2wpkh(&CompressedPublicKey(attacker_key).wpubkey_hash());
+        let mut psbt = psbt_paying_to(vec![payment.clone(), change]);
+        psbt.outputs[1].bip32_derivation.insert(key, origin.clone());
+        assert_eq!(psbt.verify_outputs_against(&wallet.xpubs(), &secp), Ok(vec![1]));
+
+        // Claiming the wallet origin for an attacker script.
+        psbt.unsigned_tx.output[1].script_pubkey = payment;
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::ScriptMismatch { index: 1 })
+        );
+
+        // Claiming the wallet origin for an attacker key.
+        psbt.outputs[1].bip32_derivation.clear();
+        psbt.outputs[1].bip32_derivation.insert(attacker_key, origin);
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::KeyMismatch { index: 1 })
+        );
+
+        // Claiming a wallet origin outside of the account.
+        let (_, (fingerprint, _)) = wallet.key(&secp, 0);
+        psbt.outputs[1]
+            .bip32_derivation
+            .insert(attacker_key, (fingerprint, "0/1".parse().unwrap()));
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::UnknownDerivation { index: 1 })
+        );
+    }
+
+    fn multisig(threshold: i64, keys: &[secp256k1::PublicKey]) -> ScriptBuf {
+        keys.iter()
+            .fold(ScriptBuf::builder().push_int(threshold), |builder, key| {
+                builder.push_slice(key.serialize())
+            })
+            .push_int(keys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    #[test]
+    fn verify_script_change() {
+        let secp = Secp256k1::new();
+        let wallet = Wallet::new(&secp, 1);
+        let cosigner = Wallet::new(&secp, 2);
+        let (key, origin) = wallet.key(&secp, 3);
+        let (cosigner_key, cosigner_origin) = cosigner.key(&secp, 3);
+        let mut keys = [key, cosigner_key];
+        keys.sort_by_key(|key| key.serialize());
+
+        let psbt_paying_to_multisig = |witness_script: ScriptBuf| {
+            let redeem_script = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+            let mut psbt = psbt_paying_to(vec![ScriptBuf::new_p2sh(&redeem_script.script_hash())]);
+            psbt.outputs[0].bip32_derivation.insert(key, origin.clone());
+            psbt.outputs[0].bip32_derivation.insert(cosigner_key, cosigner_origin.clone());
+            psbt.outputs[0].redeem_script = Some(redeem_script);
+            psbt.outputs[0].witness_script = Some(witness_script);
+            psbt
+        };
+
+        let mut psbt = psbt_paying_to_multisig(multisig(2, &keys));
+        assert_eq!(psbt.verify_outputs_against(&wallet.xpubs(), &secp), Ok(vec![0]));
+        // The cosigner sees the same output as its change.
+        assert_eq!(psbt.verify_outputs_against(&cosigner.xpubs(), &secp), Ok(vec![0]));
+
+        psbt.outputs[0].witness_script = None;
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::ScriptMismatch { index: 0 })
+        );
+
+        // The cosigner alone can spend a 1-of-2.
+        let psbt = psbt_paying_to_multisig(multisig(1, &keys));
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::ScriptMismatch { index: 0 })
+        );
+
+        // Only sorted multisig is recognized.
+        let psbt = psbt_paying_to_multisig(multisig(2, &[keys[1], keys[0]]));
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::ScriptMismatch { index: 0 })
+        );
+
+        // Any other script is rejected, even if it contains the wallet key.
+        let witness_script = ScriptBuf::builder()
+            .push_slice(key.serialize())
+            .push_opcode(OP_DROP)
+            .push_slice(cosigner_key.serialize())
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let psbt = psbt_paying_to_multisig(witness_script);
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::ScriptMismatch { index: 0 })
+        );
+    }
+
+    #[test]
+    fn verify_taproot_change() {
+        let secp = Secp256k1::new();
+        let wallet = Wallet::new(&secp, 1);
+        let (internal_key, internal_origin) = wallet.key(&secp, 0);
+        let (leaf_key, leaf_origin) = wallet.key(&secp, 1);
+        let internal_key = internal_key.x_only_public_key().0;
+        let leaf_key = leaf_key.x_only_public_key().0;
+
+        let leaf =
+            ScriptBuf::builder().push_x_only_key(&leaf_key).push_opcode(OP_CHECKSIG).into_script();
+        let builder = TaprootBuilder::new().add_leaf(0, leaf.clone()).unwrap();
+        let tap_tree = TapTree::try_from(builder.clone()).unwrap();
+        let spend_info = builder.finalize(&secp, internal_key).unwrap();
+
+        let mut psbt = psbt_paying_to(vec![ScriptBuf::new_p2tr_tweaked(spend_info.output_key())]);
+        let output = &mut psbt.outputs[0];
+        output.tap_internal_key = Some(internal_key);
+        output.tap_key_origins.insert(internal_key, (vec![], internal_origin));
+        output.tap_key_origins.insert(leaf_key, (vec![], leaf_origin));
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::ScriptMismatch { index: 0 })
+        );
+
+        psbt.outputs[0].tap_tree = Some(tap_tree);
+        assert_eq!(psbt.verify_outputs_against(&wallet.xpubs(), &secp), Ok(vec![0]));
+
+        // A leaf spendable by a third party.
+        let attacker = Wallet::new(&secp, 2);
+        let attacker_key = attacker.key(&secp, 0).0.x_only_public_key().0;
+        let attacker_leaf = ScriptBuf::builder()
+            .push_x_only_key(&attacker_key)
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let builder = TaprootBuilder::new()
+            .add_leaf(1, leaf.clone())
+            .unwrap()
+            .add_leaf(1, attacker_leaf)
+            .unwrap();
+        let spend_info = builder.clone().finalize(&secp, internal_key).unwrap();
+        psbt.unsigned_tx.output[0].script_pubkey =
+            ScriptBuf::new_p2tr_tweaked(spend_info.output_key());
+        psbt.outputs[0].tap_tree = Some(TapTree::try_from(builder).unwrap());
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::ScriptMismatch { index: 0 })
+        );
+
+        // An internal key of a third party.
+        let builder = TaprootBuilder::new().add_leaf(0, leaf).unwrap();
+        let spend_info = builder.clone().finalize(&secp, attacker_key).unwrap();
+        psbt.unsigned_tx.output[0].script_pubkey =
+            ScriptBuf::new_p2tr_tweaked(spend_info.output_key());
+        psbt.outputs[0].tap_internal_key = Some(attacker_key);
+        psbt.outputs[0].tap_tree = Some(TapTree::try_from(builder).unwrap());
+        assert_eq!(
+            psbt.verify_outputs_against(&wallet.xpubs(), &secp),
+            Err(ChangeError::ScriptMismatch { index: 0 })
+        );
+    }
+}
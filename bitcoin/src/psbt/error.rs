@@ -4,8 +4,7 @@ use core::fmt;
 
 use internals::write_err;
 
-use crate::bip32::Xpub;
-use crate::blockdata::transaction::Transaction;
+use crate::blockdata::locktime::absolute;
 use crate::consensus::encode;
 use crate::prelude::*;
 use crate::psbt::raw;
@@ -45,14 +44,6 @@ pub enum Error {
     MustHaveUnsignedTx,
     /// Signals that there are no more key-value pairs in a key-value map.
     NoMorePairs,
-    /// Attempting to combine with a PSBT describing a different unsigned
-    /// transaction.
-    UnexpectedUnsignedTx {
-        /// Expected
-        expected: Box<Transaction>,
-        /// Actual
-        actual: Box<Transaction>,
-    },
     /// Unable to parse as a standard sighash type.
     NonStandardSighashType(u32),
     /// Invalid hash when parsing slice.
@@ -66,9 +57,6 @@ pub enum Error {
         /// Hash value
         hash: Box<[u8]>,
     },
-    /// Conflicting data during combine procedure:
-    /// global extended public key has inconsistent key sources
-    CombineInconsistentKeySources(Box<Xpub>),
     /// Serialization error in bitcoin consensus-encoded structures
     ConsensusEncoding(encode::Error),
     /// Negative fee
@@ -97,6 +85,23 @@ pub enum Error {
     XPubKey(&'static str),
     /// Error related to PSBT version
     Version(&'static str),
+    /// Invalid required lock time of a version 2 PSBT input.
+    InvalidLockTime(absolute::ConversionError),
+    /// The inputs of a version 2 PSBT require both height and time based lock times.
+    IncompatibleLockTimes,
+    /// Inputs were added to a version 2 PSBT whose inputs are not modifiable.
+    InputsNotModifiable,
+    /// Outputs were added to a version 2 PSBT whose outputs are not modifiable.
+    OutputsNotModifiable,
+    /// Inputs or outputs were inserted at an index past the end of the PSBT's inputs or outputs.
+    InsertionOutOfBounds {
+        /// Attempted insertion index.
+        index: usize,
+        /// Length of the PSBT inputs or outputs vector.
+        length: usize,
+    },
+    /// Inserting inputs or outputs would invalidate existing signatures.
+    SignaturesNotModifiable,
     /// PSBT data is not consumed entirely
     PartialDataConsumption,
     /// I/O error.
@@ -125,21 +130,12 @@ impl fmt::Display for Error {
             MustHaveUnsignedTx =>
                 f.write_str("partially signed transactions must have an unsigned transaction"),
             NoMorePairs => f.write_str("no more key-value pairs for this psbt map"),
-            UnexpectedUnsignedTx { expected: ref e, actual: ref a } => write!(
-                f,
-                "different unsigned transaction: expected {}, actual {}",
-                e.compute_txid(),
-                a.compute_txid()
-            ),
             NonStandardSighashType(ref sht) => write!(f, "non-standard sighash type: {}", sht),
             InvalidHash(ref e) => write_err!(f, "invalid hash when parsing slice"; e),
             InvalidPreimageHashPair { ref preimage, ref hash, ref hash_type } => {
                 // directly using debug forms of psbthash enums
                 write!(f, "Preimage {:?} does not match {:?} hash {:?}", preimage, hash_type, hash)
             }
-            CombineInconsistentKeySources(ref s) => {
-                write!(f, "combine conflict: {}", s)
-            }
             ConsensusEncoding(ref e) => write_err!(f, "bitcoin consensus encoding error"; e),
             NegativeFee => f.write_str("PSBT has a negative fee which is not allowed"),
             FeeOverflow => f.write_str("integer overflow in fee calculation"),
@@ -154,6 +150,15 @@ impl fmt::Display for Error {
             TapTree(ref e) => write_err!(f, "taproot tree error"; e),
             XPubKey(s) => write!(f, "xpub key error -  {}", s),
             Version(s) => write!(f, "version error {}", s),
+            InvalidLockTime(ref e) => write_err!(f, "invalid required lock time"; e),
+            IncompatibleLockTimes =>
+                f.write_str("inputs require both height and time based lock times"),
+            InputsNotModifiable => f.write_str("the PSBT inputs are not modifiable"),
+            OutputsNotModifiable => f.write_str("the PSBT outputs are not modifiable"),
+            InsertionOutOfBounds { index, length } =>
+                write!(f, "insertion index {} is out-of-bounds for length {}", index, length),
+            SignaturesNotModifiable =>
+                f.write_str("the insertion would invalidate existing PSBT signatures"),
             PartialDataConsumption =>
                 f.write_str("data not consumed entirely when explicitly deserializing"),
             Io(ref e) => write_err!(f, "I/O error"; e),
@@ -169,6 +174,7 @@ impl std::error::Error for Error {
         match *self {
             InvalidHash(ref e) => Some(e),
             ConsensusEncoding(ref e) => Some(e),
+            InvalidLockTime(ref e) => Some(e),
             Io(ref e) => Some(e),
             InvalidMagic
             | MissingUtxo
@@ -181,10 +187,8 @@ impl std::error::Error for Error {
             | UnsignedTxHasScriptWitnesses
             | MustHaveUnsignedTx
             | NoMorePairs
-            | UnexpectedUnsignedTx { .. }
             | NonStandardSighashType(_)
             | InvalidPreimageHashPair { .. }
-            | CombineInconsistentKeySources(_)
             | NegativeFee
             | FeeOverflow
             | InvalidPublicKey(_)
@@ -198,6 +202,11 @@ impl std::error::Error for Error {
             | TapTree(_)
             | XPubKey(_)
             | Version(_)
+            | IncompatibleLockTimes
+            | InputsNotModifiable
+            | OutputsNotModifiable
+            | InsertionOutOfBounds { .. }
+            | SignaturesNotModifiable
             | PartialDataConsumption => None,
         }
     }
@@ -0,0 +1,487 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! PSBT Input Finalizer role.
+//!
+//! Constructs the final scriptSig and witness of inputs spending standard output types from the
+//! signatures collected in the PSBT, as described by BIP 174.
+//!
+
+use core::{fmt, mem};
+
+use internals::write_err;
+use secp256k1::{Secp256k1, Verification, XOnlyPublicKey};
+
+use crate::blockdata::opcodes::all::{OP_CHECKSIG, OP_PUSHBYTES_32};
+use crate::blockdata::script::{self, PushBytes, Script, ScriptBuf, ScriptType};
+use crate::blockdata::witness::Witness;
+use crate::crypto::ecdsa;
+use crate::crypto::key::WPubkeyHash;
+use crate::prelude::*;
+use crate::psbt::{IndexOutOfBoundsError, Input, Psbt};
+use crate::taproot::{LeafVersion, TapLeafHash};
+
+impl Psbt {
+    /// Finalizes all inputs of this PSBT that are not finalized yet.
+    ///
+    /// Inputs spending P2PKH, P2WPKH, P2SH and P2WSH wrapped multisig, P2SH wrapped P2WPKH and
+    /// P2WSH as well as P2TR outputs, using the key path or a single key script path, are
+    /// supported. See [`Psbt::finalize_input`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns a map of input index to the error encountered for each input that could not be
+    /// finalized, all other inputs are finalized regardless.
+    pub fn finalize<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+    ) -> Result<(), BTreeMap<usize, FinalizeError>> {
+        let mut errors = BTreeMap::new();
+
+        for input_index in 0..self.inputs.len() {
+            if let Err(e) = self.finalize_input(input_index, secp) {
+                errors.insert(input_index, e);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Finalizes the input at `input_index`.
+    ///
+    /// Sets `final_script_sig` and `final_script_witness` and clears all other fields except
+    /// the UTXOs, the required lock times, proprietary and unknown key-value pairs. Inputs that
+    /// already have a final scriptSig or witness are left untouched.
+    ///
+    /// Multisig scripts are satisfied using the signatures of the first keys in script order,
+    /// taproot inputs with a key path signature are spent using the key path.
+    pub fn finalize_input<C: Verification>(
+        &mut self,
+        input_index: usize,
+        secp: &Secp256k1<C>,
+    ) -> Result<(), FinalizeError> {
+        let input = self.checked_input(input_index)?;
+        if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+            return Ok(());
+        }
+
+        let utxo = self.spend_utxo(input_index).map_err(|_| FinalizeError::MissingSpendUtxo)?;
+        let (script_sig, witness) = satisfy(input, &utxo.script_pubkey, secp)?;
+
+        let input = &mut self.inputs[input_index];
+        *input = Input {
+            non_witness_utxo: input.non_witness_utxo.take(),
+            witness_utxo: input.witness_utxo.take(),
+            final_script_sig: if script_sig.is_empty() { None } else { Some(script_sig) },
+            final_script_witness: if witness.is_empty() { None } else { Some(witness) },
+            required_time_lock_time: input.required_time_lock_time,
+            required_height_lock_time: input.required_height_lock_time,
+            proprietary: mem::take(&mut input.proprietary),
+            unknown: mem::take(&mut input.unknown),
+            ..Default::default()
+        };
+        Ok(())
+    }
+}
+
+/// Returns the final scriptSig and witness of `input` spending `script_pubkey`.
+fn satisfy<C: Verification>(
+    input: &Input,
+    script_pubkey: &Script,
+    secp: &Secp256k1<C>,
+) -> Result<(ScriptBuf, Witness), FinalizeError> {
+    match script_pubkey.classify() {
+        ScriptType::P2pkh(hash) => {
+            let (pk, sig) = input
+                .partial_sigs
+                .iter()
+                .find(|(pk, _)| pk.pubkey_hash() == hash)
+                .ok_or(FinalizeError::MissingSignature)?;
+            let script_sig =
+                script::Builder::new().push_slice(sig.serialize()).push_key(pk).into_script();
+            Ok((script_sig, Witness::new()))
+        }
+        ScriptType::P2wpkh(hash) => Ok((ScriptBuf::new(), p2wpkh_witness(input, hash)?)),
+        ScriptType::P2wsh(hash) => {
+            let witness_script =
+                input.witness_script.as_ref().ok_or(FinalizeError::MissingWitnessScript)?;
+            if witness_script.wscript_hash() != hash {
+                return Err(FinalizeError::ScriptMismatch);
+            }
+            Ok((ScriptBuf::new(), p2wsh_witness(input, witness_script)?))
+        }
+        ScriptType::P2sh(hash) => {
+            let redeem_script =
+                input.redeem_script.as_ref().ok_or(FinalizeError::MissingRedeemScript)?;
+            if redeem_script.script_hash() != hash {
+                return Err(FinalizeError::ScriptMismatch);
+            }
+            let redeem_push = <&PushBytes>::try_from(redeem_script.as_bytes())
+                .map_err(|_| FinalizeError::UnsupportedScript)?;
+
+            match redeem_script.classify() {
+                ScriptType::P2wpkh(hash) => {
+                    let script_sig = script::Builder::new().push_slice(redeem_push).into_script();
+                    Ok((script_sig, p2wpkh_witness(input, hash)?))
+                }
+                ScriptType::P2wsh(hash) => {
+                    let witness_script =
+                        input.witness_script.as_ref().ok_or(FinalizeError::MissingWitnessScript)?;
+                    if witness_script.wscript_hash() != hash {
+                        return Err(FinalizeError::ScriptMismatch);
+                    }
+                    let script_sig = script::Builder::new().push_slice(redeem_push).into_script();
+                    Ok((script_sig, p2wsh_witness(input, witness_script)?))
+                }
+                ScriptType::Multisig { .. } => {
+                    // The extra element consumed by the `OP_CHECKMULTISIG` off-by-one bug.
+                    let mut builder = script::Builder::new().push_int(0);
+                    for sig in multisig_signatures(input, redeem_script)? {
+                        builder = builder.push_slice(sig.serialize());
+                    }
+                    Ok((builder.push_slice(redeem_push).into_script(), Witness::new()))
+                }
+                _ => Err(FinalizeError::UnsupportedScript),
+            }
+        }
+        ScriptType::P2tr(output_key) =>
+            Ok((ScriptBuf::new(), p2tr_witness(input, output_key.to_inner(), secp)?)),
+        _ => Err(FinalizeError::UnsupportedScript),
+    }
+}
+
+/// Returns the witness spending a P2WPKH output paying to `hash`.
+fn p2wpkh_witness(input: &Input, hash: WPubkeyHash) -> Result<Witness, FinalizeError> {
+    let (pk, sig) = input
+        .partial_sigs
+        .iter()
+        .find(|(pk, _)| pk.wpubkey_hash().ok() == Some(hash))
+        .ok_or(FinalizeError::MissingSignature)?;
+    Ok(Witness::p2wpkh(sig, &pk.inner))
+}
+
+/// Returns the witness spending a P2WSH output committing to the multisig `witness_script`.
+fn p2wsh_witness(input: &Input, witness_script: &Script) -> Result<Witness, FinalizeError> {
+    let mut witness = Witness::new();
+    // The extra element consumed by the `OP_CHECKMULTISIG` off-by-one bug.
+    witness.push([]);
+    for sig in multisig_signatures(input, witness_script)? {
+        witness.push(sig.to_vec());
+    }
+    witness.push(witness_script.as_bytes());
+    Ok(witness)
+}
+
+/// Returns the signatures satisfying the bare multisig `script`, in script order.
+fn multisig_signatures<'a>(
+    input: &'a Input,
+    script: &Script,
+) -> Result<Vec<&'a ecdsa::Signature>, FinalizeError> {
+    let (required, pubkeys) = match script.classify() {
+        ScriptType::Multisig { required, pubkeys } => (usize::from(required), pubkeys),
+        _ => return Err(FinalizeError::UnsupportedScript),
+    };
+
+    let sigs: Vec<_> =
+        pubkeys.iter().filter_map(|pk| input.partial_sigs.get(pk)).take(required).collect();
+    if sigs.len() < required {
+        return Err(FinalizeError::MissingSignature);
+    }
+    Ok(sigs)
+}
+
+/// Returns the witness spending a P2TR output with `output_key`.
+fn p2tr_witness<C: Verification>(
+    input: &Input,
+    output_key: XOnlyPublicKey,
+    secp: &Secp256k1<C>,
+) -> Result<Witness, FinalizeError> {
+    if let Some(sig) = input.tap_key_sig {
+        return Ok(Witness::p2tr_key_spend(&sig));
+    }
+
+    for (control_block, (script, leaf_version)) in &input.tap_scripts {
+        let key = match single_key_leaf(script, *leaf_version) {
+            Some(key) => key,
+            None => continue,
+        };
+        let leaf_hash = TapLeafHash::from_script(script, *leaf_version);
+        let sig = match input.tap_script_sigs.get(&(key, leaf_hash)) {
+            Some(sig) => sig,
+            None => continue,
+        };
+        if !control_block.verify_taproot_commitment(secp, output_key, script) {
+            continue;
+        }
+
+        let mut witness = Witness::new();
+        witness.push(sig.to_vec());
+        witness.push(script.as_bytes());
+        witness.push(control_block.serialize());
+        return Ok(witness);
+    }
+
+    Err(FinalizeError::MissingSignature)
+}
+
+/// Returns the key of a `<key> OP_CHECKSIG` tapscript leaf.
+fn single_key_leaf(script: &Script, leaf_version: LeafVersion) -> Option<XOnlyPublicKey> {
+    let bytes = script.as_bytes();
+    if leaf_version != LeafVersion::TapScript
+        || bytes.len() != 34
+        || bytes[0] != OP_PUSHBYTES_32.to_u8()
+        || bytes[33] != OP_CHECKSIG.to_u8()
+    {
+        return None;
+    }
+    XOnlyPublicKey::from_slice(&bytes[1..33]).ok()
+}
+
+/// Errors encountered while finalizing a PSBT input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FinalizeError {
+    /// Input index out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// Missing spending utxo.
+    MissingSpendUtxo,
+    /// Missing redeem script.
+    MissingRedeemScript,
+    /// Missing witness script.
+    MissingWitnessScript,
+    /// The redeem or witness script does not match the script it is committed to.
+    ScriptMismatch,
+    /// Not enough signatures to satisfy the spent script.
+    MissingSignature,
+    /// The spent script is not of a supported type.
+    UnsupportedScript,
+}
+
+internals::impl_from_infallible!(FinalizeError);
+
+impl fmt::Display for FinalizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use FinalizeError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            MissingSpendUtxo => write!(f, "missing spend utxo in PSBT"),
+            MissingRedeemScript => write!(f, "missing redeem script"),
+            MissingWitnessScript => write!(f, "missing witness script"),
+            ScriptMismatch => write!(f, "redeem or witness script does not match its commitment"),
+            MissingSignature => write!(f, "not enough signatures to satisfy the spent script"),
+            UnsupportedScript => write!(f, "the spent script is not of a supported type"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for FinalizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use FinalizeError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            MissingSpendUtxo | MissingRedeemScript | MissingWitnessScript | ScriptMismatch
+            | MissingSignature | UnsupportedScript => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for FinalizeError {
+    fn from(e: IndexOutOfBoundsError) -> Self { FinalizeError::IndexOutOfBounds(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use secp256k1::{All, Keypair, Message, SecretKey};
+
+    use super::*;
+    use crate::crypto::taproot;
+    use crate::psbt::test_utils::{keys, multisig, psbt_with_witness_utxo};
+    use crate::sighash::{EcdsaSighashType, TapSighashType};
+    use crate::taproot::TaprootBuilder;
+
+    // The finalizer does not verify signatures, any message will do.
+    fn ecdsa_sig(secp: &Secp256k1<All>, sk: &SecretKey) -> ecdsa::Signature {
+        let msg = Message::from_digest([0x42; 32]);
+        ecdsa::Signature {
+            signature: secp.sign_ecdsa(&msg, sk),
+            sighash_type: EcdsaSighashType::All,
+        }
+    }
+
+    fn schnorr_sig(secp: &Secp256k1<All>, sk: &SecretKey) -> taproot::Signature {
+        let msg = Message::from_digest([0x42; 32]);
+        let keypair = Keypair::from_secret_key(secp, sk);
+        taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(&msg, &keypair),
+            sighash_type: TapSighashType::Default,
+        }
+    }
+
+    #[test]
+    fn finalize_p2pkh() {
+        let secp = Secp256k1::new();
+        let (sk, pk) = keys(&secp, 1)[0];
+        let mut psbt = psbt_with_witness_utxo(ScriptBuf::new_p2pkh(&pk.pubkey_hash()));
+        assert_eq!(psbt.finalize_input(0, &secp), Err(FinalizeError::MissingSignature));
+
+        let sig = ecdsa_sig(&secp, &sk);
+        psbt.inputs[0].partial_sigs.insert(pk, sig);
+        psbt.finalize(&secp).unwrap();
+
+        let want = script::Builder::new().push_slice(sig.serialize()).push_key(&pk).into_script();
+        assert_eq!(psbt.inputs[0].final_script_sig, Some(want));
+        assert_eq!(psbt.inputs[0].final_script_witness, None);
+        assert!(psbt.inputs[0].partial_sigs.is_empty());
+    }
+
+    #[test]
+    fn finalize_p2wpkh() {
+        let secp = Secp256k1::new();
+        let (sk, pk) = keys(&secp, 1)[0];
+        let spk = ScriptBuf::new_p2wpkh(&pk.wpubkey_hash().unwrap());
+        let mut psbt = psbt_with_witness_utxo(spk.clone());
+
+        let sig = ecdsa_sig(&secp, &sk);
+        let input = &mut psbt.inputs[0];
+        input.partial_sigs.insert(pk, sig);
+        input.sighash_type = Some(EcdsaSighashType::All.into());
+        input.bip32_derivation.insert(pk.inner, Default::default());
+        psbt.finalize(&secp).unwrap();
+
+        let input = &psbt.inputs[0];
+        assert_eq!(input.final_script_witness, Some(Witness::p2wpkh(&sig, &pk.inner)));
+        assert_eq!(input.final_script_sig, None);
+        assert_eq!(input.sighash_type, None);
+        assert!(input.bip32_derivation.is_empty());
+        assert_eq!(input.witness_utxo.as_ref().unwrap().script_pubkey, spk);
+
+        // Finalized inputs are left untouched.
+        let finalized = psbt.clone();
+        psbt.finalize(&secp).unwrap();
+        assert_eq!(psbt, finalized);
+    }
+
+    #[test]
+    fn finalize_p2sh_multisig() {
+        let secp = Secp256k1::new();
+        let keys = keys(&secp, 3);
+        let pks: Vec<_> = keys.iter().map(|(_, pk)| *pk).collect();
+        let redeem_script = multisig(&pks);
+        let mut psbt = psbt_with_witness_utxo(redeem_script.to_p2sh());
+
+        psbt.inputs[0].redeem_script = Some(multisig(&pks[..2]));
+        assert_eq!(psbt.finalize_input(0, &secp), Err(FinalizeError::ScriptMismatch));
+
+        psbt.inputs[0].redeem_script = Some(redeem_script.clone());
+        let sig_2 = ecdsa_sig(&secp, &keys[2].0);
+        psbt.inputs[0].partial_sigs.insert(pks[2], sig_2);
+        assert_eq!(psbt.finalize_input(0, &secp), Err(FinalizeError::MissingSignature));
+
+        let sig_0 = ecdsa_sig(&secp, &keys[0].0);
+        psbt.inputs[0].partial_sigs.insert(pks[0], sig_0);
+        psbt.finalize(&secp).unwrap();
+
+        let want = script::Builder::new()
+            .push_int(0)
+            .push_slice(sig_0.serialize())
+            .push_slice(sig_2.serialize())
+            .push_slice(<&PushBytes>::try_from(redeem_script.as_bytes()).unwrap())
+            .into_script();
+        assert_eq!(psbt.inputs[0].final_script_sig, Some(want));
+        assert_eq!(psbt.inputs[0].redeem_script, None);
+    }
+
+    #[test]
+    fn finalize_p2wsh_multisig() {
+        let secp = Secp256k1::new();
+        let keys = keys(&secp, 3);
+        let pks: Vec<_> = keys.iter().map(|(_, pk)| *pk).collect();
+        let witness_script = multisig(&pks);
+        let mut psbt = psbt_with_witness_utxo(witness_script.to_p2wsh());
+        assert_eq!(psbt.finalize_input(0, &secp), Err(FinalizeError::MissingWitnessScript));
+
+        psbt.inputs[0].witness_script = Some(witness_script.clone());
+        let sigs: Vec<_> = keys.iter().map(|(sk, _)| ecdsa_sig(&secp, sk)).collect();
+        for (pk, sig) in pks.iter().zip(&sigs) {
+            psbt.inputs[0].partial_sigs.insert(*pk, *sig);
+        }
+        psbt.finalize(&secp).unwrap();
+
+        let mut want = Witness::new();
+        want.push([]);
+        want.push(sigs[0].to_vec());
+        want.push(sigs[1].to_vec());
+        want.push(witness_script.as_bytes());
+        assert_eq!(psbt.inputs[0].final_script_witness, Some(want));
+        assert_eq!(psbt.inputs[0].final_script_sig, None);
+    }
+
+    #[test]
+    fn finalize_p2tr() {
+        let secp = Secp256k1::new();
+        let keys = keys(&secp, 2);
+        let (internal_key, _) = keys[0].1.inner.x_only_public_key();
+        let (leaf_key, _) = keys[1].1.inner.x_only_public_key();
+
+        let leaf = script::Builder::new()
+            .push_x_only_key(&leaf_key)
+            .push_opcode(OP_CHECKSIG)
+            .into_script();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, leaf.clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+        let control_block =
+            spend_info.control_block(&(leaf.clone(), LeafVersion::TapScript)).unwrap();
+        let mut psbt = psbt_with_witness_utxo(ScriptBuf::new_p2tr_tweaked(spend_info.output_key()));
+
+        // Script path.
+        let leaf_sig = schnorr_sig(&secp, &keys[1].0);
+        let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::TapScript);
+        let input = &mut psbt.inputs[0];
+        input.tap_scripts.insert(control_block.clone(), (leaf.clone(), LeafVersion::TapScript));
+        assert_eq!(
+            psbt.finalize(&secp),
+            Err(vec![(0, FinalizeError::MissingSignature)].into_iter().collect())
+        );
+
+        let mut key_path = psbt.clone();
+        psbt.inputs[0].tap_script_sigs.insert((leaf_key, leaf_hash), leaf_sig);
+        psbt.finalize(&secp).unwrap();
+
+        let mut want = Witness::new();
+        want.push(leaf_sig.to_vec());
+        want.push(leaf.as_bytes());
+        want.push(control_block.serialize());
+        assert_eq!(psbt.inputs[0].final_script_witness, Some(want));
+        assert!(psbt.inputs[0].tap_scripts.is_empty());
+
+        // Key path.
+        let key_sig = schnorr_sig(&secp, &keys[0].0);
+        key_path.inputs[0].tap_key_sig = Some(key_sig);
+        key_path.finalize(&secp).unwrap();
+        assert_eq!(
+            key_path.inputs[0].final_script_witness,
+            Some(Witness::p2tr_key_spend(&key_sig))
+        );
+    }
+
+    #[test]
+    fn finalize_unsupported() {
+        let secp = Secp256k1::new();
+        let mut psbt = psbt_with_witness_utxo(ScriptBuf::new_op_return([0x01]));
+        let errors = psbt.finalize(&secp).unwrap_err();
+        assert_eq!(errors.get(&0), Some(&FinalizeError::UnsupportedScript));
+
+        psbt.inputs[0].witness_utxo = None;
+        assert_eq!(psbt.finalize_input(0, &secp), Err(FinalizeError::MissingSpendUtxo));
+        assert!(matches!(psbt.finalize_input(1, &secp), Err(FinalizeError::IndexOutOfBounds(_))));
+    }
+}
@@ -9,6 +9,18 @@ macro_rules! combine {
     };
 }
 
+/// Returns the name of the optional field `$thing` from the enclosing function if it is set to
+/// different values in `$slf` and `$other`.
+macro_rules! return_conflict {
+    ($thing:ident, $slf:ident, $other:ident) => {
+        if let (Some(a), Some(b)) = (&$slf.$thing, &$other.$thing) {
+            if a != b {
+                return Some(stringify!($thing));
+            }
+        }
+    };
+}
+
 macro_rules! impl_psbt_de_serialize {
     ($thing:ty) => {
         impl_psbt_serialize!($thing);
@@ -2,18 +2,33 @@
 
 use io::{BufRead, Cursor, Read};
 
+#[cfg(feature = "serde")]
+use crate::bip32::KeySource;
 use crate::bip32::{ChildNumber, DerivationPath, Fingerprint, Xpub};
-use crate::blockdata::transaction::Transaction;
-use crate::consensus::encode::MAX_VEC_SIZE;
+use crate::blockdata::locktime::absolute;
+use crate::blockdata::transaction::{self, Transaction};
+use crate::consensus::encode::{VarInt, MAX_VEC_SIZE};
 use crate::consensus::{encode, Decodable};
 use crate::prelude::*;
 use crate::psbt::map::Map;
-use crate::psbt::{raw, Error, Psbt};
+#[cfg(feature = "serde")]
+use crate::psbt::map::{take_serde_field, Input, Output};
+use crate::psbt::{raw, Error, Psbt, TxModifiable};
 
 /// Type: Unsigned Transaction PSBT_GLOBAL_UNSIGNED_TX = 0x00
 const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
 /// Type: Extended Public Key PSBT_GLOBAL_XPUB = 0x01
 const PSBT_GLOBAL_XPUB: u8 = 0x01;
+/// Type: Transaction Version PSBT_GLOBAL_TX_VERSION = 0x02
+const PSBT_GLOBAL_TX_VERSION: u8 = 0x02;
+/// Type: Fallback Locktime PSBT_GLOBAL_FALLBACK_LOCKTIME = 0x03
+const PSBT_GLOBAL_FALLBACK_LOCKTIME: u8 = 0x03;
+/// Type: Input Count PSBT_GLOBAL_INPUT_COUNT = 0x04
+const PSBT_GLOBAL_INPUT_COUNT: u8 = 0x04;
+/// Type: Output Count PSBT_GLOBAL_OUTPUT_COUNT = 0x05
+const PSBT_GLOBAL_OUTPUT_COUNT: u8 = 0x05;
+/// Type: Transaction Modifiable Flags PSBT_GLOBAL_TX_MODIFIABLE = 0x06
+const PSBT_GLOBAL_TX_MODIFIABLE: u8 = 0x06;
 /// Type: Version Number PSBT_GLOBAL_VERSION = 0xFB
 const PSBT_GLOBAL_VERSION: u8 = 0xFB;
 /// Type: Proprietary Use Type PSBT_GLOBAL_PROPRIETARY = 0xFC
@@ -23,19 +38,22 @@ impl Map for Psbt {
     fn get_pairs(&self) -> Vec<raw::Pair> {
         let mut rv: Vec<raw::Pair> = Default::default();
 
-        rv.push(raw::Pair {
-            key: raw::Key { type_value: PSBT_GLOBAL_UNSIGNED_TX, key: vec![] },
-            value: {
-                // Manually serialized to ensure 0-input txs are serialized
-                // without witnesses.
-                let mut ret = Vec::new();
-                ret.extend(encode::serialize(&self.unsigned_tx.version));
-                ret.extend(encode::serialize(&self.unsigned_tx.input));
-                ret.extend(encode::serialize(&self.unsigned_tx.output));
-                ret.extend(encode::serialize(&self.unsigned_tx.lock_time));
-                ret
-            },
-        });
+        // Version 2 PSBTs describe the unsigned transaction using separate fields.
+        if self.version < 2 {
+            rv.push(raw::Pair {
+                key: raw::Key { type_value: PSBT_GLOBAL_UNSIGNED_TX, key: vec![] },
+                value: {
+                    // Manually serialized to ensure 0-input txs are serialized
+                    // without witnesses.
+                    let mut ret = Vec::new();
+                    ret.extend(encode::serialize(&self.unsigned_tx.version));
+                    ret.extend(encode::serialize(&self.unsigned_tx.input));
+                    ret.extend(encode::serialize(&self.unsigned_tx.output));
+                    ret.extend(encode::serialize(&self.unsigned_tx.lock_time));
+                    ret
+                },
+            });
+        }
 
         for (xpub, (fingerprint, derivation)) in &self.xpub {
             rv.push(raw::Pair {
@@ -49,6 +67,33 @@ impl Map for Psbt {
             });
         }
 
+        if self.version >= 2 {
+            rv.push(raw::Pair {
+                key: raw::Key { type_value: PSBT_GLOBAL_TX_VERSION, key: vec![] },
+                value: encode::serialize(&self.unsigned_tx.version),
+            });
+            if let Some(fallback_lock_time) = self.fallback_lock_time {
+                rv.push(raw::Pair {
+                    key: raw::Key { type_value: PSBT_GLOBAL_FALLBACK_LOCKTIME, key: vec![] },
+                    value: encode::serialize(&fallback_lock_time),
+                });
+            }
+            rv.push(raw::Pair {
+                key: raw::Key { type_value: PSBT_GLOBAL_INPUT_COUNT, key: vec![] },
+                value: encode::serialize(&VarInt::from(self.unsigned_tx.input.len())),
+            });
+            rv.push(raw::Pair {
+                key: raw::Key { type_value: PSBT_GLOBAL_OUTPUT_COUNT, key: vec![] },
+                value: encode::serialize(&VarInt::from(self.unsigned_tx.output.len())),
+            });
+            if let Some(tx_modifiable) = self.tx_modifiable {
+                rv.push(raw::Pair {
+                    key: raw::Key { type_value: PSBT_GLOBAL_TX_MODIFIABLE, key: vec![] },
+                    value: vec![tx_modifiable.to_u8()],
+                });
+            }
+        }
+
         // Serializing version only for non-default value; otherwise test vectors fail
         if self.version > 0 {
             rv.push(raw::Pair {
@@ -70,10 +115,21 @@ impl Map for Psbt {
 }
 
 impl Psbt {
-    pub(crate) fn decode_global<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, Error> {
+    /// Decodes the global map, returning the PSBT along with the number of inputs and outputs.
+    ///
+    /// For version 2 PSBTs the inputs and outputs of the unsigned transaction are left empty,
+    /// they are filled in from the input and output maps.
+    pub(crate) fn decode_global<R: BufRead + ?Sized>(
+        r: &mut R,
+    ) -> Result<(Self, usize, usize), Error> {
         let mut r = r.take(MAX_VEC_SIZE as u64);
         let mut tx: Option<Transaction> = None;
         let mut version: Option<u32> = None;
+        let mut tx_version: Option<transaction::Version> = None;
+        let mut fallback_lock_time: Option<absolute::LockTime> = None;
+        let mut input_count: Option<VarInt> = None;
+        let mut output_count: Option<VarInt> = None;
+        let mut tx_modifiable: Option<u8> = None;
         let mut unknowns: BTreeMap<raw::Key, Vec<u8>> = Default::default();
         let mut xpub_map: BTreeMap<Xpub, (Fingerprint, DerivationPath)> = Default::default();
         let mut proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>> = Default::default();
@@ -160,11 +216,11 @@ impl Psbt {
                                         ));
                                     }
                                     version = Some(Decodable::consensus_decode(&mut decoder)?);
-                                    // We only understand version 0 PSBTs. According to BIP-174 we
-                                    // should throw an error if we see anything other than version 0.
-                                    if version != Some(0) {
+                                    // We only understand version 0 (BIP-174) and version 2
+                                    // (BIP-370) PSBTs.
+                                    if version != Some(0) && version != Some(2) {
                                         return Err(Error::Version(
-                                            "PSBT versions greater than 0 are not supported",
+                                            "only PSBT versions 0 and 2 are supported",
                                         ));
                                     }
                                 } else {
@@ -174,6 +230,12 @@ impl Psbt {
                                 return Err(Error::InvalidKey(pair.key));
                             }
                         }
+                        PSBT_GLOBAL_TX_VERSION => decode_keyless(&mut tx_version, pair)?,
+                        PSBT_GLOBAL_FALLBACK_LOCKTIME =>
+                            decode_keyless(&mut fallback_lock_time, pair)?,
+                        PSBT_GLOBAL_INPUT_COUNT => decode_keyless(&mut input_count, pair)?,
+                        PSBT_GLOBAL_OUTPUT_COUNT => decode_keyless(&mut output_count, pair)?,
+                        PSBT_GLOBAL_TX_MODIFIABLE => decode_keyless(&mut tx_modifiable, pair)?,
                         PSBT_GLOBAL_PROPRIETARY => match proprietary
                             .entry(raw::ProprietaryKey::try_from(pair.key.clone())?)
                         {
@@ -197,18 +259,140 @@ impl Psbt {
             }
         }
 
-        if let Some(tx) = tx {
-            Ok(Psbt {
-                unsigned_tx: tx,
-                version: version.unwrap_or(0),
-                xpub: xpub_map,
-                proprietary,
-                unknown: unknowns,
-                inputs: vec![],
-                outputs: vec![],
-            })
+        let version = version.unwrap_or(0);
+        let (unsigned_tx, input_count, output_count) = if version == 0 {
+            if tx_version.is_some()
+                || fallback_lock_time.is_some()
+                || input_count.is_some()
+                || output_count.is_some()
+                || tx_modifiable.is_some()
+            {
+                return Err(Error::Version("PSBT v0 must not contain PSBT v2 fields"));
+            }
+            let tx = tx.ok_or(Error::MustHaveUnsignedTx)?;
+            let (input_count, output_count) = (tx.input.len(), tx.output.len());
+            (tx, input_count, output_count)
         } else {
-            Err(Error::MustHaveUnsignedTx)
+            if tx.is_some() {
+                return Err(Error::Version("PSBT v2 must not contain an unsigned transaction"));
+            }
+            let tx_version =
+                tx_version.ok_or(Error::Version("PSBT v2 must have a transaction version"))?;
+            if tx_version < transaction::Version::TWO {
+                return Err(Error::Version("PSBT v2 transaction version must be at least 2"));
+            }
+            let input_count =
+                input_count.ok_or(Error::Version("PSBT v2 must have an input count"))?;
+            let output_count =
+                output_count.ok_or(Error::Version("PSBT v2 must have an output count"))?;
+            let tx = Transaction {
+                version: tx_version,
+                // Determined from the inputs once they are decoded.
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![],
+                output: vec![],
+            };
+            (tx, count_to_usize(input_count)?, count_to_usize(output_count)?)
+        };
+
+        let psbt = Psbt {
+            unsigned_tx,
+            version,
+            fallback_lock_time,
+            tx_modifiable: tx_modifiable.map(TxModifiable::from_u8),
+            xpub: xpub_map,
+            proprietary,
+            unknown: unknowns,
+            inputs: vec![],
+            outputs: vec![],
+        };
+        Ok((psbt, input_count, output_count))
+    }
+}
+
+/// The serde representation of [`Psbt`], with the layout it had before PSBT version 2 support.
+///
+/// The version 2 only fields are carried as their raw pairs in `unknown`, so data serialized by
+/// earlier versions of this crate still deserializes, also in formats that are not self-describing.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "actual_serde")]
+pub(crate) struct SerdePsbt {
+    unsigned_tx: Transaction,
+    version: u32,
+    xpub: BTreeMap<Xpub, KeySource>,
+    #[serde(with = "crate::serde_utils::btreemap_as_seq_byte_values")]
+    proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
+    #[serde(with = "crate::serde_utils::btreemap_as_seq_byte_values")]
+    unknown: BTreeMap<raw::Key, Vec<u8>>,
+    inputs: Vec<Input>,
+    outputs: Vec<Output>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Psbt> for SerdePsbt {
+    fn from(psbt: Psbt) -> Self {
+        let mut unknown = psbt.unknown;
+        if let Some(lock_time) = psbt.fallback_lock_time {
+            let key = raw::Key { type_value: PSBT_GLOBAL_FALLBACK_LOCKTIME, key: vec![] };
+            unknown.insert(key, encode::serialize(&lock_time));
+        }
+        if let Some(tx_modifiable) = psbt.tx_modifiable {
+            let key = raw::Key { type_value: PSBT_GLOBAL_TX_MODIFIABLE, key: vec![] };
+            unknown.insert(key, vec![tx_modifiable.to_u8()]);
+        }
+        SerdePsbt {
+            unsigned_tx: psbt.unsigned_tx,
+            version: psbt.version,
+            xpub: psbt.xpub,
+            proprietary: psbt.proprietary,
+            unknown,
+            inputs: psbt.inputs,
+            outputs: psbt.outputs,
         }
     }
 }
+
+#[cfg(feature = "serde")]
+impl From<SerdePsbt> for Psbt {
+    fn from(psbt: SerdePsbt) -> Self {
+        let mut unknown = psbt.unknown;
+        let fallback_lock_time =
+            take_serde_field(&mut unknown, PSBT_GLOBAL_FALLBACK_LOCKTIME, |value| {
+                encode::deserialize(value).ok()
+            });
+        let tx_modifiable =
+            take_serde_field(&mut unknown, PSBT_GLOBAL_TX_MODIFIABLE, |value| match *value {
+                [flags] => Some(TxModifiable::from_u8(flags)),
+                _ => None,
+            });
+        Psbt {
+            unsigned_tx: psbt.unsigned_tx,
+            version: psbt.version,
+            fallback_lock_time,
+            tx_modifiable,
+            xpub: psbt.xpub,
+            proprietary: psbt.proprietary,
+            unknown,
+            inputs: psbt.inputs,
+            outputs: psbt.outputs,
+        }
+    }
+}
+
+/// Decodes the value of a global pair with an empty key into `slot`.
+fn decode_keyless<T: Decodable>(slot: &mut Option<T>, pair: raw::Pair) -> Result<(), Error> {
+    if !pair.key.key.is_empty() {
+        return Err(Error::InvalidKey(pair.key));
+    }
+    if slot.is_some() {
+        return Err(Error::DuplicateKey(pair.key));
+    }
+    *slot = Some(encode::deserialize(&pair.value)?);
+    Ok(())
+}
+
+/// Converts a PSBT v2 input or output count to a `usize`.
+fn count_to_usize(count: VarInt) -> Result<usize, Error> {
+    usize::try_from(count.0).map_err(|_| Error::Version("PSBT v2 input or output count too large"))
+}
@@ -7,20 +7,24 @@ use hashes::{hash160, ripemd160, sha256, sha256d};
 use secp256k1::XOnlyPublicKey;
 
 use crate::bip32::KeySource;
+use crate::blockdata::locktime::absolute;
 use crate::blockdata::script::ScriptBuf;
-use crate::blockdata::transaction::{Transaction, TxOut};
+use crate::blockdata::transaction::{OutPoint, Sequence, Transaction, TxIn, TxOut};
 use crate::blockdata::witness::Witness;
+use crate::consensus::encode;
 use crate::crypto::key::PublicKey;
 use crate::crypto::{ecdsa, taproot};
 use crate::prelude::*;
-use crate::psbt::map::Map;
-use crate::psbt::serialize::Deserialize;
+#[cfg(feature = "serde")]
+use crate::psbt::map::take_serde_field;
+use crate::psbt::map::{maps_conflict, take_keyless_unknown, Map};
+use crate::psbt::serialize::{Deserialize, Serialize};
 use crate::psbt::{self, error, raw, Error};
 use crate::sighash::{
     EcdsaSighashType, InvalidSighashTypeError, NonStandardSighashTypeError, SighashTypeParseError,
     TapSighashType,
 };
-use crate::taproot::{ControlBlock, LeafVersion, TapLeafHash, TapNodeHash};
+use crate::taproot::{ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TaprootSpendInfo};
 
 /// Type: Non-Witness UTXO PSBT_IN_NON_WITNESS_UTXO = 0x00
 const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
@@ -48,6 +52,16 @@ const PSBT_IN_SHA256: u8 = 0x0b;
 const PSBT_IN_HASH160: u8 = 0x0c;
 /// Type: HASH256 preimage PSBT_IN_HASH256 = 0x0d
 const PSBT_IN_HASH256: u8 = 0x0d;
+/// Type: Previous TXID PSBT_IN_PREVIOUS_TXID = 0x0e
+const PSBT_IN_PREVIOUS_TXID: u8 = 0x0e;
+/// Type: Spent Output Index PSBT_IN_OUTPUT_INDEX = 0x0f
+const PSBT_IN_OUTPUT_INDEX: u8 = 0x0f;
+/// Type: Sequence Number PSBT_IN_SEQUENCE = 0x10
+const PSBT_IN_SEQUENCE: u8 = 0x10;
+/// Type: Required Time-based Locktime PSBT_IN_REQUIRED_TIME_LOCKTIME = 0x11
+const PSBT_IN_REQUIRED_TIME_LOCKTIME: u8 = 0x11;
+/// Type: Required Height-based Locktime PSBT_IN_REQUIRED_HEIGHT_LOCKTIME = 0x12
+const PSBT_IN_REQUIRED_HEIGHT_LOCKTIME: u8 = 0x12;
 /// Type: Taproot Signature in Key Spend PSBT_IN_TAP_KEY_SIG = 0x13
 const PSBT_IN_TAP_KEY_SIG: u8 = 0x13;
 /// Type: Taproot Signature in Script Spend PSBT_IN_TAP_SCRIPT_SIG = 0x14
@@ -68,6 +82,7 @@ const PSBT_IN_PROPRIETARY: u8 = 0xFC;
 #[derive(Clone, Default, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
+#[cfg_attr(feature = "serde", serde(into = "SerdeInput", from = "SerdeInput"))]
 pub struct Input {
     /// The non-witness transaction this input spends from. Should only be
     /// `Option::Some` for inputs which spend non-segwit outputs or
@@ -124,6 +139,10 @@ pub struct Input {
     pub tap_internal_key: Option<XOnlyPublicKey>,
     /// Taproot Merkle root.
     pub tap_merkle_root: Option<TapNodeHash>,
+    /// The minimum time based lock time this input requires (PSBT version 2 only).
+    pub required_time_lock_time: Option<absolute::Time>,
+    /// The minimum height based lock time this input requires (PSBT version 2 only).
+    pub required_height_lock_time: Option<absolute::Height>,
     /// Proprietary key-value pairs for this input.
     #[cfg_attr(feature = "serde", serde(with = "crate::serde_utils::btreemap_as_seq_byte_values"))]
     pub proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
@@ -240,6 +259,111 @@ impl Input {
             .unwrap_or(Ok(TapSighashType::Default))
     }
 
+    /// Returns the proprietary key-value pairs of this input with the identifier `prefix`.
+    pub fn proprietary_with_prefix<'a>(
+        &'a self,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (&'a raw::ProprietaryKey, &'a Vec<u8>)> {
+        raw::proprietary_with_prefix(&self.proprietary, prefix)
+    }
+
+    /// Sets the taproot internal key and merkle root of `spend_info` and replaces the leaf
+    /// scripts with those of `spend_info`, with their control blocks.
+    pub fn set_taproot_spend_info(&mut self, spend_info: &TaprootSpendInfo) {
+        self.tap_internal_key = Some(spend_info.internal_key());
+        self.tap_merkle_root = spend_info.merkle_root();
+        self.tap_scripts.clear();
+        for (script, leaf_version) in spend_info.script_map().keys() {
+            let script_ver = (script.clone(), *leaf_version);
+            if let Some(control_block) = spend_info.control_block(&script_ver) {
+                self.tap_scripts.insert(control_block, script_ver);
+            }
+        }
+    }
+
+    /// Adds a taproot leaf script together with the control block proving its inclusion.
+    ///
+    /// Sets the internal key and merkle root if they are not yet known.
+    ///
+    /// # Errors
+    ///
+    /// If the control block does not commit to the internal key or `script` is not in the tree
+    /// with the merkle root of this input.
+    pub fn add_tap_script(
+        &mut self,
+        control_block: ControlBlock,
+        script: ScriptBuf,
+    ) -> Result<(), TaprootFieldError> {
+        if self.tap_internal_key.map_or(false, |key| key != control_block.internal_key) {
+            return Err(TaprootFieldError::InternalKeyMismatch);
+        }
+        let mut merkle_root = TapNodeHash::from_script(&script, control_block.leaf_version);
+        for node in &control_block.merkle_branch {
+            merkle_root = TapNodeHash::from_node_hashes(merkle_root, *node);
+        }
+        if self.tap_merkle_root.map_or(false, |root| root != merkle_root) {
+            return Err(TaprootFieldError::MerkleRootMismatch);
+        }
+
+        self.tap_internal_key = Some(control_block.internal_key);
+        self.tap_merkle_root = Some(merkle_root);
+        let leaf_version = control_block.leaf_version;
+        self.tap_scripts.insert(control_block, (script, leaf_version));
+        Ok(())
+    }
+
+    /// Returns the leaf script with hash `leaf_hash` and its control block.
+    pub fn tap_script(&self, leaf_hash: TapLeafHash) -> Option<(&ScriptBuf, &ControlBlock)> {
+        self.tap_scripts.iter().find_map(|(control_block, (script, leaf_version))| {
+            (TapLeafHash::from_script(script, *leaf_version) == leaf_hash)
+                .then(|| (script, control_block))
+        })
+    }
+
+    /// Adds the origin of `key`, merging `leaf_hashes` with the leaves already recorded for it.
+    ///
+    /// An empty list of leaf hashes records the origin of a key only used for the key path.
+    ///
+    /// # Errors
+    ///
+    /// If a different origin is already recorded for `key`.
+    pub fn add_tap_key_origin(
+        &mut self,
+        key: XOnlyPublicKey,
+        leaf_hashes: impl IntoIterator<Item = TapLeafHash>,
+        origin: KeySource,
+    ) -> Result<(), TaprootFieldError> {
+        let (leaves, recorded) =
+            self.tap_key_origins.entry(key).or_insert_with(|| (vec![], origin.clone()));
+        if *recorded != origin {
+            return Err(TaprootFieldError::KeyOriginMismatch);
+        }
+        for leaf_hash in leaf_hashes {
+            if !leaves.contains(&leaf_hash) {
+                leaves.push(leaf_hash);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a script path signature of `key` for the leaf with hash `leaf_hash`.
+    ///
+    /// # Errors
+    ///
+    /// If the leaf is not in `tap_scripts`.
+    pub fn add_tap_script_sig(
+        &mut self,
+        key: XOnlyPublicKey,
+        leaf_hash: TapLeafHash,
+        signature: taproot::Signature,
+    ) -> Result<(), TaprootFieldError> {
+        if self.tap_script(leaf_hash).is_none() {
+            return Err(TaprootFieldError::UnknownLeaf(leaf_hash));
+        }
+        self.tap_script_sigs.insert((key, leaf_hash), signature);
+        Ok(())
+    }
+
     pub(super) fn insert_pair(&mut self, pair: raw::Pair) -> Result<(), Error> {
         let raw::Pair { key: raw_key, value: raw_value } = pair;
 
@@ -351,6 +475,16 @@ impl Input {
                     self.tap_merkle_root <= <raw_key: _>|< raw_value: TapNodeHash>
                 }
             }
+            PSBT_IN_REQUIRED_TIME_LOCKTIME => {
+                impl_psbt_insert_pair! {
+                    self.required_time_lock_time <= <raw_key: _>|<raw_value: absolute::Time>
+                }
+            }
+            PSBT_IN_REQUIRED_HEIGHT_LOCKTIME => {
+                impl_psbt_insert_pair! {
+                    self.required_height_lock_time <= <raw_key: _>|<raw_value: absolute::Height>
+                }
+            }
             PSBT_IN_PROPRIETARY => {
                 let key = raw::ProprietaryKey::try_from(raw_key.clone())?;
                 match self.proprietary.entry(key) {
@@ -399,6 +533,109 @@ impl Input {
         combine!(tap_key_sig, self, other);
         combine!(tap_internal_key, self, other);
         combine!(tap_merkle_root, self, other);
+        combine!(required_time_lock_time, self, other);
+        combine!(required_height_lock_time, self, other);
+    }
+
+    /// Returns the name of a field that `other` sets to a different value than this input.
+    ///
+    /// Signatures, finalized scripts and unknown pairs never conflict, BIP 174 lets the combiner
+    /// pick either value for them.
+    pub(crate) fn conflicting_field(&self, other: &Self) -> Option<&'static str> {
+        return_conflict!(non_witness_utxo, self, other);
+        return_conflict!(witness_utxo, self, other);
+        return_conflict!(sighash_type, self, other);
+        return_conflict!(redeem_script, self, other);
+        return_conflict!(witness_script, self, other);
+        return_conflict!(tap_internal_key, self, other);
+        return_conflict!(tap_merkle_root, self, other);
+        return_conflict!(required_time_lock_time, self, other);
+        return_conflict!(required_height_lock_time, self, other);
+
+        if maps_conflict(&self.bip32_derivation, &other.bip32_derivation) {
+            return Some("bip32_derivation");
+        }
+        if maps_conflict(&self.tap_scripts, &other.tap_scripts) {
+            return Some("tap_scripts");
+        }
+        if maps_conflict(&self.tap_key_origins, &other.tap_key_origins) {
+            return Some("tap_key_origins");
+        }
+        None
+    }
+
+    /// Checks that this input of a version 0 PSBT contains none of the fields [BIP-370] reserves
+    /// for version 2.
+    ///
+    /// [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
+    pub(crate) fn check_v0(&self) -> Result<(), Error> {
+        let v2_only = [PSBT_IN_PREVIOUS_TXID, PSBT_IN_OUTPUT_INDEX, PSBT_IN_SEQUENCE];
+        if self.required_time_lock_time.is_some()
+            || self.required_height_lock_time.is_some()
+            || self
+                .unknown
+                .keys()
+                .any(|key| key.key.is_empty() && v2_only.contains(&key.type_value))
+        {
+            return Err(Error::Version("PSBT v0 inputs must not contain PSBT v2 fields"));
+        }
+        Ok(())
+    }
+
+    /// Removes the [BIP-370] fields describing the spent outpoint and sequence from the unknown
+    /// key-value pairs of this input, returning the corresponding unsigned transaction input.
+    ///
+    /// [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
+    pub(crate) fn take_tx_in(&mut self) -> Result<TxIn, Error> {
+        let txid = take_keyless_unknown(&mut self.unknown, PSBT_IN_PREVIOUS_TXID)?
+            .ok_or(Error::Version("PSBT v2 input must have a previous txid"))?;
+        let vout = take_keyless_unknown(&mut self.unknown, PSBT_IN_OUTPUT_INDEX)?
+            .ok_or(Error::Version("PSBT v2 input must have an output index"))?;
+        let sequence = match take_keyless_unknown(&mut self.unknown, PSBT_IN_SEQUENCE)? {
+            Some(sequence) => Sequence::from_consensus(encode::deserialize(&sequence)?),
+            None => Sequence::MAX,
+        };
+
+        Ok(TxIn {
+            previous_output: OutPoint {
+                txid: encode::deserialize(&txid)?,
+                vout: encode::deserialize(&vout)?,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence,
+            witness: Witness::new(),
+        })
+    }
+
+    /// Serializes this input together with the [BIP-370] fields describing `tx_in`.
+    ///
+    /// [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
+    pub(crate) fn serialize_map_v2(&self, tx_in: &TxIn) -> Vec<u8> {
+        let mut pairs = vec![
+            raw::Pair {
+                key: raw::Key { type_value: PSBT_IN_PREVIOUS_TXID, key: vec![] },
+                value: encode::serialize(&tx_in.previous_output.txid),
+            },
+            raw::Pair {
+                key: raw::Key { type_value: PSBT_IN_OUTPUT_INDEX, key: vec![] },
+                value: encode::serialize(&tx_in.previous_output.vout),
+            },
+        ];
+        // An absent sequence number defaults to final.
+        if tx_in.sequence != Sequence::MAX {
+            pairs.push(raw::Pair {
+                key: raw::Key { type_value: PSBT_IN_SEQUENCE, key: vec![] },
+                value: encode::serialize(&tx_in.sequence),
+            });
+        }
+        pairs.extend(self.get_pairs());
+
+        let mut buf = Vec::new();
+        for pair in pairs {
+            buf.extend(&pair.serialize());
+        }
+        buf.push(0x00_u8);
+        buf
     }
 }
 
@@ -481,6 +718,14 @@ impl Map for Input {
         impl_psbt_get_pair! {
             rv.push(self.tap_merkle_root, PSBT_IN_TAP_MERKLE_ROOT)
         }
+
+        impl_psbt_get_pair! {
+            rv.push(self.required_time_lock_time, PSBT_IN_REQUIRED_TIME_LOCKTIME)
+        }
+
+        impl_psbt_get_pair! {
+            rv.push(self.required_height_lock_time, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME)
+        }
         for (key, value) in self.proprietary.iter() {
             rv.push(raw::Pair { key: key.to_key(), value: value.clone() });
         }
@@ -495,6 +740,166 @@ impl Map for Input {
 
 impl_psbtmap_ser_de_serialize!(Input);
 
+/// The serde representation of [`Input`], with the layout it had before PSBT version 2 support.
+///
+/// The version 2 only fields are carried as their raw pairs in `unknown`, see [`SerdePsbt`].
+///
+/// [`SerdePsbt`]: crate::psbt::map::SerdePsbt
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "actual_serde")]
+struct SerdeInput {
+    non_witness_utxo: Option<Transaction>,
+    witness_utxo: Option<TxOut>,
+    partial_sigs: BTreeMap<PublicKey, ecdsa::Signature>,
+    sighash_type: Option<PsbtSighashType>,
+    redeem_script: Option<ScriptBuf>,
+    witness_script: Option<ScriptBuf>,
+    #[serde(with = "crate::serde_utils::btreemap_as_seq")]
+    bip32_derivation: BTreeMap<secp256k1::PublicKey, KeySource>,
+    final_script_sig: Option<ScriptBuf>,
+    final_script_witness: Option<Witness>,
+    #[serde(with = "crate::serde_utils::btreemap_byte_values")]
+    ripemd160_preimages: BTreeMap<ripemd160::Hash, Vec<u8>>,
+    #[serde(with = "crate::serde_utils::btreemap_byte_values")]
+    sha256_preimages: BTreeMap<sha256::Hash, Vec<u8>>,
+    #[serde(with = "crate::serde_utils::btreemap_byte_values")]
+    hash160_preimages: BTreeMap<hash160::Hash, Vec<u8>>,
+    #[serde(with = "crate::serde_utils::btreemap_byte_values")]
+    hash256_preimages: BTreeMap<sha256d::Hash, Vec<u8>>,
+    tap_key_sig: Option<taproot::Signature>,
+    #[serde(with = "crate::serde_utils::btreemap_as_seq")]
+    tap_script_sigs: BTreeMap<(XOnlyPublicKey, TapLeafHash), taproot::Signature>,
+    #[serde(with = "crate::serde_utils::btreemap_as_seq")]
+    tap_scripts: BTreeMap<ControlBlock, (ScriptBuf, LeafVersion)>,
+    #[serde(with = "crate::serde_utils::btreemap_as_seq")]
+    tap_key_origins: BTreeMap<XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)>,
+    tap_internal_key: Option<XOnlyPublicKey>,
+    tap_merkle_root: Option<TapNodeHash>,
+    #[serde(with = "crate::serde_utils::btreemap_as_seq_byte_values")]
+    proprietary: BTreeMap<raw::ProprietaryKey, Vec<u8>>,
+    #[serde(with = "crate::serde_utils::btreemap_as_seq_byte_values")]
+    unknown: BTreeMap<raw::Key, Vec<u8>>,
+}
+
+#[cfg(feature = "serde")]
+impl From<Input> for SerdeInput {
+    fn from(input: Input) -> Self {
+        let mut unknown = input.unknown;
+        if let Some(time) = input.required_time_lock_time {
+            let key = raw::Key { type_value: PSBT_IN_REQUIRED_TIME_LOCKTIME, key: vec![] };
+            unknown.insert(key, time.serialize());
+        }
+        if let Some(height) = input.required_height_lock_time {
+            let key = raw::Key { type_value: PSBT_IN_REQUIRED_HEIGHT_LOCKTIME, key: vec![] };
+            unknown.insert(key, height.serialize());
+        }
+        SerdeInput {
+            non_witness_utxo: input.non_witness_utxo,
+            witness_utxo: input.witness_utxo,
+            partial_sigs: input.partial_sigs,
+            sighash_type: input.sighash_type,
+            redeem_script: input.redeem_script,
+            witness_script: input.witness_script,
+            bip32_derivation: input.bip32_derivation,
+            final_script_sig: input.final_script_sig,
+            final_script_witness: input.final_script_witness,
+            ripemd160_preimages: input.ripemd160_preimages,
+            sha256_preimages: input.sha256_preimages,
+            hash160_preimages: input.hash160_preimages,
+            hash256_preimages: input.hash256_preimages,
+            tap_key_sig: input.tap_key_sig,
+            tap_script_sigs: input.tap_script_sigs,
+            tap_scripts: input.tap_scripts,
+            tap_key_origins: input.tap_key_origins,
+            tap_internal_key: input.tap_internal_key,
+            tap_merkle_root: input.tap_merkle_root,
+            proprietary: input.proprietary,
+            unknown,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<SerdeInput> for Input {
+    fn from(input: SerdeInput) -> Self {
+        let mut unknown = input.unknown;
+        let required_time_lock_time =
+            take_serde_field(&mut unknown, PSBT_IN_REQUIRED_TIME_LOCKTIME, |value| {
+                absolute::Time::deserialize(value).ok()
+            });
+        let required_height_lock_time =
+            take_serde_field(&mut unknown, PSBT_IN_REQUIRED_HEIGHT_LOCKTIME, |value| {
+                absolute::Height::deserialize(value).ok()
+            });
+        Input {
+            non_witness_utxo: input.non_witness_utxo,
+            witness_utxo: input.witness_utxo,
+            partial_sigs: input.partial_sigs,
+            sighash_type: input.sighash_type,
+            redeem_script: input.redeem_script,
+            witness_script: input.witness_script,
+            bip32_derivation: input.bip32_derivation,
+            final_script_sig: input.final_script_sig,
+            final_script_witness: input.final_script_witness,
+            ripemd160_preimages: input.ripemd160_preimages,
+            sha256_preimages: input.sha256_preimages,
+            hash160_preimages: input.hash160_preimages,
+            hash256_preimages: input.hash256_preimages,
+            tap_key_sig: input.tap_key_sig,
+            tap_script_sigs: input.tap_script_sigs,
+            tap_scripts: input.tap_scripts,
+            tap_key_origins: input.tap_key_origins,
+            tap_internal_key: input.tap_internal_key,
+            tap_merkle_root: input.tap_merkle_root,
+            required_time_lock_time,
+            required_height_lock_time,
+            proprietary: input.proprietary,
+            unknown,
+        }
+    }
+}
+
+/// Error adding taproot data to an [`Input`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TaprootFieldError {
+    /// The control block is for a different internal key.
+    InternalKeyMismatch,
+    /// The leaf script is not in the tree with the merkle root of the input.
+    MerkleRootMismatch,
+    /// A different origin is already recorded for the key.
+    KeyOriginMismatch,
+    /// The leaf is not one of the input's leaf scripts.
+    UnknownLeaf(TapLeafHash),
+}
+
+internals::impl_from_infallible!(TaprootFieldError);
+
+impl fmt::Display for TaprootFieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TaprootFieldError::*;
+
+        match *self {
+            InternalKeyMismatch => f.write_str("control block is for a different internal key"),
+            MerkleRootMismatch => f.write_str("leaf script does not match the merkle root"),
+            KeyOriginMismatch => f.write_str("a different key origin is already recorded"),
+            UnknownLeaf(ref leaf_hash) => write!(f, "unknown taproot leaf {}", leaf_hash),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TaprootFieldError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use TaprootFieldError::*;
+
+        match *self {
+            InternalKeyMismatch | MerkleRootMismatch | KeyOriginMismatch | UnknownLeaf(_) => None,
+        }
+    }
+}
+
 fn psbt_insert_hash_pair<H>(
     map: &mut BTreeMap<H, Vec<u8>>,
     raw_key: raw::Key,
@@ -577,4 +982,68 @@ mod test {
         assert_eq!(back.ecdsa_hash_ty(), Err(NonStandardSighashTypeError(nonstd)));
         assert_eq!(back.taproot_hash_ty(), Err(InvalidSighashTypeError(nonstd)));
     }
+
+    #[test]
+    fn taproot_fields() {
+        use secp256k1::Secp256k1;
+
+        use crate::taproot::TaprootBuilder;
+
+        let secp = Secp256k1::verification_only();
+        let internal_key: XOnlyPublicKey =
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798".parse().unwrap();
+        let other_key: XOnlyPublicKey =
+            "c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5".parse().unwrap();
+        let leaf_a = ScriptBuf::from_bytes(vec![0x51]);
+        let leaf_b = ScriptBuf::from_bytes(vec![0x52]);
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, leaf_a.clone())
+            .unwrap()
+            .add_leaf(1, leaf_b.clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+        let leaf_hash_a = TapLeafHash::from_script(&leaf_a, LeafVersion::TapScript);
+        let control_block_a =
+            spend_info.control_block(&(leaf_a.clone(), LeafVersion::TapScript)).unwrap();
+
+        let mut input = Input::default();
+        input.set_taproot_spend_info(&spend_info);
+        assert_eq!(input.tap_internal_key, Some(internal_key));
+        assert_eq!(input.tap_merkle_root, spend_info.merkle_root());
+        assert_eq!(input.tap_scripts.len(), 2);
+        assert_eq!(input.tap_script(leaf_hash_a), Some((&leaf_a, &control_block_a)));
+
+        let mut input = Input::default();
+        input.add_tap_script(control_block_a.clone(), leaf_a.clone()).unwrap();
+        assert_eq!(input.tap_merkle_root, spend_info.merkle_root());
+        // The proof for leaf A does not prove leaf B.
+        assert_eq!(
+            input.add_tap_script(control_block_a.clone(), leaf_b),
+            Err(TaprootFieldError::MerkleRootMismatch)
+        );
+        let mut control_block = control_block_a;
+        control_block.internal_key = other_key;
+        assert_eq!(
+            input.add_tap_script(control_block, leaf_a),
+            Err(TaprootFieldError::InternalKeyMismatch)
+        );
+
+        let origin: KeySource = (Default::default(), "0/1".parse().unwrap());
+        input.add_tap_key_origin(other_key, vec![leaf_hash_a], origin.clone()).unwrap();
+        input.add_tap_key_origin(other_key, vec![leaf_hash_a], origin.clone()).unwrap();
+        assert_eq!(input.tap_key_origins[&other_key], (vec![leaf_hash_a], origin));
+        assert_eq!(
+            input.add_tap_key_origin(other_key, vec![], Default::default()),
+            Err(TaprootFieldError::KeyOriginMismatch)
+        );
+
+        let signature = taproot::Signature::from_slice(&[1; 64]).unwrap();
+        input.add_tap_script_sig(other_key, leaf_hash_a, signature).unwrap();
+        let leaf_hash_b = TapLeafHash::from_script(&ScriptBuf::new(), LeafVersion::TapScript);
+        assert_eq!(
+            input.add_tap_script_sig(other_key, leaf_hash_b, signature),
+            Err(TaprootFieldError::UnknownLeaf(leaf_hash_b))
+        );
+    }
 }
@@ -5,15 +5,17 @@ mod input;
 mod output;
 
 use crate::prelude::*;
-use crate::psbt::raw;
 use crate::psbt::serialize::Serialize;
+use crate::psbt::{raw, Error};
 
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
 pub use self::{
-    input::{Input, PsbtSighashType},
+    input::{Input, PsbtSighashType, TaprootFieldError},
     output::Output,
 };
+#[cfg(feature = "serde")]
+pub(super) use self::global::SerdePsbt;
 
 /// A trait that describes a PSBT key-value map.
 pub(super) trait Map {
@@ -36,3 +38,39 @@ pub(super) trait Map {
         buf
     }
 }
+
+/// Removes the value of the keyless pair of type `type_value` from a map of unknown pairs.
+///
+/// Used for fields only known to PSBT version 2, which are decoded before the version is known.
+fn take_keyless_unknown(
+    unknown: &mut BTreeMap<raw::Key, Vec<u8>>,
+    type_value: u8,
+) -> Result<Option<Vec<u8>>, Error> {
+    if let Some(key) =
+        unknown.keys().find(|key| key.type_value == type_value && !key.key.is_empty())
+    {
+        return Err(Error::InvalidKey(key.clone()));
+    }
+    Ok(unknown.remove(&raw::Key { type_value, key: vec![] }))
+}
+
+/// Removes the pair of type `type_value` with an empty key from `unknown` and decodes its value.
+///
+/// Used by the serde representations, which carry the PSBT version 2 only fields as raw pairs. The
+/// pair is left in place if it can not be decoded.
+#[cfg(feature = "serde")]
+fn take_serde_field<T>(
+    unknown: &mut BTreeMap<raw::Key, Vec<u8>>,
+    type_value: u8,
+    decode: impl FnOnce(&[u8]) -> Option<T>,
+) -> Option<T> {
+    let key = raw::Key { type_value, key: vec![] };
+    let value = decode(unknown.get(&key)?)?;
+    unknown.remove(&key);
+    Some(value)
+}
+
+/// Returns whether `a` and `b` map any key to different values.
+fn maps_conflict<K: Ord, V: PartialEq>(a: &BTreeMap<K, V>, b: &BTreeMap<K, V>) -> bool {
+    b.iter().any(|(key, value)| a.get(key).map_or(false, |v| v != value))
+}
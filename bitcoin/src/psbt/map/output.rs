@@ -4,8 +4,11 @@ use secp256k1::XOnlyPublicKey;
 
 use crate::bip32::KeySource;
 use crate::blockdata::script::ScriptBuf;
+use crate::blockdata::transaction::TxOut;
+use crate::consensus::encode;
 use crate::prelude::*;
-use crate::psbt::map::Map;
+use crate::psbt::map::{maps_conflict, take_keyless_unknown, Map};
+use crate::psbt::serialize::Serialize;
 use crate::psbt::{raw, Error};
 use crate::taproot::{TapLeafHash, TapTree};
 
@@ -15,6 +18,10 @@ const PSBT_OUT_REDEEM_SCRIPT: u8 = 0x00;
 const PSBT_OUT_WITNESS_SCRIPT: u8 = 0x01;
 /// Type: BIP 32 Derivation Path PSBT_OUT_BIP32_DERIVATION = 0x02
 const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+/// Type: Output Amount PSBT_OUT_AMOUNT = 0x03
+const PSBT_OUT_AMOUNT: u8 = 0x03;
+/// Type: Output Script PSBT_OUT_SCRIPT = 0x04
+const PSBT_OUT_SCRIPT: u8 = 0x04;
 /// Type: Taproot Internal Key PSBT_OUT_TAP_INTERNAL_KEY = 0x05
 const PSBT_OUT_TAP_INTERNAL_KEY: u8 = 0x05;
 /// Type: Taproot Tree PSBT_OUT_TAP_TREE = 0x06
@@ -54,6 +61,14 @@ pub struct Output {
 }
 
 impl Output {
+    /// Returns the proprietary key-value pairs of this output with the identifier `prefix`.
+    pub fn proprietary_with_prefix<'a>(
+        &'a self,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (&'a raw::ProprietaryKey, &'a Vec<u8>)> {
+        raw::proprietary_with_prefix(&self.proprietary, prefix)
+    }
+
     pub(super) fn insert_pair(&mut self, pair: raw::Pair) -> Result<(), Error> {
         let raw::Pair { key: raw_key, value: raw_value } = pair;
 
@@ -120,6 +135,71 @@ impl Output {
         combine!(tap_internal_key, self, other);
         combine!(tap_tree, self, other);
     }
+
+    /// Returns the name of a field that `other` sets to a different value than this output.
+    pub(crate) fn conflicting_field(&self, other: &Self) -> Option<&'static str> {
+        return_conflict!(redeem_script, self, other);
+        return_conflict!(witness_script, self, other);
+        return_conflict!(tap_internal_key, self, other);
+        return_conflict!(tap_tree, self, other);
+
+        if maps_conflict(&self.bip32_derivation, &other.bip32_derivation) {
+            return Some("bip32_derivation");
+        }
+        if maps_conflict(&self.tap_key_origins, &other.tap_key_origins) {
+            return Some("tap_key_origins");
+        }
+        None
+    }
+
+    /// Checks that this output of a version 0 PSBT contains none of the fields [BIP-370] reserves
+    /// for version 2.
+    ///
+    /// [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
+    pub(crate) fn check_v0(&self) -> Result<(), Error> {
+        let v2_only = [PSBT_OUT_AMOUNT, PSBT_OUT_SCRIPT];
+        if self.unknown.keys().any(|key| key.key.is_empty() && v2_only.contains(&key.type_value)) {
+            return Err(Error::Version("PSBT v0 outputs must not contain PSBT v2 fields"));
+        }
+        Ok(())
+    }
+
+    /// Removes the [BIP-370] fields describing the amount and script of this output from its
+    /// unknown key-value pairs, returning the corresponding unsigned transaction output.
+    ///
+    /// [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
+    pub(crate) fn take_tx_out(&mut self) -> Result<TxOut, Error> {
+        let amount = take_keyless_unknown(&mut self.unknown, PSBT_OUT_AMOUNT)?
+            .ok_or(Error::Version("PSBT v2 output must have an amount"))?;
+        let script = take_keyless_unknown(&mut self.unknown, PSBT_OUT_SCRIPT)?
+            .ok_or(Error::Version("PSBT v2 output must have a script"))?;
+
+        Ok(TxOut { value: encode::deserialize(&amount)?, script_pubkey: ScriptBuf::from(script) })
+    }
+
+    /// Serializes this output together with the [BIP-370] fields describing `tx_out`.
+    ///
+    /// [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
+    pub(crate) fn serialize_map_v2(&self, tx_out: &TxOut) -> Vec<u8> {
+        let mut pairs = vec![
+            raw::Pair {
+                key: raw::Key { type_value: PSBT_OUT_AMOUNT, key: vec![] },
+                value: encode::serialize(&tx_out.value),
+            },
+            raw::Pair {
+                key: raw::Key { type_value: PSBT_OUT_SCRIPT, key: vec![] },
+                value: tx_out.script_pubkey.to_bytes(),
+            },
+        ];
+        pairs.extend(self.get_pairs());
+
+        let mut buf = Vec::new();
+        for pair in pairs {
+            buf.extend(&pair.serialize());
+        }
+        buf.push(0x00_u8);
+        buf
+    }
 }
 
 impl Map for Output {
@@ -6,13 +6,22 @@
 //! defined at <https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki>
 //! except we define PSBTs containing non-standard sighash types as invalid.
 //!
+//! Version 2 PSBTs as defined by BIP370 at
+//! <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki> are supported too, the
+//! unsigned transaction is reconstructed from their per-input and per-output fields.
+//!
 
 #[macro_use]
 mod macros;
+mod change;
 mod error;
+mod finalizer;
 mod map;
 pub mod raw;
 pub mod serialize;
+#[cfg(test)]
+mod test_utils;
+mod updater;
 
 use core::{cmp, fmt};
 #[cfg(feature = "std")]
@@ -20,32 +29,42 @@ use std::collections::{HashMap, HashSet};
 
 use hashes::Hash;
 use internals::write_err;
-use secp256k1::{Message, Secp256k1, Signing};
+use secp256k1::{Keypair, Message, Parity, Secp256k1, Signing, XOnlyPublicKey};
 
 use crate::bip32::{self, KeySource, Xpriv, Xpub};
-use crate::blockdata::transaction::{self, Transaction, TxOut};
-use crate::crypto::ecdsa;
-use crate::crypto::key::{PrivateKey, PublicKey};
+use crate::blockdata::locktime::absolute;
+use crate::blockdata::transaction::{self, Transaction, TxIn, TxOut};
+use crate::crypto::key::{PrivateKey, PublicKey, TapTweak};
+use crate::crypto::{ecdsa, taproot};
 use crate::prelude::*;
-use crate::sighash::{self, EcdsaSighashType, SighashCache};
-use crate::{Amount, FeeRate};
+use crate::sighash::{self, EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use crate::taproot::TapLeafHash;
+use crate::{Amount, FeeRate, VarInt, Weight};
 
 #[rustfmt::skip]                // Keep public re-exports separate.
 #[doc(inline)]
 pub use self::{
-    map::{Input, Output, PsbtSighashType},
+    map::{Input, Output, PsbtSighashType, TaprootFieldError},
+    change::ChangeError,
     error::Error,
+    finalizer::FinalizeError,
+    updater::UpdateError,
 };
 
 /// A Partially Signed Transaction.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
+#[cfg_attr(feature = "serde", serde(into = "map::SerdePsbt", from = "map::SerdePsbt"))]
 pub struct Psbt {
     /// The unsigned transaction, scriptSigs and witnesses for each input must be empty.
     pub unsigned_tx: Transaction,
     /// The version number of this PSBT. If omitted, the version number is 0.
     pub version: u32,
+    /// The lock time to use if no input requires one (PSBT version 2 only).
+    pub fallback_lock_time: Option<absolute::LockTime>,
+    /// Whether inputs and outputs may still be added (PSBT version 2 only).
+    pub tx_modifiable: Option<TxModifiable>,
     /// A global map from extended public keys to the used key fingerprint and
     /// derivation path as defined by BIP 32.
     pub xpub: BTreeMap<Xpub, KeySource>,
@@ -62,6 +81,39 @@ pub struct Psbt {
     pub outputs: Vec<Output>,
 }
 
+/// The transaction modifiable flags of a version 2 PSBT ([BIP-370]).
+///
+/// Reserved flag bits are ignored when decoding.
+///
+/// [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(crate = "actual_serde"))]
+pub struct TxModifiable {
+    /// Inputs may be added to the PSBT.
+    pub inputs: bool,
+    /// Outputs may be added to the PSBT.
+    pub outputs: bool,
+    /// Some input is signed with `SIGHASH_SINGLE`, so inputs and outputs must be added in pairs.
+    pub has_sighash_single: bool,
+}
+
+impl TxModifiable {
+    /// Constructs the flags from their serialized form.
+    pub(crate) fn from_u8(flags: u8) -> Self {
+        TxModifiable {
+            inputs: flags & 0x01 != 0,
+            outputs: flags & 0x02 != 0,
+            has_sighash_single: flags & 0x04 != 0,
+        }
+    }
+
+    /// Returns the serialized form of the flags.
+    pub(crate) fn to_u8(self) -> u8 {
+        u8::from(self.inputs) | u8::from(self.outputs) << 1 | u8::from(self.has_sighash_single) << 2
+    }
+}
+
 impl Psbt {
     /// Returns an iterator for the funding UTXOs of the psbt
     ///
@@ -89,6 +141,14 @@ impl Psbt {
         })
     }
 
+    /// Returns the global proprietary key-value pairs of this PSBT with the identifier `prefix`.
+    pub fn proprietary_with_prefix<'a>(
+        &'a self,
+        prefix: &'a [u8],
+    ) -> impl Iterator<Item = (&'a raw::ProprietaryKey, &'a Vec<u8>)> {
+        raw::proprietary_with_prefix(&self.proprietary, prefix)
+    }
+
     /// Checks that unsigned transaction does not have scriptSig's or witness data.
     fn unsigned_tx_checks(&self) -> Result<(), Error> {
         for txin in &self.unsigned_tx.input {
@@ -117,6 +177,8 @@ impl Psbt {
             unsigned_tx: tx,
             xpub: Default::default(),
             version: 0,
+            fallback_lock_time: None,
+            tx_modifiable: None,
             proprietary: Default::default(),
             unknown: Default::default(),
         };
@@ -215,22 +277,50 @@ impl Psbt {
         Ok(tx)
     }
 
-    /// Combines this [`Psbt`] with `other` PSBT as described by BIP 174.
+    /// Combines this [`Psbt`] with `other` PSBT as described by BIP 174, returning the result.
+    ///
+    /// Signatures, key origins, preimages, proprietary and unknown key-value pairs of both PSBTs
+    /// are merged. Fields for which BIP 174 lets the combiner pick arbitrarily, such as two
+    /// signatures by the same key, keep the value from `self`.
     ///
     /// In accordance with BIP 174 this function is commutative i.e., `A.combine(B) == B.combine(A)`
-    pub fn combine(&mut self, other: Self) -> Result<(), Error> {
+    /// whenever both succeed.
+    ///
+    /// # Errors
+    ///
+    /// If the PSBTs describe different transactions or disagree on the value of a field that
+    /// identifies what is being spent or how, the returned [`CombineError`] names the conflict.
+    pub fn combine(mut self, other: Self) -> Result<Psbt, CombineError> {
         if self.unsigned_tx != other.unsigned_tx {
-            return Err(Error::UnexpectedUnsignedTx {
-                expected: Box::new(self.unsigned_tx.clone()),
+            return Err(CombineError::UnexpectedUnsignedTx {
+                expected: Box::new(self.unsigned_tx),
                 actual: Box::new(other.unsigned_tx),
             });
         }
+        if self.fallback_lock_time.is_some()
+            && other.fallback_lock_time.is_some()
+            && self.fallback_lock_time != other.fallback_lock_time
+        {
+            return Err(CombineError::ConflictingGlobal { field: "fallback_lock_time" });
+        }
+        for (index, (input, other_input)) in self.inputs.iter().zip(&other.inputs).enumerate() {
+            if let Some(field) = input.conflicting_field(other_input) {
+                return Err(CombineError::ConflictingInput { index, field });
+            }
+        }
+        for (index, (output, other_output)) in self.outputs.iter().zip(&other.outputs).enumerate() {
+            if let Some(field) = output.conflicting_field(other_output) {
+                return Err(CombineError::ConflictingOutput { index, field });
+            }
+        }
 
         // BIP 174: The Combiner must remove any duplicate key-value pairs, in accordance with
         //          the specification. It can pick arbitrarily when conflicts occur.
 
         // Keeping the highest version
         self.version = cmp::max(self.version, other.version);
+        combine!(fallback_lock_time, self, other);
+        combine!(tx_modifiable, self, other);
 
         // Merging xpubs
         for (xpub, (fingerprint1, derivation1)) in other.xpub {
@@ -262,7 +352,7 @@ impl Psbt {
                         entry.insert((fingerprint1, derivation1));
                         continue;
                     }
-                    return Err(Error::CombineInconsistentKeySources(Box::new(xpub)));
+                    return Err(CombineError::InconsistentKeySources(Box::new(xpub)));
                 }
             }
         }
@@ -278,16 +368,17 @@ impl Psbt {
             self_output.combine(other_output);
         }
 
-        Ok(())
+        Ok(self)
     }
 
     /// Attempts to create _all_ the required signatures for this PSBT using `k`.
     ///
-    /// **NOTE**: Taproot inputs are, as yet, not supported by this function. We currently only
-    /// attempt to sign ECDSA inputs.
+    /// ECDSA inputs are signed for each key in `bip32_derivation`, taproot inputs for each key in
+    /// `tap_key_origins`: the internal key signs the key path and other keys sign the script
+    /// path leaves listed with them.
     ///
-    /// If you just want to sign an input with one specific key consider using `sighash_ecdsa`. This
-    /// function does not support scripts that contain `OP_CODESEPARATOR`.
+    /// If you just want to sign an input with one specific key consider using `sighash_ecdsa` or
+    /// `sighash_taproot`. This function does not support scripts that contain `OP_CODESEPARATOR`.
     ///
     /// # Returns
     ///
@@ -313,16 +404,25 @@ impl Psbt {
         let mut errors = BTreeMap::new();
 
         for i in 0..self.inputs.len() {
-            if let Ok(SigningAlgorithm::Ecdsa) = self.signing_algorithm(i) {
-                match self.bip32_sign_ecdsa(k, i, &mut cache, secp) {
-                    Ok(v) => {
-                        used.insert(i, v);
-                    }
-                    Err(e) => {
-                        errors.insert(i, e);
+            let res = match self.signing_algorithm(i) {
+                Ok(SigningAlgorithm::Ecdsa) => self.bip32_sign_ecdsa(k, i, &mut cache, secp),
+                Ok(SigningAlgorithm::Schnorr) => self.bip32_sign_schnorr(k, i, &mut cache, secp),
+                Err(_) => continue,
+            };
+            match res {
+                Ok(v) => {
+                    if !v.is_empty() {
+                        let sighash_type = self.inputs[i]
+                            .sighash_type
+                            .unwrap_or_else(|| EcdsaSighashType::All.into());
+                        self.update_tx_modifiable(sighash_type);
                     }
+                    used.insert(i, v);
                 }
-            };
+                Err(e) => {
+                    errors.insert(i, e);
+                }
+            }
         }
         if errors.is_empty() {
             Ok(used)
@@ -385,6 +485,120 @@ impl Psbt {
         Ok(used)
     }
 
+    /// Attempts to create all signatures required by this PSBT's `tap_key_origins` field, adding
+    /// them to `tap_key_sig` and `tap_script_sigs`.
+    ///
+    /// # Returns
+    ///
+    /// - Ok: A list of the public keys used in signing.
+    /// - Err: Error encountered trying to calculate the sighash AND we had the signing key.
+    fn bip32_sign_schnorr<C, K, T>(
+        &mut self,
+        k: &K,
+        input_index: usize,
+        cache: &mut SighashCache<T>,
+        secp: &Secp256k1<C>,
+    ) -> Result<Vec<PublicKey>, SignError>
+    where
+        C: Signing,
+        T: Borrow<Transaction>,
+        K: GetKey,
+    {
+        let mut input = self.checked_input(input_index)?.clone();
+
+        let mut used = vec![]; // List of pubkeys used to sign the input.
+
+        for (&xonly, (leaf_hashes, key_source)) in input.tap_key_origins.iter() {
+            let sk = if let Ok(Some(sk)) = k.get_key(KeyRequest::Bip32(key_source.clone()), secp) {
+                sk
+            } else if let Ok(Some(sk)) = k.get_key(KeyRequest::XOnlyPubkey(xonly), secp) {
+                sk
+            } else {
+                continue;
+            };
+            let keypair = Keypair::from_secret_key(secp, &sk.inner);
+            let mut signed = false;
+
+            // BIP 371: The internal key has no leaf hashes and signs the key path.
+            if input.tap_internal_key == Some(xonly)
+                && leaf_hashes.is_empty()
+                && input.tap_key_sig.is_none()
+            {
+                let (msg, sighash_type) = self.sighash_taproot(input_index, cache, None)?;
+                // Tweaking needs a verification context, which `secp` may not be.
+                let verify = Secp256k1::verification_only();
+                let keypair = keypair.tap_tweak(&verify, input.tap_merkle_root).to_inner();
+                let signature = sign_schnorr(secp, &msg, &keypair);
+                input.tap_key_sig = Some(taproot::Signature { signature, sighash_type });
+                signed = true;
+            }
+
+            for &leaf_hash in leaf_hashes {
+                if input.tap_script_sigs.contains_key(&(xonly, leaf_hash)) {
+                    continue;
+                }
+                let (msg, sighash_type) =
+                    self.sighash_taproot(input_index, cache, Some(leaf_hash))?;
+                let signature = sign_schnorr(secp, &msg, &keypair);
+                input
+                    .tap_script_sigs
+                    .insert((xonly, leaf_hash), taproot::Signature { signature, sighash_type });
+                signed = true;
+            }
+
+            if signed {
+                used.push(sk.public_key(secp));
+            }
+        }
+
+        self.inputs[input_index] = input;
+        Ok(used)
+    }
+
+    /// Returns the sighash message to sign a taproot input along with the sighash type.
+    ///
+    /// Computes the key path sighash if `leaf_hash` is `None` and the script path sighash of the
+    /// leaf otherwise. Uses the [`TapSighashType`] from this input if one is specified. If no
+    /// sighash type is specified uses [`TapSighashType::Default`]. This function does not support
+    /// scripts that contain `OP_CODESEPARATOR`.
+    pub fn sighash_taproot<T: Borrow<Transaction>>(
+        &self,
+        input_index: usize,
+        cache: &mut SighashCache<T>,
+        leaf_hash: Option<TapLeafHash>,
+    ) -> Result<(Message, TapSighashType), SignError> {
+        if self.signing_algorithm(input_index)? != SigningAlgorithm::Schnorr {
+            return Err(SignError::WrongSigningAlgorithm);
+        }
+
+        let input = self.checked_input(input_index)?;
+        let hash_ty = input.taproot_hash_ty().map_err(|_| SignError::InvalidSighashType)?;
+
+        let spend_utxos: Vec<_> = (0..self.inputs.len()).map(|i| self.spend_utxo(i).ok()).collect();
+        let all_spend_utxos: Vec<&TxOut>;
+        let anyone_can_pay = PsbtSighashType::from(hash_ty).to_u32() & 0x80 != 0;
+        let prevouts = if anyone_can_pay {
+            Prevouts::One(input_index, self.spend_utxo(input_index)?)
+        } else if spend_utxos.iter().all(Option::is_some) {
+            all_spend_utxos = spend_utxos.into_iter().flatten().collect();
+            Prevouts::All(&all_spend_utxos)
+        } else {
+            return Err(SignError::MissingSpendUtxo);
+        };
+
+        let sighash = match leaf_hash {
+            Some(leaf_hash) => cache.taproot_script_spend_signature_hash(
+                input_index,
+                &prevouts,
+                leaf_hash,
+                hash_ty,
+            ),
+            None => cache.taproot_key_spend_signature_hash(input_index, &prevouts, hash_ty),
+        }
+        .map_err(SignError::TaprootSighash)?;
+        Ok((Message::from_digest(sighash.to_byte_array()), hash_ty))
+    }
+
     /// Returns the sighash message to sign an ECDSA input along with the sighash type.
     ///
     /// Uses the [`EcdsaSighashType`] from this input if one is specified. If no sighash type is
@@ -435,8 +649,9 @@ impl Psbt {
             Wsh | ShWsh => {
                 let witness_script =
                     input.witness_script.as_ref().ok_or(SignError::MissingWitnessScript)?;
-                let sighash =
-                    cache.p2wsh_signature_hash(input_index, witness_script, utxo.value, hash_ty).map_err(SignError::SegwitV0Sighash)?;
+                let sighash = cache
+                    .p2wsh_signature_hash(input_index, witness_script, utxo.value, hash_ty)
+                    .map_err(SignError::SegwitV0Sighash)?;
                 Ok((Message::from_digest(sighash.to_byte_array()), hash_ty))
             }
             Tr => {
@@ -490,7 +705,10 @@ impl Psbt {
     }
 
     /// Returns the algorithm used to sign this PSBT's input at `input_index`.
-    fn signing_algorithm(&self, input_index: usize) -> Result<SigningAlgorithm, SignError> {
+    pub(crate) fn signing_algorithm(
+        &self,
+        input_index: usize,
+    ) -> Result<SigningAlgorithm, SignError> {
         let output_type = self.output_type(input_index)?;
         Ok(output_type.signing_algorithm())
     }
@@ -554,6 +772,286 @@ impl Psbt {
         }
         inputs.checked_sub(outputs).map(Amount::from_sat).ok_or(Error::NegativeFee)
     }
+
+    /// Calculates the fee rate of the transaction that would be extracted from this PSBT.
+    ///
+    /// The weight used includes the final scriptSigs and witnesses present, so the fee rate of a
+    /// PSBT that is not yet finalized is overestimated.
+    ///
+    /// ## Errors
+    ///
+    /// See [`Psbt::fee`].
+    pub fn fee_rate(&self) -> Result<FeeRate, Error> {
+        let fee = self.fee()?;
+        let weight = self.extracted_weight();
+        Ok(FeeRate::from_sat_per_kwu(fee.to_sat().saturating_mul(1000) / weight.to_wu()))
+    }
+
+    /// Returns the weight of the transaction that would be extracted from this PSBT.
+    fn extracted_weight(&self) -> Weight {
+        let script_size = |len: usize| VarInt::from(len).size() + len;
+
+        let mut base_size = self.unsigned_tx.base_size();
+        let mut witness_size = 0;
+        let mut uses_segwit = self.unsigned_tx.input.is_empty();
+        for (txin, input) in self.unsigned_tx.input.iter().zip(&self.inputs) {
+            let script_sig_len = input.final_script_sig.as_ref().map_or(0, |s| s.len());
+            base_size =
+                base_size - script_size(txin.script_sig.len()) + script_size(script_sig_len);
+            match input.final_script_witness {
+                Some(ref witness) if !witness.is_empty() => {
+                    uses_segwit = true;
+                    witness_size += witness.size();
+                }
+                // An empty witness is serialized as a zero element count.
+                _ => witness_size += 1,
+            }
+        }
+
+        let mut weight = Weight::from_non_witness_data_size(base_size as u64);
+        if uses_segwit {
+            // The marker and flag bytes.
+            weight += Weight::from_witness_data_size(2 + witness_size as u64);
+        }
+        weight
+    }
+
+    /// Converts this PSBT to version 2 as defined by [BIP-370].
+    ///
+    /// The lock time of the unsigned transaction becomes the fallback lock time and the
+    /// transaction is not modifiable. Converting back with [`Psbt::into_v0`] is lossless.
+    ///
+    /// # Errors
+    ///
+    /// If the unsigned transaction version is less than 2, which BIP-370 does not allow.
+    ///
+    /// [BIP-370]: <https://github.com/bitcoin/bips/blob/master/bip-0370.mediawiki>
+    pub fn into_v2(mut self) -> Result<Self, Error> {
+        if self.version >= 2 {
+            return Ok(self);
+        }
+        if self.unsigned_tx.version < transaction::Version::TWO {
+            return Err(Error::Version("PSBT v2 transaction version must be at least 2"));
+        }
+
+        self.version = 2;
+        if self.unsigned_tx.lock_time != absolute::LockTime::ZERO {
+            self.fallback_lock_time = Some(self.unsigned_tx.lock_time);
+        }
+        Ok(self)
+    }
+
+    /// Converts this PSBT to version 0, dropping the fields only version 2 supports.
+    ///
+    /// The unsigned transaction, including its lock time, is kept as is.
+    pub fn into_v0(mut self) -> Self {
+        self.version = 0;
+        self.fallback_lock_time = None;
+        self.tx_modifiable = None;
+        for input in &mut self.inputs {
+            input.required_time_lock_time = None;
+            input.required_height_lock_time = None;
+        }
+        self
+    }
+
+    /// Adds an input to a version 2 PSBT (the Constructor role of BIP-370).
+    ///
+    /// The lock time of the unsigned transaction is updated to satisfy the requirements of the
+    /// new input.
+    ///
+    /// # Errors
+    ///
+    /// If this is not a version 2 PSBT, its inputs are not modifiable, `tx_in` is not unsigned
+    /// or the lock time required by `input` is incompatible with the other inputs.
+    pub fn add_input(&mut self, tx_in: TxIn, input: Input) -> Result<(), Error> {
+        if self.version < 2 {
+            return Err(Error::Version("only PSBT v2 inputs are modifiable"));
+        }
+        self.insert_input(self.inputs.len(), tx_in, input)
+    }
+
+    /// Inserts an input at position `index`, keeping the unsigned transaction in sync.
+    ///
+    /// Inputs can only be added if all existing signatures use `SIGHASH_ANYONECANPAY` and no
+    /// input signed with `SIGHASH_SINGLE` is moved away from its output. Version 2 PSBTs must
+    /// also have their inputs marked as modifiable and get their lock time updated to satisfy
+    /// the requirements of the new input.
+    ///
+    /// # Errors
+    ///
+    /// If `index` is greater than the number of inputs, the inputs are not modifiable, existing
+    /// signatures would be invalidated, `tx_in` is not unsigned or, for a version 2 PSBT, the lock
+    /// time required by `input` is incompatible with the other inputs.
+    pub fn insert_input(&mut self, index: usize, tx_in: TxIn, input: Input) -> Result<(), Error> {
+        if index > self.inputs.len() {
+            return Err(Error::InsertionOutOfBounds { index, length: self.inputs.len() });
+        }
+        if self.version >= 2 && !self.tx_modifiable.map_or(false, |flags| flags.inputs) {
+            return Err(Error::InputsNotModifiable);
+        }
+        if !self.signatures_allow_insertion(index, true) {
+            return Err(Error::SignaturesNotModifiable);
+        }
+        if !tx_in.script_sig.is_empty() {
+            return Err(Error::UnsignedTxHasScriptSigs);
+        }
+        if !tx_in.witness.is_empty() {
+            return Err(Error::UnsignedTxHasScriptWitnesses);
+        }
+
+        self.unsigned_tx.input.insert(index, tx_in);
+        self.inputs.insert(index, input);
+        if self.version < 2 {
+            return Ok(());
+        }
+        match self.determine_lock_time() {
+            Some(lock_time) => {
+                self.unsigned_tx.lock_time = lock_time;
+                Ok(())
+            }
+            None => {
+                self.unsigned_tx.input.remove(index);
+                self.inputs.remove(index);
+                Err(Error::IncompatibleLockTimes)
+            }
+        }
+    }
+
+    /// Adds an output to a version 2 PSBT (the Constructor role of BIP-370).
+    ///
+    /// # Errors
+    ///
+    /// If this is not a version 2 PSBT or its outputs are not modifiable.
+    pub fn add_output(&mut self, tx_out: TxOut, output: Output) -> Result<(), Error> {
+        if self.version < 2 {
+            return Err(Error::Version("only PSBT v2 outputs are modifiable"));
+        }
+        self.insert_output(self.outputs.len(), tx_out, output)
+    }
+
+    /// Inserts an output at position `index`, keeping the unsigned transaction in sync.
+    ///
+    /// Outputs can only be added if all existing signatures use `SIGHASH_NONE`, or
+    /// `SIGHASH_SINGLE` for an input whose output is not moved. Version 2 PSBTs must also have
+    /// their outputs marked as modifiable.
+    ///
+    /// # Errors
+    ///
+    /// If `index` is greater than the number of outputs, the outputs are not modifiable or
+    /// existing signatures would be invalidated.
+    pub fn insert_output(
+        &mut self,
+        index: usize,
+        tx_out: TxOut,
+        output: Output,
+    ) -> Result<(), Error> {
+        if index > self.outputs.len() {
+            return Err(Error::InsertionOutOfBounds { index, length: self.outputs.len() });
+        }
+        if self.version >= 2 && !self.tx_modifiable.map_or(false, |flags| flags.outputs) {
+            return Err(Error::OutputsNotModifiable);
+        }
+        if !self.signatures_allow_insertion(index, false) {
+            return Err(Error::SignaturesNotModifiable);
+        }
+
+        self.unsigned_tx.output.insert(index, tx_out);
+        self.outputs.insert(index, output);
+        Ok(())
+    }
+
+    /// Checks whether the existing signatures stay valid when inserting an input, or an output if
+    /// `input` is false, at position `index`.
+    ///
+    /// The signatures of finalized inputs are not inspected, they are assumed to commit to all
+    /// inputs and outputs.
+    fn signatures_allow_insertion(&self, index: usize, input: bool) -> bool {
+        const ANYONECANPAY: u32 = 0x80;
+        const NONE: u32 = 0x02;
+        const SINGLE: u32 = 0x03;
+
+        self.inputs.iter().enumerate().all(|(input_index, psbt_input)| {
+            if psbt_input.final_script_sig.is_some() || psbt_input.final_script_witness.is_some() {
+                return false;
+            }
+            let ecdsa = psbt_input.partial_sigs.values().map(|sig| sig.sighash_type.into());
+            let taproot = psbt_input
+                .tap_key_sig
+                .iter()
+                .chain(psbt_input.tap_script_sigs.values())
+                .map(|sig| sig.sighash_type.into());
+            ecdsa.chain(taproot).all(|sighash_type: PsbtSighashType| {
+                let sighash_type = sighash_type.to_u32();
+                let base = sighash_type & 0x1f;
+                // An input signed with `SIGHASH_SINGLE` must keep its position and its output.
+                let single_kept = base == SINGLE && input_index < index;
+                if input {
+                    sighash_type & ANYONECANPAY != 0 && (base != SINGLE || single_kept)
+                } else {
+                    base == NONE || single_kept
+                }
+            })
+        })
+    }
+
+    /// Determines the lock time of a version 2 PSBT from its inputs as defined by BIP-370.
+    ///
+    /// Returns `None` if the inputs require both height and time based lock times.
+    pub(crate) fn determine_lock_time(&self) -> Option<absolute::LockTime> {
+        let mut constrained = self
+            .inputs
+            .iter()
+            .filter(|i| {
+                i.required_time_lock_time.is_some() || i.required_height_lock_time.is_some()
+            })
+            .peekable();
+        if constrained.peek().is_none() {
+            return Some(self.fallback_lock_time.unwrap_or(absolute::LockTime::ZERO));
+        }
+
+        // Height based lock times are preferred if all inputs support them.
+        let heights: Option<Vec<_>> =
+            constrained.clone().map(|i| i.required_height_lock_time).collect();
+        if let Some(heights) = heights {
+            return heights.into_iter().max().map(absolute::LockTime::Blocks);
+        }
+        let times: Option<Vec<_>> = constrained.map(|i| i.required_time_lock_time).collect();
+        times?.into_iter().max().map(absolute::LockTime::Seconds)
+    }
+
+    /// Clears the modifiable flags of a version 2 PSBT that a signature of `sighash_type`
+    /// commits to.
+    fn update_tx_modifiable(&mut self, sighash_type: PsbtSighashType) {
+        if self.version < 2 {
+            return;
+        }
+
+        // The taproot default sighash type (0x00) behaves like `SIGHASH_ALL`.
+        let sighash_type = sighash_type.to_u32();
+        let flags = self.tx_modifiable.get_or_insert_with(Default::default);
+        if sighash_type & 0x80 == 0 {
+            flags.inputs = false;
+        }
+        if sighash_type & 0x1f != 0x02 {
+            flags.outputs = false;
+        }
+        if sighash_type & 0x1f == 0x03 {
+            flags.has_sighash_single = true;
+        }
+    }
+}
+
+/// Creates a schnorr signature, using auxiliary randomness if available.
+fn sign_schnorr<C: Signing>(
+    secp: &Secp256k1<C>,
+    msg: &Message,
+    keypair: &Keypair,
+) -> secp256k1::schnorr::Signature {
+    #[cfg(feature = "rand-std")]
+    return secp.sign_schnorr(msg, keypair);
+    #[cfg(not(feature = "rand-std"))]
+    return secp.sign_schnorr_no_aux_rand(msg, keypair);
 }
 
 /// Data required to call [`GetKey`] to get the private key to sign an input.
@@ -564,6 +1062,8 @@ pub enum KeyRequest {
     Pubkey(PublicKey),
     /// Request a private key using BIP-32 fingerprint and derivation path.
     Bip32(KeySource),
+    /// Request a private key using the associated x-only public key.
+    XOnlyPubkey(XOnlyPublicKey),
 }
 
 /// Trait to get a private key from a key request, key is then used to sign an input.
@@ -593,7 +1093,7 @@ impl GetKey for Xpriv {
         secp: &Secp256k1<C>,
     ) -> Result<Option<PrivateKey>, Self::Error> {
         match key_request {
-            KeyRequest::Pubkey(_) => Err(GetKeyError::NotSupported),
+            KeyRequest::Pubkey(_) | KeyRequest::XOnlyPubkey(_) => Err(GetKeyError::NotSupported),
             KeyRequest::Bip32((fingerprint, path)) => {
                 let key = if self.fingerprint(secp) == fingerprint {
                     let k = self.derive_priv(secp, &path)?;
@@ -626,7 +1126,7 @@ impl GetKey for $set<Xpriv> {
         secp: &Secp256k1<C>
     ) -> Result<Option<PrivateKey>, Self::Error> {
         match key_request {
-            KeyRequest::Pubkey(_) => Err(GetKeyError::NotSupported),
+            KeyRequest::Pubkey(_) | KeyRequest::XOnlyPubkey(_) => Err(GetKeyError::NotSupported),
             KeyRequest::Bip32((fingerprint, path)) => {
                 for xpriv in self.iter() {
                     if xpriv.parent_fingerprint == fingerprint {
@@ -657,6 +1157,11 @@ impl GetKey for $map<PublicKey, PrivateKey> {
     ) -> Result<Option<PrivateKey>, Self::Error> {
         match key_request {
             KeyRequest::Pubkey(pk) => Ok(self.get(&pk).cloned()),
+            KeyRequest::XOnlyPubkey(xonly) => {
+                let even = PublicKey::new(xonly.public_key(Parity::Even));
+                let odd = PublicKey::new(xonly.public_key(Parity::Odd));
+                Ok(self.get(&even).or_else(|| self.get(&odd)).cloned())
+            }
             KeyRequest::Bip32(_) => Err(GetKeyError::NotSupported),
         }
     }
@@ -776,6 +1281,8 @@ pub enum SignError {
     SegwitV0Sighash(transaction::InputsIndexError),
     /// Sighash computation error (p2wpkh input).
     P2wpkhSighash(sighash::P2wpkhError),
+    /// Sighash computation error (taproot input).
+    TaprootSighash(sighash::TaprootError),
     /// Unable to determine the output type.
     UnknownOutputType,
     /// Unable to find key.
@@ -784,6 +1291,8 @@ pub enum SignError {
     WrongSigningAlgorithm,
     /// Signing request currently unsupported.
     Unsupported,
+    /// The taproot input has data for several spend paths, the one to sign for is ambiguous.
+    AmbiguousTaprootSpend,
 }
 
 internals::impl_from_infallible!(SignError);
@@ -804,11 +1313,14 @@ impl fmt::Display for SignError {
             NotWpkh => write!(f, "the scriptPubkey is not a P2WPKH script"),
             SegwitV0Sighash(ref e) => write_err!(f, "segwit v0 sighash"; e),
             P2wpkhSighash(ref e) => write_err!(f, "p2wpkh sighash"; e),
+            TaprootSighash(ref e) => write_err!(f, "taproot sighash"; e),
             UnknownOutputType => write!(f, "unable to determine the output type"),
             KeyNotFound => write!(f, "unable to find key"),
             WrongSigningAlgorithm =>
                 write!(f, "attempt to sign an input with the wrong signing algorithm"),
             Unsupported => write!(f, "signing request currently unsupported"),
+            AmbiguousTaprootSpend =>
+                write!(f, "taproot input has data for several spend paths, ambiguous spend path"),
         }
     }
 }
@@ -821,6 +1333,7 @@ impl std::error::Error for SignError {
         match *self {
             SegwitV0Sighash(ref e) => Some(e),
             P2wpkhSighash(ref e) => Some(e),
+            TaprootSighash(ref e) => Some(e),
             IndexOutOfBounds(ref e) => Some(e),
             InvalidSighashType
             | MissingInputUtxo
@@ -833,7 +1346,8 @@ impl std::error::Error for SignError {
             | UnknownOutputType
             | KeyNotFound
             | WrongSigningAlgorithm
-            | Unsupported => None,
+            | Unsupported
+            | AmbiguousTaprootSpend => None,
         }
     }
 }
@@ -901,6 +1415,79 @@ impl std::error::Error for ExtractTxError {
     }
 }
 
+/// Error combining two PSBTs, see [`Psbt::combine`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CombineError {
+    /// Attempting to combine with a PSBT describing a different unsigned transaction.
+    UnexpectedUnsignedTx {
+        /// The unsigned transaction of the PSBT being combined into.
+        expected: Box<Transaction>,
+        /// The unsigned transaction of the other PSBT.
+        actual: Box<Transaction>,
+    },
+    /// A global extended public key has inconsistent key sources.
+    InconsistentKeySources(Box<Xpub>),
+    /// The PSBTs set a global field to different values.
+    ConflictingGlobal {
+        /// Name of the conflicting field.
+        field: &'static str,
+    },
+    /// The PSBTs set a field of an input to different values.
+    ConflictingInput {
+        /// Index of the input.
+        index: usize,
+        /// Name of the conflicting field, as named on [`Input`].
+        field: &'static str,
+    },
+    /// The PSBTs set a field of an output to different values.
+    ConflictingOutput {
+        /// Index of the output.
+        index: usize,
+        /// Name of the conflicting field, as named on [`Output`].
+        field: &'static str,
+    },
+}
+
+internals::impl_from_infallible!(CombineError);
+
+impl fmt::Display for CombineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CombineError::*;
+
+        match *self {
+            UnexpectedUnsignedTx { ref expected, ref actual } => write!(
+                f,
+                "different unsigned transaction: expected {}, actual {}",
+                expected.compute_txid(),
+                actual.compute_txid()
+            ),
+            InconsistentKeySources(ref xpub) =>
+                write!(f, "inconsistent key sources for global xpub {}", xpub),
+            ConflictingGlobal { field } => write!(f, "conflicting global {}", field),
+            ConflictingInput { index, field } =>
+                write!(f, "conflicting {} for input {}", field, index),
+            ConflictingOutput { index, field } =>
+                write!(f, "conflicting {} for output {}", field, index),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CombineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use CombineError::*;
+
+        match *self {
+            UnexpectedUnsignedTx { .. }
+            | InconsistentKeySources(_)
+            | ConflictingGlobal { .. }
+            | ConflictingInput { .. }
+            | ConflictingOutput { .. } => None,
+        }
+    }
+}
+
 /// Input index out of bounds (actual index, maximum index allowed).
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -963,6 +1550,7 @@ mod display_from_str {
     use internals::write_err;
 
     use super::{Error, Psbt};
+    use crate::prelude::*;
 
     /// Error encountered during PSBT decoding from Base64 string.
     #[derive(Debug)]
@@ -999,6 +1587,17 @@ mod display_from_str {
         }
     }
 
+    impl Psbt {
+        /// Encodes this PSBT as a padded base64 string, the format used by Bitcoin Core RPCs.
+        pub fn to_base64(&self) -> String { BASE64_STANDARD.encode(self.serialize()) }
+
+        /// Decodes a PSBT from a padded base64 string.
+        pub fn from_base64(s: &str) -> Result<Psbt, PsbtParseError> {
+            let data = BASE64_STANDARD.decode(s).map_err(PsbtParseError::Base64Encoding)?;
+            Psbt::deserialize(&data).map_err(PsbtParseError::PsbtEncoding)
+        }
+    }
+
     impl Display for Psbt {
         fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
             write!(f, "{}", Base64Display::new(&self.serialize(), &BASE64_STANDARD))
@@ -1008,10 +1607,7 @@ mod display_from_str {
     impl FromStr for Psbt {
         type Err = PsbtParseError;
 
-        fn from_str(s: &str) -> Result<Self, Self::Err> {
-            let data = BASE64_STANDARD.decode(s).map_err(PsbtParseError::Base64Encoding)?;
-            Psbt::deserialize(&data).map_err(PsbtParseError::PsbtEncoding)
-        }
+        fn from_str(s: &str) -> Result<Self, Self::Err> { Psbt::from_base64(s) }
     }
 }
 #[cfg(feature = "base64")]
@@ -1025,7 +1621,7 @@ mod tests {
     use secp256k1::{All, SecretKey};
 
     use super::*;
-    use crate::bip32::ChildNumber;
+    use crate::bip32::{ChildNumber, DerivationPath, Fingerprint};
     use crate::blockdata::locktime::absolute;
     use crate::blockdata::script::ScriptBuf;
     use crate::blockdata::transaction::{self, OutPoint, Sequence, TxIn};
@@ -1069,6 +1665,8 @@ mod tests {
             },
             xpub: Default::default(),
             version: 0,
+            fallback_lock_time: None,
+            tx_modifiable: None,
             proprietary: BTreeMap::new(),
             unknown: BTreeMap::new(),
 
@@ -1097,6 +1695,8 @@ mod tests {
             },
             xpub: Default::default(),
             version: 0,
+            fallback_lock_time: None,
+            tx_modifiable: None,
             proprietary: BTreeMap::new(),
             unknown: BTreeMap::new(),
 
@@ -1238,6 +1838,8 @@ mod tests {
             },
             xpub: Default::default(),
             version: 0,
+            fallback_lock_time: None,
+            tx_modifiable: None,
             proprietary: Default::default(),
             unknown: Default::default(),
             inputs: vec![Input::default()],
@@ -1326,6 +1928,8 @@ mod tests {
 
         let psbt = Psbt {
             version: 0,
+            fallback_lock_time: None,
+            tx_modifiable: None,
             xpub: {
                 let xpub: Xpub =
                     "xpub661MyMwAqRbcGoRVtwfvzZsq2VBJR1LAHfQstHUoxqDorV89vRoMxUZ27kLrraAj6MPi\
@@ -1484,6 +2088,8 @@ mod tests {
                 },
                 xpub: Default::default(),
                 version: 0,
+                fallback_lock_time: None,
+                tx_modifiable: None,
                 proprietary: BTreeMap::new(),
                 unknown: BTreeMap::new(),
 
@@ -1545,6 +2151,14 @@ mod tests {
             let base16str = "70736274ff0100750200000001268171371edff285e937adeea4b37b78000c0566cbb3ad64641713ca42171bf60000000000feffffff02d3dff505000000001976a914d0c59903c5bac2868760e90fd521a4665aa7652088ac00e1f5050000000017a9143545e6e33b832c47050f24d3eeb93c9c03948bc787b32e1300000100fda5010100000000010289a3c71eab4d20e0371bbba4cc698fa295c9463afa2e397f8533ccb62f9567e50100000017160014be18d152a9b012039daf3da7de4f53349eecb985ffffffff86f8aa43a71dff1448893a530a7237ef6b4608bbb2dd2d0171e63aec6a4890b40100000017160014fe3e9ef1a745e974d902c4355943abcb34bd5353ffffffff0200c2eb0b000000001976a91485cff1097fd9e008bb34af709c62197b38978a4888ac72fef84e2c00000017a914339725ba21efd62ac753a9bcd067d6c7a6a39d05870247304402202712be22e0270f394f568311dc7ca9a68970b8025fdd3b240229f07f8a5f3a240220018b38d7dcd314e734c9276bd6fb40f673325bc4baa144c800d2f2f02db2765c012103d2e15674941bad4a996372cb87e1856d3652606d98562fe39c5e9e7e413f210502483045022100d12b852d85dcd961d2f5f4ab660654df6eedcc794c0c33ce5cc309ffb5fce58d022067338a8e0e1725c197fb1a88af59f51e44e4255b20167c8684031c05d1f2592a01210223b72beef0965d10be0778efecd61fcac6f79a4ea169393380734464f84f2ab300000000000000";
 
             assert_eq!(unserialized.serialize_hex(), base16str);
+
+            let mut buf = Vec::new();
+            let len = unserialized.serialize_to_writer(&mut buf).unwrap();
+            assert_eq!(len, buf.len());
+            buf.extend_from_slice(b"trailing");
+            let mut reader = buf.as_slice();
+            assert_eq!(Psbt::deserialize_from_reader(&mut reader).unwrap(), unserialized);
+            assert_eq!(reader, b"trailing");
             assert_eq!(unserialized, hex_psbt(base16str).unwrap());
 
             #[cfg(feature = "base64")]
@@ -1552,6 +2166,8 @@ mod tests {
                 let base64str = "cHNidP8BAHUCAAAAASaBcTce3/KF6Tet7qSze3gADAVmy7OtZGQXE8pCFxv2AAAAAAD+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQD9pQEBAAAAAAECiaPHHqtNIOA3G7ukzGmPopXJRjr6Ljl/hTPMti+VZ+UBAAAAFxYAFL4Y0VKpsBIDna89p95PUzSe7LmF/////4b4qkOnHf8USIk6UwpyN+9rRgi7st0tAXHmOuxqSJC0AQAAABcWABT+Pp7xp0XpdNkCxDVZQ6vLNL1TU/////8CAMLrCwAAAAAZdqkUhc/xCX/Z4Ai7NK9wnGIZeziXikiIrHL++E4sAAAAF6kUM5cluiHv1irHU6m80GfWx6ajnQWHAkcwRAIgJxK+IuAnDzlPVoMR3HyppolwuAJf3TskAinwf4pfOiQCIAGLONfc0xTnNMkna9b7QPZzMlvEuqFEyADS8vAtsnZcASED0uFWdJQbrUqZY3LLh+GFbTZSYG2YVi/jnF6efkE/IQUCSDBFAiEA0SuFLYXc2WHS9fSrZgZU327tzHlMDDPOXMMJ/7X85Y0CIGczio4OFyXBl/saiK9Z9R5E5CVbIBZ8hoQDHAXR8lkqASECI7cr7vCWXRC+B3jv7NYfysb3mk6haTkzgHNEZPhPKrMAAAAAAAAA";
                 assert_eq!(Psbt::from_str(base64str).unwrap(), unserialized);
                 assert_eq!(base64str, unserialized.to_string());
+                assert_eq!(Psbt::from_base64(base64str).unwrap(), unserialized);
+                assert_eq!(base64str, unserialized.to_base64());
                 assert_eq!(Psbt::from_str(base64str).unwrap(), hex_psbt(base16str).unwrap());
             }
         }
@@ -1815,6 +2431,8 @@ mod tests {
                 ],
             },
             version: 0,
+            fallback_lock_time: None,
+            tx_modifiable: None,
             xpub: Default::default(),
             proprietary: Default::default(),
             unknown: BTreeMap::new(),
@@ -1901,29 +2519,306 @@ mod tests {
         assert!(!rtt.proprietary.is_empty());
     }
 
+    #[test]
+    fn proprietary_with_prefix() {
+        let mut input = Input::default();
+        for (prefix, subtype) in [("a", 1), ("test", 2), ("test", 0), ("testing", 0), ("u", 0)] {
+            input.proprietary.insert(raw::ProprietaryKey::new(prefix, subtype, "key"), vec![]);
+        }
+        input.proprietary.insert(raw::ProprietaryKey::new("test", 0, ""), vec![]);
+
+        let keys = input
+            .proprietary_with_prefix(b"test")
+            .map(|(key, _)| (key.subtype, key.key.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec![(0, vec![]), (0, b"key".to_vec()), (2, b"key".to_vec())]);
+        assert_eq!(input.proprietary_with_prefix(b"none").count(), 0);
+    }
+
+    #[test]
+    fn trivial_psbt_v2() {
+        let psbt = Psbt::deserialize(&hex!("70736274ff01000a0200000000000000000000"))
+            .unwrap()
+            .into_v2()
+            .unwrap();
+        let want = "70736274ff01020402000000010401000105010001fb040200000000";
+        assert_eq!(psbt.serialize_hex(), want);
+        assert_eq!(hex_psbt(want).unwrap(), psbt);
+    }
+
+    #[test]
+    fn psbt_v2_round_trip() {
+        let mut psbt = psbt_with_values(10_000, 9_000);
+        psbt.unsigned_tx.lock_time = absolute::LockTime::from_consensus(800_000);
+        psbt.outputs.push(Output::default());
+
+        let v2 = psbt.clone().into_v2().unwrap();
+        assert_eq!(v2.fallback_lock_time, Some(psbt.unsigned_tx.lock_time));
+        let decoded = hex_psbt(&v2.serialize_hex()).unwrap();
+        assert_eq!(decoded, v2);
+        assert_eq!(decoded.into_v0(), psbt);
+
+        let mut v1 = psbt.clone();
+        v1.unsigned_tx.version = transaction::Version::ONE;
+        assert!(matches!(v1.into_v2(), Err(Error::Version(_))));
+    }
+
+    #[test]
+    fn psbt_v2_invalid_globals() {
+        // Version 0 with an input count.
+        assert!(matches!(
+            hex_psbt("70736274ff01000a020000000000000000000104010000"),
+            Err(Error::Version(_))
+        ));
+        // Version 2 with an unsigned transaction.
+        assert!(matches!(
+            hex_psbt("70736274ff01000a0200000000000000000001fb040200000000"),
+            Err(Error::Version(_))
+        ));
+        // Version 2 without an output count.
+        assert!(matches!(
+            hex_psbt("70736274ff010204020000000104010001fb040200000000"),
+            Err(Error::Version(_))
+        ));
+        // Version 1 is not defined.
+        assert!(matches!(
+            hex_psbt("70736274ff01000a0200000000000000000001fb040100000000"),
+            Err(Error::Version(_))
+        ));
+    }
+
+    // These cases follow the descriptions of BIP 370's valid, invalid and lock time determination
+    // test vectors but were serialized for this crate, they are not the BIP's own vectors.
+    #[test]
+    fn psbt_v2_vectors() {
+        let vectors: serde_json::Value =
+            serde_json::from_str(include_str!("../../tests/data/psbt_v2_vectors.json")).unwrap();
+        for case in vectors["invalid"].as_array().unwrap() {
+            let comment = case["comment"].as_str().unwrap();
+            assert!(hex_psbt(case["psbt"].as_str().unwrap()).is_err(), "{}", comment);
+        }
+        for case in vectors["valid"].as_array().unwrap() {
+            let comment = case["comment"].as_str().unwrap();
+            let hex = case["psbt"].as_str().unwrap();
+            let psbt = hex_psbt(hex).unwrap_or_else(|e| panic!("{}: {}", comment, e));
+            assert_eq!(psbt.serialize_hex(), hex, "{}", comment);
+        }
+        for case in vectors["lock_time"].as_array().unwrap() {
+            let comment = case["comment"].as_str().unwrap();
+            let psbt = hex_psbt(case["psbt"].as_str().unwrap()).unwrap();
+            let lock_time = case["lock_time"].as_u64().unwrap() as u32;
+            assert_eq!(psbt.unsigned_tx.lock_time.to_consensus_u32(), lock_time, "{}", comment);
+        }
+    }
+
+    #[test]
+    fn psbt_v2_constructor() {
+        let tx_in = psbt_with_values(0, 0).unsigned_tx.input[0].clone();
+        let tx_out = TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new() };
+
+        let mut psbt = psbt_with_values(0, 0).into_v2().unwrap();
+        psbt.outputs.push(Output::default());
+        assert!(matches!(
+            psbt.add_input(tx_in.clone(), Input::default()),
+            Err(Error::InputsNotModifiable)
+        ));
+        assert!(matches!(
+            psbt.add_output(tx_out.clone(), Output::default()),
+            Err(Error::OutputsNotModifiable)
+        ));
+
+        psbt.tx_modifiable =
+            Some(TxModifiable { inputs: true, outputs: true, ..Default::default() });
+        psbt.fallback_lock_time = Some(absolute::LockTime::from_consensus(10));
+        psbt.add_output(tx_out, Output::default()).unwrap();
+        assert_eq!(psbt.unsigned_tx.output.len(), 2);
+
+        let height = |n| absolute::Height::from_consensus(n).unwrap();
+        let time = |n| absolute::Time::from_consensus(n).unwrap();
+        let input = Input { required_height_lock_time: Some(height(100)), ..Default::default() };
+        psbt.add_input(tx_in.clone(), input).unwrap();
+        assert_eq!(psbt.unsigned_tx.lock_time, absolute::LockTime::from_consensus(100));
+
+        let input = Input {
+            required_height_lock_time: Some(height(200)),
+            required_time_lock_time: Some(time(1_700_000_000)),
+            ..Default::default()
+        };
+        psbt.add_input(tx_in.clone(), input).unwrap();
+        assert_eq!(psbt.unsigned_tx.lock_time, absolute::LockTime::from_consensus(200));
+
+        let input =
+            Input { required_time_lock_time: Some(time(1_700_000_000)), ..Default::default() };
+        assert!(matches!(psbt.add_input(tx_in, input), Err(Error::IncompatibleLockTimes)));
+        assert_eq!(psbt.inputs.len(), 3);
+        assert_eq!(psbt.unsigned_tx.input.len(), 3);
+
+        let decoded = hex_psbt(&psbt.serialize_hex()).unwrap();
+        assert_eq!(decoded, psbt);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_psbt_v2() {
+        let mut psbt = psbt_with_values(0, 0).into_v2().unwrap();
+        psbt.tx_modifiable = Some(TxModifiable { inputs: true, ..Default::default() });
+        psbt.inputs[0].required_height_lock_time = absolute::Height::from_consensus(100).ok();
+        psbt.inputs[0].required_time_lock_time = absolute::Time::from_consensus(1_700_000_000).ok();
+
+        let json = serde_json::to_string(&psbt).unwrap();
+        assert_eq!(serde_json::from_str::<Psbt>(&json).unwrap(), psbt);
+        let bincode = bincode::serialize(&psbt).unwrap();
+        assert_eq!(bincode::deserialize::<Psbt>(&bincode).unwrap(), psbt);
+    }
+
+    #[test]
+    fn psbt_v2_signer_updates_modifiable_flags() {
+        let mut psbt = psbt_with_values(0, 0).into_v2().unwrap();
+        psbt.tx_modifiable =
+            Some(TxModifiable { inputs: true, outputs: true, ..Default::default() });
+
+        psbt.update_tx_modifiable(EcdsaSighashType::NonePlusAnyoneCanPay.into());
+        assert_eq!(
+            psbt.tx_modifiable,
+            Some(TxModifiable { inputs: true, outputs: true, has_sighash_single: false })
+        );
+        psbt.update_tx_modifiable(EcdsaSighashType::SinglePlusAnyoneCanPay.into());
+        assert_eq!(
+            psbt.tx_modifiable,
+            Some(TxModifiable { inputs: true, outputs: false, has_sighash_single: true })
+        );
+        psbt.update_tx_modifiable(TapSighashType::Default.into());
+        assert_eq!(
+            psbt.tx_modifiable,
+            Some(TxModifiable { inputs: false, outputs: false, has_sighash_single: true })
+        );
+        assert_eq!(psbt.tx_modifiable.unwrap().to_u8(), 0x04);
+    }
+
+    #[test]
+    fn insert_inputs_and_outputs() {
+        let signed = |sighash_type| {
+            let mut psbt = psbt_with_values(10_000, 9_000);
+            psbt.outputs.push(Output::default());
+            let signature = secp256k1::ecdsa::Signature::from_compact(&[1; 64]).unwrap();
+            let key = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+            psbt.inputs[0]
+                .partial_sigs
+                .insert(key.parse().unwrap(), ecdsa::Signature { signature, sighash_type });
+            psbt
+        };
+        let tx_in = TxIn::default();
+        let tx_out = TxOut::NULL;
+
+        let mut psbt = psbt_with_values(10_000, 9_000);
+        psbt.outputs.push(Output::default());
+        psbt.insert_input(0, tx_in.clone(), Input::default()).unwrap();
+        psbt.insert_output(1, tx_out.clone(), Output::default()).unwrap();
+        assert_eq!(psbt.unsigned_tx.input[0], tx_in);
+        assert_eq!(psbt.unsigned_tx.output[1], tx_out);
+        assert_eq!(psbt.inputs.len(), 2);
+        assert_eq!(psbt.outputs.len(), 2);
+        assert!(matches!(
+            psbt.insert_input(3, tx_in.clone(), Input::default()),
+            Err(Error::InsertionOutOfBounds { index: 3, length: 2 })
+        ));
+        assert!(matches!(
+            psbt.insert_output(3, tx_out.clone(), Output::default()),
+            Err(Error::InsertionOutOfBounds { index: 3, length: 2 })
+        ));
+
+        let mut psbt = signed(EcdsaSighashType::All);
+        assert!(matches!(
+            psbt.insert_input(1, tx_in.clone(), Input::default()),
+            Err(Error::SignaturesNotModifiable)
+        ));
+        assert!(matches!(
+            psbt.insert_output(1, tx_out.clone(), Output::default()),
+            Err(Error::SignaturesNotModifiable)
+        ));
+
+        let mut psbt = signed(EcdsaSighashType::NonePlusAnyoneCanPay);
+        psbt.insert_input(0, tx_in.clone(), Input::default()).unwrap();
+        psbt.insert_output(0, tx_out.clone(), Output::default()).unwrap();
+
+        // The input signed with `SIGHASH_SINGLE` must stay paired with its output.
+        let mut psbt = signed(EcdsaSighashType::SinglePlusAnyoneCanPay);
+        assert!(matches!(
+            psbt.insert_input(0, tx_in.clone(), Input::default()),
+            Err(Error::SignaturesNotModifiable)
+        ));
+        assert!(matches!(
+            psbt.insert_output(0, tx_out.clone(), Output::default()),
+            Err(Error::SignaturesNotModifiable)
+        ));
+        psbt.insert_input(1, tx_in.clone(), Input::default()).unwrap();
+        psbt.insert_output(1, tx_out, Output::default()).unwrap();
+
+        let mut psbt = signed(EcdsaSighashType::NonePlusAnyoneCanPay);
+        psbt.inputs[0].final_script_witness = Some(Witness::default());
+        assert!(matches!(
+            psbt.insert_input(1, tx_in, Input::default()),
+            Err(Error::SignaturesNotModifiable)
+        ));
+    }
+
     // PSBTs taken from BIP 174 test vectors.
     #[test]
     fn combine_psbts() {
-        let mut psbt1 = hex_psbt(include_str!("../../tests/data/psbt1.hex")).unwrap();
+        let psbt1 = hex_psbt(include_str!("../../tests/data/psbt1.hex")).unwrap();
         let psbt2 = hex_psbt(include_str!("../../tests/data/psbt2.hex")).unwrap();
         let psbt_combined = hex_psbt(include_str!("../../tests/data/psbt2.hex")).unwrap();
 
-        psbt1.combine(psbt2).expect("psbt combine to succeed");
+        let psbt1 = psbt1.combine(psbt2).expect("psbt combine to succeed");
         assert_eq!(psbt1, psbt_combined);
     }
 
     #[test]
     fn combine_psbts_commutative() {
-        let mut psbt1 = hex_psbt(include_str!("../../tests/data/psbt1.hex")).unwrap();
-        let mut psbt2 = hex_psbt(include_str!("../../tests/data/psbt2.hex")).unwrap();
+        let psbt1 = hex_psbt(include_str!("../../tests/data/psbt1.hex")).unwrap();
+        let psbt2 = hex_psbt(include_str!("../../tests/data/psbt2.hex")).unwrap();
 
-        let psbt1_clone = psbt1.clone();
-        let psbt2_clone = psbt2.clone();
+        let combined1 = psbt1.clone().combine(psbt2.clone()).expect("psbt1 combine to succeed");
+        let combined2 = psbt2.combine(psbt1).expect("psbt2 combine to succeed");
 
-        psbt1.combine(psbt2_clone).expect("psbt1 combine to succeed");
-        psbt2.combine(psbt1_clone).expect("psbt2 combine to succeed");
+        assert_eq!(combined1, combined2);
+    }
 
-        assert_eq!(psbt1, psbt2);
+    #[test]
+    fn combine_psbts_conflicts() {
+        let psbt = hex_psbt(include_str!("../../tests/data/psbt2.hex")).unwrap();
+
+        let mut other = psbt.clone();
+        other.unsigned_tx.lock_time = absolute::LockTime::from_consensus(1);
+        assert!(matches!(
+            psbt.clone().combine(other),
+            Err(CombineError::UnexpectedUnsignedTx { .. })
+        ));
+
+        let mut other = psbt.clone();
+        other.inputs[1].witness_script = Some(ScriptBuf::new());
+        assert_eq!(
+            psbt.clone().combine(other),
+            Err(CombineError::ConflictingInput { index: 1, field: "witness_script" })
+        );
+
+        let mut psbt = psbt;
+        let key = *psbt.inputs[1].bip32_derivation.keys().next().unwrap();
+        let path = "0".parse::<DerivationPath>().unwrap();
+        psbt.outputs[0].bip32_derivation.insert(key, (Fingerprint::default(), path));
+        let mut other = psbt.clone();
+        let path = "1".parse::<DerivationPath>().unwrap();
+        other.outputs[0].bip32_derivation.insert(key, (Fingerprint::default(), path));
+        assert_eq!(
+            psbt.clone().combine(other),
+            Err(CombineError::ConflictingOutput { index: 0, field: "bip32_derivation" })
+        );
+
+        // Fields missing from one side are taken from the other.
+        let mut other = psbt.clone();
+        other.inputs[1].witness_script = None;
+        other.outputs[0].bip32_derivation.clear();
+        assert_eq!(other.combine(psbt.clone()), Ok(psbt));
     }
 
     #[cfg(feature = "rand-std")]
@@ -1984,6 +2879,8 @@ mod tests {
             },
             xpub: Default::default(),
             version: 0,
+            fallback_lock_time: None,
+            tx_modifiable: None,
             proprietary: BTreeMap::new(),
             unknown: BTreeMap::new(),
 
@@ -2037,6 +2934,26 @@ mod tests {
             t.fee().expect("fee calculation"),
             prev_output_val - (output_0_val + output_1_val)
         );
+        let unsigned_weight = t.unsigned_tx.weight();
+        let fee_rate = t.fee_rate().expect("fee rate calculation");
+        assert_eq!(fee_rate, t.fee().unwrap() / unsigned_weight);
+        // final scripts count towards the weight
+        let mut finalized = t.clone();
+        finalized.inputs[0].final_script_sig = Some(ScriptBuf::from_bytes(vec![0; 100]));
+        assert!(finalized.fee_rate().unwrap() < fee_rate);
+        assert_eq!(
+            finalized.extracted_weight(),
+            finalized.clone().extract_tx_unchecked_fee_rate().weight()
+        );
+        finalized.inputs[0].final_script_witness = Some(Witness::from_slice(&[vec![1; 72]]));
+        assert_eq!(
+            finalized.extracted_weight(),
+            finalized.clone().extract_tx_unchecked_fee_rate().weight()
+        );
+        assert!(matches!(
+            finalized.extract_tx_with_fee_rate_limit(FeeRate::from_sat_per_kwu(1)),
+            Err(ExtractTxError::AbsurdFeeRate { .. })
+        ));
         // no previous output
         let mut t2 = t.clone();
         t2.inputs[0].non_witness_utxo = None;
@@ -2063,7 +2980,6 @@ mod tests {
     #[test]
     #[cfg(feature = "rand-std")]
     fn sign_psbt() {
-        use crate::bip32::{DerivationPath, Fingerprint};
         use crate::witness_version::WitnessVersion;
         use crate::{WPubkeyHash, WitnessProgram};
 
@@ -2106,4 +3022,143 @@ mod tests {
         assert!(sigs.len() == 1);
         assert!(sigs[&0] == vec![pk]);
     }
+
+    #[test]
+    fn sign_psbt_taproot() {
+        use crate::opcodes::all::OP_CHECKSIG;
+        use crate::taproot::{LeafVersion, TaprootBuilder};
+
+        let secp = Secp256k1::new();
+        let internal_sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let leaf_sk = secp256k1::SecretKey::from_slice(&[2; 32]).unwrap();
+        let (internal_key, _) = internal_sk.x_only_public_key(&secp);
+        let (leaf_key, _) = leaf_sk.x_only_public_key(&secp);
+
+        let leaf =
+            ScriptBuf::builder().push_x_only_key(&leaf_key).push_opcode(OP_CHECKSIG).into_script();
+        let leaf_hash = TapLeafHash::from_script(&leaf, LeafVersion::TapScript);
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(0, leaf.clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+        let output_key = spend_info.output_key().to_inner();
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        let input = &mut psbt.inputs[0];
+        input.witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        });
+        input.tap_internal_key = Some(internal_key);
+        input.tap_merkle_root = spend_info.merkle_root();
+        input.tap_key_origins.insert(internal_key, (vec![], Default::default()));
+        input.tap_key_origins.insert(leaf_key, (vec![leaf_hash], Default::default()));
+
+        // Keys are looked up by their x-only public key.
+        let mut key_map = BTreeMap::new();
+        for sk in [internal_sk, leaf_sk] {
+            let sk = PrivateKey::new(sk, NetworkKind::Test);
+            key_map.insert(sk.public_key(&secp), sk);
+        }
+        // A signing only context is enough, also for the key path.
+        let sigs = psbt.sign(&key_map, &Secp256k1::signing_only()).unwrap();
+        assert_eq!(sigs[&0].len(), 2);
+
+        let tx = psbt.unsigned_tx.clone();
+        let mut cache = SighashCache::new(&tx);
+        let input = &psbt.inputs[0];
+
+        let (msg, _) = psbt.sighash_taproot(0, &mut cache, None).unwrap();
+        let key_sig = input.tap_key_sig.unwrap();
+        secp.verify_schnorr(&key_sig.signature, &msg, &output_key).unwrap();
+
+        let (msg, _) = psbt.sighash_taproot(0, &mut cache, Some(leaf_hash)).unwrap();
+        let leaf_sig = input.tap_script_sigs[&(leaf_key, leaf_hash)];
+        secp.verify_schnorr(&leaf_sig.signature, &msg, &leaf_key).unwrap();
+
+        // Signing again does not replace existing signatures.
+        let signed = psbt.clone();
+        let sigs = psbt.sign(&key_map, &secp).unwrap();
+        assert!(sigs[&0].is_empty());
+        assert_eq!(psbt, signed);
+    }
+
+    #[test]
+    fn sighash_from_psbt_input() {
+        use crate::opcodes::all::OP_CHECKSIG;
+        use crate::taproot::{LeafVersion, TaprootBuilder};
+        use crate::WPubkeyHash;
+
+        let secp = Secp256k1::new();
+        let sk = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let pk = PublicKey::new(sk.public_key(&secp));
+        let (internal_key, _) = pk.inner.x_only_public_key();
+        let leaf = |n| ScriptBuf::builder().push_int(n).push_opcode(OP_CHECKSIG).into_script();
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, leaf(1))
+            .unwrap()
+            .add_leaf(1, leaf(2))
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+
+        let unsigned_tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn::default(), TxIn::default()],
+            output: vec![TxOut::NULL],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: ScriptBuf::new_p2wpkh(&WPubkeyHash::hash(&pk.to_bytes())),
+        });
+        psbt.inputs[0].sighash_type = Some(EcdsaSighashType::Single.into());
+        psbt.inputs[1].witness_utxo = Some(TxOut {
+            value: Amount::from_sat(20_000),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        });
+
+        let tx = psbt.unsigned_tx.clone();
+        let mut cache = SighashCache::new(&tx);
+        let (msg, ty) = psbt.sighash_ecdsa(0, &mut cache).unwrap();
+        assert_eq!(sighash::from_psbt_input(&psbt, 0), Ok((msg, ty.into())));
+
+        // Without leaf scripts the input is signed for the key path.
+        let key_path = psbt.sighash_taproot(1, &mut cache, None).unwrap();
+        let key_path = Ok((key_path.0, key_path.1.into()));
+        assert_eq!(sighash::from_psbt_input(&psbt, 1), key_path);
+        psbt.inputs[1].tap_internal_key = Some(internal_key);
+        assert_eq!(sighash::from_psbt_input(&psbt, 1), key_path);
+
+        // A single leaf script is signed for unless the input also has key path data.
+        let leaf_script = (leaf(2), LeafVersion::TapScript);
+        let control_block = spend_info.control_block(&leaf_script).unwrap();
+        psbt.inputs[1].tap_scripts.insert(control_block, leaf_script);
+        assert_eq!(sighash::from_psbt_input(&psbt, 1), Err(SignError::AmbiguousTaprootSpend));
+        psbt.inputs[1].tap_internal_key = None;
+        let leaf_hash = TapLeafHash::from_script(&leaf(2), LeafVersion::TapScript);
+        let (msg, ty) = psbt.sighash_taproot(1, &mut cache, Some(leaf_hash)).unwrap();
+        assert_eq!(sighash::from_psbt_input(&psbt, 1), Ok((msg, ty.into())));
+
+        // Script signatures name their leaf too.
+        let signature = taproot::Signature {
+            signature: secp256k1::schnorr::Signature::from_slice(&[1; 64]).unwrap(),
+            sighash_type: TapSighashType::Default,
+        };
+        psbt.inputs[1].tap_script_sigs.insert((internal_key, leaf_hash), signature);
+        assert_eq!(sighash::from_psbt_input(&psbt, 1), Ok((msg, ty.into())));
+        let other_leaf = TapLeafHash::from_script(&leaf(1), LeafVersion::TapScript);
+        psbt.inputs[1].tap_script_sigs.insert((internal_key, other_leaf), signature);
+        assert_eq!(sighash::from_psbt_input(&psbt, 1), Err(SignError::AmbiguousTaprootSpend));
+
+        assert!(matches!(sighash::from_psbt_input(&psbt, 2), Err(SignError::IndexOutOfBounds(_))));
+    }
 }
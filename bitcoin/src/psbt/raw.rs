@@ -176,10 +176,26 @@ impl<Subtype> ProprietaryKey<Subtype>
 where
     Subtype: Copy + From<u8> + Into<u8>,
 {
+    /// Constructs a proprietary key from the `prefix` identifying the application, its
+    /// `subtype` and the additional `key` data.
+    pub fn new(prefix: impl Into<Vec<u8>>, subtype: Subtype, key: impl Into<Vec<u8>>) -> Self {
+        ProprietaryKey { prefix: prefix.into(), subtype, key: key.into() }
+    }
+
     /// Constructs full [Key] corresponding to this proprietary key type
     pub fn to_key(&self) -> Key { Key { type_value: 0xFC, key: serialize(self) } }
 }
 
+/// Returns the pairs of a map of proprietary keys whose keys have the identifier `prefix`.
+pub(crate) fn proprietary_with_prefix<'a, V>(
+    map: &'a BTreeMap<ProprietaryKey, V>,
+    prefix: &'a [u8],
+) -> impl Iterator<Item = (&'a ProprietaryKey, &'a V)> {
+    // Keys are ordered by prefix first, subtype 0 and an empty key sort first within a prefix.
+    let start = ProprietaryKey::new(prefix, 0, vec![]);
+    map.range(start..).take_while(move |(key, _)| key.prefix == prefix)
+}
+
 impl<Subtype> TryFrom<Key> for ProprietaryKey<Subtype>
 where
     Subtype: Copy + From<u8> + Into<u8>,
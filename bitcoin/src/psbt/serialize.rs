@@ -6,11 +6,15 @@
 //! according to the BIP-174 specification.
 //!
 
+use core::cmp;
+
 use hashes::{hash160, ripemd160, sha256, sha256d, Hash};
+use io::{BufRead, Write};
 use secp256k1::XOnlyPublicKey;
 
 use super::map::{Input, Map, Output, PsbtSighashType};
 use crate::bip32::{ChildNumber, Fingerprint, KeySource};
+use crate::blockdata::locktime::absolute;
 use crate::blockdata::script::ScriptBuf;
 use crate::blockdata::transaction::{Transaction, TxOut};
 use crate::blockdata::witness::Witness;
@@ -23,6 +27,10 @@ use crate::taproot::{
     ControlBlock, LeafVersion, TapLeafHash, TapNodeHash, TapTree, TaprootBuilder,
 };
 use crate::VarInt;
+
+/// Maximum number of input or output maps to allocate for before decoding them.
+const MAX_PREALLOC: usize = 1024;
+
 /// A trait for serializing a value as raw data for insertion into PSBT
 /// key-value maps.
 pub(crate) trait Serialize {
@@ -51,60 +59,99 @@ impl Psbt {
 
         buf.extend(self.serialize_map());
 
-        for i in &self.inputs {
-            buf.extend(i.serialize_map());
-        }
+        if self.version >= 2 {
+            for (tx_in, input) in self.unsigned_tx.input.iter().zip(&self.inputs) {
+                buf.extend(input.serialize_map_v2(tx_in));
+            }
 
-        for i in &self.outputs {
-            buf.extend(i.serialize_map());
+            for (tx_out, output) in self.unsigned_tx.output.iter().zip(&self.outputs) {
+                buf.extend(output.serialize_map_v2(tx_out));
+            }
+        } else {
+            for i in &self.inputs {
+                buf.extend(i.serialize_map());
+            }
+
+            for i in &self.outputs {
+                buf.extend(i.serialize_map());
+            }
         }
 
         buf
     }
 
+    /// Serialize as raw binary data, including the magic bytes, to a writer.
+    ///
+    /// Returns the number of bytes written.
+    pub fn serialize_to_writer<W: Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let buf = self.serialize();
+        w.write_all(&buf)?;
+        Ok(buf.len())
+    }
+
     /// Deserialize a value from raw binary data.
     pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let mut d = bytes;
+        Self::deserialize_from_reader(&mut d)
+    }
+
+    /// Deserialize a value from raw binary data, starting with the magic bytes, read from a reader.
+    ///
+    /// Reads exactly up to the end of the last output map, leaving any trailing data unread.
+    pub fn deserialize_from_reader<R: BufRead + ?Sized>(r: &mut R) -> Result<Self, Error> {
         const MAGIC_BYTES: &[u8] = b"psbt";
-        if bytes.get(0..MAGIC_BYTES.len()) != Some(MAGIC_BYTES) {
+        let mut magic = [0u8; 4];
+        if r.read_exact(&mut magic).is_err() || magic != MAGIC_BYTES {
             return Err(Error::InvalidMagic);
         }
 
         const PSBT_SERPARATOR: u8 = 0xff_u8;
-        if bytes.get(MAGIC_BYTES.len()) != Some(&PSBT_SERPARATOR) {
+        let mut separator = [0u8; 1];
+        if r.read_exact(&mut separator).is_err() || separator[0] != PSBT_SERPARATOR {
             return Err(Error::InvalidSeparator);
         }
 
-        let mut d = bytes.get(5..).ok_or(Error::NoMorePairs)?;
-
-        let mut global = Psbt::decode_global(&mut d)?;
+        let (mut global, inputs_len, outputs_len) = Psbt::decode_global(r)?;
         global.unsigned_tx_checks()?;
 
-        let inputs: Vec<Input> = {
-            let inputs_len: usize = (global.unsigned_tx.input).len();
-
-            let mut inputs: Vec<Input> = Vec::with_capacity(inputs_len);
+        let mut inputs: Vec<Input> = {
+            // The counts are not authenticated, bound the initial allocation.
+            let mut inputs: Vec<Input> = Vec::with_capacity(cmp::min(inputs_len, MAX_PREALLOC));
 
             for _ in 0..inputs_len {
-                inputs.push(Input::decode(&mut d)?);
+                inputs.push(Input::decode(r)?);
             }
 
             inputs
         };
 
-        let outputs: Vec<Output> = {
-            let outputs_len: usize = (global.unsigned_tx.output).len();
-
-            let mut outputs: Vec<Output> = Vec::with_capacity(outputs_len);
+        let mut outputs: Vec<Output> = {
+            let mut outputs: Vec<Output> = Vec::with_capacity(cmp::min(outputs_len, MAX_PREALLOC));
 
             for _ in 0..outputs_len {
-                outputs.push(Output::decode(&mut d)?);
+                outputs.push(Output::decode(r)?);
             }
 
             outputs
         };
 
+        if global.version >= 2 {
+            global.unsigned_tx.input =
+                inputs.iter_mut().map(Input::take_tx_in).collect::<Result<_, _>>()?;
+            global.unsigned_tx.output =
+                outputs.iter_mut().map(Output::take_tx_out).collect::<Result<_, _>>()?;
+        } else {
+            inputs.iter().try_for_each(Input::check_v0)?;
+            outputs.iter().try_for_each(Output::check_v0)?;
+        }
+
         global.inputs = inputs;
         global.outputs = outputs;
+
+        if global.version >= 2 {
+            global.unsigned_tx.lock_time =
+                global.determine_lock_time().ok_or(Error::IncompatibleLockTimes)?;
+        }
         Ok(global)
     }
 }
@@ -236,6 +283,28 @@ impl Deserialize for PsbtSighashType {
     }
 }
 
+impl Serialize for absolute::Height {
+    fn serialize(&self) -> Vec<u8> { serialize(&self.to_consensus_u32()) }
+}
+
+impl Deserialize for absolute::Height {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let raw: u32 = encode::deserialize(bytes)?;
+        absolute::Height::from_consensus(raw).map_err(Error::InvalidLockTime)
+    }
+}
+
+impl Serialize for absolute::Time {
+    fn serialize(&self) -> Vec<u8> { serialize(&self.to_consensus_u32()) }
+}
+
+impl Deserialize for absolute::Time {
+    fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
+        let raw: u32 = encode::deserialize(bytes)?;
+        absolute::Time::from_consensus(raw).map_err(Error::InvalidLockTime)
+    }
+}
+
 // Taproot related ser/deser
 impl Serialize for XOnlyPublicKey {
     fn serialize(&self) -> Vec<u8> { XOnlyPublicKey::serialize(self).to_vec() }
@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! Fixtures shared by the PSBT role tests.
+
+use secp256k1::{All, Secp256k1, SecretKey};
+
+use crate::blockdata::locktime::absolute;
+use crate::blockdata::opcodes::all::OP_CHECKMULTISIG;
+use crate::blockdata::script::{Builder, ScriptBuf};
+use crate::blockdata::transaction::{self, OutPoint, Transaction, TxIn, TxOut};
+use crate::crypto::key::PublicKey;
+use crate::psbt::Psbt;
+use crate::Amount;
+
+/// Returns a transaction with a single 10 000 sat output paying to `script_pubkey`.
+pub(crate) fn prev_tx(script_pubkey: ScriptBuf) -> Transaction {
+    Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn::default()],
+        output: vec![TxOut { value: Amount::from_sat(10_000), script_pubkey }],
+    }
+}
+
+/// Returns a PSBT with a single input spending the first output of `prev_tx`.
+pub(crate) fn psbt_spending(prev_tx: &Transaction) -> Psbt {
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(prev_tx.compute_txid(), 0),
+            ..Default::default()
+        }],
+        output: vec![],
+    };
+    Psbt::from_unsigned_tx(tx).unwrap()
+}
+
+/// Returns a PSBT spending an output paying to `script_pubkey`, with its witness UTXO set.
+pub(crate) fn psbt_with_witness_utxo(script_pubkey: ScriptBuf) -> Psbt {
+    let prev = prev_tx(script_pubkey);
+    let mut psbt = psbt_spending(&prev);
+    psbt.inputs[0].witness_utxo = Some(prev.output[0].clone());
+    psbt
+}
+
+/// Returns `n` key pairs, the secret keys are `[1; 32]`, `[2; 32]` and so on.
+pub(crate) fn keys(secp: &Secp256k1<All>, n: u8) -> Vec<(SecretKey, PublicKey)> {
+    (1..=n)
+        .map(|i| {
+            let sk = SecretKey::from_slice(&[i; 32]).unwrap();
+            (sk, PublicKey::new(sk.public_key(secp)))
+        })
+        .collect()
+}
+
+/// Returns a bare 2-of-n `CHECKMULTISIG` script over `pks`.
+pub(crate) fn multisig(pks: &[PublicKey]) -> ScriptBuf {
+    pks.iter()
+        .fold(Builder::new().push_int(2), |builder, pk| builder.push_key(pk))
+        .push_int(pks.len() as i64)
+        .push_opcode(OP_CHECKMULTISIG)
+        .into_script()
+}
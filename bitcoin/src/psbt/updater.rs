@@ -0,0 +1,382 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! PSBT Input Updater role.
+//!
+//! Adds the information required to sign and finalize inputs, checking it against what the
+//! input spends as described by BIP 174.
+//!
+
+use core::fmt;
+
+use internals::write_err;
+use secp256k1::{Secp256k1, Verification};
+
+use crate::bip32::KeySource;
+use crate::blockdata::script::ScriptBuf;
+use crate::blockdata::transaction::{Transaction, TxOut, Txid};
+use crate::descriptor::Descriptor;
+use crate::psbt::{IndexOutOfBoundsError, Psbt, SigningAlgorithm};
+
+impl Psbt {
+    /// Sets the transaction containing the output spent by the input at `input_index` as its
+    /// `non_witness_utxo`.
+    ///
+    /// # Errors
+    ///
+    /// If `prev_tx` is not the transaction spent by the input, or does not match the
+    /// `witness_utxo` already set.
+    pub fn update_input_with_prev_tx(
+        &mut self,
+        input_index: usize,
+        prev_tx: Transaction,
+    ) -> Result<(), UpdateError> {
+        self.check_index_is_within_bounds(input_index)?;
+        let previous_output = self.unsigned_tx.input[input_index].previous_output;
+
+        let txid = prev_tx.compute_txid();
+        if txid != previous_output.txid {
+            return Err(UpdateError::PrevTxidMismatch { expected: previous_output.txid, txid });
+        }
+        let spent = prev_tx
+            .output
+            .get(previous_output.vout as usize)
+            .ok_or(UpdateError::MissingPrevOutput)?;
+
+        let input = &mut self.inputs[input_index];
+        if input.witness_utxo.as_ref().map_or(false, |utxo| utxo != spent) {
+            return Err(UpdateError::UtxoMismatch);
+        }
+        input.non_witness_utxo = Some(prev_tx);
+        Ok(())
+    }
+
+    /// Sets the output spent by the input at `input_index` as its `witness_utxo`.
+    ///
+    /// # Errors
+    ///
+    /// If `prevout` is not a segwit output, which includes P2SH outputs because they may wrap
+    /// a witness program, or does not match the `non_witness_utxo` already set.
+    pub fn update_input_with_prevout(
+        &mut self,
+        input_index: usize,
+        prevout: TxOut,
+    ) -> Result<(), UpdateError> {
+        self.check_index_is_within_bounds(input_index)?;
+        let vout = self.unsigned_tx.input[input_index].previous_output.vout;
+
+        if !prevout.script_pubkey.is_witness_program() && !prevout.script_pubkey.is_p2sh() {
+            return Err(UpdateError::NotSegwit);
+        }
+
+        let input = &mut self.inputs[input_index];
+        if let Some(ref prev_tx) = input.non_witness_utxo {
+            if prev_tx.output.get(vout as usize) != Some(&prevout) {
+                return Err(UpdateError::UtxoMismatch);
+            }
+        }
+        input.witness_utxo = Some(prevout);
+        Ok(())
+    }
+
+    /// Sets the redeem script of the P2SH output spent by the input at `input_index`.
+    ///
+    /// # Errors
+    ///
+    /// If the spent output is unknown or is not a P2SH output committing to `redeem_script`.
+    pub fn add_redeem_script(
+        &mut self,
+        input_index: usize,
+        redeem_script: ScriptBuf,
+    ) -> Result<(), UpdateError> {
+        let script_pubkey = &self.updated_utxo(input_index)?.script_pubkey;
+        if *script_pubkey != ScriptBuf::new_p2sh(&redeem_script.script_hash()) {
+            return Err(UpdateError::RedeemScriptMismatch);
+        }
+        self.inputs[input_index].redeem_script = Some(redeem_script);
+        Ok(())
+    }
+
+    /// Sets the witness script of the P2WSH output spent by the input at `input_index`.
+    ///
+    /// For P2SH wrapped P2WSH outputs the redeem script must be added first.
+    ///
+    /// # Errors
+    ///
+    /// If the spent output is unknown or is not a P2WSH output, directly or wrapped in P2SH,
+    /// committing to `witness_script`.
+    pub fn add_witness_script(
+        &mut self,
+        input_index: usize,
+        witness_script: ScriptBuf,
+    ) -> Result<(), UpdateError> {
+        let script_pubkey = &self.updated_utxo(input_index)?.script_pubkey;
+        let program = if script_pubkey.is_p2sh() {
+            self.inputs[input_index]
+                .redeem_script
+                .as_ref()
+                .ok_or(UpdateError::MissingRedeemScript)?
+        } else {
+            script_pubkey
+        };
+        if *program != ScriptBuf::new_p2wsh(&witness_script.wscript_hash()) {
+            return Err(UpdateError::WitnessScriptMismatch);
+        }
+        self.inputs[input_index].witness_script = Some(witness_script);
+        Ok(())
+    }
+
+    /// Records the origin of `pubkey`, a key needed to sign the input at `input_index`.
+    ///
+    /// For inputs spending taproot outputs the origin is added to `tap_key_origins` without any
+    /// leaf hashes, that is as the origin of a key path key. Add the origins of script path keys
+    /// to `tap_key_origins` directly.
+    ///
+    /// # Errors
+    ///
+    /// If the spent output is unknown.
+    pub fn add_key_origin(
+        &mut self,
+        input_index: usize,
+        pubkey: secp256k1::PublicKey,
+        origin: KeySource,
+    ) -> Result<(), UpdateError> {
+        self.updated_utxo(input_index)?;
+        let algorithm = self.signing_algorithm(input_index);
+        let input = &mut self.inputs[input_index];
+        match algorithm {
+            Ok(SigningAlgorithm::Schnorr) => {
+                let (xonly, _) = pubkey.x_only_public_key();
+                input.tap_key_origins.insert(xonly, (vec![], origin));
+            }
+            _ => {
+                input.bip32_derivation.insert(pubkey, origin);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the scripts and keys of `descriptor` to the input at `input_index`.
+    ///
+    /// Sets the redeem script of `sh()` descriptors, the witness script of `wsh()` descriptors
+    /// and the internal key of `tr()` descriptors.
+    ///
+    /// # Errors
+    ///
+    /// If the spent output is unknown or does not have the script pubkey of `descriptor`.
+    pub fn update_input_with_descriptor<C: Verification>(
+        &mut self,
+        input_index: usize,
+        descriptor: &Descriptor,
+        secp: &Secp256k1<C>,
+    ) -> Result<(), UpdateError> {
+        if self.updated_utxo(input_index)?.script_pubkey != descriptor.script_pubkey(secp) {
+            return Err(UpdateError::DescriptorMismatch);
+        }
+        if let Some(redeem_script) = descriptor.redeem_script() {
+            self.add_redeem_script(input_index, redeem_script)?;
+        }
+        if let Some(witness_script) = descriptor.witness_script() {
+            self.add_witness_script(input_index, witness_script)?;
+        }
+        if let Descriptor::Tr(internal_key) = *descriptor {
+            self.inputs[input_index].tap_internal_key = Some(internal_key);
+        }
+        Ok(())
+    }
+
+    /// Returns the output spent by the input at `input_index`, which must have been added.
+    fn updated_utxo(&self, input_index: usize) -> Result<&TxOut, UpdateError> {
+        self.check_index_is_within_bounds(input_index)?;
+        self.spend_utxo(input_index).map_err(|_| UpdateError::MissingSpendUtxo)
+    }
+}
+
+/// Errors encountered while updating a PSBT input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum UpdateError {
+    /// Input index out of bounds.
+    IndexOutOfBounds(IndexOutOfBoundsError),
+    /// Missing spending utxo.
+    MissingSpendUtxo,
+    /// The previous transaction is not the one spent by the input.
+    PrevTxidMismatch {
+        /// The txid of the outpoint spent by the input.
+        expected: Txid,
+        /// The txid of the previous transaction.
+        txid: Txid,
+    },
+    /// The previous transaction does not have the output spent by the input.
+    MissingPrevOutput,
+    /// The `witness_utxo` and the spent output of the `non_witness_utxo` differ.
+    UtxoMismatch,
+    /// A `witness_utxo` can only be set for segwit outputs.
+    NotSegwit,
+    /// The redeem script must be added before the witness script of a P2SH wrapped output.
+    MissingRedeemScript,
+    /// The spent output does not commit to the redeem script.
+    RedeemScriptMismatch,
+    /// The spent output does not commit to the witness script.
+    WitnessScriptMismatch,
+    /// The spent output does not have the script pubkey of the descriptor.
+    DescriptorMismatch,
+}
+
+internals::impl_from_infallible!(UpdateError);
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use UpdateError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => write_err!(f, "index out of bounds"; e),
+            MissingSpendUtxo => write!(f, "missing spend utxo in PSBT"),
+            PrevTxidMismatch { expected, txid } =>
+                write!(f, "previous transaction {} is not the spent one {}", txid, expected),
+            MissingPrevOutput => write!(f, "previous transaction does not have the spent output"),
+            UtxoMismatch => write!(f, "witness utxo does not match the non-witness utxo"),
+            NotSegwit => write!(f, "witness utxo is not a segwit output"),
+            MissingRedeemScript => write!(f, "missing redeem script"),
+            RedeemScriptMismatch => write!(f, "spent output does not commit to the redeem script"),
+            WitnessScriptMismatch =>
+                write!(f, "spent output does not commit to the witness script"),
+            DescriptorMismatch => write!(f, "spent output does not match the descriptor"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UpdateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use UpdateError::*;
+
+        match *self {
+            IndexOutOfBounds(ref e) => Some(e),
+            MissingSpendUtxo
+            | PrevTxidMismatch { .. }
+            | MissingPrevOutput
+            | UtxoMismatch
+            | NotSegwit
+            | MissingRedeemScript
+            | RedeemScriptMismatch
+            | WitnessScriptMismatch
+            | DescriptorMismatch => None,
+        }
+    }
+}
+
+impl From<IndexOutOfBoundsError> for UpdateError {
+    fn from(e: IndexOutOfBoundsError) -> Self { UpdateError::IndexOutOfBounds(e) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key::CompressedPublicKey;
+    use crate::psbt::test_utils::{keys, prev_tx, psbt_spending};
+    use crate::Amount;
+
+    fn key() -> CompressedPublicKey {
+        let (_, pk) = keys(&Secp256k1::new(), 1)[0];
+        CompressedPublicKey(pk.inner)
+    }
+
+    #[test]
+    fn update_utxos() {
+        let prev = prev_tx(ScriptBuf::new_p2wpkh(&key().wpubkey_hash()));
+        let mut psbt = psbt_spending(&prev);
+
+        let other = prev_tx(ScriptBuf::new_p2pkh(&key().pubkey_hash()));
+        assert!(matches!(
+            psbt.update_input_with_prev_tx(0, other.clone()),
+            Err(UpdateError::PrevTxidMismatch { .. })
+        ));
+        assert_eq!(
+            psbt.update_input_with_prevout(0, other.output[0].clone()),
+            Err(UpdateError::NotSegwit)
+        );
+
+        psbt.update_input_with_prev_tx(0, prev.clone()).unwrap();
+        let mut prevout = prev.output[0].clone();
+        prevout.value = Amount::from_sat(20_000);
+        assert_eq!(psbt.update_input_with_prevout(0, prevout), Err(UpdateError::UtxoMismatch));
+        psbt.update_input_with_prevout(0, prev.output[0].clone()).unwrap();
+
+        assert_eq!(
+            psbt.update_input_with_prev_tx(1, prev),
+            Err(UpdateError::IndexOutOfBounds(IndexOutOfBoundsError::Inputs {
+                index: 1,
+                length: 1
+            }))
+        );
+    }
+
+    #[test]
+    fn update_scripts() {
+        let secp = Secp256k1::verification_only();
+        let descriptor = Descriptor::WshMulti { threshold: 1, keys: vec![key()] };
+        let witness_script = descriptor.witness_script().unwrap();
+        let redeem_script = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        let prev = prev_tx(ScriptBuf::new_p2sh(&redeem_script.script_hash()));
+        let mut psbt = psbt_spending(&prev);
+
+        assert_eq!(
+            psbt.add_redeem_script(0, redeem_script.clone()),
+            Err(UpdateError::MissingSpendUtxo)
+        );
+        psbt.update_input_with_prevout(0, prev.output[0].clone()).unwrap();
+
+        assert_eq!(
+            psbt.add_witness_script(0, witness_script.clone()),
+            Err(UpdateError::MissingRedeemScript)
+        );
+        assert_eq!(
+            psbt.add_redeem_script(0, witness_script.clone()),
+            Err(UpdateError::RedeemScriptMismatch)
+        );
+        psbt.add_redeem_script(0, redeem_script.clone()).unwrap();
+        assert_eq!(
+            psbt.add_witness_script(0, redeem_script),
+            Err(UpdateError::WitnessScriptMismatch)
+        );
+        psbt.add_witness_script(0, witness_script.clone()).unwrap();
+        assert_eq!(psbt.inputs[0].witness_script, Some(witness_script));
+
+        assert_eq!(
+            psbt.update_input_with_descriptor(0, &descriptor, &secp),
+            Err(UpdateError::DescriptorMismatch)
+        );
+    }
+
+    #[test]
+    fn update_with_descriptor() {
+        let secp = Secp256k1::verification_only();
+        let (xonly, _) = key().0.x_only_public_key();
+
+        let descriptor = Descriptor::ShWpkh(key());
+        let mut psbt = psbt_spending(&prev_tx(descriptor.script_pubkey(&secp)));
+        let prevout = TxOut {
+            value: Amount::from_sat(10_000),
+            script_pubkey: descriptor.script_pubkey(&secp),
+        };
+        psbt.update_input_with_prevout(0, prevout).unwrap();
+        psbt.update_input_with_descriptor(0, &descriptor, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].redeem_script, descriptor.redeem_script());
+        assert_eq!(psbt.inputs[0].witness_script, None);
+
+        let origin = (Default::default(), "0/1".parse().unwrap());
+        psbt.add_key_origin(0, key().0, origin.clone()).unwrap();
+        assert_eq!(psbt.inputs[0].bip32_derivation[&key().0], origin);
+
+        let descriptor = Descriptor::Tr(xonly);
+        let prev = prev_tx(descriptor.script_pubkey(&secp));
+        let mut psbt = psbt_spending(&prev);
+        psbt.update_input_with_prev_tx(0, prev).unwrap();
+        psbt.update_input_with_descriptor(0, &descriptor, &secp).unwrap();
+        assert_eq!(psbt.inputs[0].tap_internal_key, Some(xonly));
+
+        psbt.add_key_origin(0, key().0, origin.clone()).unwrap();
+        assert_eq!(psbt.inputs[0].tap_key_origins[&xonly], (vec![], origin));
+        assert!(psbt.inputs[0].bip32_derivation.is_empty());
+    }
+}
@@ -0,0 +1,456 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! BIP 322 generic signed messages.
+//!
+//! The legacy message signing format only supports P2PKH addresses. BIP 322 instead proves control
+//! of an address by signing a virtual transaction: `to_spend` commits to the message and pays to
+//! the address, `to_sign` spends it. The signature is either the witness of `to_sign` ("simple"
+//! format) or the whole signed `to_sign` transaction ("full" format).
+//!
+//! Signing and verification are supported for P2WPKH addresses and key path spends of P2TR
+//! addresses.
+
+use core::fmt;
+
+use hashes::{sha256t_hash_newtype, Hash, HashEngine};
+use secp256k1::{Keypair, Message, Secp256k1, Signing, Verification, XOnlyPublicKey};
+
+use crate::address::Address;
+use crate::blockdata::locktime::absolute;
+use crate::blockdata::opcodes::all::{OP_PUSHBYTES_0, OP_RETURN};
+use crate::blockdata::script::{Builder, ScriptBuf};
+use crate::blockdata::transaction::{self, OutPoint, Transaction, TxIn, TxOut};
+use crate::blockdata::witness::Witness;
+use crate::consensus::{deserialize, serialize};
+use crate::crypto::key::{CompressedPublicKey, PrivateKey, TapTweak, TweakedPublicKey};
+use crate::crypto::{ecdsa, taproot};
+use crate::prelude::*;
+use crate::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use crate::{Amount, Sequence};
+
+sha256t_hash_newtype! {
+    pub struct MessageTag = hash_str("BIP0322-signed-message");
+
+    /// Tagged hash with tag \"BIP0322-signed-message\".
+    ///
+    /// This hash type is used to commit to the message in the `to_spend` transaction.
+    #[hash_newtype(forward)]
+    pub struct MessageHash(_);
+}
+
+impl MessageHash {
+    /// Computes the hash of `message`.
+    pub fn from_message(message: &[u8]) -> MessageHash {
+        let mut eng = MessageHash::engine();
+        eng.input(message);
+        MessageHash::from_engine(eng)
+    }
+}
+
+/// Creates the virtual `to_spend` transaction, committing to `message` and paying to `address`.
+pub fn to_spend(address: &Address, message: &[u8]) -> Transaction {
+    let script_sig = Builder::new()
+        .push_opcode(OP_PUSHBYTES_0)
+        .push_slice(MessageHash::from_message(message).to_byte_array())
+        .into_script();
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::null(),
+            script_sig,
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut { value: Amount::ZERO, script_pubkey: address.script_pubkey() }],
+    }
+}
+
+/// Creates the unsigned virtual `to_sign` transaction spending `to_spend`.
+pub fn to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint::new(to_spend.compute_txid(), 0),
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+/// A BIP 322 signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signature {
+    /// The witness of the `to_sign` transaction.
+    Simple(Witness),
+    /// The signed `to_sign` transaction.
+    Full(Transaction),
+}
+
+impl Signature {
+    /// Signs `message` for `address` using the simple format.
+    pub fn sign_simple<C: Signing + Verification>(
+        secp: &Secp256k1<C>,
+        address: &Address,
+        message: &[u8],
+        key: &PrivateKey,
+    ) -> Result<Signature, Error> {
+        let mut to_sign = sign(secp, &to_spend(address, message), key)?;
+        let input = to_sign.input.pop().expect("to_sign has one input");
+        Ok(Signature::Simple(input.witness))
+    }
+
+    /// Signs `message` for `address` using the full format.
+    pub fn sign_full<C: Signing + Verification>(
+        secp: &Secp256k1<C>,
+        address: &Address,
+        message: &[u8],
+        key: &PrivateKey,
+    ) -> Result<Signature, Error> {
+        sign(secp, &to_spend(address, message), key).map(Signature::Full)
+    }
+
+    /// Verifies that this is a signature of `message` by `address`.
+    ///
+    /// Full signatures may change the version, lock time and sequence of `to_sign`. Additional
+    /// inputs proving control of funds are not supported.
+    pub fn verify<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        address: &Address,
+        message: &[u8],
+    ) -> Result<(), Error> {
+        let to_spend = to_spend(address, message);
+        match self {
+            Signature::Simple(witness) => {
+                let mut to_sign = to_sign(&to_spend);
+                to_sign.input[0].witness = witness.clone();
+                verify(secp, &to_spend, &to_sign)
+            }
+            Signature::Full(to_sign) => {
+                let expected = self::to_sign(&to_spend);
+                if to_sign.input.len() > 1 {
+                    return Err(Error::ProofOfFundsUnsupported);
+                }
+                if to_sign.input.first().map(|input| input.previous_output)
+                    != Some(expected.input[0].previous_output)
+                    || to_sign.output != expected.output
+                {
+                    return Err(Error::InvalidTransaction);
+                }
+                verify(secp, &to_spend, to_sign)
+            }
+        }
+    }
+
+    /// Serializes the signature, the consensus encoding of the witness or transaction.
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            Signature::Simple(witness) => serialize(witness),
+            Signature::Full(to_sign) => serialize(to_sign),
+        }
+    }
+
+    /// Deserializes a signature in either format.
+    pub fn deserialize(bytes: &[u8]) -> Result<Signature, Error> {
+        if let Ok(witness) = deserialize(bytes) {
+            return Ok(Signature::Simple(witness));
+        }
+        deserialize(bytes).map(Signature::Full).map_err(|_| Error::InvalidEncoding)
+    }
+}
+
+/// Returns `to_sign` for `to_spend` with the witness signed by `key`.
+fn sign<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    to_spend: &Transaction,
+    key: &PrivateKey,
+) -> Result<Transaction, Error> {
+    let mut to_sign = to_sign(to_spend);
+    let prevout = &to_spend.output[0];
+    let script_pubkey = &prevout.script_pubkey;
+    let mut cache = SighashCache::new(&to_sign);
+
+    let witness = if script_pubkey.is_p2wpkh() {
+        let pubkey =
+            CompressedPublicKey::from_private_key(secp, key).map_err(|_| Error::KeyMismatch)?;
+        if ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()) != *script_pubkey {
+            return Err(Error::KeyMismatch);
+        }
+        let sighash_type = EcdsaSighashType::All;
+        let sighash = cache
+            .p2wpkh_signature_hash(0, script_pubkey, prevout.value, sighash_type)
+            .expect("to_sign has one input");
+        let msg = Message::from_digest(sighash.to_byte_array());
+        let signature = secp.sign_ecdsa(&msg, &key.inner);
+        Witness::p2wpkh(&ecdsa::Signature { signature, sighash_type }, &pubkey.0)
+    } else if script_pubkey.is_p2tr() {
+        let keypair = Keypair::from_secret_key(secp, &key.inner).tap_tweak(secp, None);
+        if ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::from_keypair(keypair)) != *script_pubkey {
+            return Err(Error::KeyMismatch);
+        }
+        let sighash_type = TapSighashType::Default;
+        let sighash = cache
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(&to_spend.output), sighash_type)
+            .expect("to_sign has one input");
+        let msg = Message::from_digest(sighash.to_byte_array());
+        #[cfg(feature = "rand-std")]
+        let signature = secp.sign_schnorr(&msg, &keypair.to_inner());
+        #[cfg(not(feature = "rand-std"))]
+        let signature = secp.sign_schnorr_no_aux_rand(&msg, &keypair.to_inner());
+        Witness::p2tr_key_spend(&taproot::Signature { signature, sighash_type })
+    } else {
+        return Err(Error::UnsupportedAddress);
+    };
+
+    to_sign.input[0].witness = witness;
+    Ok(to_sign)
+}
+
+/// Verifies the witness of the first input of `to_sign` spending the output of `to_spend`.
+fn verify<C: Verification>(
+    secp: &Secp256k1<C>,
+    to_spend: &Transaction,
+    to_sign: &Transaction,
+) -> Result<(), Error> {
+    let prevout = &to_spend.output[0];
+    let script_pubkey = &prevout.script_pubkey;
+    let witness = &to_sign.input[0].witness;
+    let mut cache = SighashCache::new(to_sign);
+
+    if script_pubkey.is_p2wpkh() {
+        if witness.len() != 2 {
+            return Err(Error::InvalidWitness);
+        }
+        let signature =
+            ecdsa::Signature::from_slice(&witness[0]).map_err(|_| Error::InvalidWitness)?;
+        let pubkey =
+            CompressedPublicKey::from_slice(&witness[1]).map_err(|_| Error::InvalidWitness)?;
+        if ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()) != *script_pubkey {
+            return Err(Error::InvalidSignature);
+        }
+        let sighash = cache
+            .p2wpkh_signature_hash(0, script_pubkey, prevout.value, signature.sighash_type)
+            .expect("to_sign has one input");
+        let msg = Message::from_digest(sighash.to_byte_array());
+        secp.verify_ecdsa(&msg, &signature.signature, &pubkey.0)
+            .map_err(|_| Error::InvalidSignature)
+    } else if script_pubkey.is_p2tr() {
+        if witness.len() != 1 {
+            return Err(Error::InvalidWitness);
+        }
+        let signature =
+            taproot::Signature::from_slice(&witness[0]).map_err(|_| Error::InvalidWitness)?;
+        let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..])
+            .map_err(|_| Error::InvalidSignature)?;
+        let sighash = cache
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::All(&to_spend.output),
+                signature.sighash_type,
+            )
+            .map_err(|_| Error::InvalidSignature)?;
+        let msg = Message::from_digest(sighash.to_byte_array());
+        secp.verify_schnorr(&signature.signature, &msg, &output_key)
+            .map_err(|_| Error::InvalidSignature)
+    } else {
+        Err(Error::UnsupportedAddress)
+    }
+}
+
+/// BIP 322 signing or verification error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// Only P2WPKH and P2TR addresses are supported.
+    UnsupportedAddress,
+    /// The private key does not control the address.
+    KeyMismatch,
+    /// The full signature does not spend `to_spend` to a single `OP_RETURN` output.
+    InvalidTransaction,
+    /// The full signature spends additional inputs to prove control of funds.
+    ProofOfFundsUnsupported,
+    /// The witness is not a key spend of the address.
+    InvalidWitness,
+    /// The signature is not valid.
+    InvalidSignature,
+    /// The signature is neither an encoded witness nor an encoded transaction.
+    InvalidEncoding,
+    /// Invalid base64 encoding.
+    InvalidBase64,
+}
+
+internals::impl_from_infallible!(Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+
+        match *self {
+            UnsupportedAddress => f.write_str("only P2WPKH and P2TR addresses are supported"),
+            KeyMismatch => f.write_str("private key does not control the address"),
+            InvalidTransaction =>
+                f.write_str("to_sign transaction does not spend to_spend to an OP_RETURN output"),
+            ProofOfFundsUnsupported => f.write_str("proof of funds is not supported"),
+            InvalidWitness => f.write_str("witness is not a key spend of the address"),
+            InvalidSignature => f.write_str("invalid signature"),
+            InvalidEncoding => f.write_str("invalid signature encoding"),
+            InvalidBase64 => f.write_str("invalid base64"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use Error::*;
+
+        match *self {
+            UnsupportedAddress
+            | KeyMismatch
+            | InvalidTransaction
+            | ProofOfFundsUnsupported
+            | InvalidWitness
+            | InvalidSignature
+            | InvalidEncoding
+            | InvalidBase64 => None,
+        }
+    }
+}
+
+#[cfg(feature = "base64")]
+mod base64_impls {
+    use base64::prelude::{Engine as _, BASE64_STANDARD};
+
+    use super::*;
+
+    impl Signature {
+        /// Converts a signature from base64 encoding.
+        pub fn from_base64(s: &str) -> Result<Signature, Error> {
+            let bytes = BASE64_STANDARD.decode(s).map_err(|_| Error::InvalidBase64)?;
+            Signature::deserialize(&bytes)
+        }
+
+        /// Converts to base64 encoding.
+        pub fn to_base64(&self) -> String { BASE64_STANDARD.encode(self.serialize()) }
+    }
+
+    impl fmt::Display for Signature {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(&self.to_base64()) }
+    }
+
+    impl core::str::FromStr for Signature {
+        type Err = Error;
+        fn from_str(s: &str) -> Result<Signature, Error> { Signature::from_base64(s) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::NetworkUnchecked;
+
+    fn address(s: &str) -> Address {
+        s.parse::<Address<NetworkUnchecked>>().unwrap().assume_checked()
+    }
+
+    fn key() -> PrivateKey {
+        PrivateKey::from_wif("L3VFeEujGtevx9w18HD1fhRbCH67Az2dpCymeRE1SoPK6XQtaN2k").unwrap()
+    }
+
+    #[test]
+    fn message_hash() {
+        assert_eq!(
+            MessageHash::from_message(b"").to_string(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            MessageHash::from_message(b"Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+    }
+
+    #[test]
+    fn virtual_transactions() {
+        let address = address("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l");
+        let cases = [
+            (
+                &b""[..],
+                "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7",
+                "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6",
+            ),
+            (
+                &b"Hello World"[..],
+                "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b",
+                "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf",
+            ),
+        ];
+        for (message, to_spend_txid, to_sign_txid) in cases {
+            let to_spend = to_spend(&address, message);
+            assert_eq!(to_spend.compute_txid().to_string(), to_spend_txid);
+            assert_eq!(to_sign(&to_spend).compute_txid().to_string(), to_sign_txid);
+        }
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let secp = Secp256k1::new();
+        let key = key();
+        let addresses = [
+            address("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l"),
+            address("bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3"),
+        ];
+        for address in &addresses {
+            let simple = Signature::sign_simple(&secp, address, b"Hello World", &key).unwrap();
+            let full = Signature::sign_full(&secp, address, b"Hello World", &key).unwrap();
+            for sig in [simple, full] {
+                sig.verify(&secp, address, b"Hello World").unwrap();
+                assert!(sig.verify(&secp, address, b"").is_err());
+                assert_eq!(Signature::deserialize(&sig.serialize()), Ok(sig));
+            }
+        }
+        assert_eq!(
+            Signature::sign_simple(
+                &secp,
+                &addresses[0],
+                b"",
+                &PrivateKey::new(
+                    secp256k1::SecretKey::from_slice(&[2; 32]).unwrap(),
+                    crate::NetworkKind::Main
+                )
+            ),
+            Err(Error::KeyMismatch)
+        );
+
+        let p2pkh = Address::p2pkh(key.public_key(&secp), crate::NetworkKind::Main);
+        assert_eq!(
+            Signature::sign_simple(&secp, &p2pkh, b"", &key),
+            Err(Error::UnsupportedAddress)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn test_vectors() {
+        let secp = Secp256k1::new();
+        let p2wpkh = address("bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l");
+        let p2tr = address("bc1ppv609nr0vr25u07u95waq5lucwfm6tde4nydujnu8npg4q75mr5sxq8lt3");
+        let cases = [
+            (&p2wpkh, &b""[..], "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="),
+            (&p2wpkh, &b"Hello World"[..], "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI="),
+            (&p2tr, &b"Hello World"[..], "AUHd69PrJQEv+oKTfZ8l+WROBHuy9HKrbFCJu7U1iK2iiEy1vMU5EfMtjc+VSHM7aU0SDbak5IUZRVno2P5mjSafAQ=="),
+        ];
+        for (address, message, sig) in cases {
+            let sig = sig.parse::<Signature>().unwrap();
+            assert!(matches!(sig, Signature::Simple(_)));
+            sig.verify(&secp, address, message).unwrap();
+            assert_eq!(sig.to_string().parse::<Signature>(), Ok(sig));
+        }
+    }
+}
@@ -10,6 +10,8 @@ use hashes::{sha256d, Hash, HashEngine};
 
 use crate::consensus::{encode, Encodable};
 
+pub mod bip322;
+
 #[rustfmt::skip]
 #[doc(inline)]
 #[cfg(feature = "secp-recovery")]
@@ -27,7 +29,8 @@ mod message_signing {
     use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
 
     use crate::address::{Address, AddressType};
-    use crate::crypto::key::PublicKey;
+    use crate::blockdata::script::ScriptBuf;
+    use crate::crypto::key::{CompressedPublicKey, PublicKey};
 
     /// An error used for dealing with Bitcoin Signed Messages.
     #[derive(Debug, Clone, PartialEq, Eq)]
@@ -41,6 +44,8 @@ mod message_signing {
         InvalidBase64,
         /// Unsupported Address Type
         UnsupportedAddressType(AddressType),
+        /// The signature was not created by a key controlling the address.
+        AddressMismatch,
     }
 
     internals::impl_from_infallible!(MessageSignatureError);
@@ -55,6 +60,7 @@ mod message_signing {
                 InvalidBase64 => write!(f, "invalid base64"),
                 UnsupportedAddressType(ref address_type) =>
                     write!(f, "unsupported address type: {}", address_type),
+                AddressMismatch => write!(f, "signature was not created by the address"),
             }
         }
     }
@@ -66,7 +72,7 @@ mod message_signing {
 
             match *self {
                 InvalidEncoding(ref e) => Some(e),
-                InvalidLength | InvalidBase64 | UnsupportedAddressType(_) => None,
+                InvalidLength | InvalidBase64 | UnsupportedAddressType(_) | AddressMismatch => None,
             }
         }
     }
@@ -110,6 +116,9 @@ mod message_signing {
         }
 
         /// Create from a byte slice.
+        ///
+        /// Besides the P2PKH header bytes this accepts the header bytes of the Electrum extension
+        /// for P2SH-P2WPKH and P2WPKH (BIP 137), which imply a compressed key.
         pub fn from_slice(bytes: &[u8]) -> Result<MessageSignature, MessageSignatureError> {
             if bytes.len() != 65 {
                 return Err(MessageSignatureError::InvalidLength);
@@ -123,7 +132,7 @@ mod message_signing {
             let recid = RecoveryId::from_i32(((bytes[0] - 27) & 0x03) as i32)?;
             Ok(MessageSignature {
                 signature: RecoverableSignature::from_compact(&bytes[1..], recid)?,
-                compressed: ((bytes[0] - 27) & 0x04) != 0,
+                compressed: (bytes[0] - 27) >= 4,
             })
         }
 
@@ -142,6 +151,9 @@ mod message_signing {
 
         /// Verify that the signature signs the message and was signed by the given address.
         ///
+        /// Supports P2PKH addresses and, as per the Electrum extension (BIP 137), P2SH-P2WPKH and
+        /// P2WPKH addresses of compressed keys.
+        ///
         /// To get the message hash from a message, use [super::signed_msg_hash].
         pub fn is_signed_by_address<C: secp256k1::Verification>(
             &self,
@@ -149,15 +161,48 @@ mod message_signing {
             address: &Address,
             msg_hash: sha256d::Hash,
         ) -> Result<bool, MessageSignatureError> {
-            match address.address_type() {
-                Some(AddressType::P2pkh) => {
-                    let pubkey = self.recover_pubkey(secp_ctx, msg_hash)?;
-                    Ok(address.pubkey_hash() == Some(pubkey.pubkey_hash()))
+            let pubkey = self.recover_pubkey(secp_ctx, msg_hash)?;
+            is_address_of(&pubkey, address)
+        }
+
+        /// Verifies that the signature signs `message` and was signed by the given address,
+        /// returning the public key that signed it.
+        ///
+        /// Unlike [`MessageSignature::is_signed_by_address`], both the compressed and the
+        /// uncompressed form of the recovered key are tried, whatever the header byte says.
+        pub fn verify<C: secp256k1::Verification>(
+            &self,
+            secp_ctx: &secp256k1::Secp256k1<C>,
+            address: &Address,
+            message: &str,
+        ) -> Result<PublicKey, MessageSignatureError> {
+            let pubkey = self.recover_pubkey(secp_ctx, super::signed_msg_hash(message))?;
+            let other = PublicKey { inner: pubkey.inner, compressed: !pubkey.compressed };
+            for pubkey in [pubkey, other] {
+                if is_address_of(&pubkey, address)? {
+                    return Ok(pubkey);
                 }
-                Some(address_type) =>
-                    Err(MessageSignatureError::UnsupportedAddressType(address_type)),
-                None => Ok(false),
             }
+            Err(MessageSignatureError::AddressMismatch)
+        }
+    }
+
+    /// Returns true if `address` is a P2PKH, P2SH-P2WPKH or P2WPKH address of `pubkey`.
+    fn is_address_of(pubkey: &PublicKey, address: &Address) -> Result<bool, MessageSignatureError> {
+        let wpkh_script = || {
+            CompressedPublicKey::try_from(*pubkey)
+                .map(|pubkey| ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash()))
+                .ok()
+        };
+        match address.address_type() {
+            Some(AddressType::P2pkh) => Ok(address.pubkey_hash() == Some(pubkey.pubkey_hash())),
+            Some(AddressType::P2sh) => Ok(wpkh_script().map_or(false, |script| {
+                address.matches_script_pubkey(&ScriptBuf::new_p2sh(&script.script_hash()))
+            })),
+            Some(AddressType::P2wpkh) =>
+                Ok(wpkh_script().map_or(false, |script| address.matches_script_pubkey(&script))),
+            Some(address_type) => Err(MessageSignatureError::UnsupportedAddressType(address_type)),
+            None => Ok(false),
         }
     }
 
@@ -248,17 +293,14 @@ mod tests {
         let p2pkh = Address::p2pkh(pubkey, NetworkKind::Main);
         assert_eq!(signature2.is_signed_by_address(&secp, &p2pkh, msg_hash), Ok(true));
         let p2wpkh = Address::p2wpkh(&pubkey, Network::Bitcoin);
-        assert_eq!(
-            signature2.is_signed_by_address(&secp, &p2wpkh, msg_hash),
-            Err(MessageSignatureError::UnsupportedAddressType(AddressType::P2wpkh))
-        );
+        assert_eq!(signature2.is_signed_by_address(&secp, &p2wpkh, msg_hash), Ok(true));
         let p2shwpkh = Address::p2shwpkh(&pubkey, NetworkKind::Main);
+        assert_eq!(signature2.is_signed_by_address(&secp, &p2shwpkh, msg_hash), Ok(true));
+        let p2tr = Address::p2tr(&secp, pubkey.0.x_only_public_key().0, None, Network::Bitcoin);
         assert_eq!(
-            signature2.is_signed_by_address(&secp, &p2shwpkh, msg_hash),
-            Err(MessageSignatureError::UnsupportedAddressType(AddressType::P2sh))
+            signature2.is_signed_by_address(&secp, &p2tr, msg_hash),
+            Err(MessageSignatureError::UnsupportedAddressType(AddressType::P2tr))
         );
-        let p2pkh = Address::p2pkh(pubkey, Network::Bitcoin);
-        assert_eq!(signature2.is_signed_by_address(&secp, &p2pkh, msg_hash), Ok(true));
 
         assert_eq!(pubkey.0, secp256k1::PublicKey::from_secret_key(&secp, &privkey));
     }
@@ -290,4 +332,42 @@ mod tests {
         let p2pkh = Address::p2pkh(pubkey, NetworkKind::Main);
         assert_eq!(signature.is_signed_by_address(&secp, &p2pkh, msg_hash), Ok(false));
     }
+
+    #[test]
+    #[cfg(feature = "secp-recovery")]
+    fn test_message_signature_verify() {
+        use crate::crypto::key::{CompressedPublicKey, PublicKey};
+        use crate::{Address, NetworkKind};
+
+        let secp = secp256k1::Secp256k1::new();
+        let message = "rust-bitcoin MessageSignature verify test";
+        let msg = secp256k1::Message::from_digest(signed_msg_hash(message).to_byte_array());
+        let privkey = secp256k1::SecretKey::from_slice(&[1; 32]).unwrap();
+        let signature = secp.sign_ecdsa_recoverable(&msg, &privkey);
+        let compressed = PublicKey::new(privkey.public_key(&secp));
+        let uncompressed = PublicKey::new_uncompressed(privkey.public_key(&secp));
+        let wpkh_key = CompressedPublicKey(compressed.inner);
+
+        let p2pkh = Address::p2pkh(compressed, NetworkKind::Main);
+        let p2pkh_uncompressed = Address::p2pkh(uncompressed, NetworkKind::Main);
+        let p2shwpkh = Address::p2shwpkh(&wpkh_key, NetworkKind::Main);
+        for compressed_flag in [true, false] {
+            let signature = MessageSignature::new(signature, compressed_flag);
+            assert_eq!(signature.verify(&secp, &p2pkh, message), Ok(compressed));
+            assert_eq!(signature.verify(&secp, &p2pkh_uncompressed, message), Ok(uncompressed));
+            assert_eq!(signature.verify(&secp, &p2shwpkh, message), Ok(compressed));
+            assert_eq!(
+                signature.verify(&secp, &p2pkh, "another message"),
+                Err(MessageSignatureError::AddressMismatch)
+            );
+        }
+
+        // Electrum P2SH-P2WPKH header bytes imply a compressed key.
+        let mut bytes = MessageSignature::new(signature, true).serialize();
+        bytes[0] += 4;
+        let signature = MessageSignature::from_slice(&bytes).unwrap();
+        assert!(signature.compressed);
+        let msg_hash = signed_msg_hash(message);
+        assert_eq!(signature.is_signed_by_address(&secp, &p2shwpkh, msg_hash), Ok(true));
+    }
 }
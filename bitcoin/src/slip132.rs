@@ -0,0 +1,256 @@
+// SPDX-License-Identifier: CC0-1.0
+
+//! SLIP-132 extended key version bytes.
+//!
+//! Many wallets (e.g. Electrum, Trezor) export extended keys with non-standard version bytes
+//! (`ypub`, `zpub`, `Zpub`, `vpub`, ...) that hint at the script type the keys are used with.
+//! This module converts such keys to and from [`Xpub`] and [`Xpriv`] plus a [`ScriptType`] hint.
+//!
+//! See [SLIP-132] for the registry of version bytes.
+//!
+//! [SLIP-132]: <https://github.com/satoshilabs/slips/blob/master/slip-0132.md>
+
+use core::fmt;
+
+use crate::bip32::{Error, Xpriv, Xpub};
+use crate::network::NetworkKind;
+use crate::prelude::*;
+
+/// The script type an extended key is used with, as indicated by its SLIP-132 version bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ScriptType {
+    /// P2PKH or P2SH, the standard BIP-32 `xpub`/`tpub` version bytes.
+    P2pkhOrP2sh,
+    /// P2WPKH nested in P2SH (`ypub`/`upub`).
+    P2shP2wpkh,
+    /// Multisig P2WSH nested in P2SH (`Ypub`/`Upub`).
+    P2shP2wsh,
+    /// P2WPKH (`zpub`/`vpub`).
+    P2wpkh,
+    /// Multisig P2WSH (`Zpub`/`Vpub`).
+    P2wsh,
+}
+
+impl fmt::Display for ScriptType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ScriptType::P2pkhOrP2sh => "p2pkh-or-p2sh",
+            ScriptType::P2shP2wpkh => "p2sh-p2wpkh",
+            ScriptType::P2shP2wsh => "p2sh-p2wsh",
+            ScriptType::P2wpkh => "p2wpkh",
+            ScriptType::P2wsh => "p2wsh",
+        })
+    }
+}
+
+/// A registered set of version bytes.
+struct Version {
+    public: [u8; 4],
+    private: [u8; 4],
+    network: NetworkKind,
+    script_type: ScriptType,
+}
+
+/// The SLIP-132 version bytes registry, the standard BIP-32 versions come first.
+const VERSIONS: [Version; 10] = [
+    Version {
+        public: [0x04, 0x88, 0xB2, 0x1E],
+        private: [0x04, 0x88, 0xAD, 0xE4],
+        network: NetworkKind::Main,
+        script_type: ScriptType::P2pkhOrP2sh,
+    },
+    Version {
+        public: [0x04, 0x35, 0x87, 0xCF],
+        private: [0x04, 0x35, 0x83, 0x94],
+        network: NetworkKind::Test,
+        script_type: ScriptType::P2pkhOrP2sh,
+    },
+    Version {
+        public: [0x04, 0x9D, 0x7C, 0xB2],
+        private: [0x04, 0x9D, 0x78, 0x78],
+        network: NetworkKind::Main,
+        script_type: ScriptType::P2shP2wpkh,
+    },
+    Version {
+        public: [0x04, 0x4A, 0x52, 0x62],
+        private: [0x04, 0x4A, 0x4E, 0x28],
+        network: NetworkKind::Test,
+        script_type: ScriptType::P2shP2wpkh,
+    },
+    Version {
+        public: [0x02, 0x95, 0xB4, 0x3F],
+        private: [0x02, 0x95, 0xB0, 0x05],
+        network: NetworkKind::Main,
+        script_type: ScriptType::P2shP2wsh,
+    },
+    Version {
+        public: [0x02, 0x42, 0x89, 0xEF],
+        private: [0x02, 0x42, 0x85, 0xB5],
+        network: NetworkKind::Test,
+        script_type: ScriptType::P2shP2wsh,
+    },
+    Version {
+        public: [0x04, 0xB2, 0x47, 0x46],
+        private: [0x04, 0xB2, 0x43, 0x0C],
+        network: NetworkKind::Main,
+        script_type: ScriptType::P2wpkh,
+    },
+    Version {
+        public: [0x04, 0x5F, 0x1C, 0xF6],
+        private: [0x04, 0x5F, 0x18, 0xBC],
+        network: NetworkKind::Test,
+        script_type: ScriptType::P2wpkh,
+    },
+    Version {
+        public: [0x02, 0xAA, 0x7E, 0xD3],
+        private: [0x02, 0xAA, 0x7A, 0x99],
+        network: NetworkKind::Main,
+        script_type: ScriptType::P2wsh,
+    },
+    Version {
+        public: [0x02, 0x57, 0x54, 0x83],
+        private: [0x02, 0x57, 0x50, 0x48],
+        network: NetworkKind::Test,
+        script_type: ScriptType::P2wsh,
+    },
+];
+
+/// Returns the registered version for `network` and `script_type`.
+fn version(network: NetworkKind, script_type: ScriptType) -> &'static Version {
+    VERSIONS
+        .iter()
+        .find(|v| v.network == network && v.script_type == script_type)
+        .expect("all combinations are registered")
+}
+
+/// Decodes base58 `s` and replaces its SLIP-132 version bytes by the standard BIP-32 ones.
+fn decode(s: &str, private: bool) -> Result<([u8; 78], ScriptType), Error> {
+    let data = base58::decode_check(s)?;
+    let mut data: [u8; 78] =
+        data.try_into().map_err(|data: Vec<u8>| base58::Error::InvalidLength(data.len()))?;
+
+    let prefix = [data[0], data[1], data[2], data[3]];
+    let found = VERSIONS
+        .iter()
+        .find(|v| if private { v.private == prefix } else { v.public == prefix })
+        .ok_or(Error::UnknownVersion(prefix))?;
+
+    let standard = version(found.network, ScriptType::P2pkhOrP2sh);
+    data[..4].copy_from_slice(if private { &standard.private } else { &standard.public });
+    Ok((data, found.script_type))
+}
+
+/// Replaces the standard BIP-32 version bytes of `data` and base58 encodes it.
+fn encode(mut data: [u8; 78], version: [u8; 4]) -> String {
+    data[..4].copy_from_slice(&version);
+    base58::encode_check(&data)
+}
+
+impl Xpub {
+    /// Parses an extended public key with any SLIP-132 version bytes.
+    ///
+    /// Returns the key together with the script type hinted at by its version bytes.
+    pub fn from_slip132_str(s: &str) -> Result<(Xpub, ScriptType), Error> {
+        let (data, script_type) = decode(s, false)?;
+        Ok((Xpub::decode(&data)?, script_type))
+    }
+
+    /// Formats this key with the SLIP-132 version bytes for `script_type`.
+    pub fn to_slip132_string(&self, script_type: ScriptType) -> String {
+        encode(self.encode(), version(self.network, script_type).public)
+    }
+}
+
+impl Xpriv {
+    /// Parses an extended private key with any SLIP-132 version bytes.
+    ///
+    /// Returns the key together with the script type hinted at by its version bytes.
+    pub fn from_slip132_str(s: &str) -> Result<(Xpriv, ScriptType), Error> {
+        let (data, script_type) = decode(s, true)?;
+        Ok((Xpriv::decode(&data)?, script_type))
+    }
+
+    /// Formats this key with the SLIP-132 version bytes for `script_type`.
+    pub fn to_slip132_string(&self, script_type: ScriptType) -> String {
+        encode(self.encode(), version(self.network, script_type).private)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex::test_hex_unwrap as hex;
+    use secp256k1::Secp256k1;
+
+    use super::*;
+
+    const PREFIXES: [(ScriptType, &str, &str, &str, &str); 5] = [
+        (ScriptType::P2pkhOrP2sh, "xpub", "xprv", "tpub", "tprv"),
+        (ScriptType::P2shP2wpkh, "ypub", "yprv", "upub", "uprv"),
+        (ScriptType::P2shP2wsh, "Ypub", "Yprv", "Upub", "Uprv"),
+        (ScriptType::P2wpkh, "zpub", "zprv", "vpub", "vprv"),
+        (ScriptType::P2wsh, "Zpub", "Zprv", "Vpub", "Vprv"),
+    ];
+
+    #[test]
+    fn slip132_roundtrip() {
+        let secp = Secp256k1::signing_only();
+        for network in [NetworkKind::Main, NetworkKind::Test] {
+            let xpriv = Xpriv::new_master(network, &[42; 32]).unwrap();
+            let xpub = Xpub::from_priv(&secp, &xpriv);
+
+            for (script_type, main_pub, main_priv, test_pub, test_priv) in PREFIXES {
+                let (pub_prefix, priv_prefix) = match network {
+                    NetworkKind::Main => (main_pub, main_priv),
+                    NetworkKind::Test => (test_pub, test_priv),
+                };
+
+                let s = xpub.to_slip132_string(script_type);
+                assert!(s.starts_with(pub_prefix), "{}", s);
+                assert_eq!(Xpub::from_slip132_str(&s).unwrap(), (xpub, script_type));
+
+                let s = xpriv.to_slip132_string(script_type);
+                assert!(s.starts_with(priv_prefix), "{}", s);
+                assert_eq!(Xpriv::from_slip132_str(&s).unwrap(), (xpriv, script_type));
+            }
+
+            let standard = xpub.to_slip132_string(ScriptType::P2pkhOrP2sh);
+            assert_eq!(standard, xpub.to_string());
+        }
+    }
+
+    #[test]
+    fn slip132_vectors() {
+        let secp = Secp256k1::signing_only();
+        // BIP 39 seed of "abandon abandon ... about", the root of the BIP 49 and BIP 84 vectors.
+        let seed = hex!("5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc19a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4");
+
+        // BIP 84 root keys.
+        let zprv = "zprvAWgYBBk7JR8Gjrh4UJQ2uJdG1r3WNRRfURiABBE3RvMXYSrRJL62XuezvGdPvG6GFBZduosCc1YP5wixPox7zhZLfiUm8aunE96BBa4Kei5";
+        let zpub = "zpub6jftahH18ngZxLmXaKw3GSZzZsszmt9WqedkyZdezFtWRFBZqsQH5hyUmb4pCEeZGmVfQuP5bedXTB8is6fTv19U1GQRyQUKQGUTzyHACMF";
+        let xpriv = Xpriv::new_master(NetworkKind::Main, &seed).unwrap();
+        let xpub = Xpub::from_priv(&secp, &xpriv);
+        assert_eq!(xpriv.to_slip132_string(ScriptType::P2wpkh), zprv);
+        assert_eq!(xpub.to_slip132_string(ScriptType::P2wpkh), zpub);
+        assert_eq!(Xpriv::from_slip132_str(zprv).unwrap(), (xpriv, ScriptType::P2wpkh));
+        assert_eq!(Xpub::from_slip132_str(zpub).unwrap(), (xpub, ScriptType::P2wpkh));
+
+        // BIP 49 testnet root key.
+        let uprv = "uprv8tXDerPXZ1QsVNjUJWTurs9kA1KGfKUAts74GCkcXtU8GwnH33GDRbNJpEqTvipfCyycARtQJhmdfWf8oKt41X9LL1zeD2pLsWmxEk3VAwd";
+        let xpriv = Xpriv::new_master(NetworkKind::Test, &seed).unwrap();
+        assert_eq!(xpriv.to_slip132_string(ScriptType::P2shP2wpkh), uprv);
+        assert_eq!(Xpriv::from_slip132_str(uprv).unwrap(), (xpriv, ScriptType::P2shP2wpkh));
+    }
+
+    #[test]
+    fn slip132_errors() {
+        let xpriv = Xpriv::new_master(NetworkKind::Main, &[42; 32]).unwrap();
+        let zprv = xpriv.to_slip132_string(ScriptType::P2wpkh);
+        // Private key versions are not accepted as public ones.
+        assert!(matches!(Xpub::from_slip132_str(&zprv), Err(Error::UnknownVersion(_))));
+        assert!(matches!(
+            Xpub::from_slip132_str("1QJVDzdqb1VpbDK7uDeyVXy9mR27CJiyhY"),
+            Err(Error::Base58(base58::Error::InvalidLength(21)))
+        ));
+    }
+}
@@ -13,6 +13,7 @@ use core::fmt;
 use core::iter::FusedIterator;
 
 use hashes::{sha256t_hash_newtype, Hash, HashEngine};
+use hex::DisplayHex;
 use internals::write_err;
 use io::Write;
 use secp256k1::{Scalar, Secp256k1};
@@ -20,7 +21,7 @@ use secp256k1::{Scalar, Secp256k1};
 use crate::consensus::Encodable;
 use crate::crypto::key::{TapTweak, TweakedPublicKey, UntweakedPublicKey, XOnlyPublicKey};
 use crate::prelude::*;
-use crate::{Script, ScriptBuf};
+use crate::{Script, ScriptBuf, Witness};
 
 // Re-export these so downstream only has to use one `taproot` module.
 #[rustfmt::skip]
@@ -208,7 +209,7 @@ impl TaprootSpendInfo {
         script_weights: I,
     ) -> Result<Self, TaprootBuilderError>
     where
-        I: IntoIterator<Item = (u32, ScriptBuf)>,
+        I: IntoIterator<Item = (u64, ScriptBuf)>,
         C: secp256k1::Verification,
     {
         let builder = TaprootBuilder::with_huffman_tree(script_weights)?;
@@ -319,6 +320,24 @@ impl TaprootSpendInfo {
             merkle_branch: smallest.clone(),
         })
     }
+
+    /// Creates the witness required to spend the output via the script path with `script_ver`.
+    ///
+    /// The witness is made up of `signature`, the script and its control block, as needed for
+    /// scripts like `<pubkey> OP_CHECKSIG` that expect a single signature. Returns `None` if the
+    /// script is not contained in the [`TaprootSpendInfo`].
+    pub fn script_spend_witness(
+        &self,
+        script_ver: &(ScriptBuf, LeafVersion),
+        signature: &Signature,
+    ) -> Option<Witness> {
+        let control_block = self.control_block(script_ver)?;
+        let mut witness = Witness::new();
+        witness.push(signature.serialize());
+        witness.push(script_ver.0.as_bytes());
+        witness.push(control_block.serialize());
+        Some(witness)
+    }
 }
 
 impl From<TaprootSpendInfo> for TapTweakHash {
@@ -388,49 +407,66 @@ impl TaprootBuilder {
     /// The weights represent the probability of each branch being taken. If probabilities/weights
     /// for each condition are known, constructing the tree as a Huffman Tree is the optimal way to
     /// minimize average case satisfaction cost. This function takes as input an iterator of
-    /// `tuple(u32, ScriptBuf)` where `u32` represents the satisfaction weights of the branch. For
+    /// `tuple(u64, ScriptBuf)` where `u64` represents the satisfaction weights of the branch. For
     /// example, [(3, S1), (2, S2), (5, S3)] would construct a [`TapTree`] that has optimal
     /// satisfaction weight when probability for S1 is 30%, S2 is 20% and S3 is 50%.
     ///
+    /// # Tie-breaking
+    ///
+    /// The tree is deterministic, so that other implementations can reproduce it: the two nodes
+    /// with the lowest weights are combined repeatedly and among nodes of equal weight, the one
+    /// created first is combined first. The leaves are created in input order and every combined
+    /// node is created after all nodes before it.
+    ///
     /// # Errors:
     ///
     /// - When the optimal Huffman Tree has a depth more than 128.
     /// - If the provided list of script weights is empty.
-    ///
-    /// # Edge Cases:
-    ///
-    /// If the script weight calculations overflow, a sub-optimal tree may be generated. This should
-    /// not happen unless you are dealing with billions of branches with weights close to 2^32.
+    /// - If the sum of the weights overflows a `u64`.
     ///
     /// [`TapTree`]: crate::taproot::TapTree
     pub fn with_huffman_tree<I>(script_weights: I) -> Result<Self, TaprootBuilderError>
     where
-        I: IntoIterator<Item = (u32, ScriptBuf)>,
+        I: IntoIterator<Item = (u64, ScriptBuf)>,
     {
-        let mut node_weights = BinaryHeap::<(Reverse<u32>, NodeInfo)>::new();
-        for (p, leaf) in script_weights {
-            node_weights
-                .push((Reverse(p), NodeInfo::new_leaf_with_ver(leaf, LeafVersion::TapScript)));
+        Self::with_huffman_tree_depths(script_weights).map(|(builder, _)| builder)
+    }
+
+    /// Creates a Huffman Tree like [`TaprootBuilder::with_huffman_tree`] and also returns the depth
+    /// of each script in input order.
+    pub fn with_huffman_tree_depths<I>(
+        script_weights: I,
+    ) -> Result<(Self, Vec<u8>), TaprootBuilderError>
+    where
+        I: IntoIterator<Item = (u64, ScriptBuf)>,
+    {
+        // Each node tracks the indices of the scripts it contains to compute their depths.
+        let mut node_weights = BinaryHeap::<(Reverse<u64>, NodeInfo, Vec<usize>)>::new();
+        for (i, (p, leaf)) in script_weights.into_iter().enumerate() {
+            let leaf = NodeInfo::new_leaf_with_ver(leaf, LeafVersion::TapScript);
+            node_weights.push((Reverse(p), leaf, vec![i]));
         }
         if node_weights.is_empty() {
             return Err(TaprootBuilderError::EmptyTree);
         }
+        let mut depths = vec![0u8; node_weights.len()];
         while node_weights.len() > 1 {
             // Combine the last two elements and insert a new node
-            let (p1, s1) = node_weights.pop().expect("len must be at least two");
-            let (p2, s2) = node_weights.pop().expect("len must be at least two");
-            // Insert the sum of first two in the tree as a new node
-            // N.B.: p1 + p2 can not practically saturate as you would need to have 2**32 max u32s
-            // from the input to overflow. However, saturating is a reasonable behavior here as
-            // huffman tree construction would treat all such elements as "very likely".
-            let p = Reverse(p1.0.saturating_add(p2.0));
-            node_weights.push((p, NodeInfo::combine(s1, s2)?));
+            let (p1, s1, mut scripts) = node_weights.pop().expect("len must be at least two");
+            let (p2, s2, scripts2) = node_weights.pop().expect("len must be at least two");
+            let p = p1.0.checked_add(p2.0).ok_or(TaprootBuilderError::WeightOverflow)?;
+            scripts.extend(scripts2);
+            for &i in &scripts {
+                // Depths are limited to 128 when combining the nodes below.
+                depths[i] = depths[i].saturating_add(1);
+            }
+            node_weights.push((Reverse(p), NodeInfo::combine(s1, s2)?, scripts));
         }
         // Every iteration of the loop reduces the node_weights.len() by exactly 1
         // Therefore, the loop will eventually terminate with exactly 1 element
         debug_assert_eq!(node_weights.len(), 1);
         let node = node_weights.pop().expect("huffman tree algorithm is broken").1;
-        Ok(TaprootBuilder { branch: vec![Some(node)] })
+        Ok((TaprootBuilder { branch: vec![Some(node)] }, depths))
     }
 
     /// Adds a leaf script at `depth` to the builder with script version `ver`. Errors if the leaves
@@ -524,6 +560,77 @@ impl TaprootBuilder {
         }
     }
 
+    /// Merges the tree built by `other` into this builder as the subtree at `depth`.
+    ///
+    /// `other` may be partially built, in which case its remaining nodes can be added to the
+    /// returned builder afterwards, at depths offset by `depth`. Like nodes, subtrees must be
+    /// merged in DFS walk order. Two complete trees can be combined into one with
+    /// `TaprootBuilder::new().merge(1, left)?.merge(1, right)`.
+    pub fn merge(mut self, depth: u8, other: TaprootBuilder) -> Result<Self, TaprootBuilderError> {
+        if (depth as usize + 1) < self.branch.len() {
+            return Err(TaprootBuilderError::NodeNotInDfsOrder);
+        }
+        for (i, node) in other.branch.into_iter().enumerate() {
+            if let Some(node) = node {
+                let node_depth = depth as usize + i;
+                if node_depth > TAPROOT_CONTROL_MAX_NODE_COUNT {
+                    return Err(TaprootBuilderError::InvalidMerkleTreeDepth(node_depth));
+                }
+                self = self.insert(node, node_depth as u8)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Replaces the leaf at `index` in the order of [`TaprootBuilder::leaves`] with `script`.
+    ///
+    /// Since the children of each node are ordered by their hashes, the order of the leaves may
+    /// change after the replacement.
+    ///
+    /// # Errors
+    ///
+    /// - [`TaprootBuilderError::LeafIndexOutOfRange`] if there is no leaf at `index`.
+    /// - [`TaprootBuilderError::HiddenNodes`] if the complete subtree containing the leaf has
+    ///   hidden nodes, because it can not be rebuilt then.
+    pub fn replace_leaf(
+        mut self,
+        index: usize,
+        script: ScriptBuf,
+        ver: LeafVersion,
+    ) -> Result<Self, TaprootBuilderError> {
+        let mut remaining = index;
+        for node in self.branch.iter_mut().flatten() {
+            if remaining >= node.leaves.len() {
+                remaining -= node.leaves.len();
+                continue;
+            }
+            if node.has_hidden_nodes {
+                return Err(TaprootBuilderError::HiddenNodes);
+            }
+            // Leaves are stored in DFS order so the subtree can be rebuilt from their depths.
+            let mut script = Some(script);
+            let mut builder = TaprootBuilder::new();
+            for (i, leaf) in node.leaves.iter().enumerate() {
+                let new_leaf = if i == remaining {
+                    NodeInfo::new_leaf_with_ver(script.take().expect("replaced once"), ver)
+                } else {
+                    let (script, ver) = leaf.leaf.as_script().expect("subtree has no hidden nodes");
+                    NodeInfo::new_leaf_with_ver(script.to_owned(), ver)
+                };
+                builder = builder.insert(new_leaf, leaf.depth())?;
+            }
+            *node = builder.try_into_node_info().expect("rebuilt from a complete subtree");
+            return Ok(self);
+        }
+        Err(TaprootBuilderError::LeafIndexOutOfRange(index))
+    }
+
+    /// Creates an iterator over the known leaves added to the builder so far, along with their
+    /// depths.
+    pub fn leaves(&self) -> BuilderLeaves<'_> {
+        BuilderLeaves { branch: self.branch.iter().enumerate(), current: None }
+    }
+
     pub(crate) fn branch(&self) -> &[Option<NodeInfo>] { &self.branch }
 
     /// Inserts a leaf at `depth`.
@@ -576,6 +683,35 @@ impl Default for TaprootBuilder {
     fn default() -> Self { Self::new() }
 }
 
+/// Iterator over the leaves of a partially built taproot tree, operating in DFS order yielding
+/// the depth of each leaf and the [`LeafNode`].
+///
+/// Returned by [`TaprootBuilder::leaves`]. This does not yield hidden nodes.
+pub struct BuilderLeaves<'a> {
+    branch: core::iter::Enumerate<core::slice::Iter<'a, Option<NodeInfo>>>,
+    current: Option<(u8, core::slice::Iter<'a, LeafNode>)>,
+}
+
+impl<'a> Iterator for BuilderLeaves<'a> {
+    type Item = (u8, &'a LeafNode);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((depth, ref mut leaves)) = self.current {
+                if let Some(leaf) = leaves.next() {
+                    return Some((depth + leaf.depth(), leaf));
+                }
+            }
+            let (depth, node) = self.branch.next()?;
+            // The builder never has more than 129 entries in the branch.
+            let depth = u8::try_from(depth).expect("depth fits in a u8");
+            self.current = node.as_ref().map(|node| (depth, node.leaves.iter()));
+        }
+    }
+}
+
+impl<'a> FusedIterator for BuilderLeaves<'a> {}
+
 /// Error happening when [`TapTree`] is constructed from a [`TaprootBuilder`]
 /// having hidden branches or not being finalized.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -700,6 +836,103 @@ impl TapTree {
 
     /// Returns the root [`TapNodeHash`] of this tree.
     pub fn root_hash(&self) -> TapNodeHash { self.0.hash }
+
+    /// Serializes the tree as in the `PSBT_OUT_TAP_TREE` field defined in BIP 371.
+    pub fn serialize_psbt(&self) -> Vec<u8> { crate::psbt::serialize::Serialize::serialize(self) }
+
+    /// Deserializes the tree from the `PSBT_OUT_TAP_TREE` field format defined in BIP 371.
+    pub fn deserialize_psbt(bytes: &[u8]) -> Result<TapTree, crate::psbt::Error> {
+        crate::psbt::serialize::Deserialize::deserialize(bytes)
+    }
+}
+
+/// Formats the tree in the nested `{A,{B,C}}` form used by descriptors.
+///
+/// Each leaf is the hex encoded script, prefixed with the hex encoded leaf version and a colon
+/// (e.g. `c2:51`) if the version is not [`LeafVersion::TapScript`].
+impl fmt::Display for TapTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn fmt_subtree(
+            f: &mut fmt::Formatter<'_>,
+            leaves: &mut core::iter::Peekable<ScriptLeaves>,
+            depth: usize,
+        ) -> fmt::Result {
+            let leaf = leaves.peek().expect("leaves of a complete tree");
+            if leaf.merkle_branch().len() == depth {
+                if leaf.version() != LeafVersion::TapScript {
+                    write!(f, "{:02x}:", leaf.version().to_consensus())?;
+                }
+                write!(f, "{:x}", leaf.script().as_bytes().as_hex())?;
+                leaves.next();
+                return Ok(());
+            }
+            f.write_str("{")?;
+            fmt_subtree(f, leaves, depth + 1)?;
+            f.write_str(",")?;
+            fmt_subtree(f, leaves, depth + 1)?;
+            f.write_str("}")
+        }
+
+        fmt_subtree(f, &mut self.script_leaves().peekable(), 0)
+    }
+}
+
+impl core::str::FromStr for TapTree {
+    type Err = ParseTapTreeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Parses the subtree at `depth` and returns the remaining input.
+        fn parse_subtree<'a>(
+            s: &'a str,
+            depth: u8,
+            leaves: &mut Vec<(u8, ScriptBuf, LeafVersion)>,
+        ) -> Result<&'a str, ParseTapTreeError> {
+            let unexpected = |rest: &str| {
+                rest.chars()
+                    .next()
+                    .map_or(ParseTapTreeError::UnexpectedEnd, ParseTapTreeError::UnexpectedChar)
+            };
+
+            if let Some(rest) = s.strip_prefix('{') {
+                if depth as usize >= TAPROOT_CONTROL_MAX_NODE_COUNT {
+                    return Err(
+                        TaprootBuilderError::InvalidMerkleTreeDepth(depth as usize + 1).into()
+                    );
+                }
+                let rest = parse_subtree(rest, depth + 1, leaves)?;
+                let rest = rest.strip_prefix(',').ok_or_else(|| unexpected(rest))?;
+                let rest = parse_subtree(rest, depth + 1, leaves)?;
+                return rest.strip_prefix('}').ok_or_else(|| unexpected(rest));
+            }
+
+            let end = s.find(|c| c == ',' || c == '{' || c == '}').unwrap_or(s.len());
+            let (leaf, rest) = s.split_at(end);
+            let (version, script) = match leaf.split_once(':') {
+                Some((version, script)) => {
+                    let version = u8::from_str_radix(version, 16)
+                        .ok()
+                        .filter(|_| version.len() == 2)
+                        .and_then(|version| LeafVersion::from_consensus(version).ok())
+                        .ok_or(ParseTapTreeError::InvalidLeafVersion)?;
+                    (version, script)
+                }
+                None => (LeafVersion::TapScript, leaf),
+            };
+            leaves.push((depth, ScriptBuf::from_hex(script)?, version));
+            Ok(rest)
+        }
+
+        let mut leaves = Vec::new();
+        let rest = parse_subtree(s, 0, &mut leaves)?;
+        if let Some(c) = rest.chars().next() {
+            return Err(ParseTapTreeError::UnexpectedChar(c));
+        }
+        let mut builder = TaprootBuilder::new();
+        for (depth, script, version) in leaves {
+            builder = builder.add_leaf_with_ver(depth, script, version)?;
+        }
+        Ok(builder.try_into_taptree().expect("parsed tree is complete and has no hidden nodes"))
+    }
 }
 
 impl TryFrom<TaprootBuilder> for TapTree {
@@ -1163,6 +1396,30 @@ impl ControlBlock {
             TapTweakHash::from_key_and_tweak(self.internal_key, Some(curr_hash)).to_scalar();
         self.internal_key.tweak_add_check(secp, &output_key, self.output_key_parity, tweak)
     }
+
+    /// Verifies that a control block is correct proof for a given output key and script.
+    ///
+    /// Like [`ControlBlock::verify_taproot_commitment`] but returns the reason the verification
+    /// failed.
+    pub fn verify_against_output_key<C: secp256k1::Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        output_key: TweakedPublicKey,
+        script: &Script,
+    ) -> Result<(), ControlBlockVerificationError> {
+        let mut curr_hash = TapNodeHash::from_script(script, self.leaf_version);
+        for elem in &self.merkle_branch {
+            curr_hash = TapNodeHash::from_node_hashes(curr_hash, *elem);
+        }
+        let (computed, parity) = self.internal_key.tap_tweak(secp, Some(curr_hash));
+        if computed != output_key {
+            return Err(ControlBlockVerificationError::OutputKeyMismatch(computed));
+        }
+        if parity != self.output_key_parity {
+            return Err(ControlBlockVerificationError::ParityMismatch(parity));
+        }
+        Ok(())
+    }
 }
 
 /// Inner type representing future (non-tapscript) leaf versions. See [`LeafVersion::Future`].
@@ -1326,6 +1583,12 @@ pub enum TaprootBuilderError {
     InvalidInternalKey(secp256k1::Error),
     /// Called finalize on a empty tree.
     EmptyTree,
+    /// The sum of the Huffman Tree weights overflows.
+    WeightOverflow,
+    /// There is no leaf at the given index.
+    LeafIndexOutOfRange(usize),
+    /// The subtree has hidden nodes and can not be rebuilt.
+    HiddenNodes,
 }
 
 internals::impl_from_infallible!(TaprootBuilderError);
@@ -1356,6 +1619,9 @@ impl fmt::Display for TaprootBuilderError {
             EmptyTree => {
                 write!(f, "Called finalize on an empty tree")
             }
+            LeafIndexOutOfRange(i) => write!(f, "no leaf at index {} in the tree", i),
+            HiddenNodes => write!(f, "the subtree has hidden nodes and can not be rebuilt"),
+            WeightOverflow => write!(f, "the sum of the Huffman Tree weights overflows"),
         }
     }
 }
@@ -1367,7 +1633,102 @@ impl std::error::Error for TaprootBuilderError {
 
         match self {
             InvalidInternalKey(e) => Some(e),
-            InvalidMerkleTreeDepth(_) | NodeNotInDfsOrder | OverCompleteTree | EmptyTree => None,
+            InvalidMerkleTreeDepth(_)
+            | NodeNotInDfsOrder
+            | OverCompleteTree
+            | EmptyTree
+            | LeafIndexOutOfRange(_)
+            | HiddenNodes
+            | WeightOverflow => None,
+        }
+    }
+}
+
+/// Error parsing a [`TapTree`] from its string form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseTapTreeError {
+    /// Invalid hex encoding of a leaf script.
+    Hex(hex::HexToBytesError),
+    /// Invalid leaf version prefix.
+    InvalidLeafVersion,
+    /// Unexpected character.
+    UnexpectedChar(char),
+    /// Unexpected end of the string.
+    UnexpectedEnd,
+    /// The parsed tree is not a valid taproot tree.
+    Builder(TaprootBuilderError),
+}
+
+internals::impl_from_infallible!(ParseTapTreeError);
+
+impl fmt::Display for ParseTapTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseTapTreeError::*;
+
+        match *self {
+            Hex(ref e) => write_err!(f, "invalid leaf script hex"; e),
+            InvalidLeafVersion => f.write_str("invalid leaf version"),
+            UnexpectedChar(c) => write!(f, "unexpected character '{}' in taproot tree", c),
+            UnexpectedEnd => f.write_str("unexpected end of taproot tree"),
+            Builder(ref e) => write_err!(f, "invalid taproot tree"; e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseTapTreeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ParseTapTreeError::*;
+
+        match self {
+            Hex(e) => Some(e),
+            Builder(e) => Some(e),
+            InvalidLeafVersion | UnexpectedChar(_) | UnexpectedEnd => None,
+        }
+    }
+}
+
+impl From<hex::HexToBytesError> for ParseTapTreeError {
+    fn from(e: hex::HexToBytesError) -> Self { Self::Hex(e) }
+}
+
+impl From<TaprootBuilderError> for ParseTapTreeError {
+    fn from(e: TaprootBuilderError) -> Self { Self::Builder(e) }
+}
+
+/// Error verifying a [`ControlBlock`] against an output key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ControlBlockVerificationError {
+    /// The internal key, script and merkle branch commit to the contained output key instead.
+    OutputKeyMismatch(TweakedPublicKey),
+    /// The output key parity in the control block is wrong, the correct parity is contained.
+    ParityMismatch(secp256k1::Parity),
+}
+
+internals::impl_from_infallible!(ControlBlockVerificationError);
+
+impl fmt::Display for ControlBlockVerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ControlBlockVerificationError::*;
+
+        match *self {
+            OutputKeyMismatch(ref key) =>
+                write!(f, "control block and script commit to a different output key {}", key),
+            ParityMismatch(parity) =>
+                write!(f, "control block has the wrong output key parity, expected {:?}", parity),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ControlBlockVerificationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        use ControlBlockVerificationError::*;
+
+        match *self {
+            OutputKeyMismatch(_) | ParityMismatch(_) => None,
         }
     }
 }
@@ -1443,7 +1804,6 @@ impl std::error::Error for TaprootError {
 mod test {
     use core::str::FromStr;
 
-    use hashes::sha256t::Tag;
     use hashes::sha256;
     use hex::FromHex;
     use secp256k1::VerifyOnly;
@@ -1602,6 +1962,56 @@ mod test {
         _verify_tap_commitments(&secp, "512093c7378d96518a75448821c4f7c8f4bae7ce60f804d03d1f0628dd5dd0f5de51", "04ffffffff203455139bf238a3067bd72ed77e0ab8db590330f55ed58dba7366b53bf4734279ba04feffffff87ab", "c1a0eb12e60a52614986c623cbb6621dcdba3a47e3be6b37e032b7a11c7b98f400c9a5cd1f6c8a81f5648e39f9810591df1c9a8f1fe97c92e03ecd7c0c016c951983e05473c6e8238cb4c780ea2ce62552b2a3eee068ceffc00517cd7b97e10dad");
     }
 
+    #[test]
+    fn script_spend_witness() {
+        let secp = Secp256k1::verification_only();
+        let internal_key = UntweakedPublicKey::from_str(
+            "93c7378d96518a75448821c4f7c8f4bae7ce60f804d03d1f0628dd5dd0f5de51",
+        )
+        .unwrap();
+        let a = (ScriptBuf::from_hex("51").unwrap(), LeafVersion::TapScript);
+        let b = (ScriptBuf::from_hex("52").unwrap(), LeafVersion::TapScript);
+        let spend_info = TaprootBuilder::new()
+            .add_leaf(1, a.0.clone())
+            .unwrap()
+            .add_leaf(1, b.0.clone())
+            .unwrap()
+            .finalize(&secp, internal_key)
+            .unwrap();
+        let output_key = spend_info.output_key();
+
+        let signature = Signature::from_slice(&[0x01; 64]).unwrap();
+        let witness = spend_info.script_spend_witness(&a, &signature).unwrap();
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness.nth(0), Some(&signature.serialize()[..]));
+        assert_eq!(witness.tapscript(), Some(a.0.as_script()));
+        let control_block = ControlBlock::decode(witness.taproot_control_block().unwrap()).unwrap();
+        assert_eq!(control_block.verify_against_output_key(&secp, output_key, &a.0), Ok(()));
+
+        let unknown = (ScriptBuf::from_hex("53").unwrap(), LeafVersion::TapScript);
+        assert_eq!(spend_info.script_spend_witness(&unknown, &signature), None);
+
+        let (actual, _) = internal_key.tap_tweak(
+            &secp,
+            Some(TapNodeHash::from_node_hashes(
+                TapNodeHash::from_script(&b.0, LeafVersion::TapScript),
+                control_block.merkle_branch.as_slice()[0],
+            )),
+        );
+        assert_eq!(
+            control_block.verify_against_output_key(&secp, output_key, &b.0),
+            Err(ControlBlockVerificationError::OutputKeyMismatch(actual))
+        );
+
+        let parity = control_block.output_key_parity;
+        let control_block =
+            ControlBlock { output_key_parity: parity ^ secp256k1::Parity::Odd, ..control_block };
+        assert_eq!(
+            control_block.verify_against_output_key(&secp, output_key, &a.0),
+            Err(ControlBlockVerificationError::ParityMismatch(parity))
+        );
+    }
+
     #[test]
     fn build_huffman_tree() {
         let secp = Secp256k1::verification_only();
@@ -1660,6 +2070,74 @@ mod test {
         }
     }
 
+    #[test]
+    fn huffman_tree_depths() {
+        let scripts = |weights: &[u64]| {
+            weights
+                .iter()
+                .enumerate()
+                .map(|(i, &w)| (w, ScriptBuf::from_bytes(vec![0x51 + i as u8])))
+                .collect::<Vec<_>>()
+        };
+
+        let (builder, depths) =
+            TaprootBuilder::with_huffman_tree_depths(scripts(&[10, 20, 20, 30, 19])).unwrap();
+        assert_eq!(depths, [3, 2, 2, 2, 3]);
+        assert_eq!(
+            builder,
+            TaprootBuilder::with_huffman_tree(scripts(&[10, 20, 20, 30, 19])).unwrap()
+        );
+
+        // Ties between equal weights are broken by the ordering of the nodes.
+        let (_, depths) = TaprootBuilder::with_huffman_tree_depths(scripts(&[1, 1, 1])).unwrap();
+        assert_eq!(depths, [2, 2, 1]);
+        let (_, depths) = TaprootBuilder::with_huffman_tree_depths(scripts(&[1, 1, 2, 2])).unwrap();
+        assert_eq!(depths, [2, 2, 2, 2]);
+
+        let (_, depths) = TaprootBuilder::with_huffman_tree_depths(scripts(&[5])).unwrap();
+        assert_eq!(depths, [0]);
+        assert_eq!(
+            TaprootBuilder::with_huffman_tree(scripts(&[u64::MAX, 1, u64::MAX])),
+            Err(TaprootBuilderError::WeightOverflow)
+        );
+    }
+
+    #[test]
+    fn taptree_string_roundtrip() {
+        let script = |hex| ScriptBuf::from_hex(hex).unwrap();
+        let tree = TaprootBuilder::new()
+            .add_leaf(1, script("51"))
+            .unwrap()
+            .add_leaf(2, script("52"))
+            .unwrap()
+            .add_leaf_with_ver(2, script(""), LeafVersion::from_consensus(0xc2).unwrap())
+            .unwrap()
+            .try_into_taptree()
+            .unwrap();
+
+        let s = tree.to_string();
+        assert_eq!(s.parse::<TapTree>(), Ok(tree.clone()));
+        assert_eq!("{51,{c2:,52}}".parse::<TapTree>(), Ok(tree.clone()));
+        assert_eq!("{{52,c2:},51}".parse::<TapTree>(), Ok(tree.clone()));
+        assert_eq!(TapTree::deserialize_psbt(&tree.serialize_psbt()).unwrap(), tree);
+
+        let leaf = "51".parse::<TapTree>().unwrap();
+        assert_eq!(leaf.to_string(), "51");
+        assert_eq!(leaf.script_leaves().next().unwrap().merkle_branch().len(), 0);
+
+        assert_eq!("{51,52".parse::<TapTree>(), Err(ParseTapTreeError::UnexpectedEnd));
+        assert_eq!("{51,52}}".parse::<TapTree>(), Err(ParseTapTreeError::UnexpectedChar('}')));
+        assert_eq!("{51}".parse::<TapTree>(), Err(ParseTapTreeError::UnexpectedChar('}')));
+        assert_eq!("c1:51".parse::<TapTree>(), Err(ParseTapTreeError::InvalidLeafVersion));
+        assert!(matches!("{51,zz}".parse::<TapTree>(), Err(ParseTapTreeError::Hex(_))));
+
+        let too_deep = format!("{}51{}", "{51,".repeat(129), "}".repeat(129));
+        assert_eq!(
+            too_deep.parse::<TapTree>(),
+            Err(ParseTapTreeError::Builder(TaprootBuilderError::InvalidMerkleTreeDepth(129)))
+        );
+    }
+
     #[test]
     fn taptree_builder() {
         let secp = Secp256k1::verification_only();
@@ -1735,6 +2213,76 @@ mod test {
         }
     }
 
+    #[test]
+    fn taptree_builder_editing() {
+        let script = |hex| ScriptBuf::from_hex(hex).unwrap();
+        let depths =
+            |builder: &TaprootBuilder| builder.leaves().map(|(depth, _)| depth).collect::<Vec<_>>();
+
+        let expected = TaprootBuilder::new()
+            .add_leaf(2, script("51"))
+            .unwrap()
+            .add_leaf(2, script("52"))
+            .unwrap()
+            .add_leaf(2, script("53"))
+            .unwrap()
+            .add_leaf(3, script("54"))
+            .unwrap()
+            .add_leaf(3, script("55"))
+            .unwrap();
+
+        // Merge a complete and a partially built subtree, then finish the latter.
+        let left = TaprootBuilder::new()
+            .add_leaf(1, script("51"))
+            .unwrap()
+            .add_leaf(1, script("52"))
+            .unwrap();
+        let right = TaprootBuilder::new().add_leaf(1, script("53")).unwrap();
+        let partial = TaprootBuilder::new().merge(1, left).unwrap().merge(1, right).unwrap();
+        assert_eq!(depths(&partial), [2, 2, 2]);
+        let merged = partial.add_leaf(3, script("54")).unwrap().add_leaf(3, script("55")).unwrap();
+        assert_eq!(merged, expected);
+        assert_eq!(depths(&merged), [2, 2, 3, 3, 2]);
+
+        // Subtrees must be merged in DFS walk order.
+        let deep = TaprootBuilder::new().add_leaf(2, script("51")).unwrap();
+        assert_eq!(
+            deep.merge(0, TaprootBuilder::new().add_leaf(0, script("52")).unwrap()),
+            Err(TaprootBuilderError::NodeNotInDfsOrder)
+        );
+
+        // Replacing a leaf gives the same tree as building it with the new leaf.
+        let index =
+            merged.leaves().position(|(_, leaf)| leaf.script() == Some(&script("54"))).unwrap();
+        let replaced = merged.replace_leaf(index, script("56"), LeafVersion::TapScript).unwrap();
+        let rebuilt = TaprootBuilder::new()
+            .add_leaf(2, script("51"))
+            .unwrap()
+            .add_leaf(2, script("52"))
+            .unwrap()
+            .add_leaf(2, script("53"))
+            .unwrap()
+            .add_leaf(3, script("56"))
+            .unwrap()
+            .add_leaf(3, script("55"))
+            .unwrap();
+        assert_eq!(replaced, rebuilt);
+        assert_eq!(
+            replaced.clone().replace_leaf(5, script("57"), LeafVersion::TapScript),
+            Err(TaprootBuilderError::LeafIndexOutOfRange(5))
+        );
+
+        let hidden = TaprootBuilder::new()
+            .add_leaf(1, script("51"))
+            .unwrap()
+            .add_hidden_node(1, replaced.try_into_node_info().unwrap().node_hash())
+            .unwrap();
+        assert_eq!(
+            hidden.replace_leaf(0, script("52"), LeafVersion::TapScript),
+            Err(TaprootBuilderError::HiddenNodes)
+        );
+    }
+
     #[test]
     #[cfg(feature = "serde")]
     fn test_leaf_version_serde() {
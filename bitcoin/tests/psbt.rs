@@ -352,11 +352,11 @@ fn signer_two_sign(psbt: Psbt, key_map: BTreeMap<bitcoin::PublicKey, PrivateKey>
 
 /// Does the combine according to the BIP, returns the combined PSBT. Verifies against BIP 174 test vector.
 #[track_caller]
-fn combine(mut this: Psbt, that: Psbt) -> Psbt {
+fn combine(this: Psbt, that: Psbt) -> Psbt {
     let expected_psbt_hex = include_str!("data/combine_psbt_hex");
     let expected_psbt: Psbt = hex_psbt(expected_psbt_hex);
 
-    this.combine(that).expect("failed to combine PSBTs");
+    let this = this.combine(that).expect("failed to combine PSBTs");
 
     assert_eq!(this, expected_psbt);
     this
@@ -398,14 +398,14 @@ fn combine_lexicographically() {
     let expected_psbt: Psbt = hex_psbt(expected_psbt_hex);
 
     let v = Vec::from_hex(psbt_1_hex).unwrap();
-    let mut psbt_1 = Psbt::deserialize(&v).expect("failed to deserialize psbt 1");
+    let psbt_1 = Psbt::deserialize(&v).expect("failed to deserialize psbt 1");
 
     let v = Vec::from_hex(psbt_2_hex).unwrap();
     let psbt_2 = Psbt::deserialize(&v).expect("failed to deserialize psbt 2");
 
-    psbt_1.combine(psbt_2).expect("failed to combine PSBTs");
+    let combined = psbt_1.combine(psbt_2).expect("failed to combine PSBTs");
 
-    assert_eq!(psbt_1, expected_psbt);
+    assert_eq!(combined, expected_psbt);
 }
 
 /// Signs `psbt` with `keys` if required.
@@ -266,6 +266,8 @@ fn serde_regression_psbt() {
 
     let psbt = Psbt {
         version: 0,
+        fallback_lock_time: None,
+        tx_modifiable: None,
         xpub: {
             let s = include_str!("data/serde/extended_pub_key");
             let xpub = Xpub::from_str(s.trim()).unwrap();
@@ -317,7 +319,9 @@ fn serde_regression_psbt() {
 
     let got = serialize(&psbt).unwrap();
     let want = include_bytes!("data/serde/psbt_bincode") as &[_];
-    assert_eq!(got, want)
+    assert_eq!(got, want);
+    let back: Psbt = bincode::deserialize(want).unwrap();
+    assert_eq!(back, psbt)
 }
 
 #[test]
@@ -427,6 +427,10 @@ impl Midstate {
 impl HashEngine {
     /// Create a new [`HashEngine`] from a [`Midstate`].
     ///
+    /// `length` is the number of bytes hashed to get to the midstate. Together with
+    /// [`HashEngine::midstate_at_block_boundary`] this allows resuming hashing where another
+    /// engine left off.
+    ///
     /// # Panics
     ///
     /// If `length` is not a multiple of the block size.
@@ -441,6 +445,19 @@ impl HashEngine {
         HashEngine { buffer: [0; BLOCK_SIZE], h: ret, length }
     }
 
+    /// Returns the midstate and the number of bytes hashed so far, if the engine is at a block
+    /// boundary.
+    ///
+    /// Returns `None` if the number of bytes hashed is not a multiple of the block size, in which
+    /// case the midstate does not include the buffered data and could not be resumed from.
+    pub fn midstate_at_block_boundary(&self) -> Option<(Midstate, usize)> {
+        if self.length % BLOCK_SIZE == 0 {
+            Some((crate::HashEngine::midstate(self), self.length))
+        } else {
+            None
+        }
+    }
+
     fn process_block(&mut self) {
         #[cfg(all(feature = "std", any(target_arch = "x86", target_arch = "x86_64")))]
         {
@@ -916,6 +933,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn midstate_at_block_boundary() {
+        let data = [0x42; 200];
+        let mut engine = sha256::Hash::engine();
+        assert!(engine.midstate_at_block_boundary().is_some());
+        engine.input(&data[..100]);
+        assert_eq!(engine.midstate_at_block_boundary(), None);
+        engine.input(&data[100..128]);
+        let (midstate, length) = engine.midstate_at_block_boundary().unwrap();
+        assert_eq!(length, 128);
+
+        let mut resumed = sha256::HashEngine::from_midstate(midstate, length);
+        resumed.input(&data[128..]);
+        assert_eq!(sha256::Hash::from_engine(resumed), sha256::Hash::hash(&data));
+    }
+
     #[test]
     fn engine_with_state() {
         let mut engine = sha256::Hash::engine();
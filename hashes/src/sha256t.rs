@@ -16,6 +16,13 @@ type HashEngine = sha256::HashEngine;
 pub trait Tag {
     /// Returns a hash engine that is pre-tagged and is ready to be used for the data.
     fn engine() -> sha256::HashEngine;
+
+    /// Returns the midstate after hashing the tag, shared by all hashes with this tag.
+    fn midstate() -> sha256::Midstate {
+        use crate::HashEngine as _;
+
+        Self::engine().midstate()
+    }
 }
 
 /// Output of the SHA256t hash function.
@@ -107,6 +114,10 @@ fn from_engine<T: Tag>(e: sha256::HashEngine) -> Hash<T> {
 /// `#[hash_newtype(...)]`). The attributes suffer same compiler performance limitations as in
 /// [`hash_newtype`] macro.
 ///
+/// The tag struct gets an inherent `engine` function returning an engine pre-seeded with the tag
+/// midstate, which is computed at compile time. Data can be streamed into it and the hash created
+/// with `from_engine` without hashing the tag again.
+///
 /// The macro accepts multiple inputs so you can define multiple hash newtypes in one macro call.
 /// Just make sure to enter the structs in order `Tag0`, `Hash0`, `Tag1`, `Hash1`...
 ///
@@ -117,6 +128,15 @@ macro_rules! sha256t_hash_newtype {
         $(
         $crate::sha256t_hash_newtype_tag!($tag_vis, $tag, stringify!($hash_name), $(#[$($tag_attr)*])*);
 
+        impl $tag {
+            /// Returns a hash engine that is pre-tagged and is ready to be used for the data.
+            #[inline]
+            #[allow(dead_code)]
+            $tag_vis fn engine() -> $crate::sha256::HashEngine {
+                <$tag as $crate::sha256t::Tag>::engine()
+            }
+        }
+
         impl $crate::sha256t::Tag for $tag {
             #[inline]
             fn engine() -> $crate::sha256::HashEngine {
@@ -213,6 +233,20 @@ mod tests {
         struct NewTypeHash(_);
     }
 
+    #[test]
+    fn tag_engine() {
+        use crate::{Hash, HashEngine};
+
+        let midstate = sha256::Midstate::from_byte_array(TEST_MIDSTATE);
+        assert_eq!(NewTypeTag::engine().midstate(), midstate);
+        assert_eq!(<NewTypeTag as sha256t::Tag>::midstate(), midstate);
+        assert_eq!(<TestHashTag as sha256t::Tag>::midstate(), midstate);
+
+        let mut engine = NewTypeTag::engine();
+        engine.input(&[0]);
+        assert_eq!(NewTypeHash::from_engine(engine), NewTypeHash::hash(&[0]));
+    }
+
     #[test]
     #[cfg(feature = "alloc")]
     fn test_sha256t() {